@@ -32,6 +32,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     entry_id,
     collection_id,
     asset_paths,
+    ..
   } in summary.entries
   {
     println!("{entry_id}/{collection_id} ({} assets)", asset_paths.len());
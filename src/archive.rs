@@ -0,0 +1,253 @@
+//! Collections delivered as `.zip`, `.tar` or `.tar.zst` archives.
+//!
+//! Build farms often receive content as signed archives rather than checkouts. When the
+//! collections directory given to
+//! [`OfflineBuildContextBuilder::collections_dir`](crate::project::OfflineBuildContextBuilder::collections_dir)
+//! names an archive, the context verifies it against its checksum and extracts it into
+//! `<target_dir>/offline-collections`, keyed by the archive's SHA-256 digest so unchanged archives
+//! are only extracted once, and scans the extracted directory. [`prepare_collections_dir`] does
+//! the same for tools working from a [`ProjectConfig`].
+
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use crate::builder::BuildResult;
+use crate::config::ProjectConfig;
+use crate::manifest::inventory::file_digest;
+
+/// Suffix of the checksum file next to an archive, e.g. `programs.zip.sha256`.
+///
+/// It holds the archive's hex SHA-256 digest, optionally followed by the file name as written by
+/// `sha256sum`.
+pub const CHECKSUM_SUFFIX: &str = ".sha256";
+
+/// Directory below the target directory holding extracted archives.
+pub const ARCHIVE_CACHE_DIR: &str = "offline-collections";
+
+/// How [`extract_collections`] verifies an archive before extracting it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ArchiveChecksum {
+  /// Compare against the digest in the archive's [`CHECKSUM_SUFFIX`] file, failing when that file
+  /// is missing.
+  #[default]
+  Sidecar,
+  /// Compare against this hex SHA-256 digest.
+  Expected(String),
+  /// Extract without verifying the archive.
+  Skip,
+}
+
+/// Archive formats accepted for `collections_dir`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+  /// `.zip`, stored or deflated.
+  Zip,
+  /// Uncompressed `.tar`.
+  Tar,
+  /// Zstandard-compressed `.tar.zst` or `.tzst`.
+  TarZstd,
+}
+
+impl ArchiveFormat {
+  /// Format of the archive at `path` by file name, `None` for anything else such as a directory.
+  pub fn from_path(path: &Path) -> Option<Self> {
+    let name = path.file_name()?.to_string_lossy().to_ascii_lowercase();
+    if name.ends_with(".zip") {
+      Some(Self::Zip)
+    } else if name.ends_with(".tar") {
+      Some(Self::Tar)
+    } else if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+      Some(Self::TarZstd)
+    } else {
+      None
+    }
+  }
+}
+
+/// Whether `path` is an archive file rather than a collections directory.
+pub fn is_collections_archive(path: &Path) -> bool {
+  ArchiveFormat::from_path(path).is_some() && path.is_file()
+}
+
+/// Collections directory of `config`, extracting it first when `collections_dir` names an
+/// archive.
+///
+/// The archive must match the digest in its `<archive>.sha256` file.
+pub fn prepare_collections_dir(
+  config: &ProjectConfig,
+  manifest_dir: &Path,
+) -> BuildResult<PathBuf> {
+  let path = config.collections_dir_path(manifest_dir);
+  if !is_collections_archive(&path) {
+    return Ok(path);
+  }
+  let cache_dir = manifest_dir
+    .join(&config.target_dir)
+    .join(ARCHIVE_CACHE_DIR);
+  extract_collections(&path, &cache_dir, &ArchiveChecksum::Sidecar)
+}
+
+/// Extract the collections archive at `archive` below `cache_dir` and return the extracted root.
+///
+/// The archive is verified as `checksum` says; a mismatch or a missing checksum file fails before
+/// anything is extracted. Extractions of other archives in `cache_dir` are removed.
+pub fn extract_collections(
+  archive: &Path,
+  cache_dir: &Path,
+  checksum: &ArchiveChecksum,
+) -> BuildResult<PathBuf> {
+  let format = ArchiveFormat::from_path(archive).ok_or_else(|| {
+    format!(
+      "{} is not a .zip, .tar or .tar.zst archive",
+      archive.display()
+    )
+  })?;
+  let (_, digest) = file_digest(archive)
+    .map_err(|err| format!("failed to read archive {}: {err}", archive.display()))?;
+
+  let expected = match checksum {
+    ArchiveChecksum::Sidecar => {
+      let checksum_path = PathBuf::from(format!("{}{CHECKSUM_SUFFIX}", archive.display()));
+      let content = fs::read_to_string(&checksum_path).map_err(|err| {
+        format!(
+          "failed to read checksum {} of {}: {err}",
+          checksum_path.display(),
+          archive.display()
+        )
+      })?;
+      Some(
+        content
+          .split_whitespace()
+          .next()
+          .unwrap_or_default()
+          .to_string(),
+      )
+    }
+    ArchiveChecksum::Expected(expected) => Some(expected.clone()),
+    ArchiveChecksum::Skip => None,
+  };
+  if let Some(expected) = expected
+    && !expected.eq_ignore_ascii_case(&digest)
+  {
+    return Err(
+      format!(
+        "checksum mismatch for {}: expected {expected}, found {digest}",
+        archive.display()
+      )
+      .into(),
+    );
+  }
+
+  let name = &digest[..16];
+  let root = cache_dir.join(name);
+  if !root.is_dir() {
+    fs::create_dir_all(cache_dir)?;
+    // Extract next to the final directory and rename afterwards so an interrupted run never
+    // leaves a partial tree that later builds would treat as complete.
+    let partial = cache_dir.join(format!("{name}.partial"));
+    if partial.exists() {
+      fs::remove_dir_all(&partial)?;
+    }
+    fs::create_dir_all(&partial)?;
+    unpack(format, archive, &partial)
+      .map_err(|err| format!("failed to extract {}: {err}", archive.display()))?;
+    fs::rename(&partial, &root)?;
+  }
+
+  for stale in fs::read_dir(cache_dir)? {
+    let stale = stale?.path();
+    if stale != root && stale.is_dir() {
+      fs::remove_dir_all(&stale)?;
+    }
+  }
+  Ok(root)
+}
+
+fn unpack(format: ArchiveFormat, archive: &Path, destination: &Path) -> BuildResult<()> {
+  match format {
+    ArchiveFormat::Zip => zip::ZipArchive::new(File::open(archive)?)?.extract(destination)?,
+    ArchiveFormat::Tar => tar::Archive::new(File::open(archive)?).unpack(destination)?,
+    ArchiveFormat::TarZstd => {
+      // Decode every frame into a temporary tar, since multi-threaded encoders write several.
+      let tar_path = destination.with_extension("partial.tar");
+      let mut source = BufReader::new(File::open(archive)?);
+      let mut tar_file = File::create(&tar_path)?;
+      while !source.fill_buf()?.is_empty() {
+        let mut frame = ruzstd::decoding::StreamingDecoder::new(&mut source)
+          .map_err(|err| format!("invalid zstd frame: {err}"))?;
+        io::copy(&mut frame, &mut tar_file)?;
+      }
+      drop(tar_file);
+      let unpacked = tar::Archive::new(File::open(&tar_path)?).unpack(destination);
+      fs::remove_file(&tar_path)?;
+      unpacked?;
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::io::Write;
+  use tempfile::tempdir;
+
+  fn tar_bytes() -> io::Result<Vec<u8>> {
+    let mut builder = tar::Builder::new(Vec::new());
+    let body = b"# Intro\n";
+    let mut header = tar::Header::new_gnu();
+    header.set_size(body.len() as u64);
+    header.set_mode(0o644);
+    builder.append_data(&mut header, "P001/intro/index.md", &body[..])?;
+    builder.into_inner()
+  }
+
+  #[test]
+  fn extracts_verified_archives_once() -> BuildResult<()> {
+    let dir = tempdir()?;
+    let cache = dir.path().join("cache");
+    let tar = tar_bytes()?;
+
+    let zst = dir.path().join("programs.tar.zst");
+    let compressed =
+      ruzstd::encoding::compress_to_vec(&tar[..], ruzstd::encoding::CompressionLevel::Fastest);
+    fs::write(&zst, [compressed.clone(), compressed].concat())?;
+    let error = extract_collections(&zst, &cache, &ArchiveChecksum::Sidecar).unwrap_err();
+    assert!(error.to_string().contains("failed to read checksum"));
+    let root = extract_collections(&zst, &cache, &ArchiveChecksum::Skip)?;
+    assert_eq!(
+      fs::read_to_string(root.join("P001/intro/index.md"))?,
+      "# Intro\n"
+    );
+    fs::write(root.join("marker"), "")?;
+    let (_, digest) = file_digest(&zst)?;
+    assert_eq!(
+      extract_collections(&zst, &cache, &ArchiveChecksum::Expected(digest))?,
+      root
+    );
+    assert!(root.join("marker").exists());
+
+    let zip_path = dir.path().join("programs.zip");
+    let mut writer = zip::ZipWriter::new(File::create(&zip_path)?);
+    writer.start_file(
+      "P002/intro/index.md",
+      zip::write::SimpleFileOptions::default(),
+    )?;
+    writer.write_all(b"# Zip\n")?;
+    writer.finish()?;
+    let (_, digest) = file_digest(&zip_path)?;
+    fs::write(
+      dir.path().join("programs.zip.sha256"),
+      format!("{digest}  programs.zip\n"),
+    )?;
+    let zip_root = extract_collections(&zip_path, &cache, &ArchiveChecksum::Sidecar)?;
+    assert!(zip_root.join("P002/intro/index.md").is_file());
+    assert!(!root.exists());
+
+    fs::write(dir.path().join("programs.zip.sha256"), "0000")?;
+    let error = extract_collections(&zip_path, &cache, &ArchiveChecksum::Sidecar).unwrap_err();
+    assert!(error.to_string().contains("checksum mismatch"));
+    Ok(())
+  }
+}
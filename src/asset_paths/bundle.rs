@@ -4,17 +4,26 @@ use crate::project::OfflineProjectLayout;
 ///
 /// The generated path always uses forward slashes so that the resulting manifest works on
 /// every platform, regardless of the native directory separator that was used when the
-/// files were discovered on disk.
+/// files were discovered on disk. Repeated separators, such as escaped Windows backslashes,
+/// collapse into one.
 pub fn make_offline_asset_path(
   layout: &OfflineProjectLayout,
   collection_id: &str,
   relative_path: &str,
 ) -> String {
-  format!(
+  let joined = format!(
     "{}/{}/{}",
     layout.collections_dir_name, collection_id, relative_path
-  )
-  .replace('\\', "/")
+  );
+  let mut path = String::with_capacity(joined.len());
+  for ch in joined.chars() {
+    let ch = if ch == '\\' { '/' } else { ch };
+    if ch == '/' && path.ends_with('/') {
+      continue;
+    }
+    path.push(ch);
+  }
+  path
 }
 
 #[cfg(test)]
@@ -33,9 +42,13 @@ mod tests {
       offline_site_root: "site".into(),
       collections_dir_name: "programs".into(),
       offline_bundle_root: "target/offline-html".into(),
+      output_root: None,
       index_html_file: "index.html".into(),
       target_dir: "target".into(),
       offline_manifest_json: "offline_manifest.json".into(),
+      scan_max_depth: 32,
+      asset_extensions: Vec::new(),
+      excluded_extensions: Vec::new(),
     }
   }
 
@@ -6,7 +6,9 @@ use crate::project::OfflineProjectLayout;
 ///
 /// References can appear relative to the entry, the optional asset slug, or via explicit
 /// leading/trailing slashes. The generator expands the provided value into a deterministic
-/// set of possibilities that can be matched against the collected asset map.
+/// set of possibilities that can be matched against the collected asset map. Parent-relative
+/// references such as `../shared/figure.png` are resolved against the entry directory; those
+/// leaving the collection are handled by [`resolve_relative_path`] against the collections root.
 pub fn generate_asset_candidates(
   layout: &OfflineProjectLayout,
   entry_id: &str,
@@ -22,10 +24,57 @@ pub fn generate_asset_candidates(
   builder.add_trimmed_candidate();
   builder.add_slug_candidates();
   builder.add_entry_scope_candidates();
+  builder.add_parent_relative_candidate();
 
   builder.finish()
 }
 
+/// Resolve `path` against the `/`-separated directory `base`, collapsing `.` and `..` segments.
+///
+/// Returns `None` when the result would climb above the directory `base` is relative to, or
+/// would be empty.
+pub fn resolve_relative_path(base: &str, path: &str) -> Option<String> {
+  let mut segments: Vec<&str> = base
+    .split('/')
+    .filter(|segment| !segment.is_empty() && *segment != ".")
+    .collect();
+  for segment in path.split('/') {
+    match segment {
+      "" | "." => {}
+      ".." => {
+        segments.pop()?;
+      }
+      segment => segments.push(segment),
+    }
+  }
+  (!segments.is_empty()).then(|| segments.join("/"))
+}
+
+/// Strip the layout's `collection_asset_literal_prefix` from `path`, returning the remaining
+/// `<collection>/<relative path>` or `None` when `path` does not start with the prefix.
+///
+/// Leading slashes are optional on both sides, so `content/programs/P001/x.png` matches the
+/// prefix `/content/programs` as well.
+pub fn strip_collection_literal_prefix<'p>(
+  layout: &OfflineProjectLayout,
+  path: &'p str,
+) -> Option<&'p str> {
+  let prefix = layout.collection_asset_literal_prefix.trim_matches('/');
+  if prefix.is_empty() {
+    return None;
+  }
+  path
+    .trim_start_matches('/')
+    .strip_prefix(prefix)?
+    .strip_prefix('/')
+    .filter(|rest| !rest.is_empty())
+}
+
+/// Returns true when `path` contains a `..` segment.
+pub(crate) fn is_parent_relative(path: &str) -> bool {
+  path.split('/').any(|segment| segment == "..")
+}
+
 struct CandidateBuilder<'a> {
   layout: &'a OfflineProjectLayout,
   original: &'a str,
@@ -118,6 +167,17 @@ impl<'a> CandidateBuilder<'a> {
     self.push(format!("{entry}/{path}"));
   }
 
+  fn add_parent_relative_candidate(&mut self) {
+    let (Some(entry), Some(path)) = (self.entry, self.trimmed) else {
+      return;
+    };
+    if is_parent_relative(path)
+      && let Some(candidate) = resolve_relative_path(entry, path)
+    {
+      self.push(candidate);
+    }
+  }
+
   fn finish(mut self) -> Vec<String> {
     self.push(self.original.to_string());
     self.result
@@ -132,7 +192,7 @@ impl<'a> CandidateBuilder<'a> {
 
 #[cfg(test)]
 mod tests {
-  use super::generate_asset_candidates;
+  use super::{generate_asset_candidates, resolve_relative_path, strip_collection_literal_prefix};
   use crate::project::OfflineProjectLayout;
 
   fn layout() -> OfflineProjectLayout {
@@ -146,9 +206,13 @@ mod tests {
       offline_site_root: "site".into(),
       collections_dir_name: "programs".into(),
       offline_bundle_root: "target/offline-html".into(),
+      output_root: None,
       index_html_file: "index.html".into(),
       target_dir: "target".into(),
       offline_manifest_json: "offline_manifest.json".into(),
+      scan_max_depth: 32,
+      asset_extensions: Vec::new(),
+      excluded_extensions: Vec::new(),
     }
   }
 
@@ -190,4 +254,37 @@ mod tests {
       format!("safety/{}/docs/intro.md", layout.entry_assets_dir()),
     ]);
   }
+
+  #[test]
+  fn resolves_parent_relative_references_against_the_entry() {
+    let layout = layout();
+    let candidates = generate_asset_candidates(&layout, "safety", None, "../shared/figure.png");
+    assert!(candidates.contains(&"shared/figure.png".to_string()));
+
+    assert_eq!(
+      resolve_relative_path("P001/safety", "../../P000-common/./assets/logo.png").as_deref(),
+      Some("P000-common/assets/logo.png")
+    );
+    assert_eq!(
+      resolve_relative_path("P001/safety", "../../../etc/passwd"),
+      None
+    );
+  }
+
+  #[test]
+  fn strips_the_collection_literal_prefix() {
+    let layout = layout();
+    assert_eq!(
+      strip_collection_literal_prefix(&layout, "/content/programs/P001/assets/x.png"),
+      Some("P001/assets/x.png")
+    );
+    assert_eq!(
+      strip_collection_literal_prefix(&layout, "content/programs/P001/x.png"),
+      Some("P001/x.png")
+    );
+    assert_eq!(
+      strip_collection_literal_prefix(&layout, "/content/programs-old/P001/x.png"),
+      None
+    );
+  }
 }
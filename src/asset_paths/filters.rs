@@ -1,33 +1,133 @@
-use regex::Regex;
-
-fn asset_reference_ignores() -> &'static [Regex] {
-  use std::sync::OnceLock;
-
-  static PATTERNS: OnceLock<Vec<Regex>> = OnceLock::new();
-  PATTERNS
-    .get_or_init(|| {
-      vec![
-        Regex::new(r"(?i)^https?://").expect("invalid http(s) regex"),
-        Regex::new(r"(?i)^data:").expect("invalid data URI regex"),
-        Regex::new(r"(?i)^mailto:").expect("invalid mailto regex"),
-      ]
-    })
-    .as_slice()
-}
+/// Prefixes, matched case-insensitively, of references that never point at a bundled file.
+const IGNORED_PREFIXES: &[&str] = &["http://", "https://", "//", "data:", "mailto:"];
 
 /// Determine whether a markdown asset reference should be ignored during offline analysis.
 ///
-/// External URLs and data URIs are intentionally excluded, since they cannot be embedded into the
-/// offline bundle and require a network connection to resolve anyway.
+/// External URLs (including protocol-relative `//host/path` ones) and data URIs are intentionally
+/// excluded, since they cannot be embedded into the offline bundle and require a network
+/// connection to resolve anyway.
 pub fn should_ignore_asset_reference(value: &str) -> bool {
-  asset_reference_ignores()
-    .iter()
-    .any(|pattern| pattern.is_match(value))
+  IGNORED_PREFIXES.iter().any(|prefix| {
+    value
+      .get(..prefix.len())
+      .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+  })
+}
+
+/// Compiled rules deciding which asset references are not bundled.
+///
+/// The built-in rules of [`should_ignore_asset_reference`] always apply; projects add URL schemes
+/// such as `tel` or app-internal `route` links and path globs on top, usually through
+/// `ProjectConfig::asset_reference_filter`. The same filter works at build time and at runtime.
+#[derive(Debug, Clone, Default)]
+pub struct AssetReferenceFilter {
+  schemes: Vec<String>,
+  path_globs: Vec<String>,
+}
+
+impl AssetReferenceFilter {
+  /// Also ignore references using any of `schemes`, given with or without the trailing `:`.
+  pub fn with_schemes<I, S>(mut self, schemes: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+  {
+    self.schemes.extend(
+      schemes
+        .into_iter()
+        .map(|scheme| {
+          scheme
+            .as_ref()
+            .trim()
+            .trim_end_matches(':')
+            .to_ascii_lowercase()
+        })
+        .filter(|scheme| !scheme.is_empty()),
+    );
+    self
+  }
+
+  /// Also ignore references whose path matches any of `globs`.
+  ///
+  /// `*` matches within one path segment, `**` across segments and `?` a single character.
+  /// Leading `./` and `/` are stripped from references before matching.
+  pub fn with_path_globs<I, S>(mut self, globs: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+  {
+    for glob in globs {
+      let glob = glob.as_ref().trim();
+      if glob.is_empty() {
+        continue;
+      }
+      self.path_globs.push(glob.to_string());
+    }
+    self
+  }
+
+  /// Schemes ignored in addition to the built-in ones.
+  pub fn schemes(&self) -> &[String] {
+    &self.schemes
+  }
+
+  /// Path globs ignored in addition to the built-in rules.
+  pub fn path_globs(&self) -> &[String] {
+    &self.path_globs
+  }
+
+  /// Returns true when `value` must not be treated as a bundled asset reference.
+  pub fn is_ignored(&self, value: &str) -> bool {
+    if should_ignore_asset_reference(value) {
+      return true;
+    }
+    let value = value.trim();
+    if let Some((scheme, _)) = value.split_once(':')
+      && self
+        .schemes
+        .iter()
+        .any(|ignored| ignored.eq_ignore_ascii_case(scheme))
+    {
+      return true;
+    }
+    let path = value.trim_start_matches("./").trim_start_matches('/');
+    self.path_globs.iter().any(|glob| {
+      let glob = glob.trim_start_matches("./").trim_start_matches('/');
+      glob_matches(glob.as_bytes(), path.as_bytes())
+    })
+  }
+}
+
+/// Match `path` against `glob`, where `*` stays within a segment, `**` crosses segments and
+/// `?` matches one character other than `/`.
+fn glob_matches(glob: &[u8], path: &[u8]) -> bool {
+  match glob {
+    [] => path.is_empty(),
+    [b'*', b'*', rest @ ..] => (0..=path.len()).any(|skip| glob_matches(rest, &path[skip..])),
+    [b'*', rest @ ..] => {
+      let segment = path
+        .iter()
+        .position(|&byte| byte == b'/')
+        .unwrap_or(path.len());
+      (0..=segment).any(|skip| glob_matches(rest, &path[skip..]))
+    }
+    [b'?', rest @ ..] => match path {
+      [first, tail @ ..] if *first != b'/' => {
+        // Skip the continuation bytes so `?` consumes a whole UTF-8 character.
+        let width = tail.iter().take_while(|&&byte| byte & 0xC0 == 0x80).count();
+        glob_matches(rest, &tail[width..])
+      }
+      _ => false,
+    },
+    [expected, rest @ ..] => path
+      .split_first()
+      .is_some_and(|(first, tail)| first == expected && glob_matches(rest, tail)),
+  }
 }
 
 #[cfg(test)]
 mod tests {
-  use super::should_ignore_asset_reference;
+  use super::{AssetReferenceFilter, should_ignore_asset_reference};
 
   #[test]
   fn ignores_http_urls() {
@@ -35,6 +135,11 @@ mod tests {
     assert!(should_ignore_asset_reference("HTTP://example.com"));
   }
 
+  #[test]
+  fn ignores_protocol_relative_urls() {
+    assert!(should_ignore_asset_reference("//cdn.example.com/video.mp4"));
+  }
+
   #[test]
   fn ignores_data_uris() {
     assert!(should_ignore_asset_reference("data:image/png;base64,abc"));
@@ -49,4 +154,25 @@ mod tests {
   fn keeps_relative_paths() {
     assert!(!should_ignore_asset_reference("images/photo.png"));
   }
+
+  #[test]
+  fn custom_rules_add_schemes_and_path_globs() {
+    let filter = AssetReferenceFilter::default()
+      .with_schemes(["tel", "route:"])
+      .with_path_globs(["drafts/**", "*.psd"]);
+
+    assert!(filter.is_ignored("TEL:+123"));
+    assert!(filter.is_ignored("route:/lessons/2"));
+    assert!(filter.is_ignored("./drafts/old/cover.png"));
+    assert!(filter.is_ignored("layered.psd"));
+    assert!(filter.is_ignored("https://example.com"));
+    assert!(!filter.is_ignored("images/layered.psd"));
+    assert!(!filter.is_ignored("images/photo.png"));
+
+    let filter = AssetReferenceFilter::default().with_path_globs(["/media/?é/**/*.mp4"]);
+    assert!(filter.is_ignored("media/xé/clips/raw/take.mp4"));
+    assert!(filter.is_ignored("media/éé/clips/take.mp4"));
+    assert!(!filter.is_ignored("media/x/é/take.mp4"));
+    assert!(!filter.is_ignored("media/xé/take.mp4.bak"));
+  }
 }
@@ -10,5 +10,9 @@ mod candidates;
 mod filters;
 
 pub use bundle::make_offline_asset_path;
-pub use candidates::generate_asset_candidates;
-pub use filters::should_ignore_asset_reference;
+#[cfg(all(feature = "markdown", feature = "patch", not(target_arch = "wasm32")))]
+pub(crate) use candidates::is_parent_relative;
+pub use candidates::{
+  generate_asset_candidates, resolve_relative_path, strip_collection_literal_prefix,
+};
+pub use filters::{AssetReferenceFilter, should_ignore_asset_reference};
@@ -1,62 +1,497 @@
 //! Offline build orchestrator responsible for generating manifests and bundling assets.
 
+use std::borrow::Cow;
 use std::collections::{BTreeMap, BTreeSet};
-use std::fs;
-use std::io::ErrorKind;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, ErrorKind, Write};
 use std::path::{Path, PathBuf};
 
 use same_file::is_same_file;
 
 use crate::asset_paths::make_offline_asset_path;
-use crate::manifest::generate_offline_manifest;
+use crate::bundle::provenance::collect_provenance;
+use crate::codegen::{AssetCodegenStrategy, CodegenOptions, DATA_SHARD_DIR};
+#[cfg(feature = "templates")]
+use crate::codegen::{CodegenContext, CodegenTemplates};
+use crate::config::BuildHooks;
+use crate::filesystem::{FilesystemCheckMode, check_bundle_file};
+use crate::manifest::embeds::{EmbedPlaceholder, replace_iframe_embeds};
+use crate::manifest::inventory::{AssetInventory, file_digest, find_duplicate_assets};
+use crate::manifest::{
+  CATALOG_TYPES, DEFAULT_EXCERPT_LENGTH, build_asset_inventory, collect_asset_credits,
+  discover_collections, generate_offline_manifest_with_filter, render_credits_page,
+  write_catalog_module,
+};
 use crate::models::{
-  AssetEntry, ManifestGenerationResult, OfflineEntryRecord, OfflineEntrySummary,
-  OfflineManifestSummary,
+  AssetEntry, COLLECTION_CATALOG_SCHEMA_VERSION, CollectionCatalog, CollectionSummaryRecord,
+  EntryRecord, ExcludedCollectionRecord, ManifestGenerationResult, OFFLINE_MANIFEST_SCHEMA_VERSION,
+  OfflineEntryRecord, OfflineEntrySummary, OfflineManifestSummary, VERSION_FILE,
 };
+use crate::pipeline::{DuplicateAssets, Pipeline};
+use crate::processor::{AssetOutcome, AssetProcessor};
 use crate::project::{OfflineBuildContext, OfflineProjectLayout};
+use crate::report::{
+  AccessibilityReport, AssetStats, BuildReport, BuildStats, EmbedFinding, MissingAltTextError,
+  timed,
+};
 use crate::selection::CollectionInclusion;
 
 /// Generic build result type used across the crate.
 pub type BuildResult<T> = Result<T, Box<dyn std::error::Error>>;
 
+/// Generated artifact, held in memory or streamed to a file.
+///
+/// Artifacts are [`ArtifactContent::File`] when the builder was configured with
+/// [`OfflineBuilder::with_artifact_dir`], so large content sets never hold them in memory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArtifactContent {
+  /// Rendered content.
+  Inline(String),
+  /// File the content was written to.
+  File(PathBuf),
+}
+
+impl ArtifactContent {
+  /// Content held in memory, `None` for artifacts written to a file.
+  pub fn as_str(&self) -> Option<&str> {
+    match self {
+      Self::Inline(content) => Some(content),
+      Self::File(_) => None,
+    }
+  }
+
+  /// File the artifact was written to, `None` for artifacts held in memory.
+  pub fn path(&self) -> Option<&Path> {
+    match self {
+      Self::Inline(_) => None,
+      Self::File(path) => Some(path),
+    }
+  }
+
+  /// Content of the artifact, reading it back from disk if it was written to a file.
+  pub fn read(&self) -> io::Result<Cow<'_, str>> {
+    match self {
+      Self::Inline(content) => Ok(Cow::Borrowed(content)),
+      Self::File(path) => fs::read_to_string(path).map(Cow::Owned),
+    }
+  }
+
+  /// Copy the content to `writer` without loading file-backed artifacts into memory.
+  pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+    match self {
+      Self::Inline(content) => writer.write_all(content.as_bytes()),
+      Self::File(path) => io::copy(&mut File::open(path)?, writer).map(|_| ()),
+    }
+  }
+
+  /// Write the content to `path`, doing nothing if the artifact already lives there.
+  pub fn save(&self, path: &Path) -> io::Result<()> {
+    match self {
+      Self::File(source) if is_same_file(source, path).unwrap_or(false) => Ok(()),
+      Self::File(source) => fs::copy(source, path).map(|_| ()),
+      Self::Inline(content) => fs::write(path, content),
+    }
+  }
+}
+
+/// Sized handle to a possibly unsized selection, so it can be passed on as `&dyn`.
+struct SelectionRef<'a, S: ?Sized>(&'a S);
+
+impl<S: CollectionInclusion + ?Sized> CollectionInclusion for SelectionRef<'_, S> {
+  fn is_included(&self, collection_id: &str) -> bool {
+    self.0.is_included(collection_id)
+  }
+
+  fn version_rejection(&self, collection_id: &str, version: Option<&str>) -> Option<String> {
+    self.0.version_rejection(collection_id, version)
+  }
+}
+
+/// Serialize `summary` into `writer` as the pretty-printed `offline_manifest.json` document.
+pub fn write_offline_manifest<W>(writer: &mut W, summary: &OfflineManifestSummary) -> io::Result<()>
+where
+  W: Write + ?Sized,
+{
+  Ok(serde_json::to_writer_pretty(writer, summary)?)
+}
+
+/// Serialize `catalog` into `writer` as the pretty-printed `collection_catalog.json` document.
+pub fn write_collection_catalog<W>(writer: &mut W, catalog: &CollectionCatalog) -> io::Result<()>
+where
+  W: Write + ?Sized,
+{
+  Ok(serde_json::to_writer_pretty(writer, catalog)?)
+}
+
+/// Render an artifact through `render` as the build step `step`, streaming it to
+/// `dir/file_name` when `dir` is set.
+fn emit_artifact<F>(
+  dir: Option<&Path>,
+  file_name: &str,
+  step: &str,
+  render: F,
+) -> BuildResult<ArtifactContent>
+where
+  F: FnOnce(&mut dyn Write) -> BuildResult<()>,
+{
+  timed(step, || match dir {
+    Some(dir) => {
+      fs::create_dir_all(dir)?;
+      let path = dir.join(file_name);
+      let mut writer = BufWriter::new(File::create(&path)?);
+      render(&mut writer)?;
+      writer.flush()?;
+      Ok(ArtifactContent::File(path))
+    }
+    None => {
+      let mut buffer = Vec::new();
+      render(&mut buffer)?;
+      Ok(ArtifactContent::Inline(String::from_utf8(buffer)?))
+    }
+  })
+}
+
 /// Collection of generated artifacts required by the offline bundle.
 pub struct OfflineArtifacts {
   /// Rust source defining the collection asset lookup table.
-  pub asset_table_code: String,
+  pub asset_table_code: ArtifactContent,
   /// Rust source providing offline entry bodies and asset mappings.
-  pub offline_manifest_code: String,
+  pub offline_manifest_code: ArtifactContent,
   /// Offline manifest serialised as prettified JSON.
-  pub offline_manifest_json: String,
+  pub offline_manifest_json: ArtifactContent,
   /// Collection catalog JSON used by the launcher UI.
-  pub collection_catalog_json: String,
+  pub collection_catalog_json: ArtifactContent,
+  /// `catalog.js` ES module exporting the collection catalog and offline manifest.
+  pub catalog_module_js: ArtifactContent,
+  /// `catalog.d.ts` TypeScript declarations for `catalog.js`.
+  pub catalog_types_ts: ArtifactContent,
+  /// CycloneDX-style inventory of every bundled asset, written alongside the offline manifest.
+  pub asset_inventory_json: ArtifactContent,
+  /// Attribution collected from `ATTRIBUTION.yaml` sidecars, serialised as `credits.json`.
+  pub credits_json: ArtifactContent,
+  /// Standalone HTML credits page listing the same attribution records.
+  pub credits_html: ArtifactContent,
+  /// Build provenance serialised as `version.json` for the bundle root.
+  pub version_json: ArtifactContent,
   /// File system paths that should trigger rerunning the build script when changed.
   pub rerun_paths: Vec<PathBuf>,
+  /// Selected collections that were dropped, e.g. for failing a `minVersion` requirement.
+  pub excluded_collections: Vec<ExcludedCollectionRecord>,
+  /// Findings such as images without alt text.
+  pub report: BuildReport,
+}
+
+/// Asset listed in the manifest that is missing from the asset mirror.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingMirrorAsset {
+  /// Collection the asset belongs to.
+  pub collection_id: String,
+  /// Entry referencing the asset, or `None` for collection hero images.
+  pub entry_id: Option<String>,
+  /// Bundle-relative asset path as listed in the offline manifest.
+  pub asset_path: String,
+  /// Location the asset was expected at inside the mirror.
+  pub mirror_path: PathBuf,
+}
+
+/// Error returned when manifest asset paths are missing from the asset mirror.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MirrorConsistencyError {
+  /// Every missing asset, in manifest order.
+  pub missing: Vec<MissingMirrorAsset>,
+}
+
+impl fmt::Display for MirrorConsistencyError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "{} manifest asset(s) are missing from the asset mirror:",
+      self.missing.len()
+    )?;
+    for asset in &self.missing {
+      match &asset.entry_id {
+        Some(entry_id) => write!(
+          f,
+          "\n  {} (entry {}/{})",
+          asset.asset_path, asset.collection_id, entry_id
+        )?,
+        None => write!(
+          f,
+          "\n  {} (hero image of {})",
+          asset.asset_path, asset.collection_id
+        )?,
+      }
+    }
+    Ok(())
+  }
+}
+
+impl std::error::Error for MirrorConsistencyError {}
+
+/// Artifacts produced for a single named profile by [`Pipeline::run_profiles`].
+pub struct ProfileArtifacts {
+  /// Profile name, also used as the bundle subdirectory.
+  pub name: String,
+  /// Layout with `offline_bundle_root` (and `output_root`, if set) pointing at the profile's
+  /// bundle directory.
+  pub layout: OfflineProjectLayout,
+  /// Generated artifacts restricted to the profile's selection.
+  pub artifacts: OfflineArtifacts,
 }
 
 /// High-level helper for generating offline manifests and preparing assets.
 pub struct OfflineBuilder<'a> {
   context: OfflineBuildContext<'a>,
+  codegen: CodegenOptions,
+  hooks: BuildHooks,
+  processors: Vec<Box<dyn AssetProcessor>>,
+  excerpt_length: usize,
+  strict_alt_text: bool,
+  deduplicate_assets: bool,
+  embed_placeholder: Option<EmbedPlaceholder>,
+  artifact_dir: Option<PathBuf>,
+  build_timestamp: Option<u64>,
+  catalog_language: Option<String>,
+  #[cfg(feature = "templates")]
+  templates: CodegenTemplates,
 }
 
 impl<'a> OfflineBuilder<'a> {
   /// Create a builder for the provided build context.
   pub fn new(context: OfflineBuildContext<'a>) -> Self {
-    Self { context }
+    Self {
+      context,
+      codegen: CodegenOptions::default(),
+      hooks: BuildHooks::default(),
+      processors: Vec::new(),
+      excerpt_length: DEFAULT_EXCERPT_LENGTH,
+      strict_alt_text: false,
+      deduplicate_assets: false,
+      embed_placeholder: None,
+      artifact_dir: None,
+      build_timestamp: None,
+      catalog_language: None,
+      #[cfg(feature = "templates")]
+      templates: CodegenTemplates::default(),
+    }
+  }
+
+  /// Build context this builder was created with.
+  pub fn context(&self) -> &OfflineBuildContext<'a> {
+    &self.context
+  }
+
+  /// Commands run by the `pre_build` and `post_bundle` pipeline stages.
+  pub fn with_hooks(mut self, hooks: BuildHooks) -> Self {
+    self.hooks = hooks;
+    self
+  }
+
+  /// Hook commands configured with [`OfflineBuilder::with_hooks`].
+  pub fn hooks(&self) -> &BuildHooks {
+    &self.hooks
+  }
+
+  /// Run `processor` on collection assets as they are mirrored, after any earlier processors.
+  pub fn with_asset_processor(mut self, processor: impl AssetProcessor + 'static) -> Self {
+    self.processors.push(Box::new(processor));
+    self
+  }
+
+  /// Limit catalog entry excerpts to `max_chars` characters instead of [`DEFAULT_EXCERPT_LENGTH`].
+  pub fn with_excerpt_length(mut self, max_chars: usize) -> Self {
+    self.excerpt_length = max_chars;
+    self
+  }
+
+  /// Fail the build with a [`MissingAltTextError`] when any entry image lacks alt text.
+  ///
+  /// Otherwise missing alt text is only listed in [`OfflineArtifacts::report`].
+  pub fn with_strict_alt_text(mut self, strict: bool) -> Self {
+    self.strict_alt_text = strict;
+    self
+  }
+
+  /// Store byte-identical collection assets once and list the copies in `asset_aliases`.
+  ///
+  /// Duplicates are found from the digests in the asset inventory, so enabling this does not
+  /// hash any file twice.
+  pub fn with_asset_deduplication(mut self, enabled: bool) -> Self {
+    self.deduplicate_assets = enabled;
+    self
+  }
+
+  /// Replace remote `<iframe>` embeds in entry bodies with `placeholder` in the generated code.
+  ///
+  /// Embeds are listed in [`BuildReport::embeds`] either way.
+  pub fn with_embed_placeholders(mut self, placeholder: EmbedPlaceholder) -> Self {
+    self.embed_placeholder = Some(placeholder);
+    self
+  }
+
+  /// Adjust visibility, function names and module wrappers of the generated Rust modules.
+  pub fn with_codegen_options(mut self, options: CodegenOptions) -> Self {
+    self.codegen = options;
+    self
+  }
+
+  /// Stream the generated artifacts into files below `dir` instead of holding them in memory.
+  ///
+  /// The code goes to `asset_table.rs` and `offline_manifest.rs`, the offline manifest to the
+  /// layout's `offline_manifest_json` file and the rest to `collection_catalog.json`,
+  /// `asset_inventory.json`, `credits.json`, `credits.html` and `version.json`, each returned as an
+  /// [`ArtifactContent::File`]. [`OfflineBuilder::build_profiles`] uses one subdirectory per
+  /// profile.
+  pub fn with_artifact_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+    self.artifact_dir = Some(dir.into());
+    self
+  }
+
+  /// Directory configured with [`OfflineBuilder::with_artifact_dir`].
+  pub fn artifact_dir(&self) -> Option<&Path> {
+    self.artifact_dir.as_deref()
+  }
+
+  /// Record `seconds` since the Unix epoch as the build time in the provenance instead of
+  /// `SOURCE_DATE_EPOCH` or the clock.
+  pub fn with_build_timestamp(mut self, seconds: u64) -> Self {
+    self.build_timestamp = Some(seconds);
+    self
+  }
+
+  /// Resolve localized collection titles and descriptions to `language` in the catalog and the
+  /// generated code instead of emitting every translation.
+  ///
+  /// Missing translations fall back as described in
+  /// [`LocalizedText::get`](crate::models::LocalizedText::get).
+  pub fn with_catalog_language(mut self, language: impl Into<String>) -> Self {
+    self.catalog_language = Some(language.into());
+    self
+  }
+
+  /// Render the generated Rust modules from caller-supplied templates.
+  ///
+  /// Modules without a template keep the built-in output.
+  #[cfg(feature = "templates")]
+  pub fn with_templates(mut self, templates: CodegenTemplates) -> Self {
+    self.templates = templates;
+    self
+  }
+
+  /// Scan the collections and return the manifest data without mirroring assets or rendering code.
+  ///
+  /// This is the read-only first step of [`OfflineBuilder::build`], useful for tools that report
+  /// on the catalog and asset graph. Nothing is written to disk.
+  pub fn scan<S: CollectionInclusion + ?Sized>(
+    &self,
+    selection: &S,
+  ) -> BuildResult<ManifestGenerationResult> {
+    let mut manifest = generate_offline_manifest_with_filter(
+      &self.context.layout,
+      &self.context.collections_dir,
+      selection,
+      &self.context.asset_reference_filter,
+    )?;
+    if self.excerpt_length != DEFAULT_EXCERPT_LENGTH {
+      manifest.refresh_excerpts(self.excerpt_length);
+    }
+    Ok(manifest)
+  }
+
+  /// List the collections with their metadata and entry counts, see [`discover_collections`].
+  ///
+  /// Much cheaper than [`OfflineBuilder::scan`]: entry bodies and assets are not read.
+  pub fn list_collections(&self) -> Vec<CollectionSummaryRecord> {
+    discover_collections(&self.context.layout, &self.context.collections_dir)
+  }
+
+  /// Run [`OfflineBuilder::scan`] and serialise the result as pretty-printed JSON.
+  ///
+  /// The JSON deserialises back into a [`ManifestGenerationResult`], so external tooling can
+  /// inspect or post-process the intermediate representation.
+  pub fn export_intermediate_json<S: CollectionInclusion + ?Sized>(
+    &self,
+    selection: &S,
+  ) -> BuildResult<String> {
+    Ok(serde_json::to_string_pretty(&self.scan(selection)?)?)
   }
 
   /// Generate the offline manifest, mirror referenced assets and return the resulting artifacts.
-  pub fn build<S: CollectionInclusion>(&self, selection: &S) -> BuildResult<OfflineArtifacts> {
+  ///
+  /// Runs [`Pipeline::standard`], including the `pre_build` hooks; use [`Pipeline::run`] to
+  /// customise the stages.
+  pub fn build<S: CollectionInclusion + ?Sized>(
+    &self,
+    selection: &S,
+  ) -> BuildResult<OfflineArtifacts> {
+    Pipeline::standard()
+      .run(self, &SelectionRef(selection))?
+      .artifacts
+      .ok_or_else(|| "build pipeline did not produce artifacts".into())
+  }
+
+  /// Build one set of artifacts per named profile with [`Pipeline::standard`].
+  ///
+  /// Collections are scanned and mirrored once for the union of all profiles, then each
+  /// profile is narrowed to its own selection; see [`Pipeline::run_profiles`] to customise the
+  /// stages.
+  pub fn build_profiles<S: CollectionInclusion>(
+    &self,
+    profiles: &[(&str, S)],
+  ) -> BuildResult<Vec<ProfileArtifacts>> {
+    Pipeline::standard().run_profiles(self, profiles)
+  }
+
+  pub(crate) fn render_artifacts(
+    &self,
+    layout: &OfflineProjectLayout,
+    manifest: ManifestGenerationResult,
+    duplicates: &DuplicateAssets,
+    inventory: Option<&AssetInventory>,
+    artifact_dir: Option<&Path>,
+  ) -> BuildResult<OfflineArtifacts> {
     let ManifestGenerationResult {
-      collection_catalog,
-      offline_entries,
+      mut collection_catalog,
+      mut offline_entries,
       asset_map,
       hero_asset_paths,
       hero_match_arms,
-    } = self.generate_manifest(selection)?;
+      excluded_collections,
+    } = manifest;
 
-    self.prepare_collection_asset_sources(&asset_map)?;
+    timed("verify", || {
+      self.verify_mirror(layout, &offline_entries, &hero_asset_paths)
+    })?;
+
+    if let Some(language) = &self.catalog_language {
+      for record in &mut collection_catalog {
+        record.meta.localize(language);
+      }
+    }
+    let accessibility = AccessibilityReport::from_entries(&offline_entries);
+    let embeds = EmbedFinding::from_entries(&offline_entries, self.embed_placeholder.is_some());
+    if let Some(placeholder) = &self.embed_placeholder {
+      for entry in &mut offline_entries {
+        entry.body = replace_iframe_embeds(&entry.body, placeholder);
+      }
+    }
+    if self.strict_alt_text && accessibility.missing_alt_count() > 0 {
+      return Err(
+        MissingAltTextError {
+          report: accessibility,
+        }
+        .into(),
+      );
+    }
+
+    // Only alias duplicates whose canonical copy is part of this selection.
+    let duplicates: DuplicateAssets = duplicates
+      .iter()
+      .filter(|(key, canonical)| asset_map.contains_key(*key) && asset_map.contains_key(*canonical))
+      .map(|(key, canonical)| (key.clone(), canonical.clone()))
+      .collect();
 
-    let layout = &self.context.layout;
     let mirror_base = &self.context.asset_mirror_dir;
     let mirror_relative = match mirror_base.strip_prefix(self.context.manifest_dir) {
       Ok(path) => path,
@@ -70,19 +505,82 @@ impl<'a> OfflineBuilder<'a> {
         .trim_start_matches('/')
     );
 
-    let (asset_definitions, asset_match_entries) =
-      render_collection_assets(&asset_map, &mirror_prefix);
+    let (asset_definitions, asset_match_entries) = render_collection_assets(
+      &asset_map,
+      &duplicates,
+      &mirror_prefix,
+      &self.codegen.asset_strategy,
+    );
     let hero_section = render_hero_match_section(&hero_match_arms);
 
-    let asset_table_code = format!(
-      r#"// Generated at build time by build tooling
-use dioxus::prelude::Asset;
+    #[cfg(feature = "templates")]
+    let context = CodegenContext::new(
+      layout,
+      &collection_catalog,
+      &offline_entries,
+      &asset_map,
+      &duplicates,
+      &mirror_prefix,
+    );
+
+    let options = &self.codegen;
+    options.validate()?;
+    let hero_fn = options.function_name("get_collection_hero_asset");
+    let asset_fn = options.function_name("get_collection_asset");
+    let entry_fn = options.function_name("offline_entry");
+    let body_fn = options.function_name("offline_entry_body");
+    let assets_fn = options.function_name("offline_entry_assets");
+    let offline_asset_fn = options.function_name("offline_collection_asset");
+    let document_fn = options.function_name("offline_entry_document");
+    let private_vis = options.item_visibility("");
+    let crate_vis = options.item_visibility("pub(crate)");
+    let pub_vis = options.item_visibility("pub");
+    let asset_import = options.asset_strategy.import();
+
+    #[cfg(feature = "templates")]
+    let asset_table_template = self
+      .templates
+      .asset_table
+      .as_ref()
+      .map(|template| CodegenTemplates::render("asset_table", template, &context))
+      .transpose()?;
+    #[cfg(not(feature = "templates"))]
+    let asset_table_template: Option<String> = None;
+    let asset_table_fallback = format!(
+      r#"{asset_import}
+
+#[allow(dead_code)]
+{private_vis}fn {hero_fn}(_collection_id: &str) -> Option<&'static Asset> {{
+    None
+}}
+
+#[allow(dead_code)]
+{crate_vis}fn {asset_fn}(_collection_id: &str, _relative_path: &str) -> Option<&'static Asset> {{
+    None
+}}
+"#
+    );
+    let asset_table_code = emit_artifact(artifact_dir, "asset_table.rs", "code", |writer| {
+      if let Some(code) = asset_table_template {
+        writer.write_all(code.as_bytes())?;
+        return Ok(());
+      }
+      options.write_module(
+        writer,
+        options.asset_table_module.as_deref(),
+        options.asset_table_feature.as_deref(),
+        &asset_table_fallback,
+        |writer| {
+          write!(
+            writer,
+            r#"// Generated at build time by build tooling
+{asset_import}
 
 // Static asset definitions for all collections
 {}
 
 // Generated lookup function
-fn get_collection_hero_asset(collection_id: &str) -> Option<&'static Asset> {{
+{private_vis}fn {hero_fn}(collection_id: &str) -> Option<&'static Asset> {{
     match collection_id {{
 {}
     }}
@@ -90,134 +588,648 @@ fn get_collection_hero_asset(collection_id: &str) -> Option<&'static Asset> {{
 
 // Lookup for arbitrary collection assets referenced in markdown
 #[allow(unreachable_patterns)]
-pub(crate) fn get_collection_asset(collection_id: &str, relative_path: &str) -> Option<&'static Asset> {{
+{crate_vis}fn {asset_fn}(collection_id: &str, relative_path: &str) -> Option<&'static Asset> {{
     match (collection_id, relative_path) {{
 {}
         _ => None,
     }}
 }}
 "#,
-      asset_definitions.join("\n"),
-      hero_section,
-      asset_match_entries.join("\n"),
-    );
-
-    let (offline_entry_code, offline_asset_code) =
-      render_offline_entry_tables(layout, &offline_entries, &asset_map);
+            asset_definitions.join("\n"),
+            hero_section,
+            asset_match_entries.join("\n"),
+          )
+        },
+      )?;
+      Ok(())
+    })?;
 
-    let offline_manifest_code = format!(
-      r#"// Generated at build time for the offline-html feature
-use serde::{{Deserialize, Serialize}};
+    let (entry_body_field, body_code, body_fallback) = if options.data_shards {
+      let shards = timed("shards", || {
+        write_data_shards(&mirror_base.join(DATA_SHARD_DIR), &offline_entries)
+      })?;
+      (
+        "",
+        render_data_shard_code(&shards, &mirror_prefix, options),
+        format!(
+          r#"#[allow(dead_code)]
+{crate_vis}async fn {body_fn}(_collection_id: &str, _entry_id: &str) -> Option<String> {{
+    None
+}}
+"#
+        ),
+      )
+    } else {
+      (
+        "\n    pub body: &'static str,",
+        format!(
+          r#"{crate_vis}fn {body_fn}(collection_id: &str, entry_id: &str) -> Option<&'static str> {{
+    {entry_fn}(collection_id, entry_id).map(|record| record.body)
+}}
+"#
+        ),
+        format!(
+          r#"#[allow(dead_code)]
+{crate_vis}fn {body_fn}(_collection_id: &str, _entry_id: &str) -> Option<&'static str> {{
+    None
+}}
+"#
+        ),
+      )
+    };
 
-#[derive(Clone)]
-pub struct OfflineEntry {{
-    pub body: &'static str,
+    #[cfg(feature = "templates")]
+    let offline_manifest_template = self
+      .templates
+      .offline_manifest
+      .as_ref()
+      .map(|template| CodegenTemplates::render("offline_manifest", template, &context))
+      .transpose()?;
+    #[cfg(not(feature = "templates"))]
+    let offline_manifest_template: Option<String> = None;
+    let offline_manifest_fallback = format!(
+      r#"#[derive(Clone)]
+pub struct OfflineEntry {{{entry_body_field}
     pub assets: &'static [&'static str],
+    pub word_count: usize,
+    pub reading_minutes: usize,
 }}
-{}
 
 #[allow(dead_code)]
-pub fn offline_entry(collection_id: &str, entry_id: &str) -> Option<OfflineEntry> {{
-    match (collection_id, entry_id) {{
-{}
-    }}
+{pub_vis}fn {entry_fn}(_collection_id: &str, _entry_id: &str) -> Option<OfflineEntry> {{
+    None
 }}
 
-pub(crate) fn offline_entry_body(collection_id: &str, entry_id: &str) -> Option<&'static str> {{
-    offline_entry(collection_id, entry_id).map(|record| record.body)
+{body_fallback}
+#[allow(dead_code)]
+{crate_vis}fn {assets_fn}(_collection_id: &str, _entry_id: &str) -> Option<&'static [&'static str]> {{
+    None
 }}
 
-pub(crate) fn offline_entry_assets(collection_id: &str, entry_id: &str) -> Option<&'static [&'static str]> {{
-    offline_entry(collection_id, entry_id).map(|record| record.assets)
+#[allow(dead_code)]
+{crate_vis}fn {document_fn}(_collection_id: &str, _entry_id: &str, _name: &str) -> Option<&'static str> {{
+    None
 }}
 
-#[allow(unreachable_patterns)]
-pub(crate) fn offline_collection_asset(collection_id: &str, relative_path: &str) -> Option<&'static str> {{
-    match (collection_id, relative_path) {{
-{}
-        _ => None,
-    }}
+#[allow(dead_code)]
+{crate_vis}fn {offline_asset_fn}(_collection_id: &str, _relative_path: &str) -> Option<&'static str> {{
+    None
 }}
-"#,
-      offline_entry_code, offline_asset_code.0, offline_asset_code.1,
+"#
     );
+    let offline_manifest_code =
+      emit_artifact(artifact_dir, "offline_manifest.rs", "code", |writer| {
+        if let Some(code) = offline_manifest_template {
+          writer.write_all(code.as_bytes())?;
+          return Ok(());
+        }
+        options.write_module(
+          writer,
+          options.offline_manifest_module.as_deref(),
+          options.offline_manifest_feature.as_deref(),
+          &offline_manifest_fallback,
+          |writer| {
+            write_offline_manifest_code(
+              writer,
+              options,
+              layout,
+              &offline_entries,
+              &asset_map,
+              entry_body_field,
+              &body_code,
+            )
+          },
+        )?;
+        Ok(())
+      })?;
 
-    let offline_manifest_json = serde_json::to_string_pretty(&OfflineManifestSummary {
+    let asset_inventory = match inventory {
+      Some(inventory) => {
+        let mut inventory = inventory.clone();
+        inventory.retain_assets(layout, &asset_map);
+        inventory
+      }
+      None => {
+        let mut inventory = timed("inventory", || {
+          build_asset_inventory(layout, &self.context.collections_dir, &asset_map)
+        })?;
+        self.rehash_processed_assets(layout, &asset_map, &mut inventory)?;
+        inventory
+      }
+    };
+    let asset_inventory_json =
+      emit_artifact(artifact_dir, "asset_inventory.json", "json", |writer| {
+        Ok(serde_json::to_writer_pretty(writer, &asset_inventory)?)
+      })?;
+
+    let catalog_entries: BTreeMap<(&str, &str), &EntryRecord> = collection_catalog
+      .iter()
+      .flat_map(|collection| {
+        collection
+          .entries
+          .iter()
+          .map(move |entry| ((collection.id.as_str(), entry.id.as_str()), entry))
+      })
+      .collect();
+    let mut manifest_summary = OfflineManifestSummary {
+      schema_version: OFFLINE_MANIFEST_SCHEMA_VERSION,
       site_root: layout.offline_site_root.clone(),
       entries: offline_entries
         .iter()
-        .map(|entry| OfflineEntrySummary {
-          collection_id: entry.collection_id.clone(),
-          entry_id: entry.entry_id.clone(),
-          asset_paths: entry.asset_paths.clone(),
+        .map(|entry| {
+          let record =
+            catalog_entries.get(&(entry.collection_id.as_str(), entry.entry_id.as_str()));
+          OfflineEntrySummary {
+            collection_id: entry.collection_id.clone(),
+            entry_id: entry.entry_id.clone(),
+            title: record.map(|record| record.title.clone()),
+            section: record.and_then(|record| record.section.clone()),
+            asset_paths: entry.asset_paths.clone(),
+            headings: entry.headings.clone(),
+            priority_assets: entry.priority_assets.clone(),
+            video_posters: entry.video_posters.clone(),
+          }
         })
         .collect(),
       hero_assets: hero_asset_paths.iter().cloned().collect(),
+      asset_hashes: asset_inventory
+        .components
+        .iter()
+        .filter_map(|component| {
+          let digest = component.hashes.first()?;
+          Some((component.name.clone(), digest.content.clone()))
+        })
+        .collect(),
+      asset_sizes: asset_inventory
+        .components
+        .iter()
+        .filter_map(|component| {
+          let size = component.property("offline:size")?.parse().ok()?;
+          Some((component.name.clone(), size))
+        })
+        .collect(),
+      asset_modified: asset_map
+        .values()
+        .filter_map(|entry| {
+          let path =
+            make_offline_asset_path(layout, &entry.collection_id, entry.output_relative_path());
+          Some((path, entry.modified?))
+        })
+        .collect(),
+      asset_aliases: duplicates
+        .iter()
+        .map(
+          |((collection_id, relative), (canonical_id, canonical_relative))| {
+            (
+              make_offline_asset_path(layout, collection_id, relative),
+              make_offline_asset_path(layout, canonical_id, canonical_relative),
+            )
+          },
+        )
+        .collect(),
+      provenance: None,
+    };
+    let provenance = collect_provenance(
+      self.context.manifest_dir,
+      &offline_entries,
+      &manifest_summary.asset_hashes,
+      self.build_timestamp,
+    );
+    manifest_summary.provenance = Some(provenance.clone());
+    let offline_manifest_json = emit_artifact(
+      artifact_dir,
+      &layout.offline_manifest_json,
+      "json",
+      |writer| Ok(write_offline_manifest(writer, &manifest_summary)?),
+    )?;
+
+    let catalog = CollectionCatalog {
+      schema_version: COLLECTION_CATALOG_SCHEMA_VERSION,
+      language: self.catalog_language.clone(),
+      collections: collection_catalog,
+    };
+    let collection_catalog_json =
+      emit_artifact(artifact_dir, "collection_catalog.json", "json", |writer| {
+        Ok(write_collection_catalog(writer, &catalog)?)
+      })?;
+    let catalog_module_js = emit_artifact(artifact_dir, "catalog.js", "js", |writer| {
+      Ok(write_catalog_module(writer, &catalog, &manifest_summary)?)
+    })?;
+    let catalog_types_ts = emit_artifact(artifact_dir, "catalog.d.ts", "js", |writer| {
+      Ok(writer.write_all(CATALOG_TYPES.as_bytes())?)
     })?;
 
-    let collection_catalog_json = serde_json::to_string_pretty(&collection_catalog)?;
+    let credits = collect_asset_credits(layout, &self.context.collections_dir, &asset_map);
+    let credits_json = emit_artifact(artifact_dir, "credits.json", "json", |writer| {
+      Ok(serde_json::to_writer_pretty(writer, &credits)?)
+    })?;
+    let credits_html = emit_artifact(artifact_dir, "credits.html", "html", |writer| {
+      Ok(writer.write_all(render_credits_page(&credits).as_bytes())?)
+    })?;
+    let version_json = emit_artifact(artifact_dir, VERSION_FILE, "json", |writer| {
+      Ok(serde_json::to_writer_pretty(writer, &provenance)?)
+    })?;
 
     let mut rerun_paths = vec![self.context.collections_dir.to_path_buf()];
     rerun_paths.push(self.context.collections_local_path.to_path_buf());
-    append_collection_metadata_paths(self.context.collections_dir, layout, &mut rerun_paths);
+    append_collection_metadata_paths(&self.context.collections_dir, layout, &mut rerun_paths);
 
     Ok(OfflineArtifacts {
       asset_table_code,
       offline_manifest_code,
       offline_manifest_json,
       collection_catalog_json,
+      catalog_module_js,
+      catalog_types_ts,
+      asset_inventory_json,
+      credits_json,
+      credits_html,
+      version_json,
       rerun_paths,
+      excluded_collections,
+      report: BuildReport {
+        accessibility,
+        embeds,
+        assets: AssetStats::from_assets(asset_map.values()),
+        stats: BuildStats::default(),
+      },
     })
   }
 
-  fn generate_manifest<S: CollectionInclusion>(
+  /// Check that every asset listed in the manifest was mirrored.
+  fn verify_mirror(
     &self,
-    selection: &S,
-  ) -> BuildResult<ManifestGenerationResult> {
-    generate_offline_manifest(
-      &self.context.layout,
-      self.context.collections_dir,
-      selection,
-    )
+    layout: &OfflineProjectLayout,
+    offline_entries: &[OfflineEntryRecord],
+    hero_asset_paths: &BTreeSet<String>,
+  ) -> Result<(), MirrorConsistencyError> {
+    let collections_prefix = format!("{}/", layout.collections_dir_name);
+    let mirror_path = |asset_path: &str| {
+      self.context.asset_mirror_dir.join(
+        asset_path
+          .strip_prefix(&collections_prefix)
+          .unwrap_or(asset_path),
+      )
+    };
+
+    let entry_assets = offline_entries.iter().flat_map(|entry| {
+      let document_paths = entry
+        .documents
+        .iter()
+        .flat_map(|document| &document.asset_paths);
+      entry.asset_paths.iter().chain(document_paths).map(|path| {
+        (
+          entry.collection_id.clone(),
+          Some(entry.entry_id.clone()),
+          path,
+        )
+      })
+    });
+    let hero_assets = hero_asset_paths.iter().map(|path| {
+      let collection_id = path
+        .strip_prefix(&collections_prefix)
+        .unwrap_or(path)
+        .split('/')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+      (collection_id, None, path)
+    });
+
+    let missing: Vec<MissingMirrorAsset> = entry_assets
+      .chain(hero_assets)
+      .filter_map(|(collection_id, entry_id, asset_path)| {
+        let mirror_path = mirror_path(asset_path);
+        (!mirror_path.is_file()).then(|| MissingMirrorAsset {
+          collection_id,
+          entry_id,
+          asset_path: asset_path.clone(),
+          mirror_path,
+        })
+      })
+      .collect();
+
+    if missing.is_empty() {
+      Ok(())
+    } else {
+      Err(MirrorConsistencyError { missing })
+    }
   }
 
-  fn prepare_collection_asset_sources(
+  /// Report assets that cannot be stored on the configured target file system.
+  fn check_target_filesystem(
     &self,
     asset_map: &BTreeMap<(String, String), AssetEntry>,
   ) -> BuildResult<()> {
+    let Some(profile) = self.context.target_filesystem else {
+      return Ok(());
+    };
+
+    let layout = &self.context.layout;
+    let site_root = layout.offline_site_root.trim_matches('/');
+    let mut issues = Vec::new();
+    for entry in asset_map.values() {
+      let Ok(metadata) = fs::metadata(entry.source_path(&self.context.collections_dir)) else {
+        continue;
+      };
+      let asset_path = make_offline_asset_path(layout, &entry.collection_id, &entry.relative_path);
+      let bundle_path = if site_root.is_empty() {
+        asset_path
+      } else {
+        format!("{site_root}/{asset_path}")
+      };
+      issues.extend(check_bundle_file(
+        profile.filesystem,
+        &bundle_path,
+        metadata.len(),
+      ));
+    }
+
+    if issues.is_empty() {
+      return Ok(());
+    }
+    match profile.mode {
+      FilesystemCheckMode::Warn => {
+        for issue in &issues {
+          println!(
+            "cargo:warning=Not compatible with {}: {}",
+            profile.filesystem, issue
+          );
+        }
+        Ok(())
+      }
+      FilesystemCheckMode::Error => {
+        let details: Vec<String> = issues.iter().map(ToString::to_string).collect();
+        Err(
+          format!(
+            "bundle is not compatible with {}:\n  {}",
+            profile.filesystem,
+            details.join("\n  ")
+          )
+          .into(),
+        )
+      }
+    }
+  }
+
+  /// Check the target file system, hash and mirror the manifest's assets and record processor
+  /// outputs on the manifest.
+  ///
+  /// Returns the duplicates found when deduplication is enabled and the inventory of the
+  /// mirrored assets, so later steps reuse its digests instead of hashing again.
+  pub(crate) fn mirror_assets(
+    &self,
+    manifest: &mut ManifestGenerationResult,
+  ) -> BuildResult<(DuplicateAssets, AssetInventory)> {
+    let layout = &self.context.layout;
+    self.check_target_filesystem(&manifest.asset_map)?;
+    let mut inventory = timed("hash", || {
+      build_asset_inventory(layout, &self.context.collections_dir, &manifest.asset_map)
+    })?;
+    let duplicates = if self.deduplicate_assets {
+      timed("dedupe", || {
+        find_duplicate_assets(layout, &inventory, &manifest.asset_map)
+      })
+    } else {
+      DuplicateAssets::new()
+    };
+    let outputs = timed("copy", || {
+      self.prepare_collection_asset_sources(&manifest.asset_map, &duplicates)
+    })?;
+    apply_asset_outputs(layout, manifest, &outputs);
+    self.rehash_processed_assets(layout, &manifest.asset_map, &mut inventory)?;
+    Ok((duplicates, inventory))
+  }
+
+  fn prepare_collection_asset_sources(
+    &self,
+    asset_map: &BTreeMap<(String, String), AssetEntry>,
+    duplicates: &DuplicateAssets,
+  ) -> BuildResult<AssetOutputs> {
     let mirror_root = &self.context.asset_mirror_dir;
-    let mut desired_relatives = BTreeSet::new();
     let mut available_assets = Vec::new();
+    let mut duplicate_assets = Vec::new();
 
-    for entry in asset_map.values() {
-      let source_path = entry.source_path(self.context.collections_dir);
+    for (key, entry) in asset_map {
+      let source_path = entry.source_path(&self.context.collections_dir);
       if !source_path.exists() {
         continue;
       }
-      let relative_path = entry.mirror_relative_path();
-      desired_relatives.insert(relative_path.clone());
-      available_assets.push((source_path, relative_path));
+      match duplicates
+        .get(key)
+        .filter(|canonical| asset_map.contains_key(*canonical))
+      {
+        Some(canonical) => duplicate_assets.push((key, entry, canonical)),
+        None => available_assets.push((key, entry, source_path)),
+      }
     }
 
     if !mirror_root.exists() {
       fs::create_dir_all(mirror_root)?;
     }
 
-    prune_mirror_tree(mirror_root, &desired_relatives)?;
+    let mut outputs = AssetOutputs::new();
+    let mut desired_relatives = BTreeSet::new();
+    for (key, entry, source) in available_assets {
+      let destination = mirror_root.join(entry.mirror_relative_path());
+      if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent)?;
+      }
+      let output = self.install_processed_asset(&source, &destination, entry)?;
+      let relative =
+        PathBuf::from(&entry.collection_id).join(output.as_deref().unwrap_or(&entry.relative_path));
+      desired_relatives.insert(relative);
+      if let Some(output) = output {
+        outputs.insert(key.clone(), output);
+      }
+    }
 
-    for (source, relative) in available_assets {
+    // Duplicates link to the canonical mirror copy so identical content is stored once. When
+    // the canonical copy was processed, duplicates take over its file name.
+    for (key, entry, canonical) in duplicate_assets {
+      let canonical_entry = &asset_map[canonical];
+      let canonical_output = outputs.get(canonical).cloned();
+      let canonical_relative = canonical_output
+        .as_deref()
+        .unwrap_or(&canonical_entry.relative_path);
+      let output = canonical_output.as_deref().map(|canonical_output| {
+        renamed_relative_path(
+          &entry.relative_path,
+          Path::new(canonical_output)
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default()
+            .as_ref(),
+        )
+      });
+      let relative =
+        PathBuf::from(&entry.collection_id).join(output.as_deref().unwrap_or(&entry.relative_path));
       let destination = mirror_root.join(&relative);
       if let Some(parent) = destination.parent() {
         fs::create_dir_all(parent)?;
       }
+      install_collection_asset(
+        &mirror_root
+          .join(&canonical_entry.collection_id)
+          .join(canonical_relative),
+        &destination,
+      )?;
+      desired_relatives.insert(relative);
+      if let Some(output) = output {
+        outputs.insert(key.clone(), output);
+      }
+    }
+
+    prune_mirror_tree(mirror_root, &desired_relatives)?;
 
-      install_collection_asset(&source, &destination)?;
+    Ok(outputs)
+  }
+
+  /// Install one asset through the registered processors, falling back to linking or copying.
+  ///
+  /// Returns the output path relative to the collection when a processor wrote the asset.
+  fn install_processed_asset(
+    &self,
+    source: &Path,
+    destination: &Path,
+    entry: &AssetEntry,
+  ) -> BuildResult<Option<String>> {
+    if !self.processors.is_empty() {
+      // Never let a processor write through a hard link into the authored source.
+      if fs::symlink_metadata(destination).is_ok_and(|metadata| !metadata.is_dir()) {
+        fs::remove_file(destination)?;
+      }
+      for processor in &self.processors {
+        match processor.process(source, destination, entry)? {
+          AssetOutcome::Unchanged => continue,
+          AssetOutcome::Written => return Ok(Some(entry.relative_path.clone())),
+          AssetOutcome::Renamed(file_name) => {
+            if file_name.is_empty()
+              || file_name == "."
+              || file_name == ".."
+              || file_name.contains(['/', '\\'])
+            {
+              return Err(
+                format!(
+                  "asset processor returned invalid file name `{file_name}` for {}",
+                  source.display()
+                )
+                .into(),
+              );
+            }
+            return Ok(Some(renamed_relative_path(
+              &entry.relative_path,
+              &file_name,
+            )));
+          }
+        }
+      }
     }
 
+    install_collection_asset(source, destination)?;
+    Ok(None)
+  }
+
+  /// Hash processed assets from the mirror, since they no longer match their authored source.
+  ///
+  /// Components still named after the authored path take the processor's output path.
+  fn rehash_processed_assets(
+    &self,
+    layout: &OfflineProjectLayout,
+    asset_map: &BTreeMap<(String, String), AssetEntry>,
+    inventory: &mut AssetInventory,
+  ) -> BuildResult<()> {
+    for entry in asset_map
+      .values()
+      .filter(|entry| entry.output_path.is_some())
+    {
+      let bundle_path =
+        make_offline_asset_path(layout, &entry.collection_id, entry.output_relative_path());
+      let source_path = make_offline_asset_path(layout, &entry.collection_id, &entry.relative_path);
+      let Some(component) = inventory
+        .components
+        .iter_mut()
+        .find(|component| component.name == bundle_path || component.name == source_path)
+      else {
+        continue;
+      };
+      component.name.clone_from(&bundle_path);
+      component.bom_ref = bundle_path;
+      let (size, digest) = file_digest(
+        &self
+          .context
+          .asset_mirror_dir
+          .join(entry.mirror_relative_path()),
+      )?;
+      for hash in &mut component.hashes {
+        hash.content = digest.clone();
+      }
+      for property in &mut component.properties {
+        if property.name == "offline:size" {
+          property.value = size.to_string();
+        }
+      }
+    }
+    inventory.components.sort_by(|a, b| a.name.cmp(&b.name));
     Ok(())
   }
 }
 
+/// Record processor output paths on the manifest and rewrite bundle paths that changed.
+fn apply_asset_outputs(
+  layout: &OfflineProjectLayout,
+  manifest: &mut ManifestGenerationResult,
+  outputs: &AssetOutputs,
+) {
+  let mut renamed = BTreeMap::new();
+  for (key, output) in outputs {
+    let Some(entry) = manifest.asset_map.get_mut(key) else {
+      continue;
+    };
+    if *output != entry.relative_path {
+      renamed.insert(
+        make_offline_asset_path(layout, &entry.collection_id, &entry.relative_path),
+        make_offline_asset_path(layout, &entry.collection_id, output),
+      );
+    }
+    entry.output_path = Some(output.clone());
+  }
+  if renamed.is_empty() {
+    return;
+  }
+
+  let rename = |path: &String| renamed.get(path).cloned().unwrap_or_else(|| path.clone());
+  for entry in &mut manifest.offline_entries {
+    entry.asset_paths = entry.asset_paths.iter().map(rename).collect();
+    entry.priority_assets = entry.priority_assets.iter().map(rename).collect();
+    entry.video_posters = entry
+      .video_posters
+      .iter()
+      .map(|(video, poster)| (rename(video), rename(poster)))
+      .collect();
+    for document in &mut entry.documents {
+      document.asset_paths = document.asset_paths.iter().map(rename).collect();
+    }
+  }
+  for intro in manifest
+    .collection_catalog
+    .iter_mut()
+    .filter_map(|record| record.intro.as_mut())
+  {
+    intro.asset_paths = intro.asset_paths.iter().map(rename).collect();
+  }
+  manifest.hero_asset_paths = manifest.hero_asset_paths.iter().map(rename).collect();
+  manifest.resolve_catalog_heroes(layout);
+}
+
+/// Replace the file name of a collection-relative path.
+fn renamed_relative_path(relative_path: &str, file_name: &str) -> String {
+  match relative_path.rsplit_once('/') {
+    Some((parent, _)) => format!("{parent}/{file_name}"),
+    None => file_name.to_string(),
+  }
+}
+
 fn append_collection_metadata_paths(
   collections_dir: &Path,
   layout: &OfflineProjectLayout,
@@ -226,7 +1238,7 @@ fn append_collection_metadata_paths(
   if let Ok(entries) = fs::read_dir(collections_dir) {
     for entry in entries.flatten() {
       if entry.file_type().is_ok_and(|ft| ft.is_dir()) {
-        let metadata = entry.path().join(&layout.collection_metadata_file);
+        let metadata = layout.collection_metadata_path(&entry.path());
         if metadata.exists() {
           rerun_paths.push(metadata);
         }
@@ -309,33 +1321,43 @@ fn install_collection_asset(source: &Path, destination: &Path) -> std::io::Resul
   }
 }
 
-type OfflineAssetTables = (String, String);
-
-type OfflineEntryTables = (String, OfflineAssetTables);
-
 type AssetMatchTables = (Vec<String>, Vec<String>);
 
+/// Collection-relative output paths of assets written by an [`AssetProcessor`].
+type AssetOutputs = BTreeMap<(String, String), String>;
+
 fn render_collection_assets(
   asset_map: &BTreeMap<(String, String), AssetEntry>,
+  duplicates: &DuplicateAssets,
   mirror_prefix: &str,
+  strategy: &AssetCodegenStrategy,
 ) -> AssetMatchTables {
   let mut asset_definitions = Vec::new();
   let mut asset_match_entries = Vec::new();
 
-  for entry in asset_map.values() {
+  for (key, entry) in asset_map {
+    let mirror_entry = duplicates
+      .get(key)
+      .and_then(|canonical| asset_map.get(canonical))
+      .unwrap_or(entry);
+    let (mirror_collection, mirror_relative) = (
+      &mirror_entry.collection_id,
+      mirror_entry.output_relative_path(),
+    );
     let mirror_path = format!(
       "{}/{}/{}",
       mirror_prefix.trim_end_matches('/'),
-      entry.collection_id,
-      entry.relative_path
+      mirror_collection,
+      mirror_relative
     );
     let mirror_literal = serde_json::to_string(&mirror_path).unwrap();
     let collection_literal = serde_json::to_string(&entry.collection_id).unwrap();
     let relative_literal = serde_json::to_string(&entry.relative_path).unwrap();
 
     asset_definitions.push(format!(
-      "static {}: Asset = dioxus::prelude::asset!({});",
-      entry.const_name, mirror_literal
+      "static {}: Asset = {};",
+      entry.const_name,
+      strategy.expression(&mirror_literal)
     ));
     asset_match_entries.push(format!(
       "        ({}, {}) => Some(&{}),",
@@ -354,72 +1376,285 @@ fn render_hero_match_section(hero_match_arms: &[String]) -> String {
   }
 }
 
-fn render_offline_entry_tables(
+/// Write the `offline_manifest.rs` module body, streaming the entry match arms (and with them
+/// any embedded markdown bodies) straight into `writer`.
+fn write_offline_manifest_code(
+  writer: &mut dyn Write,
+  options: &CodegenOptions,
   layout: &OfflineProjectLayout,
   offline_entries: &[OfflineEntryRecord],
   asset_map: &BTreeMap<(String, String), AssetEntry>,
-) -> OfflineEntryTables {
-  let mut entry_assets_statics = vec!["static OFFLINE_EMPTY_ASSETS: [&str; 0] = [];".to_string()];
-  let mut entry_match_arms = Vec::new();
-  let mut used_idents = BTreeSet::new();
+  entry_body_field: &str,
+  body_code: &str,
+) -> io::Result<()> {
+  let entry_fn = options.function_name("offline_entry");
+  let assets_fn = options.function_name("offline_entry_assets");
+  let offline_asset_fn = options.function_name("offline_collection_asset");
+  let document_fn = options.function_name("offline_entry_document");
+  let crate_vis = options.item_visibility("pub(crate)");
+  let pub_vis = options.item_visibility("pub");
+
+  write!(
+    writer,
+    r#"// Generated at build time for the offline-html feature
+use serde::{{Deserialize, Serialize}};
 
-  for entry in offline_entries {
-    let assets_ref = if entry.asset_paths.is_empty() {
-      "OFFLINE_EMPTY_ASSETS".to_string()
-    } else {
-      let ident = sanitize_entry_ident(&entry.collection_id, &entry.entry_id, &mut used_idents);
-      let asset_literals: Vec<String> = entry
-        .asset_paths
-        .iter()
-        .map(|path| serde_json::to_string(path).unwrap())
-        .collect();
-      entry_assets_statics.push(format!(
-        "static {ident}: [&str; {}] = [{}];",
-        entry.asset_paths.len(),
-        asset_literals.join(", ")
-      ));
-      ident
-    };
+#[derive(Clone)]
+pub struct OfflineEntry {{{entry_body_field}
+    pub assets: &'static [&'static str],
+    pub word_count: usize,
+    pub reading_minutes: usize,
+}}
+"#
+  )?;
+  let assets_refs = write_entry_asset_statics(writer, offline_entries)?;
+  write!(
+    writer,
+    r#"
 
-    let body_literal = serde_json::to_string(&entry.body).unwrap();
-    let collection_literal = serde_json::to_string(&entry.collection_id).unwrap();
-    let entry_literal = serde_json::to_string(&entry.entry_id).unwrap();
-    entry_match_arms.push(format!(
-      "        ({}, {}) => Some(OfflineEntry {{ body: {}, assets: &{} }}),",
-      collection_literal, entry_literal, body_literal, assets_ref
-    ));
+#[allow(dead_code)]
+{pub_vis}fn {entry_fn}(collection_id: &str, entry_id: &str) -> Option<OfflineEntry> {{
+    match (collection_id, entry_id) {{
+"#
+  )?;
+  for (entry, assets_ref) in offline_entries.iter().zip(&assets_refs) {
+    write!(
+      writer,
+      "        ({}, {}) => Some(OfflineEntry {{ ",
+      serde_json::to_string(&entry.collection_id)?,
+      serde_json::to_string(&entry.entry_id)?
+    )?;
+    if !options.data_shards {
+      writer.write_all(b"body: ")?;
+      serde_json::to_writer(&mut *writer, &entry.body)?;
+      writer.write_all(b", ")?;
+    }
+    writeln!(
+      writer,
+      "assets: &{assets_ref}, word_count: {}, reading_minutes: {} }}),",
+      entry.word_count, entry.reading_minutes
+    )?;
   }
+  write!(
+    writer,
+    r#"        _ => None,
+    }}
+}}
 
-  let entry_match_body = if entry_match_arms.is_empty() {
-    "        _ => None,".to_string()
-  } else {
-    format!("{}\n        _ => None,", entry_match_arms.join("\n"))
-  };
+{body_code}
+{crate_vis}fn {assets_fn}(collection_id: &str, entry_id: &str) -> Option<&'static [&'static str]> {{
+    {entry_fn}(collection_id, entry_id).map(|record| record.assets)
+}}
+
+// Supplementary markdown documents such as `notes.md`, keyed by file stem
+#[allow(dead_code, unreachable_patterns)]
+{crate_vis}fn {document_fn}(collection_id: &str, entry_id: &str, name: &str) -> Option<&'static str> {{
+    match (collection_id, entry_id, name) {{
+"#
+  )?;
+  for entry in offline_entries {
+    for document in &entry.documents {
+      write!(
+        writer,
+        "        ({}, {}, {}) => Some(",
+        serde_json::to_string(&entry.collection_id)?,
+        serde_json::to_string(&entry.entry_id)?,
+        serde_json::to_string(&document.name)?
+      )?;
+      serde_json::to_writer(&mut *writer, &document.body)?;
+      writer.write_all(b"),\n")?;
+    }
+  }
+  write!(
+    writer,
+    r#"        _ => None,
+    }}
+}}
 
-  let mut offline_asset_match_entries = Vec::new();
+#[allow(unreachable_patterns)]
+{crate_vis}fn {offline_asset_fn}(collection_id: &str, relative_path: &str) -> Option<&'static str> {{
+    match (collection_id, relative_path) {{
+"#
+  )?;
   for entry in asset_map.values() {
-    let offline_path = make_offline_asset_path(layout, &entry.collection_id, &entry.relative_path);
-    let literal = serde_json::to_string(&offline_path).unwrap();
-    let collection_literal = serde_json::to_string(&entry.collection_id).unwrap();
-    let relative_literal = serde_json::to_string(&entry.relative_path).unwrap();
-    offline_asset_match_entries.push(format!(
+    let offline_path =
+      make_offline_asset_path(layout, &entry.collection_id, entry.output_relative_path());
+    writeln!(
+      writer,
       "        ({}, {}) => Some({}),",
-      collection_literal, relative_literal, literal
+      serde_json::to_string(&entry.collection_id)?,
+      serde_json::to_string(&entry.relative_path)?,
+      serde_json::to_string(&offline_path)?
+    )?;
+  }
+  write!(
+    writer,
+    r#"        _ => None,
+    }}
+}}
+"#
+  )
+}
+
+/// Write the static asset lists of the offline entries, returning the static each entry uses.
+fn write_entry_asset_statics(
+  writer: &mut dyn Write,
+  offline_entries: &[OfflineEntryRecord],
+) -> io::Result<Vec<String>> {
+  writer.write_all(b"static OFFLINE_EMPTY_ASSETS: [&str; 0] = [];")?;
+  let mut used_idents = BTreeSet::new();
+  let mut assets_refs = Vec::with_capacity(offline_entries.len());
+  for entry in offline_entries {
+    if entry.asset_paths.is_empty() {
+      assets_refs.push("OFFLINE_EMPTY_ASSETS".to_string());
+      continue;
+    }
+    let ident = sanitize_entry_ident(&entry.collection_id, &entry.entry_id, &mut used_idents);
+    let asset_literals = entry
+      .asset_paths
+      .iter()
+      .map(serde_json::to_string)
+      .collect::<Result<Vec<_>, _>>()?;
+    write!(
+      writer,
+      "\n\nstatic {ident}: [&str; {}] = [{}];",
+      entry.asset_paths.len(),
+      asset_literals.join(", ")
+    )?;
+    assets_refs.push(ident);
+  }
+  Ok(assets_refs)
+}
+
+/// Write one `<collection>.json` object of entry bodies keyed by entry id, and a `.js` script
+/// registering the same object, per collection into `dir`.
+///
+/// Returns the collection ids with the file stem of their shards. Stale shards are left to the
+/// next mirror pruning, since profiles built from one mirror share the directory.
+fn write_data_shards(
+  dir: &Path,
+  offline_entries: &[OfflineEntryRecord],
+) -> BuildResult<Vec<(String, String)>> {
+  let mut collections: BTreeMap<&str, BTreeMap<&str, &str>> = BTreeMap::new();
+  for entry in offline_entries {
+    collections
+      .entry(&entry.collection_id)
+      .or_default()
+      .insert(&entry.entry_id, &entry.body);
+  }
+
+  fs::create_dir_all(dir)?;
+  let mut shards = Vec::with_capacity(collections.len());
+  for (collection_id, bodies) in collections {
+    let stem = collection_id.replace(['/', '\\'], "~");
+    let json = serde_json::to_string(&bodies)?;
+    let script = format!(
+      "(globalThis.__offlineDataShards ??= {{}})[{}] = {json};\n",
+      serde_json::to_string(collection_id)?
+    );
+    fs::write(dir.join(format!("{stem}.json")), &json)?;
+    fs::write(dir.join(format!("{stem}.js")), script)?;
+    shards.push((collection_id.to_string(), stem));
+  }
+  Ok(shards)
+}
+
+/// Script run through `document::eval` to load a shard: it receives the JSON and script URLs
+/// and the collection id, fetches the JSON unless the page was opened from `file://` and
+/// otherwise, or when fetching fails, appends the script to the page.
+const DATA_SHARD_LOADER: &str = r#"const [json, script, id] = await dioxus.recv();
+const shards = (globalThis.__offlineDataShards ??= {});
+if (!shards[id] && location.protocol !== "file:") {
+  try {
+    const response = await fetch(json);
+    if (response.ok) shards[id] = await response.json();
+  } catch (_) {}
+}
+if (!shards[id]) {
+  await new Promise((resolve, reject) => {
+    const element = document.createElement("script");
+    element.src = script;
+    element.onload = resolve;
+    element.onerror = reject;
+    document.head.appendChild(element);
+  });
+}
+return shards[id] ?? {};
+"#;
+
+/// Shard assets, their lookup and the asynchronous, cached `offline_entry_body`.
+fn render_data_shard_code(
+  shards: &[(String, String)],
+  mirror_prefix: &str,
+  options: &CodegenOptions,
+) -> String {
+  let shard_fn = options.function_name("offline_data_shard");
+  let body_fn = options.function_name("offline_entry_body");
+  let crate_vis = options.item_visibility("pub(crate)");
+  let strategy = &options.asset_strategy;
+  let asset_type = strategy.asset_type();
+  let mut statics = Vec::new();
+  let mut arms = Vec::new();
+  for (index, (collection_id, stem)) in shards.iter().enumerate() {
+    for (suffix, extension) in [("JSON", "json"), ("JS", "js")] {
+      let path = format!(
+        "{}/{DATA_SHARD_DIR}/{stem}.{extension}",
+        mirror_prefix.trim_end_matches('/')
+      );
+      statics.push(format!(
+        "static OFFLINE_DATA_SHARD_{index}_{suffix}: {asset_type} = {};",
+        strategy.expression(&serde_json::to_string(&path).unwrap())
+      ));
+    }
+    arms.push(format!(
+      "        {} => Some((&OFFLINE_DATA_SHARD_{index}_JSON, &OFFLINE_DATA_SHARD_{index}_JS)),",
+      serde_json::to_string(collection_id).unwrap()
     ));
   }
+  arms.push("        _ => None,".to_string());
 
-  let offline_asset_match_body = if offline_asset_match_entries.is_empty() {
-    "        _ => None,".to_string()
-  } else {
-    format!(
-      "{}\n        _ => None,",
-      offline_asset_match_entries.join("\n")
-    )
-  };
+  format!(
+    r#"// Entry bodies live in per-collection JSON shards loaded on first use
+{}
+
+#[allow(unreachable_patterns)]
+fn {shard_fn}(collection_id: &str) -> Option<(&'static {asset_type}, &'static {asset_type})> {{
+    match collection_id {{
+{}
+    }}
+}}
 
-  (
-    entry_assets_statics.join("\n\n"),
-    (entry_match_body, offline_asset_match_body),
+type OfflineDataShard = std::rc::Rc<std::collections::HashMap<String, String>>;
+
+thread_local! {{
+    static OFFLINE_DATA_SHARDS: std::cell::RefCell<std::collections::HashMap<String, OfflineDataShard>> =
+        Default::default();
+}}
+
+const OFFLINE_DATA_SHARD_LOADER: &str = {};
+
+#[allow(dead_code)]
+{crate_vis}async fn {body_fn}(collection_id: &str, entry_id: &str) -> Option<String> {{
+    let cached = OFFLINE_DATA_SHARDS.with(|shards| shards.borrow().get(collection_id).cloned());
+    let shard = match cached {{
+        Some(shard) => shard,
+        None => {{
+            let (json, script) = {shard_fn}(collection_id)?;
+            let mut eval = dioxus::prelude::document::eval(OFFLINE_DATA_SHARD_LOADER);
+            eval.send((json.to_string(), script.to_string(), collection_id)).ok()?;
+            let shard: OfflineDataShard = std::rc::Rc::new(eval.join().await.ok()?);
+            OFFLINE_DATA_SHARDS.with(|shards| {{
+                shards.borrow_mut().insert(collection_id.to_string(), shard.clone())
+            }});
+            shard
+        }}
+    }};
+    shard.get(entry_id).cloned()
+}}
+"#,
+    statics.join("\n"),
+    arms.join("\n"),
+    serde_json::to_string(DATA_SHARD_LOADER).unwrap(),
   )
 }
 
@@ -483,6 +1718,624 @@ mod tests {
     Ok(())
   }
 
+  #[test]
+  fn catalog_keeps_or_resolves_localized_metadata() -> BuildResult<()> {
+    let temp = tempdir()?;
+    let manifest_dir = temp.path();
+    let collections_dir = manifest_dir.join("programs");
+    fs::create_dir_all(collections_dir.join("P001"))?;
+    fs::write(
+      collections_dir.join("P001/collection.json"),
+      r#"{"title":{"en":"Safety","fr":"Sécurité"},"description":"Site rules"}"#,
+    )?;
+    let context = || {
+      OfflineBuildContext::builder()
+        .manifest_dir(manifest_dir)
+        .collections_dir(&collections_dir)
+        .build()
+    };
+
+    let artifacts = OfflineBuilder::new(context()?).build(&crate::IncludeAll)?;
+    let catalog =
+      crate::runtime::parse_collection_catalog(&artifacts.collection_catalog_json.read()?)?;
+    let meta = &catalog.collections[0].meta;
+    assert_eq!(catalog.language, None);
+    assert_eq!(meta.title.get("fr-CA"), "Sécurité");
+    assert_eq!(meta.title.get("de"), "Safety");
+    assert_eq!(meta.description.as_ref().unwrap().get("fr"), "Site rules");
+
+    let artifacts = OfflineBuilder::new(context()?)
+      .with_catalog_language("fr")
+      .build(&crate::IncludeAll)?;
+    let catalog =
+      crate::runtime::parse_collection_catalog(&artifacts.collection_catalog_json.read()?)?;
+    assert_eq!(catalog.language.as_deref(), Some("fr"));
+    assert_eq!(catalog.collections[0].meta.title, "Sécurité");
+    Ok(())
+  }
+
+  #[test]
+  fn build_profiles_narrows_shared_scan_per_profile() -> BuildResult<()> {
+    let temp = tempdir()?;
+    let manifest_dir = temp.path();
+    let collections_dir = manifest_dir.join("programs");
+    for id in ["P001", "P002"] {
+      let collection = collections_dir.join(id);
+      fs::create_dir_all(collection.join("assets"))?;
+      fs::write(
+        collection.join("collection.json"),
+        r#"{"title":"Program","heroImage":"/assets/cover.png"}"#,
+      )?;
+      fs::write(collection.join("assets/cover.png"), id)?;
+    }
+
+    let context = OfflineBuildContext::builder()
+      .manifest_dir(manifest_dir)
+      .collections_dir(&collections_dir)
+      .build()?;
+    let builder = OfflineBuilder::new(context);
+    let profiles = [
+      ("first", BTreeSet::from(["P001".to_string()])),
+      (
+        "all",
+        BTreeSet::from(["P001".to_string(), "P002".to_string()]),
+      ),
+    ];
+    let outputs = builder.build_profiles(&profiles)?;
+
+    assert_eq!(outputs.len(), 2);
+    assert!(
+      outputs[0]
+        .artifacts
+        .collection_catalog_json
+        .as_str()
+        .unwrap()
+        .contains("P001")
+    );
+    assert!(
+      !outputs[0]
+        .artifacts
+        .collection_catalog_json
+        .as_str()
+        .unwrap()
+        .contains("P002")
+    );
+    assert!(
+      !outputs[0]
+        .artifacts
+        .asset_table_code
+        .as_str()
+        .unwrap()
+        .contains("\"P002\"")
+    );
+    assert!(
+      outputs[1]
+        .artifacts
+        .collection_catalog_json
+        .as_str()
+        .unwrap()
+        .contains("P002")
+    );
+
+    let first_root = outputs[0].layout.bundle_root(manifest_dir);
+    assert!(first_root.ends_with("first"));
+    assert!(first_root.join("offline_manifest.json").is_file());
+    let provenance = crate::bundle::provenance::read_version_file(&first_root)?;
+    let manifest =
+      crate::OfflineManifestIndex::parse(&outputs[0].artifacts.offline_manifest_json.read()?)?;
+    assert_eq!(manifest.provenance(), Some(&provenance));
+    assert!(
+      builder
+        .context
+        .asset_mirror_dir
+        .join("P002/assets/cover.png")
+        .exists()
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn scan_returns_manifest_without_writing() -> BuildResult<()> {
+    let temp = tempdir()?;
+    let manifest_dir = temp.path();
+    let collections_dir = manifest_dir.join("programs");
+    let collection = collections_dir.join("P001");
+    fs::create_dir_all(collection.join("assets"))?;
+    fs::write(
+      collection.join("collection.json"),
+      r#"{"title":"Program","heroImage":"/assets/cover.png"}"#,
+    )?;
+    fs::write(collection.join("assets/cover.png"), "cover")?;
+
+    let context = OfflineBuildContext::builder()
+      .manifest_dir(manifest_dir)
+      .collections_dir(&collections_dir)
+      .build()?;
+    let mirror_dir = context.asset_mirror_dir.clone();
+    let manifest = OfflineBuilder::new(context).scan(&crate::IncludeAll)?;
+
+    assert_eq!(manifest.collection_catalog.len(), 1);
+    assert_eq!(manifest.collection_catalog[0].id, "P001");
+    assert!(
+      manifest
+        .hero_asset_paths
+        .contains("programs/P001/assets/cover.png")
+    );
+    assert!(!mirror_dir.exists());
+
+    Ok(())
+  }
+
+  #[test]
+  fn data_shards_replace_embedded_bodies() -> BuildResult<()> {
+    let temp = tempdir()?;
+    let manifest_dir = temp.path();
+    let collections_dir = manifest_dir.join("programs");
+    let collection = collections_dir.join("P001");
+    fs::create_dir_all(collection.join("intro"))?;
+    fs::write(collection.join("collection.json"), r#"{"title":"Program"}"#)?;
+    fs::write(
+      collection.join("intro/index.md"),
+      "# Intro\n\nStow the lines.\n",
+    )?;
+
+    let context = OfflineBuildContext::builder()
+      .manifest_dir(manifest_dir)
+      .collections_dir(&collections_dir)
+      .build()?;
+    let shard_dir = context.asset_mirror_dir.join(DATA_SHARD_DIR);
+    let artifacts = OfflineBuilder::new(context)
+      .with_codegen_options(CodegenOptions {
+        data_shards: true,
+        offline_manifest_feature: Some("offline-html".into()),
+        ..Default::default()
+      })
+      .build(&crate::IncludeAll)?;
+
+    let code = artifacts.offline_manifest_code.as_str().unwrap();
+    assert!(!code.contains("Stow the lines."));
+    assert!(!code.contains("pub body"));
+    assert!(code.contains("_offline_data/P001.json\");"));
+    assert!(code.contains("pub(crate) async fn offline_entry_body("));
+    assert!(code.contains(
+      "#[cfg(not(feature = \"offline-html\"))]\n#[allow(dead_code)]\npub(crate) async fn offline_entry_body("
+    ));
+    let shard: BTreeMap<String, String> =
+      serde_json::from_str(&fs::read_to_string(shard_dir.join("P001.json"))?)?;
+    assert!(shard["intro"].contains("Stow the lines."));
+    assert!(
+      fs::read_to_string(shard_dir.join("P001.js"))?
+        .starts_with("(globalThis.__offlineDataShards ??= {})[\"P001\"] = {\"intro\":")
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn scan_uses_configured_excerpt_length() -> BuildResult<()> {
+    let temp = tempdir()?;
+    let manifest_dir = temp.path();
+    let collections_dir = manifest_dir.join("programs");
+    let collection = collections_dir.join("P001");
+    fs::create_dir_all(collection.join("intro"))?;
+    fs::write(collection.join("collection.json"), r#"{"title":"Program"}"#)?;
+    fs::write(
+      collection.join("intro/index.md"),
+      "# Intro\n\nCheck every life jacket before leaving port.\n",
+    )?;
+
+    let context = OfflineBuildContext::builder()
+      .manifest_dir(manifest_dir)
+      .collections_dir(&collections_dir)
+      .build()?;
+    let builder = OfflineBuilder::new(context);
+    let full = builder.scan(&crate::IncludeAll)?;
+    let short = builder.with_excerpt_length(20).scan(&crate::IncludeAll)?;
+
+    assert_eq!(
+      full.collection_catalog[0].entries[0].excerpt.as_deref(),
+      Some("Check every life jacket before leaving port.")
+    );
+    assert_eq!(
+      short.collection_catalog[0].entries[0].excerpt.as_deref(),
+      Some("Check every life…")
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn intermediate_json_round_trips() -> BuildResult<()> {
+    let temp = tempdir()?;
+    let manifest_dir = temp.path();
+    let collections_dir = manifest_dir.join("programs");
+    let collection = collections_dir.join("P001");
+    fs::create_dir_all(collection.join("intro/assets"))?;
+    fs::write(collection.join("collection.json"), r#"{"title":"Program"}"#)?;
+    fs::write(
+      collection.join("intro/index.md"),
+      "---\ntitle: Intro\n---\n![Diagram](assets/diagram.png)\n",
+    )?;
+    fs::write(collection.join("intro/assets/diagram.png"), "png")?;
+
+    let context = OfflineBuildContext::builder()
+      .manifest_dir(manifest_dir)
+      .collections_dir(&collections_dir)
+      .build()?;
+    let builder = OfflineBuilder::new(context);
+    let json = builder.export_intermediate_json(&crate::IncludeAll)?;
+    let restored: ManifestGenerationResult = serde_json::from_str(&json)?;
+    let original = builder.scan(&crate::IncludeAll)?;
+
+    assert!(json.contains("\"relative_path\": \"intro/assets/diagram.png\""));
+    assert_eq!(
+      restored.asset_map.keys().collect::<Vec<_>>(),
+      original.asset_map.keys().collect::<Vec<_>>()
+    );
+    assert_eq!(restored.offline_entries.len(), 1);
+    assert_eq!(
+      restored.offline_entries[0].asset_paths,
+      original.offline_entries[0].asset_paths
+    );
+
+    Ok(())
+  }
+
+  #[cfg(feature = "templates")]
+  #[test]
+  fn templates_replace_generated_modules() -> BuildResult<()> {
+    let temp = tempdir()?;
+    let manifest_dir = temp.path();
+    let collections_dir = manifest_dir.join("programs");
+    let collection = collections_dir.join("P001");
+    fs::create_dir_all(collection.join("assets"))?;
+    fs::write(
+      collection.join("collection.json"),
+      r#"{"title":"Program","heroImage":"/assets/cover.png"}"#,
+    )?;
+    fs::write(collection.join("assets/cover.png"), "cover")?;
+
+    let context = OfflineBuildContext::builder()
+      .manifest_dir(manifest_dir)
+      .collections_dir(&collections_dir)
+      .build()?;
+    let artifacts = OfflineBuilder::new(context)
+      .with_templates(CodegenTemplates {
+        asset_table: Some(
+          "{% for hero in heroes %}hero!({{ hero.collection_id | rust_str }}, {{ hero.const_name }});\n{% endfor %}"
+            .into(),
+        ),
+        offline_manifest: None,
+      })
+      .build(&crate::IncludeAll)?;
+
+    assert!(
+      artifacts
+        .asset_table_code
+        .as_str()
+        .unwrap()
+        .starts_with("hero!(\"P001\", ")
+    );
+    assert!(
+      artifacts
+        .offline_manifest_code
+        .as_str()
+        .unwrap()
+        .contains("pub fn offline_entry(")
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn feature_gated_manifest_code_has_fallback() -> BuildResult<()> {
+    let temp = tempdir()?;
+    let manifest_dir = temp.path();
+    let collections_dir = manifest_dir.join("programs");
+    fs::create_dir_all(collections_dir.join("P001"))?;
+    fs::write(
+      collections_dir.join("P001/collection.json"),
+      r#"{"title":"Program"}"#,
+    )?;
+
+    let context = OfflineBuildContext::builder()
+      .manifest_dir(manifest_dir)
+      .collections_dir(&collections_dir)
+      .build()?;
+    let artifacts = OfflineBuilder::new(context)
+      .with_codegen_options(CodegenOptions {
+        function_prefix: "docs_".into(),
+        offline_manifest_feature: Some("offline-html".into()),
+        ..Default::default()
+      })
+      .build(&crate::IncludeAll)?;
+
+    let code = artifacts.offline_manifest_code.as_str().unwrap();
+    assert!(code.contains(
+      "#[cfg(feature = \"offline-html\")]\n#[allow(dead_code)]\npub fn docs_offline_entry("
+    ));
+    assert!(code.contains(
+      "#[cfg(not(feature = \"offline-html\"))]\n#[allow(dead_code)]\npub fn docs_offline_entry("
+    ));
+    assert!(
+      !artifacts
+        .asset_table_code
+        .as_str()
+        .unwrap()
+        .contains("#[cfg(")
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn artifacts_stream_into_the_artifact_dir() -> BuildResult<()> {
+    let temp = tempdir()?;
+    let manifest_dir = temp.path();
+    let collections_dir = manifest_dir.join("programs");
+    fs::create_dir_all(collections_dir.join("P001/intro"))?;
+    fs::write(
+      collections_dir.join("P001/collection.json"),
+      r#"{"title":"Program"}"#,
+    )?;
+    fs::write(collections_dir.join("P001/intro/index.md"), "# Intro\n")?;
+
+    let context = || {
+      OfflineBuildContext::builder()
+        .manifest_dir(manifest_dir)
+        .collections_dir(&collections_dir)
+        .build()
+    };
+    let options = CodegenOptions {
+      offline_manifest_module: Some("offline".into()),
+      offline_manifest_feature: Some("offline-html".into()),
+      ..Default::default()
+    };
+    let inline = OfflineBuilder::new(context()?)
+      .with_codegen_options(options.clone())
+      .with_build_timestamp(1_700_000_000)
+      .build(&crate::IncludeAll)?;
+    let artifact_dir = manifest_dir.join("target/artifacts");
+    let streamed = OfflineBuilder::new(context()?)
+      .with_codegen_options(options)
+      .with_build_timestamp(1_700_000_000)
+      .with_artifact_dir(&artifact_dir)
+      .build(&crate::IncludeAll)?;
+
+    assert_eq!(
+      streamed.offline_manifest_json.path(),
+      Some(artifact_dir.join("offline_manifest.json").as_path())
+    );
+    for (inline, streamed) in [
+      (&inline.asset_table_code, &streamed.asset_table_code),
+      (
+        &inline.offline_manifest_code,
+        &streamed.offline_manifest_code,
+      ),
+      (
+        &inline.offline_manifest_json,
+        &streamed.offline_manifest_json,
+      ),
+      (
+        &inline.collection_catalog_json,
+        &streamed.collection_catalog_json,
+      ),
+      (&inline.catalog_module_js, &streamed.catalog_module_js),
+      (&inline.catalog_types_ts, &streamed.catalog_types_ts),
+      (&inline.credits_html, &streamed.credits_html),
+    ] {
+      assert!(streamed.as_str().is_none());
+      assert_eq!(inline.read()?, streamed.read()?);
+    }
+    let mut copied = Vec::new();
+    streamed.offline_manifest_code.write_to(&mut copied)?;
+    assert!(String::from_utf8(copied)?.contains("pub mod offline {"));
+
+    Ok(())
+  }
+
+  #[test]
+  fn asset_processors_can_rename_outputs() -> BuildResult<()> {
+    let temp = tempdir()?;
+    let manifest_dir = temp.path();
+    let collections_dir = manifest_dir.join("programs");
+    for id in ["P001", "P002"] {
+      let collection = collections_dir.join(id);
+      fs::create_dir_all(collection.join("assets"))?;
+      fs::write(
+        collection.join("collection.json"),
+        r#"{"title":"Program","heroImage":"/assets/cover.png"}"#,
+      )?;
+      fs::write(collection.join("assets/cover.png"), "png")?;
+    }
+
+    let context = OfflineBuildContext::builder()
+      .manifest_dir(manifest_dir)
+      .collections_dir(&collections_dir)
+      .build()?;
+    let mirror_dir = context.asset_mirror_dir.clone();
+    let artifacts = OfflineBuilder::new(context)
+      .with_asset_processor(
+        |source: &Path, destination: &Path, _: &AssetEntry| -> BuildResult<AssetOutcome> {
+          if source.extension().is_none_or(|ext| ext != "png") {
+            return Ok(AssetOutcome::Unchanged);
+          }
+          fs::write(destination.with_extension("webp"), "webp")?;
+          Ok(AssetOutcome::Renamed("cover.webp".into()))
+        },
+      )
+      .build(&crate::IncludeAll)?;
+
+    assert_eq!(
+      fs::read_to_string(collections_dir.join("P001/assets/cover.png"))?,
+      "png"
+    );
+    for id in ["P001", "P002"] {
+      assert_eq!(
+        fs::read_to_string(mirror_dir.join(id).join("assets/cover.webp"))?,
+        "webp"
+      );
+      assert!(!mirror_dir.join(id).join("assets/cover.png").exists());
+    }
+    assert!(
+      artifacts
+        .offline_manifest_json
+        .as_str()
+        .unwrap()
+        .contains(r#""programs/P002/assets/cover.webp""#)
+    );
+    assert!(
+      artifacts
+        .offline_manifest_json
+        .as_str()
+        .unwrap()
+        .contains(r#""programs/P002/assets/cover.webp": 4"#)
+    );
+    assert!(
+      artifacts
+        .offline_manifest_code
+        .as_str()
+        .unwrap()
+        .contains(r#"("P001", "assets/cover.png") => Some("programs/P001/assets/cover.webp"),"#)
+    );
+    assert!(
+      artifacts
+        .asset_table_code
+        .as_str()
+        .unwrap()
+        .contains("/P001/assets/cover.webp\")")
+    );
+    assert!(
+      !artifacts
+        .asset_inventory_json
+        .as_str()
+        .unwrap()
+        .contains("8f8cbb7dcf46e0bc7d53265749a6c17d116093a6ba95e442764060c76fd4a86c")
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn build_mirrors_identical_assets_once() -> BuildResult<()> {
+    let temp = tempdir()?;
+    let manifest_dir = temp.path();
+    let collections_dir = manifest_dir.join("programs");
+    for id in ["P001", "P002"] {
+      let collection = collections_dir.join(id);
+      fs::create_dir_all(collection.join("assets"))?;
+      fs::write(
+        collection.join("collection.json"),
+        r#"{"title":"Program","heroImage":"/assets/logo.png"}"#,
+      )?;
+      fs::write(collection.join("assets/logo.png"), "same logo")?;
+    }
+
+    let context = OfflineBuildContext::builder()
+      .manifest_dir(manifest_dir)
+      .collections_dir(&collections_dir)
+      .build()?;
+    let mirror_dir = context.asset_mirror_dir.clone();
+    let builder = OfflineBuilder::new(context);
+    let artifacts = builder.build(&crate::IncludeAll)?;
+    let manifest = artifacts.offline_manifest_json.as_str().unwrap();
+    assert!(!manifest.contains("asset_aliases"));
+    assert!(manifest.contains(r#""programs/P002/assets/logo.png": ""#));
+    assert!(!is_same_file(
+      mirror_dir.join("P001/assets/logo.png"),
+      mirror_dir.join("P002/assets/logo.png")
+    )?);
+
+    let artifacts = builder
+      .with_asset_deduplication(true)
+      .build(&crate::IncludeAll)?;
+    assert!(is_same_file(
+      mirror_dir.join("P001/assets/logo.png"),
+      mirror_dir.join("P002/assets/logo.png")
+    )?);
+    assert!(
+      !artifacts
+        .asset_table_code
+        .as_str()
+        .unwrap()
+        .contains("/P002/assets/logo.png")
+    );
+    assert!(
+      artifacts
+        .offline_manifest_json
+        .as_str()
+        .unwrap()
+        .contains(r#""programs/P002/assets/logo.png": "programs/P001/assets/logo.png""#)
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn target_filesystem_errors_fail_the_build() -> BuildResult<()> {
+    let temp = tempdir()?;
+    let manifest_dir = temp.path();
+    let collections_dir = manifest_dir.join("programs");
+    let collection = collections_dir.join("P001");
+    fs::create_dir_all(collection.join("assets"))?;
+    fs::write(
+      collection.join("collection.json"),
+      r#"{"title":"Program","heroImage":"/assets/cover:1.png"}"#,
+    )?;
+    fs::write(collection.join("assets/cover:1.png"), "cover")?;
+
+    let context = OfflineBuildContext::builder()
+      .manifest_dir(manifest_dir)
+      .collections_dir(&collections_dir)
+      .target_filesystem(crate::filesystem::FilesystemProfile {
+        filesystem: crate::filesystem::TargetFilesystem::Fat32,
+        mode: FilesystemCheckMode::Error,
+      })
+      .build()?;
+    let error = match OfflineBuilder::new(context).build(&crate::IncludeAll) {
+      Ok(_) => panic!("expected the FAT32 check to fail"),
+      Err(error) => error,
+    };
+    assert!(error.to_string().contains("cover:1.png"));
+
+    Ok(())
+  }
+
+  #[test]
+  fn build_reports_missing_mirror_assets() -> BuildResult<()> {
+    let temp = tempdir()?;
+    let manifest_dir = temp.path();
+    let collections_dir = manifest_dir.join("programs");
+    let collection = collections_dir.join("P001");
+    fs::create_dir_all(&collection)?;
+    fs::write(
+      collection.join("collection.json"),
+      r#"{"title":"Program","heroImage":"/assets/missing.png"}"#,
+    )?;
+
+    let context = OfflineBuildContext::builder()
+      .manifest_dir(manifest_dir)
+      .collections_dir(&collections_dir)
+      .build()?;
+    let error = match OfflineBuilder::new(context).build(&crate::IncludeAll) {
+      Ok(_) => panic!("expected the missing hero image to be reported"),
+      Err(error) => error,
+    };
+    let error = error
+      .downcast_ref::<MirrorConsistencyError>()
+      .expect("structured consistency error");
+    assert_eq!(error.missing.len(), 1);
+    assert_eq!(error.missing[0].collection_id, "P001");
+    assert_eq!(error.missing[0].entry_id, None);
+    assert_eq!(
+      error.missing[0].asset_path,
+      "programs/P001/assets/missing.png"
+    );
+
+    Ok(())
+  }
+
   #[test]
   fn install_collection_asset_reuses_existing_links() -> std::io::Result<()> {
     let temp = tempdir()?;
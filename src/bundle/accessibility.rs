@@ -0,0 +1,288 @@
+//! Accessibility audit of the generated HTML pages.
+//!
+//! [`audit_html_accessibility`] checks every HTML page below a bundle or site root, i.e. the
+//! patched index, entry-point pages, the launcher pages and any pre-rendered entry pages, for the
+//! WCAG 2.1 problems the bundler can introduce: a missing `lang`, a missing title, images without
+//! `alt` and injected theme colors below the contrast minimum.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use regex::Regex;
+
+use crate::report::{PageFinding, PageIssue};
+
+/// Minimum contrast ratio of text colors (WCAG 1.4.3).
+pub const TEXT_CONTRAST: f64 = 4.5;
+/// Minimum contrast ratio of other theme colors, e.g. borders and icons (WCAG 1.4.11).
+pub const NON_TEXT_CONTRAST: f64 = 3.0;
+
+/// Theme variables used as the background the other colors are checked against.
+const BACKGROUND_VARIABLES: [&str; 3] = ["background", "bg", "surface"];
+/// Theme variables containing one of these words are held to [`TEXT_CONTRAST`].
+const TEXT_VARIABLES: [&str; 3] = ["text", "foreground", "fg"];
+
+const AUDITED_EXTENSIONS: [&str; 2] = ["html", "htm"];
+
+/// Audit every HTML page below `root`, sorted by file.
+///
+/// Theme colors are read from the `<style id="offline-theme">` blocks injected by site patching
+/// and the launcher. Light colors are checked against the theme's `background`, `bg` or
+/// `surface` variable, or white; dark overrides only when the dark palette sets a background.
+pub fn audit_html_accessibility(root: &Path) -> Result<Vec<PageFinding>> {
+  let checks = PageChecks::new();
+  let mut findings = Vec::new();
+  let mut pending = vec![root.to_path_buf()];
+
+  while let Some(dir) = pending.pop() {
+    for entry in fs::read_dir(&dir).with_context(|| format!("failed to read {}", dir.display()))? {
+      let entry = entry?;
+      let path = entry.path();
+      if entry.file_type()?.is_dir() {
+        pending.push(path);
+        continue;
+      }
+      let audited = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| AUDITED_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()));
+      if !audited {
+        continue;
+      }
+
+      let bytes = fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+      let relative = path.strip_prefix(root).unwrap_or(&path);
+      for (issue, detail) in checks.run(&String::from_utf8_lossy(&bytes)) {
+        findings.push(PageFinding {
+          file: relative.to_path_buf(),
+          issue,
+          detail,
+        });
+      }
+    }
+  }
+
+  findings.sort_by(|a, b| a.file.cmp(&b.file).then(a.issue.cmp(&b.issue)));
+  Ok(findings)
+}
+
+struct PageChecks {
+  html: Regex,
+  lang: Regex,
+  title: Regex,
+  image: Regex,
+  alt: Regex,
+  src: Regex,
+  theme: Regex,
+  variable: Regex,
+}
+
+impl PageChecks {
+  fn new() -> Self {
+    Self {
+      html: Regex::new(r"(?i)<html\b([^>]*)>").expect("invalid html regex"),
+      lang: Regex::new(r#"(?i)\blang\s*=\s*(?:"\s*[^"\s]|'\s*[^'\s]|[^\s"'>])"#)
+        .expect("invalid lang regex"),
+      title: Regex::new(r"(?is)<title\b[^>]*>(.*?)</title>").expect("invalid title regex"),
+      image: Regex::new(r"(?is)<img\b([^>]*)>").expect("invalid img regex"),
+      alt: Regex::new(r"(?i)(?:^|\s)alt(?:\s*=|\s|/|$)").expect("invalid alt regex"),
+      src: Regex::new(r#"(?i)\bsrc\s*=\s*["']?([^"'\s>]+)"#).expect("invalid src regex"),
+      theme: Regex::new(r#"(?is)<style\b[^>]*\bid\s*=\s*["']offline-theme["'][^>]*>(.*?)</style>"#)
+        .expect("invalid theme regex"),
+      variable: Regex::new(r"--([\w-]+)\s*:\s*([^;}]+)").expect("invalid variable regex"),
+    }
+  }
+
+  fn run(&self, text: &str) -> Vec<(PageIssue, String)> {
+    let mut findings = Vec::new();
+    let has_lang = self
+      .html
+      .captures(text)
+      .is_some_and(|caps| self.lang.is_match(&caps[1]));
+    if !has_lang {
+      findings.push((
+        PageIssue::MissingLang,
+        "<html> has no lang attribute".into(),
+      ));
+    }
+    let has_title = self
+      .title
+      .captures(text)
+      .is_some_and(|caps| !caps[1].trim().is_empty());
+    if !has_title {
+      findings.push((PageIssue::MissingTitle, "page has no <title>".into()));
+    }
+    for caps in self.image.captures_iter(text) {
+      if self.alt.is_match(&caps[1]) {
+        continue;
+      }
+      let src = self
+        .src
+        .captures(&caps[1])
+        .map_or("<img>".to_string(), |src| src[1].to_string());
+      findings.push((PageIssue::MissingAlt, src));
+    }
+    for caps in self.theme.captures_iter(text) {
+      findings.extend(self.theme_contrast(&caps[1]));
+    }
+    findings
+  }
+
+  /// Low-contrast colors in a theme stylesheet rendered by
+  /// [`ThemePalette::stylesheet`](crate::theme::ThemePalette::stylesheet).
+  fn theme_contrast(&self, css: &str) -> Vec<(PageIssue, String)> {
+    let (light, dark) = css
+      .split_once("prefers-color-scheme: dark")
+      .unwrap_or((css, ""));
+    let mut findings = Vec::new();
+    for (variables, scheme) in [(light, ""), (dark, " (dark)")] {
+      let colors: BTreeMap<&str, &str> = self
+        .variable
+        .captures_iter(variables)
+        .map(|caps| {
+          let (_, [name, value]) = caps.extract();
+          (name, value.trim())
+        })
+        .collect();
+      let background = BACKGROUND_VARIABLES.iter().find_map(|name| {
+        colors
+          .get(name)
+          .and_then(|value| Some((*value, parse_color(value)?)))
+      });
+      let (background_value, background) = match background {
+        Some(background) => background,
+        None if scheme.is_empty() => ("#ffffff", [255, 255, 255]),
+        None => continue,
+      };
+      for (name, value) in &colors {
+        if BACKGROUND_VARIABLES.contains(name) {
+          continue;
+        }
+        let Some(color) = parse_color(value) else {
+          continue;
+        };
+        let required = if TEXT_VARIABLES.iter().any(|word| name.contains(word)) {
+          TEXT_CONTRAST
+        } else {
+          NON_TEXT_CONTRAST
+        };
+        let ratio = contrast_ratio(color, background);
+        if ratio < required {
+          findings.push((
+            PageIssue::LowContrast,
+            format!(
+              "--{name} {value} on {background_value}{scheme}: {ratio:.2}:1, needs {required}:1"
+            ),
+          ));
+        }
+      }
+    }
+    findings
+  }
+}
+
+/// sRGB channels of a `#rgb`, `#rrggbb` (optionally with alpha, which is ignored) or
+/// `rgb()`/`rgba()` color; other notations are not checked.
+fn parse_color(value: &str) -> Option<[u8; 3]> {
+  let value = value.trim();
+  if let Some(hex) = value.strip_prefix('#') {
+    let digits: Vec<u8> = hex
+      .chars()
+      .map(|c| c.to_digit(16).map(|digit| digit as u8))
+      .collect::<Option<_>>()?;
+    return match digits.len() {
+      3 | 4 => Some([digits[0] * 17, digits[1] * 17, digits[2] * 17]),
+      6 | 8 => Some([
+        digits[0] * 16 + digits[1],
+        digits[2] * 16 + digits[3],
+        digits[4] * 16 + digits[5],
+      ]),
+      _ => None,
+    };
+  }
+  let arguments = value
+    .strip_prefix("rgba(")
+    .or_else(|| value.strip_prefix("rgb("))?
+    .strip_suffix(')')?;
+  let channels: Vec<u8> = arguments
+    .split([',', ' ', '/'])
+    .filter(|part| !part.is_empty())
+    .take(3)
+    .map(|part| {
+      part
+        .parse::<f64>()
+        .ok()
+        .map(|c| c.clamp(0.0, 255.0).round() as u8)
+    })
+    .collect::<Option<_>>()?;
+  channels.try_into().ok()
+}
+
+/// WCAG contrast ratio between two sRGB colors, from 1 to 21.
+fn contrast_ratio(a: [u8; 3], b: [u8; 3]) -> f64 {
+  let (a, b) = (relative_luminance(a), relative_luminance(b));
+  (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+fn relative_luminance(color: [u8; 3]) -> f64 {
+  let [r, g, b] = color.map(|channel| {
+    let c = f64::from(channel) / 255.0;
+    if c <= 0.04045 {
+      c / 12.92
+    } else {
+      ((c + 0.055) / 1.055).powf(2.4)
+    }
+  });
+  0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::tempdir;
+
+  #[test]
+  fn reports_missing_lang_title_alt_and_low_contrast() -> Result<()> {
+    assert_eq!(parse_color("#0a84ff"), Some([10, 132, 255]));
+    assert_eq!(parse_color("rgb(250, 250, 250)"), Some([250, 250, 250]));
+    assert_eq!(parse_color("var(--brand)"), None);
+    assert!((contrast_ratio([0, 0, 0], [255, 255, 255]) - 21.0).abs() < 1e-9);
+
+    let dir = tempdir()?;
+    fs::create_dir_all(dir.path().join("site"))?;
+    fs::write(
+      dir.path().join("index.html"),
+      r#"<!DOCTYPE html><html lang="en"><head><title>Course</title></head><body>
+<img src="logo.svg" alt=""><img alt src="divider.svg"></body></html>"#,
+    )?;
+    fs::write(
+      dir.path().join("site/index.html"),
+      r#"<html><head><title> </title>
+<style id="offline-theme">:root{--brand:#ffdd00;--text:#767676;--muted:#949494;}@media (prefers-color-scheme: dark){:root{--background:#111;--text:#333;}}</style>
+</head><body><img src="cover.png"></body></html>"#,
+    )?;
+
+    let findings = audit_html_accessibility(dir.path())?;
+    let summary: Vec<String> = findings.iter().map(ToString::to_string).collect();
+    let page = Path::new("site").join("index.html");
+    assert_eq!(summary, [
+      format!(
+        "{}: missing-lang: <html> has no lang attribute",
+        page.display()
+      ),
+      format!("{}: missing-title: page has no <title>", page.display()),
+      format!("{}: missing-alt: cover.png", page.display()),
+      format!(
+        "{}: low-contrast: --brand #ffdd00 on #ffffff: 1.35:1, needs 3:1",
+        page.display()
+      ),
+      format!(
+        "{}: low-contrast: --text #333 on #111 (dark): 1.49:1, needs 4.5:1",
+        page.display()
+      ),
+    ]);
+    Ok(())
+  }
+}
@@ -0,0 +1,204 @@
+//! Copy mirrored collection assets into the bundled site root.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, anyhow};
+
+use crate::bundle::manifest::{OfflineManifest, resolve_site_root};
+use crate::project::OfflineProjectLayout;
+
+/// Files touched by [`assemble`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AssemblyReport {
+  /// Site directory the assets were copied into.
+  pub site_root: PathBuf,
+  /// Bundle paths copied from the mirror.
+  pub copied: Vec<String>,
+  /// Files below the collections directory that the manifest no longer references.
+  pub pruned: Vec<String>,
+}
+
+/// Place every asset listed in `manifest` at its bundle path below the resolved site root.
+///
+/// Manifest paths have the `make_offline_asset_path` form
+/// (`<collections_dir_name>/<collection>/<path>`) and are read from `mirror_dir/<collection>/<path>`,
+/// the asset mirror written by `OfflineBuilder`. Files below the site's collections directory
+/// that the manifest does not list are removed afterwards. The site root comes from
+/// [`resolve_site_root`], so relative bundle roots resolve against the current directory while
+/// an `output_root` is used as is.
+pub fn assemble(
+  layout: &OfflineProjectLayout,
+  manifest: &OfflineManifest,
+  mirror_dir: &Path,
+) -> Result<AssemblyReport> {
+  let (site_root, _) = resolve_site_root(layout, manifest);
+  let collections_prefix = format!("{}/", layout.collections_dir_name);
+
+  let expected: BTreeSet<&str> = manifest
+    .entries
+    .iter()
+    .flat_map(|entry| entry.asset_paths.iter())
+    .chain(&manifest.hero_assets)
+    .chain(manifest.asset_hashes.keys())
+    .chain(manifest.asset_aliases.keys())
+    .map(String::as_str)
+    .collect();
+
+  let mut report = AssemblyReport {
+    site_root: site_root.clone(),
+    ..AssemblyReport::default()
+  };
+  for path in &expected {
+    let Some(mirror_relative) = path.strip_prefix(&collections_prefix) else {
+      return Err(anyhow!(
+        "manifest asset {path} is outside of the `{}` directory",
+        layout.collections_dir_name
+      ));
+    };
+    let source = mirror_dir.join(mirror_relative);
+    let destination = site_root.join(path);
+    if let Some(parent) = destination.parent() {
+      fs::create_dir_all(parent)
+        .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    // Earlier runs or `link_duplicate_assets` may have left a hard link here; replace it
+    // instead of writing through it.
+    if destination.exists() {
+      fs::remove_file(&destination)
+        .with_context(|| format!("failed to remove {}", destination.display()))?;
+    }
+    fs::copy(&source, &destination).with_context(|| {
+      format!(
+        "failed to copy mirrored asset {} to {}",
+        source.display(),
+        destination.display()
+      )
+    })?;
+    report.copied.push(path.to_string());
+  }
+
+  let collections_root = site_root.join(&layout.collections_dir_name);
+  if collections_root.is_dir() {
+    prune_stale(
+      &collections_root,
+      &layout.collections_dir_name,
+      &expected,
+      &mut report.pruned,
+    )?;
+  }
+  Ok(report)
+}
+
+fn prune_stale(
+  dir: &Path,
+  bundle_path: &str,
+  expected: &BTreeSet<&str>,
+  pruned: &mut Vec<String>,
+) -> Result<()> {
+  let mut entries = fs::read_dir(dir)
+    .with_context(|| format!("failed to read {}", dir.display()))?
+    .collect::<std::io::Result<Vec<_>>>()?;
+  entries.sort_by_key(|entry| entry.file_name());
+
+  for entry in entries {
+    let path = entry.path();
+    let child = format!("{bundle_path}/{}", entry.file_name().to_string_lossy());
+    if entry.file_type()?.is_dir() {
+      prune_stale(&path, &child, expected, pruned)?;
+      if fs::read_dir(&path)?.next().is_none() {
+        fs::remove_dir(&path).with_context(|| format!("failed to remove {}", path.display()))?;
+      }
+    } else if !expected.contains(child.as_str()) {
+      fs::remove_file(&path).with_context(|| format!("failed to remove {}", path.display()))?;
+      pruned.push(child);
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bundle::manifest::OfflineEntry;
+  use crate::models::OFFLINE_MANIFEST_SCHEMA_VERSION;
+  use tempfile::tempdir;
+
+  fn layout(bundle_root: &Path) -> OfflineProjectLayout {
+    OfflineProjectLayout {
+      entry_assets_dir: "assets".into(),
+      entry_markdown_file: "index.md".into(),
+      collection_metadata_file: "collection.json".into(),
+      excluded_dir_name: "prod".into(),
+      excluded_path_fragment: "/prod/".into(),
+      collection_asset_literal_prefix: "/content/programs".into(),
+      offline_site_root: "site".into(),
+      collections_dir_name: "programs".into(),
+      offline_bundle_root: bundle_root.to_string_lossy().into_owned(),
+      output_root: None,
+      index_html_file: "index.html".into(),
+      target_dir: "target".into(),
+      offline_manifest_json: "offline_manifest.json".into(),
+      scan_max_depth: 32,
+      asset_extensions: Vec::new(),
+      excluded_extensions: Vec::new(),
+    }
+  }
+
+  fn write_file(path: &Path, contents: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, contents).unwrap();
+  }
+
+  #[test]
+  fn copies_manifest_assets_and_prunes_stale_files() {
+    let dir = tempdir().unwrap();
+    let mirror = dir.path().join("mirror");
+    write_file(&mirror.join("alpha/assets/cover.png"), "cover");
+    write_file(&mirror.join("alpha/assets/diagram.svg"), "svg");
+    write_file(&mirror.join("alpha/assets/unused.png"), "unused");
+
+    let bundle = dir.path().join("bundle");
+    let site = bundle.join("site");
+    write_file(&site.join("programs/beta/assets/old.png"), "old");
+    write_file(&site.join("index.html"), "<html></html>");
+
+    let manifest = OfflineManifest {
+      schema_version: OFFLINE_MANIFEST_SCHEMA_VERSION,
+      site_root: None,
+      hero_assets: vec!["programs/alpha/assets/cover.png".into()],
+      entries: vec![OfflineEntry {
+        collection_id: "alpha".into(),
+        entry_id: "intro".into(),
+        title: None,
+        section: None,
+        asset_paths: vec!["programs/alpha/assets/diagram.svg".into()],
+        headings: Default::default(),
+        priority_assets: Vec::new(),
+        video_posters: Default::default(),
+      }],
+      asset_hashes: Default::default(),
+      asset_aliases: Default::default(),
+      asset_sizes: Default::default(),
+      asset_modified: Default::default(),
+      provenance: None,
+    };
+
+    let report = assemble(&layout(&bundle), &manifest, &mirror).unwrap();
+
+    assert_eq!(report.site_root, site);
+    assert_eq!(report.copied, [
+      "programs/alpha/assets/cover.png",
+      "programs/alpha/assets/diagram.svg"
+    ]);
+    assert_eq!(report.pruned, ["programs/beta/assets/old.png"]);
+    assert_eq!(
+      fs::read_to_string(site.join("programs/alpha/assets/diagram.svg")).unwrap(),
+      "svg"
+    );
+    assert!(!site.join("programs/alpha/assets/unused.png").exists());
+    assert!(!site.join("programs/beta").exists());
+    assert!(site.join("index.html").exists());
+  }
+}
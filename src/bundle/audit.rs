@@ -0,0 +1,321 @@
+//! Audit of network references left in the final offline bundle.
+//!
+//! Every HTML, CSS and JavaScript file below the site root is scanned for absolute `http(s)://`
+//! URLs and for protocol-relative `//host/path` URLs in attributes and CSS `url()` values, where
+//! they cannot be confused with JavaScript comments. Each reference is classified so that policies can, for example, fail on web fonts and
+//! CDN scripts while merely reporting documentation links embedded in JavaScript strings.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, anyhow};
+use regex::Regex;
+
+use crate::bundle::site::{external_host, host_matches};
+
+/// Category of a network reference found in the bundle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum NetworkReferenceKind {
+  /// Web font files or font stylesheets.
+  Font,
+  /// Resources served from a content delivery network.
+  Cdn,
+  /// Pages embedded through `<iframe>`.
+  Iframe,
+  /// Remote JavaScript.
+  Script,
+  /// Remote stylesheets.
+  Stylesheet,
+  /// Any other URL, e.g. links or documentation URLs embedded in code.
+  Other,
+}
+
+/// Absolute URL found in a bundled file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkReference {
+  /// File containing the reference, relative to the audited site root.
+  pub file: PathBuf,
+  /// One-based line number of the reference.
+  pub line: usize,
+  /// Referenced URL.
+  pub url: String,
+  /// Classification of the reference.
+  pub kind: NetworkReferenceKind,
+}
+
+/// Decides which network references are acceptable in an offline bundle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkAuditPolicy {
+  /// Reference kinds that make [`NetworkAuditReport::enforce`] fail.
+  pub fail_on: BTreeSet<NetworkReferenceKind>,
+  /// Hosts that are never reported, e.g. XML namespace URIs.
+  pub allow_hosts: Vec<String>,
+}
+
+/// Result of [`audit_network_references`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NetworkAuditReport {
+  /// Every reference that was not allowed by the policy, sorted by file and line.
+  pub references: Vec<NetworkReference>,
+}
+
+const KNOWN_FONT_HOSTS: [&str; 3] = [
+  "fonts.googleapis.com",
+  "fonts.gstatic.com",
+  "use.typekit.net",
+];
+
+const KNOWN_CDN_HOSTS: [&str; 7] = [
+  "cdn.jsdelivr.net",
+  "unpkg.com",
+  "cdnjs.cloudflare.com",
+  "ajax.googleapis.com",
+  "code.jquery.com",
+  "stackpath.bootstrapcdn.com",
+  "cdn.tailwindcss.com",
+];
+
+const AUDITED_EXTENSIONS: [&str; 5] = ["html", "htm", "css", "js", "mjs"];
+
+impl Default for NetworkAuditPolicy {
+  fn default() -> Self {
+    Self {
+      fail_on: BTreeSet::from([
+        NetworkReferenceKind::Font,
+        NetworkReferenceKind::Cdn,
+        NetworkReferenceKind::Iframe,
+        NetworkReferenceKind::Script,
+        NetworkReferenceKind::Stylesheet,
+      ]),
+      allow_hosts: vec!["www.w3.org".into(), "w3.org".into()],
+    }
+  }
+}
+
+impl fmt::Display for NetworkReferenceKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let label = match self {
+      Self::Font => "font",
+      Self::Cdn => "CDN",
+      Self::Iframe => "iframe",
+      Self::Script => "script",
+      Self::Stylesheet => "stylesheet",
+      Self::Other => "other",
+    };
+    f.write_str(label)
+  }
+}
+
+impl fmt::Display for NetworkReference {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "{}:{}: {} reference to {}",
+      self.file.display(),
+      self.line,
+      self.kind,
+      self.url
+    )
+  }
+}
+
+impl NetworkAuditReport {
+  /// References whose kind is listed in the policy's `fail_on` set.
+  pub fn violations<'a>(
+    &'a self,
+    policy: &'a NetworkAuditPolicy,
+  ) -> impl Iterator<Item = &'a NetworkReference> + 'a {
+    self
+      .references
+      .iter()
+      .filter(|reference| policy.fail_on.contains(&reference.kind))
+  }
+
+  /// Fail with a list of every violating reference.
+  pub fn enforce(&self, policy: &NetworkAuditPolicy) -> Result<()> {
+    let violations: Vec<String> = self.violations(policy).map(ToString::to_string).collect();
+    if violations.is_empty() {
+      Ok(())
+    } else {
+      Err(anyhow!(
+        "offline bundle still depends on the network:\n  {}",
+        violations.join("\n  ")
+      ))
+    }
+  }
+}
+
+/// Scan every HTML, CSS and JavaScript file below `site_root` for network references.
+pub fn audit_network_references(
+  site_root: &Path,
+  policy: &NetworkAuditPolicy,
+) -> Result<NetworkAuditReport> {
+  let url_pattern = Regex::new(r#"https?://[^\s"'`<>()\\]+"#).expect("invalid url regex");
+  let protocol_relative_pattern = Regex::new(
+    r#"(?i)(?:\b(?:src|href|poster|action)\s*=\s*["']?|url\(\s*["']?)(//[^\s"'`<>()\\]+)"#,
+  )
+  .expect("invalid protocol-relative url regex");
+  let mut references = Vec::new();
+  let mut pending = vec![site_root.to_path_buf()];
+
+  while let Some(dir) = pending.pop() {
+    for entry in fs::read_dir(&dir).with_context(|| format!("failed to read {}", dir.display()))? {
+      let entry = entry?;
+      let path = entry.path();
+      if entry.file_type()?.is_dir() {
+        pending.push(path);
+        continue;
+      }
+      let audited = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| AUDITED_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()));
+      if !audited {
+        continue;
+      }
+
+      let bytes = fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+      let text = String::from_utf8_lossy(&bytes);
+      let relative = path.strip_prefix(site_root).unwrap_or(&path).to_path_buf();
+
+      let absolute = url_pattern.find_iter(&text);
+      let protocol_relative = protocol_relative_pattern
+        .captures_iter(&text)
+        .filter_map(|caps| caps.get(1));
+      for found in absolute.chain(protocol_relative) {
+        let url = found.as_str().trim_end_matches([',', ';', '.']);
+        let Some(host) = external_host(url).filter(|host| host.contains('.')) else {
+          continue;
+        };
+        if host_matches(&host, &policy.allow_hosts) {
+          continue;
+        }
+        references.push(NetworkReference {
+          file: relative.clone(),
+          line: text[..found.start()].matches('\n').count() + 1,
+          url: url.to_string(),
+          kind: classify_reference(&text[..found.start()], &host, url),
+        });
+      }
+    }
+  }
+
+  references.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+  Ok(NetworkAuditReport { references })
+}
+
+fn classify_reference(preceding: &str, host: &str, url: &str) -> NetworkReferenceKind {
+  let path = url
+    .split(['?', '#'])
+    .next()
+    .unwrap_or_default()
+    .to_ascii_lowercase();
+  let tag = preceding
+    .rfind('<')
+    .filter(|start| !preceding[*start..].contains('>'))
+    .map(|start| preceding[start..].to_ascii_lowercase());
+
+  if tag.as_deref().is_some_and(|tag| tag.starts_with("<iframe")) {
+    NetworkReferenceKind::Iframe
+  } else if KNOWN_FONT_HOSTS.contains(&host)
+    || [".woff", ".woff2", ".ttf", ".otf", ".eot"]
+      .iter()
+      .any(|ext| path.ends_with(ext))
+  {
+    NetworkReferenceKind::Font
+  } else if KNOWN_CDN_HOSTS.contains(&host) || host.starts_with("cdn.") {
+    NetworkReferenceKind::Cdn
+  } else if path.ends_with(".js") || path.ends_with(".mjs") {
+    NetworkReferenceKind::Script
+  } else if path.ends_with(".css") {
+    NetworkReferenceKind::Stylesheet
+  } else {
+    NetworkReferenceKind::Other
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::tempdir;
+
+  #[test]
+  fn classifies_references_and_enforces_policy() {
+    let dir = tempdir().unwrap();
+    let site_root = dir.path();
+    fs::create_dir_all(site_root.join("assets")).unwrap();
+    fs::write(
+      site_root.join("index.html"),
+      "<html>\n<link href=\"https://fonts.googleapis.com/css2?family=Inter\">\n<iframe src=\"https://video.example.com/embed/1\"></iframe>\n<svg xmlns=\"http://www.w3.org/2000/svg\"></svg>\n</html>",
+    )
+    .unwrap();
+    fs::write(
+      site_root.join("assets/app.js"),
+      "import 'https://cdn.jsdelivr.net/npm/lib.js';\nconst docs = 'https://docs.example.com/help';",
+    )
+    .unwrap();
+    fs::write(
+      site_root.join("assets/data.json"),
+      "\"https://ignored.example.com\"",
+    )
+    .unwrap();
+
+    let policy = NetworkAuditPolicy::default();
+    let report = audit_network_references(site_root, &policy).unwrap();
+    let kinds: Vec<NetworkReferenceKind> = report.references.iter().map(|r| r.kind).collect();
+    assert_eq!(kinds, vec![
+      NetworkReferenceKind::Cdn,
+      NetworkReferenceKind::Other,
+      NetworkReferenceKind::Font,
+      NetworkReferenceKind::Iframe,
+    ]);
+    assert_eq!(report.references[2].line, 2);
+
+    let error = report.enforce(&policy).unwrap_err().to_string();
+    assert!(error.contains("fonts.googleapis.com"));
+    assert!(!error.contains("docs.example.com"));
+
+    let lenient = NetworkAuditPolicy {
+      fail_on: BTreeSet::new(),
+      ..NetworkAuditPolicy::default()
+    };
+    assert!(report.enforce(&lenient).is_ok());
+  }
+
+  #[test]
+  fn reports_protocol_relative_urls_outside_comments() {
+    let dir = tempdir().unwrap();
+    let site_root = dir.path();
+    fs::write(
+      site_root.join("index.html"),
+      "<video src=\"//media.example.com/intro.mp4\"></video>",
+    )
+    .unwrap();
+    fs::write(
+      site_root.join("style.css"),
+      "@font-face { src: url(//fonts.example.com/inter.woff2); }",
+    )
+    .unwrap();
+    fs::write(
+      site_root.join("app.js"),
+      "// see docs.example.com\nlet a = 1;",
+    )
+    .unwrap();
+
+    let report = audit_network_references(site_root, &NetworkAuditPolicy::default()).unwrap();
+    let urls: Vec<(&str, NetworkReferenceKind)> = report
+      .references
+      .iter()
+      .map(|reference| (reference.url.as_str(), reference.kind))
+      .collect();
+    assert_eq!(urls, [
+      ("//media.example.com/intro.mp4", NetworkReferenceKind::Other),
+      (
+        "//fonts.example.com/inter.woff2",
+        NetworkReferenceKind::Font
+      ),
+    ]);
+  }
+}
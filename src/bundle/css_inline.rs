@@ -0,0 +1,226 @@
+//! Inline small images referenced from the site's stylesheets as `data:` URIs.
+//!
+//! Icon sprites and background images referenced through `url(...)` each become a file of their
+//! own in the bundle. [`inline_css_images`] embeds those below the size limit into every
+//! stylesheet referencing them and deletes the files nothing else mentions.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use aho_corasick::AhoCorasick;
+use anyhow::{Context, Result};
+use regex::{Captures, Regex};
+
+use crate::bundle::css_purge::collect_files;
+use crate::manifest::inline_images::image_data_uri;
+
+/// Files searched for remaining references before an inlined image is deleted.
+const REFERENCE_EXTENSIONS: [&str; 9] = [
+  "html",
+  "htm",
+  "js",
+  "mjs",
+  "wasm",
+  "json",
+  "css",
+  "svg",
+  "webmanifest",
+];
+
+/// Outcome of [`inline_css_images`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CssInlineStats {
+  /// Number of stylesheets rewritten.
+  pub files: usize,
+  /// Images inlined, relative to the site root.
+  pub images: Vec<PathBuf>,
+  /// Inlined images deleted because no other file references their name.
+  pub removed: Vec<PathBuf>,
+}
+
+/// Replace `url(...)` references to images of at most `max_bytes` in every stylesheet below
+/// `site_root` with `data:` URIs.
+///
+/// Only relative and root-relative URLs resolving inside `site_root` are inlined. Afterwards an
+/// inlined image is deleted unless its file name still appears in a page, script, manifest or
+/// WebAssembly module, matched as a plain substring like class names in CSS purging.
+pub fn inline_css_images(site_root: &Path, max_bytes: u64) -> Result<CssInlineStats> {
+  let url_pattern =
+    Regex::new(r#"url\(\s*(?:"([^"]*)"|'([^']*)'|([^)"'\s]*))\s*\)"#).expect("invalid url regex");
+  let mut data_uris: BTreeMap<PathBuf, Option<String>> = BTreeMap::new();
+  let mut stats = CssInlineStats::default();
+
+  for path in collect_files(site_root, &|ext| ext == "css")? {
+    let source =
+      fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))?;
+    let dir = path.parent().unwrap_or(site_root);
+    let mut failure = None;
+    let rewritten = url_pattern.replace_all(&source, |caps: &Captures| {
+      let url = caps
+        .get(1)
+        .or_else(|| caps.get(2))
+        .or_else(|| caps.get(3))
+        .map_or("", |m| m.as_str());
+      let Some(relative) = resolve_url(site_root, dir, url) else {
+        return caps[0].to_string();
+      };
+      if !data_uris.contains_key(&relative) {
+        match load_data_uri(site_root, &relative, max_bytes) {
+          Ok(data_uri) => {
+            data_uris.insert(relative.clone(), data_uri);
+          }
+          Err(err) => {
+            failure.get_or_insert(err);
+            return caps[0].to_string();
+          }
+        }
+      }
+      match &data_uris[&relative] {
+        Some(data_uri) => format!("url(\"{data_uri}\")"),
+        None => caps[0].to_string(),
+      }
+    });
+    if let Some(err) = failure {
+      return Err(err);
+    }
+    if rewritten != source {
+      fs::write(&path, rewritten.as_bytes())
+        .with_context(|| format!("failed to write {}", path.display()))?;
+      stats.files += 1;
+    }
+  }
+
+  stats.images = data_uris
+    .into_iter()
+    .filter_map(|(path, data_uri)| data_uri.map(|_| path))
+    .collect();
+  stats.removed = remove_unreferenced(site_root, &stats.images)?;
+  Ok(stats)
+}
+
+/// Path relative to `site_root` of a stylesheet URL, or `None` for external, `data:` and
+/// fragment URLs and paths leaving the site.
+fn resolve_url(site_root: &Path, dir: &Path, url: &str) -> Option<PathBuf> {
+  let url = url.split(['?', '#']).next().unwrap_or_default();
+  if url.is_empty() || url.starts_with("//") || url.contains(':') {
+    return None;
+  }
+  let joined = match url.strip_prefix('/') {
+    Some(root_relative) => PathBuf::from(root_relative),
+    None => dir.strip_prefix(site_root).ok()?.join(url),
+  };
+  let mut resolved = PathBuf::new();
+  for component in joined.components() {
+    match component {
+      Component::Normal(segment) => resolved.push(segment),
+      Component::CurDir => {}
+      Component::ParentDir => {
+        if !resolved.pop() {
+          return None;
+        }
+      }
+      Component::RootDir | Component::Prefix(_) => return None,
+    }
+  }
+  Some(resolved)
+}
+
+fn load_data_uri(site_root: &Path, relative: &Path, max_bytes: u64) -> Result<Option<String>> {
+  let path = site_root.join(relative);
+  let Ok(metadata) = fs::metadata(&path) else {
+    return Ok(None);
+  };
+  if !metadata.is_file() || metadata.len() > max_bytes {
+    return Ok(None);
+  }
+  let bytes = fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+  Ok(image_data_uri(&relative.to_string_lossy(), &bytes))
+}
+
+fn remove_unreferenced(site_root: &Path, images: &[PathBuf]) -> Result<Vec<PathBuf>> {
+  let names: Vec<String> = images
+    .iter()
+    .map(|path| {
+      path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+    })
+    .collect();
+  if names.is_empty() {
+    return Ok(Vec::new());
+  }
+
+  let matcher = AhoCorasick::new(&names).context("failed to build image name matcher")?;
+  let mut found = vec![false; names.len()];
+  for path in collect_files(site_root, &|ext| REFERENCE_EXTENSIONS.contains(&ext))? {
+    let bytes = fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+    for hit in matcher.find_overlapping_iter(&bytes) {
+      found[hit.pattern().as_usize()] = true;
+    }
+  }
+
+  let mut removed = Vec::new();
+  for (relative, found) in images.iter().zip(found) {
+    if found {
+      continue;
+    }
+    let path = site_root.join(relative);
+    fs::remove_file(&path).with_context(|| format!("failed to remove {}", path.display()))?;
+    removed.push(relative.clone());
+  }
+  Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::tempdir;
+
+  #[test]
+  fn inlines_small_stylesheet_images() -> Result<()> {
+    let dir = tempdir()?;
+    let assets = dir.path().join("assets");
+    fs::create_dir_all(assets.join("icons"))?;
+    fs::write(assets.join("icons/check.svg"), "<svg/>")?;
+    fs::write(assets.join("icons/logo.png"), "png")?;
+    fs::write(assets.join("hero.jpg"), vec![0; 2048])?;
+    fs::write(
+      assets.join("main.css"),
+      concat!(
+        ".ok{background:url(icons/check.svg)}",
+        ".logo{background:url('/assets/icons/logo.png?v=1')}",
+        ".hero{background:url(\"hero.jpg\")}",
+        ".remote{background:url(https://example.com/a.png)}",
+        ".up{background:url(../../outside.png)}",
+      ),
+    )?;
+    fs::write(
+      dir.path().join("index.html"),
+      r#"<link rel="icon" href="assets/icons/logo.png">"#,
+    )?;
+
+    let stats = inline_css_images(dir.path(), 1024)?;
+
+    assert_eq!(stats.files, 1);
+    assert_eq!(stats.images, [
+      PathBuf::from("assets/icons/check.svg"),
+      PathBuf::from("assets/icons/logo.png"),
+    ]);
+    assert_eq!(stats.removed, [PathBuf::from("assets/icons/check.svg")]);
+    assert_eq!(
+      fs::read_to_string(assets.join("main.css"))?,
+      concat!(
+        ".ok{background:url(\"data:image/svg+xml;base64,PHN2Zy8+\")}",
+        ".logo{background:url(\"data:image/png;base64,cG5n\")}",
+        ".hero{background:url(\"hero.jpg\")}",
+        ".remote{background:url(https://example.com/a.png)}",
+        ".up{background:url(../../outside.png)}",
+      )
+    );
+    assert!(!assets.join("icons/check.svg").exists());
+    assert!(assets.join("icons/logo.png").exists());
+    Ok(())
+  }
+}
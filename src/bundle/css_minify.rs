@@ -0,0 +1,111 @@
+//! Optional minification of the stylesheets shipped in the offline site.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use lightningcss::stylesheet::{MinifyOptions, ParserOptions, PrinterOptions, StyleSheet};
+
+/// Size savings recorded by [`minify_site_css`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CssMinifyStats {
+  /// Number of stylesheets rewritten.
+  pub files: usize,
+  /// Combined size of the rewritten stylesheets before minification.
+  pub original_bytes: u64,
+  /// Combined size of the rewritten stylesheets after minification.
+  pub minified_bytes: u64,
+  /// Stylesheets that could not be parsed and were left untouched, relative to the site root.
+  pub skipped: Vec<PathBuf>,
+}
+
+impl CssMinifyStats {
+  /// Bytes removed by minification.
+  pub fn saved_bytes(&self) -> u64 {
+    self.original_bytes - self.minified_bytes
+  }
+}
+
+/// Minify every `.css` file below `site_root` in place.
+///
+/// Run this after stylesheet aliases were created so the aliased copies are minified too.
+/// Files are only rewritten when minification makes them smaller.
+pub fn minify_site_css(site_root: &Path) -> Result<CssMinifyStats> {
+  let mut stats = CssMinifyStats::default();
+  let mut pending = vec![site_root.to_path_buf()];
+
+  while let Some(dir) = pending.pop() {
+    for entry in fs::read_dir(&dir).with_context(|| format!("failed to read {}", dir.display()))? {
+      let entry = entry?;
+      let path = entry.path();
+      if entry.file_type()?.is_dir() {
+        pending.push(path);
+        continue;
+      }
+      if !path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("css"))
+      {
+        continue;
+      }
+
+      let source =
+        fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))?;
+      let Some(minified) = minify_css(&source) else {
+        let relative = path.strip_prefix(site_root).unwrap_or(&path).to_path_buf();
+        stats.skipped.push(relative);
+        continue;
+      };
+      if minified.len() >= source.len() {
+        continue;
+      }
+
+      fs::write(&path, &minified).with_context(|| format!("failed to write {}", path.display()))?;
+      stats.files += 1;
+      stats.original_bytes += source.len() as u64;
+      stats.minified_bytes += minified.len() as u64;
+    }
+  }
+
+  stats.skipped.sort();
+  Ok(stats)
+}
+
+fn minify_css(source: &str) -> Option<String> {
+  let mut sheet = StyleSheet::parse(source, ParserOptions::default()).ok()?;
+  sheet.minify(MinifyOptions::default()).ok()?;
+  let printed = sheet
+    .to_css(PrinterOptions {
+      minify: true,
+      ..PrinterOptions::default()
+    })
+    .ok()?;
+  Some(printed.code)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::tempdir;
+
+  #[test]
+  fn minifies_stylesheets_and_records_savings() {
+    let dir = tempdir().unwrap();
+    let site_root = dir.path();
+    fs::create_dir_all(site_root.join("assets")).unwrap();
+    fs::write(
+      site_root.join("assets/main-abc.css"),
+      "/* layout */\nbody {\n  margin: 0px;\n  color: #ffffff;\n}\n",
+    )
+    .unwrap();
+    fs::write(site_root.join("main.css"), "a {\n  color: red;\n}\n").unwrap();
+
+    let stats = minify_site_css(site_root).unwrap();
+    assert_eq!(stats.files, 2);
+    assert!(stats.saved_bytes() > 0);
+    assert_eq!(
+      fs::read_to_string(site_root.join("assets/main-abc.css")).unwrap(),
+      "body{color:#fff;margin:0}"
+    );
+  }
+}
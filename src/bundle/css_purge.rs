@@ -0,0 +1,404 @@
+//! Removal of stylesheet rules whose classes never appear in the bundled content.
+//!
+//! Class names are matched as plain substrings of the site's HTML, JavaScript and WebAssembly
+//! files plus any extra content roots such as the authored entry bodies. Substring matching
+//! keeps a rule whenever its class could be referenced, which errs on the side of shipping too
+//! much CSS rather than breaking styles built from string literals inside the WebAssembly data.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use aho_corasick::AhoCorasick;
+use anyhow::{Context, Result};
+use regex::Regex;
+
+/// Settings for [`purge_unused_css`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CssPurgeOptions {
+  /// Additional directories scanned for class names, e.g. the authored collections or
+  /// pre-rendered HTML.
+  pub content_roots: Vec<PathBuf>,
+  /// Classes that are always kept. A trailing `*` keeps every class with that prefix.
+  pub safelist: Vec<String>,
+}
+
+/// Outcome of [`purge_unused_css`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CssPurgeStats {
+  /// Number of stylesheets rewritten.
+  pub files: usize,
+  /// Number of style rules removed.
+  pub removed_rules: usize,
+  /// Combined size of the rewritten stylesheets before purging.
+  pub original_bytes: u64,
+  /// Combined size of the rewritten stylesheets after purging.
+  pub purged_bytes: u64,
+}
+
+const CONTENT_EXTENSIONS: [&str; 10] = [
+  "html", "htm", "js", "mjs", "wasm", "json", "md", "markdown", "txt", "svg",
+];
+
+const NESTING_AT_RULES: [&str; 5] = ["media", "supports", "layer", "container", "document"];
+
+/// Strip rules from every stylesheet below `site_root` whose selectors only use unused classes.
+///
+/// Rules without class selectors, `@font-face`, `@keyframes` and other non-nesting at-rules are
+/// always kept; empty `@media`/`@supports` blocks left behind are removed.
+pub fn purge_unused_css(site_root: &Path, options: &CssPurgeOptions) -> Result<CssPurgeStats> {
+  let stylesheets = collect_files(site_root, &|ext| ext == "css")?;
+  let mut sources = Vec::with_capacity(stylesheets.len());
+  let mut classes = BTreeSet::new();
+  for path in stylesheets {
+    let source =
+      fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))?;
+    collect_classes(&source, &mut classes);
+    sources.push((path, source));
+  }
+
+  let used = find_used_classes(site_root, options, &classes)?;
+  let mut stats = CssPurgeStats::default();
+  for (path, source) in sources {
+    let mut removed = 0;
+    let purged = purge_block(&source, &used, &mut removed);
+    if removed == 0 {
+      continue;
+    }
+    fs::write(&path, &purged).with_context(|| format!("failed to write {}", path.display()))?;
+    stats.files += 1;
+    stats.removed_rules += removed;
+    stats.original_bytes += source.len() as u64;
+    stats.purged_bytes += purged.len() as u64;
+  }
+
+  Ok(stats)
+}
+
+fn find_used_classes(
+  site_root: &Path,
+  options: &CssPurgeOptions,
+  classes: &BTreeSet<String>,
+) -> Result<BTreeSet<String>> {
+  let mut used: BTreeSet<String> = classes
+    .iter()
+    .filter(|class| is_safelisted(class, &options.safelist))
+    .cloned()
+    .collect();
+  let candidates: Vec<&String> = classes
+    .iter()
+    .filter(|class| !used.contains(*class))
+    .collect();
+  if candidates.is_empty() {
+    return Ok(used);
+  }
+
+  let matcher = AhoCorasick::new(&candidates).context("failed to build class matcher")?;
+  let is_content = |ext: &str| CONTENT_EXTENSIONS.contains(&ext);
+  let mut files = collect_files(site_root, &is_content)?;
+  for root in &options.content_roots {
+    files.extend(collect_files(root, &is_content)?);
+  }
+
+  let mut found = vec![false; candidates.len()];
+  for path in files {
+    let bytes = fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+    for hit in matcher.find_overlapping_iter(&bytes) {
+      found[hit.pattern().as_usize()] = true;
+    }
+  }
+
+  used.extend(
+    candidates
+      .into_iter()
+      .zip(found)
+      .filter(|(_, found)| *found)
+      .map(|(class, _)| class.clone()),
+  );
+  Ok(used)
+}
+
+fn is_safelisted(class: &str, safelist: &[String]) -> bool {
+  safelist.iter().any(|entry| match entry.strip_suffix('*') {
+    Some(prefix) => class.starts_with(prefix),
+    None => class == entry,
+  })
+}
+
+pub(crate) fn collect_files(root: &Path, accept: &dyn Fn(&str) -> bool) -> Result<Vec<PathBuf>> {
+  let mut files = Vec::new();
+  if !root.is_dir() {
+    return Ok(files);
+  }
+  let mut pending = vec![root.to_path_buf()];
+  while let Some(dir) = pending.pop() {
+    for entry in fs::read_dir(&dir).with_context(|| format!("failed to read {}", dir.display()))? {
+      let entry = entry?;
+      let path = entry.path();
+      if entry.file_type()?.is_dir() {
+        pending.push(path);
+      } else if path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| accept(&ext.to_ascii_lowercase()))
+      {
+        files.push(path);
+      }
+    }
+  }
+  files.sort();
+  Ok(files)
+}
+
+static CLASS_PATTERN: LazyLock<Regex> =
+  LazyLock::new(|| Regex::new(r"\.((?:\\.|[A-Za-z0-9_-])+)").expect("invalid class regex"));
+
+/// `:not(...)` groups and `[...]` attribute selectors, whose contents name no required class.
+static IGNORED_GROUPS: LazyLock<Regex> = LazyLock::new(|| {
+  Regex::new(r#":not\([^)]*\)|\[(?:"[^"]*"|'[^']*'|[^\]"'])*\]"#)
+    .expect("invalid ignored selector regex")
+});
+
+/// Class names referenced by a selector, with CSS escapes such as `md\:flex` resolved.
+fn selector_classes(selector: &str) -> Vec<String> {
+  let selector = IGNORED_GROUPS.replace_all(selector, "");
+  CLASS_PATTERN
+    .captures_iter(&selector)
+    .map(|caps| unescape(&caps[1]))
+    .filter(|class| !class.starts_with(|c: char| c.is_ascii_digit()))
+    .collect()
+}
+
+fn unescape(class: &str) -> String {
+  let mut result = String::with_capacity(class.len());
+  let mut chars = class.chars();
+  while let Some(c) = chars.next() {
+    if c == '\\' {
+      if let Some(escaped) = chars.next() {
+        result.push(escaped);
+      }
+    } else {
+      result.push(c);
+    }
+  }
+  result
+}
+
+fn collect_classes(css: &str, classes: &mut BTreeSet<String>) {
+  for_each_rule(css, &mut |prelude, _| {
+    if !prelude.starts_with('@') {
+      classes.extend(selector_classes(prelude));
+    }
+  });
+}
+
+/// Visit the prelude and body of every rule, descending into nesting at-rules.
+fn for_each_rule(css: &str, visit: &mut dyn FnMut(&str, &str)) {
+  let mut rest = css;
+  while let Some((_, prelude, body, tail)) = next_rule(rest) {
+    visit(prelude.trim(), body);
+    if let Some(name) = at_rule_name(prelude)
+      && NESTING_AT_RULES.contains(&name.as_str())
+    {
+      for_each_rule(body, visit);
+    }
+    rest = tail;
+  }
+}
+
+fn purge_block(css: &str, used: &BTreeSet<String>, removed: &mut usize) -> String {
+  let mut output = String::with_capacity(css.len());
+  let mut rest = css;
+
+  while let Some((leading, prelude, body, tail)) = next_rule(rest) {
+    output.push_str(leading);
+    let trimmed = prelude.trim();
+    match at_rule_name(trimmed) {
+      Some(name) if NESTING_AT_RULES.contains(&name.as_str()) => {
+        let inner = purge_block(body, used, removed);
+        if !inner.trim().is_empty() {
+          output.push_str(&format!("{prelude}{{{inner}}}"));
+        }
+      }
+      Some(_) => output.push_str(&format!("{prelude}{{{body}}}")),
+      None => {
+        let keep = split_selectors(trimmed).iter().any(|selector| {
+          selector_classes(selector)
+            .iter()
+            .all(|class| used.contains(class))
+        });
+        if keep {
+          output.push_str(&format!("{prelude}{{{body}}}"));
+        } else {
+          *removed += 1;
+        }
+      }
+    }
+    rest = tail;
+  }
+
+  output.push_str(rest);
+  output
+}
+
+fn at_rule_name(prelude: &str) -> Option<String> {
+  let name = prelude.trim_start().strip_prefix('@')?;
+  Some(
+    name
+      .chars()
+      .take_while(|c| c.is_ascii_alphanumeric() || *c == '-')
+      .collect::<String>()
+      .to_ascii_lowercase(),
+  )
+}
+
+fn split_selectors(prelude: &str) -> Vec<&str> {
+  let mut selectors = Vec::new();
+  let mut depth = 0usize;
+  let mut start = 0;
+  for (index, c) in prelude.char_indices() {
+    match c {
+      '(' | '[' => depth += 1,
+      ')' | ']' => depth = depth.saturating_sub(1),
+      ',' if depth == 0 => {
+        selectors.push(&prelude[start..index]);
+        start = index + 1;
+      }
+      _ => {}
+    }
+  }
+  selectors.push(&prelude[start..]);
+  selectors
+}
+
+/// Split off the next `prelude { body }` rule, returning the text before it (whitespace,
+/// comments and `@import`-style statements), the prelude, the body and the remaining input.
+fn next_rule(css: &str) -> Option<(&str, &str, &str, &str)> {
+  let bytes = css.as_bytes();
+  let mut index = 0;
+  let mut statement_start = 0;
+  let mut open = None;
+
+  while index < bytes.len() {
+    match bytes[index] {
+      b'/' if bytes.get(index + 1) == Some(&b'*') => {
+        index = css[index + 2..]
+          .find("*/")
+          .map_or(bytes.len(), |end| index + end + 4);
+        if open.is_none() && css[statement_start..index].trim_start().starts_with("/*") {
+          statement_start = index;
+        }
+        continue;
+      }
+      quote @ (b'"' | b'\'') => index = skip_string(bytes, index, quote),
+      b';' if open.is_none() => statement_start = index + 1,
+      b'{' => {
+        open = Some(index);
+        break;
+      }
+      _ => {}
+    }
+    index += 1;
+  }
+
+  let open = open?;
+  let mut depth = 0usize;
+  let mut index = open;
+  while index < bytes.len() {
+    match bytes[index] {
+      b'/' if bytes.get(index + 1) == Some(&b'*') => {
+        index = css[index + 2..]
+          .find("*/")
+          .map_or(bytes.len(), |end| index + end + 4);
+        continue;
+      }
+      quote @ (b'"' | b'\'') => index = skip_string(bytes, index, quote),
+      b'{' => depth += 1,
+      b'}' => {
+        depth -= 1;
+        if depth == 0 {
+          let prelude_start = statement_start + css[statement_start..open].len()
+            - css[statement_start..open].trim_start().len();
+          return Some((
+            &css[..prelude_start],
+            &css[prelude_start..open],
+            &css[open + 1..index],
+            &css[index + 1..],
+          ));
+        }
+      }
+      _ => {}
+    }
+    index += 1;
+  }
+  None
+}
+
+fn skip_string(bytes: &[u8], start: usize, quote: u8) -> usize {
+  let mut index = start + 1;
+  while index < bytes.len() {
+    match bytes[index] {
+      b'\\' => index += 1,
+      byte if byte == quote => return index,
+      _ => {}
+    }
+    index += 1;
+  }
+  index
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::tempdir;
+
+  #[test]
+  fn removes_rules_for_unused_classes() {
+    let dir = tempdir().unwrap();
+    let site_root = dir.path().join("site");
+    let content = dir.path().join("programs");
+    fs::create_dir_all(site_root.join("assets")).unwrap();
+    fs::create_dir_all(&content).unwrap();
+    fs::write(
+      site_root.join("index.html"),
+      r#"<div class="flex md:px-4">"#,
+    )
+    .unwrap();
+    fs::write(
+      site_root.join("assets/app_bg.wasm"),
+      b"\0\x01grid-cols-2Hello",
+    )
+    .unwrap();
+    fs::write(
+      content.join("index.md"),
+      "<span class=\"prose-lg\">Hi</span>",
+    )
+    .unwrap();
+    fs::write(
+      site_root.join("assets/main.css"),
+      "@charset \"utf-8\";\n/* header */\nbody{margin:0}\n.flex{display:flex}\n.hidden{display:none}\n\
+@media (min-width:768px){.md\\:px-4{padding:1rem}.md\\:hidden{display:none}}\n\
+@media print{.unused{color:red}}\n.grid-cols-2,.nope{content:\"}\"}\n.prose-lg{font-size:2rem}\n\
+.btn-primary{color:blue}\na[href$=\".pdf\"]{color:red}\n@font-face{font-family:x}\n",
+    )
+    .unwrap();
+
+    let options = CssPurgeOptions {
+      content_roots: vec![content],
+      safelist: vec!["btn-*".into()],
+    };
+    let stats = purge_unused_css(&site_root, &options).unwrap();
+    assert_eq!(stats.files, 1);
+    assert_eq!(stats.removed_rules, 3);
+    assert!(stats.purged_bytes < stats.original_bytes);
+
+    let purged = fs::read_to_string(site_root.join("assets/main.css")).unwrap();
+    assert_eq!(
+      purged,
+      "@charset \"utf-8\";\n/* header */\nbody{margin:0}\n.flex{display:flex}\n\n\
+@media (min-width:768px){.md\\:px-4{padding:1rem}}\n\n.grid-cols-2,.nope{content:\"}\"}\n.prose-lg{font-size:2rem}\n\
+.btn-primary{color:blue}\na[href$=\".pdf\"]{color:red}\n@font-face{font-family:x}\n"
+    );
+  }
+}
@@ -0,0 +1,73 @@
+//! Store byte-identical collection assets once inside an assembled bundle.
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use same_file::is_same_file;
+
+use crate::bundle::manifest::OfflineManifest;
+
+/// Replace every aliased asset below `site_root` with a hard link to its canonical copy.
+///
+/// Aliases come from the manifest's `asset_aliases`, so logical paths keep working while the
+/// content is stored once. Returns the number of files that were linked. Aliases whose files
+/// are missing, or file systems without hard link support, leave the copies untouched.
+pub fn link_duplicate_assets(site_root: &Path, manifest: &OfflineManifest) -> Result<usize> {
+  let mut linked = 0;
+
+  for (path, canonical) in &manifest.asset_aliases {
+    let duplicate = site_root.join(path);
+    let canonical = site_root.join(canonical);
+    if !duplicate.is_file() || !canonical.is_file() || is_same_file(&duplicate, &canonical)? {
+      continue;
+    }
+
+    let staging = duplicate.with_extension("dedup-tmp");
+    match fs::hard_link(&canonical, &staging) {
+      Ok(()) => {}
+      Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+        fs::remove_file(&staging)?;
+        fs::hard_link(&canonical, &staging)?;
+      }
+      Err(_) => continue,
+    }
+    fs::rename(&staging, &duplicate)
+      .with_context(|| format!("failed to replace {}", duplicate.display()))?;
+    linked += 1;
+  }
+
+  Ok(linked)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::tempdir;
+
+  #[test]
+  fn links_aliases_to_canonical_copy() {
+    let dir = tempdir().unwrap();
+    let site_root = dir.path();
+    fs::create_dir_all(site_root.join("programs/P001")).unwrap();
+    fs::create_dir_all(site_root.join("programs/P002")).unwrap();
+    fs::write(site_root.join("programs/P001/logo.png"), "logo").unwrap();
+    fs::write(site_root.join("programs/P002/logo.png"), "logo").unwrap();
+
+    let manifest: OfflineManifest = serde_json::from_str(
+      r#"{"entries": [], "asset_aliases": {"programs/P002/logo.png": "programs/P001/logo.png"}}"#,
+    )
+    .unwrap();
+
+    assert_eq!(link_duplicate_assets(site_root, &manifest).unwrap(), 1);
+    assert!(
+      is_same_file(
+        site_root.join("programs/P001/logo.png"),
+        site_root.join("programs/P002/logo.png")
+      )
+      .unwrap()
+    );
+    assert_eq!(link_duplicate_assets(site_root, &manifest).unwrap(), 0);
+  }
+}
@@ -0,0 +1,246 @@
+//! Patch bundles containing only what changed since a previously shipped release.
+//!
+//! The site shell (HTML, JavaScript, WebAssembly and styles) is always included because it is
+//! small and embeds the entry bodies. Collection assets are compared against the
+//! `asset_hashes` recorded in the previous offline manifest and only added or changed files
+//! are copied. Removed assets and entries are listed in `update_manifest.json` so the
+//! installer can delete them.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::bundle::manifest::{OfflineManifest, load_manifest};
+use crate::bundle::split::{copy_tree, site_location};
+use crate::manifest::inventory::file_digest;
+use crate::project::OfflineProjectLayout;
+
+/// File name of the update description written at the root of a delta bundle.
+pub const UPDATE_MANIFEST_FILE: &str = "update_manifest.json";
+
+/// Description of the changes contained in a delta bundle.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct UpdateManifest {
+  /// Bundle paths of assets that did not exist in the previous release.
+  pub added_assets: Vec<String>,
+  /// Bundle paths of assets whose content changed.
+  pub changed_assets: Vec<String>,
+  /// Bundle paths of assets that must be deleted from the installed bundle.
+  pub removed_assets: Vec<String>,
+  /// Entries (`collection_id/entry_id`) added since the previous release.
+  pub added_entries: Vec<String>,
+  /// Entries (`collection_id/entry_id`) removed since the previous release.
+  pub removed_entries: Vec<String>,
+}
+
+/// Write a delta bundle for `bundle_root` relative to the `previous` release manifest.
+///
+/// The output contains the site shell, the new offline manifest, every added or changed
+/// collection asset and an [`UPDATE_MANIFEST_FILE`] describing deletions.
+pub fn write_delta_bundle(
+  layout: &OfflineProjectLayout,
+  previous: &OfflineManifest,
+  bundle_root: &Path,
+  output_root: &Path,
+) -> Result<UpdateManifest> {
+  if previous.asset_hashes.is_empty() && !previous.entries.is_empty() {
+    return Err(anyhow!(
+      "previous manifest does not record asset hashes; rebuild it with a newer bundler"
+    ));
+  }
+  if output_root.starts_with(bundle_root) {
+    return Err(anyhow!(
+      "delta bundles must be written outside of {}",
+      bundle_root.display()
+    ));
+  }
+
+  let current = load_manifest(&bundle_root.join(&layout.offline_manifest_json))?;
+  let (site_relative, _) = site_location(layout, &current);
+  let collections_relative = site_relative.join(&layout.collections_dir_name);
+  let site_root = bundle_root.join(&site_relative);
+
+  let current_hashes = if current.asset_hashes.is_empty() {
+    hash_bundled_assets(&site_root, &layout.collections_dir_name)?
+  } else {
+    current.asset_hashes.clone()
+  };
+
+  let mut update = UpdateManifest::default();
+  for (path, digest) in &current_hashes {
+    match previous.asset_hashes.get(path) {
+      None => update.added_assets.push(path.clone()),
+      Some(previous_digest) if previous_digest != digest => {
+        update.changed_assets.push(path.clone())
+      }
+      Some(_) => {}
+    }
+  }
+  update.removed_assets = previous
+    .asset_hashes
+    .keys()
+    .filter(|path| !current_hashes.contains_key(*path))
+    .cloned()
+    .collect();
+
+  let previous_entries = entry_keys(previous);
+  let current_entries = entry_keys(&current);
+  update.added_entries = current_entries
+    .difference(&previous_entries)
+    .cloned()
+    .collect();
+  update.removed_entries = previous_entries
+    .difference(&current_entries)
+    .cloned()
+    .collect();
+
+  if output_root.exists() {
+    fs::remove_dir_all(output_root)
+      .with_context(|| format!("failed to clear {}", output_root.display()))?;
+  }
+  copy_tree(bundle_root, output_root, &|relative| {
+    relative == collections_relative
+  })?;
+
+  for path in update.added_assets.iter().chain(&update.changed_assets) {
+    let source = site_root.join(path);
+    if !source.is_file() {
+      continue;
+    }
+    let destination = output_root.join(&site_relative).join(path);
+    if let Some(parent) = destination.parent() {
+      fs::create_dir_all(parent)
+        .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    fs::copy(&source, &destination).with_context(|| {
+      format!(
+        "failed to copy {} to {}",
+        source.display(),
+        destination.display()
+      )
+    })?;
+  }
+
+  let update_path = output_root.join(UPDATE_MANIFEST_FILE);
+  fs::write(&update_path, serde_json::to_string_pretty(&update)?)
+    .with_context(|| format!("failed to write {}", update_path.display()))?;
+
+  Ok(update)
+}
+
+fn entry_keys(manifest: &OfflineManifest) -> BTreeSet<String> {
+  manifest
+    .entries
+    .iter()
+    .map(|entry| format!("{}/{}", entry.collection_id, entry.entry_id))
+    .collect()
+}
+
+fn hash_bundled_assets(
+  site_root: &Path,
+  collections_dir_name: &str,
+) -> Result<BTreeMap<String, String>> {
+  let mut hashes = BTreeMap::new();
+  let mut pending = vec![site_root.join(collections_dir_name)];
+  while let Some(dir) = pending.pop() {
+    let Ok(entries) = fs::read_dir(&dir) else {
+      continue;
+    };
+    for entry in entries {
+      let entry = entry?;
+      let path = entry.path();
+      if entry.file_type()?.is_dir() {
+        pending.push(path);
+        continue;
+      }
+      let (_, digest) =
+        file_digest(&path).with_context(|| format!("failed to hash {}", path.display()))?;
+      let relative = path
+        .strip_prefix(site_root)
+        .unwrap_or(&path)
+        .to_string_lossy()
+        .replace('\\', "/");
+      hashes.insert(relative, digest);
+    }
+  }
+  Ok(hashes)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::tempdir;
+
+  fn layout() -> OfflineProjectLayout {
+    OfflineProjectLayout {
+      excluded_dir_name: "prod".into(),
+      excluded_path_fragment: "/prod/".into(),
+      ..Default::default()
+    }
+  }
+
+  fn write_file(path: &Path, contents: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, contents).unwrap();
+  }
+
+  #[test]
+  fn copies_only_changed_assets_and_lists_deletions() {
+    let dir = tempdir().unwrap();
+    let bundle_root = dir.path().join("bundle");
+    write_file(&bundle_root.join("site/index.html"), "site");
+    write_file(&bundle_root.join("site/programs/P001/same.png"), "same");
+    write_file(&bundle_root.join("site/programs/P001/edited.png"), "new");
+    write_file(&bundle_root.join("site/programs/P002/added.png"), "added");
+    write_file(
+      &bundle_root.join("offline_manifest.json"),
+      r#"{"site_root": "site", "entries": [
+        {"collection_id": "P001", "entry_id": "intro"},
+        {"collection_id": "P002", "entry_id": "intro"}
+      ]}"#,
+    );
+
+    let (_, same) = file_digest(&bundle_root.join("site/programs/P001/same.png")).unwrap();
+    let previous: OfflineManifest = serde_json::from_value(serde_json::json!({
+      "site_root": "site",
+      "entries": [
+        {"collection_id": "P001", "entry_id": "intro"},
+        {"collection_id": "P001", "entry_id": "legacy"}
+      ],
+      "asset_hashes": {
+        "programs/P001/same.png": same,
+        "programs/P001/edited.png": "0000",
+        "programs/P001/gone.png": "1111"
+      }
+    }))
+    .unwrap();
+
+    let output = dir.path().join("delta");
+    let update = write_delta_bundle(&layout(), &previous, &bundle_root, &output).unwrap();
+
+    assert_eq!(update.added_assets, vec!["programs/P002/added.png"]);
+    assert_eq!(update.changed_assets, vec!["programs/P001/edited.png"]);
+    assert_eq!(update.removed_assets, vec!["programs/P001/gone.png"]);
+    assert_eq!(update.added_entries, vec!["P002/intro"]);
+    assert_eq!(update.removed_entries, vec!["P001/legacy"]);
+
+    assert!(output.join("site/index.html").is_file());
+    assert!(output.join("offline_manifest.json").is_file());
+    assert!(output.join(UPDATE_MANIFEST_FILE).is_file());
+    assert!(output.join("site/programs/P001/edited.png").is_file());
+    assert!(!output.join("site/programs/P001/same.png").exists());
+  }
+
+  #[test]
+  fn requires_hashes_in_previous_manifest() {
+    let dir = tempdir().unwrap();
+    let previous: OfflineManifest =
+      serde_json::from_str(r#"{"entries": [{"collection_id": "P001", "entry_id": "intro"}]}"#)
+        .unwrap();
+    let result = write_delta_bundle(&layout(), &previous, dir.path(), &dir.path().join("out"));
+    assert!(result.is_err());
+  }
+}
@@ -0,0 +1,149 @@
+//! Additional entry-point pages generated from the patched index.
+//!
+//! Every [`EntryPoint`] becomes a copy of the patched index with an inline script that publishes
+//! the definition as `window.__offlineEntryPoint` and implements its behaviour: opening the
+//! start route, re-entering fullscreen and reloading after the configured idle time. The app
+//! reads the `flags`, e.g. `showDrafts`, to adapt what it shows.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, anyhow};
+
+use crate::bundle::PATCH_MARKER;
+use crate::config::EntryPoint;
+use crate::project::OfflineProjectLayout;
+
+/// Route opened by entry points without a `start_route`.
+pub const DEFAULT_START_ROUTE: &str = "#/";
+
+const ENTRY_POINT_SCRIPT: &str = r#"    <script id="offline-entry-point">
+      window.__offlineEntryPoint = __ENTRY_POINT__;
+      (() => {
+        const entry = window.__offlineEntryPoint;
+        if (!window.location.hash) {
+          window.location.replace(entry.startRoute);
+        }
+        if (entry.fullscreen) {
+          const enter = () => {
+            const root = document.documentElement;
+            if (!document.fullscreenElement && root.requestFullscreen) {
+              root.requestFullscreen().catch(() => {});
+            }
+          };
+          ['pointerdown', 'keydown'].forEach((name) => window.addEventListener(name, enter));
+        }
+        if (entry.idleResetSecs > 0) {
+          let timer;
+          const reset = () => {
+            history.replaceState(null, '', entry.startRoute);
+            window.location.reload();
+          };
+          const arm = () => {
+            clearTimeout(timer);
+            timer = setTimeout(reset, entry.idleResetSecs * 1000);
+          };
+          ['pointerdown', 'pointermove', 'keydown', 'wheel', 'touchstart'].forEach((name) =>
+            window.addEventListener(name, arm, { passive: true })
+          );
+          arm();
+        }
+      })();
+    </script>
+"#;
+
+/// Write one page per entry point next to the patched index in `site_root`.
+///
+/// Existing pages are overwritten, so the function can run again after the index was
+/// re-patched. Fails when the index has not been patched yet.
+pub fn write_entry_points(
+  layout: &OfflineProjectLayout,
+  site_root: &Path,
+  entry_points: &[EntryPoint],
+) -> Result<Vec<PathBuf>> {
+  if entry_points.is_empty() {
+    return Ok(Vec::new());
+  }
+  let index_path = site_root.join(&layout.index_html_file);
+  let index = fs::read_to_string(&index_path)
+    .with_context(|| format!("failed to read {}", index_path.display()))?;
+  if !index.contains(PATCH_MARKER) {
+    return Err(anyhow!(
+      "{} must be patched before entry points are written",
+      index_path.display()
+    ));
+  }
+  let head_end = index
+    .to_ascii_lowercase()
+    .rfind("</head>")
+    .ok_or_else(|| anyhow!("failed to locate </head> tag in {}", index_path.display()))?;
+
+  entry_points
+    .iter()
+    .map(|entry_point| {
+      let page = format!(
+        "{}{}  {}",
+        &index[..head_end],
+        entry_point_script(entry_point)?,
+        &index[head_end..]
+      );
+      let path = site_root.join(&entry_point.file);
+      fs::write(&path, page).with_context(|| format!("failed to write {}", path.display()))?;
+      Ok(path)
+    })
+    .collect()
+}
+
+/// Inline script publishing `entry_point` with its start route filled in.
+fn entry_point_script(entry_point: &EntryPoint) -> Result<String> {
+  let mut entry_point = entry_point.clone();
+  if entry_point.start_route.trim().is_empty() {
+    entry_point.start_route = DEFAULT_START_ROUTE.into();
+  }
+  // `</script>` inside a string literal would end the inline script early.
+  let json = serde_json::to_string(&entry_point)?.replace("</", "<\\/");
+  Ok(ENTRY_POINT_SCRIPT.replace("__ENTRY_POINT__", &json))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bundle::site::patch_site_index;
+  use tempfile::tempdir;
+
+  #[test]
+  fn writes_variants_of_the_patched_index() -> Result<()> {
+    let dir = tempdir()?;
+    let layout = OfflineProjectLayout::default();
+    let assets = dir.path().join(layout.entry_assets_dir());
+    fs::create_dir_all(&assets)?;
+    fs::write(assets.join("app.js"), "function __wbg_init() {}")?;
+    fs::write(assets.join("app_bg.wasm"), "wasm")?;
+    fs::write(
+      dir.path().join(&layout.index_html_file),
+      r#"<html><head><script type="module" src="/./assets/app.js"></script></head><body></body></html>"#,
+    )?;
+
+    let entry_points = [EntryPoint {
+      file: "kiosk.html".into(),
+      fullscreen: true,
+      idle_reset_secs: 120,
+      flags: vec!["</script>".into()],
+      ..EntryPoint::default()
+    }];
+    assert!(write_entry_points(&layout, dir.path(), &entry_points).is_err());
+
+    patch_site_index(&layout, dir.path())?;
+    let index = fs::read_to_string(dir.path().join(&layout.index_html_file))?;
+    let written = write_entry_points(&layout, dir.path(), &entry_points)?;
+    assert_eq!(written, [dir.path().join("kiosk.html")]);
+
+    let page = fs::read_to_string(&written[0])?;
+    assert!(page.contains(r##""fullscreen":true,"idleResetSecs":120,"startRoute":"#/""##));
+    assert!(page.contains(r#""flags":["<\/script>"]"#));
+    let script = page.find("offline-entry-point").unwrap();
+    assert!(script < page.find("</head>").unwrap());
+    assert!(page.starts_with(&index[..index.find("</head>").unwrap()]));
+    Ok(())
+  }
+}
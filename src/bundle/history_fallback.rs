@@ -0,0 +1,184 @@
+//! Fallback files for bundles served over HTTP with history-mode (path) routing.
+//!
+//! A static server answers a refresh on `/programs/intro` with a 404 because no such file
+//! exists. [`write_history_fallback`] writes a `404.html` copy of the patched index, which GitHub
+//! Pages and most static hosts serve for unknown paths, a `_redirects` file rewriting every path
+//! to the index for Netlify-style hosts, and optionally a `<route>/index.html` copy per route so
+//! servers without rewrites also find a page. Every copy gets a `<base href>` so its relative
+//! asset URLs resolve from any depth.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, anyhow};
+
+use crate::bundle::PATCH_MARKER;
+use crate::bundle::site::set_base_href;
+use crate::project::OfflineProjectLayout;
+
+/// Page most static hosts serve for paths without a file.
+pub const NOT_FOUND_PAGE: &str = "404.html";
+
+/// Rewrite rules read by Netlify and compatible hosts.
+pub const REDIRECTS_FILE: &str = "_redirects";
+
+/// Fallback files written by [`write_history_fallback`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryFallback {
+  /// URL path the site root is served from, e.g. `/` or `/site/`; the slashes around it are
+  /// optional.
+  pub base_path: String,
+  /// Routes getting their own `index.html` copy, e.g. `/programs/intro`; see [`expand_routes`].
+  pub routes: Vec<String>,
+}
+
+/// Routes for `template`, e.g. `/programs/{collection}` or `/programs/{collection}/{entry}`,
+/// filled in from `(collection id, entry id)` pairs, in order and without duplicates.
+pub fn expand_routes<'a>(
+  template: &str,
+  entries: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> Vec<String> {
+  let mut seen = BTreeSet::new();
+  entries
+    .into_iter()
+    .map(|(collection, entry)| {
+      template
+        .replace("{collection}", collection)
+        .replace("{entry}", entry)
+    })
+    .filter(|route| seen.insert(route.clone()))
+    .collect()
+}
+
+/// Write the `404.html`, `_redirects` and per-route copies of the patched index in `site_root`.
+///
+/// Copies from an earlier run are overwritten, while other existing files at a route's
+/// `index.html` are left alone and reported as an error. Fails when the index has not been
+/// patched yet.
+pub fn write_history_fallback(
+  layout: &OfflineProjectLayout,
+  site_root: &Path,
+  fallback: &HistoryFallback,
+) -> Result<Vec<PathBuf>> {
+  let index_path = site_root.join(&layout.index_html_file);
+  let index = fs::read_to_string(&index_path)
+    .with_context(|| format!("failed to read {}", index_path.display()))?;
+  if !index.contains(PATCH_MARKER) {
+    return Err(anyhow!(
+      "{} must be patched before history fallback pages are written",
+      index_path.display()
+    ));
+  }
+
+  let mut pages = Vec::new();
+  for route in &fallback.routes {
+    let relative = route.trim_matches('/');
+    if relative.is_empty() {
+      continue;
+    }
+    if relative.split('/').any(|segment| {
+      segment.is_empty() || segment == "." || segment == ".." || segment.contains('\\')
+    }) {
+      return Err(anyhow!("history route {route:?} is not a plain path"));
+    }
+    let path = site_root.join(relative).join(&layout.index_html_file);
+    if path.exists()
+      && !fs::read_to_string(&path).is_ok_and(|existing| existing.contains(PATCH_MARKER))
+    {
+      return Err(anyhow!(
+        "{} already exists and is not a copy of the patched index",
+        path.display()
+      ));
+    }
+    pages.push(path);
+  }
+
+  let base_path = format!("/{}/", fallback.base_path.trim_matches('/')).replace("//", "/");
+  let page = set_base_href(&index, &base_path)?;
+  let redirects = format!(
+    "{base_path}*    {base_path}{}    200\n",
+    layout.index_html_file
+  );
+  let mut written = Vec::new();
+  for (path, contents) in [
+    (site_root.join(NOT_FOUND_PAGE), page.as_str()),
+    (site_root.join(REDIRECTS_FILE), redirects.as_str()),
+  ]
+  .into_iter()
+  .chain(pages.into_iter().map(|path| (path, page.as_str())))
+  {
+    if let Some(dir) = path.parent() {
+      fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    }
+    fs::write(&path, contents).with_context(|| format!("failed to write {}", path.display()))?;
+    written.push(path);
+  }
+  Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bundle::site::patch_site_index;
+  use tempfile::tempdir;
+
+  #[test]
+  fn writes_fallback_pages_for_history_routes() -> Result<()> {
+    let dir = tempdir()?;
+    let layout = OfflineProjectLayout::default();
+    let assets = dir.path().join(layout.entry_assets_dir());
+    fs::create_dir_all(&assets)?;
+    fs::write(assets.join("app.js"), "function __wbg_init() {}")?;
+    fs::write(assets.join("app_bg.wasm"), "wasm")?;
+    fs::write(
+      dir.path().join(&layout.index_html_file),
+      r#"<html><head><script type="module" src="/./assets/app.js"></script></head><body></body></html>"#,
+    )?;
+    let routes = expand_routes("/programs/{collection}", [
+      ("intro", "a"),
+      ("intro", "b"),
+      ("advanced", "a"),
+    ]);
+    assert_eq!(routes, ["/programs/intro", "/programs/advanced"]);
+    let fallback = HistoryFallback {
+      base_path: "site".into(),
+      routes,
+    };
+    assert!(write_history_fallback(&layout, dir.path(), &fallback).is_err());
+
+    patch_site_index(&layout, dir.path())?;
+    let written = write_history_fallback(&layout, dir.path(), &fallback)?;
+    assert_eq!(written, [
+      dir.path().join("404.html"),
+      dir.path().join("_redirects"),
+      dir.path().join("programs/intro/index.html"),
+      dir.path().join("programs/advanced/index.html"),
+    ]);
+    let page = fs::read_to_string(dir.path().join("404.html"))?;
+    assert!(page.contains(r#"<base href="/site/">"#));
+    assert!(page.contains(r#"<script defer src="assets/app.js"></script>"#));
+    assert_eq!(
+      fs::read_to_string(dir.path().join("_redirects"))?,
+      "/site/*    /site/index.html    200\n"
+    );
+    assert_eq!(
+      write_history_fallback(&layout, dir.path(), &fallback)?.len(),
+      4
+    );
+
+    fs::create_dir_all(dir.path().join("notes"))?;
+    fs::write(dir.path().join("notes/index.html"), "hand written")?;
+    let fallback = HistoryFallback {
+      base_path: "/".into(),
+      routes: vec!["/notes".into()],
+    };
+    assert!(write_history_fallback(&layout, dir.path(), &fallback).is_err());
+    let fallback = HistoryFallback {
+      base_path: "/".into(),
+      routes: vec!["/../outside".into()],
+    };
+    assert!(write_history_fallback(&layout, dir.path(), &fallback).is_err());
+    Ok(())
+  }
+}
@@ -1,17 +1,87 @@
 //! Mutations applied to the generated JavaScript bootstrap for offline use.
 
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 use anyhow::{Context, Result, anyhow};
 use base64::{Engine as _, engine::general_purpose};
 use regex::Regex;
+use serde::Serialize;
 use serde_json::Value;
 
+use crate::bundle::site::{PatchedSiteIndex, SitePatchMode, SitePatchOptions};
+use crate::bundle::{PATCH_MARKER, PATCH_VERSION};
 use crate::project::OfflineProjectLayout;
 
+/// Raw bytes per base64 chunk of [`WasmEncoding::Chunked`]; a multiple of three so every chunk
+/// decodes on its own.
+pub const WASM_CHUNK_BYTES: usize = 48 * 1024;
+
+/// How the main WebAssembly module is embedded into the patched JavaScript module.
+///
+/// Sizes are relative to the `.wasm` file; the encoding and both sizes of a build are recorded
+/// in [`BuildStats::wasm`](crate::report::BuildStats::wasm) and the time spent in `patch/js`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WasmEncoding {
+  /// One base64 string decoded with `atob`: about 1.33x on disk, and startup holds the base64
+  /// literal, the decoded binary string and the bytes at once.
+  #[default]
+  Base64,
+  /// A string literal with one character per byte, read with `charCodeAt` and no `atob`: about
+  /// 1.0x to 1.5x on disk depending on how many bytes are above 0x7f, which take two bytes in
+  /// UTF-8. The page must load the script as UTF-8.
+  Latin1,
+  /// Base64 split into [`WASM_CHUNK_BYTES`] chunks decoded straight into the final buffer:
+  /// the size of [`Base64`](Self::Base64), but only one chunk is held as a binary string.
+  /// Decoding starts with the bootstrap and yields to the page about every 50 ms, so a
+  /// [`SplashScreen`](crate::bundle::site::SplashScreen) can show its progress.
+  Chunked,
+  /// The bytes appended after the code inside a trailing comment with a length header and read
+  /// back through `Function.prototype.toString`: the size of [`Latin1`](Self::Latin1), and the
+  /// parser skips the payload instead of tokenizing a large string literal.
+  TrailingBlob,
+}
+
+impl WasmEncoding {
+  /// Name used in configuration and reports, e.g. `trailing-blob`.
+  pub fn as_str(self) -> &'static str {
+    match self {
+      Self::Base64 => "base64",
+      Self::Latin1 => "latin1",
+      Self::Chunked => "chunked",
+      Self::TrailingBlob => "trailing-blob",
+    }
+  }
+}
+
+/// Files [`patch_site_module`] writes for the patched JavaScript module.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ModuleOutput {
+  /// Only the classic script loaded by the patched index.
+  #[default]
+  Classic,
+  /// The classic script plus an ES module next to it, written by [`write_module_wrapper`], so
+  /// host pages embedding the app in a larger portal can still `import` its exports.
+  Dual,
+}
+
+/// Size of the WebAssembly module embedded by [`patch_js_module_with`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct WasmEmbedding {
+  /// Encoding used.
+  pub encoding: WasmEncoding,
+  /// Size of the `.wasm` file.
+  pub wasm_bytes: u64,
+  /// Bytes the encoded payload adds to the JavaScript file.
+  pub embedded_bytes: u64,
+}
+
 /// Patch the generated JavaScript module so it can bootstrap without a network request.
+///
+/// Uses [`WasmEncoding::Base64`]; see [`patch_js_module_with`].
 pub fn patch_js_module<F>(
   layout: &OfflineProjectLayout,
   site_root: &Path,
@@ -19,6 +89,38 @@ pub fn patch_js_module<F>(
   wasm_name: &str,
   resolve_binary_name: F,
 ) -> Result<()>
+where
+  F: FnOnce() -> Result<String>,
+{
+  patch_js_module_with(
+    layout,
+    site_root,
+    js_name,
+    wasm_name,
+    WasmEncoding::Base64,
+    resolve_binary_name,
+  )?;
+  Ok(())
+}
+
+/// Variant of [`patch_js_module`] embedding the WebAssembly module with `encoding`.
+///
+/// The patched file starts with a marker comment; patching it again is a no-op, while files
+/// patched by an incompatible version are rejected.
+///
+/// Static imports of local modules, such as the `snippets/` directory produced for
+/// `#[wasm_bindgen(module = "...")]`, are inlined because the patched bootstrap is loaded as a
+/// classic script. `resolve_binary_name` is only called when the module does not reference `wasm_name`
+/// directly, e.g. when the wasm file was renamed after the build. Returns `None` when the module
+/// was already patched.
+pub fn patch_js_module_with<F>(
+  layout: &OfflineProjectLayout,
+  site_root: &Path,
+  js_name: &str,
+  wasm_name: &str,
+  encoding: WasmEncoding,
+  resolve_binary_name: F,
+) -> Result<Option<WasmEmbedding>>
 where
   F: FnOnce() -> Result<String>,
 {
@@ -26,13 +128,26 @@ where
   let mut text = fs::read_to_string(&js_path)
     .with_context(|| format!("failed to read {}", js_path.display()))?;
 
+  if is_patched(&js_path, &text)? {
+    return Ok(None);
+  }
+
+  let mut inlined = BTreeMap::new();
+  let mut modules = String::new();
+  text = inline_local_imports(&text, &js_path, site_root, &mut inlined, &mut modules)?;
+  text.insert_str(0, &modules);
+
   let assets_prefix = format!("{}/", layout.entry_assets_dir());
   text = text.replace(
     &format!("\"/./{}", assets_prefix),
     &format!("\"{}", assets_prefix),
   );
 
-  let export_pattern = Regex::new(r"export\{[^}]+\};?$").expect("invalid export regex");
+  let export_pattern = Regex::new(r"export\{([^}]+)\};?$").expect("invalid export regex");
+  let bindings = export_pattern
+    .captures(&text)
+    .map(|caps| export_bindings(&caps[1]))
+    .unwrap_or_default();
   text = export_pattern.replace_all(&text, "").into_owned();
 
   let import_meta_pattern =
@@ -47,36 +162,51 @@ const importMeta={url:__offlineScript?__offlineScript.src:window.location.href,m
   let wasm_path = site_root.join(layout.entry_assets_dir()).join(wasm_name);
   let wasm_bytes =
     fs::read(&wasm_path).with_context(|| format!("failed to read {}", wasm_path.display()))?;
-  let wasm_base64 = general_purpose::STANDARD.encode(wasm_bytes);
-
+  let embedded = embed_wasm(&wasm_bytes, encoding);
   let decoder_snippet = format!(
-    "const __offlineWasmBytes=(function(){{const binary=atob('{encoded}');\
-const length=binary.length;const bytes=new Uint8Array(length);\
-for(let i=0;i<length;i++){{bytes[i]=binary.charCodeAt(i);}}\
-return bytes;}})();window.__pivotOfflineWasm=__offlineWasmBytes;\
-globalThis.__pivotOfflineWasm=__offlineWasmBytes;",
-    encoded = wasm_base64,
+    "const __offlineWasmBytes={};const __offlineWasmReady={};\
+window.__pivotOfflineWasm=__offlineWasmBytes;globalThis.__pivotOfflineWasm=__offlineWasmBytes;",
+    embedded.decoder, embedded.ready
   );
+  let chunk_snippet = split_chunk_snippet(&site_root.join(layout.entry_assets_dir()), wasm_name)?;
   text = text.replace(
     "let wasm;",
-    format!("let wasm;{decoder}", decoder = decoder_snippet).as_str(),
+    format!("let wasm;{decoder_snippet}{chunk_snippet}").as_str(),
   );
 
-  let binary_name = resolve_binary_name()?;
-  let wasm_url_pattern = Regex::new(&format!(
-    r#"new URL\("{}_bg\.wasm",importMeta\.url\)"#,
-    regex::escape(&binary_name)
-  ))
-  .expect("invalid wasm URL regex");
+  // Release builds hash file names (`app_bg-abc123.wasm`), so match the URL of the wasm file
+  // that is actually shipped and only fall back to the binary name when it is not referenced.
+  let wasm_url_pattern = Regex::new(r#"new URL\("(?:\./)?([^"/]+\.wasm)",importMeta\.url\)"#)
+    .expect("invalid wasm URL regex");
+  let references_wasm_name = wasm_url_pattern
+    .captures_iter(&text)
+    .any(|caps| &caps[1] == wasm_name);
+  let wasm_file_pattern = if references_wasm_name {
+    Regex::new(&format!("^{}$", regex::escape(wasm_name)))
+  } else {
+    let binary_name = resolve_binary_name()?;
+    Regex::new(&format!(
+      r"^{}_bg(?:-[0-9A-Za-z_]+)?\.wasm$",
+      regex::escape(&binary_name)
+    ))
+  }
+  .expect("invalid wasm file regex");
   text = wasm_url_pattern
-    .replace_all(&text, "__offlineWasmBytes")
+    .replace_all(&text, |caps: &regex::Captures| {
+      if wasm_file_pattern.is_match(&caps[1]) {
+        "__offlineWasmBytes".to_string()
+      } else {
+        caps[0].to_string()
+      }
+    })
     .into_owned();
 
   let bootstrap_pattern = Regex::new(
     r#"(?s)(?:window\.|globalThis\.)?__wasm_split_main_initSync=initSync;__wbg_init\(\{module_or_path:"[^"]+"\}\)\.then\(wasm=>\{.*\}\);"#,
   )
   .expect("invalid bootstrap regex");
-  let bootstrap_replacement = "const __offlineInit=(bytes=__offlineWasmBytes)=>__wbg_init({module_or_path:bytes,module:bytes}).then(wasm=>{\
+  let bootstrap_replacement = "const __offlineInit=(bytes=__offlineWasmBytes)=>__offlineWasmReady(window.__offlineProgress)\
+.then(()=>__wbg_init({module_or_path:bytes,module:bytes})).then(wasm=>{\
 window.__dx_mainWasm=wasm;globalThis.__dx_mainWasm=wasm;if(wasm.__wbindgen_start===undefined){wasm.main();}return wasm;});\
 window.__wasm_split_main_initSync=initSync;globalThis.__wasm_split_main_initSync=initSync;\
 window.__dx___wbg_get_imports=__wbg_get_imports;globalThis.__dx___wbg_get_imports=__wbg_get_imports;\
@@ -86,9 +216,524 @@ globalThis.__dx_mainInit=__offlineInit;";
     .replace_all(&text, bootstrap_replacement)
     .into_owned();
 
+  // The default export starts the app like the loader once the bootstrap was replaced.
+  let has_init = text.contains("const __offlineInit=");
+  text.push_str(&exports_record(bindings.into_iter().map(
+    |(exported, local)| {
+      if exported == "default" && has_init {
+        (exported, "__offlineInit".to_string())
+      } else {
+        (exported, local)
+      }
+    },
+  )));
+
+  text.insert_str(0, &format!("/*{PATCH_MARKER} v{PATCH_VERSION}*/\n"));
+  text.push_str(&embedded.trailer);
   fs::write(&js_path, text).with_context(|| format!("failed to write {}", js_path.display()))?;
 
-  Ok(())
+  Ok(Some(WasmEmbedding {
+    encoding,
+    wasm_bytes: wasm_bytes.len() as u64,
+    embedded_bytes: embedded.payload_bytes as u64,
+  }))
+}
+
+/// Patch the module [`patch_site_index_with`](crate::bundle::site::patch_site_index_with)
+/// located, as expected by `options.mode`.
+pub fn patch_site_module<F>(
+  layout: &OfflineProjectLayout,
+  site_root: &Path,
+  patched: &PatchedSiteIndex,
+  options: &SitePatchOptions,
+  resolve_binary_name: F,
+) -> Result<Option<WasmEmbedding>>
+where
+  F: FnOnce() -> Result<String>,
+{
+  let (embedding, js_path) = match options.mode {
+    SitePatchMode::Dioxus => (
+      patch_js_module_with(
+        layout,
+        site_root,
+        &patched.js_name,
+        &patched.wasm_name,
+        options.wasm_encoding,
+        resolve_binary_name,
+      )?,
+      site_root
+        .join(layout.entry_assets_dir())
+        .join(&patched.js_name),
+    ),
+    SitePatchMode::Generic => (
+      patch_generic_js_module(
+        site_root,
+        &patched.js_name,
+        &patched.wasm_name,
+        options.wasm_encoding,
+      )?,
+      site_root.join(&patched.js_name),
+    ),
+  };
+  if options.module_output == ModuleOutput::Dual {
+    write_module_wrapper(&js_path)?;
+  }
+  Ok(embedding)
+}
+
+/// Write `<stem>.mjs` next to the patched script at `js_path`: an ES module that runs the
+/// script and re-exports the exports it recorded, `default` starting the application.
+///
+/// Importing the wrapper evaluates the script as a module, so a page must not also load it
+/// through the patched index.
+pub fn write_module_wrapper(js_path: &Path) -> Result<PathBuf> {
+  let text =
+    fs::read_to_string(js_path).with_context(|| format!("failed to read {}", js_path.display()))?;
+  let record_pattern =
+    Regex::new(r"const __offlineExports=\{([^}]*)\};").expect("invalid exports regex");
+  let record = record_pattern.captures(&text).ok_or_else(|| {
+    anyhow!(
+      "{} does not record its exports; patch it first",
+      js_path.display()
+    )
+  })?;
+  let file_name = js_path
+    .file_name()
+    .map(|name| name.to_string_lossy().into_owned())
+    .ok_or_else(|| anyhow!("{} has no file name", js_path.display()))?;
+
+  let mut wrapper = format!(
+    "/*{PATCH_MARKER} v{PATCH_VERSION}*/\nimport \"./{file_name}\";\n\
+const __offlineExports = globalThis.__offlineExports;\n"
+  );
+  for name in record[1]
+    .split(',')
+    .filter_map(|pair| pair.split_once(':'))
+    .map(|(name, _)| name)
+  {
+    if name == "default" {
+      wrapper.push_str("export default __offlineExports.default;\n");
+    } else {
+      wrapper.push_str(&format!("export const {name} = __offlineExports.{name};\n"));
+    }
+  }
+  let path = js_path.with_extension("mjs");
+  fs::write(&path, wrapper).with_context(|| format!("failed to write {}", path.display()))?;
+  Ok(path)
+}
+
+/// `(exported, local)` names of an `export { ... }` list.
+fn export_bindings(list: &str) -> Vec<(String, String)> {
+  list
+    .split(',')
+    .map(str::trim)
+    .filter(|binding| !binding.is_empty())
+    .map(|binding| match binding.split_once(" as ") {
+      Some((local, exported)) => (exported.trim().to_string(), local.trim().to_string()),
+      None => (binding.to_string(), binding.to_string()),
+    })
+    .collect()
+}
+
+/// Statements publishing `bindings` as `window.__offlineExports` for [`write_module_wrapper`].
+fn exports_record(bindings: impl IntoIterator<Item = (String, String)>) -> String {
+  let fields: Vec<String> = bindings
+    .into_iter()
+    .map(|(exported, local)| format!("{exported}:{local}"))
+    .collect();
+  format!(
+    "const __offlineExports={{{}}};window.__offlineExports=__offlineExports;\
+globalThis.__offlineExports=__offlineExports;",
+    fields.join(",")
+  )
+}
+
+/// Patch a plain `wasm-bindgen` `web` target module, e.g. from trunk, for
+/// [`SitePatchMode::Generic`].
+///
+/// `js_name` and `wasm_name` are relative to `site_root`. The module is turned into a classic
+/// script exposing its exports as `window.__offlineModule` and
+/// `window.__offlineInit()`, which runs the default `init` export with the embedded
+/// WebAssembly module. Returns `None` when the module was already patched.
+pub fn patch_generic_js_module(
+  site_root: &Path,
+  js_name: &str,
+  wasm_name: &str,
+  encoding: WasmEncoding,
+) -> Result<Option<WasmEmbedding>> {
+  let js_path = site_root.join(js_name);
+  let text = fs::read_to_string(&js_path)
+    .with_context(|| format!("failed to read {}", js_path.display()))?;
+  if is_patched(&js_path, &text)? {
+    return Ok(None);
+  }
+
+  let mut inlined = BTreeMap::new();
+  let mut modules = String::new();
+  let text = inline_local_imports(&text, &js_path, site_root, &mut inlined, &mut modules)?;
+  let import_meta_url = Regex::new(r"import\.meta\.url").expect("invalid import.meta regex");
+  let text = import_meta_url.replace_all(&text, "__offlineScriptUrl");
+  let text = text.replace("import.meta", "({url:__offlineScriptUrl})");
+
+  let wasm_path = site_root.join(wasm_name);
+  let wasm_bytes =
+    fs::read(&wasm_path).with_context(|| format!("failed to read {}", wasm_path.display()))?;
+  let embedded = embed_wasm(&wasm_bytes, encoding);
+  // Older `wasm-bindgen` versions take the module directly instead of an options object.
+  let init_argument = if text.contains("module_or_path") {
+    "{module_or_path:__offlineWasmBytes}"
+  } else {
+    "__offlineWasmBytes"
+  };
+
+  let (module, exported) = wrap_module("__offlineModule", &text);
+  let output = format!(
+    "/*{PATCH_MARKER} v{PATCH_VERSION}*/\n\
+const __offlineScriptUrl=document.currentScript?document.currentScript.src:window.location.href;\
+const __offlineWasmBytes={};const __offlineWasmReady={};\n{modules}{}\
+window.__offlineModule=__offlineModule;globalThis.__offlineModule=__offlineModule;\
+window.__offlineInit=()=>__offlineWasmReady(window.__offlineProgress)\
+.then(()=>__offlineModule.default({init_argument}));\
+globalThis.__offlineInit=window.__offlineInit;{}{}",
+    embedded.decoder,
+    embedded.ready,
+    module,
+    exports_record(exported.into_iter().map(|name| {
+      let local = if name == "default" {
+        "window.__offlineInit".to_string()
+      } else {
+        format!("__offlineModule.{name}")
+      };
+      (name, local)
+    })),
+    embedded.trailer
+  );
+  fs::write(&js_path, output).with_context(|| format!("failed to write {}", js_path.display()))?;
+
+  Ok(Some(WasmEmbedding {
+    encoding,
+    wasm_bytes: wasm_bytes.len() as u64,
+    embedded_bytes: embedded.payload_bytes as u64,
+  }))
+}
+
+/// Whether the module at `js_path` was already patched by this version.
+///
+/// Modules patched by an incompatible version, or without a marker, are rejected.
+fn is_patched(js_path: &Path, text: &str) -> Result<bool> {
+  let marker_pattern = Regex::new(&format!(r"/\*{}\s+v(\d+)\*/", regex::escape(PATCH_MARKER)))
+    .expect("invalid marker regex");
+  if let Some(caps) = marker_pattern.captures(text) {
+    if caps[1] == PATCH_VERSION.to_string() {
+      return Ok(true);
+    }
+    return Err(anyhow!(
+      "{} was patched by an incompatible bundler version (format v{}); rebuild it with `dx build`",
+      js_path.display(),
+      &caps[1]
+    ));
+  }
+  if text.contains("const __offlineWasmBytes=") {
+    return Err(anyhow!(
+      "{} is already patched but has no patch marker; rebuild it with `dx build`",
+      js_path.display()
+    ));
+  }
+  Ok(false)
+}
+
+/// Decoder expression and trailing code for an embedded WebAssembly module.
+struct EmbeddedWasm {
+  /// Expression evaluating to the module's `Uint8Array`.
+  decoder: String,
+  /// Function taking an optional `(loaded, total)` progress callback and returning a promise
+  /// resolved once `__offlineWasmBytes` holds the whole module.
+  ready: String,
+  /// Code appended to the end of the file.
+  trailer: String,
+  /// UTF-8 size of the encoded bytes in the file.
+  payload_bytes: usize,
+}
+
+fn embed_wasm(bytes: &[u8], encoding: WasmEncoding) -> EmbeddedWasm {
+  match encoding {
+    WasmEncoding::Base64 => {
+      let encoded = general_purpose::STANDARD.encode(bytes);
+      EmbeddedWasm {
+        decoder: format!(
+          "(function(){{const binary=atob('{encoded}');\
+const length=binary.length;const bytes=new Uint8Array(length);\
+for(let i=0;i<length;i++){{bytes[i]=binary.charCodeAt(i);}}return bytes;}})()"
+        ),
+        ready: DECODED_READY.into(),
+        trailer: String::new(),
+        payload_bytes: encoded.len(),
+      }
+    }
+    WasmEncoding::Latin1 => {
+      let literal = latin1_literal(bytes);
+      EmbeddedWasm {
+        decoder: format!(
+          "(function(){{const text='{literal}';const bytes=new Uint8Array(text.length);\
+for(let i=0;i<text.length;i++){{bytes[i]=text.charCodeAt(i);}}return bytes;}})()"
+        ),
+        ready: DECODED_READY.into(),
+        trailer: String::new(),
+        payload_bytes: literal.len(),
+      }
+    }
+    WasmEncoding::Chunked => {
+      let chunks: Vec<String> = bytes
+        .chunks(WASM_CHUNK_BYTES)
+        .map(|chunk| format!("'{}'", general_purpose::STANDARD.encode(chunk)))
+        .collect();
+      EmbeddedWasm {
+        decoder: format!("new Uint8Array({})", bytes.len()),
+        ready: format!(
+          "(function(){{let pending;return(progress)=>pending||(pending=(async()=>{{\
+const chunks=[{}];const bytes=__offlineWasmBytes;let offset=0;let yielded=Date.now();\
+for(const chunk of chunks){{const binary=atob(chunk);\
+for(let i=0;i<binary.length;i++){{bytes[offset++]=binary.charCodeAt(i);}}\
+if(progress){{progress(offset,bytes.length);}}\
+if(Date.now()-yielded>50){{await new Promise((resolve)=>setTimeout(resolve,0));yielded=Date.now();}}}}}})());}})()",
+          chunks.join(",")
+        ),
+        trailer: String::new(),
+        payload_bytes: chunks.iter().map(|chunk| chunk.len() - 2).sum(),
+      }
+    }
+    WasmEncoding::TrailingBlob => {
+      let payload = blob_payload(bytes);
+      EmbeddedWasm {
+        decoder: format!(
+          "(function(){{const source=__offlineWasmBlob.toString();\
+const header=source.indexOf('{BLOB_HEADER}')+{header_len};const start=source.indexOf('\\n',header)+1;\
+const bytes=new Uint8Array(Number(source.slice(header,start-1)));\
+for(let i=0;i<bytes.length;i++){{const code=source.charCodeAt(start+i);\
+bytes[i]=code===256?42:code===257?13:code;}}return bytes;}})()",
+          header_len = BLOB_HEADER.len(),
+        ),
+        ready: DECODED_READY.into(),
+        trailer: format!(
+          "\nfunction __offlineWasmBlob(){{/*{BLOB_HEADER}{}\n{payload}*/}}\n",
+          bytes.len()
+        ),
+        payload_bytes: payload.len(),
+      }
+    }
+  }
+}
+
+/// `ready` function of encodings decoded while the script is evaluated, reporting completion.
+const DECODED_READY: &str = "(progress)=>{if(progress){progress(__offlineWasmBytes.length,\
+__offlineWasmBytes.length);}return Promise.resolve();}";
+
+/// Start of the comment holding a [`WasmEncoding::TrailingBlob`] payload, followed by the byte
+/// length and a newline.
+const BLOB_HEADER: &str = "offline-wasm:";
+
+/// Body of a single-quoted JavaScript string with one character per byte.
+fn latin1_literal(bytes: &[u8]) -> String {
+  let mut literal = String::with_capacity(bytes.len() * 3 / 2);
+  for &byte in bytes {
+    match byte {
+      b'\\' => literal.push_str("\\\\"),
+      b'\'' => literal.push_str("\\'"),
+      b'\n' => literal.push_str("\\n"),
+      b'\r' => literal.push_str("\\r"),
+      _ => literal.push(char::from(byte)),
+    }
+  }
+  literal
+}
+
+/// One character per byte for a block comment: `*` and carriage returns are moved to U+0100 and
+/// U+0101, so the payload can neither close the comment nor have its line endings normalised.
+fn blob_payload(bytes: &[u8]) -> String {
+  bytes
+    .iter()
+    .map(|&byte| match byte {
+      b'*' => '\u{100}',
+      b'\r' => '\u{101}',
+      _ => char::from(byte),
+    })
+    .collect()
+}
+
+/// Names of the lazily loaded `.wasm` chunks produced by wasm-split, i.e. every WebAssembly
+/// file in `assets_dir` other than the main module.
+pub fn find_split_chunks(assets_dir: &Path, wasm_name: &str) -> Result<Vec<String>> {
+  let mut chunks: Vec<String> = fs::read_dir(assets_dir)
+    .with_context(|| format!("failed to read assets directory: {}", assets_dir.display()))?
+    .filter_map(|entry| entry.ok())
+    .filter(|entry| entry.file_type().is_ok_and(|ft| ft.is_file()))
+    .map(|entry| entry.file_name().to_string_lossy().into_owned())
+    .filter(|name| name.ends_with(".wasm") && name != wasm_name)
+    .collect();
+  chunks.sort();
+  Ok(chunks)
+}
+
+/// Embed wasm-split chunks and serve them from memory when the split loader fetches them.
+///
+/// Browsers refuse `fetch` for `file://` URLs, so lazy routes would never load their chunk.
+/// The returned snippet keeps each chunk base64 encoded until it is requested and answers
+/// `fetch` calls whose file name matches a chunk with an `application/wasm` response. Returns
+/// an empty string when the build has no chunks.
+fn split_chunk_snippet(assets_dir: &Path, wasm_name: &str) -> Result<String> {
+  let chunks = find_split_chunks(assets_dir, wasm_name)?;
+  if chunks.is_empty() {
+    return Ok(String::new());
+  }
+
+  let mut encoded = serde_json::Map::new();
+  for chunk in chunks {
+    let path = assets_dir.join(&chunk);
+    let bytes = fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+    encoded.insert(
+      chunk,
+      Value::String(general_purpose::STANDARD.encode(bytes)),
+    );
+  }
+
+  Ok(format!(
+    "const __offlineWasmChunks={chunks};\
+const __offlineWasmChunk=(name)=>{{const encoded=__offlineWasmChunks[name];\
+if(encoded===undefined){{return undefined;}}const binary=atob(encoded);\
+const bytes=new Uint8Array(binary.length);for(let i=0;i<binary.length;i++){{bytes[i]=binary.charCodeAt(i);}}\
+return bytes;}};window.__offlineWasmChunk=__offlineWasmChunk;globalThis.__offlineWasmChunk=__offlineWasmChunk;\
+if(typeof window.fetch==='function'){{const __offlineFetch=window.fetch.bind(window);\
+window.fetch=(input,init)=>{{const url=typeof Request!=='undefined'&&input instanceof Request?input.url:String(input);\
+const name=url.split(/[?#]/)[0].split('/').pop();\
+if(Object.prototype.hasOwnProperty.call(__offlineWasmChunks,name)){{\
+return Promise.resolve(new Response(__offlineWasmChunk(name),{{headers:{{'Content-Type':'application/wasm'}}}}));}}\
+return __offlineFetch(input,init);}};globalThis.fetch=window.fetch;}}",
+    chunks = Value::Object(encoded),
+  ))
+}
+
+/// Replace static imports of local modules with inlined copies of those modules.
+///
+/// Each module is wrapped in a function scope that returns its exports and appended to
+/// `modules`, dependencies first, so it is evaluated once even when imported from several
+/// places. `inlined` maps already inlined files to the name of the variable holding their
+/// exports.
+fn inline_local_imports(
+  text: &str,
+  module_path: &Path,
+  site_root: &Path,
+  inlined: &mut BTreeMap<PathBuf, String>,
+  modules: &mut String,
+) -> Result<String> {
+  let import_pattern = Regex::new(
+    r#"import\s*(?:\*\s*as\s+([A-Za-z_$][\w$]*)|\{([^}]*)\})\s*from\s*["']([^"']+)["'];?"#,
+  )
+  .expect("invalid import regex");
+
+  let mut output = String::with_capacity(text.len());
+  let mut last = 0;
+
+  for caps in import_pattern.captures_iter(text) {
+    let specifier = &caps[3];
+    let Some(path) = resolve_local_module(specifier, module_path, site_root) else {
+      continue;
+    };
+
+    let exports = match inlined.get(&path) {
+      Some(exports) => exports.clone(),
+      None => {
+        let source = fs::read_to_string(&path)
+          .with_context(|| format!("failed to read imported module {}", path.display()))?;
+        let exports = format!("__offlineModule{}", inlined.len());
+        inlined.insert(path.clone(), exports.clone());
+        let body = inline_local_imports(&source, &path, site_root, inlined, modules)?;
+        modules.push_str(&wrap_module(&exports, &body).0);
+        exports
+      }
+    };
+
+    let whole = caps.get(0).expect("regex match");
+    output.push_str(&text[last..whole.start()]);
+    if let Some(namespace) = caps.get(1) {
+      output.push_str(&format!("const {}={exports};", namespace.as_str()));
+    } else {
+      let bindings: Vec<String> = caps[2]
+        .split(',')
+        .map(str::trim)
+        .filter(|binding| !binding.is_empty())
+        .map(|binding| match binding.split_once(" as ") {
+          Some((name, local)) => format!("{}:{}", name.trim(), local.trim()),
+          None => binding.to_string(),
+        })
+        .collect();
+      output.push_str(&format!("const {{{}}}={exports};", bindings.join(",")));
+    }
+    last = whole.end();
+  }
+
+  output.push_str(&text[last..]);
+  Ok(output)
+}
+
+/// Map an import specifier to a file below `site_root`, ignoring bare and remote specifiers.
+fn resolve_local_module(specifier: &str, module_path: &Path, site_root: &Path) -> Option<PathBuf> {
+  let path = if specifier.starts_with("./") || specifier.starts_with("../") {
+    module_path.parent()?.join(specifier)
+  } else if specifier.starts_with('/') && !specifier.starts_with("//") {
+    site_root.join(specifier.trim_start_matches('/').trim_start_matches("./"))
+  } else {
+    return None;
+  };
+  path.is_file().then_some(path)
+}
+
+/// Turn an ES module body into a function scope returning its named exports, together with
+/// the exported names.
+fn wrap_module(exports: &str, body: &str) -> (String, Vec<String>) {
+  let declaration_pattern = Regex::new(
+    r"(?m)^(\s*)export\s+((?:async\s+)?function\*?|class|const|let|var)\s+([A-Za-z_$][\w$]*)",
+  )
+  .expect("invalid export declaration regex");
+  let list_pattern = Regex::new(r"export\s*\{([^}]*)\};?").expect("invalid export list regex");
+  let default_pattern =
+    Regex::new(r"(?m)^(\s*)export\s+default\s+").expect("invalid default regex");
+
+  let mut names: Vec<String> = Vec::new();
+  for caps in declaration_pattern.captures_iter(body) {
+    names.push(caps[3].to_string());
+  }
+  for caps in list_pattern.captures_iter(body) {
+    for (exported, local) in export_bindings(&caps[1]) {
+      names.push(if exported == local {
+        exported
+      } else {
+        format!("{exported}:{local}")
+      });
+    }
+  }
+
+  let mut body = declaration_pattern
+    .replace_all(body, "$1$2 $3")
+    .into_owned();
+  body = list_pattern.replace_all(&body, "").into_owned();
+  if default_pattern.is_match(&body) {
+    body = default_pattern
+      .replace(&body, "${1}const __offlineDefault=")
+      .into_owned();
+    names.push("default:__offlineDefault".into());
+  }
+
+  let exported = names
+    .iter()
+    .map(|name| name.split(':').next().unwrap_or_default().to_string())
+    .collect();
+  (
+    format!(
+      "const {exports}=(function(){{\n{body}\nreturn {{{}}};}})();\n",
+      names.join(",")
+    ),
+    exported,
+  )
 }
 
 /// Determine the primary binary target name from `cargo metadata`.
@@ -146,9 +791,13 @@ mod tests {
       offline_site_root: "site".into(),
       collections_dir_name: "programs".into(),
       offline_bundle_root: "target/offline-html".into(),
+      output_root: None,
       index_html_file: "index.html".into(),
       target_dir: "target".into(),
       offline_manifest_json: "offline_manifest.json".into(),
+      scan_max_depth: 32,
+      asset_extensions: Vec::new(),
+      excluded_extensions: Vec::new(),
     }
   }
 
@@ -172,6 +821,12 @@ mod tests {
     .unwrap();
 
     let updated = fs::read_to_string(&js_path).unwrap();
+    patch_js_module(&layout, dir.path(), "module.js", "module_bg.wasm", || {
+      Ok("module".into())
+    })
+    .unwrap();
+    assert_eq!(fs::read_to_string(&js_path).unwrap(), updated);
+    assert!(updated.starts_with("/*offline_dx_bundler:patched v1*/"));
     assert!(updated.contains("window.__dx_mainInit"));
     assert!(updated.contains("globalThis.__dx_mainInit"));
     assert!(updated.contains("__offlineWasmBytes"));
@@ -179,4 +834,221 @@ mod tests {
     assert!(!updated.contains("globalThis.const"));
     assert!(!updated.contains("new URL(\"module_bg.wasm\",importMeta.url)"));
   }
+
+  #[test]
+  fn writes_module_wrapper_re_exporting_the_bootstrap() {
+    let dir = tempdir().unwrap();
+    let layout = layout();
+    let assets_dir = dir.path().join(layout.entry_assets_dir());
+    fs::create_dir_all(&assets_dir).unwrap();
+    fs::write(
+      assets_dir.join("module.js"),
+      "let wasm;\nfunction initSync(){}\nfunction __wbg_init(){}\nwindow.__wasm_split_main_initSync=initSync;__wbg_init({module_or_path:\"module_bg.wasm\"}).then(wasm=>{wasm.main();});\nexport{initSync,__wbg_init as default};",
+    )
+    .unwrap();
+    fs::write(assets_dir.join("module_bg.wasm"), [0u8, 1, 2]).unwrap();
+
+    let patched = PatchedSiteIndex {
+      js_name: "module.js".into(),
+      wasm_name: "module_bg.wasm".into(),
+      module_scripts: Vec::new(),
+    };
+    let options = SitePatchOptions {
+      module_output: ModuleOutput::Dual,
+      ..SitePatchOptions::default()
+    };
+    patch_site_module(&layout, dir.path(), &patched, &options, || {
+      Ok("module".into())
+    })
+    .unwrap();
+
+    let script = fs::read_to_string(assets_dir.join("module.js")).unwrap();
+    assert!(!script.contains("export{"));
+    assert!(script.contains(
+      "const __offlineExports={initSync:initSync,default:__offlineInit};window.__offlineExports="
+    ));
+    assert_eq!(
+      fs::read_to_string(assets_dir.join("module.mjs")).unwrap(),
+      "/*offline_dx_bundler:patched v1*/\nimport \"./module.js\";\n\
+const __offlineExports = globalThis.__offlineExports;\n\
+export const initSync = __offlineExports.initSync;\n\
+export default __offlineExports.default;\n"
+    );
+  }
+
+  #[test]
+  fn patches_hashed_release_file_names() {
+    let dir = tempdir().unwrap();
+    let layout = layout();
+    let assets_dir = dir.path().join(layout.entry_assets_dir());
+    fs::create_dir_all(&assets_dir).unwrap();
+
+    let js_path = assets_dir.join("app-abc123.js");
+    fs::write(
+      &js_path,
+      "let wasm;\nfunction boot() {\n  new URL(\"app_bg-abc123.wasm\",importMeta.url);\n  new URL(\"other.wasm\",importMeta.url);\n}\n",
+    )
+    .unwrap();
+    fs::write(assets_dir.join("app_bg-abc123.wasm"), [0u8, 1, 2]).unwrap();
+
+    patch_js_module(
+      &layout,
+      dir.path(),
+      "app-abc123.js",
+      "app_bg-abc123.wasm",
+      || -> Result<String> { panic!("binary name should not be needed") },
+    )
+    .unwrap();
+
+    let updated = fs::read_to_string(&js_path).unwrap();
+    assert!(!updated.contains("app_bg-abc123.wasm"));
+    assert!(updated.contains("new URL(\"other.wasm\",importMeta.url)"));
+  }
+
+  #[test]
+  fn inlines_wasm_bindgen_snippets() {
+    let dir = tempdir().unwrap();
+    let layout = layout();
+    let assets_dir = dir.path().join(layout.entry_assets_dir());
+    let snippet_dir = assets_dir.join("snippets/app-1a2b/js");
+    fs::create_dir_all(&snippet_dir).unwrap();
+    fs::write(
+      snippet_dir.join("foo.js"),
+      "import { helper } from './helper.js';\nexport function greet(name) { return helper(name); }\nconst answer = 42;\nexport { answer as theAnswer };\n",
+    )
+    .unwrap();
+    fs::write(
+      snippet_dir.join("helper.js"),
+      "export const helper = (name) => `hi ${name}`;\n",
+    )
+    .unwrap();
+
+    let js_path = assets_dir.join("module.js");
+    fs::write(
+      &js_path,
+      "import { greet, theAnswer as answer } from './snippets/app-1a2b/js/foo.js';\nimport * as again from './snippets/app-1a2b/js/foo.js';\nlet wasm;\nnew URL(\"module_bg.wasm\",importMeta.url);\n",
+    )
+    .unwrap();
+    fs::write(assets_dir.join("module_bg.wasm"), [0u8]).unwrap();
+
+    patch_js_module(&layout, dir.path(), "module.js", "module_bg.wasm", || {
+      Ok("module".into())
+    })
+    .unwrap();
+
+    let updated = fs::read_to_string(&js_path).unwrap();
+    assert!(!updated.contains("import "));
+    assert!(!updated.contains("export "));
+    assert!(updated.contains("const {greet,theAnswer:answer}=__offlineModule0;"));
+    assert!(updated.contains("const again=__offlineModule0;"));
+    assert!(updated.contains("return {greet,theAnswer:answer};"));
+    assert!(updated.contains("const {helper}=__offlineModule1;"));
+    assert_eq!(updated.matches("__offlineModule1=(function()").count(), 1);
+  }
+
+  #[test]
+  fn embeds_wasm_split_chunks() {
+    let dir = tempdir().unwrap();
+    let layout = layout();
+    let assets_dir = dir.path().join(layout.entry_assets_dir());
+    fs::create_dir_all(&assets_dir).unwrap();
+
+    let js_path = assets_dir.join("module.js");
+    fs::write(
+      &js_path,
+      "let wasm;\nnew URL(\"module_bg.wasm\",importMeta.url);\n",
+    )
+    .unwrap();
+    fs::write(assets_dir.join("module_bg.wasm"), [0u8]).unwrap();
+    fs::write(assets_dir.join("chunk_1_route.wasm"), [7u8, 7]).unwrap();
+
+    assert_eq!(
+      find_split_chunks(&assets_dir, "module_bg.wasm").unwrap(),
+      vec!["chunk_1_route.wasm".to_string()]
+    );
+
+    patch_js_module(&layout, dir.path(), "module.js", "module_bg.wasm", || {
+      Ok("module".into())
+    })
+    .unwrap();
+
+    let updated = fs::read_to_string(&js_path).unwrap();
+    assert!(updated.contains(r#"const __offlineWasmChunks={"chunk_1_route.wasm":"Bwc="};"#));
+    assert!(updated.contains("window.fetch=(input,init)=>"));
+  }
+
+  #[test]
+  fn embeds_wasm_with_each_encoding() {
+    let dir = tempdir().unwrap();
+    let layout = layout();
+    let assets_dir = dir.path().join(layout.entry_assets_dir());
+    fs::create_dir_all(&assets_dir).unwrap();
+    let wasm: Vec<u8> = (0..=255u8).chain(*b"*/\r\n'\\").collect();
+    fs::write(assets_dir.join("module_bg.wasm"), &wasm).unwrap();
+
+    for (encoding, snippet) in [
+      (WasmEncoding::Base64, "atob('AAEC"),
+      (WasmEncoding::Latin1, "const text='\0\u{1}"),
+      (WasmEncoding::Chunked, "const chunks=['AAEC"),
+      (WasmEncoding::TrailingBlob, "__offlineWasmBlob.toString()"),
+    ] {
+      let js_path = assets_dir.join("module.js");
+      fs::write(
+        &js_path,
+        "let wasm;\nnew URL(\"module_bg.wasm\",importMeta.url);\n",
+      )
+      .unwrap();
+      let embedding = patch_js_module_with(
+        &layout,
+        dir.path(),
+        "module.js",
+        "module_bg.wasm",
+        encoding,
+        || Ok("module".into()),
+      )
+      .unwrap()
+      .unwrap();
+      assert_eq!(embedding.encoding, encoding);
+      assert_eq!(embedding.wasm_bytes, wasm.len() as u64);
+
+      let updated = fs::read_to_string(&js_path).unwrap();
+      assert!(updated.contains(snippet), "{encoding:?}");
+      if encoding == WasmEncoding::TrailingBlob {
+        let header = format!("/*{BLOB_HEADER}{}\n", wasm.len());
+        let start = updated.find(&header).unwrap() + header.len();
+        let payload = &updated[start..updated.rfind("*/}").unwrap()];
+        assert_eq!(payload.len() as u64, embedding.embedded_bytes);
+        let decoded: Vec<u8> = payload
+          .chars()
+          .map(|c| match c {
+            '\u{100}' => b'*',
+            '\u{101}' => b'\r',
+            _ => c as u8,
+          })
+          .collect();
+        assert_eq!(decoded, wasm);
+      }
+    }
+  }
+
+  #[test]
+  fn rejects_unmarked_or_incompatible_patched_modules() {
+    let dir = tempdir().unwrap();
+    let layout = layout();
+    let assets_dir = dir.path().join(layout.entry_assets_dir());
+    fs::create_dir_all(&assets_dir).unwrap();
+    fs::write(assets_dir.join("module_bg.wasm"), [0u8]).unwrap();
+
+    for contents in [
+      "let wasm;const __offlineWasmBytes=(function(){})();",
+      "/*offline_dx_bundler:patched v0*/\nlet wasm;",
+    ] {
+      fs::write(assets_dir.join("module.js"), contents).unwrap();
+      let error = patch_js_module(&layout, dir.path(), "module.js", "module_bg.wasm", || {
+        Ok("module".into())
+      })
+      .unwrap_err();
+      assert!(error.to_string().contains("rebuild it with `dx build`"));
+    }
+  }
 }
@@ -1,11 +1,13 @@
 //! Generate a tiny launcher HTML file for offline bundles with nested site roots.
 
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
 
+use crate::manifest::attribution::escape_html;
 use crate::project::OfflineProjectLayout;
+use crate::theme::ThemePalette;
 
 /// Write a root `index.html` that redirects into the bundled site when required.
 pub fn write_root_launcher(
@@ -25,7 +27,7 @@ pub fn write_root_launcher(
   let redirect_target = format!("{}/{}", trimmed_prefix, layout.index_html_file);
   let html = format!(
     r#"<!DOCTYPE html>
-<html>
+<html lang="en">
   <head>
     <meta charset=\"utf-8\">
     <title>Offline Bundle</title>
@@ -53,6 +55,150 @@ pub fn write_root_launcher(
   fs::write(&target, html).with_context(|| format!("failed to write {}", target.display()))
 }
 
+/// File name of the Windows launcher script written by [`write_launcher_scripts`].
+pub const WINDOWS_LAUNCHER: &str = "start.bat";
+/// File name of the macOS launcher script written by [`write_launcher_scripts`].
+pub const MACOS_LAUNCHER: &str = "start.command";
+/// File name of the Linux desktop entry written by [`write_launcher_scripts`].
+pub const LINUX_LAUNCHER: &str = "start.desktop";
+/// File name of the instructions page written by [`write_launcher_scripts`].
+pub const INSTRUCTIONS_PAGE: &str = "HOW-TO-OPEN.html";
+
+/// Write double-clickable launchers and an instructions page into the bundle root.
+///
+/// The scripts open the bundle's index page in the default browser using a path relative to
+/// the script, so they keep working wherever the USB stick is mounted. `title` is shown in the
+/// Linux desktop entry and the instructions page. Returns the written files.
+pub fn write_launcher_scripts(
+  layout: &OfflineProjectLayout,
+  root_dir: &Path,
+  site_prefix: &str,
+  title: &str,
+) -> Result<Vec<PathBuf>> {
+  write_themed_launcher_scripts(layout, root_dir, site_prefix, title, None)
+}
+
+/// Variant of [`write_launcher_scripts`] that brands the instructions page with `theme`.
+///
+/// The palette is injected as CSS variables and the logo, when set, is shown above the heading.
+pub fn write_themed_launcher_scripts(
+  layout: &OfflineProjectLayout,
+  root_dir: &Path,
+  site_prefix: &str,
+  title: &str,
+  theme: Option<&ThemePalette>,
+) -> Result<Vec<PathBuf>> {
+  fs::create_dir_all(root_dir)
+    .with_context(|| format!("failed to create {}", root_dir.display()))?;
+
+  let trimmed_prefix = site_prefix.trim_matches('/');
+  let index_path = if trimmed_prefix.is_empty() {
+    layout.index_html_file.clone()
+  } else {
+    format!("{}/{}", trimmed_prefix, layout.index_html_file)
+  };
+
+  let windows = format!(
+    "@echo off\r\nstart \"\" \"%~dp0{}\"\r\n",
+    index_path.replace('/', "\\")
+  );
+  let macos = format!("#!/bin/sh\ncd \"$(dirname \"$0\")\" && open \"{index_path}\"\n");
+  let linux = format!(
+    "[Desktop Entry]\nType=Application\nName={title}\nExec=sh -c 'xdg-open \"$(dirname \"$1\")/{index_path}\"' sh %k\nTerminal=false\n"
+  );
+  let asset_prefix = if trimmed_prefix.is_empty() {
+    String::new()
+  } else {
+    format!("{trimmed_prefix}/")
+  };
+  let instructions = render_instructions_page(title, &index_path, theme, &asset_prefix);
+
+  let mut written = Vec::new();
+  for (name, contents) in [
+    (WINDOWS_LAUNCHER, windows),
+    (MACOS_LAUNCHER, macos),
+    (LINUX_LAUNCHER, linux),
+    (INSTRUCTIONS_PAGE, instructions),
+  ] {
+    let target = root_dir.join(name);
+    fs::write(&target, contents)
+      .with_context(|| format!("failed to write {}", target.display()))?;
+    if name != INSTRUCTIONS_PAGE {
+      mark_executable(&target)?;
+    }
+    written.push(target);
+  }
+
+  Ok(written)
+}
+
+fn render_instructions_page(
+  title: &str,
+  index_path: &str,
+  theme: Option<&ThemePalette>,
+  asset_prefix: &str,
+) -> String {
+  let title = escape_html(title);
+  let index_path = escape_html(index_path);
+  let style = theme
+    .filter(|theme| !theme.is_empty())
+    .map(|theme| {
+      format!(
+        "\n    <style id=\"offline-theme\">{}</style>",
+        theme.stylesheet(asset_prefix)
+      )
+    })
+    .unwrap_or_default();
+  let logo = theme
+    .and_then(|theme| theme.logo.as_deref())
+    .map(|logo| {
+      format!(
+        "\n    <img src=\"{}{}\" alt=\"\" style=\"max-height:4rem\">",
+        escape_html(asset_prefix),
+        escape_html(logo.trim_start_matches('/'))
+      )
+    })
+    .unwrap_or_default();
+  format!(
+    r#"<!DOCTYPE html>
+<html lang="en">
+  <head>
+    <meta charset="utf-8">
+    <title>How to open {title}</title>{style}
+  </head>
+  <body>{logo}
+    <h1>How to open {title}</h1>
+    <p>This content works without an internet connection. Keep all files together and open it
+    with one of the options below.</p>
+    <ul>
+      <li><strong>Windows:</strong> double-click <code>{WINDOWS_LAUNCHER}</code>.</li>
+      <li><strong>macOS:</strong> double-click <code>{MACOS_LAUNCHER}</code>. If macOS refuses,
+      right-click it and choose <em>Open</em>.</li>
+      <li><strong>Linux:</strong> double-click <code>{LINUX_LAUNCHER}</code> and choose
+      <em>Trust and Launch</em> if asked.</li>
+    </ul>
+    <p>You can also open <a href="{index_path}">{index_path}</a> directly in a web browser.</p>
+  </body>
+</html>
+"#
+  )
+}
+
+#[cfg(unix)]
+fn mark_executable(path: &Path) -> Result<()> {
+  use std::os::unix::fs::PermissionsExt;
+
+  let mut permissions = fs::metadata(path)?.permissions();
+  permissions.set_mode(0o755);
+  fs::set_permissions(path, permissions)
+    .with_context(|| format!("failed to mark {} executable", path.display()))
+}
+
+#[cfg(not(unix))]
+fn mark_executable(_path: &Path) -> Result<()> {
+  Ok(())
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -69,9 +215,13 @@ mod tests {
       offline_site_root: "site".into(),
       collections_dir_name: "programs".into(),
       offline_bundle_root: "target/offline-html".into(),
+      output_root: None,
       index_html_file: "index.html".into(),
       target_dir: "target".into(),
       offline_manifest_json: "offline_manifest.json".into(),
+      scan_max_depth: 32,
+      asset_extensions: Vec::new(),
+      excluded_extensions: Vec::new(),
     }
   }
 
@@ -102,4 +252,39 @@ mod tests {
     let content = fs::read_to_string(index_path).unwrap();
     assert_eq!(content, "original");
   }
+
+  #[test]
+  fn writes_launcher_scripts_with_relative_index_path() {
+    let dir = tempdir().unwrap();
+    let root = dir.path().join("offline");
+    let written = write_launcher_scripts(&layout(), &root, "site", "Safety Training").unwrap();
+    assert_eq!(written.len(), 4);
+
+    let windows = fs::read_to_string(root.join(WINDOWS_LAUNCHER)).unwrap();
+    assert!(windows.contains("%~dp0site\\index.html"));
+    let macos = fs::read_to_string(root.join(MACOS_LAUNCHER)).unwrap();
+    assert!(macos.contains("open \"site/index.html\""));
+    let linux = fs::read_to_string(root.join(LINUX_LAUNCHER)).unwrap();
+    assert!(linux.contains("Name=Safety Training"));
+    let instructions = fs::read_to_string(root.join(INSTRUCTIONS_PAGE)).unwrap();
+    assert!(instructions.contains("href=\"site/index.html\""));
+  }
+
+  #[test]
+  fn brands_instructions_page_with_theme() {
+    let dir = tempdir().unwrap();
+    let root = dir.path().join("offline");
+    let theme = ThemePalette {
+      colors: vec![("brand".into(), "#0a84ff".into())],
+      dark_colors: Vec::new(),
+      logo: Some("branding/logo.svg".into()),
+    };
+    write_themed_launcher_scripts(&layout(), &root, "site", "Safety Training", Some(&theme))
+      .unwrap();
+
+    let instructions = fs::read_to_string(root.join(INSTRUCTIONS_PAGE)).unwrap();
+    assert!(instructions.contains("<style id=\"offline-theme\">:root{--brand:#0a84ff;"));
+    assert!(instructions.contains("url(\"site/branding/logo.svg\")"));
+    assert!(instructions.contains("<img src=\"site/branding/logo.svg\""));
+  }
 }
@@ -1,16 +1,21 @@
 //! Loading and interpreting the build-time offline manifest.
 
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
 use serde::Deserialize;
 
+use crate::models::{BuildProvenance, legacy_manifest_schema_version};
 use crate::project::OfflineProjectLayout;
 
 /// Deserialised representation of the build-time offline manifest.
 #[derive(Debug, Deserialize)]
 pub struct OfflineManifest {
+  /// Format version; manifests written before versioning report `1`.
+  #[serde(default = "legacy_manifest_schema_version")]
+  pub schema_version: u32,
   /// Optional site root specified in the manifest JSON.
   #[serde(default)]
   pub site_root: Option<String>,
@@ -19,6 +24,21 @@ pub struct OfflineManifest {
   pub hero_assets: Vec<String>,
   /// Entries discovered during the build.
   pub entries: Vec<OfflineEntry>,
+  /// SHA-256 digests of bundled collection assets keyed by bundle path.
+  #[serde(default)]
+  pub asset_hashes: BTreeMap<String, String>,
+  /// Duplicate asset paths mapped to the canonical copy with identical content.
+  #[serde(default)]
+  pub asset_aliases: BTreeMap<String, String>,
+  /// Byte sizes of bundled collection assets keyed by bundle path.
+  #[serde(default)]
+  pub asset_sizes: BTreeMap<String, u64>,
+  /// Source modification times of bundled collection assets keyed by bundle path.
+  #[serde(default)]
+  pub asset_modified: BTreeMap<String, u64>,
+  /// Tool version, commit, content hash and time of the build, from schema version 4 on.
+  #[serde(default)]
+  pub provenance: Option<BuildProvenance>,
 }
 
 /// Offline entry contained within the manifest.
@@ -28,9 +48,24 @@ pub struct OfflineEntry {
   pub collection_id: String,
   /// Entry identifier within the collection.
   pub entry_id: String,
+  /// Entry title, when recorded.
+  #[serde(default)]
+  pub title: Option<String>,
+  /// Entry section, when recorded.
+  #[serde(default)]
+  pub section: Option<String>,
   /// Asset paths referenced by the entry body.
   #[serde(default)]
   pub asset_paths: Vec<String>,
+  /// Heading anchors mapped to the heading text.
+  #[serde(default)]
+  pub headings: BTreeMap<String, String>,
+  /// Assets to load first, from schema version 5 on.
+  #[serde(default)]
+  pub priority_assets: Vec<String>,
+  /// Videos mapped to their generated poster frames, from schema version 6 on.
+  #[serde(default)]
+  pub video_posters: BTreeMap<String, String>,
 }
 
 /// Load an offline manifest from disk.
@@ -47,7 +82,7 @@ pub fn resolve_site_root(
   layout: &OfflineProjectLayout,
   manifest: &OfflineManifest,
 ) -> (PathBuf, String) {
-  let offline_root = PathBuf::from(&layout.offline_bundle_root);
+  let offline_root = layout.bundle_root(Path::new(""));
   let site_raw = manifest
     .site_root
     .as_deref()
@@ -84,20 +119,42 @@ mod tests {
       offline_site_root: "site".into(),
       collections_dir_name: "programs".into(),
       offline_bundle_root: "target/offline-html".into(),
+      output_root: None,
       index_html_file: "index.html".into(),
       target_dir: "target".into(),
       offline_manifest_json: "offline_manifest.json".into(),
+      scan_max_depth: 32,
+      asset_extensions: Vec::new(),
+      excluded_extensions: Vec::new(),
     }
   }
 
   fn manifest_with_site_root(root: Option<&str>) -> OfflineManifest {
     OfflineManifest {
+      schema_version: crate::models::OFFLINE_MANIFEST_SCHEMA_VERSION,
       site_root: root.map(|value| value.to_string()),
       hero_assets: Vec::new(),
       entries: Vec::new(),
+      asset_hashes: BTreeMap::new(),
+      asset_aliases: BTreeMap::new(),
+      asset_sizes: BTreeMap::new(),
+      asset_modified: BTreeMap::new(),
+      provenance: None,
     }
   }
 
+  #[test]
+  fn unversioned_manifests_load_as_version_one() {
+    let manifest: OfflineManifest = serde_json::from_str(
+      r#"{"entries":[{"collection_id":"alpha","entry_id":"intro","asset_paths":[]}]}"#,
+    )
+    .unwrap();
+
+    assert_eq!(manifest.schema_version, 1);
+    assert_eq!(manifest.entries[0].title, None);
+    assert!(manifest.asset_sizes.is_empty());
+  }
+
   #[test]
   fn defaults_to_offline_site_root() {
     let manifest = manifest_with_site_root(None);
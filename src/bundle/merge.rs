@@ -0,0 +1,201 @@
+//! Combine a core bundle with an add-on bundle into a single offline bundle.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow};
+
+use crate::bundle::manifest::{OfflineManifest, load_manifest};
+use crate::bundle::split::{copy_tree, site_location};
+use crate::models::{OFFLINE_MANIFEST_SCHEMA_VERSION, OfflineEntrySummary, OfflineManifestSummary};
+use crate::project::OfflineProjectLayout;
+
+/// Merge `overlay` on top of `base`, writing the combined bundle to `output`.
+///
+/// Files and manifest entries from the overlay replace those of the base when both define
+/// them. The merged manifest is validated afterwards: every referenced asset must exist in the
+/// output, otherwise an error listing the missing paths is returned.
+pub fn merge(
+  layout: &OfflineProjectLayout,
+  base: &Path,
+  overlay: &Path,
+  output: &Path,
+) -> Result<OfflineManifestSummary> {
+  if output.starts_with(base) || output.starts_with(overlay) {
+    return Err(anyhow!(
+      "merged bundle must be written outside of the input bundles"
+    ));
+  }
+
+  let base_manifest = load_manifest(&base.join(&layout.offline_manifest_json))?;
+  let overlay_manifest = load_manifest(&overlay.join(&layout.offline_manifest_json))?;
+
+  let (base_site, base_prefix) = site_location(layout, &base_manifest);
+  let (overlay_site, _) = site_location(layout, &overlay_manifest);
+  if base_site != overlay_site {
+    return Err(anyhow!(
+      "cannot merge bundles with different site roots ({} and {})",
+      base_site.display(),
+      overlay_site.display()
+    ));
+  }
+
+  if output.exists() {
+    fs::remove_dir_all(output).with_context(|| format!("failed to clear {}", output.display()))?;
+  }
+  copy_tree(base, output, &|_| false)?;
+  copy_tree(overlay, output, &|_| false)?;
+
+  let merged = merge_manifests(base_prefix, &base_manifest, &overlay_manifest);
+
+  let site_root = output.join(&base_site);
+  let missing: Vec<&str> = merged
+    .entries
+    .iter()
+    .flat_map(|entry| entry.asset_paths.iter())
+    .chain(&merged.hero_assets)
+    .chain(merged.asset_hashes.keys())
+    .map(String::as_str)
+    .filter(|path| !site_root.join(path).is_file())
+    .collect();
+  if !missing.is_empty() {
+    return Err(anyhow!(
+      "merged bundle references missing assets: {}",
+      missing.join(", ")
+    ));
+  }
+
+  let manifest_path = output.join(&layout.offline_manifest_json);
+  fs::write(&manifest_path, serde_json::to_string_pretty(&merged)?)
+    .with_context(|| format!("failed to write {}", manifest_path.display()))?;
+
+  Ok(merged)
+}
+
+fn merge_manifests(
+  site_root: String,
+  base: &OfflineManifest,
+  overlay: &OfflineManifest,
+) -> OfflineManifestSummary {
+  let mut entries = BTreeMap::new();
+  for entry in base.entries.iter().chain(&overlay.entries) {
+    entries.insert(
+      (entry.collection_id.clone(), entry.entry_id.clone()),
+      OfflineEntrySummary {
+        collection_id: entry.collection_id.clone(),
+        entry_id: entry.entry_id.clone(),
+        title: entry.title.clone(),
+        section: entry.section.clone(),
+        asset_paths: entry.asset_paths.clone(),
+        headings: entry.headings.clone(),
+        priority_assets: entry.priority_assets.clone(),
+        video_posters: entry.video_posters.clone(),
+      },
+    );
+  }
+
+  let mut hero_assets = base.hero_assets.clone();
+  for path in &overlay.hero_assets {
+    if !hero_assets.contains(path) {
+      hero_assets.push(path.clone());
+    }
+  }
+
+  let mut asset_hashes = base.asset_hashes.clone();
+  asset_hashes.extend(overlay.asset_hashes.clone());
+
+  let mut asset_aliases = base.asset_aliases.clone();
+  asset_aliases.extend(overlay.asset_aliases.clone());
+
+  let mut asset_sizes = base.asset_sizes.clone();
+  asset_sizes.extend(overlay.asset_sizes.clone());
+
+  let mut asset_modified = base.asset_modified.clone();
+  asset_modified.extend(overlay.asset_modified.clone());
+
+  OfflineManifestSummary {
+    schema_version: OFFLINE_MANIFEST_SCHEMA_VERSION,
+    site_root,
+    entries: entries.into_values().collect(),
+    hero_assets,
+    asset_hashes,
+    asset_aliases,
+    asset_sizes,
+    asset_modified,
+    // The merged bundle runs the base bundle's application shell.
+    provenance: base.provenance.clone(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::tempdir;
+
+  fn layout() -> OfflineProjectLayout {
+    OfflineProjectLayout {
+      excluded_dir_name: "prod".into(),
+      excluded_path_fragment: "/prod/".into(),
+      ..Default::default()
+    }
+  }
+
+  fn write_file(path: &Path, contents: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, contents).unwrap();
+  }
+
+  #[test]
+  fn overlay_wins_and_entries_are_unioned() {
+    let dir = tempdir().unwrap();
+    let base = dir.path().join("base");
+    let overlay = dir.path().join("overlay");
+    write_file(&base.join("site/index.html"), "core");
+    write_file(&base.join("site/programs/P001/a.png"), "a");
+    write_file(
+      &base.join("offline_manifest.json"),
+      r#"{"site_root": "site", "entries": [
+        {"collection_id": "P001", "entry_id": "intro", "asset_paths": ["programs/P001/a.png"]}
+      ]}"#,
+    );
+    write_file(&overlay.join("site/index.html"), "addon");
+    write_file(&overlay.join("site/programs/P002/b.png"), "b");
+    write_file(
+      &overlay.join("offline_manifest.json"),
+      r#"{"site_root": "site", "entries": [
+        {"collection_id": "P002", "entry_id": "intro", "asset_paths": ["programs/P002/b.png"]}
+      ]}"#,
+    );
+
+    let output = dir.path().join("merged");
+    let merged = merge(&layout(), &base, &overlay, &output).unwrap();
+
+    assert_eq!(merged.entries.len(), 2);
+    assert_eq!(
+      fs::read_to_string(output.join("site/index.html")).unwrap(),
+      "addon"
+    );
+    assert!(output.join("site/programs/P001/a.png").is_file());
+    let written = load_manifest(&output.join("offline_manifest.json")).unwrap();
+    assert_eq!(written.entries.len(), 2);
+  }
+
+  #[test]
+  fn rejects_manifests_referencing_missing_assets() {
+    let dir = tempdir().unwrap();
+    let base = dir.path().join("base");
+    let overlay = dir.path().join("overlay");
+    write_file(
+      &base.join("offline_manifest.json"),
+      r#"{"site_root": "site", "entries": []}"#,
+    );
+    write_file(
+      &overlay.join("offline_manifest.json"),
+      r#"{"site_root": "site", "hero_assets": ["programs/P002/hero.png"], "entries": []}"#,
+    );
+
+    let error = merge(&layout(), &base, &overlay, &dir.path().join("merged")).unwrap_err();
+    assert!(error.to_string().contains("programs/P002/hero.png"));
+  }
+}
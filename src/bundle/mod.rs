@@ -1,7 +1,40 @@
 //! Helpers for patching the generated `dx build` output into an offline-ready bundle.
 
+pub mod accessibility;
+pub mod assemble;
+pub mod audit;
+pub mod css_inline;
+#[cfg(feature = "css-minify")]
+pub mod css_minify;
+pub mod css_purge;
+pub mod dedup;
+pub mod delta;
+pub mod entry_points;
+pub mod history_fallback;
 pub mod js_patch;
 pub mod launcher;
 pub mod manifest;
+pub mod merge;
+pub mod orchestrate;
+pub mod priority_hints;
+pub mod provenance;
 pub mod site;
+#[cfg(feature = "smoke-test")]
+pub mod smoke;
+pub mod split;
+pub mod stable_names;
+pub mod stage;
 pub mod styles;
+pub mod updates;
+pub mod verify;
+
+pub use assemble::assemble;
+pub use merge::merge;
+pub use orchestrate::{DxBuildOptions, OfflinePipeline};
+pub use verify::verify;
+
+/// Marker embedded in patched files so that re-running the patch stage is detected.
+pub(crate) const PATCH_MARKER: &str = "offline_dx_bundler:patched";
+
+/// Version of the patch format recorded next to [`PATCH_MARKER`].
+pub(crate) const PATCH_VERSION: u32 = 1;
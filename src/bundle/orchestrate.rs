@@ -0,0 +1,389 @@
+//! Run `dx build` and turn its output into an offline bundle in one call.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result, anyhow};
+
+use crate::bundle::accessibility::audit_html_accessibility;
+use crate::bundle::css_inline::{CssInlineStats, inline_css_images};
+use crate::bundle::entry_points::write_entry_points;
+use crate::bundle::history_fallback::{HistoryFallback, expand_routes, write_history_fallback};
+use crate::bundle::js_patch::{WasmEmbedding, patch_site_module};
+use crate::bundle::launcher::{write_root_launcher, write_themed_launcher_scripts};
+use crate::bundle::manifest::load_manifest;
+use crate::bundle::provenance::stamp_bundle;
+use crate::bundle::site::{
+  InitialRoute, PatchedSiteIndex, SitePatchMode, SitePatchOptions, SplashScreen,
+  patch_site_index_with,
+};
+use crate::bundle::split::copy_tree;
+use crate::bundle::stable_names::stabilize_asset_names;
+use crate::bundle::stage::stage_site;
+use crate::bundle::styles::ensure_stylesheet_aliases_with;
+use crate::bundle::updates::stamp_update_file;
+use crate::config::ProjectConfig;
+use crate::hooks::{POST_BUNDLE, run_hook_commands};
+use crate::project::OfflineProjectLayout;
+use crate::report::PageFinding;
+
+/// Arguments passed to `dx` when [`DxBuildOptions::args`] is left at its default.
+pub const DEFAULT_DX_ARGS: &[&str] = &["build", "--release", "--platform", "web"];
+
+/// How [`OfflinePipeline`] invokes `dx` and where it picks up the result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DxBuildOptions {
+  /// Program to run, `dx` unless a wrapper or pinned binary is used.
+  pub program: String,
+  /// Arguments passed to `program`, see [`DEFAULT_DX_ARGS`].
+  pub args: Vec<String>,
+  /// Site directory written by the build, relative to the manifest directory. `None` uses
+  /// `<target_dir>/dx/<package_name>/release/web/public`.
+  pub output_dir: Option<PathBuf>,
+  /// Reuse the existing output instead of running `program`.
+  pub skip_build: bool,
+}
+
+impl Default for DxBuildOptions {
+  fn default() -> Self {
+    Self {
+      program: "dx".into(),
+      args: DEFAULT_DX_ARGS.iter().map(|arg| arg.to_string()).collect(),
+      output_dir: None,
+      skip_build: false,
+    }
+  }
+}
+
+/// Summary of a finished [`OfflinePipeline::run`].
+#[derive(Debug, Clone)]
+pub struct OfflinePipelineReport {
+  /// Bundle directory containing the launcher and the patched site.
+  pub bundle_root: PathBuf,
+  /// Patched copy of the `dx build` output.
+  pub site_root: PathBuf,
+  /// Number of files copied out of the `dx build` output.
+  pub copied_files: usize,
+  /// Script names discovered while patching the index.
+  pub patched_index: PatchedSiteIndex,
+  /// Launcher scripts written when [`OfflinePipeline::launcher_title`] is set.
+  pub launcher_scripts: Vec<PathBuf>,
+  /// Accessibility problems in the bundle's HTML pages, relative to `bundle_root`.
+  pub accessibility: Vec<PageFinding>,
+  /// Encoding and size of the embedded WebAssembly module.
+  pub wasm: Option<WasmEmbedding>,
+  /// Hashed assets renamed to stable names, as `hashed name → stable name`.
+  pub renamed_assets: BTreeMap<String, String>,
+  /// Stylesheet images inlined as `data:` URIs when `inline_image_kib` is set.
+  pub inlined_images: CssInlineStats,
+  /// Entry-point pages written next to the index.
+  pub entry_points: Vec<PathBuf>,
+  /// `404.html`, `_redirects` and route pages written for history-mode routing.
+  pub history_fallback: Vec<PathBuf>,
+  /// `version.json` written from the provenance in the bundle's offline manifest, if any.
+  pub version_file: Option<PathBuf>,
+  /// `update.json` written when a `bundle_version` is configured.
+  pub update_file: Option<PathBuf>,
+}
+
+/// Everything between `dx build` and a finished offline bundle.
+///
+/// [`run`](Self::run) builds the web app, copies the output into the configured site root and
+/// applies the same steps projects otherwise script by hand: index and module patching,
+/// stylesheet aliases, inlining of small stylesheet images, the root launcher, `version.json`, `update.json`, an accessibility audit
+/// of the pages and the `post_bundle` hooks. The
+/// `dx build` output itself is never modified.
+#[derive(Debug, Clone)]
+pub struct OfflinePipeline<'a> {
+  manifest_dir: &'a Path,
+  config: &'a ProjectConfig,
+  dx: DxBuildOptions,
+  patch_options: SitePatchOptions,
+  launcher_title: Option<String>,
+}
+
+impl<'a> OfflinePipeline<'a> {
+  /// Pipeline for the project at `manifest_dir` using its resolved configuration.
+  pub fn new(manifest_dir: &'a Path, config: &'a ProjectConfig) -> Self {
+    Self {
+      manifest_dir,
+      config,
+      dx: DxBuildOptions::default(),
+      patch_options: SitePatchOptions::default(),
+      launcher_title: None,
+    }
+  }
+
+  /// Replace how `dx` is invoked.
+  pub fn dx_options(mut self, options: DxBuildOptions) -> Self {
+    self.dx = options;
+    self
+  }
+
+  /// Options used when patching the index. A missing theme, history fallback and empty entry
+  /// points fall back to the configured ones, and stable asset names are also used when
+  /// configured.
+  pub fn patch_options(mut self, options: SitePatchOptions) -> Self {
+    self.patch_options = options;
+    self
+  }
+
+  /// Also write the double-click launcher scripts, titled `title`.
+  pub fn launcher_title(mut self, title: impl Into<String>) -> Self {
+    self.launcher_title = Some(title.into());
+    self
+  }
+
+  /// Directory the `dx build` output is read from.
+  pub fn dx_output_dir(&self) -> PathBuf {
+    match &self.dx.output_dir {
+      Some(dir) => self.manifest_dir.join(dir),
+      None => self
+        .manifest_dir
+        .join(&self.config.target_dir)
+        .join("dx")
+        .join(&self.config.package_name)
+        .join("release")
+        .join("web")
+        .join("public"),
+    }
+  }
+
+  /// Build the app with `dx` and assemble the offline bundle from its output.
+  pub fn run(&self) -> Result<OfflinePipelineReport> {
+    if !self.dx.skip_build {
+      self.run_dx_build()?;
+    }
+
+    let layout = self.config.to_layout();
+    let source = self.dx_output_dir();
+    if !source.is_dir() {
+      return Err(anyhow!(
+        "dx build output {} does not exist",
+        source.display()
+      ));
+    }
+
+    let bundle_root = layout.bundle_root(self.manifest_dir);
+    let site_prefix = self.config.offline_site_root.trim_matches('/');
+    let site_root = bundle_root.join(site_prefix);
+    // A site at the bundle root shares its directory with the manifest, so it is copied over
+    // rather than replaced.
+    let copied_files = if site_prefix.is_empty() {
+      copy_tree(&source, &site_root, &|_| false)?
+    } else {
+      stage_site(&source, &site_root)?
+    };
+
+    let mut options = self.patch_options.clone();
+    if options.theme.is_none() {
+      options.theme = self.config.theme_palette()?;
+    }
+    if options.splash.is_none() {
+      options.splash = self.config.splash_text().map(|text| SplashScreen {
+        logo: options.theme.as_ref().and_then(|theme| theme.logo.clone()),
+        progress_text: text.to_string(),
+      });
+    }
+    if options.initial_route == InitialRoute::default() {
+      options.initial_route = match self.config.initial_route()? {
+        Some(route) if self.config.force_initial_route => InitialRoute::Always(route.into()),
+        Some(route) => InitialRoute::WhenMissing(route.into()),
+        None => InitialRoute::Keep,
+      };
+    }
+    if options.entry_points.is_empty() {
+      options.entry_points.clone_from(&self.config.entry_points);
+    }
+    options.stable_asset_names |= self.config.stable_asset_names;
+    if self.config.generic_patch_mode()? {
+      options.mode = SitePatchMode::Generic;
+    }
+    if options.history_fallback.is_none() && self.config.history_fallback {
+      options.history_fallback = Some(self.history_fallback(&layout, &bundle_root, site_prefix)?);
+    }
+    let mut patched_index = patch_site_index_with(&layout, &site_root, &options)?;
+    let wasm = patch_site_module(&layout, &site_root, &patched_index, &options, || {
+      Ok(self.config.package_name.replace('-', "_"))
+    })?;
+    ensure_stylesheet_aliases_with(&layout, &site_root, &self.config.stylesheet_aliases()?)?;
+    let inlined_images = match self.config.inline_image_limit()? {
+      Some(max_bytes) => inline_css_images(&site_root, max_bytes)?,
+      None => CssInlineStats::default(),
+    };
+    let renamed_assets = if options.stable_asset_names {
+      stabilize_asset_names(&layout, &site_root, options.stable_name_hashes)?
+    } else {
+      BTreeMap::new()
+    };
+    for name in [&mut patched_index.js_name, &mut patched_index.wasm_name] {
+      if let Some(stable) = renamed_assets.get(name.as_str()) {
+        name.clone_from(stable);
+      }
+    }
+    let entry_points = write_entry_points(&layout, &site_root, &options.entry_points)?;
+    let history_fallback = match &options.history_fallback {
+      Some(fallback) => write_history_fallback(&layout, &site_root, fallback)?,
+      None => Vec::new(),
+    };
+    write_root_launcher(&layout, &bundle_root, site_prefix)?;
+    let version_file = stamp_bundle(&layout, &bundle_root)?;
+    let update_file = stamp_update_file(self.config, &bundle_root)?;
+
+    let launcher_scripts = match &self.launcher_title {
+      Some(title) => write_themed_launcher_scripts(
+        &layout,
+        &bundle_root,
+        site_prefix,
+        title,
+        options.theme.as_ref(),
+      )?,
+      None => Vec::new(),
+    };
+    let accessibility = audit_html_accessibility(&bundle_root)?;
+
+    run_hook_commands(
+      POST_BUNDLE,
+      &self.config.hooks.post_bundle,
+      self.manifest_dir,
+      &bundle_root,
+    )
+    .map_err(|err| anyhow!("{err}"))?;
+
+    Ok(OfflinePipelineReport {
+      bundle_root,
+      site_root,
+      copied_files,
+      patched_index,
+      launcher_scripts,
+      accessibility,
+      wasm,
+      renamed_assets,
+      inlined_images,
+      entry_points,
+      history_fallback,
+      version_file,
+      update_file,
+    })
+  }
+
+  /// History fallback for a bundle root served as the web root, with one page per manifest
+  /// entry when `history_routes` is set.
+  fn history_fallback(
+    &self,
+    layout: &OfflineProjectLayout,
+    bundle_root: &Path,
+    site_prefix: &str,
+  ) -> Result<HistoryFallback> {
+    let template = self.config.history_routes.trim();
+    let manifest_path = bundle_root.join(&layout.offline_manifest_json);
+    let routes = if template.is_empty() || !manifest_path.is_file() {
+      Vec::new()
+    } else {
+      let manifest = load_manifest(&manifest_path)?;
+      expand_routes(
+        template,
+        manifest
+          .entries
+          .iter()
+          .map(|entry| (entry.collection_id.as_str(), entry.entry_id.as_str())),
+      )
+    };
+    Ok(HistoryFallback {
+      base_path: site_prefix.into(),
+      routes,
+    })
+  }
+
+  fn run_dx_build(&self) -> Result<()> {
+    let command_line = std::iter::once(self.dx.program.as_str())
+      .chain(self.dx.args.iter().map(String::as_str))
+      .collect::<Vec<_>>()
+      .join(" ");
+    let status = Command::new(&self.dx.program)
+      .args(&self.dx.args)
+      .current_dir(self.manifest_dir)
+      .status()
+      .with_context(|| format!("failed to run `{command_line}`"))?;
+    if !status.success() {
+      return Err(anyhow!("`{command_line}` failed with {status}"));
+    }
+    Ok(())
+  }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+  use super::*;
+  use crate::report::PageIssue;
+  use std::fs;
+  use tempfile::tempdir;
+
+  #[test]
+  fn runs_build_command_and_patches_copy() {
+    let dir = tempdir().unwrap();
+    let script = concat!(
+      "mkdir -p out/assets && ",
+      "printf '%s' '<html><head></head><body><script type=\"module\" src=\"/./assets/app.js\"></script></body></html>' > out/index.html && ",
+      "printf '%s\\n' 'let wasm;' 'new URL(\"app_bg.wasm\",importMeta.url);' > out/assets/app.js && ",
+      "printf 'x' > out/assets/app_bg.wasm && ",
+      "printf 'p{}' > out/assets/tailwind-abc123.css"
+    );
+    let config = ProjectConfig::builder().build().unwrap();
+    let dx = DxBuildOptions {
+      program: "sh".into(),
+      args: vec!["-c".into(), script.into()],
+      output_dir: Some("out".into()),
+      skip_build: false,
+    };
+
+    let report = OfflinePipeline::new(dir.path(), &config)
+      .dx_options(dx.clone())
+      .run()
+      .unwrap();
+
+    assert_eq!(
+      report.site_root,
+      dir.path().join("target/offline-html/site")
+    );
+    assert_eq!(report.copied_files, 4);
+    assert!(report.site_root.join("tailwind.css").exists());
+    assert_eq!(report.patched_index.js_name, "app.js");
+    assert!(
+      fs::read_to_string(report.site_root.join("assets/app.js"))
+        .unwrap()
+        .contains("__offlineWasmBytes")
+    );
+    assert!(
+      !fs::read_to_string(dir.path().join("out/assets/app.js"))
+        .unwrap()
+        .contains("__offlineWasmBytes")
+    );
+    assert!(report.bundle_root.join("index.html").exists());
+    let audited: Vec<_> = report
+      .accessibility
+      .iter()
+      .map(|finding| {
+        (
+          finding.file.to_string_lossy().replace('\\', "/"),
+          finding.issue,
+        )
+      })
+      .collect();
+    assert_eq!(audited, [
+      ("site/index.html".to_string(), PageIssue::MissingLang),
+      ("site/index.html".to_string(), PageIssue::MissingTitle),
+    ]);
+
+    let failing = DxBuildOptions {
+      args: vec!["-c".into(), "exit 3".into()],
+      ..dx
+    };
+    let err = OfflinePipeline::new(dir.path(), &config)
+      .dx_options(failing)
+      .run()
+      .unwrap_err();
+    assert!(err.to_string().contains("failed with"));
+  }
+}
@@ -0,0 +1,96 @@
+//! Resource hints for an entry's high-priority assets.
+//!
+//! Entries record the assets shown first in
+//! [`OfflineEntrySummary::priority_assets`]. Pre-rendered entry pages call
+//! [`inject_priority_hints`] so browsers fetch those assets before the rest of the page, which
+//! keeps hero content from waiting behind slow kiosk disks.
+
+use anyhow::{Result, anyhow};
+
+use crate::manifest::attribution::escape_html;
+use crate::models::OfflineEntrySummary;
+
+/// Extensions preloaded as images with `fetchpriority="high"`; other priority assets are
+/// prefetched.
+pub const PRELOADED_IMAGE_EXTENSIONS: [&str; 7] =
+  ["png", "jpg", "jpeg", "gif", "webp", "avif", "svg"];
+
+/// `<link>` hints for the priority assets of `entry`, one per line.
+///
+/// `asset_prefix` is prepended to the bundle paths, e.g. `../` for pages one directory below
+/// the site root.
+pub fn render_priority_hints(entry: &OfflineEntrySummary, asset_prefix: &str) -> String {
+  entry
+    .priority_assets
+    .iter()
+    .map(|path| {
+      let href = escape_html(&format!("{asset_prefix}{path}"));
+      let extension = path
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_ascii_lowercase())
+        .unwrap_or_default();
+      if PRELOADED_IMAGE_EXTENSIONS.contains(&extension.as_str()) {
+        format!(r#"    <link rel="preload" as="image" href="{href}" fetchpriority="high">"#)
+      } else {
+        format!(r#"    <link rel="prefetch" href="{href}" fetchpriority="high">"#)
+      }
+    })
+    .map(|link| link + "\n")
+    .collect()
+}
+
+/// Insert the [`render_priority_hints`] of `entry` at the end of the `<head>` of `html`.
+pub fn inject_priority_hints(
+  html: &str,
+  entry: &OfflineEntrySummary,
+  asset_prefix: &str,
+) -> Result<String> {
+  let hints = render_priority_hints(entry, asset_prefix);
+  if hints.is_empty() {
+    return Ok(html.to_string());
+  }
+  let head_end = html.to_ascii_lowercase().rfind("</head>").ok_or_else(|| {
+    anyhow!(
+      "failed to locate </head> tag in the page of {}",
+      entry.entry_id
+    )
+  })?;
+  Ok(format!(
+    "{}{hints}  {}",
+    &html[..head_end],
+    &html[head_end..]
+  ))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn preloads_images_and_prefetches_other_priority_assets() -> Result<()> {
+    let entry = OfflineEntrySummary {
+      collection_id: "P001".into(),
+      entry_id: "intro".into(),
+      title: None,
+      section: None,
+      asset_paths: vec![
+        "assets/P001/cover.webp".into(),
+        "assets/P001/later.png".into(),
+      ],
+      headings: Default::default(),
+      priority_assets: vec![
+        "assets/P001/cover.webp".into(),
+        "assets/P001/intro.mp4".into(),
+      ],
+      video_posters: Default::default(),
+    };
+
+    let page = inject_priority_hints("<html><head></head><body></body></html>", &entry, "../")?;
+    assert_eq!(
+      page,
+      "<html><head>    <link rel=\"preload\" as=\"image\" href=\"../assets/P001/cover.webp\" fetchpriority=\"high\">\n    <link rel=\"prefetch\" href=\"../assets/P001/intro.mp4\" fetchpriority=\"high\">\n  </head><body></body></html>"
+    );
+    assert!(inject_priority_hints("<p>fragment</p>", &entry, "").is_err());
+    Ok(())
+  }
+}
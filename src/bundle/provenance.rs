@@ -0,0 +1,169 @@
+//! Build provenance stamped into the offline manifest and the bundle's `version.json`.
+//!
+//! [`collect_provenance`] records the tool version, the project's git commit, a hash of the
+//! bundled content and the build time. [`write_version_file`] puts it next to the launcher so
+//! support can ask users to open one file, and [`read_version_file`] reads it back.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+
+use crate::bundle::manifest::load_manifest;
+use crate::models::{BuildProvenance, OfflineEntryRecord, VERSION_FILE};
+use crate::project::OfflineProjectLayout;
+
+/// Provenance of a build of the project in `project_dir`.
+///
+/// `built_at` overrides the build time, which otherwise comes from `SOURCE_DATE_EPOCH` or the
+/// clock.
+pub fn collect_provenance(
+  project_dir: &Path,
+  entries: &[OfflineEntryRecord],
+  asset_hashes: &BTreeMap<String, String>,
+  built_at: Option<u64>,
+) -> BuildProvenance {
+  BuildProvenance {
+    tool_version: env!("CARGO_PKG_VERSION").to_string(),
+    git_commit: git_commit(project_dir),
+    content_hash: content_hash(entries, asset_hashes),
+    built_at: built_at.unwrap_or_else(build_timestamp),
+  }
+}
+
+/// `HEAD` commit of the git checkout containing `dir`, or `None` outside a checkout or when
+/// `git` is not installed.
+pub fn git_commit(dir: &Path) -> Option<String> {
+  let output = Command::new("git")
+    .args(["rev-parse", "HEAD"])
+    .current_dir(dir)
+    .output()
+    .ok()?;
+  let commit = String::from_utf8(output.stdout).ok()?;
+  let commit = commit.trim();
+  (output.status.success() && !commit.is_empty()).then(|| commit.to_string())
+}
+
+/// SHA-256 over every entry body and every asset digest, in manifest order.
+pub fn content_hash(
+  entries: &[OfflineEntryRecord],
+  asset_hashes: &BTreeMap<String, String>,
+) -> String {
+  let mut hasher = Sha256::new();
+  for entry in entries {
+    for part in [&entry.collection_id, &entry.entry_id, &entry.body] {
+      hasher.update(part.as_bytes());
+      hasher.update([0]);
+    }
+  }
+  for (path, digest) in asset_hashes {
+    hasher.update(path.as_bytes());
+    hasher.update([0]);
+    hasher.update(digest.as_bytes());
+    hasher.update([0]);
+  }
+  format!("{:x}", hasher.finalize())
+}
+
+/// Seconds since the Unix epoch, from `SOURCE_DATE_EPOCH` for reproducible builds.
+pub fn build_timestamp() -> u64 {
+  env::var("SOURCE_DATE_EPOCH")
+    .ok()
+    .and_then(|value| value.trim().parse().ok())
+    .unwrap_or_else(|| {
+      SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+    })
+}
+
+/// Write `provenance` to [`VERSION_FILE`] in `bundle_root`.
+pub fn write_version_file(bundle_root: &Path, provenance: &BuildProvenance) -> Result<PathBuf> {
+  let path = bundle_root.join(VERSION_FILE);
+  fs::create_dir_all(bundle_root)
+    .with_context(|| format!("failed to create {}", bundle_root.display()))?;
+  fs::write(&path, serde_json::to_string_pretty(provenance)?)
+    .with_context(|| format!("failed to write {}", path.display()))?;
+  Ok(path)
+}
+
+/// Read the [`VERSION_FILE`] of the bundle in `bundle_root`.
+pub fn read_version_file(bundle_root: &Path) -> Result<BuildProvenance> {
+  let path = bundle_root.join(VERSION_FILE);
+  let json =
+    fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))?;
+  BuildProvenance::from_json(&json).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+/// Write the provenance recorded in the bundle's offline manifest to its [`VERSION_FILE`].
+///
+/// Returns `None` when the bundle has no manifest or the manifest predates provenance.
+pub fn stamp_bundle(layout: &OfflineProjectLayout, bundle_root: &Path) -> Result<Option<PathBuf>> {
+  let manifest_path = bundle_root.join(&layout.offline_manifest_json);
+  if !manifest_path.is_file() {
+    return Ok(None);
+  }
+  match load_manifest(&manifest_path)?.provenance {
+    Some(provenance) => write_version_file(bundle_root, &provenance).map(Some),
+    None => Ok(None),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::tempdir;
+
+  fn entry(body: &str) -> OfflineEntryRecord {
+    OfflineEntryRecord {
+      collection_id: "alpha".into(),
+      entry_id: "intro".into(),
+      body: body.into(),
+      asset_paths: Vec::new(),
+      word_count: 0,
+      reading_minutes: 0,
+      headings: Default::default(),
+      images: Vec::new(),
+      documents: Vec::new(),
+      priority_assets: Vec::new(),
+      video_posters: Default::default(),
+    }
+  }
+
+  #[test]
+  fn stamps_and_reads_back_the_version_file() -> Result<()> {
+    let dir = tempdir()?;
+    let assets = BTreeMap::from([("programs/alpha/a.png".to_string(), "abc".to_string())]);
+    let provenance = collect_provenance(dir.path(), &[entry("# Intro")], &assets, Some(42));
+    assert_eq!(provenance.tool_version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(provenance.git_commit, None);
+    assert_eq!(provenance.built_at, 42);
+    assert_eq!(
+      provenance.content_hash,
+      content_hash(&[entry("# Intro")], &assets)
+    );
+    assert_ne!(
+      provenance.content_hash,
+      content_hash(&[entry("# Intro!")], &assets)
+    );
+
+    let layout = OfflineProjectLayout::default();
+    assert_eq!(stamp_bundle(&layout, dir.path())?, None);
+    fs::write(
+      dir.path().join(&layout.offline_manifest_json),
+      serde_json::json!({ "entries": [], "provenance": provenance }).to_string(),
+    )?;
+    assert_eq!(
+      stamp_bundle(&layout, dir.path())?,
+      Some(dir.path().join(VERSION_FILE))
+    );
+    assert_eq!(read_version_file(dir.path())?, provenance);
+    Ok(())
+  }
+}
@@ -2,113 +2,1020 @@
 
 use std::fs;
 use std::path::Path;
+use std::sync::LazyLock;
 
 use anyhow::{Context, Result, anyhow};
 use regex::Regex;
 
+use crate::bundle::entry_points::DEFAULT_START_ROUTE;
+use crate::bundle::history_fallback::HistoryFallback;
+use crate::bundle::js_patch::{ModuleOutput, WasmEncoding};
+use crate::bundle::stable_names::StableNameHashes;
+use crate::bundle::{PATCH_MARKER, PATCH_VERSION};
+use crate::config::EntryPoint;
+use crate::manifest::attribution::escape_html;
 use crate::project::OfflineProjectLayout;
+use crate::theme::ThemePalette;
 
-const INLINE_LOADER_TEMPLATE: &str = r#"    <script>
+/// Opening of every loader script, defining the `showLoadError` and `hideSplash` helpers.
+const LOADER_PRELUDE: &str = r#"    <script>
       window.addEventListener('DOMContentLoaded', () => {
-        if (!window.location.hash) {
-          window.location.replace('#/');
-        }
+        const showLoadError = (detail) => {
+          const panel = document.getElementById('offline-load-error');
+          if (!panel) {
+            return;
+          }
+          const detailNode = panel.querySelector('[data-offline-error-detail]');
+          if (detailNode && detail) {
+            detailNode.textContent = String(detail);
+          }
+          panel.hidden = false;
+        };
+        const hideSplash = () => {
+          const splash = document.getElementById('offline-splash');
+          if (splash) {
+            splash.remove();
+          }
+        };
+"#;
+
+/// Loader body starting the Dioxus bootstrap with the inlined WebAssembly bytes.
+const INLINE_LOADER_BODY: &str = r#"__INITIAL_ROUTE__
         const init = window.__dx_mainInit;
         if (!init) {
           console.error('Offline loader could not find Dioxus bootstrap.');
+          hideSplash();
+          showLoadError('Offline loader could not find Dioxus bootstrap.');
           return;
         }
         const wasmBytes = window.__pivotOfflineWasm;
-        init(wasmBytes).catch((err) => {
+        init(wasmBytes).then(hideSplash, (err) => {
+          console.error('Failed to launch offline bundle', err);
+          hideSplash();
+          showLoadError(err);
+        });
+"#;
+
+/// Loader body starting a plain wasm-bindgen module, see [`SitePatchMode::Generic`].
+const GENERIC_LOADER_BODY: &str = r#"        const init = window.__offlineInit;
+        if (!init) {
+          console.error('Offline loader could not find the application module.');
+          hideSplash();
+          showLoadError('Offline loader could not find the application module.');
+          return;
+        }
+        init().then((wasm) => {
+          hideSplash();
+          window.wasmBindings = window.__offlineModule;
+          window.dispatchEvent(new CustomEvent('TrunkApplicationStarted', { detail: { wasm } }));
+        }).catch((err) => {
           console.error('Failed to launch offline bundle', err);
+          hideSplash();
+          showLoadError(err);
         });
-      });
-    </script>
 "#;
 
+/// Complete loader script running `body` once the document has loaded.
+fn loader_script(body: &str) -> String {
+  format!("{LOADER_PRELUDE}{body}      }});\n    </script>\n")
+}
+
+/// User-facing text shown when the offline application cannot start.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FallbackContent {
+  /// Message rendered inside `<noscript>` for browsers with JavaScript disabled.
+  pub noscript_message: String,
+  /// Heading of the panel shown when the application fails to load.
+  pub load_error_title: String,
+  /// Troubleshooting text shown below the load error heading.
+  pub load_error_message: String,
+}
+
+/// Splash element covering the page while the embedded WebAssembly module is decoded and the
+/// application starts.
+///
+/// The element shows the logo, a spinner and the progress text, and is removed once the
+/// bootstrap resolves. Decoding progress is reported per chunk with
+/// [`WasmEncoding::Chunked`]; the other encodings decode in one step.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplashScreen {
+  /// Logo path relative to the site root; `None` shows only the spinner.
+  pub logo: Option<String>,
+  /// Text below the spinner; `{percent}` is replaced with the decoding progress.
+  pub progress_text: String,
+}
+
+impl Default for SplashScreen {
+  fn default() -> Self {
+    Self {
+      logo: None,
+      progress_text: "Loading… {percent}%".into(),
+    }
+  }
+}
+
+/// Options controlling how [`patch_site_index_with`] rewrites the generated index and how the
+/// JavaScript module is patched after it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SitePatchOptions {
+  /// Fallback content injected into the page body; `None` leaves the body untouched.
+  pub fallback: Option<FallbackContent>,
+  /// Hosts whose `<script>`, `<link>` and `<img>` tags are removed from the index.
+  pub external_tags: ExternalTagFilter,
+  /// What happens to `type="module"` scripts other than the main Dioxus module.
+  pub extra_module_scripts: ModuleScriptHandling,
+  /// Which `<link rel="preload">` hints end up in the index.
+  pub preloads: PreloadPolicy,
+  /// `<base href>` inserted into, or rewritten in, the index head.
+  pub base_href: BaseHref,
+  /// Brand palette injected as an inline `:root` stylesheet, see `ProjectConfig::theme_palette`.
+  pub theme: Option<ThemePalette>,
+  /// Splash screen shown while the application loads; `None` leaves the page blank until then.
+  pub splash: Option<SplashScreen>,
+  /// Hash route the loader opens before starting the application in [`SitePatchMode::Dioxus`].
+  pub initial_route: InitialRoute,
+  /// Encoding of the WebAssembly module embedded into the JavaScript module.
+  pub wasm_encoding: WasmEncoding,
+  /// Whether an ES module re-exporting the patched script is written next to it.
+  pub module_output: ModuleOutput,
+  /// Kind of build output being patched.
+  pub mode: SitePatchMode,
+  /// Pages written from the patched index by
+  /// [`write_entry_points`](crate::bundle::entry_points::write_entry_points).
+  pub entry_points: Vec<EntryPoint>,
+  /// Fallback pages for history-mode routing written by
+  /// [`write_history_fallback`](crate::bundle::history_fallback::write_history_fallback).
+  pub history_fallback: Option<HistoryFallback>,
+  /// Rename the hashed JavaScript, stylesheet and WebAssembly outputs with
+  /// [`stabilize_asset_names`](crate::bundle::stable_names::stabilize_asset_names).
+  pub stable_asset_names: bool,
+  /// Suffixes [`stable_asset_names`](Self::stable_asset_names) strips as content hashes.
+  pub stable_name_hashes: StableNameHashes,
+}
+
+/// Kind of `wasm-bindgen` output [`patch_site_index_with`] and
+/// [`patch_site_module`](crate::bundle::js_patch::patch_site_module) expect.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SitePatchMode {
+  /// `dx build` output: the bootstrap module in the assets directory, started through the
+  /// Dioxus globals.
+  #[default]
+  Dioxus,
+  /// Any page loading a `wasm-bindgen` `web` target module, e.g. trunk builds of Yew apps. The
+  /// module and WebAssembly file are located from the index wherever they sit below the site
+  /// root, root-relative paths are made relative and no Dioxus globals are assumed. JavaScript
+  /// and WebAssembly preloads are removed regardless of [`SitePatchOptions::preloads`].
+  Generic,
+}
+
+/// Hash route the Dioxus loader navigates to before starting the application.
+///
+/// Entry-point pages written by
+/// [`write_entry_points`](crate::bundle::entry_points::write_entry_points) keep their own start
+/// route in every case.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InitialRoute {
+  /// Open the route, e.g. `#/programs/intro`, when the page has no hash.
+  WhenMissing(String),
+  /// Open the route on every load, replacing deep links; for kiosks that must always boot
+  /// into the same program.
+  Always(String),
+  /// Leave the URL untouched.
+  Keep,
+}
+
+impl Default for InitialRoute {
+  fn default() -> Self {
+    Self::WhenMissing(DEFAULT_START_ROUTE.into())
+  }
+}
+
+/// `<base href>` handling for bundles whose site root is nested or hosted in a sub-directory.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum BaseHref {
+  /// Leave any existing `<base>` tag untouched and insert none.
+  #[default]
+  Keep,
+  /// Use the layout's site root, e.g. `/site/deep/` for `offline_site_root = "site/deep"`.
+  SitePrefix,
+  /// Use the given URL or path verbatim.
+  Custom(String),
+}
+
+/// Handling of resource preload hints in the patched index.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PreloadPolicy {
+  /// Remove JavaScript preloads and add one for the main WebAssembly module.
+  #[default]
+  MainWasm,
+  /// Keep author-provided preloads, adding the WebAssembly preload only when it is missing.
+  Preserve,
+  /// Replace every preload with hints for assets of at least `min_size` bytes.
+  Regenerate {
+    /// Smallest file size, in bytes, that receives a preload hint.
+    min_size: u64,
+  },
+  /// Remove every preload, e.g. for `file://` targets where fetch preloads are ignored.
+  Skip,
+}
+
+/// Treatment of additional module scripts, e.g. third-party integrations, in the index.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ModuleScriptHandling {
+  /// Leave the script tag untouched.
+  Preserve,
+  /// Load the script as a classic deferred script, which works from `file://`.
+  #[default]
+  Demote,
+  /// Remove the script tag.
+  Remove,
+}
+
+/// Result of [`patch_site_index_with`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatchedSiteIndex {
+  /// Main JavaScript module, relative to the assets directory, or to the site root in
+  /// [`SitePatchMode::Generic`].
+  pub js_name: String,
+  /// Main WebAssembly module, relative to the same directory as `js_name`.
+  pub wasm_name: String,
+  /// `src` of every module script found in the index, in document order.
+  pub module_scripts: Vec<String>,
+}
+
+/// Allow and deny lists deciding which externally hosted tags are stripped from the index.
+///
+/// Hosts match exactly or as a parent domain, so `google-analytics.com` also matches
+/// `ssl.google-analytics.com`. A deny entry of `*` strips every external tag that is not
+/// explicitly allowed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExternalTagFilter {
+  /// Hosts whose tags are removed.
+  pub deny_hosts: Vec<String>,
+  /// Hosts whose tags are kept even when a deny entry matches.
+  pub allow_hosts: Vec<String>,
+}
+
+/// Analytics and telemetry hosts stripped by default.
+pub const DEFAULT_DENIED_HOSTS: [&str; 10] = [
+  "google-analytics.com",
+  "googletagmanager.com",
+  "doubleclick.net",
+  "plausible.io",
+  "segment.com",
+  "segment.io",
+  "hotjar.com",
+  "mixpanel.com",
+  "clarity.ms",
+  "connect.facebook.net",
+];
+
+impl Default for SitePatchOptions {
+  fn default() -> Self {
+    Self {
+      fallback: Some(FallbackContent::default()),
+      external_tags: ExternalTagFilter::default(),
+      extra_module_scripts: ModuleScriptHandling::default(),
+      preloads: PreloadPolicy::default(),
+      base_href: BaseHref::default(),
+      theme: None,
+      splash: None,
+      initial_route: InitialRoute::default(),
+      wasm_encoding: WasmEncoding::default(),
+      module_output: ModuleOutput::default(),
+      mode: SitePatchMode::default(),
+      entry_points: Vec::new(),
+      history_fallback: None,
+      stable_asset_names: false,
+      stable_name_hashes: StableNameHashes::default(),
+    }
+  }
+}
+
+impl Default for ExternalTagFilter {
+  fn default() -> Self {
+    Self {
+      deny_hosts: DEFAULT_DENIED_HOSTS
+        .iter()
+        .map(|host| host.to_string())
+        .collect(),
+      allow_hosts: Vec::new(),
+    }
+  }
+}
+
+impl ExternalTagFilter {
+  /// Returns true when a tag referencing `url` must be removed.
+  pub fn denies(&self, url: &str) -> bool {
+    let Some(host) = external_host(url) else {
+      return false;
+    };
+    host_matches(&host, &self.deny_hosts) && !host_matches(&host, &self.allow_hosts)
+  }
+}
+
+/// Returns true when `host` equals a candidate, is a subdomain of one, or a candidate is `*`.
+pub(crate) fn host_matches(host: &str, candidates: &[String]) -> bool {
+  candidates.iter().any(|candidate| {
+    let candidate = candidate.trim().to_ascii_lowercase();
+    candidate == "*"
+      || host == candidate
+      || host
+        .strip_suffix(candidate.as_str())
+        .is_some_and(|prefix| prefix.ends_with('.'))
+  })
+}
+
+/// Lowercase host of an absolute or protocol-relative URL.
+pub(crate) fn external_host(url: &str) -> Option<String> {
+  let url = url.trim();
+  let lowered = url.to_ascii_lowercase();
+  let rest = ["https://", "http://", "//"]
+    .iter()
+    .find_map(|scheme| lowered.strip_prefix(scheme))?;
+  let host = rest
+    .split(['/', '?', '#'])
+    .next()
+    .unwrap_or_default()
+    .rsplit('@')
+    .next()
+    .unwrap_or_default();
+  let host = host.split(':').next().unwrap_or_default();
+  (!host.is_empty()).then(|| host.to_string())
+}
+
+/// `<script>`, `<link>` and `<img>` tags capturing the URL they load.
+///
+/// The attribute must follow whitespace so `data-src` and `data-href` are not mistaken for it.
+static EXTERNAL_TAG_PATTERNS: LazyLock<[Regex; 3]> = LazyLock::new(|| {
+  [
+    r#"(?is)<script\b[^>]*\ssrc\s*=\s*["']([^"']+)["'][^>]*>.*?</script>\s*"#,
+    r#"(?i)<link\b[^>]*\shref\s*=\s*["']([^"']+)["'][^>]*>\s*"#,
+    r#"(?i)<img\b[^>]*\ssrc\s*=\s*["']([^"']+)["'][^>]*>\s*"#,
+  ]
+  .map(|pattern| Regex::new(pattern).expect("invalid external tag regex"))
+});
+
+/// `<script>` elements capturing their attributes and body.
+static SCRIPT_TAG_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+  Regex::new(r#"(?is)<script\b([^>]*)>(.*?)</script>"#).expect("invalid script regex")
+});
+
+/// `type="module"` inside the attributes of a script tag.
+static TYPE_MODULE_PATTERN: LazyLock<Regex> =
+  LazyLock::new(|| Regex::new(r#"(?i)\btype\s*=\s*["']module["']"#).expect("invalid type regex"));
+
+/// `src` attribute of a script tag capturing its URL.
+static SCRIPT_SRC_PATTERN: LazyLock<Regex> =
+  LazyLock::new(|| Regex::new(r#"(?i)\bsrc\s*=\s*["']([^"']+)["']"#).expect("invalid src regex"));
+
+/// Remove `<script>`, `<link>` and `<img>` tags whose URL is denied by `filter`.
+fn strip_external_tags(text: &str, filter: &ExternalTagFilter) -> String {
+  let mut text = text.to_string();
+  for regex in EXTERNAL_TAG_PATTERNS.iter() {
+    text = regex
+      .replace_all(&text, |caps: &regex::Captures| {
+        if filter.denies(&caps[1]) {
+          String::new()
+        } else {
+          caps[0].to_string()
+        }
+      })
+      .into_owned();
+  }
+  text
+}
+
+impl Default for FallbackContent {
+  fn default() -> Self {
+    Self {
+      noscript_message: "This content needs JavaScript. Please enable JavaScript in your browser \
+                         settings and reload the page."
+        .into(),
+      load_error_title: "The content could not be started".into(),
+      load_error_message: "Open this page in an up-to-date version of Chrome, Edge, Firefox or \
+                           Safari, make sure all files were copied from the USB stick, and try \
+                           again."
+        .into(),
+    }
+  }
+}
+
 /// Update the generated `index.html` to load JavaScript and WebAssembly without a module loader.
+///
+/// Uses [`SitePatchOptions::default`], which injects the default [`FallbackContent`].
 pub fn patch_site_index(
   layout: &OfflineProjectLayout,
   site_root: &Path,
 ) -> Result<(String, String)> {
+  patch_site_index_with(layout, site_root, &SitePatchOptions::default())
+    .map(|patched| (patched.js_name, patched.wasm_name))
+}
+
+/// Variant of [`patch_site_index`] with explicit [`SitePatchOptions`].
+///
+/// When the index contains several module scripts, the one that bootstraps wasm-bindgen is
+/// patched and the others are handled according to [`SitePatchOptions::extra_module_scripts`].
+///
+/// The patched index records a marker comment. Patching it again returns the recorded names
+/// without touching the file (listing only the main script in `module_scripts`), while indexes
+/// patched by an incompatible version are rejected.
+pub fn patch_site_index_with(
+  layout: &OfflineProjectLayout,
+  site_root: &Path,
+  options: &SitePatchOptions,
+) -> Result<PatchedSiteIndex> {
   let index_path = site_root.join(&layout.index_html_file);
   let mut text = fs::read_to_string(&index_path)
     .with_context(|| format!("failed to read {}", index_path.display()))?;
 
   let assets_prefix = format!("{}/", layout.entry_assets_dir());
+  if options.mode == SitePatchMode::Generic {
+    if let Some(patched) = already_patched(&index_path, &text, "")? {
+      return Ok(patched);
+    }
+    return patch_generic_index(layout, site_root, &index_path, &text, options);
+  }
+  if let Some(patched) = already_patched(&index_path, &text, &assets_prefix)? {
+    return Ok(patched);
+  }
+
+  text = strip_external_tags(&text, &options.external_tags);
+
   text = text.replace(&format!("/./{}", assets_prefix), &assets_prefix);
 
-  let escaped_assets_prefix = regex::escape(&assets_prefix);
-  let script_pattern = Regex::new(&format!(
-    r#"(?i)<script[^>]*type="module"[^>]*src="{}([^"]+\.js)"[^>]*></script>"#,
-    escaped_assets_prefix
-  ))
-  .expect("invalid script regex");
-  let script_caps = script_pattern
-    .captures(&text)
-    .ok_or_else(|| anyhow!("failed to locate module script tag in offline index.html"))?;
-  let js_name = script_caps
-    .get(1)
-    .map(|m| m.as_str().to_string())
-    .ok_or_else(|| anyhow!("failed to extract JS module name"))?;
+  let module_scripts: Vec<String> = SCRIPT_TAG_PATTERN
+    .captures_iter(&text)
+    .filter(|caps| caps[2].trim().is_empty() && TYPE_MODULE_PATTERN.is_match(&caps[1]))
+    .filter_map(|caps| {
+      SCRIPT_SRC_PATTERN
+        .captures(&caps[1])
+        .map(|src| src[1].to_string())
+    })
+    .collect();
 
-  // Find WASM file in assets directory since Dioxus no longer generates preload links
   let assets_dir = site_root.join(layout.entry_assets_dir());
-  let wasm_name = fs::read_dir(&assets_dir)
+  let candidates: Vec<&str> = module_scripts
+    .iter()
+    .filter_map(|src| src.strip_prefix(&assets_prefix))
+    .filter(|name| name.ends_with(".js"))
+    .collect();
+  let js_name = candidates
+    .iter()
+    .find(|name| {
+      fs::read_to_string(assets_dir.join(name)).is_ok_and(|script| script.contains("__wbg_init"))
+    })
+    .or_else(|| candidates.first())
+    .map(|name| name.to_string())
+    .ok_or_else(|| anyhow!("failed to locate module script tag in offline index.html"))?;
+
+  // Find WASM file in assets directory since Dioxus no longer generates preload links. Builds
+  // using wasm-split also emit lazily loaded chunks, so prefer the wasm-bindgen `_bg` module.
+  let mut wasm_names: Vec<String> = fs::read_dir(&assets_dir)
     .with_context(|| format!("failed to read assets directory: {}", assets_dir.display()))?
     .filter_map(|entry| entry.ok())
     .filter(|entry| entry.file_type().is_ok_and(|ft| ft.is_file()))
-    .find_map(|entry| {
-      let file_name = entry.file_name();
-      let file_name_str = file_name.to_string_lossy();
-      if file_name_str.ends_with(".wasm") {
-        Some(file_name_str.to_string())
-      } else {
-        None
-      }
-    })
+    .map(|entry| entry.file_name().to_string_lossy().into_owned())
+    .filter(|name| name.ends_with(".wasm"))
+    .collect();
+  wasm_names.sort();
+  let wasm_name = wasm_names
+    .iter()
+    .find(|name| name.contains("_bg"))
+    .or_else(|| wasm_names.first())
+    .cloned()
     .ok_or_else(|| anyhow!("failed to locate wasm file in assets directory"))?;
 
+  let head_insert_pattern = Regex::new(r"(?i)\s*</head>").expect("invalid head insert regex");
+  if !head_insert_pattern.is_match(&text) {
+    return Err(anyhow!("failed to locate </head> tag in index.html"));
+  }
+
+  if let Some(href) = base_href(layout, &options.base_href) {
+    text = set_base_href(&text, &href)?;
+  }
+
+  let all_preloads_pattern =
+    Regex::new(r#"(?i)\s*<link[^>]*rel="(?:preload|modulepreload)"[^>]*>"#)
+      .expect("invalid preload regex");
+  let escaped_assets_dir = regex::escape(layout.entry_assets_dir());
+  let js_preload_pattern = Regex::new(&format!(
+    r#"(?i)<link[^>]*rel="preload"[^>]*{}/[^>]*\.js[^>]*>"#,
+    escaped_assets_dir
+  ))
+  .expect("invalid preload regex");
+
   // Generate WASM preload link manually since Dioxus no longer includes it
   let wasm_preload_link = format!(
     r#"<link rel="preload" as="fetch" type="application/wasm" href="{}{}" crossorigin>"#,
     assets_prefix, wasm_name
   );
+  let preload_links = match options.preloads {
+    PreloadPolicy::MainWasm => {
+      text = js_preload_pattern.replace_all(&text, "").into_owned();
+      vec![wasm_preload_link]
+    }
+    PreloadPolicy::Preserve => {
+      if text.contains(&format!("href=\"{assets_prefix}{wasm_name}\"")) {
+        Vec::new()
+      } else {
+        vec![wasm_preload_link]
+      }
+    }
+    PreloadPolicy::Regenerate { min_size } => {
+      text = all_preloads_pattern.replace_all(&text, "").into_owned();
+      asset_preload_links(&assets_dir, &assets_prefix, min_size)?
+    }
+    PreloadPolicy::Skip => {
+      text = all_preloads_pattern.replace_all(&text, "").into_owned();
+      Vec::new()
+    }
+  };
 
-  // Insert WASM preload link into the head section
-  let head_insert_pattern = Regex::new(r"(?i)\s*</head>").expect("invalid head insert regex");
-  if !head_insert_pattern.is_match(&text) {
-    return Err(anyhow!("failed to locate </head> tag in index.html"));
+  // Insert preload links and the theme stylesheet into the head section
+  let mut preload_links = preload_links;
+  if let Some(theme) = options.theme.as_ref().filter(|theme| !theme.is_empty()) {
+    preload_links.push(format!(
+      r#"<style id="offline-theme">{}</style>"#,
+      theme.stylesheet("")
+    ));
+  }
+  if !preload_links.is_empty() {
+    let links = preload_links.join("\n    ");
+    text = head_insert_pattern
+      .replace_all(&text, format!("{links}\n  </head>"))
+      .into_owned();
+  }
+
+  let main_src = format!("{assets_prefix}{js_name}");
+  let loader = loader_script(INLINE_LOADER_BODY).replace(
+    "__INITIAL_ROUTE__\n",
+    &initial_route_script(&options.initial_route)?,
+  );
+  let replacement = format!("<script defer src=\"{main_src}\"></script>\n{loader}");
+  let mut main_patched = false;
+  text = SCRIPT_TAG_PATTERN
+    .replace_all(&text, |caps: &regex::Captures| {
+      let attributes = &caps[1];
+      if !caps[2].trim().is_empty() || !TYPE_MODULE_PATTERN.is_match(attributes) {
+        return caps[0].to_string();
+      }
+      let src = SCRIPT_SRC_PATTERN
+        .captures(attributes)
+        .map(|src| src[1].to_string());
+      if !main_patched && src.as_deref() == Some(main_src.as_str()) {
+        main_patched = true;
+        return replacement.clone();
+      }
+      match options.extra_module_scripts {
+        ModuleScriptHandling::Preserve => caps[0].to_string(),
+        ModuleScriptHandling::Remove => String::new(),
+        ModuleScriptHandling::Demote => TYPE_MODULE_PATTERN.replace(&caps[0], "defer").into_owned(),
+      }
+    })
+    .into_owned();
+
+  let crossorigin_pattern = Regex::new(r"\s+crossorigin").expect("invalid crossorigin regex");
+  text = crossorigin_pattern.replace_all(&text, "").into_owned();
+
+  if let Some(fallback) = &options.fallback {
+    text = inject_fallback_content(&text, fallback);
+  }
+  if let Some(splash) = &options.splash {
+    text = inject_splash_screen(&text, splash);
   }
+
+  let marker =
+    format!("<!-- {PATCH_MARKER} v{PATCH_VERSION} js=\"{js_name}\" wasm=\"{wasm_name}\" -->");
   text = head_insert_pattern
-    .replace_all(&text, format!("{}\n  </head>", wasm_preload_link))
+    .replace(&text, format!("\n    {marker}\n  </head>"))
     .into_owned();
 
-  let escaped_assets_dir = regex::escape(layout.entry_assets_dir());
-  let preload_pattern = Regex::new(&format!(
-    r#"(?i)<link[^>]*rel="preload"[^>]*{}/[^>]*\.js[^>]*>"#,
-    escaped_assets_dir
+  fs::write(&index_path, &text)
+    .with_context(|| format!("failed to write {}", index_path.display()))?;
+
+  Ok(PatchedSiteIndex {
+    js_name,
+    wasm_name,
+    module_scripts,
+  })
+}
+
+/// Detect an index that was already patched, returning the names recorded in its marker.
+fn already_patched(
+  index_path: &Path,
+  text: &str,
+  assets_prefix: &str,
+) -> Result<Option<PatchedSiteIndex>> {
+  let marker_pattern = Regex::new(&format!(
+    r#"<!-- {}\s+v(\d+)\s+js="([^"]*)"\s+wasm="([^"]*)" -->"#,
+    regex::escape(PATCH_MARKER)
   ))
+  .expect("invalid marker regex");
+
+  let Some(caps) = marker_pattern.captures(text) else {
+    if text.contains("window.__dx_mainInit") || text.contains("window.__offlineInit") {
+      return Err(anyhow!(
+        "{} is already patched but has no patch marker; rebuild it with `dx build`",
+        index_path.display()
+      ));
+    }
+    return Ok(None);
+  };
+  if caps[1] != PATCH_VERSION.to_string() {
+    return Err(anyhow!(
+      "{} was patched by an incompatible bundler version (format v{}); rebuild it with `dx build`",
+      index_path.display(),
+      &caps[1]
+    ));
+  }
+
+  Ok(Some(PatchedSiteIndex {
+    js_name: caps[2].to_string(),
+    wasm_name: caps[3].to_string(),
+    module_scripts: vec![format!("{assets_prefix}{}", &caps[2])],
+  }))
+}
+
+/// Patch the index of a [`SitePatchMode::Generic`] site, see [`patch_site_index_with`].
+fn patch_generic_index(
+  layout: &OfflineProjectLayout,
+  site_root: &Path,
+  index_path: &Path,
+  text: &str,
+  options: &SitePatchOptions,
+) -> Result<PatchedSiteIndex> {
+  let mut text = strip_external_tags(text, &options.external_tags);
+
+  // Inline module scripts, as written by trunk, import the module and pass it the wasm URL.
+  let import_pattern = Regex::new(r#"(?:\bfrom|\bimport)\s*\(?\s*["']([^"']+\.m?js)["']"#)
+    .expect("invalid import regex");
+  let wasm_pattern = Regex::new(r#"["']([^"']+\.wasm)["']"#).expect("invalid wasm regex");
+
+  let mut module_scripts = Vec::new();
+  let mut wasm_refs = Vec::new();
+  for caps in SCRIPT_TAG_PATTERN.captures_iter(&text) {
+    if !TYPE_MODULE_PATTERN.is_match(&caps[1]) {
+      continue;
+    }
+    let src = match SCRIPT_SRC_PATTERN.captures(&caps[1]) {
+      Some(src) => src[1].to_string(),
+      None => match import_pattern.captures(&caps[2]) {
+        Some(import) => import[1].to_string(),
+        None => continue,
+      },
+    };
+    module_scripts.push(src);
+    wasm_refs.extend(
+      wasm_pattern
+        .captures_iter(&caps[2])
+        .map(|wasm| wasm[1].to_string()),
+    );
+  }
+
+  let candidates: Vec<(String, String)> = module_scripts
+    .iter()
+    .filter_map(|src| Some((src.clone(), site_path(site_root, src)?)))
+    .collect();
+  let (main_src, js_name) = candidates
+    .iter()
+    .find(|(_, path)| {
+      fs::read_to_string(site_root.join(path)).is_ok_and(|script| script.contains("__wbg_init"))
+    })
+    .or_else(|| candidates.first())
+    .cloned()
+    .ok_or_else(|| {
+      anyhow!(
+        "failed to locate a module script in {}",
+        index_path.display()
+      )
+    })?;
+
+  let link_wasm = Regex::new(r#"(?i)<link\b[^>]*\bhref\s*=\s*["']([^"']+\.wasm)["']"#)
+    .expect("invalid wasm link regex");
+  let js_path = Path::new(&js_name);
+  let sibling = js_path.with_file_name(format!(
+    "{}_bg.wasm",
+    js_path.file_stem().unwrap_or_default().to_string_lossy()
+  ));
+  let wasm_name = wasm_refs
+    .iter()
+    .chain(
+      link_wasm
+        .captures_iter(&text)
+        .map(|caps| caps[1].to_string())
+        .collect::<Vec<_>>()
+        .iter(),
+    )
+    .find_map(|src| site_path(site_root, src))
+    .or_else(|| {
+      let sibling = sibling.to_string_lossy().replace('\\', "/");
+      site_root.join(&sibling).is_file().then_some(sibling)
+    })
+    .or_else(|| single_wasm_next_to(site_root, js_path))
+    .ok_or_else(|| anyhow!("failed to locate the wasm file loaded by {js_name}"))?;
+
+  let head_insert_pattern = Regex::new(r"(?i)\s*</head>").expect("invalid head insert regex");
+  if !head_insert_pattern.is_match(&text) {
+    return Err(anyhow!("failed to locate </head> tag in index.html"));
+  }
+  if let Some(href) = base_href(layout, &options.base_href) {
+    text = set_base_href(&text, &href)?;
+  }
+
+  let preload_pattern = Regex::new(
+    r#"(?i)\s*<link\b[^>]*\brel\s*=\s*["'](?:preload|modulepreload)["'][^>]*\.(?:m?js|wasm)["'][^>]*>"#,
+  )
   .expect("invalid preload regex");
   text = preload_pattern.replace_all(&text, "").into_owned();
+  if let Some(theme) = options.theme.as_ref().filter(|theme| !theme.is_empty()) {
+    let style = format!(
+      r#"<style id="offline-theme">{}</style>"#,
+      theme.stylesheet("")
+    );
+    text = head_insert_pattern
+      .replace(&text, format!("\n    {style}\n  </head>"))
+      .into_owned();
+  }
+
+  // `file://` pages resolve `/app.js` against the file system root.
+  let root_path_pattern = Regex::new(r#"(?i)\b(src|href)\s*=\s*(["'])/([^/"'][^"']*)["']"#)
+    .expect("invalid root path regex");
+  text = root_path_pattern
+    .replace_all(&text, |caps: &regex::Captures| {
+      match site_path(site_root, &caps[3]) {
+        Some(_) => format!("{}={}{}{}", &caps[1], &caps[2], &caps[3], &caps[2]),
+        None => caps[0].to_string(),
+      }
+    })
+    .into_owned();
 
   let replacement = format!(
-    "<script defer src=\"{prefix}{js}\"></script>\n{loader}",
-    prefix = assets_prefix,
-    js = js_name,
-    loader = INLINE_LOADER_TEMPLATE
+    "<script defer src=\"{}\"></script>\n{}",
+    escape_html(&js_name),
+    loader_script(GENERIC_LOADER_BODY)
   );
-  text = script_pattern
-    .replace_all(&text, replacement.as_str())
+  let mut main_patched = false;
+  text = SCRIPT_TAG_PATTERN
+    .replace_all(&text, |caps: &regex::Captures| {
+      let attributes = &caps[1];
+      if !TYPE_MODULE_PATTERN.is_match(attributes) {
+        return caps[0].to_string();
+      }
+      let src = SCRIPT_SRC_PATTERN
+        .captures(attributes)
+        .map(|src| src[1].to_string())
+        .or_else(|| {
+          import_pattern
+            .captures(&caps[2])
+            .map(|import| import[1].to_string())
+        });
+      if !main_patched
+        && src.is_some_and(|src| site_path(site_root, &src).as_ref() == Some(&js_name))
+      {
+        main_patched = true;
+        return replacement.clone();
+      }
+      match options.extra_module_scripts {
+        ModuleScriptHandling::Preserve => caps[0].to_string(),
+        ModuleScriptHandling::Remove => String::new(),
+        ModuleScriptHandling::Demote => TYPE_MODULE_PATTERN.replace(&caps[0], "defer").into_owned(),
+      }
+    })
     .into_owned();
+  if !main_patched {
+    return Err(anyhow!("failed to replace the module script {main_src}"));
+  }
 
   let crossorigin_pattern = Regex::new(r"\s+crossorigin").expect("invalid crossorigin regex");
   text = crossorigin_pattern.replace_all(&text, "").into_owned();
+  if let Some(fallback) = &options.fallback {
+    text = inject_fallback_content(&text, fallback);
+  }
+  if let Some(splash) = &options.splash {
+    text = inject_splash_screen(&text, splash);
+  }
 
-  fs::write(&index_path, &text)
+  let marker =
+    format!("<!-- {PATCH_MARKER} v{PATCH_VERSION} js=\"{js_name}\" wasm=\"{wasm_name}\" -->");
+  text = head_insert_pattern
+    .replace(&text, format!("\n    {marker}\n  </head>"))
+    .into_owned();
+  fs::write(index_path, &text)
     .with_context(|| format!("failed to write {}", index_path.display()))?;
 
-  Ok((js_name, wasm_name))
+  Ok(PatchedSiteIndex {
+    js_name,
+    wasm_name,
+    module_scripts,
+  })
+}
+
+/// Path below `site_root` that a root-relative or relative `url` in the index points at, if that
+/// file exists.
+fn site_path(site_root: &Path, url: &str) -> Option<String> {
+  if external_host(url).is_some() || url.contains(':') {
+    return None;
+  }
+  let path = url.split(['?', '#']).next().unwrap_or_default();
+  let path = path.trim_start_matches("./").trim_start_matches('/');
+  (!path.is_empty() && site_root.join(path).is_file()).then(|| path.to_string())
+}
+
+/// The only `.wasm` file in the directory of `js_path`, relative to `site_root`.
+fn single_wasm_next_to(site_root: &Path, js_path: &Path) -> Option<String> {
+  let dir = js_path.parent().unwrap_or(Path::new(""));
+  let mut names = fs::read_dir(site_root.join(dir))
+    .ok()?
+    .filter_map(|entry| entry.ok())
+    .map(|entry| entry.file_name().to_string_lossy().into_owned())
+    .filter(|name| name.ends_with(".wasm"));
+  let name = names.next()?;
+  if names.next().is_some() {
+    return None;
+  }
+  Some(dir.join(name).to_string_lossy().replace('\\', "/"))
+}
+
+/// `<base href>` value requested by `base_href`, if any.
+fn base_href(layout: &OfflineProjectLayout, base_href: &BaseHref) -> Option<String> {
+  match base_href {
+    BaseHref::Keep => None,
+    BaseHref::SitePrefix => {
+      let prefix = layout.offline_site_root.trim_matches('/');
+      Some(if prefix.is_empty() {
+        "/".to_string()
+      } else {
+        format!("/{prefix}/")
+      })
+    }
+    BaseHref::Custom(href) => Some(href.clone()),
+  }
+}
+
+/// Rewrite the existing `<base>` tag, or insert one at the start of `<head>`.
+pub(crate) fn set_base_href(text: &str, href: &str) -> Result<String> {
+  let base_tag = format!(r#"<base href="{}">"#, escape_html(href));
+  let base_pattern = Regex::new(r"(?i)<base\b[^>]*>").expect("invalid base regex");
+  if base_pattern.is_match(text) {
+    return Ok(base_pattern.replace(text, base_tag.as_str()).into_owned());
+  }
+
+  let head_open_pattern = Regex::new(r"(?i)<head\b[^>]*>").expect("invalid head regex");
+  let head = head_open_pattern
+    .find(text)
+    .ok_or_else(|| anyhow!("failed to locate <head> tag in index.html"))?;
+  Ok(format!(
+    "{}\n    {base_tag}{}",
+    &text[..head.end()],
+    &text[head.end()..]
+  ))
+}
+
+/// Preload links for every file in `assets_dir` of at least `min_size` bytes whose type has a
+/// matching `as` destination.
+fn asset_preload_links(
+  assets_dir: &Path,
+  assets_prefix: &str,
+  min_size: u64,
+) -> Result<Vec<String>> {
+  let mut assets: Vec<(String, u64)> = fs::read_dir(assets_dir)
+    .with_context(|| format!("failed to read assets directory: {}", assets_dir.display()))?
+    .filter_map(|entry| entry.ok())
+    .filter_map(|entry| {
+      let metadata = entry.metadata().ok()?;
+      metadata.is_file().then(|| {
+        (
+          entry.file_name().to_string_lossy().into_owned(),
+          metadata.len(),
+        )
+      })
+    })
+    .filter(|(_, size)| *size >= min_size)
+    .collect();
+  assets.sort();
+
+  Ok(
+    assets
+      .into_iter()
+      .filter_map(|(name, _)| {
+        let extension = name.rsplit_once('.')?.1.to_ascii_lowercase();
+        let attributes = match extension.as_str() {
+          "wasm" => r#"as="fetch" type="application/wasm" crossorigin"#,
+          "js" | "mjs" => r#"as="script""#,
+          "css" => r#"as="style""#,
+          "woff" | "woff2" | "ttf" | "otf" => r#"as="font" crossorigin"#,
+          "png" | "jpg" | "jpeg" | "gif" | "webp" | "avif" | "svg" => r#"as="image""#,
+          _ => return None,
+        };
+        Some(format!(
+          r#"<link rel="preload" {attributes} href="{assets_prefix}{name}">"#
+        ))
+      })
+      .collect(),
+  )
+}
+
+/// Insert the `<noscript>` notice and the hidden load error panel right after `<body>`.
+fn inject_fallback_content(text: &str, fallback: &FallbackContent) -> String {
+  let body_pattern = Regex::new(r"(?i)<body[^>]*>").expect("invalid body regex");
+  let Some(body) = body_pattern.find(text) else {
+    return text.to_string();
+  };
+
+  let markup = format!(
+    r#"
+    <noscript><p class="offline-noscript">{noscript}</p></noscript>
+    <div id="offline-load-error" class="offline-load-error" role="alert" hidden>
+      <h1>{title}</h1>
+      <p>{message}</p>
+      <pre data-offline-error-detail></pre>
+    </div>"#,
+    noscript = escape_html(&fallback.noscript_message),
+    title = escape_html(&fallback.load_error_title),
+    message = escape_html(&fallback.load_error_message),
+  );
+
+  format!("{}{}{}", &text[..body.end()], markup, &text[body.end()..])
+}
+
+/// Loader statements navigating to `route`, indented to fit [`INLINE_LOADER_BODY`].
+fn initial_route_script(route: &InitialRoute) -> Result<String> {
+  let (always, route) = match route {
+    InitialRoute::WhenMissing(route) => (false, route),
+    InitialRoute::Always(route) => (true, route),
+    InitialRoute::Keep => return Ok(String::new()),
+  };
+  // `</script>` inside a string literal would end the inline script early.
+  let route = serde_json::to_string(route)?.replace("</", "<\\/");
+  let condition = if always {
+    format!("!window.__offlineEntryPoint && window.location.hash !== {route}")
+  } else {
+    "!window.location.hash".to_string()
+  };
+  Ok(format!(
+    "        if ({condition}) {{\n          window.location.replace({route});\n        }}\n"
+  ))
+}
+
+/// Add the [`SplashScreen`] stylesheet to the head and its element to the start of the body.
+///
+/// The element stays hidden without JavaScript, so it never covers the `<noscript>` message.
+fn inject_splash_screen(text: &str, splash: &SplashScreen) -> String {
+  let body_pattern = Regex::new(r"(?i)<body[^>]*>").expect("invalid body regex");
+  let Some(body) = body_pattern.find(text) else {
+    return text.to_string();
+  };
+
+  let logo = splash
+    .logo
+    .as_deref()
+    .map(|logo| {
+      format!(
+        r#"
+      <img class="offline-splash-logo" src="{}" alt="">"#,
+        escape_html(logo.trim_start_matches('/'))
+      )
+    })
+    .unwrap_or_default();
+  let template = serde_json::to_string(&splash.progress_text)
+    .expect("string serialization cannot fail")
+    .replace("</", "<\\/");
+  let markup = format!(
+    r#"
+    <div id="offline-splash" class="offline-splash" role="status" aria-live="polite" hidden>{logo}
+      <div class="offline-splash-spinner" aria-hidden="true"></div>
+      <p data-offline-splash-text>{text}</p>
+    </div>
+    <script>
+      (() => {{
+        const splash = document.getElementById('offline-splash');
+        const text = splash.querySelector('[data-offline-splash-text]');
+        splash.hidden = false;
+        window.__offlineProgress = (loaded, total) => {{
+          const percent = total ? Math.floor((loaded * 100) / total) : 0;
+          text.textContent = {template}.split('{{percent}}').join(String(percent));
+        }};
+      }})();
+    </script>"#,
+    text = escape_html(&splash.progress_text.replace("{percent}", "0")),
+  );
+  let text = format!("{}{}{}", &text[..body.end()], markup, &text[body.end()..]);
+
+  let style = "<style id=\"offline-splash-style\">\
+.offline-splash{position:fixed;inset:0;z-index:2147483647;display:flex;flex-direction:column;\
+align-items:center;justify-content:center;gap:1rem;background:var(--background,var(--surface,#fff));\
+color:var(--text,#222);font-family:system-ui,sans-serif}\
+.offline-splash[hidden]{display:none}\
+.offline-splash-logo{max-width:12rem;max-height:6rem}\
+.offline-splash-spinner{width:2.5rem;height:2.5rem;border:.25rem solid rgba(128,128,128,.3);\
+border-top-color:var(--brand,#0a84ff);border-radius:50%;animation:offline-splash-spin 1s linear infinite}\
+@keyframes offline-splash-spin{to{transform:rotate(360deg)}}\
+@media (prefers-reduced-motion:reduce){.offline-splash-spinner{animation-duration:4s}}</style>";
+  let head_insert_pattern = Regex::new(r"(?i)\s*</head>").expect("invalid head insert regex");
+  head_insert_pattern
+    .replace(&text, format!("\n    {style}\n  </head>"))
+    .into_owned()
 }
 
 #[cfg(test)]
@@ -127,9 +1034,13 @@ mod tests {
       offline_site_root: "site".into(),
       collections_dir_name: "programs".into(),
       offline_bundle_root: "target/offline-html".into(),
+      output_root: None,
       index_html_file: "index.html".into(),
       target_dir: "target".into(),
       offline_manifest_json: "offline_manifest.json".into(),
+      scan_max_depth: 32,
+      asset_extensions: Vec::new(),
+      excluded_extensions: Vec::new(),
     }
   }
 
@@ -160,9 +1071,361 @@ mod tests {
     assert_eq!(wasm_name, "module_bg.wasm");
 
     let updated = fs::read_to_string(&index_path).unwrap();
+    assert_eq!(
+      patch_site_index(&layout, dir.path()).unwrap(),
+      (js_name, wasm_name)
+    );
+    assert_eq!(fs::read_to_string(&index_path).unwrap(), updated);
+    assert!(
+      updated.contains(
+        "<!-- offline_dx_bundler:patched v1 js=\"module.js\" wasm=\"module_bg.wasm\" -->"
+      )
+    );
     assert!(updated.contains("window.addEventListener('DOMContentLoaded'"));
     assert!(!updated.contains("crossorigin"));
     assert!(updated.contains("<script defer src=\"assets/module.js\"></script>"));
     assert!(updated.contains("rel=\"preload\" as=\"fetch\" type=\"application/wasm\""));
+    assert!(updated.contains("<noscript>"));
+    assert!(updated.contains("id=\"offline-load-error\""));
+    assert!(updated.contains("showLoadError(err)"));
+  }
+
+  #[test]
+  fn uses_custom_fallback_strings_or_none() {
+    let dir = tempdir().unwrap();
+    let layout = layout();
+    fs::create_dir_all(dir.path().join("assets")).unwrap();
+    fs::write(dir.path().join("assets/module_bg.wasm"), "wasm").unwrap();
+    let original = r#"<html><head></head><body class="app"><script type="module" src="/./assets/module.js"></script></body></html>"#;
+    let index_path = dir.path().join("index.html");
+
+    fs::write(&index_path, original).unwrap();
+    let options = SitePatchOptions {
+      fallback: Some(FallbackContent {
+        noscript_message: "Bitte JavaScript aktivieren".into(),
+        load_error_title: "Fehler & Hilfe".into(),
+        ..FallbackContent::default()
+      }),
+      ..SitePatchOptions::default()
+    };
+    patch_site_index_with(&layout, dir.path(), &options).unwrap();
+    let updated = fs::read_to_string(&index_path).unwrap();
+    assert!(updated.contains("<body class=\"app\">\n    <noscript>"));
+    assert!(updated.contains("Bitte JavaScript aktivieren"));
+    assert!(updated.contains("Fehler &amp; Hilfe"));
+
+    fs::write(&index_path, original).unwrap();
+    let options = SitePatchOptions {
+      fallback: None,
+      ..SitePatchOptions::default()
+    };
+    patch_site_index_with(&layout, dir.path(), &options).unwrap();
+    let updated = fs::read_to_string(&index_path).unwrap();
+    assert!(!updated.contains("<noscript>"));
+  }
+
+  #[test]
+  fn injects_splash_screen_removed_by_loader() {
+    let dir = tempdir().unwrap();
+    let layout = layout();
+    fs::create_dir_all(dir.path().join("assets")).unwrap();
+    fs::write(dir.path().join("assets/module_bg.wasm"), "wasm").unwrap();
+    let index_path = dir.path().join("index.html");
+    fs::write(
+      &index_path,
+      r#"<html><head></head><body><script type="module" src="/./assets/module.js"></script></body></html>"#,
+    )
+    .unwrap();
+
+    let options = SitePatchOptions {
+      splash: Some(SplashScreen {
+        logo: Some("/branding/logo.svg".into()),
+        progress_text: "Lade </script> {percent} %".into(),
+      }),
+      ..SitePatchOptions::default()
+    };
+    patch_site_index_with(&layout, dir.path(), &options).unwrap();
+    let updated = fs::read_to_string(&index_path).unwrap();
+    assert!(updated.contains(r#"<style id="offline-splash-style">"#));
+    assert!(
+      updated.contains(r#"<img class="offline-splash-logo" src="branding/logo.svg" alt="">"#)
+    );
+    assert!(updated.contains("<p data-offline-splash-text>Lade &lt;/script&gt; 0 %</p>"));
+    assert!(updated.contains(r#"text.textContent = "Lade <\/script> {percent} %""#));
+    assert!(updated.contains("init(wasmBytes).then(hideSplash, (err) => {"));
+  }
+
+  #[test]
+  fn renders_configured_initial_route() {
+    let dir = tempdir().unwrap();
+    let layout = layout();
+    fs::create_dir_all(dir.path().join("assets")).unwrap();
+    fs::write(dir.path().join("assets/module_bg.wasm"), "wasm").unwrap();
+    let index_path = dir.path().join("index.html");
+    let original = r#"<html><head></head><body><script type="module" src="/./assets/module.js"></script></body></html>"#;
+
+    for (initial_route, expected) in [
+      (
+        InitialRoute::default(),
+        Some("if (!window.location.hash) {\n          window.location.replace(\"#/\");"),
+      ),
+      (
+        InitialRoute::Always("#/programs/intro".into()),
+        Some(
+          "if (!window.__offlineEntryPoint && window.location.hash !== \"#/programs/intro\") {\n          window.location.replace(\"#/programs/intro\");",
+        ),
+      ),
+      (InitialRoute::Keep, None),
+    ] {
+      fs::write(&index_path, original).unwrap();
+      let options = SitePatchOptions {
+        initial_route,
+        ..SitePatchOptions::default()
+      };
+      patch_site_index_with(&layout, dir.path(), &options).unwrap();
+      let updated = fs::read_to_string(&index_path).unwrap();
+      assert!(!updated.contains("__INITIAL_ROUTE__"));
+      match expected {
+        Some(script) => assert!(updated.contains(script), "{updated}"),
+        None => assert!(!updated.contains("window.location.replace")),
+      }
+    }
+  }
+
+  #[test]
+  fn strips_denied_external_tags() {
+    let html = r#"<head>
+      <script async src="https://www.googletagmanager.com/gtag/js?id=G-1"></script>
+      <link rel="preconnect" href="//stats.example.org">
+      <link rel="stylesheet" href="https://fonts.example.com/font.css">
+      <img src="https://pixel.hotjar.com/p.gif" alt="">
+      <img src="assets/lazy.png" data-src="https://pixel.hotjar.com/lazy.gif" alt="">
+      <script src="assets/app.js"></script>
+    </head>"#;
+
+    let stripped = strip_external_tags(html, &ExternalTagFilter::default());
+    assert!(!stripped.contains("googletagmanager"));
+    assert!(!stripped.contains("hotjar.com/p.gif"));
+    assert!(stripped.contains("assets/lazy.png"));
+    assert!(stripped.contains("stats.example.org"));
+    assert!(stripped.contains("assets/app.js"));
+
+    let filter = ExternalTagFilter {
+      deny_hosts: vec!["*".into()],
+      allow_hosts: vec!["example.com".into()],
+    };
+    let stripped = strip_external_tags(html, &filter);
+    assert!(!stripped.contains("stats.example.org"));
+    assert!(stripped.contains("fonts.example.com"));
+    assert!(stripped.contains("assets/app.js"));
+  }
+
+  #[test]
+  fn patches_main_module_and_handles_extra_module_scripts() {
+    let dir = tempdir().unwrap();
+    let layout = layout();
+    fs::create_dir_all(dir.path().join("assets")).unwrap();
+    fs::write(dir.path().join("assets/app_bg.wasm"), "wasm").unwrap();
+    fs::write(dir.path().join("assets/chunk_1.wasm"), "chunk").unwrap();
+    fs::write(
+      dir.path().join("assets/widget.js"),
+      "console.log('widget');",
+    )
+    .unwrap();
+    fs::write(dir.path().join("assets/app.js"), "__wbg_init({});").unwrap();
+    let original = r#"<html><head>
+      <script type="module" src="/./assets/widget.js"></script>
+      <script src="/./assets/app.js" type="module"></script>
+    </head><body></body></html>"#;
+    let index_path = dir.path().join("index.html");
+
+    fs::write(&index_path, original).unwrap();
+    let patched = patch_site_index_with(&layout, dir.path(), &SitePatchOptions::default()).unwrap();
+    assert_eq!(patched.js_name, "app.js");
+    assert_eq!(patched.wasm_name, "app_bg.wasm");
+    assert_eq!(patched.module_scripts, vec![
+      "assets/widget.js".to_string(),
+      "assets/app.js".to_string()
+    ]);
+    let updated = fs::read_to_string(&index_path).unwrap();
+    assert!(updated.contains(r#"<script defer src="assets/widget.js"></script>"#));
+    assert!(updated.contains(r#"<script defer src="assets/app.js"></script>"#));
+    assert_eq!(updated.matches("DOMContentLoaded").count(), 1);
+    assert!(!updated.contains("type=\"module\""));
+
+    for (handling, expected) in [
+      (ModuleScriptHandling::Preserve, true),
+      (ModuleScriptHandling::Remove, false),
+    ] {
+      fs::write(&index_path, original).unwrap();
+      let options = SitePatchOptions {
+        extra_module_scripts: handling,
+        ..SitePatchOptions::default()
+      };
+      patch_site_index_with(&layout, dir.path(), &options).unwrap();
+      let updated = fs::read_to_string(&index_path).unwrap();
+      assert_eq!(updated.contains("widget.js"), expected);
+    }
+  }
+
+  #[test]
+  fn applies_preload_policies() {
+    let dir = tempdir().unwrap();
+    let layout = layout();
+    fs::create_dir_all(dir.path().join("assets")).unwrap();
+    fs::write(dir.path().join("assets/app_bg.wasm"), vec![0u8; 64]).unwrap();
+    fs::write(dir.path().join("assets/hero.png"), vec![0u8; 32]).unwrap();
+    fs::write(dir.path().join("assets/tiny.css"), "a{}").unwrap();
+    let original = r#"<html><head>
+      <link rel="preload" href="/./assets/app.js" as="script">
+      <link rel="preload" href="/./assets/font.woff2" as="font">
+      <script type="module" src="/./assets/app.js"></script>
+    </head><body></body></html>"#;
+    let index_path = dir.path().join("index.html");
+
+    let patch = |preloads| {
+      fs::write(&index_path, original).unwrap();
+      let options = SitePatchOptions {
+        preloads,
+        ..SitePatchOptions::default()
+      };
+      patch_site_index_with(&layout, dir.path(), &options).unwrap();
+      fs::read_to_string(&index_path).unwrap()
+    };
+
+    let updated = patch(PreloadPolicy::Preserve);
+    assert!(updated.contains(r#"href="assets/app.js" as="script""#));
+    assert!(updated.contains("assets/font.woff2"));
+    assert!(updated.contains(r#"href="assets/app_bg.wasm""#));
+
+    let updated = patch(PreloadPolicy::Regenerate { min_size: 16 });
+    assert!(!updated.contains("font.woff2"));
+    assert!(updated.contains(r#"<link rel="preload" as="image" href="assets/hero.png">"#));
+    assert!(updated.contains(r#"as="fetch" type="application/wasm" href="assets/app_bg.wasm""#));
+    assert!(!updated.contains("tiny.css"));
+
+    let updated = patch(PreloadPolicy::Skip);
+    assert!(!updated.contains("rel=\"preload\""));
+  }
+
+  #[test]
+  fn inserts_or_rewrites_base_href() {
+    let html = "<html><head lang=\"en\"><title>x</title></head></html>";
+    assert_eq!(
+      set_base_href(html, "/site/deep/").unwrap(),
+      "<html><head lang=\"en\">\n    <base href=\"/site/deep/\"><title>x</title></head></html>"
+    );
+
+    let html = "<head><base target=\"_self\" href=\"/\"></head>";
+    assert_eq!(
+      set_base_href(html, "./").unwrap(),
+      "<head><base href=\"./\"></head>"
+    );
+
+    let dir = tempdir().unwrap();
+    let layout = OfflineProjectLayout {
+      offline_site_root: "site/deep".into(),
+      ..layout()
+    };
+    fs::create_dir_all(dir.path().join("assets")).unwrap();
+    fs::write(dir.path().join("assets/module_bg.wasm"), "wasm").unwrap();
+    fs::write(
+      dir.path().join("index.html"),
+      r#"<html><head></head><body><script type="module" src="/./assets/module.js"></script></body></html>"#,
+    )
+    .unwrap();
+    let options = SitePatchOptions {
+      base_href: BaseHref::SitePrefix,
+      ..SitePatchOptions::default()
+    };
+    patch_site_index_with(&layout, dir.path(), &options).unwrap();
+    let updated = fs::read_to_string(dir.path().join("index.html")).unwrap();
+    assert!(updated.contains("<base href=\"/site/deep/\">"));
+  }
+
+  #[test]
+  fn injects_theme_stylesheet() {
+    let dir = tempdir().unwrap();
+    let layout = layout();
+    fs::create_dir_all(dir.path().join("assets")).unwrap();
+    fs::write(dir.path().join("assets/module_bg.wasm"), "wasm").unwrap();
+    fs::write(
+      dir.path().join("index.html"),
+      r#"<html><head></head><body><script type="module" src="/./assets/module.js"></script></body></html>"#,
+    )
+    .unwrap();
+    let options = SitePatchOptions {
+      theme: Some(ThemePalette {
+        colors: vec![("brand".into(), "#0a84ff".into())],
+        dark_colors: vec![("brand".into(), "#409cff".into())],
+        logo: None,
+      }),
+      ..SitePatchOptions::default()
+    };
+    patch_site_index_with(&layout, dir.path(), &options).unwrap();
+    let updated = fs::read_to_string(dir.path().join("index.html")).unwrap();
+    assert!(updated.contains(
+      "<style id=\"offline-theme\">:root{--brand:#0a84ff;}@media (prefers-color-scheme: dark){:root{--brand:#409cff;}}</style>"
+    ));
+  }
+
+  #[test]
+  fn patches_plain_wasm_bindgen_sites_in_generic_mode() {
+    let dir = tempdir().unwrap();
+    let layout = layout();
+    fs::write(
+      dir.path().join("app-9f2c1e7a.js"),
+      "let wasm;\nexport function start() {}\nasync function __wbg_init(module_or_path) {\n  if (typeof module_or_path === 'undefined') { module_or_path = new URL('app-9f2c1e7a_bg.wasm', import.meta.url); }\n}\nexport { __wbg_init as default };\n",
+    )
+    .unwrap();
+    fs::write(dir.path().join("app-9f2c1e7a_bg.wasm"), [0, 97, 115, 109]).unwrap();
+    fs::write(dir.path().join("styles.css"), "body{}").unwrap();
+    fs::write(
+      dir.path().join("index.html"),
+      r#"<html><head>
+<link rel="stylesheet" href="/styles.css" integrity="sha384-x" crossorigin="anonymous">
+<link rel="modulepreload" href="/app-9f2c1e7a.js" crossorigin="anonymous">
+<link rel="preload" href="/app-9f2c1e7a_bg.wasm" as="fetch" type="application/wasm" crossorigin="anonymous">
+<script type="module">
+import init, * as bindings from '/app-9f2c1e7a.js';
+const wasm = await init({ module_or_path: '/app-9f2c1e7a_bg.wasm' });
+window.wasmBindings = bindings;
+dispatchEvent(new CustomEvent("TrunkApplicationStarted", {detail: {wasm}}));
+</script>
+</head><body></body></html>"#,
+    )
+    .unwrap();
+
+    let options = SitePatchOptions {
+      mode: SitePatchMode::Generic,
+      ..SitePatchOptions::default()
+    };
+    let patched = patch_site_index_with(&layout, dir.path(), &options).unwrap();
+    assert_eq!(patched.js_name, "app-9f2c1e7a.js");
+    assert_eq!(patched.wasm_name, "app-9f2c1e7a_bg.wasm");
+    assert_eq!(patched.module_scripts, ["/app-9f2c1e7a.js"]);
+    let index = fs::read_to_string(dir.path().join("index.html")).unwrap();
+    assert!(index.contains(r#"href="styles.css""#));
+    assert!(index.contains(r#"<script defer src="app-9f2c1e7a.js"></script>"#));
+    assert!(index.contains("window.__offlineInit"));
+    assert!(!index.contains("preload") && !index.contains("crossorigin"));
+    assert!(!index.contains("type=\"module\"") && !index.contains("__dx_mainInit"));
+    let repatched = patch_site_index_with(&layout, dir.path(), &options).unwrap();
+    assert_eq!(
+      (repatched.js_name.as_str(), repatched.wasm_name.as_str()),
+      (patched.js_name.as_str(), patched.wasm_name.as_str())
+    );
+
+    crate::bundle::js_patch::patch_site_module(&layout, dir.path(), &patched, &options, || {
+      unreachable!("generic modules do not need the binary name")
+    })
+    .unwrap();
+    let js = fs::read_to_string(dir.path().join("app-9f2c1e7a.js")).unwrap();
+    assert!(js.contains(
+      "window.__offlineInit=()=>__offlineWasmReady(window.__offlineProgress)\
+.then(()=>__offlineModule.default({module_or_path:__offlineWasmBytes}))"
+    ));
+    assert!(js.contains("return {start,default:__wbg_init};"));
+    assert!(!js.contains("import.meta") && !js.contains("export "));
   }
 }
@@ -0,0 +1,183 @@
+//! Headless-browser smoke test of a patched offline bundle.
+//!
+//! Regressions in the regex-based patching usually only show up when the bundle is opened in a
+//! browser. This module loads the patched index in headless Chromium, waits for the WebAssembly
+//! module to be published as `__dx_mainWasm`, and fails when that does not happen or when the
+//! page logs errors on the way.
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, anyhow};
+use headless_chrome::protocol::cdp::Log::LogEntryLevel;
+use headless_chrome::protocol::cdp::Runtime::ConsoleAPICalledEventTypeOption;
+use headless_chrome::protocol::cdp::types::Event;
+use headless_chrome::{Browser, LaunchOptions};
+
+use crate::project::OfflineProjectLayout;
+
+/// Global set by the patched JavaScript bootstrap once the WebAssembly module is running.
+pub const READY_GLOBAL: &str = "__dx_mainWasm";
+
+/// Settings for [`smoke_test`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SmokeTestOptions {
+  /// URL to open instead of the bundled index, e.g. a preview server.
+  pub url: Option<String>,
+  /// How long to wait for the WebAssembly module to start.
+  pub timeout: Duration,
+}
+
+impl Default for SmokeTestOptions {
+  fn default() -> Self {
+    Self {
+      url: None,
+      timeout: Duration::from_secs(30),
+    }
+  }
+}
+
+/// Open the patched index below `site_root` in headless Chromium and check that it boots.
+///
+/// The page is loaded from `file://` unless [`SmokeTestOptions::url`] is set. Fails when
+/// `__dx_mainWasm` is not set within the timeout or when the page reports console errors or
+/// uncaught exceptions.
+pub fn smoke_test(
+  layout: &OfflineProjectLayout,
+  site_root: &Path,
+  options: &SmokeTestOptions,
+) -> Result<()> {
+  let url = match &options.url {
+    Some(url) => url.clone(),
+    None => {
+      let index = site_root.join(&layout.index_html_file);
+      let index = index
+        .canonicalize()
+        .with_context(|| format!("failed to resolve {}", index.display()))?;
+      file_url(&index)
+    }
+  };
+
+  let browser = Browser::new(LaunchOptions {
+    headless: true,
+    ..Default::default()
+  })
+  .context("failed to launch headless Chromium")?;
+  let tab = browser.new_tab().context("failed to open browser tab")?;
+
+  let errors = Arc::new(Mutex::new(Vec::new()));
+  let collected = Arc::clone(&errors);
+  tab
+    .add_event_listener(Arc::new(move |event: &Event| {
+      let message = match event {
+        Event::LogEntryAdded(added) if added.params.entry.level == LogEntryLevel::Error => {
+          Some(added.params.entry.text.clone())
+        }
+        Event::RuntimeConsoleAPICalled(called)
+          if called.params.Type == ConsoleAPICalledEventTypeOption::Error =>
+        {
+          Some(
+            called
+              .params
+              .args
+              .iter()
+              .map(|arg| match (&arg.value, &arg.description) {
+                (Some(serde_json::Value::String(text)), _) => text.clone(),
+                (Some(value), _) => value.to_string(),
+                (None, Some(description)) => description.clone(),
+                (None, None) => String::new(),
+              })
+              .collect::<Vec<_>>()
+              .join(" "),
+          )
+        }
+        Event::RuntimeExceptionThrown(thrown) => {
+          let details = &thrown.params.exception_details;
+          Some(
+            details
+              .exception
+              .as_ref()
+              .and_then(|exception| exception.description.clone())
+              .unwrap_or_else(|| details.text.clone()),
+          )
+        }
+        _ => None,
+      };
+      if let Some(message) = message {
+        collected.lock().unwrap().push(message);
+      }
+    }))
+    .context("failed to listen for console events")?;
+  tab.enable_log()?;
+  tab.enable_runtime()?;
+
+  tab
+    .navigate_to(&url)
+    .and_then(|tab| tab.wait_until_navigated())
+    .with_context(|| format!("failed to load {url}"))?;
+
+  let expression = format!("typeof window.{READY_GLOBAL} !== 'undefined'");
+  let deadline = Instant::now() + options.timeout;
+  let mut ready = false;
+  while Instant::now() < deadline {
+    let result = tab.evaluate(&expression, false)?;
+    if result.value == Some(serde_json::Value::Bool(true)) {
+      ready = true;
+      break;
+    }
+    thread::sleep(Duration::from_millis(100));
+  }
+
+  let errors = errors.lock().unwrap();
+  let mut problems = Vec::new();
+  if !ready {
+    problems.push(format!(
+      "`{READY_GLOBAL}` was not set within {:?}",
+      options.timeout
+    ));
+  }
+  problems.extend(errors.iter().map(|error| format!("console error: {error}")));
+
+  if problems.is_empty() {
+    Ok(())
+  } else {
+    Err(anyhow!(
+      "smoke test of {url} failed:\n  {}",
+      problems.join("\n  ")
+    ))
+  }
+}
+
+fn file_url(path: &Path) -> String {
+  let path = path.to_string_lossy().replace('\\', "/");
+  let path = path.trim_start_matches("//?/");
+  let encoded = path
+    .replace('%', "%25")
+    .replace(' ', "%20")
+    .replace('#', "%23")
+    .replace('?', "%3F");
+  if encoded.starts_with('/') {
+    format!("file://{encoded}")
+  } else {
+    format!("file:///{encoded}")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn builds_file_urls() {
+    assert_eq!(
+      file_url(Path::new("/media/usb/My Bundle/index.html")),
+      "file:///media/usb/My%20Bundle/index.html"
+    );
+    assert_eq!(
+      file_url(Path::new(r"\\?\C:\bundle\index.html")),
+      "file:///C:/bundle/index.html"
+    );
+  }
+}
@@ -0,0 +1,276 @@
+//! Split an assembled offline bundle into independent per-collection bundles.
+//!
+//! Each collection bundle contains a copy of the patched site, only the assets belonging to
+//! that collection and an offline manifest restricted to the collection's entries, so a
+//! single program can be distributed on its own media.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, anyhow};
+
+use crate::bundle::manifest::{OfflineManifest, resolve_site_root};
+use crate::models::{OFFLINE_MANIFEST_SCHEMA_VERSION, OfflineEntrySummary, OfflineManifestSummary};
+use crate::project::OfflineProjectLayout;
+
+/// Summary of a bundle written by [`write_collection_bundles`].
+#[derive(Debug, Clone)]
+pub struct CollectionBundle {
+  /// Collection the bundle was produced for.
+  pub collection_id: String,
+  /// Root directory of the standalone bundle.
+  pub root: PathBuf,
+  /// Number of entries listed in the bundle manifest.
+  pub entries: usize,
+  /// Number of collection asset files copied into the bundle.
+  pub assets: usize,
+}
+
+/// Write one standalone bundle per collection listed in `manifest` below `output_root/<id>/`.
+///
+/// `bundle_root` is the assembled bundle (the directory
+/// [`bundle_root`](OfflineProjectLayout::bundle_root) points at). Everything outside the site's collections directory is shared and copied into every
+/// bundle; the collections directory is narrowed to a single collection.
+pub fn write_collection_bundles(
+  layout: &OfflineProjectLayout,
+  bundle_root: &Path,
+  manifest: &OfflineManifest,
+  output_root: &Path,
+) -> Result<Vec<CollectionBundle>> {
+  if output_root.starts_with(bundle_root) {
+    return Err(anyhow!(
+      "collection bundles must be written outside of {}",
+      bundle_root.display()
+    ));
+  }
+
+  let (site_relative, site_prefix) = site_location(layout, manifest);
+  let collections_relative = site_relative.join(&layout.collections_dir_name);
+
+  let collection_ids: BTreeSet<&str> = manifest
+    .entries
+    .iter()
+    .map(|entry| entry.collection_id.as_str())
+    .collect();
+
+  let mut bundles = Vec::new();
+  for collection_id in collection_ids {
+    let root = output_root.join(collection_id);
+    if root.exists() {
+      fs::remove_dir_all(&root).with_context(|| format!("failed to clear {}", root.display()))?;
+    }
+
+    copy_tree(bundle_root, &root, &|relative| {
+      relative == collections_relative || relative == Path::new(&layout.offline_manifest_json)
+    })?;
+
+    let source_assets = bundle_root.join(&collections_relative).join(collection_id);
+    let assets = if source_assets.is_dir() {
+      copy_tree(
+        &source_assets,
+        &root.join(&collections_relative).join(collection_id),
+        &|_| false,
+      )?
+    } else {
+      0
+    };
+
+    let asset_prefix = format!("{}/{}/", layout.collections_dir_name, collection_id);
+    let summary = OfflineManifestSummary {
+      schema_version: OFFLINE_MANIFEST_SCHEMA_VERSION,
+      site_root: site_prefix.clone(),
+      entries: manifest
+        .entries
+        .iter()
+        .filter(|entry| entry.collection_id == collection_id)
+        .map(|entry| OfflineEntrySummary {
+          collection_id: entry.collection_id.clone(),
+          entry_id: entry.entry_id.clone(),
+          title: entry.title.clone(),
+          section: entry.section.clone(),
+          asset_paths: entry.asset_paths.clone(),
+          headings: entry.headings.clone(),
+          priority_assets: entry.priority_assets.clone(),
+          video_posters: entry.video_posters.clone(),
+        })
+        .collect(),
+      hero_assets: manifest
+        .hero_assets
+        .iter()
+        .filter(|path| path.starts_with(&asset_prefix))
+        .cloned()
+        .collect(),
+      asset_hashes: manifest
+        .asset_hashes
+        .iter()
+        .filter(|(path, _)| path.starts_with(&asset_prefix))
+        .map(|(path, digest)| (path.clone(), digest.clone()))
+        .collect(),
+      asset_aliases: manifest
+        .asset_aliases
+        .iter()
+        .filter(|(path, canonical)| {
+          path.starts_with(&asset_prefix) && canonical.starts_with(&asset_prefix)
+        })
+        .map(|(path, canonical)| (path.clone(), canonical.clone()))
+        .collect(),
+      asset_sizes: manifest
+        .asset_sizes
+        .iter()
+        .filter(|(path, _)| path.starts_with(&asset_prefix))
+        .map(|(path, size)| (path.clone(), *size))
+        .collect(),
+      asset_modified: manifest
+        .asset_modified
+        .iter()
+        .filter(|(path, _)| path.starts_with(&asset_prefix))
+        .map(|(path, modified)| (path.clone(), *modified))
+        .collect(),
+      provenance: manifest.provenance.clone(),
+    };
+    let manifest_path = root.join(&layout.offline_manifest_json);
+    fs::write(&manifest_path, serde_json::to_string_pretty(&summary)?)
+      .with_context(|| format!("failed to write {}", manifest_path.display()))?;
+
+    bundles.push(CollectionBundle {
+      collection_id: collection_id.to_string(),
+      root,
+      entries: summary.entries.len(),
+      assets,
+    });
+  }
+
+  Ok(bundles)
+}
+
+/// Site directory relative to the bundle root together with its manifest prefix.
+pub(crate) fn site_location(
+  layout: &OfflineProjectLayout,
+  manifest: &OfflineManifest,
+) -> (PathBuf, String) {
+  let (site_root, prefix) = resolve_site_root(layout, manifest);
+  let relative = site_root
+    .strip_prefix(layout.bundle_root(Path::new("")))
+    .map(Path::to_path_buf)
+    .unwrap_or_default();
+  (relative, prefix)
+}
+
+/// Recursively copy `source` into `destination`, returning the number of files copied.
+///
+/// `skip` receives paths relative to `source` and prunes matching files and directories.
+pub(crate) fn copy_tree(
+  source: &Path,
+  destination: &Path,
+  skip: &dyn Fn(&Path) -> bool,
+) -> Result<usize> {
+  copy_subtree(source, destination, Path::new(""), skip)
+}
+
+fn copy_subtree(
+  source: &Path,
+  destination: &Path,
+  relative: &Path,
+  skip: &dyn Fn(&Path) -> bool,
+) -> Result<usize> {
+  let current = source.join(relative);
+  let target = destination.join(relative);
+  fs::create_dir_all(&target).with_context(|| format!("failed to create {}", target.display()))?;
+
+  let mut copied = 0;
+  for entry in
+    fs::read_dir(&current).with_context(|| format!("failed to read {}", current.display()))?
+  {
+    let entry = entry?;
+    let child = relative.join(entry.file_name());
+    if skip(&child) {
+      continue;
+    }
+
+    if entry.file_type()?.is_dir() {
+      copied += copy_subtree(source, destination, &child, skip)?;
+    } else {
+      fs::copy(entry.path(), destination.join(&child)).with_context(|| {
+        format!(
+          "failed to copy {} to {}",
+          entry.path().display(),
+          destination.join(&child).display()
+        )
+      })?;
+      copied += 1;
+    }
+  }
+
+  Ok(copied)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bundle::manifest::load_manifest;
+  use tempfile::tempdir;
+
+  fn layout() -> OfflineProjectLayout {
+    OfflineProjectLayout {
+      excluded_dir_name: "prod".into(),
+      excluded_path_fragment: "/prod/".into(),
+      ..Default::default()
+    }
+  }
+
+  fn write_file(path: &Path, contents: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, contents).unwrap();
+  }
+
+  #[test]
+  fn writes_one_bundle_per_collection() {
+    let dir = tempdir().unwrap();
+    let bundle_root = dir.path().join("bundle");
+    write_file(&bundle_root.join("index.html"), "launcher");
+    write_file(&bundle_root.join("site/index.html"), "site");
+    write_file(&bundle_root.join("site/assets/app.wasm"), "wasm");
+    write_file(&bundle_root.join("site/programs/P001/a.png"), "a");
+    write_file(&bundle_root.join("site/programs/P002/b.png"), "b");
+    write_file(
+      &bundle_root.join("offline_manifest.json"),
+      r#"{
+        "site_root": "site",
+        "hero_assets": ["programs/P001/a.png", "programs/P002/b.png"],
+        "entries": [
+          {"collection_id": "P001", "entry_id": "intro", "asset_paths": ["programs/P001/a.png"]},
+          {"collection_id": "P002", "entry_id": "intro", "asset_paths": ["programs/P002/b.png"]}
+        ]
+      }"#,
+    );
+
+    let manifest = load_manifest(&bundle_root.join("offline_manifest.json")).unwrap();
+    let output = dir.path().join("split");
+    let bundles = write_collection_bundles(&layout(), &bundle_root, &manifest, &output).unwrap();
+
+    assert_eq!(bundles.len(), 2);
+    assert_eq!(bundles[0].collection_id, "P001");
+    assert_eq!(bundles[0].entries, 1);
+    assert_eq!(bundles[0].assets, 1);
+
+    let first = output.join("P001");
+    assert!(first.join("index.html").is_file());
+    assert!(first.join("site/assets/app.wasm").is_file());
+    assert!(first.join("site/programs/P001/a.png").is_file());
+    assert!(!first.join("site/programs/P002").exists());
+
+    let split_manifest = load_manifest(&first.join("offline_manifest.json")).unwrap();
+    assert_eq!(split_manifest.entries.len(), 1);
+    assert_eq!(split_manifest.hero_assets, vec!["programs/P001/a.png"]);
+  }
+
+  #[test]
+  fn rejects_output_inside_bundle() {
+    let dir = tempdir().unwrap();
+    let manifest: OfflineManifest = serde_json::from_str(r#"{"entries": []}"#).unwrap();
+    let result =
+      write_collection_bundles(&layout(), dir.path(), &manifest, &dir.path().join("split"));
+    assert!(result.is_err());
+  }
+}
@@ -0,0 +1,249 @@
+//! Deterministic names for the hashed JavaScript, stylesheet and WebAssembly outputs.
+//!
+//! `dx build` appends a content hash to every asset, e.g. `app-dxh4f0c2a.js`, so paths change
+//! with each release. [`stabilize_asset_names`] generalises the stylesheet aliases: it renames
+//! the hashed files in the assets directory to `<stem>.<ext>` and rewrites the references in the
+//! site's HTML pages and in the renamed scripts and stylesheets, so external integrations such
+//! as LMS wrappers or signage players can hardcode paths across releases.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use aho_corasick::AhoCorasick;
+use anyhow::{Context, Result, anyhow};
+
+use crate::project::OfflineProjectLayout;
+
+/// Extensions of the assets renamed by [`stabilize_asset_names`].
+pub const STABLE_NAME_EXTENSIONS: [&str; 4] = ["js", "mjs", "css", "wasm"];
+
+/// Which file name suffixes [`stable_name`] treats as content hashes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StableNameHashes {
+  /// Only the `dxh`-prefixed hashes written by `dx`.
+  #[default]
+  Dx,
+  /// Also any suffix of at least eight letters and digits that contains a digit, for outputs
+  /// of other bundlers. This also matches authored names such as `print-20240101.css`.
+  Any,
+}
+
+/// Rename the hashed outputs in the assets directory below `site_root` to stable names.
+///
+/// Returns the renamed files as `hashed name → stable name`. Fails without touching anything
+/// when two outputs would get the same name or the stable name is already taken. Every page and
+/// script is read and rewritten in memory before the first file changes, and a failure while
+/// writing restores the renamed files and original contents.
+pub fn stabilize_asset_names(
+  layout: &OfflineProjectLayout,
+  site_root: &Path,
+  hashes: StableNameHashes,
+) -> Result<BTreeMap<String, String>> {
+  let assets_dir = site_root.join(layout.entry_assets_dir());
+  let mut names: Vec<String> = fs::read_dir(&assets_dir)
+    .with_context(|| format!("failed to read assets directory {}", assets_dir.display()))?
+    .filter_map(|entry| entry.ok())
+    .filter(|entry| entry.file_type().is_ok_and(|ft| ft.is_file()))
+    .filter_map(|entry| entry.file_name().to_str().map(str::to_string))
+    .collect();
+  names.sort();
+
+  let mut renames = BTreeMap::new();
+  let mut targets: BTreeMap<String, String> = BTreeMap::new();
+  for name in &names {
+    let Some(stable) = stable_name(name, hashes) else {
+      continue;
+    };
+    if names.contains(&stable) {
+      return Err(anyhow!(
+        "cannot rename {name}: {stable} already exists in {}",
+        assets_dir.display()
+      ));
+    }
+    if let Some(other) = targets.insert(stable.clone(), name.clone()) {
+      return Err(anyhow!(
+        "cannot rename both {other} and {name} to {stable}; remove stale build outputs"
+      ));
+    }
+    renames.insert(name.clone(), stable);
+  }
+  if renames.is_empty() {
+    return Ok(renames);
+  }
+
+  let patterns: Vec<&String> = renames.keys().collect();
+  let replacements: Vec<&String> = renames.values().collect();
+  let matcher = AhoCorasick::new(&patterns)?;
+  let pages = fs::read_dir(site_root)
+    .with_context(|| format!("failed to read {}", site_root.display()))?
+    .filter_map(|entry| entry.ok())
+    .map(|entry| entry.path())
+    .filter(|path| path.extension().is_some_and(|ext| ext == "html"));
+  let scripts = renames
+    .keys()
+    .filter(|name| !name.ends_with(".wasm"))
+    .map(|name| assets_dir.join(name));
+  let mut rewrites = Vec::new();
+  for path in pages.chain(scripts) {
+    let text =
+      fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))?;
+    let rewritten = matcher.replace_all(&text, &replacements);
+    if rewritten != text {
+      rewrites.push((path, text, rewritten));
+    }
+  }
+
+  let mut applied = Applied::default();
+  if let Err(err) = applied.apply(&assets_dir, &renames, &rewrites) {
+    applied.roll_back();
+    return Err(err);
+  }
+  Ok(renames)
+}
+
+/// Changes made by [`stabilize_asset_names`] so far, undone in reverse order on failure.
+#[derive(Default)]
+struct Applied {
+  renamed: Vec<(PathBuf, PathBuf)>,
+  written: Vec<(PathBuf, String)>,
+}
+
+impl Applied {
+  fn apply(
+    &mut self,
+    assets_dir: &Path,
+    renames: &BTreeMap<String, String>,
+    rewrites: &[(PathBuf, String, String)],
+  ) -> Result<()> {
+    for (hashed, stable) in renames {
+      let (from, to) = (assets_dir.join(hashed), assets_dir.join(stable));
+      fs::rename(&from, &to)
+        .with_context(|| format!("failed to rename {} to {}", from.display(), to.display()))?;
+      self.renamed.push((from, to));
+    }
+    for (path, original, rewritten) in rewrites {
+      let path = self
+        .renamed
+        .iter()
+        .find(|(from, _)| from == path)
+        .map_or(path, |(_, to)| to);
+      fs::write(path, rewritten).with_context(|| format!("failed to write {}", path.display()))?;
+      self.written.push((path.clone(), original.clone()));
+    }
+    Ok(())
+  }
+
+  fn roll_back(self) {
+    for (path, original) in self.written.into_iter().rev() {
+      let _ = fs::write(path, original);
+    }
+    for (from, to) in self.renamed.into_iter().rev() {
+      let _ = fs::rename(to, from);
+    }
+  }
+}
+
+/// `<stem>.<ext>` for a hashed `<stem>-<hash>.<ext>` output with one of the
+/// [`STABLE_NAME_EXTENSIONS`].
+///
+/// `hashes` selects which suffixes count as hashes, see [`StableNameHashes`].
+pub fn stable_name(file_name: &str, hashes: StableNameHashes) -> Option<String> {
+  let (base, extension) = file_name.rsplit_once('.')?;
+  if !STABLE_NAME_EXTENSIONS.contains(&extension) {
+    return None;
+  }
+  let (stem, hash) = base.rsplit_once('-')?;
+  let is_hash = hash.chars().all(|c| c.is_ascii_alphanumeric())
+    && (hash
+      .strip_prefix("dxh")
+      .is_some_and(|rest| !rest.is_empty())
+      || (hashes == StableNameHashes::Any
+        && hash.len() >= 8
+        && hash.chars().any(|c| c.is_ascii_digit())));
+  (!stem.is_empty() && is_hash).then(|| format!("{stem}.{extension}"))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::tempdir;
+
+  #[test]
+  fn renames_hashed_outputs_and_rewrites_references() -> Result<()> {
+    let dx = StableNameHashes::Dx;
+    assert_eq!(
+      stable_name("app-dxh4f0c2a.js", dx).as_deref(),
+      Some("app.js")
+    );
+    assert_eq!(stable_name("app_bg-0123abcd.wasm", dx), None);
+    assert_eq!(stable_name("print-20240101.css", dx), None);
+    assert_eq!(
+      stable_name("app_bg-0123abcd.wasm", StableNameHashes::Any).as_deref(),
+      Some("app_bg.wasm")
+    );
+    assert_eq!(
+      stable_name("highlight-theme.css", StableNameHashes::Any),
+      None
+    );
+    assert_eq!(stable_name("cover-dxh4f0c2a.png", dx), None);
+
+    let dir = tempdir()?;
+    let layout = OfflineProjectLayout::default();
+    let assets = dir.path().join("assets");
+    fs::create_dir_all(&assets)?;
+    fs::write(
+      assets.join("app-dxh4f0c2a.js"),
+      "fetch('./assets/app_bg-dxh99e1.wasm')",
+    )?;
+    fs::write(assets.join("app_bg-dxh99e1.wasm"), "wasm")?;
+    fs::write(assets.join("tailwind-dxh77b0.css"), "body{}")?;
+    fs::write(assets.join("cover.png"), "png")?;
+    fs::write(assets.join("print-20240101.css"), "@media print{}")?;
+    let page =
+      r#"<link href="assets/tailwind-dxh77b0.css"><script src="assets/app-dxh4f0c2a.js"></script>"#;
+    fs::write(dir.path().join("index.html"), page)?;
+    fs::write(dir.path().join("kiosk.html"), page)?;
+
+    let renames = stabilize_asset_names(&layout, dir.path(), dx)?;
+    assert_eq!(renames.len(), 3);
+    assert_eq!(renames["app-dxh4f0c2a.js"], "app.js");
+    assert_eq!(
+      fs::read_to_string(assets.join("app.js"))?,
+      "fetch('./assets/app_bg.wasm')"
+    );
+    for page in ["index.html", "kiosk.html"] {
+      assert_eq!(
+        fs::read_to_string(dir.path().join(page))?,
+        r#"<link href="assets/tailwind.css"><script src="assets/app.js"></script>"#
+      );
+    }
+    assert!(assets.join("app_bg.wasm").is_file() && assets.join("cover.png").is_file());
+    assert!(assets.join("print-20240101.css").is_file());
+
+    fs::write(assets.join("app-dxh0000.js"), "")?;
+    fs::write(assets.join("app-dxh1111.js"), "")?;
+    assert!(stabilize_asset_names(&layout, dir.path(), dx).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn failed_renames_restore_earlier_renames() -> Result<()> {
+    let dir = tempdir()?;
+    let layout = OfflineProjectLayout::default();
+    let assets = dir.path().join("assets");
+    fs::create_dir_all(assets.join("app.js"))?;
+    fs::write(assets.join("app.js/keep"), "")?;
+    fs::write(assets.join("a-dxh1.css"), "a")?;
+    fs::write(assets.join("app-dxh4f0c2a.js"), "app")?;
+    let page = r#"<link href="assets/a-dxh1.css"><script src="assets/app-dxh4f0c2a.js"></script>"#;
+    fs::write(dir.path().join("index.html"), page)?;
+
+    // `a-dxh1.css` is renamed first; the directory in the way then fails `app.js`.
+    assert!(stabilize_asset_names(&layout, dir.path(), StableNameHashes::Dx).is_err());
+    assert!(assets.join("a-dxh1.css").is_file() && !assets.join("a.css").exists());
+    assert!(assets.join("app-dxh4f0c2a.js").is_file());
+    assert_eq!(fs::read_to_string(dir.path().join("index.html"))?, page);
+    Ok(())
+  }
+}
@@ -0,0 +1,106 @@
+//! Patch a copy of the `dx build` output instead of the output itself.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow};
+
+use crate::bundle::js_patch::patch_site_module;
+use crate::bundle::site::{PatchedSiteIndex, SitePatchOptions, patch_site_index_with};
+use crate::bundle::split::copy_tree;
+use crate::project::OfflineProjectLayout;
+
+/// Copy the pristine site at `source` into `staging`, replacing any previous copy.
+///
+/// Returns the number of files copied. The two directories must not contain each other.
+pub fn stage_site(source: &Path, staging: &Path) -> Result<usize> {
+  if staging.starts_with(source) || source.starts_with(staging) {
+    return Err(anyhow!(
+      "staging directory {} must be outside of the site {}",
+      staging.display(),
+      source.display()
+    ));
+  }
+
+  if staging.exists() {
+    fs::remove_dir_all(staging)
+      .with_context(|| format!("failed to clear {}", staging.display()))?;
+  }
+  copy_tree(source, staging, &|_| false)
+}
+
+/// Stage the site at `source` into `staging` and patch only the staged copy.
+///
+/// The `dx build` output stays untouched, so the patch stage can be re-run and compared against
+/// the original output without rebuilding.
+pub fn patch_staged_site<F>(
+  layout: &OfflineProjectLayout,
+  source: &Path,
+  staging: &Path,
+  options: &SitePatchOptions,
+  resolve_binary_name: F,
+) -> Result<PatchedSiteIndex>
+where
+  F: FnOnce() -> Result<String>,
+{
+  stage_site(source, staging)?;
+  let patched = patch_site_index_with(layout, staging, options)?;
+  patch_site_module(layout, staging, &patched, options, resolve_binary_name)?;
+  Ok(patched)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::tempdir;
+
+  fn layout() -> OfflineProjectLayout {
+    OfflineProjectLayout {
+      excluded_dir_name: "prod".into(),
+      excluded_path_fragment: "/prod/".into(),
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn patches_copy_and_keeps_original() {
+    let dir = tempdir().unwrap();
+    let source = dir.path().join("dx");
+    fs::create_dir_all(source.join("assets")).unwrap();
+    let index = r#"<html><head></head><body><script type="module" src="/./assets/app.js"></script></body></html>"#;
+    let script = "let wasm;\nnew URL(\"app_bg.wasm\",importMeta.url);\n";
+    fs::write(source.join("index.html"), index).unwrap();
+    fs::write(source.join("assets/app.js"), script).unwrap();
+    fs::write(source.join("assets/app_bg.wasm"), [0u8]).unwrap();
+
+    let staging = dir.path().join("staging");
+    fs::create_dir_all(&staging).unwrap();
+    fs::write(staging.join("stale.txt"), "old").unwrap();
+
+    let patched = patch_staged_site(
+      &layout(),
+      &source,
+      &staging,
+      &SitePatchOptions::default(),
+      || Ok("app".into()),
+    )
+    .unwrap();
+
+    assert_eq!(patched.js_name, "app.js");
+    assert_eq!(
+      fs::read_to_string(source.join("index.html")).unwrap(),
+      index
+    );
+    assert_eq!(
+      fs::read_to_string(source.join("assets/app.js")).unwrap(),
+      script
+    );
+    assert!(
+      fs::read_to_string(staging.join("assets/app.js"))
+        .unwrap()
+        .contains("__offlineWasmBytes")
+    );
+    assert!(!staging.join("stale.txt").exists());
+    assert!(stage_site(&source, &source.join("copy")).is_err());
+  }
+}
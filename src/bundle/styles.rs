@@ -7,27 +7,58 @@ use anyhow::{Context, Result, anyhow};
 
 use crate::project::OfflineProjectLayout;
 
+/// Stylesheet aliases used when none are configured: `tailwind-<hash>.css` to `tailwind.css`.
+pub const DEFAULT_STYLESHEET_ALIASES: [(&str, &str); 1] = [("tailwind", "tailwind.css")];
+
 /// Ensure deterministic stylesheet names are available for the offline launcher.
 pub fn ensure_stylesheet_aliases(layout: &OfflineProjectLayout, site_root: &Path) -> Result<()> {
-  ensure_tailwind_alias(layout, site_root)?;
+  let aliases: Vec<(String, String)> = DEFAULT_STYLESHEET_ALIASES
+    .iter()
+    .map(|(stem, target)| (stem.to_string(), target.to_string()))
+    .collect();
+  ensure_stylesheet_aliases_with(layout, site_root, &aliases)
+}
+
+/// Copy the newest `<stem>-<hash>.css` in the assets directory to `<target_name>` in the site
+/// root for every `(stem, target_name)` pair, e.g. from `ProjectConfig::stylesheet_aliases`.
+///
+/// Existing targets are left untouched. The `tailwind` stem falls back to the compiled debug
+/// stylesheet when the release output still contains uncompiled Tailwind directives.
+pub fn ensure_stylesheet_aliases_with(
+  layout: &OfflineProjectLayout,
+  site_root: &Path,
+  aliases: &[(String, String)],
+) -> Result<()> {
+  for (stem, target_name) in aliases {
+    ensure_alias(layout, site_root, stem, target_name)?;
+  }
   Ok(())
 }
 
-fn ensure_tailwind_alias(layout: &OfflineProjectLayout, site_root: &Path) -> Result<()> {
-  let target = site_root.join("tailwind.css");
+fn ensure_alias(
+  layout: &OfflineProjectLayout,
+  site_root: &Path,
+  stem: &str,
+  target_name: &str,
+) -> Result<()> {
+  let target = site_root.join(target_name);
   if target.exists() {
     return Ok(());
   }
 
   let assets_dir = site_root.join(layout.entry_assets_dir());
-  let Some(source) = find_hashed_stylesheet(&assets_dir, "tailwind")? else {
+  let Some(source) = find_hashed_stylesheet(&assets_dir, stem)? else {
     return Err(anyhow!(
-      "failed to locate hashed tailwind stylesheet in {}",
+      "failed to locate hashed {stem} stylesheet in {}",
       assets_dir.display()
     ));
   };
 
-  let effective_source = resolve_tailwind_source(layout, &source)?;
+  let effective_source = if stem == "tailwind" {
+    resolve_tailwind_source(layout, &source)?
+  } else {
+    source
+  };
 
   fs::copy(&effective_source, &target).with_context(|| {
     format!(
@@ -154,4 +185,28 @@ mod tests {
     let compiled = is_compiled_tailwind(&file).unwrap();
     assert!(!compiled);
   }
+
+  #[test]
+  fn creates_configured_aliases() {
+    let dir = tempdir().unwrap();
+    let site_root = dir.path();
+    fs::create_dir_all(site_root.join("assets")).unwrap();
+    fs::write(site_root.join("assets/main-abc.css"), "body{}").unwrap();
+    fs::write(site_root.join("assets/print-def.css"), "@media print{}").unwrap();
+    let layout = crate::config::ProjectConfig::default().into_layout();
+
+    let aliases = vec![
+      ("main".to_string(), "main.css".to_string()),
+      ("print".to_string(), "print.css".to_string()),
+    ];
+    ensure_stylesheet_aliases_with(&layout, site_root, &aliases).unwrap();
+    assert_eq!(
+      fs::read_to_string(site_root.join("main.css")).unwrap(),
+      "body{}"
+    );
+    assert!(site_root.join("print.css").is_file());
+
+    let missing = vec![("theme".to_string(), "theme.css".to_string())];
+    assert!(ensure_stylesheet_aliases_with(&layout, site_root, &missing).is_err());
+  }
 }
@@ -0,0 +1,197 @@
+//! Bundle versions for launchers that offer to update an installed copy.
+//!
+//! [`stamp_update_file`] writes the configured bundle id and version together with the content
+//! hash from `version.json` to the bundle's `update.json`. Launcher shells read the file from
+//! the installed copy and from a newly inserted stick and call [`compare`] to tell users whether
+//! the stick is newer than what they have.
+
+use std::cmp::Ordering;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::bundle::provenance::read_version_file;
+use crate::config::ProjectConfig;
+use crate::models::{UPDATE_FILE, UpdateMetadata, VERSION_FILE};
+use crate::selection::parse_lenient_version;
+
+/// How a candidate bundle relates to the installed one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UpdateComparison {
+  /// The candidate has a higher version.
+  Newer,
+  /// Same version and content.
+  Same,
+  /// Same version but different content, e.g. a stick rebuilt without bumping the version.
+  Rebuilt,
+  /// The candidate has a lower version.
+  Older,
+  /// The candidate is a different bundle altogether.
+  DifferentBundle,
+  /// One of the versions is not valid semver.
+  Unknown,
+}
+
+impl UpdateComparison {
+  /// Kebab-case name for launcher scripts, e.g. `different-bundle`.
+  pub fn as_str(self) -> &'static str {
+    match self {
+      Self::Newer => "newer",
+      Self::Same => "same",
+      Self::Rebuilt => "rebuilt",
+      Self::Older => "older",
+      Self::DifferentBundle => "different-bundle",
+      Self::Unknown => "unknown",
+    }
+  }
+
+  /// Whether the candidate should be offered as an update.
+  pub fn is_update(self) -> bool {
+    matches!(self, Self::Newer | Self::Rebuilt)
+  }
+}
+
+/// Compare the `candidate` bundle against the `installed` one.
+///
+/// Versions are parsed like collection `minVersion` checks, so `v2.1` equals `2.1.0`.
+pub fn compare(installed: &UpdateMetadata, candidate: &UpdateMetadata) -> UpdateComparison {
+  if installed.bundle_id != candidate.bundle_id {
+    return UpdateComparison::DifferentBundle;
+  }
+  let (Some(installed_version), Some(candidate_version)) = (
+    parse_lenient_version(&installed.version),
+    parse_lenient_version(&candidate.version),
+  ) else {
+    return UpdateComparison::Unknown;
+  };
+  match candidate_version.cmp(&installed_version) {
+    Ordering::Greater => UpdateComparison::Newer,
+    Ordering::Less => UpdateComparison::Older,
+    Ordering::Equal if installed.content_hash == candidate.content_hash => UpdateComparison::Same,
+    Ordering::Equal => UpdateComparison::Rebuilt,
+  }
+}
+
+/// Whether an app at `app_version` can open `bundle`.
+///
+/// Bundles without a `min_app_version` open everywhere; unparseable versions never satisfy one.
+pub fn supports_app_version(bundle: &UpdateMetadata, app_version: &str) -> bool {
+  let Some(min_app_version) = &bundle.min_app_version else {
+    return true;
+  };
+  match (
+    parse_lenient_version(app_version),
+    parse_lenient_version(min_app_version),
+  ) {
+    (Some(app), Some(min)) => app >= min,
+    _ => false,
+  }
+}
+
+/// Write `metadata` to [`UPDATE_FILE`] in `bundle_root`.
+pub fn write_update_file(bundle_root: &Path, metadata: &UpdateMetadata) -> Result<PathBuf> {
+  let path = bundle_root.join(UPDATE_FILE);
+  fs::create_dir_all(bundle_root)
+    .with_context(|| format!("failed to create {}", bundle_root.display()))?;
+  fs::write(&path, serde_json::to_string_pretty(metadata)?)
+    .with_context(|| format!("failed to write {}", path.display()))?;
+  Ok(path)
+}
+
+/// Read the [`UPDATE_FILE`] of the bundle in `bundle_root`.
+pub fn read_update_file(bundle_root: &Path) -> Result<UpdateMetadata> {
+  let path = bundle_root.join(UPDATE_FILE);
+  let json =
+    fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))?;
+  UpdateMetadata::from_json(&json).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+/// Write the [`UPDATE_FILE`] for the bundle in `bundle_root` from `config` and the content hash
+/// in its [`VERSION_FILE`].
+///
+/// Returns `None` when no `bundle_version` is configured or the bundle has no `version.json`.
+pub fn stamp_update_file(config: &ProjectConfig, bundle_root: &Path) -> Result<Option<PathBuf>> {
+  if !bundle_root.join(VERSION_FILE).is_file() {
+    return Ok(None);
+  }
+  let provenance = read_version_file(bundle_root)?;
+  match config.update_metadata(provenance.content_hash)? {
+    Some(metadata) => write_update_file(bundle_root, &metadata).map(Some),
+    None => Ok(None),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bundle::provenance::write_version_file;
+  use crate::models::BuildProvenance;
+  use tempfile::tempdir;
+
+  fn metadata(version: &str, content_hash: &str) -> UpdateMetadata {
+    UpdateMetadata {
+      bundle_id: "course".into(),
+      version: version.into(),
+      content_hash: content_hash.into(),
+      min_app_version: Some("1.2".into()),
+    }
+  }
+
+  #[test]
+  fn compares_installed_and_candidate_bundles() -> Result<()> {
+    let installed = metadata("1.4", "aaa");
+    assert_eq!(
+      compare(&installed, &metadata("v1.10.0", "bbb")),
+      UpdateComparison::Newer
+    );
+    assert_eq!(
+      compare(&installed, &metadata("1.4.0", "aaa")),
+      UpdateComparison::Same
+    );
+    assert_eq!(
+      compare(&installed, &metadata("1.4", "bbb")),
+      UpdateComparison::Rebuilt
+    );
+    assert_eq!(
+      compare(&installed, &metadata("1.3.9", "bbb")),
+      UpdateComparison::Older
+    );
+    assert_eq!(
+      compare(&installed, &metadata("latest", "bbb")),
+      UpdateComparison::Unknown
+    );
+    let other = UpdateMetadata {
+      bundle_id: "other".into(),
+      ..metadata("2.0", "bbb")
+    };
+    assert_eq!(
+      compare(&installed, &other),
+      UpdateComparison::DifferentBundle
+    );
+    assert!(supports_app_version(&installed, "1.2.0"));
+    assert!(!supports_app_version(&installed, "1.1"));
+
+    let dir = tempdir()?;
+    let config = ProjectConfig::builder()
+      .package_name("course")
+      .bundle_version("1.4")
+      .min_app_version("1.2")
+      .build()?;
+    assert_eq!(stamp_update_file(&config, dir.path())?, None);
+    write_version_file(dir.path(), &BuildProvenance {
+      tool_version: "0.0.0".into(),
+      git_commit: None,
+      content_hash: "aaa".into(),
+      built_at: 0,
+    })?;
+    assert_eq!(
+      stamp_update_file(&config, dir.path())?,
+      Some(dir.path().join(UPDATE_FILE))
+    );
+    assert_eq!(read_update_file(dir.path())?, installed);
+    Ok(())
+  }
+}
@@ -0,0 +1,289 @@
+//! Integrity check for a finished offline bundle.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use regex::Regex;
+
+use crate::bundle::manifest::load_manifest;
+use crate::bundle::split::site_location;
+use crate::manifest::inventory::file_digest;
+use crate::project::OfflineProjectLayout;
+
+/// Problem detected by [`verify`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerificationIssue {
+  /// An asset listed in the manifest does not exist.
+  MissingAsset {
+    /// Site-relative asset path.
+    path: String,
+  },
+  /// An asset exists but is empty, which usually means the copy was truncated.
+  EmptyAsset {
+    /// Site-relative asset path.
+    path: String,
+  },
+  /// An asset's SHA-256 digest differs from the manifest.
+  ChecksumMismatch {
+    /// Site-relative asset path.
+    path: String,
+    /// Digest recorded in the manifest.
+    expected: String,
+    /// Digest of the file on disk.
+    actual: String,
+  },
+  /// The site index was not rewritten by `patch_site_index`.
+  IndexNotPatched {
+    /// What is wrong with the index.
+    reason: String,
+  },
+  /// None of the deferred scripts was rewritten by `patch_js_module`.
+  ScriptNotPatched {
+    /// Site-relative script path.
+    path: String,
+  },
+  /// No WebAssembly module was found in the site's assets directory.
+  MissingWasm,
+}
+
+/// Outcome of [`verify`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerificationReport {
+  /// Number of distinct assets checked.
+  pub checked_assets: usize,
+  /// Number of assets whose checksum was compared.
+  pub checksummed_assets: usize,
+  /// Every problem found, assets first.
+  pub issues: Vec<VerificationIssue>,
+}
+
+impl VerificationReport {
+  /// Returns true when no problems were found.
+  pub fn is_ok(&self) -> bool {
+    self.issues.is_empty()
+  }
+}
+
+impl fmt::Display for VerificationIssue {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::MissingAsset { path } => write!(f, "missing asset {path}"),
+      Self::EmptyAsset { path } => write!(f, "empty asset {path}"),
+      Self::ChecksumMismatch {
+        path,
+        expected,
+        actual,
+      } => write!(
+        f,
+        "checksum mismatch for {path}: expected {expected}, found {actual}"
+      ),
+      Self::IndexNotPatched { reason } => write!(f, "index not patched: {reason}"),
+      Self::ScriptNotPatched { path } => write!(f, "script {path} not patched"),
+      Self::MissingWasm => write!(f, "no WebAssembly module found"),
+    }
+  }
+}
+
+/// Verify the bundle at `bundle_root` against its offline manifest.
+///
+/// Checks that every asset listed in the manifest exists and is non-empty, compares SHA-256
+/// digests when the manifest records them, and confirms that the index, JavaScript bootstrap
+/// and WebAssembly module were patched for offline use. Only failing to read the manifest is
+/// reported as an error; everything else ends up in the report.
+pub fn verify(layout: &OfflineProjectLayout, bundle_root: &Path) -> Result<VerificationReport> {
+  let manifest = load_manifest(&bundle_root.join(&layout.offline_manifest_json))?;
+  let (site_relative, _) = site_location(layout, &manifest);
+  let site_root = bundle_root.join(site_relative);
+
+  let mut report = VerificationReport::default();
+  let mut paths: Vec<&String> = manifest
+    .entries
+    .iter()
+    .flat_map(|entry| entry.asset_paths.iter())
+    .chain(&manifest.hero_assets)
+    .chain(manifest.asset_hashes.keys())
+    .collect();
+  paths.sort();
+  paths.dedup();
+
+  for path in paths {
+    report.checked_assets += 1;
+    let file = site_root.join(path);
+    let Ok(metadata) = fs::metadata(&file) else {
+      report
+        .issues
+        .push(VerificationIssue::MissingAsset { path: path.clone() });
+      continue;
+    };
+    if metadata.len() == 0 {
+      report
+        .issues
+        .push(VerificationIssue::EmptyAsset { path: path.clone() });
+      continue;
+    }
+    if let Some(expected) = manifest.asset_hashes.get(path) {
+      report.checksummed_assets += 1;
+      let (_, actual) =
+        file_digest(&file).with_context(|| format!("failed to hash {}", file.display()))?;
+      if &actual != expected {
+        report.issues.push(VerificationIssue::ChecksumMismatch {
+          path: path.clone(),
+          expected: expected.clone(),
+          actual,
+        });
+      }
+    }
+  }
+
+  verify_patched_site(layout, &site_root, &mut report);
+  Ok(report)
+}
+
+fn verify_patched_site(
+  layout: &OfflineProjectLayout,
+  site_root: &Path,
+  report: &mut VerificationReport,
+) {
+  let index_path = site_root.join(&layout.index_html_file);
+  let Ok(index) = fs::read_to_string(&index_path) else {
+    report.issues.push(VerificationIssue::IndexNotPatched {
+      reason: format!("{} is missing", layout.index_html_file),
+    });
+    return;
+  };
+
+  if !index.contains("window.__dx_mainInit") {
+    report.issues.push(VerificationIssue::IndexNotPatched {
+      reason: "offline loader missing".into(),
+    });
+  }
+
+  let assets_prefix = format!("{}/", layout.entry_assets_dir());
+  let deferred_script = Regex::new(&format!(
+    r#"<script defer src="{}([^"]+\.js)"></script>"#,
+    regex::escape(&assets_prefix)
+  ))
+  .expect("invalid script regex");
+  // Demoted or preserved extra module scripts are left alone, so only require one deferred
+  // script to be the patched bootstrap.
+  let scripts: Vec<String> = deferred_script
+    .captures_iter(&index)
+    .map(|caps| format!("{}{}", assets_prefix, &caps[1]))
+    .collect();
+  if scripts.is_empty() {
+    report.issues.push(VerificationIssue::IndexNotPatched {
+      reason: "deferred bootstrap script missing".into(),
+    });
+  }
+  let any_patched = scripts.iter().any(|path| {
+    fs::read_to_string(site_root.join(path))
+      .is_ok_and(|script| script.contains("__offlineWasmBytes") && script.contains("__dx_mainInit"))
+  });
+  if !any_patched {
+    report.issues.extend(
+      scripts
+        .into_iter()
+        .map(|path| VerificationIssue::ScriptNotPatched { path }),
+    );
+  }
+
+  let has_wasm = fs::read_dir(site_root.join(layout.entry_assets_dir()))
+    .map(|entries| {
+      entries
+        .flatten()
+        .any(|entry| entry.file_name().to_string_lossy().ends_with(".wasm"))
+    })
+    .unwrap_or(false);
+  if !has_wasm {
+    report.issues.push(VerificationIssue::MissingWasm);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::tempdir;
+
+  fn layout() -> OfflineProjectLayout {
+    OfflineProjectLayout {
+      excluded_dir_name: "prod".into(),
+      excluded_path_fragment: "/prod/".into(),
+      ..Default::default()
+    }
+  }
+
+  fn write_file(path: &Path, contents: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, contents).unwrap();
+  }
+
+  fn write_patched_site(root: &Path) {
+    write_file(
+      &root.join("site/index.html"),
+      "<script defer src=\"assets/app.js\"></script><script>window.__dx_mainInit</script>",
+    );
+    write_file(
+      &root.join("site/assets/app.js"),
+      "const __offlineWasmBytes=1;window.__dx_mainInit=init;",
+    );
+    write_file(&root.join("site/assets/app_bg.wasm"), "wasm");
+  }
+
+  #[test]
+  fn accepts_complete_bundle() {
+    let dir = tempdir().unwrap();
+    let root = dir.path();
+    write_patched_site(root);
+    write_file(&root.join("site/programs/P001/a.png"), "abc");
+    write_file(
+      &root.join("offline_manifest.json"),
+      r#"{"site_root": "site", "entries": [
+        {"collection_id": "P001", "entry_id": "intro", "asset_paths": ["programs/P001/a.png"]}
+      ], "asset_hashes": {
+        "programs/P001/a.png": "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+      }}"#,
+    );
+
+    let report = verify(&layout(), root).unwrap();
+    assert!(report.is_ok(), "{:?}", report.issues);
+    assert_eq!(report.checked_assets, 1);
+    assert_eq!(report.checksummed_assets, 1);
+  }
+
+  #[test]
+  fn reports_missing_corrupt_and_unpatched_files() {
+    let dir = tempdir().unwrap();
+    let root = dir.path();
+    write_file(
+      &root.join("site/index.html"),
+      "<script type=\"module\" src=\"/./assets/app.js\"></script>",
+    );
+    write_file(&root.join("site/programs/P001/a.png"), "tampered");
+    write_file(&root.join("site/programs/P001/empty.png"), "");
+    write_file(
+      &root.join("offline_manifest.json"),
+      r#"{"site_root": "site", "hero_assets": ["programs/P001/empty.png"], "entries": [
+        {"collection_id": "P001", "entry_id": "intro",
+         "asset_paths": ["programs/P001/a.png", "programs/P001/gone.png"]}
+      ], "asset_hashes": {"programs/P001/a.png": "00"}}"#,
+    );
+
+    let report = verify(&layout(), root).unwrap();
+    let rendered: Vec<String> = report.issues.iter().map(ToString::to_string).collect();
+    assert!(matches!(
+      report.issues[0],
+      VerificationIssue::ChecksumMismatch { .. }
+    ));
+    assert!(rendered.contains(&"empty asset programs/P001/empty.png".to_string()));
+    assert!(rendered.contains(&"missing asset programs/P001/gone.png".to_string()));
+    assert!(report.issues.contains(&VerificationIssue::MissingWasm));
+    assert!(
+      report
+        .issues
+        .iter()
+        .any(|issue| matches!(issue, VerificationIssue::IndexNotPatched { .. }))
+    );
+  }
+}
@@ -0,0 +1,626 @@
+//! Data and options used when rendering the generated Rust modules.
+//!
+//! [`OfflineBuilder`](crate::OfflineBuilder) renders `asset_table_code` and
+//! `offline_manifest_code` with built-in `format!` templates, shaped by [`CodegenOptions`]. With the `templates` feature,
+//! callers can replace either module with a [minijinja](https://docs.rs/minijinja) template
+//! that receives a [`CodegenContext`].
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::asset_paths::make_offline_asset_path;
+use crate::config::ConfigValidationError;
+use crate::models::{AssetEntry, CollectionCatalogRecord, EntryDocumentRecord, OfflineEntryRecord};
+use crate::project::OfflineProjectLayout;
+
+/// Directory of the asset mirror holding the entry body shards of [`CodegenOptions::data_shards`].
+pub const DATA_SHARD_DIR: &str = "_offline_data";
+
+/// How the built-in asset table turns mirrored files into values.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum AssetCodegenStrategy {
+  /// `dioxus::prelude::asset!`, producing `dioxus::prelude::Asset`.
+  #[default]
+  Dioxus,
+  /// `manganis::asset!`, for apps depending on manganis directly.
+  Manganis,
+  /// Plain `&'static str` paths of the mirrored files, for consumers without an asset macro.
+  Paths,
+  /// A custom macro invoked with the path literal.
+  Macro {
+    /// Path of the macro without the `!`, e.g. `my_assets::asset`.
+    name: String,
+    /// Type of the values the macro produces, e.g. `my_assets::Asset`.
+    asset_type: String,
+  },
+}
+
+impl AssetCodegenStrategy {
+  /// Type of the generated asset statics.
+  pub fn asset_type(&self) -> &str {
+    match self {
+      Self::Dioxus => "dioxus::prelude::Asset",
+      Self::Manganis => "manganis::Asset",
+      Self::Paths => "&'static str",
+      Self::Macro { asset_type, .. } => asset_type,
+    }
+  }
+
+  /// Expression producing the asset for the Rust string literal `path_literal`.
+  pub fn expression(&self, path_literal: &str) -> String {
+    match self {
+      Self::Dioxus => format!("dioxus::prelude::asset!({path_literal})"),
+      Self::Manganis => format!("manganis::asset!({path_literal})"),
+      Self::Paths => path_literal.to_string(),
+      Self::Macro { name, .. } => format!("{name}!({path_literal})"),
+    }
+  }
+
+  /// Item making [`AssetCodegenStrategy::asset_type`] available as `Asset`.
+  pub(crate) fn import(&self) -> String {
+    match self {
+      Self::Dioxus | Self::Manganis => format!("use {};", self.asset_type()),
+      Self::Paths | Self::Macro { .. } => format!("type Asset = {};", self.asset_type()),
+    }
+  }
+
+  fn validate(&self) -> Result<(), ConfigValidationError> {
+    let Self::Macro { name, asset_type } = self else {
+      return Ok(());
+    };
+    if !name.trim_start_matches("::").split("::").all(is_identifier) {
+      return Err(ConfigValidationError::Invalid {
+        field: "asset_strategy",
+        value: name.clone(),
+        reason: "expected a macro path such as `my_assets::asset`",
+      });
+    }
+    if asset_type.trim().is_empty() {
+      return Err(ConfigValidationError::Invalid {
+        field: "asset_strategy",
+        value: asset_type.clone(),
+        reason: "expected the type produced by the macro",
+      });
+    }
+    Ok(())
+  }
+}
+
+/// Options shaping the built-in generated Rust modules.
+///
+/// The defaults reproduce the historical output: unprefixed function names, a mix of `pub`,
+/// `pub(crate)` and private functions, and no wrapping module.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CodegenOptions {
+  /// Visibility applied to every generated function, e.g. `pub` or `pub(crate)`.
+  ///
+  /// `None` keeps the built-in visibility of each function; an empty string makes them private.
+  pub visibility: Option<String>,
+  /// Prefix prepended to every generated function name, e.g. `docs_` for `docs_offline_entry`.
+  pub function_prefix: String,
+  /// Wrap `asset_table_code` in `mod <name> { ... }`.
+  pub asset_table_module: Option<String>,
+  /// Wrap `offline_manifest_code` in `mod <name> { ... }`.
+  pub offline_manifest_module: Option<String>,
+  /// Only compile `asset_table_code` with this cargo feature of the consuming crate enabled.
+  ///
+  /// A fallback with the same functions, all returning `None`, is emitted for builds without
+  /// the feature.
+  pub asset_table_feature: Option<String>,
+  /// Only compile `offline_manifest_code` with this cargo feature, e.g. `offline-html`.
+  ///
+  /// Like [`CodegenOptions::asset_table_feature`], a fallback returning `None` is emitted for
+  /// builds without the feature.
+  pub offline_manifest_feature: Option<String>,
+  /// Load entry bodies at runtime from per-collection JSON shards instead of compiling them in.
+  ///
+  /// The shards are written to [`DATA_SHARD_DIR`] in the asset mirror, together with a script
+  /// variant loaded on `file://` pages where `fetch` is unavailable. `offline_entry_body`
+  /// becomes an `async fn` returning `Option<String>` and `OfflineEntry` loses its `body`.
+  pub data_shards: bool,
+  /// How asset statics are generated, `dioxus::prelude::asset!` by default.
+  pub asset_strategy: AssetCodegenStrategy,
+}
+
+impl CodegenOptions {
+  /// Check that the prefix and module names are valid Rust identifiers.
+  pub fn validate(&self) -> Result<(), ConfigValidationError> {
+    self.asset_strategy.validate()?;
+    if !self.function_prefix.is_empty() && !is_identifier(&format!("{}f", self.function_prefix)) {
+      return Err(ConfigValidationError::Invalid {
+        field: "function_prefix",
+        value: self.function_prefix.clone(),
+        reason: "expected the start of a Rust identifier",
+      });
+    }
+    for (field, module) in [
+      ("asset_table_module", &self.asset_table_module),
+      ("offline_manifest_module", &self.offline_manifest_module),
+    ] {
+      if let Some(module) = module
+        && !is_identifier(module)
+      {
+        return Err(ConfigValidationError::Invalid {
+          field,
+          value: module.clone(),
+          reason: "expected a Rust identifier",
+        });
+      }
+    }
+    for (field, feature) in [
+      ("asset_table_feature", &self.asset_table_feature),
+      ("offline_manifest_feature", &self.offline_manifest_feature),
+    ] {
+      if let Some(feature) = feature
+        && (feature.is_empty()
+          || !feature
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+' | '.')))
+      {
+        return Err(ConfigValidationError::Invalid {
+          field,
+          value: feature.clone(),
+          reason: "expected a cargo feature name",
+        });
+      }
+    }
+    Ok(())
+  }
+
+  /// Name of a generated function after applying [`CodegenOptions::function_prefix`].
+  pub fn function_name(&self, name: &str) -> String {
+    format!("{}{name}", self.function_prefix)
+  }
+
+  /// Visibility prefix, including a trailing space, for an item whose built-in visibility is
+  /// `default`.
+  pub(crate) fn item_visibility(&self, default: &str) -> String {
+    let visibility = self.visibility.as_deref().unwrap_or(default).trim();
+    if visibility.is_empty() {
+      String::new()
+    } else {
+      format!("{visibility} ")
+    }
+  }
+
+  /// Stream the code written by `code` into `writer`, wrapped in `mod <module> { ... }` when
+  /// `module` is set.
+  ///
+  /// With `feature`, every top-level item is gated behind it and `fallback`, wrapped in the same
+  /// module, is appended for builds without the feature.
+  pub(crate) fn write_module<F>(
+    &self,
+    writer: &mut dyn Write,
+    module: Option<&str>,
+    feature: Option<&str>,
+    fallback: &str,
+    code: F,
+  ) -> io::Result<()>
+  where
+    F: FnOnce(&mut dyn Write) -> io::Result<()>,
+  {
+    let enabled = feature.map(|feature| format!("feature = \"{feature}\""));
+    let mut out = ModuleWriter::new(writer, self, module, enabled)?;
+    code(&mut out)?;
+    out.finish()?;
+    if let Some(feature) = feature {
+      write!(
+        writer,
+        "\n// Fallback for builds without the `{feature}` feature\n"
+      )?;
+      let disabled = format!("not(feature = \"{feature}\")");
+      let mut out = ModuleWriter::new(writer, self, module, Some(disabled))?;
+      out.write_all(fallback.as_bytes())?;
+      out.finish()?;
+    }
+    Ok(())
+  }
+}
+
+/// Line-oriented writer that indents code into a module and inserts `#[cfg(predicate)]` before
+/// every top-level item (lines starting in the first column), so generated modules can be
+/// streamed rather than post-processed as strings.
+struct ModuleWriter<'a> {
+  inner: &'a mut dyn Write,
+  indent: bool,
+  predicate: Option<String>,
+  in_attributes: bool,
+  line: Vec<u8>,
+}
+
+impl<'a> ModuleWriter<'a> {
+  fn new(
+    inner: &'a mut dyn Write,
+    options: &CodegenOptions,
+    module: Option<&str>,
+    predicate: Option<String>,
+  ) -> io::Result<Self> {
+    let mut writer = Self {
+      inner,
+      indent: false,
+      predicate,
+      in_attributes: false,
+      line: Vec::new(),
+    };
+    if let Some(module) = module {
+      let header = format!("{}mod {module} {{", options.item_visibility("pub"));
+      writer.emit(header.as_bytes())?;
+      writer.indent = true;
+    }
+    Ok(writer)
+  }
+
+  fn push_line(&mut self, line: &[u8]) -> io::Result<()> {
+    if self.indent && !line.is_empty() {
+      self.emit(&[b"    ", line].concat())
+    } else {
+      self.emit(line)
+    }
+  }
+
+  fn emit(&mut self, line: &[u8]) -> io::Result<()> {
+    if let Some(predicate) = &self.predicate {
+      let top_level = line
+        .first()
+        .is_some_and(|c| !c.is_ascii_whitespace() && *c != b'}')
+        && !line.starts_with(b"//");
+      if top_level && !self.in_attributes {
+        writeln!(self.inner, "#[cfg({predicate})]")?;
+      }
+      if top_level {
+        self.in_attributes = line.starts_with(b"#[");
+      }
+    }
+    self.inner.write_all(line)?;
+    self.inner.write_all(b"\n")
+  }
+
+  /// Write any unterminated last line and close the module.
+  fn finish(mut self) -> io::Result<()> {
+    if !self.line.is_empty() {
+      let line = std::mem::take(&mut self.line);
+      self.push_line(&line)?;
+    }
+    if self.indent {
+      self.indent = false;
+      self.emit(b"}")?;
+    }
+    Ok(())
+  }
+}
+
+impl Write for ModuleWriter<'_> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    if !self.indent && self.predicate.is_none() {
+      return self.inner.write(buf);
+    }
+    for chunk in buf.split_inclusive(|byte| *byte == b'\n') {
+      match chunk.strip_suffix(b"\n") {
+        Some(rest) => {
+          self.line.extend_from_slice(rest);
+          let line = std::mem::take(&mut self.line);
+          self.push_line(&line)?;
+        }
+        None => self.line.extend_from_slice(chunk),
+      }
+    }
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.inner.flush()
+  }
+}
+
+fn is_identifier(name: &str) -> bool {
+  let mut chars = name.chars();
+  chars
+    .next()
+    .is_some_and(|first| first == '_' || first.is_ascii_alphabetic())
+    && chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
+    && name != "_"
+}
+
+/// Structured manifest data passed to code generation templates.
+#[derive(Debug, Clone, Serialize)]
+pub struct CodegenContext {
+  /// Every bundled collection asset, ordered by collection and relative path.
+  pub assets: Vec<CodegenAsset>,
+  /// Collection hero images, pointing at constants in [`CodegenContext::assets`].
+  pub heroes: Vec<CodegenHero>,
+  /// Rendered entries in manifest order.
+  pub entries: Vec<CodegenEntry>,
+  /// Collection catalog records, as written to the catalog JSON.
+  pub collections: Vec<CollectionCatalogRecord>,
+}
+
+/// Collection asset as seen by code generation templates.
+#[derive(Debug, Clone, Serialize)]
+pub struct CodegenAsset {
+  /// Collection identifier associated with the asset.
+  pub collection_id: String,
+  /// Relative path of the asset within the collection directory.
+  pub relative_path: String,
+  /// Constant name generated for the asset.
+  pub const_name: String,
+  /// Path of the mirrored file handed to the asset macro; duplicates share one mirror path.
+  pub mirror_path: String,
+  /// Bundle-relative path of the asset in the offline site.
+  pub offline_path: String,
+}
+
+/// Hero image lookup for a collection.
+#[derive(Debug, Clone, Serialize)]
+pub struct CodegenHero {
+  /// Collection identifier.
+  pub collection_id: String,
+  /// Constant name of the hero asset.
+  pub const_name: String,
+}
+
+/// Offline entry as seen by code generation templates.
+#[derive(Debug, Clone, Serialize)]
+pub struct CodegenEntry {
+  /// Collection identifier the entry belongs to.
+  pub collection_id: String,
+  /// Entry identifier.
+  pub entry_id: String,
+  /// Rendered HTML body for the entry.
+  pub body: String,
+  /// Bundle-relative asset paths referenced by the entry.
+  pub asset_paths: Vec<String>,
+  /// Number of words in the entry's text.
+  pub word_count: usize,
+  /// Estimated reading time in whole minutes.
+  pub reading_minutes: usize,
+  /// Supplementary markdown documents of the entry, sorted by name.
+  pub documents: Vec<EntryDocumentRecord>,
+}
+
+impl CodegenContext {
+  #[cfg_attr(not(feature = "templates"), allow(dead_code))]
+  pub(crate) fn new(
+    layout: &OfflineProjectLayout,
+    collection_catalog: &[CollectionCatalogRecord],
+    offline_entries: &[OfflineEntryRecord],
+    asset_map: &BTreeMap<(String, String), AssetEntry>,
+    duplicates: &BTreeMap<(String, String), (String, String)>,
+    mirror_prefix: &str,
+  ) -> Self {
+    let assets = asset_map
+      .iter()
+      .map(|(key, entry)| {
+        let mirror_entry = duplicates
+          .get(key)
+          .and_then(|canonical| asset_map.get(canonical))
+          .unwrap_or(entry);
+        CodegenAsset {
+          collection_id: entry.collection_id.clone(),
+          relative_path: entry.relative_path.clone(),
+          const_name: entry.const_name.clone(),
+          mirror_path: format!(
+            "{}/{}/{}",
+            mirror_prefix.trim_end_matches('/'),
+            mirror_entry.collection_id,
+            mirror_entry.output_relative_path()
+          ),
+          offline_path: make_offline_asset_path(
+            layout,
+            &entry.collection_id,
+            entry.output_relative_path(),
+          ),
+        }
+      })
+      .collect();
+
+    let heroes = collection_catalog
+      .iter()
+      .filter_map(|record| {
+        let hero_image = record.meta.hero_image.as_deref()?;
+        let hero_rel = hero_image.trim_start_matches('/').replace('\\', "/");
+        let entry = asset_map.get(&(record.id.clone(), hero_rel))?;
+        Some(CodegenHero {
+          collection_id: record.id.clone(),
+          const_name: entry.const_name.clone(),
+        })
+      })
+      .collect();
+
+    let entries = offline_entries
+      .iter()
+      .map(|entry| CodegenEntry {
+        collection_id: entry.collection_id.clone(),
+        entry_id: entry.entry_id.clone(),
+        body: entry.body.clone(),
+        asset_paths: entry.asset_paths.clone(),
+        word_count: entry.word_count,
+        reading_minutes: entry.reading_minutes,
+        documents: entry.documents.clone(),
+      })
+      .collect();
+
+    Self {
+      assets,
+      heroes,
+      entries,
+      collections: collection_catalog.to_vec(),
+    }
+  }
+}
+
+/// Caller-supplied templates replacing the built-in generated modules.
+///
+/// Templates are rendered with [`CodegenContext`] as their context. The `rust_str` filter turns
+/// any value into a Rust string literal, e.g. `{{ asset.mirror_path | rust_str }}`.
+#[cfg(feature = "templates")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CodegenTemplates {
+  /// Template for [`OfflineArtifacts::asset_table_code`](crate::OfflineArtifacts::asset_table_code).
+  pub asset_table: Option<String>,
+  /// Template for
+  /// [`OfflineArtifacts::offline_manifest_code`](crate::OfflineArtifacts::offline_manifest_code).
+  pub offline_manifest: Option<String>,
+}
+
+#[cfg(feature = "templates")]
+impl CodegenTemplates {
+  /// Render `template` with the provided context; `name` is used in error messages.
+  pub fn render(
+    name: &str,
+    template: &str,
+    context: &CodegenContext,
+  ) -> Result<String, minijinja::Error> {
+    let mut env = minijinja::Environment::new();
+    env.set_keep_trailing_newline(true);
+    env.add_filter("rust_str", |value: minijinja::Value| {
+      let text = value
+        .as_str()
+        .map(str::to_owned)
+        .unwrap_or_else(|| value.to_string());
+      serde_json::to_string(&text).unwrap()
+    });
+    env.add_template(name, template)?;
+    env.get_template(name)?.render(context)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn render(
+    options: &CodegenOptions,
+    module: Option<&str>,
+    feature: Option<&str>,
+    code: &str,
+    fallback: &str,
+  ) -> String {
+    let mut out = Vec::new();
+    options
+      .write_module(&mut out, module, feature, fallback, |writer| {
+        // Split the write to exercise lines spanning several calls.
+        let (head, tail) = code.split_at(code.len() / 2);
+        writer.write_all(head.as_bytes())?;
+        writer.write_all(tail.as_bytes())
+      })
+      .unwrap();
+    String::from_utf8(out).unwrap()
+  }
+
+  #[test]
+  fn options_rename_and_wrap_items() {
+    let options = CodegenOptions {
+      visibility: Some("pub(crate)".into()),
+      function_prefix: "docs_".into(),
+      asset_table_module: Some("docs_assets".into()),
+      ..Default::default()
+    };
+    assert!(options.validate().is_ok());
+    assert_eq!(options.function_name("offline_entry"), "docs_offline_entry");
+    assert_eq!(options.item_visibility("pub"), "pub(crate) ");
+    assert_eq!(
+      render(
+        &options,
+        Some("docs_assets"),
+        None,
+        "use a;\n\nfn b() {}\n",
+        ""
+      ),
+      "pub(crate) mod docs_assets {\n    use a;\n\n    fn b() {}\n}\n"
+    );
+    assert_eq!(render(&options, None, None, "x", ""), "x");
+
+    let invalid = CodegenOptions {
+      offline_manifest_module: Some("offline-manifest".into()),
+      ..Default::default()
+    };
+    assert!(invalid.validate().is_err());
+  }
+
+  #[test]
+  fn asset_strategies_render_statics() {
+    let literal = "\"/assets/P001/a.png\"";
+    let custom = AssetCodegenStrategy::Macro {
+      name: "my_assets::asset".into(),
+      asset_type: "my_assets::Asset".into(),
+    };
+    assert_eq!(
+      AssetCodegenStrategy::Dioxus.expression(literal),
+      "dioxus::prelude::asset!(\"/assets/P001/a.png\")"
+    );
+    assert_eq!(
+      AssetCodegenStrategy::Manganis.import(),
+      "use manganis::Asset;"
+    );
+    assert_eq!(AssetCodegenStrategy::Paths.expression(literal), literal);
+    assert_eq!(
+      AssetCodegenStrategy::Paths.import(),
+      "type Asset = &'static str;"
+    );
+    assert_eq!(
+      custom.expression(literal),
+      "my_assets::asset!(\"/assets/P001/a.png\")"
+    );
+    assert_eq!(custom.import(), "type Asset = my_assets::Asset;");
+
+    let invalid = CodegenOptions {
+      asset_strategy: AssetCodegenStrategy::Macro {
+        name: "my-assets!".into(),
+        asset_type: "Asset".into(),
+      },
+      ..Default::default()
+    };
+    assert!(invalid.validate().is_err());
+  }
+
+  #[test]
+  fn feature_gates_items_and_emits_fallback() {
+    let options = CodegenOptions {
+      offline_manifest_feature: Some("offline-html".into()),
+      ..Default::default()
+    };
+    let code = render(
+      &options,
+      None,
+      options.offline_manifest_feature.as_deref(),
+      "// header\nuse a;\n\n#[allow(dead_code)]\npub fn f() {\n    g();\n}\n",
+      "pub fn f() {}\n",
+    );
+
+    assert_eq!(
+      code,
+      "// header\n#[cfg(feature = \"offline-html\")]\nuse a;\n\n\
+       #[cfg(feature = \"offline-html\")]\n#[allow(dead_code)]\npub fn f() {\n    g();\n}\n\n\
+       // Fallback for builds without the `offline-html` feature\n\
+       #[cfg(not(feature = \"offline-html\"))]\npub fn f() {}\n"
+    );
+  }
+
+  #[cfg(feature = "templates")]
+  #[test]
+  fn renders_templates_with_rust_string_literals() {
+    let context = CodegenContext {
+      assets: vec![CodegenAsset {
+        collection_id: "P001".into(),
+        relative_path: "assets/a \"b\".png".into(),
+        const_name: "P001_A".into(),
+        mirror_path: "/target/offline-assets/P001/assets/a \"b\".png".into(),
+        offline_path: "programs/P001/assets/a \"b\".png".into(),
+      }],
+      heroes: Vec::new(),
+      entries: Vec::new(),
+      collections: Vec::new(),
+    };
+    let template = "{% for asset in assets %}static {{ asset.const_name }}: Asset = \
+                    my_asset!({{ asset.mirror_path | rust_str }});\n{% endfor %}";
+
+    let code = CodegenTemplates::render("asset_table", template, &context).unwrap();
+
+    assert_eq!(
+      code,
+      "static P001_A: Asset = my_asset!(\"/target/offline-assets/P001/assets/a \\\"b\\\".png\");\n"
+    );
+  }
+}
@@ -1,12 +1,34 @@
 //! Project configuration loader for describing offline bundle layout.
 
+use std::collections::BTreeMap;
+use std::fmt;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-use serde::{Deserialize, de::DeserializeOwned};
-use serde_json::Value;
+use serde::de::{DeserializeOwned, Error as _};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
 
-use crate::project::OfflineProjectLayout;
+use crate::asset_paths::AssetReferenceFilter;
+use crate::filesystem::FilesystemProfile;
+use crate::models::UpdateMetadata;
+use crate::project::{
+  DEFAULT_SCAN_MAX_DEPTH, LEGACY_COLLECTION_METADATA_FILE, OfflineProjectLayout,
+  collection_metadata_path, normalize_extensions,
+};
+use crate::selection::parse_lenient_version;
+use crate::theme::{ThemePalette, parse_colors};
+
+/// Prefix for environment variables that override discovered configuration values.
+///
+/// Each field maps to an upper-case variable, e.g. `OFFLINE_BUNDLER_OFFLINE_BUNDLE_ROOT`.
+pub const ENV_PREFIX: &str = "OFFLINE_BUNDLER_";
+
+/// Dedicated configuration file looked up in the workspace root and the crate manifest directory.
+///
+/// The file contains the same camelCase keys as the `config` object of collection metadata.
+pub const CONFIG_FILE: &str = "offline_bundler.json";
 
 /// Discoverable project configuration describing filesystem layout and output paths.
 #[derive(Debug, Clone, Deserialize)]
@@ -36,12 +58,154 @@ pub struct ProjectConfig {
   pub collections_dir_name: String,
   /// Path where the offline HTML bundle should be written.
   pub offline_bundle_root: String,
+  /// Absolute directory used for the bundle instead of `offline_bundle_root`; empty keeps
+  /// `offline_bundle_root`.
+  pub output_root: String,
   /// File name of the application entry point HTML.
   pub index_html_file: String,
   /// Cargo target directory used during builds.
   pub target_dir: String,
   /// Name of the serialized offline manifest JSON file.
   pub offline_manifest_json: String,
+  /// Maximum directory depth below a collection that asset scanning descends into.
+  pub scan_max_depth: usize,
+  /// Comma-separated extensions bundled from assets trees, e.g. `png,jpg,svg,mp4`; empty allows
+  /// every extension.
+  pub asset_extensions: String,
+  /// Comma-separated extensions never bundled from assets trees, e.g. `mov,zip,psd`.
+  pub excluded_extensions: String,
+  /// File system the bundle is copied to (`fat32` or `exfat`); empty disables the checks.
+  pub target_filesystem: String,
+  /// Whether target file system problems `warn` or fail the build (`error`).
+  pub filesystem_check: String,
+  /// Comma-separated `stem=target` pairs giving hashed stylesheets stable names in the site
+  /// root, e.g. `tailwind=tailwind.css,main=main.css`; empty disables the aliases.
+  pub stylesheet_aliases: String,
+  /// Whether hashed JavaScript, stylesheet and WebAssembly outputs are renamed to stable names.
+  pub stable_asset_names: bool,
+  /// Kind of build output patched: `dioxus` for `dx build` sites or `generic` for any
+  /// `wasm-bindgen` web output, e.g. trunk builds of Yew apps.
+  pub patch_mode: String,
+  /// Comma-separated classes never purged from bundled stylesheets; a trailing `*` keeps every
+  /// class with that prefix.
+  pub css_safelist: String,
+  /// Comma-separated URL schemes whose references are never bundled, e.g. `tel,geo,route`, on
+  /// top of the built-in `http(s)`, `data` and `mailto`.
+  pub ignored_asset_schemes: String,
+  /// Comma-separated path globs of references that are never bundled, e.g. `drafts/**,*.psd`.
+  pub ignored_asset_paths: String,
+  /// Comma-separated `name=value` brand colors emitted as `--<name>` CSS variables.
+  pub theme_colors: String,
+  /// Comma-separated `name=value` overrides for `prefers-color-scheme: dark`.
+  pub theme_dark_colors: String,
+  /// Logo path relative to the site root, exposed as the `--logo` CSS variable.
+  pub theme_logo: String,
+  /// Text of the splash screen shown while the application loads, `{percent}` being replaced
+  /// with the decoding progress; empty disables the splash screen.
+  pub splash_text: String,
+  /// Hash route the loader opens when the page has no hash, e.g. `#/programs/intro`; empty
+  /// leaves the URL untouched.
+  pub initial_route: String,
+  /// Whether `initial_route` also replaces deep links on every load.
+  pub force_initial_route: bool,
+  /// Whether `404.html` and `_redirects` fallbacks for history-mode routing are written next to
+  /// the index.
+  pub history_fallback: bool,
+  /// Route template, e.g. `/programs/{collection}/{entry}`, of the `index.html` copies written
+  /// per manifest entry with `history_fallback`; empty writes none.
+  pub history_routes: String,
+  /// Size in KiB below which images referenced from entry bodies and site stylesheets are
+  /// inlined as data URIs; `0` disables inlining.
+  pub inline_image_kib: u64,
+  /// Identifier written to `update.json`; empty uses `package_name`.
+  pub bundle_id: String,
+  /// Version of the bundled content, e.g. `2024.3.1`; empty skips `update.json`.
+  pub bundle_version: String,
+  /// Oldest app version able to open the bundle, recorded in `update.json`.
+  pub min_app_version: String,
+  /// External commands run before the build and after bundling.
+  pub hooks: BuildHooks,
+  /// Additional pages generated from the patched index, e.g. a kiosk variant.
+  pub entry_points: Vec<EntryPoint>,
+  /// Origin of every value that was overridden during discovery.
+  #[serde(skip)]
+  sources: BTreeMap<&'static str, ConfigSource>,
+}
+
+/// External commands run around the build, each given in argv form.
+///
+/// Commands run from the crate manifest directory with `OFFLINE_BUNDLE_ROOT`,
+/// `OFFLINE_MANIFEST_DIR` and `OFFLINE_HOOK` set in their environment; see
+/// [`crate::hooks::run_hook_commands`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct BuildHooks {
+  /// Commands run before collections are scanned, e.g. content linters.
+  pub pre_build: Vec<Vec<String>>,
+  /// Commands run once the bundle has been written, e.g. signing tools.
+  pub post_bundle: Vec<Vec<String>>,
+}
+
+impl BuildHooks {
+  /// Returns true when no commands are configured.
+  pub fn is_empty(&self) -> bool {
+    self.pre_build.is_empty() && self.post_bundle.is_empty()
+  }
+}
+
+/// Hook lists set by one configuration layer; each list present replaces the earlier one.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct BuildHooksOverrides {
+  /// Commands run before collections are scanned.
+  pub pre_build: Option<Vec<Vec<String>>>,
+  /// Commands run once the bundle has been written.
+  pub post_bundle: Option<Vec<Vec<String>>>,
+}
+
+/// Additional page written next to the patched index that starts the same app in another mode.
+///
+/// The page exposes its definition to the app as `window.__offlineEntryPoint`; see
+/// [`crate::bundle::entry_points`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct EntryPoint {
+  /// File name of the page in the site root, e.g. `kiosk.html`.
+  pub file: String,
+  /// Switch to fullscreen on the first interaction and after it was left.
+  pub fullscreen: bool,
+  /// Seconds without input after which the page reloads at its start route; 0 disables.
+  pub idle_reset_secs: u64,
+  /// Route opened when the page loads; empty uses `#/`.
+  pub start_route: String,
+  /// Flags the app reads from `window.__offlineEntryPoint.flags`, e.g. `showDrafts`.
+  pub flags: Vec<String>,
+}
+
+/// Location a configuration value was taken from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+  /// Built-in default value.
+  Default,
+  /// [`CONFIG_FILE`] in the Cargo workspace root.
+  WorkspaceFile(PathBuf),
+  /// [`CONFIG_FILE`] in the crate manifest directory.
+  ManifestFile(PathBuf),
+  /// `config` object of the root collection metadata document.
+  CollectionMetadata(PathBuf),
+  /// `OFFLINE_BUNDLER_*` environment variable.
+  Environment(String),
+}
+
+/// Resolved configuration value together with the layer it came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigValueExplanation {
+  /// Snake-case field name.
+  pub field: &'static str,
+  /// Effective value.
+  pub value: String,
+  /// Layer that supplied the value.
+  pub source: ConfigSource,
 }
 
 /// Optional configuration overrides embedded within collection metadata files.
@@ -52,19 +216,19 @@ pub struct CollectionConfigOverrides {
   #[serde(default)]
   pub package_name: Option<String>,
   /// Relative path from the manifest directory to the authored collections.
-  #[serde(default)]
+  #[serde(default, alias = "programsDir")]
   pub collections_dir: Option<String>,
   /// File containing the optional collection inclusion list.
-  #[serde(default)]
+  #[serde(default, alias = "programsLocalPath")]
   pub collections_local_path: Option<String>,
   /// Directory containing static assets for each collection entry.
-  #[serde(default)]
+  #[serde(default, alias = "moduleAssetsDir")]
   pub entry_assets_dir: Option<String>,
   /// Markdown filename that represents collection entries.
-  #[serde(default)]
+  #[serde(default, alias = "moduleMarkdownFile")]
   pub entry_markdown_file: Option<String>,
   /// Metadata filename describing a collection.
-  #[serde(default)]
+  #[serde(default, alias = "programMetadataFile")]
   pub collection_metadata_file: Option<String>,
   /// Directory that should be excluded from offline bundles.
   #[serde(default)]
@@ -73,17 +237,20 @@ pub struct CollectionConfigOverrides {
   #[serde(default)]
   pub excluded_path_fragment: Option<String>,
   /// Literal prefix used when embedding assets in generated code.
-  #[serde(default)]
+  #[serde(default, alias = "programAssetLiteralPrefix")]
   pub collection_asset_literal_prefix: Option<String>,
   /// Relative site root within the offline bundle output.
   #[serde(default)]
   pub offline_site_root: Option<String>,
   /// Directory name that stores all collections inside the offline bundle.
-  #[serde(default)]
+  #[serde(default, alias = "programsDirName")]
   pub collections_dir_name: Option<String>,
   /// Output directory for the offline HTML bundle.
   #[serde(default)]
   pub offline_bundle_root: Option<String>,
+  /// Absolute directory used for the bundle instead of `offline_bundle_root`.
+  #[serde(default)]
+  pub output_root: Option<String>,
   /// File name of the application entry point HTML.
   #[serde(default)]
   pub index_html_file: Option<String>,
@@ -93,8 +260,469 @@ pub struct CollectionConfigOverrides {
   /// Name of the serialized offline manifest JSON file.
   #[serde(default)]
   pub offline_manifest_json: Option<String>,
+  /// Maximum directory depth below a collection that asset scanning descends into.
+  #[serde(default, deserialize_with = "lenient")]
+  pub scan_max_depth: Option<usize>,
+  /// Comma-separated extensions bundled from assets trees.
+  #[serde(default)]
+  pub asset_extensions: Option<String>,
+  /// Comma-separated extensions never bundled from assets trees.
+  #[serde(default)]
+  pub excluded_extensions: Option<String>,
+  /// File system the bundle is copied to.
+  #[serde(default)]
+  pub target_filesystem: Option<String>,
+  /// Whether target file system problems warn or fail the build.
+  #[serde(default)]
+  pub filesystem_check: Option<String>,
+  /// Comma-separated `stem=target` stylesheet aliases.
+  #[serde(default)]
+  pub stylesheet_aliases: Option<String>,
+  /// Whether hashed outputs are renamed to stable names.
+  #[serde(default, deserialize_with = "lenient")]
+  pub stable_asset_names: Option<bool>,
+  /// Kind of build output patched.
+  #[serde(default)]
+  pub patch_mode: Option<String>,
+  /// Comma-separated classes never purged from bundled stylesheets.
+  #[serde(default)]
+  pub css_safelist: Option<String>,
+  /// Comma-separated URL schemes whose references are never bundled.
+  #[serde(default)]
+  pub ignored_asset_schemes: Option<String>,
+  /// Comma-separated path globs of references that are never bundled.
+  #[serde(default)]
+  pub ignored_asset_paths: Option<String>,
+  /// Comma-separated `name=value` brand colors.
+  #[serde(default)]
+  pub theme_colors: Option<String>,
+  /// Comma-separated `name=value` dark mode color overrides.
+  #[serde(default)]
+  pub theme_dark_colors: Option<String>,
+  /// Logo path relative to the site root.
+  #[serde(default)]
+  pub theme_logo: Option<String>,
+  /// Text of the splash screen shown while the application loads.
+  #[serde(default)]
+  pub splash_text: Option<String>,
+  /// Hash route the loader opens when the page has no hash.
+  #[serde(default)]
+  pub initial_route: Option<String>,
+  /// Whether `initial_route` also replaces deep links.
+  #[serde(default, deserialize_with = "lenient")]
+  pub force_initial_route: Option<bool>,
+  /// Whether history-mode routing fallbacks are written.
+  #[serde(default, deserialize_with = "lenient")]
+  pub history_fallback: Option<bool>,
+  /// Route template of the per-entry `index.html` copies.
+  #[serde(default)]
+  pub history_routes: Option<String>,
+  /// Size in KiB below which images are inlined as data URIs.
+  #[serde(default, deserialize_with = "lenient")]
+  pub inline_image_kib: Option<u64>,
+  /// Identifier written to `update.json`.
+  #[serde(default)]
+  pub bundle_id: Option<String>,
+  /// Version of the bundled content.
+  #[serde(default)]
+  pub bundle_version: Option<String>,
+  /// Oldest app version able to open the bundle.
+  #[serde(default)]
+  pub min_app_version: Option<String>,
+  /// Commands run before the build and after bundling; only honoured in [`CONFIG_FILE`].
+  #[serde(default)]
+  pub hooks: Option<BuildHooksOverrides>,
+  /// Additional entry-point pages; replaces entry points from earlier layers.
+  #[serde(default)]
+  pub entry_points: Option<Vec<EntryPoint>>,
+  /// Keys that did not match any known override, retained for diagnostics.
+  #[serde(flatten)]
+  unknown: BTreeMap<String, Value>,
+  /// Legacy key names found in the source document, retained for diagnostics.
+  #[serde(skip)]
+  legacy: Vec<LegacyConfigKey>,
+  /// Keys whose values could not be parsed, retained for diagnostics.
+  #[serde(skip)]
+  invalid: Vec<InvalidConfigKey>,
+}
+
+/// Configuration key that does not match any known override.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownConfigKey {
+  /// Key as written in the configuration document.
+  pub key: String,
+  /// Closest known key when the unknown key looks like a misspelling.
+  pub suggestion: Option<String>,
+}
+
+/// Configuration key whose value has the wrong type and was ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidConfigKey {
+  /// Key as written in the configuration document; empty when the document itself is not an
+  /// object of keys.
+  pub key: String,
+  /// Why the value was rejected.
+  pub reason: String,
+}
+
+/// Key names used by older content trees, paired with the key that replaced them.
+///
+/// The legacy spellings are still accepted as aliases but reported so they can be renamed.
+pub const LEGACY_CONFIG_KEYS: &[(&str, &str)] = &[
+  ("programsDir", "collectionsDir"),
+  ("programsLocalPath", "collectionsLocalPath"),
+  ("moduleAssetsDir", "entryAssetsDir"),
+  ("moduleMarkdownFile", "entryMarkdownFile"),
+  ("programMetadataFile", "collectionMetadataFile"),
+  ("programAssetLiteralPrefix", "collectionAssetLiteralPrefix"),
+  ("programsDirName", "collectionsDirName"),
+];
+
+/// Configuration key written with a legacy name that should be renamed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LegacyConfigKey {
+  /// Key as written in the configuration document.
+  pub key: String,
+  /// Current name of the key.
+  pub replacement: String,
+}
+
+/// Legacy name found in a content tree that should be renamed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LegacyName {
+  /// Configuration key spelled with a name from [`LEGACY_CONFIG_KEYS`].
+  Key {
+    /// Document containing the key.
+    path: PathBuf,
+    /// The key and its replacement.
+    key: LegacyConfigKey,
+  },
+  /// Collection metadata stored in [`LEGACY_COLLECTION_METADATA_FILE`].
+  MetadataFile {
+    /// Legacy metadata document.
+    path: PathBuf,
+    /// Path the document should be renamed to.
+    replacement: PathBuf,
+  },
+}
+
+/// Builder for [`ProjectConfig`] starting from the built-in defaults.
+///
+/// Values are validated when [`ProjectConfigBuilder::build`] is called.
+#[derive(Debug, Clone, Default)]
+pub struct ProjectConfigBuilder {
+  config: ProjectConfig,
+}
+
+/// Errors reported when validating configuration or build context values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigValidationError {
+  /// A required value was not provided.
+  Missing {
+    /// Name of the missing field.
+    field: &'static str,
+  },
+  /// A value was provided but is not acceptable.
+  Invalid {
+    /// Name of the offending field.
+    field: &'static str,
+    /// Value that failed validation.
+    value: String,
+    /// Explanation of the expected format.
+    reason: &'static str,
+  },
+  /// A path that must exist on disk could not be found.
+  PathNotFound {
+    /// Name of the offending field.
+    field: &'static str,
+    /// Path that does not exist.
+    path: PathBuf,
+  },
+  /// A collections archive could not be verified or extracted.
+  Archive {
+    /// Path of the archive.
+    path: PathBuf,
+    /// Why verification or extraction failed.
+    reason: String,
+  },
 }
 
+impl ProjectConfigBuilder {
+  /// Set the name of the main cargo package for build output paths.
+  pub fn package_name(mut self, value: impl Into<String>) -> Self {
+    self.config.package_name = value.into();
+    self
+  }
+
+  /// Set the relative path from the manifest directory to the authored collections.
+  pub fn collections_dir(mut self, value: impl Into<String>) -> Self {
+    self.config.collections_dir = value.into();
+    self
+  }
+
+  /// Set the optional JSON file describing which collections to include in builds.
+  pub fn collections_local_path(mut self, value: impl Into<String>) -> Self {
+    self.config.collections_local_path = value.into();
+    self
+  }
+
+  /// Set the directory containing static assets for each collection.
+  pub fn entry_assets_dir(mut self, value: impl Into<String>) -> Self {
+    self.config.entry_assets_dir = value.into();
+    self
+  }
+
+  /// Set the markdown filename that represents collection entries.
+  pub fn entry_markdown_file(mut self, value: impl Into<String>) -> Self {
+    self.config.entry_markdown_file = value.into();
+    self
+  }
+
+  /// Set the metadata filename describing the collection (title, description, etc.).
+  pub fn collection_metadata_file(mut self, value: impl Into<String>) -> Self {
+    self.config.collection_metadata_file = value.into();
+    self
+  }
+
+  /// Set the directory name containing assets that should be ignored for offline bundles.
+  pub fn excluded_dir_name(mut self, value: impl Into<String>) -> Self {
+    self.config.excluded_dir_name = value.into();
+    self
+  }
+
+  /// Set the path fragment that signals a resource should be excluded from offline bundles.
+  pub fn excluded_path_fragment(mut self, value: impl Into<String>) -> Self {
+    self.config.excluded_path_fragment = value.into();
+    self
+  }
+
+  /// Set the string literal prefix used when embedding assets in generated code.
+  pub fn collection_asset_literal_prefix(mut self, value: impl Into<String>) -> Self {
+    self.config.collection_asset_literal_prefix = value.into();
+    self
+  }
+
+  /// Set the relative site root within the offline bundle output.
+  pub fn offline_site_root(mut self, value: impl Into<String>) -> Self {
+    self.config.offline_site_root = value.into();
+    self
+  }
+
+  /// Set the directory name holding all collections.
+  pub fn collections_dir_name(mut self, value: impl Into<String>) -> Self {
+    self.config.collections_dir_name = value.into();
+    self
+  }
+
+  /// Set the path where the offline HTML bundle should be written.
+  pub fn offline_bundle_root(mut self, value: impl Into<String>) -> Self {
+    self.config.offline_bundle_root = value.into();
+    self
+  }
+
+  /// Set the absolute directory used for the bundle instead of `offline_bundle_root`.
+  pub fn output_root(mut self, value: impl Into<String>) -> Self {
+    self.config.output_root = value.into();
+    self
+  }
+
+  /// Set the file name of the application entry point HTML.
+  pub fn index_html_file(mut self, value: impl Into<String>) -> Self {
+    self.config.index_html_file = value.into();
+    self
+  }
+
+  /// Set the cargo target directory used during builds.
+  pub fn target_dir(mut self, value: impl Into<String>) -> Self {
+    self.config.target_dir = value.into();
+    self
+  }
+
+  /// Set the name of the serialized offline manifest JSON file.
+  pub fn offline_manifest_json(mut self, value: impl Into<String>) -> Self {
+    self.config.offline_manifest_json = value.into();
+    self
+  }
+
+  /// Set the maximum directory depth below a collection that asset scanning descends into.
+  pub fn scan_max_depth(mut self, value: usize) -> Self {
+    self.config.scan_max_depth = value;
+    self
+  }
+
+  /// Set the comma-separated extensions bundled from assets trees.
+  pub fn asset_extensions(mut self, value: impl Into<String>) -> Self {
+    self.config.asset_extensions = value.into();
+    self
+  }
+
+  /// Set the comma-separated extensions never bundled from assets trees.
+  pub fn excluded_extensions(mut self, value: impl Into<String>) -> Self {
+    self.config.excluded_extensions = value.into();
+    self
+  }
+
+  /// Set the file system the bundle is copied to (`fat32` or `exfat`).
+  pub fn target_filesystem(mut self, value: impl Into<String>) -> Self {
+    self.config.target_filesystem = value.into();
+    self
+  }
+
+  /// Set whether target file system problems `warn` or fail the build (`error`).
+  pub fn filesystem_check(mut self, value: impl Into<String>) -> Self {
+    self.config.filesystem_check = value.into();
+    self
+  }
+
+  /// Set the comma-separated `stem=target` stylesheet aliases.
+  pub fn stylesheet_aliases(mut self, value: impl Into<String>) -> Self {
+    self.config.stylesheet_aliases = value.into();
+    self
+  }
+
+  /// Set whether hashed outputs are renamed to stable names.
+  pub fn stable_asset_names(mut self, value: bool) -> Self {
+    self.config.stable_asset_names = value;
+    self
+  }
+
+  /// Set the kind of build output patched (`dioxus` or `generic`).
+  pub fn patch_mode(mut self, value: impl Into<String>) -> Self {
+    self.config.patch_mode = value.into();
+    self
+  }
+
+  /// Set the comma-separated classes never purged from bundled stylesheets.
+  pub fn css_safelist(mut self, value: impl Into<String>) -> Self {
+    self.config.css_safelist = value.into();
+    self
+  }
+
+  /// Set the comma-separated URL schemes whose references are never bundled.
+  pub fn ignored_asset_schemes(mut self, value: impl Into<String>) -> Self {
+    self.config.ignored_asset_schemes = value.into();
+    self
+  }
+
+  /// Set the comma-separated path globs of references that are never bundled.
+  pub fn ignored_asset_paths(mut self, value: impl Into<String>) -> Self {
+    self.config.ignored_asset_paths = value.into();
+    self
+  }
+
+  /// Set the comma-separated `name=value` brand colors.
+  pub fn theme_colors(mut self, value: impl Into<String>) -> Self {
+    self.config.theme_colors = value.into();
+    self
+  }
+
+  /// Set the comma-separated `name=value` dark mode color overrides.
+  pub fn theme_dark_colors(mut self, value: impl Into<String>) -> Self {
+    self.config.theme_dark_colors = value.into();
+    self
+  }
+
+  /// Set the logo path relative to the site root.
+  pub fn theme_logo(mut self, value: impl Into<String>) -> Self {
+    self.config.theme_logo = value.into();
+    self
+  }
+
+  /// Set the splash screen text, `{percent}` being replaced with the decoding progress.
+  pub fn splash_text(mut self, value: impl Into<String>) -> Self {
+    self.config.splash_text = value.into();
+    self
+  }
+
+  /// Set the hash route the loader opens, e.g. `#/programs/intro`.
+  pub fn initial_route(mut self, value: impl Into<String>) -> Self {
+    self.config.initial_route = value.into();
+    self
+  }
+
+  /// Set whether the initial route also replaces deep links.
+  pub fn force_initial_route(mut self, value: bool) -> Self {
+    self.config.force_initial_route = value;
+    self
+  }
+
+  /// Set whether history-mode routing fallbacks are written.
+  pub fn history_fallback(mut self, value: bool) -> Self {
+    self.config.history_fallback = value;
+    self
+  }
+
+  /// Set the route template of the per-entry `index.html` copies, e.g.
+  /// `/programs/{collection}/{entry}`.
+  pub fn history_routes(mut self, value: impl Into<String>) -> Self {
+    self.config.history_routes = value.into();
+    self
+  }
+
+  /// Set the size in KiB below which images are inlined as data URIs; `0` disables inlining.
+  pub fn inline_image_kib(mut self, value: u64) -> Self {
+    self.config.inline_image_kib = value;
+    self
+  }
+
+  /// Set the identifier written to `update.json`.
+  pub fn bundle_id(mut self, value: impl Into<String>) -> Self {
+    self.config.bundle_id = value.into();
+    self
+  }
+
+  /// Set the version of the bundled content.
+  pub fn bundle_version(mut self, value: impl Into<String>) -> Self {
+    self.config.bundle_version = value.into();
+    self
+  }
+
+  /// Set the oldest app version able to open the bundle.
+  pub fn min_app_version(mut self, value: impl Into<String>) -> Self {
+    self.config.min_app_version = value.into();
+    self
+  }
+
+  /// Set the commands run before the build and after bundling.
+  pub fn hooks(mut self, value: BuildHooks) -> Self {
+    self.config.hooks = value;
+    self
+  }
+
+  /// Add an entry-point page generated from the patched index.
+  pub fn entry_point(mut self, value: EntryPoint) -> Self {
+    self.config.entry_points.push(value);
+    self
+  }
+
+  /// Validate the configured values and produce the configuration.
+  pub fn build(self) -> Result<ProjectConfig, ConfigValidationError> {
+    self.config.validate()?;
+    Ok(self.config)
+  }
+}
+
+impl fmt::Display for ConfigValidationError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Missing { field } => write!(f, "missing required value for `{field}`"),
+      Self::Invalid {
+        field,
+        value,
+        reason,
+      } => write!(f, "invalid value {value:?} for `{field}`: {reason}"),
+      Self::PathNotFound { field, path } => {
+        write!(f, "path for `{field}` does not exist: {}", path.display())
+      }
+      Self::Archive { path, reason } => {
+        write!(f, "collections archive {}: {reason}", path.display())
+      }
+    }
+  }
+}
+
+impl std::error::Error for ConfigValidationError {}
+
 impl Default for ProjectConfig {
   fn default() -> Self {
     Self {
@@ -110,9 +738,36 @@ impl Default for ProjectConfig {
       offline_site_root: "site".into(),
       collections_dir_name: "programs".into(),
       offline_bundle_root: "target/offline-html".into(),
+      output_root: String::new(),
       index_html_file: "index.html".into(),
       target_dir: "target".into(),
       offline_manifest_json: "offline_manifest.json".into(),
+      scan_max_depth: DEFAULT_SCAN_MAX_DEPTH,
+      asset_extensions: String::new(),
+      excluded_extensions: String::new(),
+      target_filesystem: String::new(),
+      filesystem_check: "warn".into(),
+      stylesheet_aliases: "tailwind=tailwind.css".into(),
+      stable_asset_names: false,
+      patch_mode: "dioxus".into(),
+      css_safelist: String::new(),
+      ignored_asset_schemes: String::new(),
+      ignored_asset_paths: String::new(),
+      theme_colors: String::new(),
+      theme_dark_colors: String::new(),
+      theme_logo: String::new(),
+      splash_text: String::new(),
+      initial_route: "#/".into(),
+      force_initial_route: false,
+      history_fallback: false,
+      history_routes: String::new(),
+      inline_image_kib: 0,
+      bundle_id: String::new(),
+      bundle_version: String::new(),
+      min_app_version: String::new(),
+      hooks: BuildHooks::default(),
+      entry_points: Vec::new(),
+      sources: BTreeMap::new(),
     }
   }
 }
@@ -121,23 +776,390 @@ impl ProjectConfig {
   /// Attempt to load configuration from the provided directory.
   ///
   /// When configuration overrides do not exist or fail to parse we fall back to default
-  /// values so downstream callers can continue operating with sensible assumptions.
+  /// values so downstream callers can continue operating with sensible assumptions. A key whose
+  /// value has the wrong type is skipped and reported as a cargo warning; the other keys of its
+  /// document still apply.
+  ///
+  /// Layers are merged in the following order, later layers winning:
+  ///
+  /// 1. built-in defaults,
+  /// 2. [`CONFIG_FILE`] in the Cargo workspace root (when the crate is a workspace member),
+  /// 3. [`CONFIG_FILE`] in `manifest_dir`,
+  /// 4. the `config` object of the root collection metadata document, except `hooks`, which is
+  ///    ignored with a warning because content must not be able to run commands,
+  /// 5. `OFFLINE_BUNDLER_*` environment variables (see [`ENV_PREFIX`]).
+  ///
+  /// Per-collection `config` objects are applied on top of the resulting layout while walking
+  /// the content tree (see [`OfflineProjectLayout::with_overrides`]). Environment values are
+  /// also consulted before the root metadata file is located so CI can point discovery at an
+  /// alternate content checkout. Use [`ProjectConfig::explain`] to see which layer supplied
+  /// each value.
   pub fn discover(manifest_dir: &Path) -> Self {
+    Self::discover_with_env(manifest_dir, |name| std::env::var(name).ok())
+  }
+
+  fn discover_with_env<F>(manifest_dir: &Path, lookup: F) -> Self
+  where
+    F: Fn(&str) -> Option<String>,
+  {
     let mut config = Self::default();
 
-    let root_metadata_path = manifest_dir
-      .join(&config.collections_dir)
-      .join(&config.collection_metadata_file);
+    if let Some(workspace_root) = find_workspace_root(manifest_dir) {
+      let path = workspace_root.join(CONFIG_FILE);
+      if let Some(overrides) = load_config_file(&path) {
+        config.apply_overrides(&overrides, ConfigSource::WorkspaceFile(path));
+      }
+    }
+
+    let manifest_file = manifest_dir.join(CONFIG_FILE);
+    if let Some(overrides) = load_config_file(&manifest_file) {
+      config.apply_overrides(&overrides, ConfigSource::ManifestFile(manifest_file));
+    }
+
+    let env_overrides = env_overrides(&lookup);
+    for invalid in env_overrides.invalid_keys() {
+      println!("cargo:warning={invalid}");
+    }
+    config.apply_env_overrides(&env_overrides);
+
+    let root_metadata_path = collection_metadata_path(
+      &manifest_dir.join(&config.collections_dir),
+      &config.collection_metadata_file,
+    );
 
-    if let Some(overrides) = load_config_overrides(&root_metadata_path) {
-      config.apply_overrides(&overrides);
+    if let Some(mut overrides) = load_config_overrides(&root_metadata_path) {
+      // Collection metadata is authored content and must not be able to run commands.
+      if overrides.hooks.take().is_some() {
+        println!(
+          "cargo:warning=ignoring configuration key `hooks` in {}: hooks can only be set in {CONFIG_FILE}",
+          root_metadata_path.display()
+        );
+      }
+      config.apply_overrides(
+        &overrides,
+        ConfigSource::CollectionMetadata(root_metadata_path),
+      );
+      config.apply_env_overrides(&env_overrides);
     }
 
     config
   }
 
+  /// Start building a configuration from the built-in defaults.
+  pub fn builder() -> ProjectConfigBuilder {
+    ProjectConfigBuilder::default()
+  }
+
+  /// Check that the configured values are usable.
+  ///
+  /// Every value must be non-empty, except for `offline_site_root` which may be empty to place
+  /// the site at the bundle root, and the optional `target_filesystem`, `stylesheet_aliases`,
+  /// `css_safelist`, `splash_text`, `initial_route`, `history_routes`, `ignored_asset_*`,
+  /// `*_extensions` and `theme_*` values. The file system settings, stylesheet aliases and
+  /// theme colors must parse and the derived layout must pass
+  /// [`OfflineProjectLayout::validate`].
+  pub fn validate(&self) -> Result<(), ConfigValidationError> {
+    for (field, value) in self.fields() {
+      if value.trim().is_empty()
+        && !matches!(
+          field,
+          "offline_site_root"
+            | "output_root"
+            | "target_filesystem"
+            | "stylesheet_aliases"
+            | "css_safelist"
+            | "splash_text"
+            | "initial_route"
+            | "history_routes"
+            | "bundle_id"
+            | "bundle_version"
+            | "min_app_version"
+        )
+        && !field.starts_with("theme_")
+        && !field.starts_with("ignored_asset_")
+        && !field.ends_with("_extensions")
+      {
+        return Err(ConfigValidationError::Missing { field });
+      }
+    }
+
+    for (field, commands) in [
+      ("hooks.pre_build", &self.hooks.pre_build),
+      ("hooks.post_bundle", &self.hooks.post_bundle),
+    ] {
+      if let Some(command) = commands
+        .iter()
+        .find(|argv| argv.is_empty() || argv[0].is_empty())
+      {
+        return Err(ConfigValidationError::Invalid {
+          field,
+          value: format!("{command:?}"),
+          reason: "expected a non-empty program name",
+        });
+      }
+    }
+    self.validate_entry_points()?;
+    self.scan_max_depth()?;
+    self.filesystem_profile()?;
+    self.stylesheet_aliases()?;
+    self.generic_patch_mode()?;
+    self.initial_route()?;
+    self.inline_image_limit()?;
+    self.theme_palette()?;
+    self.update_metadata("")?;
+    self.to_layout().validate()
+  }
+
+  fn validate_entry_points(&self) -> Result<(), ConfigValidationError> {
+    let mut files = vec![self.index_html_file.as_str()];
+    for entry_point in &self.entry_points {
+      let file = entry_point.file.as_str();
+      let reason = if !file.ends_with(".html") || file.contains(['/', '\\']) {
+        "expected an `.html` file name in the site root"
+      } else if files.contains(&file) {
+        "must differ from the index and the other entry points"
+      } else {
+        files.push(file);
+        continue;
+      };
+      return Err(ConfigValidationError::Invalid {
+        field: "entry_points",
+        value: file.to_string(),
+        reason,
+      });
+    }
+    Ok(())
+  }
+
+  /// Parsed `scan_max_depth`.
+  pub fn scan_max_depth(&self) -> Result<usize, ConfigValidationError> {
+    if self.scan_max_depth == 0 {
+      return Err(ConfigValidationError::Invalid {
+        field: "scan_max_depth",
+        value: self.scan_max_depth.to_string(),
+        reason: "expected a positive number of directory levels",
+      });
+    }
+    Ok(self.scan_max_depth)
+  }
+
+  /// Target file system checks requested by the configuration, if any.
+  pub fn filesystem_profile(&self) -> Result<Option<FilesystemProfile>, ConfigValidationError> {
+    if self.target_filesystem.trim().is_empty() {
+      return Ok(None);
+    }
+
+    let filesystem =
+      self
+        .target_filesystem
+        .parse()
+        .map_err(|_| ConfigValidationError::Invalid {
+          field: "target_filesystem",
+          value: self.target_filesystem.clone(),
+          reason: "expected `fat32` or `exfat`",
+        })?;
+    let mode = self
+      .filesystem_check
+      .parse()
+      .map_err(|_| ConfigValidationError::Invalid {
+        field: "filesystem_check",
+        value: self.filesystem_check.clone(),
+        reason: "expected `warn` or `error`",
+      })?;
+
+    Ok(Some(FilesystemProfile { filesystem, mode }))
+  }
+
+  /// Parsed `(stem, target_name)` pairs from `stylesheet_aliases`.
+  ///
+  /// Each hashed `<stem>-<hash>.css` stylesheet is copied to `<target_name>` in the site root.
+  pub fn stylesheet_aliases(&self) -> Result<Vec<(String, String)>, ConfigValidationError> {
+    self
+      .stylesheet_aliases
+      .split(',')
+      .map(str::trim)
+      .filter(|pair| !pair.is_empty())
+      .map(|pair| match pair.split_once('=') {
+        Some((stem, target))
+          if !stem.trim().is_empty()
+            && !target.trim().is_empty()
+            && !target.contains(['/', '\\']) =>
+        {
+          Ok((stem.trim().to_string(), target.trim().to_string()))
+        }
+        _ => Err(ConfigValidationError::Invalid {
+          field: "stylesheet_aliases",
+          value: self.stylesheet_aliases.clone(),
+          reason: "expected comma-separated `stem=file.css` pairs",
+        }),
+      })
+      .collect()
+  }
+
+  /// Hash route from `initial_route`, or `None` when the loader leaves the URL untouched.
+  pub fn initial_route(&self) -> Result<Option<&str>, ConfigValidationError> {
+    let route = self.initial_route.trim();
+    if route.is_empty() {
+      return Ok(None);
+    }
+    if !route.starts_with('#') {
+      return Err(ConfigValidationError::Invalid {
+        field: "initial_route",
+        value: self.initial_route.clone(),
+        reason: "expected a hash route such as `#/programs/intro`",
+      });
+    }
+    Ok(Some(route))
+  }
+
+  /// Byte size below which images are inlined as data URIs, or `None` when `inline_image_kib`
+  /// is `0`.
+  pub fn inline_image_limit(&self) -> Result<Option<u64>, ConfigValidationError> {
+    self
+      .inline_image_kib
+      .checked_mul(1024)
+      .map(|limit| (limit > 0).then_some(limit))
+      .ok_or_else(|| ConfigValidationError::Invalid {
+        field: "inline_image_kib",
+        value: self.inline_image_kib.to_string(),
+        reason: "expected a size in KiB, or `0` to disable inlining",
+      })
+  }
+
+  /// Whether `patch_mode` selects generic `wasm-bindgen` patching.
+  pub fn generic_patch_mode(&self) -> Result<bool, ConfigValidationError> {
+    match self.patch_mode.trim() {
+      "dioxus" => Ok(false),
+      "generic" => Ok(true),
+      _ => Err(ConfigValidationError::Invalid {
+        field: "patch_mode",
+        value: self.patch_mode.clone(),
+        reason: "expected `dioxus` or `generic`",
+      }),
+    }
+  }
+
+  /// Classes from `css_safelist` that CSS purging must keep.
+  pub fn css_safelist(&self) -> Vec<String> {
+    self
+      .css_safelist
+      .split(',')
+      .map(str::trim)
+      .filter(|class| !class.is_empty())
+      .map(|class| class.trim_start_matches('.').to_string())
+      .collect()
+  }
+
+  /// Asset reference rules from `ignored_asset_schemes` and `ignored_asset_paths`.
+  ///
+  /// Pass the result to [`OfflineBuildContextBuilder::asset_reference_filter`] for builds and
+  /// use it at runtime so both sides skip the same references.
+  ///
+  /// [`OfflineBuildContextBuilder::asset_reference_filter`]: crate::project::OfflineBuildContextBuilder::asset_reference_filter
+  pub fn asset_reference_filter(&self) -> AssetReferenceFilter {
+    let split = |value: &str| {
+      value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect::<Vec<_>>()
+    };
+    AssetReferenceFilter::default()
+      .with_schemes(split(&self.ignored_asset_schemes))
+      .with_path_globs(split(&self.ignored_asset_paths))
+  }
+
+  /// Brand palette from the `theme_*` values, or `None` when no theme is configured.
+  pub fn theme_palette(&self) -> Result<Option<ThemePalette>, ConfigValidationError> {
+    let parse = |field: &'static str, value: &String| {
+      parse_colors(value).ok_or_else(|| ConfigValidationError::Invalid {
+        field,
+        value: value.clone(),
+        reason: "expected comma-separated `name=color` pairs",
+      })
+    };
+    let logo = self.theme_logo.trim();
+    let palette = ThemePalette {
+      colors: parse("theme_colors", &self.theme_colors)?,
+      dark_colors: parse("theme_dark_colors", &self.theme_dark_colors)?,
+      logo: (!logo.is_empty()).then(|| logo.to_string()),
+    };
+    Ok((!palette.is_empty()).then_some(palette))
+  }
+
+  /// Splash screen text from `splash_text`, or `None` when the splash screen is disabled.
+  pub fn splash_text(&self) -> Option<&str> {
+    let text = self.splash_text.trim();
+    (!text.is_empty()).then_some(text)
+  }
+
+  /// `update.json` contents for a bundle whose content hashes to `content_hash`, or `None` when
+  /// no `bundle_version` is configured.
+  pub fn update_metadata(
+    &self,
+    content_hash: impl Into<String>,
+  ) -> Result<Option<UpdateMetadata>, ConfigValidationError> {
+    let version = |field: &'static str, value: &String| {
+      let trimmed = value.trim();
+      if trimmed.is_empty() {
+        return Ok(None);
+      }
+      parse_lenient_version(trimmed)
+        .map(|_| Some(trimmed.to_string()))
+        .ok_or_else(|| ConfigValidationError::Invalid {
+          field,
+          value: value.clone(),
+          reason: "expected a version such as `1.4` or `2024.3.1`",
+        })
+    };
+    let min_app_version = version("min_app_version", &self.min_app_version)?;
+    let Some(bundle_version) = version("bundle_version", &self.bundle_version)? else {
+      return Ok(None);
+    };
+    let bundle_id = match self.bundle_id.trim() {
+      "" => self.package_name.trim(),
+      id => id,
+    };
+    Ok(Some(UpdateMetadata {
+      bundle_id: bundle_id.to_string(),
+      version: bundle_version,
+      content_hash: content_hash.into(),
+      min_app_version,
+    }))
+  }
+
+  /// Report every configuration value together with the layer that supplied it.
+  pub fn explain(&self) -> Vec<ConfigValueExplanation> {
+    let typed = [
+      ("scan_max_depth", self.scan_max_depth.to_string()),
+      ("stable_asset_names", self.stable_asset_names.to_string()),
+      ("force_initial_route", self.force_initial_route.to_string()),
+      ("history_fallback", self.history_fallback.to_string()),
+      ("inline_image_kib", self.inline_image_kib.to_string()),
+    ];
+    self
+      .fields()
+      .into_iter()
+      .map(|(field, value)| (field, value.clone()))
+      .chain(typed)
+      .map(|(field, value)| ConfigValueExplanation {
+        field,
+        value,
+        source: self
+          .sources
+          .get(field)
+          .cloned()
+          .unwrap_or(ConfigSource::Default),
+      })
+      .collect()
+  }
+
   /// Convert the configuration into an owned layout description.
   pub fn into_layout(self) -> OfflineProjectLayout {
+    let scan_max_depth = self.scan_max_depth().unwrap_or(DEFAULT_SCAN_MAX_DEPTH);
+    let asset_extensions = split_extensions(&self.asset_extensions);
+    let excluded_extensions = split_extensions(&self.excluded_extensions);
     OfflineProjectLayout {
       entry_assets_dir: self.entry_assets_dir,
       entry_markdown_file: self.entry_markdown_file,
@@ -148,9 +1170,13 @@ impl ProjectConfig {
       offline_site_root: self.offline_site_root,
       collections_dir_name: self.collections_dir_name,
       offline_bundle_root: self.offline_bundle_root,
+      output_root: output_root(&self.output_root),
       index_html_file: self.index_html_file,
       target_dir: self.target_dir,
       offline_manifest_json: self.offline_manifest_json,
+      scan_max_depth,
+      asset_extensions,
+      excluded_extensions,
     }
   }
 
@@ -166,9 +1192,13 @@ impl ProjectConfig {
       offline_site_root: self.offline_site_root.clone(),
       collections_dir_name: self.collections_dir_name.clone(),
       offline_bundle_root: self.offline_bundle_root.clone(),
+      output_root: output_root(&self.output_root),
       index_html_file: self.index_html_file.clone(),
       target_dir: self.target_dir.clone(),
       offline_manifest_json: self.offline_manifest_json.clone(),
+      scan_max_depth: self.scan_max_depth().unwrap_or(DEFAULT_SCAN_MAX_DEPTH),
+      asset_extensions: split_extensions(&self.asset_extensions),
+      excluded_extensions: split_extensions(&self.excluded_extensions),
     }
   }
 
@@ -178,58 +1208,236 @@ impl ProjectConfig {
   }
 
   /// Path to the local selection file.
+  ///
+  /// When `collections_dir` names a file such as a collections archive, the selection file is
+  /// resolved next to it.
   pub fn collections_local_file(&self, manifest_dir: &Path) -> PathBuf {
-    manifest_dir
-      .join(&self.collections_dir)
-      .join(&self.collections_local_path)
+    let collections_dir = self.collections_dir_path(manifest_dir);
+    match collections_dir.parent() {
+      Some(parent) if collections_dir.is_file() => parent.join(&self.collections_local_path),
+      _ => collections_dir.join(&self.collections_local_path),
+    }
   }
 
-  fn apply_overrides(&mut self, overrides: &CollectionConfigOverrides) {
-    if let Some(value) = &overrides.collections_dir {
-      self.collections_dir = value.clone();
-    }
-    if let Some(value) = &overrides.collections_local_path {
-      self.collections_local_path = value.clone();
+  /// Legacy key and file names in the configuration files and collection metadata that
+  /// [`ProjectConfig::discover`] would read for `manifest_dir`.
+  ///
+  /// Nothing is renamed; the result lists what should be migrated.
+  pub fn legacy_names(&self, manifest_dir: &Path) -> Vec<LegacyName> {
+    let mut names = Vec::new();
+    let config_files = find_workspace_root(manifest_dir)
+      .map(|root| root.join(CONFIG_FILE))
+      .into_iter()
+      .chain([manifest_dir.join(CONFIG_FILE)]);
+    for path in config_files {
+      if let Some(value) = fs::read_to_string(&path)
+        .ok()
+        .and_then(|content| serde_json::from_str::<Value>(&content).ok())
+      {
+        let overrides = CollectionConfigOverrides::from_value(value);
+        push_legacy_keys(&mut names, &path, &overrides);
+      }
     }
-    if let Some(value) = &overrides.entry_assets_dir {
-      self.entry_assets_dir = value.clone();
+    let max_depth = self.scan_max_depth().unwrap_or(DEFAULT_SCAN_MAX_DEPTH);
+    self.collect_legacy_metadata(
+      &self.collections_dir_path(manifest_dir),
+      max_depth,
+      &mut names,
+    );
+    names
+  }
+
+  fn collect_legacy_metadata(&self, dir: &Path, depth: usize, names: &mut Vec<LegacyName>) {
+    let path = collection_metadata_path(dir, &self.collection_metadata_file);
+    if path.ends_with(LEGACY_COLLECTION_METADATA_FILE)
+      && self.collection_metadata_file != LEGACY_COLLECTION_METADATA_FILE
+    {
+      names.push(LegacyName::MetadataFile {
+        replacement: dir.join(&self.collection_metadata_file),
+        path: path.clone(),
+      });
     }
-    if let Some(value) = &overrides.entry_markdown_file {
-      self.entry_markdown_file = value.clone();
+    if let Some((_, overrides)) = fs::read_to_string(&path)
+      .ok()
+      .and_then(|content| split_document(&content))
+    {
+      push_legacy_keys(names, &path, &overrides);
     }
-    if let Some(value) = &overrides.collection_metadata_file {
-      self.collection_metadata_file = value.clone();
+    let Some(depth) = depth.checked_sub(1) else {
+      return;
+    };
+    let Ok(children) = fs::read_dir(dir) else {
+      return;
+    };
+    let mut children: Vec<PathBuf> = children
+      .flatten()
+      .filter(|child| child.file_type().is_ok_and(|ft| ft.is_dir()))
+      .filter(|child| !child.file_name().to_string_lossy().starts_with('.'))
+      .map(|child| child.path())
+      .collect();
+    children.sort();
+    for child in children {
+      self.collect_legacy_metadata(&child, depth, names);
     }
-    if let Some(value) = &overrides.excluded_dir_name {
-      self.excluded_dir_name = value.clone();
+  }
+
+  fn apply_overrides(&mut self, overrides: &CollectionConfigOverrides, source: ConfigSource) {
+    self.apply_overrides_with(overrides, |_| source.clone());
+  }
+
+  fn apply_env_overrides(&mut self, overrides: &CollectionConfigOverrides) {
+    self.apply_overrides_with(overrides, |field| {
+      ConfigSource::Environment(format!("{ENV_PREFIX}{}", field.to_uppercase()))
+    });
+  }
+
+  fn apply_overrides_with<F>(&mut self, overrides: &CollectionConfigOverrides, source: F)
+  where
+    F: Fn(&'static str) -> ConfigSource,
+  {
+    let mut applied = Vec::new();
+    for ((field, target), (_, value)) in self.fields_mut().into_iter().zip(overrides.fields()) {
+      if let Some(value) = value {
+        target.clone_from(value);
+        applied.push(field);
+      }
     }
-    if let Some(value) = &overrides.excluded_path_fragment {
-      self.excluded_path_fragment = value.clone();
+    if let Some(depth) = overrides.scan_max_depth {
+      self.scan_max_depth = depth;
+      applied.push("scan_max_depth");
     }
-    if let Some(value) = &overrides.collection_asset_literal_prefix {
-      self.collection_asset_literal_prefix = value.clone();
+    if let Some(value) = overrides.stable_asset_names {
+      self.stable_asset_names = value;
+      applied.push("stable_asset_names");
     }
-    if let Some(value) = &overrides.offline_site_root {
-      self.offline_site_root = value.clone();
+    if let Some(value) = overrides.force_initial_route {
+      self.force_initial_route = value;
+      applied.push("force_initial_route");
     }
-    if let Some(value) = &overrides.collections_dir_name {
-      self.collections_dir_name = value.clone();
+    if let Some(value) = overrides.history_fallback {
+      self.history_fallback = value;
+      applied.push("history_fallback");
     }
-    if let Some(value) = &overrides.offline_bundle_root {
-      self.offline_bundle_root = value.clone();
+    if let Some(value) = overrides.inline_image_kib {
+      self.inline_image_kib = value;
+      applied.push("inline_image_kib");
     }
-    if let Some(value) = &overrides.index_html_file {
-      self.index_html_file = value.clone();
+    if let Some(hooks) = &overrides.hooks {
+      if let Some(commands) = &hooks.pre_build {
+        self.hooks.pre_build.clone_from(commands);
+        applied.push("hooks.pre_build");
+      }
+      if let Some(commands) = &hooks.post_bundle {
+        self.hooks.post_bundle.clone_from(commands);
+        applied.push("hooks.post_bundle");
+      }
     }
-    if let Some(value) = &overrides.target_dir {
-      self.target_dir = value.clone();
+    if let Some(entry_points) = &overrides.entry_points {
+      self.entry_points.clone_from(entry_points);
+      applied.push("entry_points");
     }
-    if let Some(value) = &overrides.offline_manifest_json {
-      self.offline_manifest_json = value.clone();
+    for field in applied {
+      self.sources.insert(field, source(field));
     }
   }
+
+  fn fields(&self) -> [(&'static str, &String); 34] {
+    [
+      ("package_name", &self.package_name),
+      ("collections_dir", &self.collections_dir),
+      ("collections_local_path", &self.collections_local_path),
+      ("entry_assets_dir", &self.entry_assets_dir),
+      ("entry_markdown_file", &self.entry_markdown_file),
+      ("collection_metadata_file", &self.collection_metadata_file),
+      ("excluded_dir_name", &self.excluded_dir_name),
+      ("excluded_path_fragment", &self.excluded_path_fragment),
+      (
+        "collection_asset_literal_prefix",
+        &self.collection_asset_literal_prefix,
+      ),
+      ("offline_site_root", &self.offline_site_root),
+      ("collections_dir_name", &self.collections_dir_name),
+      ("offline_bundle_root", &self.offline_bundle_root),
+      ("output_root", &self.output_root),
+      ("index_html_file", &self.index_html_file),
+      ("target_dir", &self.target_dir),
+      ("offline_manifest_json", &self.offline_manifest_json),
+      ("asset_extensions", &self.asset_extensions),
+      ("excluded_extensions", &self.excluded_extensions),
+      ("target_filesystem", &self.target_filesystem),
+      ("filesystem_check", &self.filesystem_check),
+      ("stylesheet_aliases", &self.stylesheet_aliases),
+      ("patch_mode", &self.patch_mode),
+      ("css_safelist", &self.css_safelist),
+      ("ignored_asset_schemes", &self.ignored_asset_schemes),
+      ("ignored_asset_paths", &self.ignored_asset_paths),
+      ("theme_colors", &self.theme_colors),
+      ("theme_dark_colors", &self.theme_dark_colors),
+      ("theme_logo", &self.theme_logo),
+      ("splash_text", &self.splash_text),
+      ("initial_route", &self.initial_route),
+      ("history_routes", &self.history_routes),
+      ("bundle_id", &self.bundle_id),
+      ("bundle_version", &self.bundle_version),
+      ("min_app_version", &self.min_app_version),
+    ]
+  }
+
+  fn fields_mut(&mut self) -> [(&'static str, &mut String); 34] {
+    [
+      ("package_name", &mut self.package_name),
+      ("collections_dir", &mut self.collections_dir),
+      ("collections_local_path", &mut self.collections_local_path),
+      ("entry_assets_dir", &mut self.entry_assets_dir),
+      ("entry_markdown_file", &mut self.entry_markdown_file),
+      (
+        "collection_metadata_file",
+        &mut self.collection_metadata_file,
+      ),
+      ("excluded_dir_name", &mut self.excluded_dir_name),
+      ("excluded_path_fragment", &mut self.excluded_path_fragment),
+      (
+        "collection_asset_literal_prefix",
+        &mut self.collection_asset_literal_prefix,
+      ),
+      ("offline_site_root", &mut self.offline_site_root),
+      ("collections_dir_name", &mut self.collections_dir_name),
+      ("offline_bundle_root", &mut self.offline_bundle_root),
+      ("output_root", &mut self.output_root),
+      ("index_html_file", &mut self.index_html_file),
+      ("target_dir", &mut self.target_dir),
+      ("offline_manifest_json", &mut self.offline_manifest_json),
+      ("asset_extensions", &mut self.asset_extensions),
+      ("excluded_extensions", &mut self.excluded_extensions),
+      ("target_filesystem", &mut self.target_filesystem),
+      ("filesystem_check", &mut self.filesystem_check),
+      ("stylesheet_aliases", &mut self.stylesheet_aliases),
+      ("patch_mode", &mut self.patch_mode),
+      ("css_safelist", &mut self.css_safelist),
+      ("ignored_asset_schemes", &mut self.ignored_asset_schemes),
+      ("ignored_asset_paths", &mut self.ignored_asset_paths),
+      ("theme_colors", &mut self.theme_colors),
+      ("theme_dark_colors", &mut self.theme_dark_colors),
+      ("theme_logo", &mut self.theme_logo),
+      ("splash_text", &mut self.splash_text),
+      ("initial_route", &mut self.initial_route),
+      ("history_routes", &mut self.history_routes),
+      ("bundle_id", &mut self.bundle_id),
+      ("bundle_version", &mut self.bundle_version),
+      ("min_app_version", &mut self.min_app_version),
+    ]
+  }
 }
 
+/// Overrides holding numbers or booleans rather than strings, and so missing from `fields()`.
+const TYPED_FIELDS: [&str; 5] = [
+  "scan_max_depth",
+  "stable_asset_names",
+  "force_initial_route",
+  "history_fallback",
+  "inline_image_kib",
+];
+
 impl CollectionConfigOverrides {
   /// Apply overrides that are valid for individual collection layouts.
   pub fn apply_to_layout(&self, layout: &mut OfflineProjectLayout) {
@@ -251,11 +1459,150 @@ impl CollectionConfigOverrides {
     if let Some(value) = &self.collection_asset_literal_prefix {
       layout.collection_asset_literal_prefix = value.clone();
     }
+    if let Some(depth) = self.scan_max_depth.filter(|depth| *depth > 0) {
+      layout.scan_max_depth = depth;
+    }
+    if let Some(value) = &self.asset_extensions {
+      layout.asset_extensions = split_extensions(value);
+    }
+    if let Some(value) = &self.excluded_extensions {
+      layout.excluded_extensions = split_extensions(value);
+    }
+  }
+
+  fn fields(&self) -> [(&'static str, &Option<String>); 34] {
+    [
+      ("package_name", &self.package_name),
+      ("collections_dir", &self.collections_dir),
+      ("collections_local_path", &self.collections_local_path),
+      ("entry_assets_dir", &self.entry_assets_dir),
+      ("entry_markdown_file", &self.entry_markdown_file),
+      ("collection_metadata_file", &self.collection_metadata_file),
+      ("excluded_dir_name", &self.excluded_dir_name),
+      ("excluded_path_fragment", &self.excluded_path_fragment),
+      (
+        "collection_asset_literal_prefix",
+        &self.collection_asset_literal_prefix,
+      ),
+      ("offline_site_root", &self.offline_site_root),
+      ("collections_dir_name", &self.collections_dir_name),
+      ("offline_bundle_root", &self.offline_bundle_root),
+      ("output_root", &self.output_root),
+      ("index_html_file", &self.index_html_file),
+      ("target_dir", &self.target_dir),
+      ("offline_manifest_json", &self.offline_manifest_json),
+      ("asset_extensions", &self.asset_extensions),
+      ("excluded_extensions", &self.excluded_extensions),
+      ("target_filesystem", &self.target_filesystem),
+      ("filesystem_check", &self.filesystem_check),
+      ("stylesheet_aliases", &self.stylesheet_aliases),
+      ("patch_mode", &self.patch_mode),
+      ("css_safelist", &self.css_safelist),
+      ("ignored_asset_schemes", &self.ignored_asset_schemes),
+      ("ignored_asset_paths", &self.ignored_asset_paths),
+      ("theme_colors", &self.theme_colors),
+      ("theme_dark_colors", &self.theme_dark_colors),
+      ("theme_logo", &self.theme_logo),
+      ("splash_text", &self.splash_text),
+      ("initial_route", &self.initial_route),
+      ("history_routes", &self.history_routes),
+      ("bundle_id", &self.bundle_id),
+      ("bundle_version", &self.bundle_version),
+      ("min_app_version", &self.min_app_version),
+    ]
+  }
+
+  /// Keys present in the source document that do not correspond to any override.
+  pub fn unknown_keys(&self) -> Vec<UnknownConfigKey> {
+    let known: Vec<String> = self
+      .fields()
+      .into_iter()
+      .map(|(field, _)| field)
+      .chain(TYPED_FIELDS)
+      .map(snake_to_camel_case)
+      .chain(["hooks".to_string(), "entryPoints".to_string()])
+      .collect();
+
+    self
+      .unknown
+      .keys()
+      .map(|key| UnknownConfigKey {
+        key: key.clone(),
+        suggestion: suggest_key(key, &known),
+      })
+      .collect()
+  }
+
+  /// Keys in the source document spelled with a legacy name from [`LEGACY_CONFIG_KEYS`].
+  pub fn legacy_keys(&self) -> &[LegacyConfigKey] {
+    &self.legacy
+  }
+
+  /// Keys in the source document whose values could not be parsed.
+  ///
+  /// Such keys are skipped while the remaining keys of the document still apply.
+  pub fn invalid_keys(&self) -> &[InvalidConfigKey] {
+    &self.invalid
+  }
+
+  /// Parse overrides from a configuration object, recording legacy and invalid keys.
+  fn from_value(value: Value) -> Self {
+    let Value::Object(mut object) = value else {
+      return Self {
+        invalid: vec![InvalidConfigKey {
+          key: String::new(),
+          reason: format!("expected an object of configuration keys, found {value}"),
+        }],
+        ..Self::default()
+      };
+    };
+    let legacy = LEGACY_CONFIG_KEYS
+      .iter()
+      .filter(|(key, _)| object.contains_key(*key))
+      .map(|(key, replacement)| LegacyConfigKey {
+        key: key.to_string(),
+        replacement: replacement.to_string(),
+      })
+      .collect();
+
+    let mut invalid = Vec::new();
+    let overrides = match serde_json::from_value(Value::Object(object.clone())) {
+      Ok(overrides) => overrides,
+      Err(_) => {
+        // Find the offending keys one by one so every other key still applies.
+        object.retain(|key, value| {
+          let single = Map::from_iter([(key.clone(), value.clone())]);
+          match serde_json::from_value::<Self>(Value::Object(single)) {
+            Ok(_) => true,
+            Err(err) => {
+              invalid.push(InvalidConfigKey {
+                key: key.clone(),
+                reason: err.to_string(),
+              });
+              false
+            }
+          }
+        });
+        serde_json::from_value(Value::Object(object)).unwrap_or_else(|err| {
+          invalid.push(InvalidConfigKey {
+            key: String::new(),
+            reason: err.to_string(),
+          });
+          Self::default()
+        })
+      }
+    };
+    Self {
+      legacy,
+      invalid,
+      ..overrides
+    }
   }
 
   /// Returns true when no overrides are specified.
   pub fn is_empty(&self) -> bool {
-    self.collections_dir.is_none()
+    self.package_name.is_none()
+      && self.collections_dir.is_none()
       && self.collections_local_path.is_none()
       && self.entry_assets_dir.is_none()
       && self.entry_markdown_file.is_none()
@@ -266,9 +1613,314 @@ impl CollectionConfigOverrides {
       && self.offline_site_root.is_none()
       && self.collections_dir_name.is_none()
       && self.offline_bundle_root.is_none()
+      && self.output_root.is_none()
       && self.index_html_file.is_none()
       && self.target_dir.is_none()
       && self.offline_manifest_json.is_none()
+      && self.scan_max_depth.is_none()
+      && self.asset_extensions.is_none()
+      && self.excluded_extensions.is_none()
+      && self.target_filesystem.is_none()
+      && self.filesystem_check.is_none()
+      && self.stylesheet_aliases.is_none()
+      && self.stable_asset_names.is_none()
+      && self.patch_mode.is_none()
+      && self.css_safelist.is_none()
+      && self.ignored_asset_schemes.is_none()
+      && self.ignored_asset_paths.is_none()
+      && self.theme_colors.is_none()
+      && self.theme_dark_colors.is_none()
+      && self.theme_logo.is_none()
+      && self.splash_text.is_none()
+      && self.initial_route.is_none()
+      && self.force_initial_route.is_none()
+      && self.history_fallback.is_none()
+      && self.history_routes.is_none()
+      && self.inline_image_kib.is_none()
+      && self.bundle_id.is_none()
+      && self.bundle_version.is_none()
+      && self.min_app_version.is_none()
+      && self.hooks.is_none()
+      && self.entry_points.is_none()
+  }
+}
+
+/// `output_root` as a path, or `None` when it is empty.
+fn output_root(value: &str) -> Option<PathBuf> {
+  let value = value.trim();
+  (!value.is_empty()).then(|| PathBuf::from(value))
+}
+
+/// Normalised extensions from a comma-separated list such as `.PNG, jpg`.
+fn split_extensions(value: &str) -> Vec<String> {
+  normalize_extensions(value.split(','))
+}
+
+/// Collect configuration overrides from `OFFLINE_BUNDLER_*` variables using `lookup`.
+///
+/// Empty values are treated as unset.
+fn env_overrides<F>(lookup: &F) -> CollectionConfigOverrides
+where
+  F: Fn(&str) -> Option<String>,
+{
+  let var = |field: &str| {
+    lookup(&format!("{ENV_PREFIX}{}", field.to_uppercase())).filter(|value| !value.is_empty())
+  };
+  let mut invalid = Vec::new();
+
+  CollectionConfigOverrides {
+    package_name: var("package_name"),
+    collections_dir: var("collections_dir"),
+    collections_local_path: var("collections_local_path"),
+    entry_assets_dir: var("entry_assets_dir"),
+    entry_markdown_file: var("entry_markdown_file"),
+    collection_metadata_file: var("collection_metadata_file"),
+    excluded_dir_name: var("excluded_dir_name"),
+    excluded_path_fragment: var("excluded_path_fragment"),
+    collection_asset_literal_prefix: var("collection_asset_literal_prefix"),
+    offline_site_root: var("offline_site_root"),
+    collections_dir_name: var("collections_dir_name"),
+    offline_bundle_root: var("offline_bundle_root"),
+    output_root: var("output_root"),
+    index_html_file: var("index_html_file"),
+    target_dir: var("target_dir"),
+    offline_manifest_json: var("offline_manifest_json"),
+    scan_max_depth: parse_env_value("scan_max_depth", var("scan_max_depth"), &mut invalid),
+    asset_extensions: var("asset_extensions"),
+    excluded_extensions: var("excluded_extensions"),
+    target_filesystem: var("target_filesystem"),
+    filesystem_check: var("filesystem_check"),
+    stylesheet_aliases: var("stylesheet_aliases"),
+    stable_asset_names: parse_env_value(
+      "stable_asset_names",
+      var("stable_asset_names"),
+      &mut invalid,
+    ),
+    patch_mode: var("patch_mode"),
+    css_safelist: var("css_safelist"),
+    ignored_asset_schemes: var("ignored_asset_schemes"),
+    ignored_asset_paths: var("ignored_asset_paths"),
+    theme_colors: var("theme_colors"),
+    theme_dark_colors: var("theme_dark_colors"),
+    theme_logo: var("theme_logo"),
+    splash_text: var("splash_text"),
+    initial_route: var("initial_route"),
+    force_initial_route: parse_env_value(
+      "force_initial_route",
+      var("force_initial_route"),
+      &mut invalid,
+    ),
+    history_fallback: parse_env_value("history_fallback", var("history_fallback"), &mut invalid),
+    history_routes: var("history_routes"),
+    inline_image_kib: parse_env_value("inline_image_kib", var("inline_image_kib"), &mut invalid),
+    bundle_id: var("bundle_id"),
+    bundle_version: var("bundle_version"),
+    min_app_version: var("min_app_version"),
+    hooks: None,
+    entry_points: None,
+    unknown: BTreeMap::new(),
+    legacy: Vec::new(),
+    invalid,
+  }
+}
+
+/// Parse the environment value of `field`, recording a value that does not parse.
+fn parse_env_value<T>(
+  field: &str,
+  value: Option<String>,
+  invalid: &mut Vec<InvalidConfigKey>,
+) -> Option<T>
+where
+  T: FromStr,
+  T::Err: fmt::Display,
+{
+  let value = value?;
+  value
+    .trim()
+    .parse()
+    .map_err(|err| {
+      invalid.push(InvalidConfigKey {
+        key: format!("{ENV_PREFIX}{}", field.to_uppercase()),
+        reason: format!("invalid value {value:?}: {err}"),
+      })
+    })
+    .ok()
+}
+
+/// Deserialize a typed override written either as its JSON value or as a string, e.g. `4` or
+/// `"4"`, so values in the string form used by environment variables keep working.
+fn lenient<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+  D: Deserializer<'de>,
+  T: DeserializeOwned + FromStr,
+  T::Err: fmt::Display,
+{
+  match Value::deserialize(deserializer)? {
+    Value::Null => Ok(None),
+    Value::String(text) => text
+      .trim()
+      .parse()
+      .map(Some)
+      .map_err(|err| D::Error::custom(format!("invalid value {text:?}: {err}"))),
+    value => T::deserialize(value).map(Some).map_err(D::Error::custom),
+  }
+}
+
+/// Read a dedicated [`CONFIG_FILE`] document, ignoring missing or malformed files.
+fn load_config_file(path: &Path) -> Option<CollectionConfigOverrides> {
+  let content = fs::read_to_string(path).ok()?;
+  let value = serde_json::from_str::<Value>(&content).ok()?;
+  let overrides = CollectionConfigOverrides::from_value(value);
+  report_unknown_keys(path, &overrides);
+  Some(overrides).filter(|overrides| !overrides.is_empty())
+}
+
+/// Emit a cargo warning for every unrecognised, invalid or legacy configuration key.
+fn report_unknown_keys(path: &Path, overrides: &CollectionConfigOverrides) {
+  for unknown in overrides.unknown_keys() {
+    println!("cargo:warning={} in {}", unknown, path.display());
+  }
+  for invalid in overrides.invalid_keys() {
+    println!("cargo:warning={} in {}", invalid, path.display());
+  }
+  for legacy in overrides.legacy_keys() {
+    println!("cargo:warning={} in {}", legacy, path.display());
+  }
+}
+
+fn push_legacy_keys(
+  names: &mut Vec<LegacyName>,
+  path: &Path,
+  overrides: &CollectionConfigOverrides,
+) {
+  names.extend(overrides.legacy_keys().iter().map(|key| LegacyName::Key {
+    path: path.to_path_buf(),
+    key: key.clone(),
+  }));
+}
+
+fn snake_to_camel_case(value: &str) -> String {
+  let mut result = String::with_capacity(value.len());
+  let mut upper_next = false;
+  for ch in value.chars() {
+    if ch == '_' {
+      upper_next = true;
+    } else if upper_next {
+      result.push(ch.to_ascii_uppercase());
+      upper_next = false;
+    } else {
+      result.push(ch);
+    }
+  }
+  result
+}
+
+/// Pick the known key closest to `key` when it is within a plausible typo distance.
+fn suggest_key(key: &str, known: &[String]) -> Option<String> {
+  let lowered = key.to_lowercase();
+  known
+    .iter()
+    .map(|candidate| {
+      (
+        edit_distance(&lowered, &candidate.to_lowercase()),
+        candidate,
+      )
+    })
+    .filter(|(distance, candidate)| *distance <= (candidate.len() / 3).max(2))
+    .min_by_key(|(distance, _)| *distance)
+    .map(|(_, candidate)| candidate.clone())
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+  let b_chars: Vec<char> = b.chars().collect();
+  let mut previous: Vec<usize> = (0..=b_chars.len()).collect();
+
+  for (i, a_char) in a.chars().enumerate() {
+    let mut current = vec![i + 1];
+    for (j, b_char) in b_chars.iter().enumerate() {
+      let substitution = previous[j] + usize::from(a_char != *b_char);
+      current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+    }
+    previous = current;
+  }
+
+  previous[b_chars.len()]
+}
+
+impl fmt::Display for LegacyConfigKey {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "legacy configuration key `{}` should be renamed to `{}`",
+      self.key, self.replacement
+    )
+  }
+}
+
+impl fmt::Display for LegacyName {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Key { path, key } => write!(
+        f,
+        "{}: rename key `{}` to `{}`",
+        path.display(),
+        key.key,
+        key.replacement
+      ),
+      Self::MetadataFile { path, replacement } => write!(
+        f,
+        "{}: rename file to {}",
+        path.display(),
+        replacement.display()
+      ),
+    }
+  }
+}
+
+impl fmt::Display for InvalidConfigKey {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if self.key.is_empty() {
+      write!(f, "invalid configuration: {}", self.reason)
+    } else {
+      write!(
+        f,
+        "ignoring configuration key `{}`: {}",
+        self.key, self.reason
+      )
+    }
+  }
+}
+
+impl fmt::Display for UnknownConfigKey {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "unknown configuration key `{}`", self.key)?;
+    if let Some(suggestion) = &self.suggestion {
+      write!(f, " (did you mean `{suggestion}`?)")?;
+    }
+    Ok(())
+  }
+}
+
+/// Locate the Cargo workspace root containing `manifest_dir`, excluding the crate itself.
+fn find_workspace_root(manifest_dir: &Path) -> Option<PathBuf> {
+  manifest_dir.ancestors().skip(1).find_map(|dir| {
+    let manifest = fs::read_to_string(dir.join("Cargo.toml")).ok()?;
+    manifest
+      .lines()
+      .any(|line| line.trim() == "[workspace]")
+      .then(|| dir.to_path_buf())
+  })
+}
+
+impl fmt::Display for ConfigSource {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Default => write!(f, "default"),
+      Self::WorkspaceFile(path) => write!(f, "workspace config {}", path.display()),
+      Self::ManifestFile(path) => write!(f, "crate config {}", path.display()),
+      Self::CollectionMetadata(path) => write!(f, "collection metadata {}", path.display()),
+      Self::Environment(name) => write!(f, "environment variable {name}"),
+    }
   }
 }
 
@@ -290,16 +1942,20 @@ where
 }
 
 /// Read a collection document returning the payload and any embedded overrides.
+///
+/// Unrecognised and invalid keys inside the `config` object are reported as cargo warnings.
 pub fn load_document(path: &Path) -> Option<(Value, CollectionConfigOverrides)> {
   let content = fs::read_to_string(path).ok()?;
-  split_document(&content)
+  let (value, overrides) = split_document(&content)?;
+  report_unknown_keys(path, &overrides);
+  Some((value, overrides))
 }
 
 fn split_document(content: &str) -> Option<(Value, CollectionConfigOverrides)> {
   let mut value: Value = serde_json::from_str(content).ok()?;
   let overrides = if let Some(object) = value.as_object_mut() {
     match object.remove("config") {
-      Some(config_value) => serde_json::from_value(config_value).unwrap_or_default(),
+      Some(config_value) => CollectionConfigOverrides::from_value(config_value),
       None => CollectionConfigOverrides::default(),
     }
   } else {
@@ -308,3 +1964,497 @@ fn split_document(content: &str) -> Option<(Value, CollectionConfigOverrides)> {
 
   Some((value, overrides))
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::collections::BTreeMap;
+  use tempfile::tempdir;
+
+  fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+    let vars: BTreeMap<String, String> = vars
+      .iter()
+      .map(|(key, value)| (key.to_string(), value.to_string()))
+      .collect();
+    move |name| vars.get(name).cloned()
+  }
+
+  #[test]
+  fn environment_overrides_take_precedence_over_files() {
+    let dir = tempdir().unwrap();
+    let output_root = dir.path().join("cache").to_string_lossy().into_owned();
+    let collections_dir = dir.path().join("content");
+    fs::create_dir_all(&collections_dir).unwrap();
+    fs::write(
+      collections_dir.join("collection.json"),
+      r#"{"config": {"offlineBundleRoot": "from-file", "entryAssetsDir": "media"}}"#,
+    )
+    .unwrap();
+
+    let config = ProjectConfig::discover_with_env(
+      dir.path(),
+      env(&[
+        ("OFFLINE_BUNDLER_COLLECTIONS_DIR", "content"),
+        ("OFFLINE_BUNDLER_OFFLINE_BUNDLE_ROOT", "/tmp/ci-output"),
+        ("OFFLINE_BUNDLER_OUTPUT_ROOT", &output_root),
+        ("OFFLINE_BUNDLER_TARGET_DIR", ""),
+      ]),
+    );
+
+    assert_eq!(config.collections_dir, "content");
+    assert_eq!(config.offline_bundle_root, "/tmp/ci-output");
+    assert_eq!(
+      config.to_layout().bundle_root(dir.path()),
+      Path::new(&output_root)
+    );
+    assert_eq!(config.entry_assets_dir, "media");
+    assert_eq!(config.target_dir, "target");
+  }
+
+  #[test]
+  fn legacy_names_are_accepted_and_reported() {
+    let dir = tempdir().unwrap();
+    fs::write(
+      dir.path().join(CONFIG_FILE),
+      r#"{"programsDir": "programs"}"#,
+    )
+    .unwrap();
+    let program_dir = dir.path().join("programs/P001");
+    fs::create_dir_all(&program_dir).unwrap();
+    fs::write(
+      dir.path().join("programs/program.json"),
+      r#"{"config": {"moduleAssetsDir": "media", "programAssetLiteralPrefix": "/content/programs"}}"#,
+    )
+    .unwrap();
+    fs::write(program_dir.join("program.json"), r#"{"title": "Intro"}"#).unwrap();
+
+    let config = ProjectConfig::discover_with_env(dir.path(), env(&[]));
+
+    assert_eq!(config.collections_dir, "programs");
+    assert_eq!(config.entry_assets_dir, "media");
+    assert_eq!(config.collection_asset_literal_prefix, "/content/programs");
+    let names: Vec<String> = config
+      .legacy_names(dir.path())
+      .iter()
+      .map(|name| {
+        name
+          .to_string()
+          .replace(&dir.path().display().to_string(), "")
+      })
+      .collect();
+    assert_eq!(names, [
+      "/offline_bundler.json: rename key `programsDir` to `collectionsDir`",
+      "/programs/program.json: rename file to /programs/collection.json",
+      "/programs/program.json: rename key `moduleAssetsDir` to `entryAssetsDir`",
+      "/programs/program.json: rename key `programAssetLiteralPrefix` to `collectionAssetLiteralPrefix`",
+      "/programs/P001/program.json: rename file to /programs/P001/collection.json",
+    ]);
+  }
+
+  #[test]
+  fn hook_lists_merge_per_layer_and_ignore_collection_metadata() {
+    let dir = tempdir().unwrap();
+    let workspace = dir.path();
+    let crate_dir = workspace.join("app");
+    let collections_dir = crate_dir.join("content");
+    fs::create_dir_all(&collections_dir).unwrap();
+    fs::write(
+      workspace.join("Cargo.toml"),
+      "[workspace]\nmembers = [\"app\"]\n",
+    )
+    .unwrap();
+    fs::write(
+      workspace.join(CONFIG_FILE),
+      r#"{"collectionsDir": "content", "hooks": {
+        "preBuild": [["cargo", "run", "--bin", "lint"]],
+        "postBundle": [["sign", "--all"]]
+      }}"#,
+    )
+    .unwrap();
+    fs::write(
+      crate_dir.join(CONFIG_FILE),
+      r#"{"hooks": {"postBundle": [["sign", "--app"]]}}"#,
+    )
+    .unwrap();
+    fs::write(
+      collections_dir.join("collection.json"),
+      r#"{"config": {"hooks": {"preBuild": [["curl", "evil.sh"]], "postBundle": [["notarize"]]}}}"#,
+    )
+    .unwrap();
+
+    let config = ProjectConfig::discover_with_env(&crate_dir, env(&[]));
+
+    assert_eq!(config.hooks.pre_build, [vec![
+      "cargo", "run", "--bin", "lint"
+    ]]);
+    assert_eq!(config.hooks.post_bundle, [vec!["sign", "--app"]]);
+    assert!(
+      ProjectConfig::builder()
+        .hooks(BuildHooks {
+          pre_build: vec![Vec::new()],
+          ..Default::default()
+        })
+        .build()
+        .is_err()
+    );
+  }
+
+  #[test]
+  fn entry_points_are_loaded_and_validated() {
+    let dir = tempdir().unwrap();
+    fs::write(
+      dir.path().join(CONFIG_FILE),
+      r#"{"entryPoints": [
+        {"file": "kiosk.html", "fullscreen": true, "idleResetSecs": 120},
+        {"file": "review.html", "flags": ["showDrafts"]}
+      ]}"#,
+    )
+    .unwrap();
+
+    let config = ProjectConfig::discover_with_env(dir.path(), env(&[]));
+    assert_eq!(config.entry_points.len(), 2);
+    assert_eq!(config.entry_points[0].idle_reset_secs, 120);
+    assert_eq!(config.entry_points[1].flags, ["showDrafts"]);
+    assert!(config.validate().is_ok());
+
+    for file in ["index.html", "kiosk/index.html", "kiosk"] {
+      let error = ProjectConfig::builder()
+        .entry_point(EntryPoint {
+          file: file.into(),
+          ..Default::default()
+        })
+        .build()
+        .unwrap_err();
+      assert!(matches!(error, ConfigValidationError::Invalid {
+        field: "entry_points",
+        ..
+      }));
+    }
+  }
+
+  #[test]
+  fn merges_layers_and_explains_sources() {
+    let dir = tempdir().unwrap();
+    let workspace = dir.path();
+    let crate_dir = workspace.join("app");
+    let collections_dir = crate_dir.join("content");
+    fs::create_dir_all(&collections_dir).unwrap();
+    fs::write(
+      workspace.join("Cargo.toml"),
+      "[workspace]\nmembers = [\"app\"]\n",
+    )
+    .unwrap();
+    fs::write(
+      workspace.join(CONFIG_FILE),
+      r#"{"collectionsDir": "content", "offlineSiteRoot": "www", "targetDir": "../target"}"#,
+    )
+    .unwrap();
+    fs::write(
+      crate_dir.join(CONFIG_FILE),
+      r#"{"offlineSiteRoot": "site/app"}"#,
+    )
+    .unwrap();
+    fs::write(
+      collections_dir.join("collection.json"),
+      r#"{"config": {"entryAssetsDir": "media"}}"#,
+    )
+    .unwrap();
+
+    let config = ProjectConfig::discover_with_env(&crate_dir, env(&[]));
+    let explanation = config.explain();
+    let source_of = |field: &str| {
+      explanation
+        .iter()
+        .find(|item| item.field == field)
+        .map(|item| item.source.clone())
+        .unwrap()
+    };
+
+    assert_eq!(config.target_dir, "../target");
+    assert_eq!(config.offline_site_root, "site/app");
+    assert_eq!(config.entry_assets_dir, "media");
+    assert_eq!(
+      source_of("target_dir"),
+      ConfigSource::WorkspaceFile(workspace.join(CONFIG_FILE))
+    );
+    assert_eq!(
+      source_of("offline_site_root"),
+      ConfigSource::ManifestFile(crate_dir.join(CONFIG_FILE))
+    );
+    assert_eq!(
+      source_of("entry_assets_dir"),
+      ConfigSource::CollectionMetadata(collections_dir.join("collection.json"))
+    );
+    assert_eq!(source_of("index_html_file"), ConfigSource::Default);
+  }
+
+  #[test]
+  fn builder_applies_values_and_validates() {
+    let config = ProjectConfig::builder()
+      .collections_dir("content")
+      .offline_site_root("")
+      .build()
+      .unwrap();
+    assert_eq!(config.collections_dir, "content");
+    assert_eq!(config.entry_markdown_file, "index.md");
+
+    let error = ProjectConfig::builder()
+      .collection_asset_literal_prefix("content/programs")
+      .build()
+      .unwrap_err();
+    assert!(matches!(error, ConfigValidationError::Invalid {
+      field: "collection_asset_literal_prefix",
+      ..
+    }));
+
+    let error = ProjectConfig::builder()
+      .entry_markdown_file(" ")
+      .build()
+      .unwrap_err();
+    assert_eq!(error, ConfigValidationError::Missing {
+      field: "entry_markdown_file"
+    });
+
+    let config = ProjectConfig::builder()
+      .target_filesystem("FAT32")
+      .filesystem_check("error")
+      .build()
+      .unwrap();
+    assert_eq!(
+      config.filesystem_profile().unwrap(),
+      Some(FilesystemProfile {
+        filesystem: crate::filesystem::TargetFilesystem::Fat32,
+        mode: crate::filesystem::FilesystemCheckMode::Error,
+      })
+    );
+
+    let error = ProjectConfig::builder()
+      .target_filesystem("ntfs")
+      .build()
+      .unwrap_err();
+    assert!(matches!(error, ConfigValidationError::Invalid {
+      field: "target_filesystem",
+      ..
+    }));
+
+    let config = ProjectConfig::builder()
+      .stylesheet_aliases("tailwind=tailwind.css, main = main.css")
+      .build()
+      .unwrap();
+    assert_eq!(config.stylesheet_aliases().unwrap(), vec![
+      ("tailwind".to_string(), "tailwind.css".to_string()),
+      ("main".to_string(), "main.css".to_string()),
+    ]);
+
+    let config = ProjectConfig::builder()
+      .css_safelist(".prose, btn-*")
+      .build()
+      .unwrap();
+    assert_eq!(config.css_safelist(), vec!["prose", "btn-*"]);
+    assert_eq!(config.theme_palette().unwrap(), None);
+
+    let config = ProjectConfig::builder()
+      .initial_route("#/programs/intro")
+      .force_initial_route(true)
+      .build()
+      .unwrap();
+    assert_eq!(config.initial_route().unwrap(), Some("#/programs/intro"));
+    assert!(config.force_initial_route);
+    let error = ProjectConfig::builder()
+      .initial_route("/programs/intro")
+      .build()
+      .unwrap_err();
+    assert!(matches!(error, ConfigValidationError::Invalid {
+      field: "initial_route",
+      ..
+    }));
+
+    let config = ProjectConfig::builder()
+      .inline_image_kib(4)
+      .build()
+      .unwrap();
+    assert_eq!(config.inline_image_limit().unwrap(), Some(4096));
+    assert!(
+      ProjectConfig::builder()
+        .inline_image_kib(u64::MAX)
+        .build()
+        .is_err()
+    );
+
+    let filter = ProjectConfig::builder()
+      .ignored_asset_schemes("tel, geo:, route")
+      .ignored_asset_paths("drafts/**")
+      .build()
+      .unwrap()
+      .asset_reference_filter();
+    assert_eq!(filter.schemes(), ["tel", "geo", "route"]);
+    assert!(filter.is_ignored("route:/lessons/2"));
+    assert!(filter.is_ignored("drafts/cover.png"));
+
+    let config = ProjectConfig::builder()
+      .theme_colors("brand=#0a84ff")
+      .theme_logo("logo.svg")
+      .build()
+      .unwrap();
+    let palette = config.theme_palette().unwrap().unwrap();
+    assert_eq!(palette.colors, vec![("brand".into(), "#0a84ff".into())]);
+    assert_eq!(palette.logo.as_deref(), Some("logo.svg"));
+    assert!(
+      ProjectConfig::builder()
+        .theme_dark_colors("brand")
+        .build()
+        .is_err()
+    );
+
+    let error = ProjectConfig::builder()
+      .stylesheet_aliases("main=css/main.css")
+      .build()
+      .unwrap_err();
+    assert!(matches!(error, ConfigValidationError::Invalid {
+      field: "stylesheet_aliases",
+      ..
+    }));
+  }
+
+  #[test]
+  fn config_file_with_only_package_name_applies() {
+    let dir = tempdir().unwrap();
+    fs::write(dir.path().join(CONFIG_FILE), r#"{"packageName": "atlas"}"#).unwrap();
+
+    let config = ProjectConfig::discover_with_env(dir.path(), env(&[]));
+    assert_eq!(config.package_name, "atlas");
+    assert_eq!(
+      config
+        .explain()
+        .into_iter()
+        .find(|item| item.field == "package_name")
+        .unwrap()
+        .source,
+      ConfigSource::ManifestFile(dir.path().join(CONFIG_FILE))
+    );
+  }
+
+  #[test]
+  fn selection_file_sits_next_to_collections_archives() {
+    let dir = tempdir().unwrap();
+    let mut config = ProjectConfig {
+      collections_dir: "content/programs.zip".into(),
+      ..ProjectConfig::default()
+    };
+    fs::create_dir_all(dir.path().join("content")).unwrap();
+    fs::write(dir.path().join("content/programs.zip"), "").unwrap();
+    assert_eq!(
+      config.collections_local_file(dir.path()),
+      dir
+        .path()
+        .join("content")
+        .join(&config.collections_local_path)
+    );
+
+    config.collections_dir = "content".into();
+    assert_eq!(
+      config.collections_local_file(dir.path()),
+      dir
+        .path()
+        .join("content")
+        .join(&config.collections_local_path)
+    );
+  }
+
+  #[test]
+  fn invalid_values_skip_only_their_key() {
+    let dir = tempdir().unwrap();
+    fs::write(
+      dir.path().join(CONFIG_FILE),
+      r#"{"collectionsDir": "content", "targetDir": 5, "offlineSiteRoot": "www"}"#,
+    )
+    .unwrap();
+
+    let config = ProjectConfig::discover_with_env(dir.path(), env(&[]));
+    assert_eq!(config.collections_dir, "content");
+    assert_eq!(config.offline_site_root, "www");
+    assert_eq!(config.target_dir, "target");
+
+    let overrides = CollectionConfigOverrides::from_value(serde_json::json!({
+      "targetDir": 5,
+      "entryAssetsDir": "media",
+    }));
+    assert_eq!(overrides.entry_assets_dir.as_deref(), Some("media"));
+    assert_eq!(overrides.invalid_keys().len(), 1);
+    assert_eq!(
+      overrides.invalid_keys()[0].to_string(),
+      "ignoring configuration key `targetDir`: invalid type: integer `5`, expected a string"
+    );
+    assert_eq!(
+      CollectionConfigOverrides::from_value(serde_json::json!([]))
+        .invalid_keys()
+        .len(),
+      1
+    );
+  }
+
+  #[test]
+  fn typed_values_accept_json_and_string_forms() {
+    let dir = tempdir().unwrap();
+    fs::write(
+      dir.path().join(CONFIG_FILE),
+      r#"{"collectionsDir": "content", "scanMaxDepth": 4, "stableAssetNames": true,
+        "forceInitialRoute": "true", "historyFallback": true,
+        "inlineImageKib": 8}"#,
+    )
+    .unwrap();
+    let config = ProjectConfig::discover_with_env(dir.path(), env(&[]));
+    assert_eq!(config.collections_dir, "content");
+    assert_eq!(config.scan_max_depth().unwrap(), 4);
+    assert_eq!(config.to_layout().scan_max_depth, 4);
+    assert!(config.stable_asset_names);
+    assert!(config.force_initial_route);
+    assert!(config.history_fallback);
+    assert_eq!(config.inline_image_limit().unwrap(), Some(8192));
+
+    let config =
+      ProjectConfig::discover_with_env(dir.path(), env(&[("OFFLINE_BUNDLER_SCAN_MAX_DEPTH", "6")]));
+    assert_eq!(config.scan_max_depth, 6);
+    let config = ProjectConfig::discover_with_env(
+      dir.path(),
+      env(&[("OFFLINE_BUNDLER_SCAN_MAX_DEPTH", "deep")]),
+    );
+    assert_eq!(config.scan_max_depth, 4);
+
+    let overrides = CollectionConfigOverrides::from_value(serde_json::json!({
+      "scanMaxDepth": " 8 ",
+    }));
+    assert_eq!(overrides.scan_max_depth, Some(8));
+    let overrides = CollectionConfigOverrides::from_value(serde_json::json!({
+      "scanMaxDepth": "deep",
+    }));
+    assert_eq!(overrides.scan_max_depth, None);
+    assert_eq!(overrides.invalid_keys()[0].key, "scanMaxDepth");
+    assert!(ProjectConfig::builder().scan_max_depth(0).build().is_err());
+  }
+
+  #[test]
+  fn reports_unknown_keys_with_suggestions() {
+    let (_, overrides) = split_document(
+      r#"{"config": {"entryAssetDir": "media", "entryMarkdownFile": "README.md", "colour": "red"}}"#,
+    )
+    .unwrap();
+
+    assert_eq!(overrides.entry_markdown_file.as_deref(), Some("README.md"));
+    assert!(overrides.entry_assets_dir.is_none());
+    assert_eq!(overrides.unknown_keys(), vec![
+      UnknownConfigKey {
+        key: "colour".into(),
+        suggestion: None,
+      },
+      UnknownConfigKey {
+        key: "entryAssetDir".into(),
+        suggestion: Some("entryAssetsDir".into()),
+      },
+    ]);
+    assert_eq!(
+      overrides.unknown_keys()[1].to_string(),
+      "unknown configuration key `entryAssetDir` (did you mean `entryAssetsDir`?)"
+    );
+  }
+}
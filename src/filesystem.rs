@@ -0,0 +1,300 @@
+//! Compatibility checks for the removable media offline bundles are copied to.
+//!
+//! USB sticks are commonly formatted FAT32 or exFAT. Both reject characters that are valid on
+//! Linux and macOS, FAT32 cannot store files of 4 GiB or more, and copying tools silently
+//! truncate or skip offending files instead of failing.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+/// File system the offline bundle will be copied to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetFilesystem {
+  /// FAT32, limited to files smaller than 4 GiB.
+  Fat32,
+  /// exFAT, which lifts the file size limit but keeps FAT naming rules.
+  ExFat,
+}
+
+/// Whether compatibility problems are reported as warnings or fail the build.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FilesystemCheckMode {
+  /// Report problems as cargo warnings.
+  #[default]
+  Warn,
+  /// Fail the build when any problem is found.
+  Error,
+}
+
+/// Target file system together with how strictly it is enforced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FilesystemProfile {
+  /// File system the bundle must be compatible with.
+  pub filesystem: TargetFilesystem,
+  /// How detected problems are surfaced.
+  pub mode: FilesystemCheckMode,
+}
+
+/// Problem found while checking a bundle path against a target file system.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilesystemIssue {
+  /// Bundle-relative path of the offending file.
+  pub path: String,
+  /// Description of the incompatibility.
+  pub kind: FilesystemIssueKind,
+}
+
+/// Kinds of incompatibilities detected by [`check_bundle_file`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilesystemIssueKind {
+  /// The file is larger than the file system can store.
+  FileTooLarge {
+    /// Size of the file in bytes.
+    size: u64,
+    /// Largest size the file system supports.
+    limit: u64,
+  },
+  /// The full path is longer than the supported maximum.
+  PathTooLong {
+    /// Path length in UTF-16 code units.
+    length: usize,
+    /// Maximum supported length.
+    limit: usize,
+  },
+  /// A single path segment is longer than the supported maximum.
+  NameTooLong {
+    /// Offending path segment.
+    name: String,
+  },
+  /// A path segment contains a character the file system rejects.
+  InvalidCharacter {
+    /// Offending path segment.
+    name: String,
+    /// First rejected character.
+    character: char,
+  },
+  /// A path segment is a reserved device name or ends in a dot or space.
+  ReservedName {
+    /// Offending path segment.
+    name: String,
+  },
+}
+
+/// Largest file FAT32 can store (4 GiB minus one byte).
+pub const FAT32_MAX_FILE_SIZE: u64 = u32::MAX as u64;
+
+/// Longest path accepted by the common Windows tooling used to read FAT media.
+pub const MAX_PATH_LENGTH: usize = 260;
+
+/// Longest single file or directory name on FAT32 and exFAT.
+pub const MAX_NAME_LENGTH: usize = 255;
+
+const RESERVED_NAMES: [&str; 22] = [
+  "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+  "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+impl TargetFilesystem {
+  /// Largest file size the file system supports, if it has a practical limit.
+  pub fn max_file_size(self) -> Option<u64> {
+    match self {
+      Self::Fat32 => Some(FAT32_MAX_FILE_SIZE),
+      Self::ExFat => None,
+    }
+  }
+}
+
+impl FromStr for TargetFilesystem {
+  type Err = ();
+
+  fn from_str(value: &str) -> Result<Self, Self::Err> {
+    match value.trim().to_ascii_lowercase().as_str() {
+      "fat32" | "vfat" => Ok(Self::Fat32),
+      "exfat" => Ok(Self::ExFat),
+      _ => Err(()),
+    }
+  }
+}
+
+impl FromStr for FilesystemCheckMode {
+  type Err = ();
+
+  fn from_str(value: &str) -> Result<Self, Self::Err> {
+    match value.trim().to_ascii_lowercase().as_str() {
+      "warn" => Ok(Self::Warn),
+      "error" => Ok(Self::Error),
+      _ => Err(()),
+    }
+  }
+}
+
+impl fmt::Display for TargetFilesystem {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Fat32 => write!(f, "FAT32"),
+      Self::ExFat => write!(f, "exFAT"),
+    }
+  }
+}
+
+impl fmt::Display for FilesystemIssue {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match &self.kind {
+      FilesystemIssueKind::FileTooLarge { size, limit } => write!(
+        f,
+        "{} is {size} bytes, larger than the {limit} byte limit",
+        self.path
+      ),
+      FilesystemIssueKind::PathTooLong { length, limit } => write!(
+        f,
+        "{} is {length} characters long, longer than the {limit} character limit",
+        self.path
+      ),
+      FilesystemIssueKind::NameTooLong { name } => write!(
+        f,
+        "{}: name `{name}` is longer than {MAX_NAME_LENGTH} characters",
+        self.path
+      ),
+      FilesystemIssueKind::InvalidCharacter { name, character } => write!(
+        f,
+        "{}: name `{name}` contains invalid character {character:?}",
+        self.path
+      ),
+      FilesystemIssueKind::ReservedName { name } => {
+        write!(f, "{}: name `{name}` is reserved", self.path)
+      }
+    }
+  }
+}
+
+/// Check a single bundle-relative path (using `/` separators) and its size.
+pub fn check_bundle_file(
+  filesystem: TargetFilesystem,
+  path: &str,
+  size: u64,
+) -> Vec<FilesystemIssue> {
+  let mut kinds = Vec::new();
+
+  if let Some(limit) = filesystem.max_file_size()
+    && size > limit
+  {
+    kinds.push(FilesystemIssueKind::FileTooLarge { size, limit });
+  }
+
+  let length = path.encode_utf16().count();
+  if length > MAX_PATH_LENGTH {
+    kinds.push(FilesystemIssueKind::PathTooLong {
+      length,
+      limit: MAX_PATH_LENGTH,
+    });
+  }
+
+  for name in path.split('/').filter(|segment| !segment.is_empty()) {
+    if name.encode_utf16().count() > MAX_NAME_LENGTH {
+      kinds.push(FilesystemIssueKind::NameTooLong { name: name.into() });
+    }
+    if let Some(character) = name
+      .chars()
+      .find(|c| c.is_control() || "\"*:<>?\\|".contains(*c))
+    {
+      kinds.push(FilesystemIssueKind::InvalidCharacter {
+        name: name.into(),
+        character,
+      });
+    }
+    let stem = name.split('.').next().unwrap_or(name);
+    if name.ends_with(['.', ' '])
+      || RESERVED_NAMES
+        .iter()
+        .any(|reserved| stem.eq_ignore_ascii_case(reserved))
+    {
+      kinds.push(FilesystemIssueKind::ReservedName { name: name.into() });
+    }
+  }
+
+  kinds
+    .into_iter()
+    .map(|kind| FilesystemIssue {
+      path: path.into(),
+      kind,
+    })
+    .collect()
+}
+
+/// Check every file below `root`, reporting paths relative to it.
+pub fn check_directory(
+  filesystem: TargetFilesystem,
+  root: &Path,
+) -> io::Result<Vec<FilesystemIssue>> {
+  let mut issues = Vec::new();
+  let mut pending = vec![root.to_path_buf()];
+
+  while let Some(dir) = pending.pop() {
+    for entry in fs::read_dir(&dir)? {
+      let entry = entry?;
+      let path = entry.path();
+      let metadata = entry.metadata()?;
+      if metadata.is_dir() {
+        pending.push(path);
+        continue;
+      }
+      let relative = path
+        .strip_prefix(root)
+        .unwrap_or(&path)
+        .to_string_lossy()
+        .replace('\\', "/");
+      issues.extend(check_bundle_file(filesystem, &relative, metadata.len()));
+    }
+  }
+
+  issues.sort_by(|a, b| a.path.cmp(&b.path));
+  Ok(issues)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn flags_oversized_files_only_on_fat32() {
+    let size = FAT32_MAX_FILE_SIZE + 1;
+    assert_eq!(
+      check_bundle_file(TargetFilesystem::Fat32, "programs/P001/video.mp4", size).len(),
+      1
+    );
+    assert!(check_bundle_file(TargetFilesystem::ExFat, "programs/P001/video.mp4", size).is_empty());
+  }
+
+  #[test]
+  fn flags_invalid_and_reserved_names() {
+    let issues = check_bundle_file(TargetFilesystem::ExFat, "programs/P001/what?.png", 1);
+    assert!(matches!(
+      issues[0].kind,
+      FilesystemIssueKind::InvalidCharacter { character: '?', .. }
+    ));
+
+    let issues = check_bundle_file(TargetFilesystem::Fat32, "programs/aux.txt", 1);
+    assert!(matches!(
+      issues[0].kind,
+      FilesystemIssueKind::ReservedName { .. }
+    ));
+
+    let long_path = format!("programs/{}", "a/".repeat(150));
+    let issues = check_bundle_file(TargetFilesystem::Fat32, &long_path, 1);
+    assert!(matches!(
+      issues[0].kind,
+      FilesystemIssueKind::PathTooLong { .. }
+    ));
+  }
+
+  #[test]
+  fn parses_profile_names() {
+    assert_eq!("FAT32".parse(), Ok(TargetFilesystem::Fat32));
+    assert_eq!("exfat".parse(), Ok(TargetFilesystem::ExFat));
+    assert!("ntfs".parse::<TargetFilesystem>().is_err());
+    assert_eq!("error".parse(), Ok(FilesystemCheckMode::Error));
+  }
+}
@@ -0,0 +1,68 @@
+//! Run the external commands configured in [`BuildHooks`](crate::config::BuildHooks).
+
+use std::path::Path;
+use std::process::Command;
+
+use crate::builder::BuildResult;
+
+/// Name passed in `OFFLINE_HOOK` to commands from [`BuildHooks::pre_build`](crate::config::BuildHooks::pre_build).
+pub const PRE_BUILD: &str = "pre_build";
+/// Name passed in `OFFLINE_HOOK` to commands from [`BuildHooks::post_bundle`](crate::config::BuildHooks::post_bundle).
+pub const POST_BUNDLE: &str = "post_bundle";
+
+/// Run `commands` one after another from `manifest_dir`, stopping at the first failure.
+///
+/// Every command inherits the current environment plus `OFFLINE_HOOK` (the hook name),
+/// `OFFLINE_MANIFEST_DIR` and `OFFLINE_BUNDLE_ROOT`. Output is passed through unchanged.
+pub fn run_hook_commands(
+  hook: &str,
+  commands: &[Vec<String>],
+  manifest_dir: &Path,
+  bundle_root: &Path,
+) -> BuildResult<()> {
+  for argv in commands {
+    let Some((program, args)) = argv.split_first() else {
+      return Err(format!("{hook} hook contains an empty command").into());
+    };
+    let status = Command::new(program)
+      .args(args)
+      .current_dir(manifest_dir)
+      .env("OFFLINE_HOOK", hook)
+      .env("OFFLINE_MANIFEST_DIR", manifest_dir)
+      .env("OFFLINE_BUNDLE_ROOT", bundle_root)
+      .status()
+      .map_err(|err| format!("failed to run {hook} hook `{}`: {err}", argv.join(" ")))?;
+    if !status.success() {
+      return Err(format!("{hook} hook `{}` failed with {status}", argv.join(" ")).into());
+    }
+  }
+  Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+  use super::*;
+  use std::fs;
+  use tempfile::tempdir;
+
+  #[test]
+  fn runs_commands_with_bundle_environment() {
+    let dir = tempdir().unwrap();
+    let bundle_root = dir.path().join("target/offline-html");
+    let commands = vec![vec![
+      "sh".to_string(),
+      "-c".to_string(),
+      "printf '%s %s' \"$OFFLINE_HOOK\" \"$OFFLINE_BUNDLE_ROOT\" > hook.txt".to_string(),
+    ]];
+
+    run_hook_commands(POST_BUNDLE, &commands, dir.path(), &bundle_root).unwrap();
+
+    assert_eq!(
+      fs::read_to_string(dir.path().join("hook.txt")).unwrap(),
+      format!("post_bundle {}", bundle_root.display())
+    );
+    let err =
+      run_hook_commands(PRE_BUILD, &[vec!["false".into()]], dir.path(), &bundle_root).unwrap_err();
+    assert!(err.to_string().starts_with("pre_build hook `false` failed"));
+  }
+}
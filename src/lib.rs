@@ -1,20 +1,58 @@
-#![doc = include_str!("../README.md")]
+#![cfg_attr(all(feature = "markdown", feature = "patch"), doc = include_str!("../README.md"))]
+#![cfg_attr(
+  not(all(feature = "markdown", feature = "patch")),
+  doc = "Runtime helpers of offline_dx_bundler; enable the `markdown` and `patch` features for the build side."
+)]
 #![warn(missing_docs)]
 #![allow(clippy::module_inception)]
 
+#[cfg(all(feature = "archive", not(target_arch = "wasm32")))]
+pub mod archive;
 pub mod asset_paths;
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(feature = "markdown", feature = "patch", not(target_arch = "wasm32")))]
 pub mod builder;
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(feature = "markdown", feature = "patch", not(target_arch = "wasm32")))]
 pub mod bundle;
+#[cfg(all(feature = "markdown", feature = "patch", not(target_arch = "wasm32")))]
+pub mod codegen;
 pub mod config;
-#[cfg(not(target_arch = "wasm32"))]
+pub mod filesystem;
+#[cfg(all(feature = "markdown", feature = "patch", not(target_arch = "wasm32")))]
+pub mod hooks;
+#[cfg(all(feature = "markdown", feature = "patch", not(target_arch = "wasm32")))]
 pub mod manifest;
+#[cfg(all(feature = "media", not(target_arch = "wasm32")))]
+pub mod media;
 pub mod models;
+#[cfg(all(feature = "markdown", feature = "patch", not(target_arch = "wasm32")))]
+pub mod pipeline;
+#[cfg(all(feature = "markdown", feature = "patch", not(target_arch = "wasm32")))]
+pub mod processor;
 pub mod project;
+#[cfg(all(feature = "markdown", feature = "patch", not(target_arch = "wasm32")))]
+pub mod report;
+pub mod runtime;
 pub mod selection;
+#[cfg(all(feature = "test-fixtures", not(target_arch = "wasm32")))]
+pub mod test_support;
+pub mod theme;
+#[cfg(all(feature = "tui", not(target_arch = "wasm32")))]
+pub mod tui;
 
-#[cfg(not(target_arch = "wasm32"))]
-pub use builder::{BuildResult, OfflineArtifacts, OfflineBuilder};
+#[cfg(all(feature = "markdown", feature = "patch", not(target_arch = "wasm32")))]
+pub use builder::{
+  ArtifactContent, BuildResult, MirrorConsistencyError, OfflineArtifacts, OfflineBuilder,
+  ProfileArtifacts, write_collection_catalog, write_offline_manifest,
+};
+#[cfg(all(feature = "markdown", feature = "patch", not(target_arch = "wasm32")))]
+pub use manifest::generate_offline_manifest;
+pub use models::ManifestGenerationResult;
+#[cfg(all(feature = "markdown", feature = "patch", not(target_arch = "wasm32")))]
+pub use pipeline::{BuildState, Pipeline, PipelineStage};
+#[cfg(all(feature = "markdown", feature = "patch", not(target_arch = "wasm32")))]
+pub use processor::{AssetOutcome, AssetProcessor};
 pub use project::{OfflineBuildContext, OfflineProjectLayout};
-pub use selection::CollectionInclusion;
+#[cfg(all(feature = "markdown", feature = "patch", not(target_arch = "wasm32")))]
+pub use report::{BuildReport, BuildStats, StepTiming};
+pub use runtime::OfflineManifestIndex;
+pub use selection::{CollectionInclusion, IncludeAll, IncludeNone};
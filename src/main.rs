@@ -1,7 +1,17 @@
-use clap::Parser;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
+use offline_dx_bundler::config::ProjectConfig;
+use offline_dx_bundler::{bundle, hooks};
 
 #[derive(Parser, Debug)]
-#[clap(author = "Albert O'Shea", version, about)]
+#[clap(
+  author = "Albert O'Shea",
+  version,
+  about,
+  args_conflicts_with_subcommands = true
+)]
 /// Application configuration
 struct Args {
   /// whether to be verbose
@@ -11,15 +21,182 @@ struct Args {
   /// an optional name to greet
   #[arg()]
   name: Option<String>,
+
+  #[command(subcommand)]
+  command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+  /// Check an offline bundle against its manifest
+  Verify {
+    /// directory containing the offline manifest
+    bundle_root: PathBuf,
+
+    /// project directory whose offline config describes the bundle layout
+    #[arg(long, default_value = ".")]
+    project: PathBuf,
+  },
+  /// Print the tool version, commit, content hash and build time of an offline bundle
+  Provenance {
+    /// bundle root containing `version.json`
+    bundle_root: PathBuf,
+  },
+  /// Run the hook commands configured in the project's offline config
+  Hook {
+    /// hook to run
+    #[arg(value_parser = [hooks::PRE_BUILD, hooks::POST_BUNDLE])]
+    hook: String,
+
+    /// project directory whose offline config lists the hooks
+    #[arg(long, default_value = ".")]
+    project: PathBuf,
+  },
+  /// List legacy metadata file and configuration key names that should be renamed
+  Migrate {
+    /// project directory whose offline config locates the collections
+    #[arg(long, default_value = ".")]
+    project: PathBuf,
+  },
+  /// Pick the collections to bundle and write the local selection file
+  #[cfg(feature = "tui")]
+  Select {
+    /// project directory whose offline config locates the collections
+    #[arg(long, default_value = ".")]
+    project: PathBuf,
+  },
 }
 
-fn main() {
+fn main() -> ExitCode {
   let args = Args::parse();
   if args.verbose {
     println!("DEBUG {args:?}");
   }
-  println!(
-    "Hello {} (from offline_dx_bundler)!",
-    args.name.unwrap_or("world".to_string())
-  );
+  match args.command {
+    Some(Command::Verify {
+      bundle_root,
+      project,
+    }) => verify(&bundle_root, &project),
+    Some(Command::Provenance { bundle_root }) => provenance(&bundle_root),
+    Some(Command::Hook { hook, project }) => run_hook(&hook, &project),
+    Some(Command::Migrate { project }) => migrate(&project),
+    #[cfg(feature = "tui")]
+    Some(Command::Select { project }) => select(&project),
+    None => {
+      println!(
+        "Hello {} (from offline_dx_bundler)!",
+        args.name.unwrap_or("world".to_string())
+      );
+      ExitCode::SUCCESS
+    }
+  }
+}
+
+fn verify(bundle_root: &Path, project: &Path) -> ExitCode {
+  let layout = ProjectConfig::discover(project).to_layout();
+  match bundle::verify(&layout, bundle_root) {
+    Ok(report) if report.is_ok() => {
+      println!(
+        "{}: {} assets present, {} checksums verified",
+        bundle_root.display(),
+        report.checked_assets,
+        report.checksummed_assets
+      );
+      ExitCode::SUCCESS
+    }
+    Ok(report) => {
+      for issue in &report.issues {
+        eprintln!("{issue}");
+      }
+      eprintln!(
+        "{}: {} problem(s) found",
+        bundle_root.display(),
+        report.issues.len()
+      );
+      ExitCode::FAILURE
+    }
+    Err(err) => {
+      eprintln!("{err:#}");
+      ExitCode::FAILURE
+    }
+  }
+}
+
+fn provenance(bundle_root: &Path) -> ExitCode {
+  match bundle::provenance::read_version_file(bundle_root) {
+    Ok(provenance) => {
+      println!("tool version: {}", provenance.tool_version);
+      println!(
+        "git commit:   {}",
+        provenance.git_commit.as_deref().unwrap_or("unknown")
+      );
+      println!("content hash: {}", provenance.content_hash);
+      println!("built at:     {} (Unix time)", provenance.built_at);
+      ExitCode::SUCCESS
+    }
+    Err(err) => {
+      eprintln!("{err:#}");
+      ExitCode::FAILURE
+    }
+  }
+}
+
+fn migrate(project: &Path) -> ExitCode {
+  let names = ProjectConfig::discover(project).legacy_names(project);
+  if names.is_empty() {
+    println!("no legacy names found");
+  }
+  for name in &names {
+    println!("{name}");
+  }
+  ExitCode::SUCCESS
+}
+
+#[cfg(feature = "tui")]
+fn select(project: &Path) -> ExitCode {
+  let config = ProjectConfig::discover(project);
+  let selection_path = config.collections_local_file(project);
+  #[cfg(feature = "archive")]
+  let collections_dir = match offline_dx_bundler::archive::prepare_collections_dir(&config, project)
+  {
+    Ok(dir) => dir,
+    Err(err) => {
+      eprintln!("{err}");
+      return ExitCode::FAILURE;
+    }
+  };
+  #[cfg(not(feature = "archive"))]
+  let collections_dir = config.collections_dir_path(project);
+  match offline_dx_bundler::tui::edit_selection_file(
+    &config.to_layout(),
+    &collections_dir,
+    &selection_path,
+  ) {
+    Ok(true) => {
+      println!("wrote {}", selection_path.display());
+      ExitCode::SUCCESS
+    }
+    Ok(false) => ExitCode::SUCCESS,
+    Err(err) => {
+      eprintln!("{err}");
+      ExitCode::FAILURE
+    }
+  }
+}
+
+fn run_hook(hook: &str, project: &Path) -> ExitCode {
+  let config = ProjectConfig::discover(project);
+  let commands = if hook == hooks::PRE_BUILD {
+    &config.hooks.pre_build
+  } else {
+    &config.hooks.post_bundle
+  };
+  let bundle_root = config.to_layout().bundle_root(project);
+  match hooks::run_hook_commands(hook, commands, project, &bundle_root) {
+    Ok(()) => ExitCode::SUCCESS,
+    Err(err) => {
+      eprintln!("{err}");
+      ExitCode::FAILURE
+    }
+  }
 }
@@ -0,0 +1,297 @@
+//! License and attribution sidecars collected from authored collections.
+//!
+//! An `ATTRIBUTION.yaml` file applies to every asset in its directory and below. Values from
+//! deeper directories override those inherited from parents, and a `files` map keyed by file
+//! name can attach attribution to individual assets:
+//!
+//! ```yaml
+//! author: Stock Photo Co.
+//! license: CC-BY-4.0
+//! files:
+//!   hero.jpg:
+//!     author: Jane Doe
+//!     source: https://example.com/hero
+//! ```
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::asset_paths::make_offline_asset_path;
+use crate::models::AssetEntry;
+use crate::project::OfflineProjectLayout;
+
+/// File name of attribution sidecars. Sidecars are never bundled as assets.
+pub const ATTRIBUTION_FILE: &str = "ATTRIBUTION.yaml";
+
+/// Attribution details describing the origin and license of an asset.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttributionRecord {
+  /// Title of the original work.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub title: Option<String>,
+  /// Author or rights holder that must be credited.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub author: Option<String>,
+  /// License identifier or name.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub license: Option<String>,
+  /// Link to the full license text.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub license_url: Option<String>,
+  /// Original source location of the work.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub source: Option<String>,
+  /// Free-form attribution text required by the license.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub notes: Option<String>,
+}
+
+/// Attribution attached to a single bundled asset.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetCredit {
+  /// Bundle-relative path of the asset.
+  pub path: String,
+  /// Collection the asset belongs to.
+  pub collection_id: String,
+  /// Resolved attribution for the asset.
+  #[serde(flatten)]
+  pub attribution: AttributionRecord,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct AttributionFile {
+  #[serde(flatten)]
+  defaults: AttributionRecord,
+  #[serde(default)]
+  files: BTreeMap<String, AttributionRecord>,
+}
+
+impl AttributionRecord {
+  /// Returns true when no attribution fields are set.
+  pub fn is_empty(&self) -> bool {
+    self == &Self::default()
+  }
+
+  /// Fill unset fields from a less specific record.
+  fn inherit(&mut self, parent: &AttributionRecord) {
+    let fields = [
+      (&mut self.title, &parent.title),
+      (&mut self.author, &parent.author),
+      (&mut self.license, &parent.license),
+      (&mut self.license_url, &parent.license_url),
+      (&mut self.source, &parent.source),
+      (&mut self.notes, &parent.notes),
+    ];
+    for (value, inherited) in fields {
+      if value.is_none() {
+        value.clone_from(inherited);
+      }
+    }
+  }
+}
+
+/// Resolve attribution for every asset that has a sidecar in scope.
+///
+/// Credits are returned sorted by bundle path; assets without attribution are omitted.
+pub fn collect_asset_credits(
+  layout: &OfflineProjectLayout,
+  collections_dir: &Path,
+  asset_map: &BTreeMap<(String, String), AssetEntry>,
+) -> Vec<AssetCredit> {
+  let mut cache: BTreeMap<PathBuf, Option<AttributionFile>> = BTreeMap::new();
+  let mut credits = Vec::new();
+
+  for entry in asset_map.values() {
+    let collection_root = collections_dir.join(&entry.collection_id);
+    let source = entry.source_path(collections_dir);
+    let file_name = source
+      .file_name()
+      .map(|name| name.to_string_lossy().to_string())
+      .unwrap_or_default();
+
+    let mut attribution = AttributionRecord::default();
+    let mut directory = source.parent();
+    let mut is_asset_directory = true;
+
+    while let Some(dir) = directory {
+      let sidecar = cache
+        .entry(dir.to_path_buf())
+        .or_insert_with(|| load_attribution_file(&dir.join(ATTRIBUTION_FILE)));
+      if let Some(sidecar) = sidecar {
+        if is_asset_directory && let Some(record) = sidecar.files.get(&file_name) {
+          attribution.inherit(record);
+        }
+        attribution.inherit(&sidecar.defaults);
+      }
+
+      if dir == collection_root {
+        break;
+      }
+      is_asset_directory = false;
+      directory = dir.parent();
+    }
+
+    if !attribution.is_empty() {
+      credits.push(AssetCredit {
+        path: make_offline_asset_path(layout, &entry.collection_id, entry.output_relative_path()),
+        collection_id: entry.collection_id.clone(),
+        attribution,
+      });
+    }
+  }
+
+  credits.sort_by(|a, b| a.path.cmp(&b.path));
+  credits
+}
+
+/// Render a standalone HTML credits page listing the provided credits.
+pub fn render_credits_page(credits: &[AssetCredit]) -> String {
+  let mut rows = String::new();
+  for credit in credits {
+    let record = &credit.attribution;
+    let license = match (&record.license, &record.license_url) {
+      (Some(license), Some(url)) => format!(
+        "<a href=\"{}\">{}</a>",
+        escape_html(url),
+        escape_html(license)
+      ),
+      (Some(license), None) => escape_html(license),
+      (None, Some(url)) => format!("<a href=\"{0}\">{0}</a>", escape_html(url)),
+      (None, None) => String::new(),
+    };
+    rows.push_str(&format!(
+      "        <tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+      escape_html(&credit.path),
+      escape_html(record.title.as_deref().unwrap_or_default()),
+      escape_html(record.author.as_deref().unwrap_or_default()),
+      license,
+      escape_html(
+        record
+          .notes
+          .as_deref()
+          .or(record.source.as_deref())
+          .unwrap_or_default()
+      ),
+    ));
+  }
+
+  format!(
+    r#"<!DOCTYPE html>
+<html lang="en">
+  <head>
+    <meta charset="utf-8">
+    <title>Credits</title>
+  </head>
+  <body>
+    <h1>Credits</h1>
+    <table>
+      <thead>
+        <tr><th>Asset</th><th>Title</th><th>Author</th><th>License</th><th>Notes</th></tr>
+      </thead>
+      <tbody>
+{rows}      </tbody>
+    </table>
+  </body>
+</html>
+"#
+  )
+}
+
+fn load_attribution_file(path: &Path) -> Option<AttributionFile> {
+  let content = fs::read_to_string(path).ok()?;
+  match serde_yaml::from_str(&content) {
+    Ok(file) => Some(file),
+    Err(err) => {
+      println!(
+        "cargo:warning=Failed to parse attribution file {}: {}",
+        path.display(),
+        err
+      );
+      None
+    }
+  }
+}
+
+pub(crate) fn escape_html(value: &str) -> String {
+  value
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::tempdir;
+
+  fn layout() -> OfflineProjectLayout {
+    OfflineProjectLayout {
+      excluded_dir_name: "prod".into(),
+      excluded_path_fragment: "/prod/".into(),
+      ..Default::default()
+    }
+  }
+
+  fn asset(relative_path: &str) -> ((String, String), AssetEntry) {
+    (
+      ("P001".to_string(), relative_path.to_string()),
+      AssetEntry {
+        const_name: "CONST".into(),
+        literal_path: String::new(),
+        collection_id: "P001".into(),
+        relative_path: relative_path.into(),
+        output_path: None,
+        source_file: None,
+        size: None,
+        modified: None,
+      },
+    )
+  }
+
+  #[test]
+  fn merges_directory_and_file_attribution() {
+    let dir = tempdir().unwrap();
+    let collections_dir = dir.path();
+    let assets_dir = collections_dir.join("P001/entry/assets");
+    fs::create_dir_all(&assets_dir).unwrap();
+    fs::write(
+      collections_dir.join("P001").join(ATTRIBUTION_FILE),
+      "license: CC-BY-4.0\nauthor: Stock Co.\n",
+    )
+    .unwrap();
+    fs::write(
+      assets_dir.join(ATTRIBUTION_FILE),
+      "files:\n  hero.jpg:\n    author: Jane Doe\n",
+    )
+    .unwrap();
+
+    let asset_map = BTreeMap::from([
+      asset("entry/assets/hero.jpg"),
+      asset("entry/assets/icon.png"),
+    ]);
+    let credits = collect_asset_credits(&layout(), collections_dir, &asset_map);
+
+    assert_eq!(credits.len(), 2);
+    assert_eq!(credits[0].path, "programs/P001/entry/assets/hero.jpg");
+    assert_eq!(credits[0].attribution.author.as_deref(), Some("Jane Doe"));
+    assert_eq!(credits[0].attribution.license.as_deref(), Some("CC-BY-4.0"));
+    assert_eq!(credits[1].attribution.author.as_deref(), Some("Stock Co."));
+
+    let page = render_credits_page(&credits);
+    assert!(page.contains("Jane Doe"));
+  }
+
+  #[test]
+  fn omits_assets_without_attribution() {
+    let dir = tempdir().unwrap();
+    let asset_map = BTreeMap::from([asset("assets/logo.png")]);
+    let credits = collect_asset_credits(&layout(), dir.path(), &asset_map);
+    assert!(credits.is_empty());
+  }
+}
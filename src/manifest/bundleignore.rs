@@ -0,0 +1,162 @@
+//! `.bundleignore` files excluding working files from asset scanning.
+//!
+//! The syntax follows `.gitignore`: one glob per line, `#` comments, `!` to re-include, a
+//! trailing `/` for directories only, and patterns containing a `/` anchored to the directory
+//! holding the file. Rules from deeper files are applied after those of their parents, and the
+//! last matching rule wins. As with git, files inside an ignored directory cannot be re-included.
+
+use std::fs;
+use std::path::Path;
+
+use regex::Regex;
+
+/// Name of the ignore file honoured in collections and any of their subdirectories.
+pub const BUNDLE_IGNORE_FILE: &str = ".bundleignore";
+
+/// Ignore rules collected from the `.bundleignore` files on the way to a directory.
+#[derive(Debug, Clone, Default)]
+pub struct BundleIgnore {
+  rules: Vec<IgnoreRule>,
+}
+
+#[derive(Debug, Clone)]
+struct IgnoreRule {
+  base: String,
+  pattern: Regex,
+  anchored: bool,
+  negated: bool,
+  dir_only: bool,
+}
+
+impl BundleIgnore {
+  /// Add the rules of `dir/.bundleignore`, if present. `base` is the directory's path relative
+  /// to the scan root, empty for the root itself.
+  pub fn load(&mut self, dir: &Path, base: &str) {
+    if let Ok(contents) = fs::read_to_string(dir.join(BUNDLE_IGNORE_FILE)) {
+      self.add_rules(&contents, base);
+    }
+  }
+
+  /// Add the rules in `contents`, relative to the directory `base`.
+  pub fn add_rules(&mut self, contents: &str, base: &str) {
+    for line in contents.lines() {
+      let line = line.trim_end();
+      if line.is_empty() || line.starts_with('#') {
+        continue;
+      }
+      let (negated, line) = match line.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, line.strip_prefix('\\').unwrap_or(line)),
+      };
+      let (dir_only, line) = match line.strip_suffix('/') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+      };
+      if line.is_empty() {
+        continue;
+      }
+      let anchored = line.contains('/');
+      self.rules.push(IgnoreRule {
+        base: base.trim_matches('/').to_string(),
+        pattern: glob_regex(line.trim_start_matches('/')),
+        anchored,
+        negated,
+        dir_only,
+      });
+    }
+  }
+
+  /// Returns true when `relative_path` (relative to the scan root, `/`-separated) is ignored.
+  pub fn is_ignored(&self, relative_path: &str, is_dir: bool) -> bool {
+    let mut ignored = false;
+    for rule in &self.rules {
+      if rule.dir_only && !is_dir {
+        continue;
+      }
+      let rest = if rule.base.is_empty() {
+        Some(relative_path)
+      } else {
+        relative_path
+          .strip_prefix(rule.base.as_str())
+          .and_then(|rest| rest.strip_prefix('/'))
+      };
+      let Some(rest) = rest else {
+        continue;
+      };
+      let subject = if rule.anchored {
+        rest
+      } else {
+        rest.rsplit('/').next().unwrap_or(rest)
+      };
+      if rule.pattern.is_match(subject) {
+        ignored = !rule.negated;
+      }
+    }
+    ignored
+  }
+}
+
+fn glob_regex(glob: &str) -> Regex {
+  let mut pattern = String::from("^");
+  let mut chars = glob.chars().peekable();
+  while let Some(ch) = chars.next() {
+    match ch {
+      '*' if chars.peek() == Some(&'*') => {
+        chars.next();
+        if chars.peek() == Some(&'/') {
+          chars.next();
+          pattern.push_str("(?:.*/)?");
+        } else {
+          pattern.push_str(".*");
+        }
+      }
+      '*' => pattern.push_str("[^/]*"),
+      '?' => pattern.push_str("[^/]"),
+      '[' => {
+        let mut class = String::new();
+        for ch in chars.by_ref() {
+          if ch == ']' {
+            break;
+          }
+          class.push(ch);
+        }
+        let class = match class.strip_prefix('!') {
+          Some(rest) => format!("^{}", rest.replace('\\', "\\\\")),
+          None => class.replace('\\', "\\\\"),
+        };
+        pattern.push_str(&format!("[{class}]"));
+      }
+      '\\' => {
+        if let Some(next) = chars.next() {
+          pattern.push_str(&regex::escape(&next.to_string()));
+        }
+      }
+      _ => pattern.push_str(&regex::escape(&ch.to_string())),
+    }
+  }
+  pattern.push('$');
+  Regex::new(&pattern).unwrap_or_else(|_| Regex::new("$^").expect("valid never-matching regex"))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn follows_gitignore_rules() {
+    let mut ignore = BundleIgnore::default();
+    ignore.add_rules("# working files\n*.psd\nraw/\n/drafts/**\n!keep.psd\n", "");
+    ignore.add_rules("footage-*.mov\n!logo.psd\n", "entries/intro");
+
+    assert!(ignore.is_ignored("cover.psd", false));
+    assert!(ignore.is_ignored("entries/intro/assets/banner.psd", false));
+    assert!(!ignore.is_ignored("keep.psd", false));
+    assert!(!ignore.is_ignored("entries/intro/logo.psd", false));
+    assert!(ignore.is_ignored("entries/intro/assets/raw", true));
+    assert!(!ignore.is_ignored("entries/intro/assets/raw", false));
+    assert!(ignore.is_ignored("drafts/old/cover.png", false));
+    assert!(!ignore.is_ignored("entries/drafts/cover.png", false));
+    assert!(ignore.is_ignored("entries/intro/assets/footage-01.mov", false));
+    assert!(!ignore.is_ignored("entries/other/footage-01.mov", false));
+  }
+}
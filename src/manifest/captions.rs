@@ -0,0 +1,243 @@
+//! Pair subtitle and caption sidecars with the videos they belong to.
+//!
+//! Captions are rarely referenced explicitly, so they kept getting lost from bundles. A
+//! `clip.vtt`, `clip.en.vtt` or `clip.srt` next to `clip.mp4` is a sidecar of that video:
+//! [`pair_caption_sidecars`] adds the sidecars of every video an entry references to its asset
+//! paths and gives `<video>` elements in the body a `<track>` for each WebVTT sidecar. SubRip
+//! files are bundled too, but browsers cannot play them as tracks.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use regex::{Captures, Regex};
+
+use crate::asset_paths::make_offline_asset_path;
+use crate::manifest::markdown::resolve_markdown_assets;
+use crate::manifest::posters::attribute;
+use crate::models::AssetEntry;
+use crate::project::OfflineProjectLayout;
+
+/// Extensions of assets whose caption sidecars are paired.
+pub const VIDEO_EXTENSIONS: [&str; 7] = ["mp4", "m4v", "mov", "mkv", "webm", "avi", "ogv"];
+/// Extensions of caption sidecars.
+pub const CAPTION_EXTENSIONS: [&str; 2] = ["vtt", "srt"];
+
+/// Caption file next to a video, sharing its stem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptionSidecar {
+  /// Relative path of the sidecar within the collection.
+  pub relative_path: String,
+  /// Language tag between the stem and the extension, e.g. `en` in `clip.en.vtt`.
+  pub language: Option<String>,
+  /// Whether the sidecar is WebVTT and can be played as a `<track>`.
+  pub webvtt: bool,
+}
+
+fn extension(path: &str) -> Option<String> {
+  let name = path.rsplit('/').next().unwrap_or(path);
+  Some(name.rsplit_once('.')?.1.to_ascii_lowercase())
+}
+
+/// Whether `path` has one of the [`VIDEO_EXTENSIONS`].
+pub fn is_video(path: &str) -> bool {
+  extension(path).is_some_and(|extension| VIDEO_EXTENSIONS.contains(&extension.as_str()))
+}
+
+/// Caption sidecars of the video at `video` in `collection_id`, ordered by path.
+pub fn caption_sidecars(
+  asset_map: &BTreeMap<(String, String), AssetEntry>,
+  collection_id: &str,
+  video: &str,
+) -> Vec<CaptionSidecar> {
+  let Some((stem, _)) = video.rsplit_once('.') else {
+    return Vec::new();
+  };
+  if stem.ends_with('/') {
+    return Vec::new();
+  }
+  let prefix = format!("{stem}.");
+  asset_map
+    .range((collection_id.to_string(), prefix.clone())..)
+    .take_while(|((collection, path), _)| collection == collection_id && path.starts_with(&prefix))
+    .filter_map(|((_, path), _)| {
+      let rest = &path[prefix.len()..];
+      let (language, extension) = match rest.rsplit_once('.') {
+        Some((language, extension)) => (Some(language), extension),
+        None => (None, rest),
+      };
+      let extension = extension.to_ascii_lowercase();
+      if !CAPTION_EXTENSIONS.contains(&extension.as_str())
+        || language.is_some_and(|language| language.is_empty() || language.contains(['.', '/']))
+      {
+        return None;
+      }
+      Some(CaptionSidecar {
+        relative_path: path.clone(),
+        language: language.map(str::to_string),
+        webvtt: extension == "vtt",
+      })
+    })
+    .collect()
+}
+
+/// Add the caption sidecars of the videos among `asset_paths` to them and return `body` with a
+/// `<track>` for every WebVTT sidecar its `<video>` elements do not already list.
+///
+/// Track sources are written relative to the video reference so they resolve like it.
+pub fn pair_caption_sidecars(
+  layout: &OfflineProjectLayout,
+  asset_map: &BTreeMap<(String, String), AssetEntry>,
+  collection_id: &str,
+  entry_id: &str,
+  asset_slug: Option<&str>,
+  body: &str,
+  asset_paths: &mut Vec<String>,
+) -> String {
+  let collection_prefix = make_offline_asset_path(layout, collection_id, "");
+  let relative_video = |bundle_path: &str| {
+    bundle_path
+      .strip_prefix(&collection_prefix)
+      .filter(|relative| is_video(relative))
+      .map(str::to_string)
+  };
+
+  let videos: Vec<String> = asset_paths
+    .iter()
+    .filter_map(|path| relative_video(path))
+    .collect();
+  let mut paired = false;
+  for video in videos {
+    for sidecar in caption_sidecars(asset_map, collection_id, &video) {
+      let path = make_offline_asset_path(layout, collection_id, &sidecar.relative_path);
+      if !asset_paths.contains(&path) {
+        asset_paths.push(path);
+        paired = true;
+      }
+    }
+  }
+  if paired {
+    asset_paths.sort();
+  }
+
+  let video_pattern =
+    Regex::new(r"(?is)<video\b([^>]*)>(.*?)</video>").expect("invalid video regex");
+  let tag_pattern = Regex::new(r"(?is)<(source|track)\b([^>]*)>").expect("invalid tag regex");
+  video_pattern
+    .replace_all(body, |caps: &Captures| {
+      let mut reference = attribute(&caps[1], "src");
+      let mut listed = BTreeSet::new();
+      for tag in tag_pattern.captures_iter(&caps[2]) {
+        let Some(src) = attribute(&tag[2], "src") else {
+          continue;
+        };
+        if tag[1].eq_ignore_ascii_case("track") {
+          listed.insert(src);
+        } else if reference.is_none() {
+          reference = Some(src);
+        }
+      }
+      let Some(reference) = reference else {
+        return caps[0].to_string();
+      };
+      let (resolved, _) = resolve_markdown_assets(
+        layout,
+        &BTreeSet::from([reference.clone()]),
+        asset_map,
+        collection_id,
+        entry_id,
+        asset_slug,
+      );
+      let Some(video) = resolved.first().and_then(|path| relative_video(path)) else {
+        return caps[0].to_string();
+      };
+
+      let reference_dir = reference
+        .rsplit_once('/')
+        .map_or(String::new(), |(dir, _)| format!("{dir}/"));
+      let mut tracks = String::new();
+      for sidecar in caption_sidecars(asset_map, collection_id, &video) {
+        let name = sidecar.relative_path.rsplit('/').next().unwrap_or_default();
+        let src = format!("{reference_dir}{name}");
+        if !sidecar.webvtt || listed.contains(&src) {
+          continue;
+        }
+        tracks.push_str(&format!("<track kind=\"captions\" src=\"{src}\""));
+        if let Some(language) = &sidecar.language {
+          tracks.push_str(&format!(" srclang=\"{language}\" label=\"{language}\""));
+        }
+        tracks.push('>');
+      }
+      if tracks.is_empty() {
+        return caps[0].to_string();
+      }
+      format!("<video{}>{}{tracks}</video>", &caps[1], &caps[2])
+    })
+    .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn asset_map(paths: &[&str]) -> BTreeMap<(String, String), AssetEntry> {
+    paths
+      .iter()
+      .map(|path| {
+        (("alpha".to_string(), path.to_string()), AssetEntry {
+          const_name: path.to_uppercase().replace(['/', '.', '-'], "_"),
+          literal_path: format!("/content/programs/alpha/{path}"),
+          collection_id: "alpha".into(),
+          relative_path: path.to_string(),
+          output_path: None,
+          source_file: None,
+          size: None,
+          modified: None,
+        })
+      })
+      .collect()
+  }
+
+  #[test]
+  fn pairs_sidecars_and_injects_webvtt_tracks() {
+    let layout = OfflineProjectLayout::default();
+    let assets = asset_map(&[
+      "intro/assets/clip.de.vtt",
+      "intro/assets/clip.en.vtt",
+      "intro/assets/clip.mp4",
+      "intro/assets/clip.png",
+      "intro/assets/clip.srt",
+      "intro/assets/clip.extra.notes.vtt",
+      "intro/assets/clipper.vtt",
+    ]);
+    let path = |relative: &str| make_offline_asset_path(&layout, "alpha", relative);
+    let mut asset_paths = vec![path("intro/assets/clip.mp4")];
+
+    let body = pair_caption_sidecars(
+      &layout,
+      &assets,
+      "alpha",
+      "intro",
+      None,
+      concat!(
+        "<video controls>\n<source src=\"assets/clip.mp4\" type=\"video/mp4\">\n",
+        "<track kind=\"captions\" src=\"assets/clip.de.vtt\" srclang=\"de\">\n</video>",
+      ),
+      &mut asset_paths,
+    );
+
+    assert_eq!(asset_paths, [
+      path("intro/assets/clip.de.vtt"),
+      path("intro/assets/clip.en.vtt"),
+      path("intro/assets/clip.mp4"),
+      path("intro/assets/clip.srt"),
+    ]);
+    assert_eq!(
+      body,
+      concat!(
+        "<video controls>\n<source src=\"assets/clip.mp4\" type=\"video/mp4\">\n",
+        "<track kind=\"captions\" src=\"assets/clip.de.vtt\" srclang=\"de\">\n",
+        "<track kind=\"captions\" src=\"assets/clip.en.vtt\" srclang=\"en\" label=\"en\">",
+        "</video>",
+      )
+    );
+  }
+}
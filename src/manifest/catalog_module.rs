@@ -0,0 +1,224 @@
+//! `catalog.js` ES module and `catalog.d.ts` declarations for companion web tooling.
+//!
+//! Admin dashboards and signage controllers import the catalog and manifest data from the
+//! module instead of fetching the JSON artifacts, and get matching TypeScript types.
+
+use std::io::{self, Write};
+
+use crate::models::{CollectionCatalog, OfflineManifestSummary};
+
+/// TypeScript declarations of `catalog.js`, mirroring the JSON written for [`CollectionCatalog`]
+/// and [`OfflineManifestSummary`].
+pub const CATALOG_TYPES: &str = r#"// Generated by offline_dx_bundler; do not edit.
+
+/** Plain text, or translations keyed by language tag. */
+export type LocalizedText = string | Record<string, string>;
+
+export interface CollectionMeta {
+  title: LocalizedText;
+  description: LocalizedText | null;
+  version: string | null;
+  assetSlug: string | null;
+  heroImage: string | null;
+  order: number | null;
+}
+
+export interface EntryRecord {
+  id: string;
+  title: string;
+  section: string | null;
+  sequence: number;
+  source: string;
+  word_count: number;
+  reading_minutes: number;
+  excerpt?: string;
+}
+
+export interface CollectionIntro {
+  source: string;
+  title?: string;
+  body: string;
+  asset_paths: string[];
+}
+
+export interface CollectionRecord {
+  id: string;
+  meta: CollectionMeta;
+  entries: EntryRecord[];
+  /** Bundle path of the hero image. */
+  hero_asset?: string;
+  /** Bundle paths of hero variants keyed by suffix, e.g. `thumb` or `2x`. */
+  hero_variants?: Record<string, string>;
+  intro?: CollectionIntro;
+}
+
+export interface CollectionCatalog {
+  schema_version: number;
+  /** Language the localized text was resolved to, if any. */
+  language?: string;
+  collections: CollectionRecord[];
+}
+
+export interface OfflineEntrySummary {
+  collection_id: string;
+  entry_id: string;
+  title?: string;
+  section?: string;
+  asset_paths: string[];
+  /** Heading anchors without the `#` mapped to the heading text. */
+  headings?: Record<string, string>;
+  priority_assets?: string[];
+  /** Bundle paths of videos mapped to the poster frame generated for them. */
+  video_posters?: Record<string, string>;
+}
+
+export interface BuildProvenance {
+  tool_version: string;
+  git_commit?: string;
+  content_hash: string;
+  /** Seconds since the Unix epoch. */
+  built_at: number;
+}
+
+export interface OfflineManifest {
+  schema_version: number;
+  site_root: string;
+  entries: OfflineEntrySummary[];
+  hero_assets: string[];
+  asset_hashes?: Record<string, string>;
+  asset_aliases?: Record<string, string>;
+  asset_sizes?: Record<string, number>;
+  asset_modified?: Record<string, number>;
+  provenance?: BuildProvenance;
+}
+
+export declare const catalog: CollectionCatalog;
+export declare const manifest: OfflineManifest;
+export default catalog;
+"#;
+
+/// Write `catalog.js`, an ES module exporting `catalog` (also the default export) and
+/// `manifest`, serializing both straight into `writer`.
+pub fn write_catalog_module(
+  writer: &mut dyn Write,
+  catalog: &CollectionCatalog,
+  manifest: &OfflineManifestSummary,
+) -> io::Result<()> {
+  writer.write_all(b"// Generated by offline_dx_bundler; do not edit.\nexport const catalog = ")?;
+  serde_json::to_writer_pretty(&mut *writer, catalog)?;
+  writer.write_all(b";\nexport const manifest = ")?;
+  serde_json::to_writer_pretty(&mut *writer, manifest)?;
+  writer.write_all(b";\nexport default catalog;\n")
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::BTreeMap;
+
+  use serde_json::Value;
+
+  use super::*;
+  use crate::models::{
+    BuildProvenance, CollectionCatalogRecord, CollectionIntroRecord, CollectionMetaRecord,
+    EntryRecord, OfflineEntrySummary,
+  };
+
+  /// Object keys of `value`, skipping the dynamic keys of the `Record` fields.
+  fn keys(value: &Value, found: &mut Vec<String>) {
+    match value {
+      Value::Object(map) => {
+        for (key, value) in map {
+          found.push(key.clone());
+          if !matches!(
+            key.as_str(),
+            "hero_variants" | "headings" | "video_posters" | "title" | "description"
+          ) && !key.starts_with("asset_")
+          {
+            keys(value, found);
+          }
+        }
+      }
+      Value::Array(items) => items.iter().for_each(|item| keys(item, found)),
+      _ => {}
+    }
+  }
+
+  #[test]
+  fn declares_every_serialized_field() {
+    let catalog = CollectionCatalog {
+      schema_version: 3,
+      language: Some("en".into()),
+      collections: vec![CollectionCatalogRecord {
+        id: "intro".into(),
+        meta: CollectionMetaRecord {
+          title: "Intro".into(),
+          description: Some("First steps".into()),
+          version: None,
+          asset_slug: None,
+          hero_image: None,
+          order: Some(1),
+        },
+        entries: vec![EntryRecord {
+          id: "welcome".into(),
+          title: "Welcome".into(),
+          section: None,
+          sequence: 1,
+          source: "intro/welcome/index.md".into(),
+          word_count: 3,
+          reading_minutes: 1,
+          excerpt: Some("Hello".into()),
+        }],
+        hero_asset: Some("programs/intro/hero.png".into()),
+        hero_variants: BTreeMap::from([("thumb".into(), "programs/intro/hero-thumb.png".into())]),
+        intro: Some(CollectionIntroRecord {
+          source: "intro/README.md".into(),
+          title: Some("About".into()),
+          body: "About".into(),
+          asset_paths: Vec::new(),
+        }),
+      }],
+    };
+    let manifest = OfflineManifestSummary {
+      schema_version: 6,
+      site_root: "site".into(),
+      entries: vec![OfflineEntrySummary {
+        collection_id: "intro".into(),
+        entry_id: "welcome".into(),
+        title: Some("Welcome".into()),
+        section: Some("Basics".into()),
+        asset_paths: Vec::new(),
+        headings: BTreeMap::from([("start".into(), "Start".into())]),
+        priority_assets: vec!["programs/intro/hero.png".into()],
+        video_posters: BTreeMap::from([("a".into(), "b".into())]),
+      }],
+      hero_assets: Vec::new(),
+      asset_hashes: BTreeMap::from([("a".into(), "b".into())]),
+      asset_aliases: BTreeMap::from([("a".into(), "b".into())]),
+      asset_sizes: BTreeMap::from([("a".into(), 1)]),
+      asset_modified: BTreeMap::from([("a".into(), 1)]),
+      provenance: Some(BuildProvenance {
+        tool_version: "0.1.1".into(),
+        git_commit: Some("abc".into()),
+        content_hash: "hash".into(),
+        built_at: 0,
+      }),
+    };
+
+    let mut found = Vec::new();
+    keys(&serde_json::to_value(&catalog).unwrap(), &mut found);
+    keys(&serde_json::to_value(&manifest).unwrap(), &mut found);
+    for key in found {
+      assert!(
+        CATALOG_TYPES.contains(&format!("  {key}:"))
+          || CATALOG_TYPES.contains(&format!("  {key}?:")),
+        "`{key}` is missing from CATALOG_TYPES"
+      );
+    }
+
+    let mut module = Vec::new();
+    write_catalog_module(&mut module, &catalog, &manifest).unwrap();
+    let module = String::from_utf8(module).unwrap();
+    assert!(module.contains("export const catalog = {\n  \"schema_version\": 3,"));
+    assert!(module.ends_with("export default catalog;\n"));
+  }
+}
@@ -0,0 +1,194 @@
+//! Detect embedded remote media in entry bodies and swap it for offline placeholders.
+
+use pulldown_cmark::{Event, Options, Parser, Tag};
+use regex::{Captures, Regex};
+use serde::Serialize;
+
+use crate::bundle::site::external_host;
+use crate::manifest::attribution::escape_html;
+
+/// Kind of remote content found in an entry body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmbedKind {
+  /// `<iframe>` pointing at a remote page.
+  Iframe,
+  /// YouTube video, embedded or linked.
+  YouTube,
+  /// Vimeo video, embedded or linked.
+  Vimeo,
+}
+
+/// Remote embed or video link found in an entry body.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ExternalEmbed {
+  /// What kind of content the URL points at.
+  pub kind: EmbedKind,
+  /// URL as written by the author.
+  pub url: String,
+  /// Whether the reference is an `<iframe>` rather than a plain link.
+  pub iframe: bool,
+}
+
+/// Text of the placeholder that replaces `<iframe>` embeds in offline builds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmbedPlaceholder {
+  /// Note telling readers the content needs a connection.
+  pub note: String,
+  /// Label of the link to the original content.
+  pub link_label: String,
+}
+
+impl Default for EmbedPlaceholder {
+  fn default() -> Self {
+    Self {
+      note: "This content requires an internet connection.".into(),
+      link_label: "Open online".into(),
+    }
+  }
+}
+
+fn iframe_pattern() -> Regex {
+  Regex::new(r"(?is)<iframe\b([^>]*)>.*?</iframe\s*>|<iframe\b([^>]*)/>")
+    .expect("invalid iframe regex")
+}
+
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+  Regex::new(&format!(r#"(?i)\b{name}\s*=\s*(?:"([^"]*)"|'([^']*)')"#))
+    .expect("invalid attribute regex")
+    .captures(attributes)
+    .and_then(|caps| caps.get(1).or_else(|| caps.get(2)))
+    .map(|value| value.as_str().to_string())
+}
+
+/// Classify `url` as a YouTube or Vimeo video, if it is one.
+pub fn video_kind(url: &str) -> Option<EmbedKind> {
+  let host = external_host(url)?;
+  let matches = |domain: &str| host == domain || host.ends_with(&format!(".{domain}"));
+  if matches("youtube.com") || matches("youtu.be") || matches("youtube-nocookie.com") {
+    Some(EmbedKind::YouTube)
+  } else if matches("vimeo.com") {
+    Some(EmbedKind::Vimeo)
+  } else {
+    None
+  }
+}
+
+/// Find remote `<iframe>` embeds and YouTube/Vimeo links in a markdown body, in document order.
+///
+/// Iframes with a local `src` are ignored since they keep working offline.
+pub fn detect_external_embeds(markdown: &str) -> Vec<ExternalEmbed> {
+  let iframes = iframe_pattern();
+  let mut embeds = Vec::new();
+  for event in Parser::new_ext(markdown, Options::all()) {
+    match event {
+      Event::Start(Tag::Link { dest_url, .. }) => {
+        if let Some(kind) = video_kind(&dest_url) {
+          embeds.push(ExternalEmbed {
+            kind,
+            url: dest_url.to_string(),
+            iframe: false,
+          });
+        }
+      }
+      Event::Html(html) | Event::InlineHtml(html) => {
+        for caps in iframes.captures_iter(&html) {
+          let attributes = caps
+            .get(1)
+            .or_else(|| caps.get(2))
+            .map_or("", |m| m.as_str());
+          let Some(url) = attribute(attributes, "src") else {
+            continue;
+          };
+          if external_host(&url).is_none() {
+            continue;
+          }
+          embeds.push(ExternalEmbed {
+            kind: video_kind(&url).unwrap_or(EmbedKind::Iframe),
+            url,
+            iframe: true,
+          });
+        }
+      }
+      _ => {}
+    }
+  }
+  embeds
+}
+
+/// Replace remote `<iframe>` embeds with a static placeholder.
+///
+/// The placeholder shows a play-button graphic, the iframe's `title` when present, the note and
+/// a link to the original URL, and uses `offline-embed-placeholder` classes for styling.
+pub fn replace_iframe_embeds(markdown: &str, placeholder: &EmbedPlaceholder) -> String {
+  iframe_pattern()
+    .replace_all(markdown, |caps: &Captures| {
+      let attributes = caps.get(1).or_else(|| caps.get(2)).map_or("", |m| m.as_str());
+      let Some(url) = attribute(attributes, "src")
+        .filter(|url| external_host(url).is_some())
+      else {
+        return caps[0].to_string();
+      };
+      let title = attribute(attributes, "title")
+        .map(|title| {
+          format!(
+            r#"<strong class="offline-embed-placeholder__title">{}</strong>"#,
+            escape_html(&title)
+          )
+        })
+        .unwrap_or_default();
+      format!(
+        concat!(
+          r#"<div class="offline-embed-placeholder" data-embed-url="{url}">"#,
+          r#"<svg class="offline-embed-placeholder__thumbnail" viewBox="0 0 64 40" aria-hidden="true">"#,
+          r#"<rect width="64" height="40" rx="4" fill="currentColor" opacity="0.15"/>"#,
+          r#"<path d="M27 13v14l12-7z" fill="currentColor"/></svg>"#,
+          r#"{title}<p class="offline-embed-placeholder__note">{note}</p>"#,
+          r#"<a class="offline-embed-placeholder__link" href="{url}">{label}</a></div>"#
+        ),
+        url = escape_html(&url),
+        title = title,
+        note = escape_html(&placeholder.note),
+        label = escape_html(&placeholder.link_label),
+      )
+    })
+    .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const BODY: &str = concat!(
+    "Watch [the drill](https://youtu.be/abc123) first.\n\n",
+    "<iframe src=\"https://player.vimeo.com/video/42\" title=\"Knots & hitches\"></iframe>\n\n",
+    "<iframe src=\"https://maps.example.com/embed\"></iframe>\n\n",
+    "<iframe src=\"assets/local.html\"></iframe>\n",
+  );
+
+  #[test]
+  fn detects_remote_iframes_and_video_links() {
+    let embeds = detect_external_embeds(BODY);
+    let summary: Vec<(EmbedKind, bool)> = embeds
+      .iter()
+      .map(|embed| (embed.kind, embed.iframe))
+      .collect();
+
+    assert_eq!(summary, [
+      (EmbedKind::YouTube, false),
+      (EmbedKind::Vimeo, true),
+      (EmbedKind::Iframe, true),
+    ]);
+  }
+
+  #[test]
+  fn replaces_remote_iframes_with_placeholders() {
+    let replaced = replace_iframe_embeds(BODY, &EmbedPlaceholder::default());
+
+    assert!(!replaced.contains("player.vimeo.com/video/42\"></iframe>"));
+    assert!(replaced.contains("Knots &amp; hitches"));
+    assert!(replaced.contains("This content requires an internet connection."));
+    assert!(replaced.contains(r#"<iframe src="assets/local.html"></iframe>"#));
+    assert!(replaced.contains("https://youtu.be/abc123"));
+  }
+}
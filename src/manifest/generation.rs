@@ -1,30 +1,53 @@
 //! Generate the offline manifest by scanning authored content and assets.
 
 use std::collections::{BTreeMap, BTreeSet};
-use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use crate::asset_paths::make_offline_asset_path;
+use crate::asset_paths::{AssetReferenceFilter, make_offline_asset_path};
 use crate::builder::BuildResult;
-use crate::config::load_document;
+use crate::manifest::captions::pair_caption_sidecars;
 use crate::manifest::markdown::{
-  collect_markdown_asset_references, extract_first_heading, parse_entry_markdown,
-  parse_order_from_id, resolve_markdown_assets,
+  DEFAULT_EXCERPT_LENGTH, collect_heading_anchors, collect_image_alt_texts,
+  collect_markdown_asset_references_with, count_words, extract_excerpt, extract_first_heading,
+  lead_section, parse_entry_markdown, parse_order_from_id, reading_minutes,
+  resolve_markdown_assets,
 };
-use crate::manifest::scanning::{collect_assets_recursively, sanitize_const_name};
+use crate::manifest::scanning::{collect_collection_assets, sanitize_const_name};
+use crate::manifest::source::{SourceEntry, SourceTree};
 use crate::models::{
   AssetCollectionContext, AssetEntry, AssetScanningConfig, CollectionCatalogRecord,
-  CollectionMetaRecord, EntryRecord, ManifestGenerationContext, ManifestGenerationResult,
+  CollectionIntroRecord, CollectionMetaRecord, CollectionSummaryRecord, EntryDocumentRecord,
+  EntryRecord, ExcludedCollectionRecord, ManifestGenerationContext, ManifestGenerationResult,
   OfflineEntryRecord,
 };
 use crate::project::OfflineProjectLayout;
+use crate::report::timed;
 use crate::selection::CollectionInclusion;
 
+/// Markdown files in a collection's root directory read as its intro, in order of preference.
+pub const COLLECTION_INTRO_FILES: [&str; 2] = ["README.md", "intro.md"];
+
 /// Traverse the authored collections and build the intermediate offline manifest data structure.
-pub fn generate_offline_manifest<S: CollectionInclusion>(
+pub fn generate_offline_manifest<S: CollectionInclusion + ?Sized>(
+  layout: &OfflineProjectLayout,
+  collections_dir: &Path,
+  selection: &S,
+) -> BuildResult<ManifestGenerationResult> {
+  generate_offline_manifest_with_filter(
+    layout,
+    collections_dir,
+    selection,
+    &AssetReferenceFilter::default(),
+  )
+}
+
+/// Generate the manifest like [`generate_offline_manifest`], skipping entry references that
+/// `reference_filter` ignores.
+pub fn generate_offline_manifest_with_filter<S: CollectionInclusion + ?Sized>(
   layout: &OfflineProjectLayout,
   collections_dir: &Path,
   selection: &S,
+  reference_filter: &AssetReferenceFilter,
 ) -> BuildResult<ManifestGenerationResult> {
   let mut hero_match_arms = Vec::new();
   let mut asset_map: BTreeMap<(String, String), AssetEntry> = BTreeMap::new();
@@ -32,6 +55,7 @@ pub fn generate_offline_manifest<S: CollectionInclusion>(
   let mut collection_catalog: Vec<CollectionCatalogRecord> = Vec::new();
   let mut offline_entries: Vec<OfflineEntryRecord> = Vec::new();
   let mut hero_asset_paths: BTreeSet<String> = BTreeSet::new();
+  let mut excluded_collections: Vec<ExcludedCollectionRecord> = Vec::new();
 
   let assets_context = AssetCollectionContext {
     asset_map: &mut asset_map,
@@ -44,55 +68,601 @@ pub fn generate_offline_manifest<S: CollectionInclusion>(
     assets: assets_context,
     collection_catalog: &mut collection_catalog,
     offline_entries: &mut offline_entries,
+    excluded_collections: &mut excluded_collections,
+    reference_filter,
   };
 
-  if let Ok(entries) = fs::read_dir(collections_dir) {
-    for entry in entries.flatten() {
-      if !entry.file_type().is_ok_and(|ft| ft.is_dir()) {
+  let sources = SourceTree::default();
+  for entry in sources.list(collections_dir).iter() {
+    if !entry.is_dir || entry.name.starts_with('.') {
+      continue;
+    }
+    walk_collection_tree(
+      layout,
+      &sources,
+      &entry.path,
+      &entry.name,
+      selection,
+      &mut manifest_context,
+    );
+  }
+
+  let mut result = ManifestGenerationResult {
+    collection_catalog,
+    offline_entries,
+    asset_map,
+    hero_asset_paths,
+    hero_match_arms,
+    excluded_collections,
+  };
+  result.sort_catalog();
+  result.resolve_catalog_heroes(layout);
+  Ok(result)
+}
+
+impl ManifestGenerationResult {
+  /// Keep only the collections for which `keep` returns `true`.
+  ///
+  /// Entries, assets and hero lookups belonging to dropped collections are removed so the
+  /// result can be rendered as if it had been generated with a narrower selection.
+  pub fn retain_collections<F>(&mut self, layout: &OfflineProjectLayout, mut keep: F)
+  where
+    F: FnMut(&CollectionCatalogRecord) -> bool,
+  {
+    self.collection_catalog.retain(|record| keep(record));
+    let kept: BTreeSet<&str> = self
+      .collection_catalog
+      .iter()
+      .map(|record| record.id.as_str())
+      .collect();
+
+    self
+      .offline_entries
+      .retain(|entry| kept.contains(entry.collection_id.as_str()));
+    self
+      .asset_map
+      .retain(|(collection_id, _), _| kept.contains(collection_id.as_str()));
+    self
+      .excluded_collections
+      .retain(|record| !kept.contains(record.collection_id.as_str()));
+
+    self.hero_match_arms.clear();
+    self.hero_asset_paths.clear();
+    for record in &self.collection_catalog {
+      let Some(hero_image) = record.meta.hero_image.as_deref() else {
         continue;
+      };
+      let hero_rel = hero_image.trim_start_matches('/').replace('\\', "/");
+      if let Some(entry) = self.asset_map.get(&(record.id.clone(), hero_rel)) {
+        self
+          .hero_match_arms
+          .push(hero_match_arm(&record.id, &entry.const_name));
+        self.hero_asset_paths.insert(make_offline_asset_path(
+          layout,
+          &entry.collection_id,
+          entry.output_relative_path(),
+        ));
       }
+    }
+  }
+}
 
-      let collection_name = entry.file_name().to_string_lossy().to_string();
-      if collection_name.starts_with('.') {
-        continue;
+impl ManifestGenerationResult {
+  /// Recompute every catalog entry's excerpt with a limit of `max_chars` characters.
+  pub fn refresh_excerpts(&mut self, max_chars: usize) {
+    let bodies: BTreeMap<(&str, &str), &str> = self
+      .offline_entries
+      .iter()
+      .map(|entry| {
+        (
+          (entry.collection_id.as_str(), entry.entry_id.as_str()),
+          entry.body.as_str(),
+        )
+      })
+      .collect();
+    for record in &mut self.collection_catalog {
+      for entry in &mut record.entries {
+        if let Some(body) = bodies.get(&(record.id.as_str(), entry.id.as_str())) {
+          entry.excerpt = extract_excerpt(body, max_chars);
+        }
       }
+    }
+  }
+
+  /// Sort the catalog by each collection's `order`, falling back to the numeric prefix of its
+  /// directory name and then its identifier.
+  ///
+  /// Nested collections compare segment by segment, so they stay below their parent and are
+  /// ordered among their siblings.
+  pub(crate) fn sort_catalog(&mut self) {
+    sort_collections(&mut self.collection_catalog, |record| {
+      (&record.id, record.meta.order)
+    });
+  }
 
-      let collection_path = entry.path();
-      walk_collection_tree(
+  /// Fill in the bundle paths of each catalog record's hero image and its variants.
+  ///
+  /// Runs after scanning and again once asset processors have renamed outputs.
+  pub(crate) fn resolve_catalog_heroes(&mut self, layout: &OfflineProjectLayout) {
+    for record in &mut self.collection_catalog {
+      record.hero_asset = None;
+      record.hero_variants.clear();
+      let Some(hero_image) = record.meta.hero_image.as_deref() else {
+        continue;
+      };
+      let hero_rel = hero_image.trim_start_matches('/').replace('\\', "/");
+      let Some(hero) = self.asset_map.get(&(record.id.clone(), hero_rel.clone())) else {
+        continue;
+      };
+      record.hero_asset = Some(make_offline_asset_path(
         layout,
-        &collection_path,
-        &collection_name,
-        selection,
-        &mut manifest_context,
+        &hero.collection_id,
+        hero.output_relative_path(),
+      ));
+
+      let (parent, file_name) = match hero_rel.rsplit_once('/') {
+        Some((parent, file_name)) => (format!("{parent}/"), file_name),
+        None => (String::new(), hero_rel.as_str()),
+      };
+      let stem = file_name
+        .rsplit_once('.')
+        .map_or(file_name, |(stem, _)| stem);
+      for ((collection_id, relative_path), entry) in &self.asset_map {
+        if *collection_id != record.id || *relative_path == hero_rel {
+          continue;
+        }
+        let Some(variant) = relative_path
+          .strip_prefix(&parent)
+          .filter(|name| !name.contains('/'))
+          .and_then(|name| name.strip_prefix(stem))
+          .and_then(|rest| rest.strip_prefix(['-', '_', '.', '@']))
+        else {
+          continue;
+        };
+        let suffix = variant
+          .rsplit_once('.')
+          .map_or(variant, |(suffix, _)| suffix);
+        if suffix.is_empty() || !variant.contains('.') {
+          continue;
+        }
+        record.hero_variants.insert(
+          suffix.to_string(),
+          make_offline_asset_path(layout, collection_id, entry.output_relative_path()),
+        );
+      }
+    }
+  }
+}
+
+/// Sort collection records by the explicit order and id that `key` returns for each of them.
+///
+/// See [`ManifestGenerationResult::sort_catalog`] for the ordering rules.
+fn sort_collections<T>(records: &mut [T], key: impl Fn(&T) -> (&str, Option<usize>)) {
+  let explicit: BTreeMap<String, usize> = records
+    .iter()
+    .filter_map(|record| {
+      let (id, order) = key(record);
+      Some((id.to_string(), order?))
+    })
+    .collect();
+  let sort_key = |id: &str| -> Vec<(usize, String)> {
+    let mut prefix = String::new();
+    id.split('/')
+      .map(|segment| {
+        if !prefix.is_empty() {
+          prefix.push('/');
+        }
+        prefix.push_str(segment);
+        let order = explicit
+          .get(&prefix)
+          .copied()
+          .or_else(|| parse_order_from_id(segment))
+          .unwrap_or(usize::MAX);
+        (order, segment.to_string())
+      })
+      .collect()
+  };
+  records.sort_by_cached_key(|record| sort_key(key(record).0));
+}
+
+/// List every collection below `collections_dir` with its metadata and entry count, in catalog
+/// order.
+///
+/// Only metadata files and directory listings are read: no markdown is parsed, no assets are
+/// scanned and nothing is written, so this is cheap enough for selection UIs and CI reports.
+pub fn discover_collections(
+  layout: &OfflineProjectLayout,
+  collections_dir: &Path,
+) -> Vec<CollectionSummaryRecord> {
+  let mut collections = Vec::new();
+  let sources = SourceTree::default();
+  for entry in sources.list(collections_dir).iter() {
+    if entry.is_dir && !entry.name.starts_with('.') {
+      discover_collection_tree(layout, &sources, &entry.path, &entry.name, &mut collections);
+    }
+  }
+  sort_collections(&mut collections, |record| (&record.id, record.meta.order));
+  collections
+}
+
+fn discover_collection_tree(
+  parent_layout: &OfflineProjectLayout,
+  sources: &SourceTree,
+  collection_path: &Path,
+  collection_id: &str,
+  collections: &mut Vec<CollectionSummaryRecord>,
+) {
+  let mut collection_layout = parent_layout.clone();
+  if let Some((payload, overrides)) = sources
+    .metadata_path(collection_path, &parent_layout.collection_metadata_file)
+    .and_then(|path| sources.document(&path))
+  {
+    overrides.apply_to_layout(&mut collection_layout);
+    if let Ok(meta) = serde_json::from_value(payload) {
+      collections.push(CollectionSummaryRecord {
+        id: collection_id.to_string(),
+        meta,
+        entry_count: find_entry_dirs(&collection_layout, sources, collection_path).len(),
+      });
+    }
+  }
+
+  for child in sources.list(collection_path).iter() {
+    if is_nested_collection(&collection_layout, sources, child) {
+      discover_collection_tree(
+        &collection_layout,
+        sources,
+        &child.path,
+        &format!("{collection_id}/{}", child.name),
+        collections,
       );
     }
   }
+}
 
-  Ok(ManifestGenerationResult {
-    collection_catalog,
-    offline_entries,
-    asset_map,
-    hero_asset_paths,
-    hero_match_arms,
+/// Whether `child` of a collection directory is a nested collection with its own metadata.
+fn is_nested_collection(
+  layout: &OfflineProjectLayout,
+  sources: &SourceTree,
+  child: &SourceEntry,
+) -> bool {
+  child.is_dir
+    && !child.name.starts_with('.')
+    && sources
+      .metadata_path(&child.path, &layout.collection_metadata_file)
+      .is_some()
+}
+
+fn hero_match_arm(collection_id: &str, const_name: &str) -> String {
+  let collection_literal = serde_json::to_string(collection_id).unwrap();
+  format!("        {} => Some(&{}),", collection_literal, const_name)
+}
+
+/// Parse the first of [`COLLECTION_INTRO_FILES`] found in `collection_path`.
+///
+/// Its asset references resolve like those of an entry placed at the collection root.
+fn load_collection_intro(
+  layout: &OfflineProjectLayout,
+  sources: &SourceTree,
+  collection_path: &Path,
+  collection_id: &str,
+  asset_slug: Option<&str>,
+  context: &ManifestGenerationContext,
+) -> Option<CollectionIntroRecord> {
+  let (file_name, (frontmatter, body)) = COLLECTION_INTRO_FILES.iter().find_map(|file_name| {
+    if !sources.has_file(collection_path, file_name) {
+      return None;
+    }
+    timed("markdown", || {
+      parse_entry_markdown(&collection_path.join(file_name))
+    })
+    .map(|parsed| (file_name, parsed))
+  })?;
+
+  let references = collect_markdown_asset_references_with(&body, context.reference_filter);
+  let (asset_paths, unresolved_assets) = resolve_markdown_assets(
+    layout,
+    &references,
+    context.assets.asset_map,
+    collection_id,
+    "",
+    asset_slug,
+  );
+  for unresolved in unresolved_assets {
+    println!(
+      "cargo:warning=Unresolved offline asset reference '{}' in {}/{}",
+      unresolved, collection_id, file_name
+    );
+  }
+
+  Some(CollectionIntroRecord {
+    source: format!("{collection_id}/{file_name}"),
+    title: frontmatter.title.or_else(|| extract_first_heading(&body)),
+    body,
+    asset_paths,
   })
 }
 
-fn walk_collection_tree<S: CollectionInclusion>(
+/// Entry directories of a collection with their identifiers and, for entries one level below a
+/// group directory, the group.
+///
+/// Directories without an entry markdown file group the entries one level below them, unless
+/// they are nested collections, which are walked on their own.
+fn find_entry_dirs(
+  layout: &OfflineProjectLayout,
+  sources: &SourceTree,
+  collection_path: &Path,
+) -> Vec<EntryDir> {
+  let is_candidate = |entry: &SourceEntry| {
+    entry.is_dir && !entry.name.starts_with('.') && entry.name != layout.entry_assets_dir
+  };
+  let mut entry_dirs = Vec::new();
+  for entry in sources.list(collection_path).iter() {
+    if !is_candidate(entry) {
+      continue;
+    }
+    if sources.has_file(&entry.path, &layout.entry_markdown_file) {
+      entry_dirs.push(EntryDir {
+        id: entry.name.clone(),
+        path: entry.path.clone(),
+        group: None,
+      });
+      continue;
+    }
+    if sources
+      .metadata_path(&entry.path, &layout.collection_metadata_file)
+      .is_some()
+    {
+      continue;
+    }
+    for child in sources.list(&entry.path).iter() {
+      if is_candidate(child) && sources.has_file(&child.path, &layout.entry_markdown_file) {
+        let group = EntryGroup {
+          order: parse_order_from_id(&entry.name).unwrap_or(usize::MAX),
+          section: group_section_title(&entry.name),
+        };
+        entry_dirs.push(EntryDir {
+          id: format!("{}/{}", entry.name, child.name),
+          path: child.path.clone(),
+          group: Some(group),
+        });
+      }
+    }
+  }
+  entry_dirs
+}
+
+/// Entry directory found by [`find_entry_dirs`].
+struct EntryDir {
+  /// Entry identifier, `group/child` for grouped entries.
+  id: String,
+  path: PathBuf,
+  group: Option<EntryGroup>,
+}
+
+/// Directory grouping entries one level below the collection root.
+struct EntryGroup {
+  /// Order parsed from the directory name, applied before the order of its entries.
+  order: usize,
+  /// Section assigned to grouped entries whose front matter names none.
+  section: String,
+}
+
+/// Section title for a group directory: its name without the numeric order prefix, with
+/// dashes and underscores turned into spaces (`03-engines` becomes `engines`).
+fn group_section_title(dir_name: &str) -> String {
+  let name = dir_name
+    .trim_start_matches(|c: char| c.is_ascii_digit())
+    .trim_start_matches(['-', '_', '.', ' ']);
+  let name = if name.is_empty() { dir_name } else { name };
+  name.replace(['-', '_'], " ")
+}
+
+/// Parse the markdown files in `entry_path` other than the entry markdown itself.
+///
+/// Their asset references resolve exactly like those of the entry body.
+fn load_entry_documents(
+  layout: &OfflineProjectLayout,
+  sources: &SourceTree,
+  collection_id: &str,
+  entry_id: &str,
+  entry_path: &Path,
+  asset_slug: Option<&str>,
+  context: &ManifestGenerationContext,
+) -> Vec<EntryDocumentRecord> {
+  let mut documents: Vec<EntryDocumentRecord> = sources
+    .list(entry_path)
+    .iter()
+    .filter_map(|file| {
+      let file_name = &file.name;
+      let name = file_name.strip_suffix(".md")?;
+      if name.is_empty()
+        || name.starts_with('.')
+        || *file_name == layout.entry_markdown_file
+        || !file.is_file
+      {
+        return None;
+      }
+      let (frontmatter, body) = timed("markdown", || parse_entry_markdown(&file.path))?;
+
+      let references = collect_markdown_asset_references_with(&body, context.reference_filter);
+      let (asset_paths, unresolved_assets) = resolve_markdown_assets(
+        layout,
+        &references,
+        context.assets.asset_map,
+        collection_id,
+        entry_id,
+        asset_slug,
+      );
+      for unresolved in unresolved_assets {
+        println!(
+          "cargo:warning=Unresolved offline asset reference '{}' in {}/{}/{}",
+          unresolved, collection_id, entry_id, file_name
+        );
+      }
+
+      Some(EntryDocumentRecord {
+        name: name.to_string(),
+        title: frontmatter.title.or_else(|| extract_first_heading(&body)),
+        body,
+        asset_paths,
+      })
+    })
+    .collect();
+  documents.sort_by(|a, b| a.name.cmp(&b.name));
+  documents
+}
+
+/// Parse the entry in `entry_dir`, record its offline entry and return its catalog record
+/// with the key it is sorted by.
+fn load_entry(
+  layout: &OfflineProjectLayout,
+  sources: &SourceTree,
+  collection_id: &str,
+  entry_dir: &EntryDir,
+  asset_slug: Option<&str>,
+  context: &mut ManifestGenerationContext,
+) -> Option<((usize, usize), EntryRecord)> {
+  let entry_id = entry_dir.id.as_str();
+  let entry_path = entry_dir.path.as_path();
+  let group = entry_dir.group.as_ref();
+  let (frontmatter, body) = timed("markdown", || {
+    parse_entry_markdown(&entry_path.join(&layout.entry_markdown_file))
+  })?;
+  let entry_title = frontmatter
+    .title
+    .clone()
+    .or_else(|| extract_first_heading(&body))
+    .unwrap_or_else(|| entry_id.to_string());
+
+  let entry_name = entry_id.rsplit('/').next().unwrap_or(entry_id);
+  let order = frontmatter
+    .order
+    .or_else(|| parse_order_from_id(entry_name))
+    .unwrap_or(usize::MAX);
+  let sort_key = match group {
+    Some(group) => (group.order, order),
+    None => (order, 0),
+  };
+  let section = frontmatter
+    .section
+    .clone()
+    .or_else(|| group.map(|group| group.section.clone()));
+
+  let references = collect_markdown_asset_references_with(&body, context.reference_filter);
+  let (mut resolved_assets, unresolved_assets) = resolve_markdown_assets(
+    layout,
+    &references,
+    context.assets.asset_map,
+    collection_id,
+    entry_id,
+    asset_slug,
+  );
+  let mut priority_references =
+    collect_markdown_asset_references_with(lead_section(&body), context.reference_filter);
+  priority_references.extend(frontmatter.priority_assets.iter().cloned());
+  let (priority_assets, unresolved_priority) = resolve_markdown_assets(
+    layout,
+    &priority_references,
+    context.assets.asset_map,
+    collection_id,
+    entry_id,
+    asset_slug,
+  );
+  let unresolved_assets = unresolved_assets.into_iter().chain(
+    unresolved_priority
+      .into_iter()
+      .filter(|reference| !references.contains(reference)),
+  );
+
+  for unresolved in unresolved_assets {
+    println!(
+      "cargo:warning=Unresolved offline asset reference '{}' in {}/{}",
+      unresolved, collection_id, entry_id
+    );
+  }
+
+  let word_count = count_words(&body);
+  let offline_body = pair_caption_sidecars(
+    layout,
+    context.assets.asset_map,
+    collection_id,
+    entry_id,
+    asset_slug,
+    &body,
+    &mut resolved_assets,
+  );
+  context.offline_entries.push(OfflineEntryRecord {
+    collection_id: collection_id.to_string(),
+    entry_id: entry_id.to_string(),
+    body: offline_body,
+    asset_paths: resolved_assets,
+    word_count,
+    reading_minutes: reading_minutes(word_count),
+    headings: collect_heading_anchors(&body),
+    images: collect_image_alt_texts(&body),
+    documents: load_entry_documents(
+      layout,
+      sources,
+      collection_id,
+      entry_id,
+      entry_path,
+      asset_slug,
+      context,
+    ),
+    priority_assets,
+    video_posters: BTreeMap::new(),
+  });
+
+  Some((sort_key, EntryRecord {
+    id: entry_id.to_string(),
+    title: entry_title,
+    section,
+    sequence: order,
+    source: format!(
+      "{}/{}/{}",
+      collection_id, entry_id, layout.entry_markdown_file
+    ),
+    word_count,
+    reading_minutes: reading_minutes(word_count),
+    excerpt: extract_excerpt(&body, DEFAULT_EXCERPT_LENGTH),
+  }))
+}
+
+fn walk_collection_tree<S: CollectionInclusion + ?Sized>(
   parent_layout: &OfflineProjectLayout,
+  sources: &SourceTree,
   collection_path: &Path,
   collection_id: &str,
   selection: &S,
   context: &mut ManifestGenerationContext,
 ) {
-  let metadata_path = collection_path.join(&parent_layout.collection_metadata_file);
   let mut collection_layout = parent_layout.clone();
   let mut meta: Option<CollectionMetaRecord> = None;
 
-  if let Some((payload, overrides)) = load_document(&metadata_path) {
+  if let Some((payload, overrides)) = sources
+    .metadata_path(collection_path, &parent_layout.collection_metadata_file)
+    .and_then(|path| sources.document(&path))
+  {
     overrides.apply_to_layout(&mut collection_layout);
     meta = serde_json::from_value(payload).ok();
   }
 
+  if let Some(record) = meta.as_ref()
+    && selection.is_included(collection_id)
+    && let Some(reason) = selection.version_rejection(collection_id, record.version.as_deref())
+  {
+    println!(
+      "cargo:warning=Excluding collection '{}': {}",
+      collection_id, reason
+    );
+    context.excluded_collections.push(ExcludedCollectionRecord {
+      collection_id: collection_id.to_string(),
+      reason,
+    });
+    meta = None;
+  }
+
   if let Some(meta) = meta
     && selection.is_included(collection_id)
   {
@@ -103,17 +673,21 @@ fn walk_collection_tree<S: CollectionInclusion>(
       excluded_path_fragment: &collection_layout.excluded_path_fragment,
       collection_asset_literal_prefix: &collection_layout.collection_asset_literal_prefix,
       collection_metadata_file: collection_layout.collection_metadata_file.as_str(),
+      max_depth: collection_layout.scan_max_depth,
+      asset_extensions: &collection_layout.asset_extensions,
+      excluded_extensions: &collection_layout.excluded_extensions,
     };
 
-    collect_assets_recursively(
-      collection_id,
-      collection_path,
-      Path::new(""),
-      false,
-      context.assets.asset_map,
-      context.assets.used_names,
-      &scanning_config,
-    );
+    timed("assets", || {
+      collect_collection_assets(
+        collection_id,
+        collection_path,
+        context.assets.asset_map,
+        context.assets.used_names,
+        &scanning_config,
+        sources,
+      )
+    });
 
     if let Some(hero_image) = meta.hero_image.as_deref() {
       let hero_rel = hero_image.trim_start_matches('/').replace('\\', "/");
@@ -137,6 +711,10 @@ fn walk_collection_tree<S: CollectionInclusion>(
               literal_path: asset_path,
               collection_id: collection_id.to_string(),
               relative_path: hero_rel.clone(),
+              output_path: None,
+              source_file: None,
+              size: None,
+              modified: None,
             }
           });
 
@@ -145,11 +723,10 @@ fn walk_collection_tree<S: CollectionInclusion>(
           .asset_map
           .get(&(collection_id.to_string(), hero_rel.clone()))
         {
-          let collection_literal = serde_json::to_string(collection_id).unwrap();
-          context.assets.hero_match_arms.push(format!(
-            "        {} => Some(&{}),",
-            collection_literal, entry.const_name
-          ));
+          context
+            .assets
+            .hero_match_arms
+            .push(hero_match_arm(collection_id, &entry.const_name));
           context
             .assets
             .hero_asset_paths
@@ -162,78 +739,19 @@ fn walk_collection_tree<S: CollectionInclusion>(
       }
     }
 
-    let mut entry_records: Vec<(usize, EntryRecord)> = Vec::new();
-
-    if let Ok(entry_iter) = fs::read_dir(collection_path) {
-      for entry_dir in entry_iter.flatten() {
-        let entry_path = entry_dir.path();
-
-        if !entry_path.is_dir() {
-          continue;
-        }
-
-        let entry_id = entry_dir.file_name().to_string_lossy().to_string();
+    let mut entry_records: Vec<((usize, usize), EntryRecord)> = Vec::new();
+    let asset_slug = meta.asset_slug.as_deref();
 
-        if entry_id.starts_with('.') || entry_id == collection_layout.entry_assets_dir {
-          continue;
-        }
-
-        let markdown_path = entry_path.join(&collection_layout.entry_markdown_file);
-        if !markdown_path.exists() {
-          continue;
-        }
-
-        if let Some((frontmatter, body)) = parse_entry_markdown(&markdown_path) {
-          let entry_title = frontmatter
-            .title
-            .clone()
-            .or_else(|| extract_first_heading(&body))
-            .unwrap_or_else(|| entry_id.clone());
-
-          let order = frontmatter
-            .order
-            .or_else(|| parse_order_from_id(&entry_id))
-            .unwrap_or(usize::MAX);
-
-          let asset_slug = meta.asset_slug.as_deref();
-
-          let references = collect_markdown_asset_references(&body);
-          let (resolved_assets, unresolved_assets) = resolve_markdown_assets(
-            &collection_layout,
-            &references,
-            context.assets.asset_map,
-            collection_id,
-            &entry_id,
-            asset_slug,
-          );
-
-          if !unresolved_assets.is_empty() {
-            for unresolved in unresolved_assets {
-              println!(
-                "cargo:warning=Unresolved offline asset reference '{}' in {}/{}",
-                unresolved, collection_id, entry_id
-              );
-            }
-          }
-
-          context.offline_entries.push(OfflineEntryRecord {
-            collection_id: collection_id.to_string(),
-            entry_id: entry_id.clone(),
-            body: body.clone(),
-            asset_paths: resolved_assets,
-          });
-
-          entry_records.push((order, EntryRecord {
-            id: entry_id.clone(),
-            title: entry_title,
-            section: frontmatter.section.clone(),
-            sequence: order,
-            source: format!(
-              "{}/{}/{}",
-              collection_id, entry_id, collection_layout.entry_markdown_file
-            ),
-          }));
-        }
+    for entry_dir in find_entry_dirs(&collection_layout, sources, collection_path) {
+      if let Some(record) = load_entry(
+        &collection_layout,
+        sources,
+        collection_id,
+        &entry_dir,
+        asset_slug,
+        context,
+      ) {
+        entry_records.push(record);
       }
     }
 
@@ -252,46 +770,44 @@ fn walk_collection_tree<S: CollectionInclusion>(
       })
       .collect();
 
+    let intro = load_collection_intro(
+      &collection_layout,
+      sources,
+      collection_path,
+      collection_id,
+      meta.asset_slug.as_deref(),
+      context,
+    );
+
     context.collection_catalog.push(CollectionCatalogRecord {
       id: collection_id.to_string(),
       meta,
       entries,
+      hero_asset: None,
+      hero_variants: BTreeMap::new(),
+      intro,
     });
   }
 
-  if let Ok(children) = fs::read_dir(collection_path) {
-    for child in children.flatten() {
-      if !child.file_type().is_ok_and(|ft| ft.is_dir()) {
-        continue;
-      }
-
-      let name = child.file_name().to_string_lossy().to_string();
-      if name.starts_with('.') {
-        continue;
-      }
-
-      let child_path = child.path();
-      if !child_path
-        .join(&collection_layout.collection_metadata_file)
-        .exists()
-      {
-        continue;
-      }
+  for child in sources.list(collection_path).iter() {
+    if !is_nested_collection(&collection_layout, sources, child) {
+      continue;
+    }
 
-      let child_id = if collection_id.is_empty() {
-        name.clone()
-      } else {
-        format!("{}/{}", collection_id, name)
-      };
+    let child_id = if collection_id.is_empty() {
+      child.name.clone()
+    } else {
+      format!("{}/{}", collection_id, child.name)
+    };
 
-      walk_collection_tree(
-        &collection_layout,
-        &child_path,
-        &child_id,
-        selection,
-        context,
-      );
-    }
+    walk_collection_tree(
+      &collection_layout,
+      sources,
+      &child.path,
+      &child_id,
+      selection,
+      context,
+    );
   }
 }
 
@@ -300,6 +816,7 @@ mod tests {
   use super::*;
   use crate::project::OfflineProjectLayout;
   use crate::selection::CollectionInclusion;
+  use std::fs;
   use tempfile::tempdir;
 
   impl CollectionInclusion for () {
@@ -319,9 +836,13 @@ mod tests {
       offline_site_root: "site".into(),
       collections_dir_name: "programs".into(),
       offline_bundle_root: "target/offline-html".into(),
+      output_root: None,
       index_html_file: "index.html".into(),
       target_dir: "target".into(),
       offline_manifest_json: "offline_manifest.json".into(),
+      scan_max_depth: 32,
+      asset_extensions: Vec::new(),
+      excluded_extensions: Vec::new(),
     }
   }
 
@@ -374,7 +895,7 @@ mod tests {
     assert!(
       result
         .asset_map
-        .contains_key(&("p001-intro".into(), "assets/image.png".into()))
+        .contains_key(&("p001-intro".into(), "001-welcome/assets/image.png".into()))
     );
     assert!(
       result
@@ -383,4 +904,295 @@ mod tests {
     );
     assert!(!result.hero_match_arms.is_empty());
   }
+
+  #[test]
+  fn marks_lead_and_front_matter_assets_as_priority() {
+    let dir = tempdir().unwrap();
+    let entry_dir = dir.path().join("p001/001-welcome");
+    write_file(&dir.path().join("p001/collection.json"), r#"{"title":"P"}"#);
+    for name in ["hero.png", "diagram.png", "clip.mp4"] {
+      write_file(&entry_dir.join("assets").join(name), name);
+    }
+    write_file(
+      &entry_dir.join("index.md"),
+      "---
+priority_assets: [clip.mp4]
+---
+# Welcome
+
+![Hero](hero.png)
+
+## Steps
+
+![Diagram](diagram.png)
+[Clip](clip.mp4)
+",
+    );
+    assert_eq!(
+      lead_section(
+        "# Title
+
+Intro
+
+## Next
+"
+      ),
+      "# Title
+
+Intro
+
+"
+    );
+
+    let result = generate_offline_manifest(&layout(), dir.path(), &()).unwrap();
+    let entry = &result.offline_entries[0];
+    assert_eq!(entry.asset_paths.len(), 3);
+    assert_eq!(entry.priority_assets, [
+      "programs/p001/001-welcome/assets/clip.mp4",
+      "programs/p001/001-welcome/assets/hero.png"
+    ]);
+  }
+
+  #[test]
+  fn catalog_records_hero_bundle_paths_and_variants() {
+    let dir = tempdir().unwrap();
+    let collections_dir = dir.path();
+    let collection_dir = collections_dir.join("P001");
+    write_file(
+      &collection_dir.join("collection.json"),
+      r#"{"title":"Intro","heroImage":"/assets/cover.png"}"#,
+    );
+    write_file(&collection_dir.join("assets/cover.png"), "hero");
+    write_file(&collection_dir.join("assets/cover-thumb.webp"), "thumb");
+    write_file(&collection_dir.join("assets/cover@2x.png"), "large");
+    write_file(&collection_dir.join("assets/covers.png"), "other");
+
+    let result = generate_offline_manifest(&layout(), collections_dir, &()).unwrap();
+    let record = &result.collection_catalog[0];
+
+    assert_eq!(
+      record.hero_asset.as_deref(),
+      Some("programs/P001/assets/cover.png")
+    );
+    assert_eq!(
+      record.hero_variants,
+      BTreeMap::from([
+        (
+          "2x".to_string(),
+          "programs/P001/assets/cover@2x.png".to_string()
+        ),
+        (
+          "thumb".to_string(),
+          "programs/P001/assets/cover-thumb.webp".to_string()
+        ),
+      ])
+    );
+    let json = serde_json::to_string(&result.collection_catalog).unwrap();
+    assert!(json.contains(r#""hero_asset":"programs/P001/assets/cover.png""#));
+  }
+
+  #[test]
+  fn reads_collection_root_intro_outside_entries() {
+    let dir = tempdir().unwrap();
+    let collection_dir = dir.path().join("P001");
+    write_file(
+      &collection_dir.join("collection.json"),
+      r#"{"title":"Intro"}"#,
+    );
+    write_file(
+      &collection_dir.join("README.md"),
+      "# About this course\n\n![Map](assets/map.png)\n",
+    );
+    write_file(&collection_dir.join("intro.md"), "# Ignored\n");
+    write_file(&collection_dir.join("assets/map.png"), "map");
+    write_file(&collection_dir.join("001-start/index.md"), "# Start\n");
+
+    let result = generate_offline_manifest(&layout(), dir.path(), &()).unwrap();
+    let record = &result.collection_catalog[0];
+    let intro = record.intro.as_ref().unwrap();
+
+    assert_eq!(intro.source, "P001/README.md");
+    assert_eq!(intro.title.as_deref(), Some("About this course"));
+    assert_eq!(intro.asset_paths, ["programs/P001/assets/map.png"]);
+    assert_eq!(record.entries.len(), 1);
+    assert_eq!(record.entries[0].sequence, 1);
+  }
+
+  #[test]
+  fn maps_entry_group_directories_to_sections() {
+    let dir = tempdir().unwrap();
+    let collection_dir = dir.path().join("P001");
+    write_file(
+      &collection_dir.join("collection.json"),
+      r#"{"title":"Course"}"#,
+    );
+    write_file(&collection_dir.join("01-intro/index.md"), "# Intro\n");
+    write_file(
+      &collection_dir.join("03-engines/02-tuning/index.md"),
+      "# Tuning\n\n![Chart](chart.png)\n",
+    );
+    write_file(
+      &collection_dir.join("03-engines/02-tuning/assets/chart.png"),
+      "chart",
+    );
+    write_file(
+      &collection_dir.join("03-engines/01-overview/index.md"),
+      "---\nsection: Powertrain\n---\n# Overview\n",
+    );
+    write_file(&collection_dir.join("04-wrap-up/index.md"), "# Wrap up\n");
+
+    let result = generate_offline_manifest(&layout(), dir.path(), &()).unwrap();
+    let entries = &result.collection_catalog[0].entries;
+
+    let order: Vec<(&str, Option<&str>)> = entries
+      .iter()
+      .map(|entry| (entry.id.as_str(), entry.section.as_deref()))
+      .collect();
+    assert_eq!(order, [
+      ("01-intro", None),
+      ("03-engines/01-overview", Some("Powertrain")),
+      ("03-engines/02-tuning", Some("engines")),
+      ("04-wrap-up", None),
+    ]);
+    assert_eq!(entries[2].sequence, 3);
+    assert_eq!(entries[2].source, "P001/03-engines/02-tuning/index.md");
+    let tuning = result
+      .offline_entries
+      .iter()
+      .find(|entry| entry.entry_id == "03-engines/02-tuning")
+      .unwrap();
+    assert_eq!(tuning.asset_paths, [
+      "programs/P001/03-engines/02-tuning/assets/chart.png"
+    ]);
+  }
+
+  #[test]
+  fn parses_supplementary_entry_documents() {
+    let dir = tempdir().unwrap();
+    let entry_dir = dir.path().join("P001/001-start");
+    write_file(
+      &dir.path().join("P001/collection.json"),
+      r#"{"title":"Course"}"#,
+    );
+    write_file(&entry_dir.join("index.md"), "# Start\n");
+    write_file(
+      &entry_dir.join("quiz.md"),
+      "---\ntitle: Check yourself\n---\n![Q1](q1.png)\n",
+    );
+    write_file(&entry_dir.join("notes.md"), "# Notes\n");
+    write_file(&entry_dir.join("assets/q1.png"), "q1");
+
+    let result = generate_offline_manifest(&layout(), dir.path(), &()).unwrap();
+    let documents = &result.offline_entries[0].documents;
+
+    let names: Vec<(&str, Option<&str>)> = documents
+      .iter()
+      .map(|document| (document.name.as_str(), document.title.as_deref()))
+      .collect();
+    assert_eq!(names, [
+      ("notes", Some("Notes")),
+      ("quiz", Some("Check yourself"))
+    ]);
+    assert_eq!(documents[1].asset_paths, [
+      "programs/P001/001-start/assets/q1.png"
+    ]);
+    assert!(result.offline_entries[0].asset_paths.is_empty());
+  }
+
+  #[test]
+  fn sorts_catalog_by_order_then_directory_prefix() {
+    let dir = tempdir().unwrap();
+    for (path, meta) in [
+      ("10-advanced", r#"{"title":"Advanced"}"#),
+      ("2-basics", r#"{"title":"Basics"}"#),
+      ("welcome", r#"{"title":"Welcome","order":1}"#),
+      ("extras", r#"{"title":"Extras"}"#),
+      ("2-basics/02-drills", r#"{"title":"Drills"}"#),
+      ("2-basics/01-theory", r#"{"title":"Theory"}"#),
+    ] {
+      write_file(&dir.path().join(path).join("collection.json"), meta);
+    }
+
+    let result = generate_offline_manifest(&layout(), dir.path(), &()).unwrap();
+    let ids: Vec<&str> = result
+      .collection_catalog
+      .iter()
+      .map(|record| record.id.as_str())
+      .collect();
+
+    assert_eq!(ids, [
+      "welcome",
+      "2-basics",
+      "2-basics/01-theory",
+      "2-basics/02-drills",
+      "10-advanced",
+      "extras",
+    ]);
+  }
+
+  #[test]
+  fn discovers_collections_without_scanning() {
+    let dir = tempdir().unwrap();
+    write_file(
+      &dir.path().join("2-basics/collection.json"),
+      r#"{"title":"Basics","version":"1.0"}"#,
+    );
+    write_file(&dir.path().join("2-basics/01-start/index.md"), "# Start\n");
+    write_file(&dir.path().join("2-basics/02-group/01-a/index.md"), "# A\n");
+    write_file(&dir.path().join("2-basics/assets/cover.png"), "cover");
+    write_file(
+      &dir.path().join("1-intro/collection.json"),
+      r#"{"title":"Intro"}"#,
+    );
+    write_file(&dir.path().join("drafts/notes.txt"), "not a collection");
+
+    let collections = discover_collections(&layout(), dir.path());
+
+    let summary: Vec<(&str, &str, usize)> = collections
+      .iter()
+      .map(|record| {
+        (
+          record.id.as_str(),
+          record.meta.title.as_str(),
+          record.entry_count,
+        )
+      })
+      .collect();
+    assert_eq!(summary, [
+      ("1-intro", "Intro", 0),
+      ("2-basics", "Basics", 2)
+    ]);
+    assert_eq!(collections[1].meta.version.as_deref(), Some("1.0"));
+  }
+
+  #[test]
+  fn excludes_collections_failing_version_requirements() {
+    let dir = tempdir().unwrap();
+    let collections_dir = dir.path();
+    write_file(
+      &collections_dir.join("P001/collection.json"),
+      r#"{"title":"Old","version":"1.4"}"#,
+    );
+    write_file(
+      &collections_dir.join("P002/collection.json"),
+      r#"{"title":"New","version":"2.0.1"}"#,
+    );
+    let selection_path = collections_dir.join("collections.local.json");
+    write_file(
+      &selection_path,
+      r#"{"minVersion": {"P001": ">=2.0", "P002": ">=2.0"}}"#,
+    );
+
+    let selection = crate::selection::CollectionSelection::load_from_path(&selection_path).unwrap();
+    let result = generate_offline_manifest(&layout(), collections_dir, &selection).unwrap();
+
+    let ids: Vec<&str> = result
+      .collection_catalog
+      .iter()
+      .map(|collection| collection.id.as_str())
+      .collect();
+    assert_eq!(ids, vec!["P002"]);
+    assert_eq!(result.excluded_collections.len(), 1);
+    assert_eq!(result.excluded_collections[0].collection_id, "P001");
+  }
 }
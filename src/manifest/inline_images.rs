@@ -0,0 +1,305 @@
+//! Inline small images referenced from entry bodies as `data:` URIs.
+//!
+//! Bundles with hundreds of tiny icons spend most of their file count, and on exFAT most of
+//! their cluster slack, on files of a few hundred bytes. [`inline_small_images`] rewrites every
+//! body reference to an image below the size limit into a base64 `data:` URI and drops images no
+//! longer referenced anywhere from the asset map, so the mirror never copies them.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+use base64::{Engine as _, engine::general_purpose};
+use regex::{Captures, Regex};
+
+use crate::asset_paths::{AssetReferenceFilter, make_offline_asset_path};
+use crate::builder::BuildResult;
+use crate::manifest::markdown::{collect_markdown_asset_references_with, resolve_markdown_assets};
+use crate::models::ManifestGenerationResult;
+use crate::project::OfflineProjectLayout;
+
+/// Image extensions eligible for inlining and their media types.
+const IMAGE_TYPES: [(&str, &str); 8] = [
+  ("png", "image/png"),
+  ("jpg", "image/jpeg"),
+  ("jpeg", "image/jpeg"),
+  ("gif", "image/gif"),
+  ("webp", "image/webp"),
+  ("avif", "image/avif"),
+  ("svg", "image/svg+xml"),
+  ("ico", "image/x-icon"),
+];
+
+/// Collection asset replaced by `data:` URIs in the entry bodies.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InlinedImage {
+  /// Collection the asset belongs to.
+  pub collection_id: String,
+  /// Relative path of the asset within the collection.
+  pub relative_path: String,
+  /// Size of the image in bytes.
+  pub size: u64,
+  /// Whether the asset was dropped from the asset map because nothing else references it, e.g.
+  /// a hero image, an intro or an entry document.
+  pub removed: bool,
+}
+
+/// Media type of an image path eligible for inlining, by extension.
+pub fn image_media_type(path: &str) -> Option<&'static str> {
+  let extension = path.rsplit_once('.')?.1.to_ascii_lowercase();
+  IMAGE_TYPES
+    .iter()
+    .find(|(candidate, _)| *candidate == extension)
+    .map(|(_, media_type)| *media_type)
+}
+
+/// Base64 `data:` URI of an image's bytes, or `None` when `path` is not an inlinable image.
+pub fn image_data_uri(path: &str, bytes: &[u8]) -> Option<String> {
+  let media_type = image_media_type(path)?;
+  Some(format!(
+    "data:{media_type};base64,{}",
+    general_purpose::STANDARD.encode(bytes)
+  ))
+}
+
+/// Replace references to images of at most `max_bytes` in every entry body with `data:` URIs.
+///
+/// Inlined images leave the entry's asset and priority asset paths; those referenced nowhere
+/// else are removed from the asset map. Image records keep the authored path for reports.
+pub fn inline_small_images(
+  layout: &OfflineProjectLayout,
+  collections_dir: &Path,
+  manifest: &mut ManifestGenerationResult,
+  filter: &AssetReferenceFilter,
+  max_bytes: u64,
+) -> BuildResult<Vec<InlinedImage>> {
+  let asset_slugs: BTreeMap<&str, Option<&str>> = manifest
+    .collection_catalog
+    .iter()
+    .map(|record| (record.id.as_str(), record.meta.asset_slug.as_deref()))
+    .collect();
+  let keys_by_path: BTreeMap<String, (String, String)> = manifest
+    .asset_map
+    .iter()
+    .map(|(key, entry)| {
+      let path =
+        make_offline_asset_path(layout, &entry.collection_id, entry.output_relative_path());
+      (path, key.clone())
+    })
+    .collect();
+  let mut data_uris: BTreeMap<(String, String), Option<(String, u64)>> = BTreeMap::new();
+
+  for entry in &mut manifest.offline_entries {
+    let asset_slug = asset_slugs
+      .get(entry.collection_id.as_str())
+      .copied()
+      .flatten();
+    for reference in collect_markdown_asset_references_with(&entry.body, filter) {
+      let (resolved, _) = resolve_markdown_assets(
+        layout,
+        &BTreeSet::from([reference.clone()]),
+        &manifest.asset_map,
+        &entry.collection_id,
+        &entry.entry_id,
+        asset_slug,
+      );
+      let [bundle_path] = resolved.as_slice() else {
+        continue;
+      };
+      let Some(key) = keys_by_path.get(bundle_path) else {
+        continue;
+      };
+      if !data_uris.contains_key(key) {
+        let source = manifest.asset_map[key].source_path(collections_dir);
+        let size = fs::metadata(&source)?.len();
+        let data_uri = match image_media_type(&key.1) {
+          Some(_) if size <= max_bytes => image_data_uri(&key.1, &fs::read(&source)?),
+          _ => None,
+        };
+        data_uris.insert(key.clone(), data_uri.map(|uri| (uri, size)));
+      }
+      let Some((data_uri, _)) = &data_uris[key] else {
+        continue;
+      };
+
+      entry.body = replace_reference(&entry.body, &reference, data_uri);
+      entry.asset_paths.retain(|path| path != bundle_path);
+      entry.priority_assets.retain(|path| path != bundle_path);
+    }
+  }
+
+  let mut referenced: BTreeSet<&str> = manifest
+    .hero_asset_paths
+    .iter()
+    .map(String::as_str)
+    .collect();
+  for entry in &manifest.offline_entries {
+    referenced.extend(entry.asset_paths.iter().map(String::as_str));
+    for document in &entry.documents {
+      referenced.extend(document.asset_paths.iter().map(String::as_str));
+    }
+  }
+  for record in &manifest.collection_catalog {
+    if let Some(intro) = &record.intro {
+      referenced.extend(intro.asset_paths.iter().map(String::as_str));
+    }
+  }
+
+  let mut inlined = Vec::new();
+  let mut removed_keys = Vec::new();
+  for (bundle_path, key) in &keys_by_path {
+    let Some(Some((_, size))) = data_uris.get(key) else {
+      continue;
+    };
+    let removed = !referenced.contains(bundle_path.as_str());
+    if removed {
+      removed_keys.push(key.clone());
+    }
+    inlined.push(InlinedImage {
+      collection_id: key.0.clone(),
+      relative_path: key.1.clone(),
+      size: *size,
+      removed,
+    });
+  }
+  for key in removed_keys {
+    manifest.asset_map.remove(&key);
+  }
+  Ok(inlined)
+}
+
+/// Replace `reference` where it appears as a whole link target or attribute value.
+fn replace_reference(body: &str, reference: &str, data_uri: &str) -> String {
+  let pattern = Regex::new(&format!(
+    r#"([(\s"'=])({})([)\s"'>])"#,
+    regex::escape(reference)
+  ))
+  .expect("invalid reference regex");
+  pattern
+    .replace_all(body, |caps: &Captures| {
+      format!("{}{data_uri}{}", &caps[1], &caps[3])
+    })
+    .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::models::{AssetEntry, OfflineEntryRecord};
+  use tempfile::tempdir;
+
+  fn asset(collection_id: &str, relative_path: &str) -> AssetEntry {
+    AssetEntry {
+      const_name: relative_path.to_uppercase().replace(['/', '.'], "_"),
+      literal_path: format!("/content/programs/{collection_id}/{relative_path}"),
+      collection_id: collection_id.into(),
+      relative_path: relative_path.into(),
+      output_path: None,
+      source_file: None,
+      size: None,
+      modified: None,
+    }
+  }
+
+  #[test]
+  fn inlines_small_images_and_drops_them_from_the_asset_map() -> BuildResult<()> {
+    let dir = tempdir()?;
+    let layout = OfflineProjectLayout::default();
+    let assets = dir.path().join("alpha/intro/assets");
+    fs::create_dir_all(&assets)?;
+    fs::write(assets.join("icon.png"), [0x89, b'P', b'N', b'G'])?;
+    fs::write(assets.join("photo.png"), vec![0; 2048])?;
+    fs::write(assets.join("shared.svg"), "<svg/>")?;
+
+    let entry_asset = |name: &str| {
+      let relative = format!("intro/assets/{name}");
+      (
+        ("alpha".to_string(), relative.clone()),
+        asset("alpha", &relative),
+      )
+    };
+    let mut manifest = ManifestGenerationResult {
+      collection_catalog: Vec::new(),
+      offline_entries: vec![OfflineEntryRecord {
+        collection_id: "alpha".into(),
+        entry_id: "intro".into(),
+        body: concat!(
+          "![Icon](assets/icon.png) ![Photo](assets/photo.png)\n\n",
+          "<img src=\"assets/shared.svg\" alt=\"Shared\">\n",
+        )
+        .into(),
+        asset_paths: ["icon.png", "photo.png", "shared.svg"]
+          .map(|name| make_offline_asset_path(&layout, "alpha", &format!("intro/assets/{name}")))
+          .to_vec(),
+        word_count: 0,
+        reading_minutes: 0,
+        headings: Default::default(),
+        images: Vec::new(),
+        documents: Vec::new(),
+        priority_assets: vec![make_offline_asset_path(
+          &layout,
+          "alpha",
+          "intro/assets/icon.png",
+        )],
+        video_posters: BTreeMap::new(),
+      }],
+      asset_map: ["icon.png", "photo.png", "shared.svg"]
+        .map(entry_asset)
+        .into_iter()
+        .collect(),
+      hero_asset_paths: BTreeSet::from([make_offline_asset_path(
+        &layout,
+        "alpha",
+        "intro/assets/shared.svg",
+      )]),
+      hero_match_arms: Vec::new(),
+      excluded_collections: Vec::new(),
+    };
+
+    let inlined = inline_small_images(
+      &layout,
+      dir.path(),
+      &mut manifest,
+      &AssetReferenceFilter::default(),
+      1024,
+    )?;
+
+    assert_eq!(inlined, [
+      InlinedImage {
+        collection_id: "alpha".into(),
+        relative_path: "intro/assets/icon.png".into(),
+        size: 4,
+        removed: true,
+      },
+      InlinedImage {
+        collection_id: "alpha".into(),
+        relative_path: "intro/assets/shared.svg".into(),
+        size: 6,
+        removed: false,
+      },
+    ]);
+    let entry = &manifest.offline_entries[0];
+    assert_eq!(
+      entry.body,
+      concat!(
+        "![Icon](data:image/png;base64,iVBORw==) ![Photo](assets/photo.png)\n\n",
+        "<img src=\"data:image/svg+xml;base64,PHN2Zy8+\" alt=\"Shared\">\n",
+      )
+    );
+    assert_eq!(entry.asset_paths, [make_offline_asset_path(
+      &layout,
+      "alpha",
+      "intro/assets/photo.png"
+    )]);
+    assert!(entry.priority_assets.is_empty());
+    assert_eq!(
+      manifest
+        .asset_map
+        .keys()
+        .map(|(_, path)| path.as_str())
+        .collect::<Vec<_>>(),
+      ["intro/assets/photo.png", "intro/assets/shared.svg"]
+    );
+    Ok(())
+  }
+}
@@ -0,0 +1,299 @@
+//! Software bill of materials describing every asset shipped in the offline bundle.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::asset_paths::make_offline_asset_path;
+use crate::models::AssetEntry;
+use crate::project::OfflineProjectLayout;
+
+/// CycloneDX specification version emitted by [`build_asset_inventory`].
+pub const INVENTORY_SPEC_VERSION: &str = "1.5";
+
+/// CycloneDX-style inventory listing the assets included in an offline bundle.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetInventory {
+  /// Document format marker, always `CycloneDX`.
+  pub bom_format: String,
+  /// Version of the CycloneDX specification the document follows.
+  pub spec_version: String,
+  /// Revision of the inventory document.
+  pub version: u32,
+  /// One component per bundled asset, sorted by bundle path.
+  pub components: Vec<InventoryComponent>,
+}
+
+/// Inventory record describing a single bundled file.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct InventoryComponent {
+  /// Component type, always `file` for bundled assets.
+  #[serde(rename = "type")]
+  pub component_type: String,
+  /// Unique reference for the component, identical to its bundle path.
+  #[serde(rename = "bom-ref")]
+  pub bom_ref: String,
+  /// Bundle-relative path of the asset.
+  pub name: String,
+  /// Content hashes computed from the authored source file.
+  pub hashes: Vec<InventoryHash>,
+  /// Additional properties such as byte size and source collection.
+  pub properties: Vec<InventoryProperty>,
+}
+
+/// Hash entry attached to an inventory component.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct InventoryHash {
+  /// Hash algorithm name using CycloneDX spelling.
+  pub alg: String,
+  /// Lowercase hexadecimal digest.
+  pub content: String,
+}
+
+/// Name/value property attached to an inventory component.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct InventoryProperty {
+  /// Property name namespaced with `offline:`.
+  pub name: String,
+  /// Property value rendered as a string.
+  pub value: String,
+}
+
+impl InventoryComponent {
+  /// Look up a property value by name.
+  pub fn property(&self, name: &str) -> Option<&str> {
+    self
+      .properties
+      .iter()
+      .find(|property| property.name == name)
+      .map(|property| property.value.as_str())
+  }
+}
+
+/// Hash a file on disk returning its byte size and lowercase SHA-256 digest.
+pub fn file_digest(path: &Path) -> io::Result<(u64, String)> {
+  let mut file = File::open(path)?;
+  let mut hasher = Sha256::new();
+  let size = io::copy(&mut file, &mut hasher)?;
+  Ok((size, format!("{:x}", hasher.finalize())))
+}
+
+/// Build the asset inventory for every asset whose authored source exists on disk.
+///
+/// Missing sources are skipped, matching the behaviour of the asset mirror.
+pub fn build_asset_inventory(
+  layout: &OfflineProjectLayout,
+  collections_dir: &Path,
+  asset_map: &BTreeMap<(String, String), AssetEntry>,
+) -> io::Result<AssetInventory> {
+  let mut components = Vec::new();
+
+  for entry in asset_map.values() {
+    let source_path = entry.source_path(collections_dir);
+    if !source_path.is_file() {
+      continue;
+    }
+
+    let (size, digest) = file_digest(&source_path)?;
+    let bundle_path =
+      make_offline_asset_path(layout, &entry.collection_id, entry.output_relative_path());
+    components.push(InventoryComponent {
+      component_type: "file".into(),
+      bom_ref: bundle_path.clone(),
+      name: bundle_path,
+      hashes: vec![InventoryHash {
+        alg: "SHA-256".into(),
+        content: digest,
+      }],
+      properties: vec![
+        InventoryProperty {
+          name: "offline:size".into(),
+          value: size.to_string(),
+        },
+        InventoryProperty {
+          name: "offline:collection".into(),
+          value: entry.collection_id.clone(),
+        },
+        InventoryProperty {
+          name: "offline:relativePath".into(),
+          value: entry.relative_path.clone(),
+        },
+      ],
+    });
+  }
+
+  components.sort_by(|a, b| a.name.cmp(&b.name));
+
+  Ok(AssetInventory {
+    bom_format: "CycloneDX".into(),
+    spec_version: INVENTORY_SPEC_VERSION.into(),
+    version: 1,
+    components,
+  })
+}
+
+impl AssetInventory {
+  /// Byte size and SHA-256 digest recorded for the asset at `bundle_path`.
+  pub fn digest(&self, bundle_path: &str) -> Option<(u64, &str)> {
+    let component = self
+      .components
+      .binary_search_by(|component| component.name.as_str().cmp(bundle_path))
+      .ok()
+      .map(|index| &self.components[index])?;
+    let size = component.property("offline:size")?.parse().ok()?;
+    Some((size, component.hashes.first()?.content.as_str()))
+  }
+
+  /// Keep only the components of assets in `asset_map`.
+  pub fn retain_assets(
+    &mut self,
+    layout: &OfflineProjectLayout,
+    asset_map: &BTreeMap<(String, String), AssetEntry>,
+  ) {
+    let paths: BTreeSet<String> = asset_map
+      .values()
+      .map(|entry| {
+        make_offline_asset_path(layout, &entry.collection_id, entry.output_relative_path())
+      })
+      .collect();
+    self
+      .components
+      .retain(|component| paths.contains(&component.name));
+  }
+}
+
+/// Find assets whose content is byte-identical to an earlier asset.
+///
+/// Compares the digests already recorded in `inventory`, so no file is hashed again. Returns a
+/// map from each duplicate asset key to the key of its canonical copy, which is the first asset
+/// with the same content in `asset_map` order. Assets missing from the inventory are ignored.
+pub fn find_duplicate_assets<'i>(
+  layout: &OfflineProjectLayout,
+  inventory: &'i AssetInventory,
+  asset_map: &BTreeMap<(String, String), AssetEntry>,
+) -> BTreeMap<(String, String), (String, String)> {
+  let mut canonical_by_digest: BTreeMap<(u64, &'i str), &(String, String)> = BTreeMap::new();
+  let mut duplicates = BTreeMap::new();
+
+  for (key, entry) in asset_map {
+    let bundle_path =
+      make_offline_asset_path(layout, &entry.collection_id, entry.output_relative_path());
+    let Some(digest) = inventory.digest(&bundle_path) else {
+      continue;
+    };
+    match canonical_by_digest.get(&digest) {
+      Some(canonical) => {
+        duplicates.insert(key.clone(), (*canonical).clone());
+      }
+      None => {
+        canonical_by_digest.insert(digest, key);
+      }
+    }
+  }
+
+  duplicates
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+  use tempfile::tempdir;
+
+  fn layout() -> OfflineProjectLayout {
+    OfflineProjectLayout {
+      excluded_dir_name: "prod".into(),
+      excluded_path_fragment: "/prod/".into(),
+      ..Default::default()
+    }
+  }
+
+  fn asset(collection_id: &str, relative_path: &str) -> AssetEntry {
+    AssetEntry {
+      const_name: "CONST".into(),
+      literal_path: String::new(),
+      collection_id: collection_id.into(),
+      relative_path: relative_path.into(),
+      output_path: None,
+      source_file: None,
+      size: None,
+      modified: None,
+    }
+  }
+
+  #[test]
+  fn lists_existing_assets_with_hashes_and_sizes() {
+    let dir = tempdir().unwrap();
+    let collections_dir = dir.path();
+    fs::create_dir_all(collections_dir.join("P001/assets")).unwrap();
+    fs::write(collections_dir.join("P001/assets/logo.png"), "abc").unwrap();
+
+    let mut asset_map = BTreeMap::new();
+    for relative in ["assets/logo.png", "assets/missing.png"] {
+      asset_map.insert(
+        ("P001".to_string(), relative.to_string()),
+        asset("P001", relative),
+      );
+    }
+
+    let inventory = build_asset_inventory(&layout(), collections_dir, &asset_map).unwrap();
+
+    assert_eq!(inventory.bom_format, "CycloneDX");
+    assert_eq!(inventory.components.len(), 1);
+    let component = &inventory.components[0];
+    assert_eq!(component.name, "programs/P001/assets/logo.png");
+    assert_eq!(
+      component.hashes[0].content,
+      "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    assert_eq!(component.property("offline:size"), Some("3"));
+    assert_eq!(component.property("offline:collection"), Some("P001"));
+  }
+
+  #[test]
+  fn maps_identical_assets_to_first_copy() {
+    let dir = tempdir().unwrap();
+    let collections_dir = dir.path();
+    fs::create_dir_all(collections_dir.join("P001")).unwrap();
+    fs::create_dir_all(collections_dir.join("P002")).unwrap();
+    fs::write(collections_dir.join("P001/logo.png"), "logo").unwrap();
+    fs::write(collections_dir.join("P002/logo.png"), "logo").unwrap();
+    fs::write(collections_dir.join("P002/other.png"), "other").unwrap();
+
+    let mut asset_map = BTreeMap::new();
+    for (collection_id, relative) in [
+      ("P001", "logo.png"),
+      ("P002", "logo.png"),
+      ("P002", "other.png"),
+    ] {
+      asset_map.insert(
+        (collection_id.to_string(), relative.to_string()),
+        asset(collection_id, relative),
+      );
+    }
+
+    let inventory = build_asset_inventory(&layout(), collections_dir, &asset_map).unwrap();
+    let duplicates = find_duplicate_assets(&layout(), &inventory, &asset_map);
+    assert_eq!(duplicates.len(), 1);
+    assert_eq!(
+      duplicates[&("P002".to_string(), "logo.png".to_string())],
+      ("P001".to_string(), "logo.png".to_string())
+    );
+
+    let mut inventory = inventory;
+    asset_map.retain(|(collection_id, _), _| collection_id == "P002");
+    inventory.retain_assets(&layout(), &asset_map);
+    assert_eq!(inventory.components.len(), 2);
+    assert_eq!(
+      inventory
+        .digest("programs/P002/other.png")
+        .map(|(size, _)| size),
+      Some(5)
+    );
+  }
+}
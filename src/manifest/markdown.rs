@@ -8,11 +8,274 @@ use gray_matter::{Matter, engine::YAML};
 use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
 
 use crate::asset_paths::{
-  generate_asset_candidates, make_offline_asset_path, should_ignore_asset_reference,
+  AssetReferenceFilter, generate_asset_candidates, is_parent_relative, make_offline_asset_path,
+  resolve_relative_path, strip_collection_literal_prefix,
 };
-use crate::models::{AssetEntry, EntryFrontmatterRecord};
+use regex::Regex;
+
+use crate::models::{AssetEntry, EntryFrontmatterRecord, ImageAltRecord};
 use crate::project::OfflineProjectLayout;
 
+/// Reading speed used by [`reading_minutes`].
+pub const WORDS_PER_MINUTE: usize = 200;
+
+/// Count the words in the text of a markdown document, ignoring markup, link targets, HTML and
+/// tokens without letters or digits.
+///
+/// Inline and fenced code is counted, since readers still have to get through it.
+pub fn count_words(markdown: &str) -> usize {
+  let mut options = Options::empty();
+  options.insert(Options::ENABLE_TABLES);
+  options.insert(Options::ENABLE_FOOTNOTES);
+  options.insert(Options::ENABLE_STRIKETHROUGH);
+  options.insert(Options::ENABLE_TASKLISTS);
+  options.insert(Options::ENABLE_SMART_PUNCTUATION);
+  options.insert(Options::ENABLE_HEADING_ATTRIBUTES);
+  options.insert(Options::ENABLE_YAML_STYLE_METADATA_BLOCKS);
+
+  let mut in_metadata = false;
+  let mut words = 0;
+  for event in Parser::new_ext(markdown, options) {
+    match event {
+      Event::Start(Tag::MetadataBlock(_)) => in_metadata = true,
+      Event::End(TagEnd::MetadataBlock(_)) => in_metadata = false,
+      Event::Text(text) | Event::Code(text) if !in_metadata => {
+        words += text
+          .split_whitespace()
+          .filter(|word| word.chars().any(char::is_alphanumeric))
+          .count();
+      }
+      _ => {}
+    }
+  }
+  words
+}
+
+/// Estimated reading time for `word_count` words at [`WORDS_PER_MINUTE`], rounded up.
+///
+/// Any non-empty text takes at least one minute, so listings never show "0 min read".
+pub fn reading_minutes(word_count: usize) -> usize {
+  word_count.div_ceil(WORDS_PER_MINUTE)
+}
+
+/// Excerpt length in characters used unless `OfflineBuilder::with_excerpt_length` says otherwise.
+pub const DEFAULT_EXCERPT_LENGTH: usize = 200;
+
+/// Plain-text excerpt of the first paragraph with text, or `None` when the entry has none.
+///
+/// Markup, HTML and image alt text are dropped and whitespace is collapsed. Excerpts longer than
+/// `max_chars` are cut at a word boundary and end with `…`.
+pub fn extract_excerpt(markdown: &str, max_chars: usize) -> Option<String> {
+  let mut options = Options::empty();
+  options.insert(Options::ENABLE_TABLES);
+  options.insert(Options::ENABLE_FOOTNOTES);
+  options.insert(Options::ENABLE_STRIKETHROUGH);
+  options.insert(Options::ENABLE_TASKLISTS);
+  options.insert(Options::ENABLE_SMART_PUNCTUATION);
+  options.insert(Options::ENABLE_HEADING_ATTRIBUTES);
+  options.insert(Options::ENABLE_YAML_STYLE_METADATA_BLOCKS);
+
+  let mut in_paragraph = false;
+  let mut image_depth = 0usize;
+  let mut text = String::new();
+  for event in Parser::new_ext(markdown, options) {
+    match event {
+      Event::Start(Tag::Paragraph) => {
+        in_paragraph = true;
+        text.clear();
+      }
+      Event::End(TagEnd::Paragraph) => {
+        in_paragraph = false;
+        if text.split_whitespace().next().is_some() {
+          break;
+        }
+      }
+      Event::Start(Tag::Image { .. }) => image_depth += 1,
+      Event::End(TagEnd::Image) => image_depth = image_depth.saturating_sub(1),
+      Event::Text(value) | Event::Code(value) if in_paragraph && image_depth == 0 => {
+        text.push_str(&value);
+      }
+      Event::SoftBreak | Event::HardBreak if in_paragraph => text.push(' '),
+      _ => {}
+    }
+  }
+
+  let words: Vec<&str> = text.split_whitespace().collect();
+  if words.is_empty() {
+    return None;
+  }
+  let full = words.join(" ");
+  if full.chars().count() <= max_chars {
+    return Some(full);
+  }
+
+  let mut excerpt = String::new();
+  for word in words {
+    let separator = usize::from(!excerpt.is_empty());
+    if excerpt.chars().count() + separator + word.chars().count() + 1 > max_chars {
+      break;
+    }
+    if separator == 1 {
+      excerpt.push(' ');
+    }
+    excerpt.push_str(word);
+  }
+  if excerpt.is_empty() {
+    excerpt = full.chars().take(max_chars.saturating_sub(1)).collect();
+  }
+  let excerpt = excerpt.trim_end_matches([',', ';', ':', '.', ' ']);
+  Some(format!("{excerpt}…"))
+}
+
+/// Slug used for a heading's `#fragment`, matching GitHub-style anchors.
+///
+/// Text is lowercased, characters other than letters, digits, spaces, `-` and `_` are dropped
+/// and spaces become `-`, so `Getting Started (v2)` becomes `getting-started-v2`.
+pub fn heading_slug(text: &str) -> String {
+  text
+    .trim()
+    .chars()
+    .flat_map(char::to_lowercase)
+    .filter_map(|c| match c {
+      ' ' => Some('-'),
+      c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+      _ => None,
+    })
+    .collect()
+}
+
+/// Map every heading's anchor to its text.
+///
+/// Explicit `{#id}` attributes win; other headings use [`heading_slug`], with `-1`, `-2`, ...
+/// appended to repeated slugs in document order.
+pub fn collect_heading_anchors(markdown: &str) -> BTreeMap<String, String> {
+  let mut options = Options::empty();
+  options.insert(Options::ENABLE_TABLES);
+  options.insert(Options::ENABLE_FOOTNOTES);
+  options.insert(Options::ENABLE_STRIKETHROUGH);
+  options.insert(Options::ENABLE_TASKLISTS);
+  options.insert(Options::ENABLE_SMART_PUNCTUATION);
+  options.insert(Options::ENABLE_HEADING_ATTRIBUTES);
+  options.insert(Options::ENABLE_YAML_STYLE_METADATA_BLOCKS);
+
+  let mut anchors = BTreeMap::new();
+  let mut seen: BTreeMap<String, usize> = BTreeMap::new();
+  let mut current: Option<(Option<String>, String)> = None;
+  for event in Parser::new_ext(markdown, options) {
+    match event {
+      Event::Start(Tag::Heading { id, .. }) => {
+        current = Some((id.map(|id| id.to_string()), String::new()));
+      }
+      Event::Text(text) | Event::Code(text) => {
+        if let Some((_, heading)) = current.as_mut() {
+          heading.push_str(&text);
+        }
+      }
+      Event::End(TagEnd::Heading(_)) => {
+        let Some((id, text)) = current.take() else {
+          continue;
+        };
+        let text = text.trim().to_string();
+        let anchor = match id {
+          Some(id) => id,
+          None => {
+            let slug = heading_slug(&text);
+            let count = seen.entry(slug.clone()).or_insert(0);
+            let anchor = if *count == 0 {
+              slug
+            } else {
+              format!("{slug}-{count}")
+            };
+            *count += 1;
+            anchor
+          }
+        };
+        if !anchor.is_empty() {
+          anchors.insert(anchor, text);
+        }
+      }
+      _ => {}
+    }
+  }
+  anchors
+}
+
+/// Lead of an entry: the markdown before the first heading, not counting headings the entry
+/// starts with, such as its title. Assets referenced here are shown first.
+pub fn lead_section(markdown: &str) -> &str {
+  let mut options = Options::empty();
+  options.insert(Options::ENABLE_TABLES);
+  options.insert(Options::ENABLE_FOOTNOTES);
+  options.insert(Options::ENABLE_STRIKETHROUGH);
+  options.insert(Options::ENABLE_TASKLISTS);
+  options.insert(Options::ENABLE_SMART_PUNCTUATION);
+  options.insert(Options::ENABLE_HEADING_ATTRIBUTES);
+  options.insert(Options::ENABLE_YAML_STYLE_METADATA_BLOCKS);
+
+  let mut in_heading = false;
+  let mut seen_content = false;
+  for (event, range) in Parser::new_ext(markdown, options).into_offset_iter() {
+    match event {
+      Event::Start(Tag::Heading { .. }) if seen_content => return &markdown[..range.start],
+      Event::Start(Tag::Heading { .. }) => in_heading = true,
+      Event::End(TagEnd::Heading(_)) => in_heading = false,
+      Event::Start(_) | Event::Html(_) | Event::Rule if !in_heading => seen_content = true,
+      _ => {}
+    }
+  }
+  markdown
+}
+
+/// Record every markdown image and inline `<img>` tag with its alternative text.
+pub fn collect_image_alt_texts(markdown: &str) -> Vec<ImageAltRecord> {
+  let mut options = Options::empty();
+  options.insert(Options::ENABLE_TABLES);
+  options.insert(Options::ENABLE_FOOTNOTES);
+  options.insert(Options::ENABLE_STRIKETHROUGH);
+  options.insert(Options::ENABLE_TASKLISTS);
+  options.insert(Options::ENABLE_SMART_PUNCTUATION);
+  options.insert(Options::ENABLE_HEADING_ATTRIBUTES);
+  options.insert(Options::ENABLE_YAML_STYLE_METADATA_BLOCKS);
+
+  let img_tag = Regex::new(r"(?i)<img\b[^>]*>").expect("invalid img regex");
+  let attribute = |tag: &str, name: &str| {
+    Regex::new(&format!(r#"(?i)\b{name}\s*=\s*(?:"([^"]*)"|'([^']*)')"#))
+      .expect("invalid attribute regex")
+      .captures(tag)
+      .and_then(|caps| caps.get(1).or_else(|| caps.get(2)))
+      .map(|value| value.as_str().to_string())
+  };
+
+  let mut images = Vec::new();
+  let mut current: Option<ImageAltRecord> = None;
+  for event in Parser::new_ext(markdown, options) {
+    match event {
+      Event::Start(Tag::Image { dest_url, .. }) => {
+        current = Some(ImageAltRecord {
+          src: dest_url.to_string(),
+          alt: String::new(),
+        });
+      }
+      Event::Text(text) | Event::Code(text) => {
+        if let Some(image) = current.as_mut() {
+          image.alt.push_str(&text);
+        }
+      }
+      Event::End(TagEnd::Image) => images.extend(current.take()),
+      Event::Html(html) | Event::InlineHtml(html) => {
+        for tag in img_tag.find_iter(&html) {
+          images.push(ImageAltRecord {
+            src: attribute(tag.as_str(), "src").unwrap_or_default(),
+            alt: attribute(tag.as_str(), "alt").unwrap_or_default(),
+          });
+        }
+      }
+      _ => {}
+    }
+  }
+  images
+}
+
 /// Parse the numeric ordering prefix from an entry identifier if present.
 pub fn parse_order_from_id(id: &str) -> Option<usize> {
   let prefix = id.split_once('-').map(|(value, _)| value).unwrap_or(id);
@@ -26,6 +289,15 @@ pub fn parse_order_from_id(id: &str) -> Option<usize> {
 
 /// Collect asset references (links, images and inline HTML) from markdown content.
 pub fn collect_markdown_asset_references(markdown: &str) -> BTreeSet<String> {
+  collect_markdown_asset_references_with(markdown, &AssetReferenceFilter::default())
+}
+
+/// Collect asset references like [`collect_markdown_asset_references`], skipping every reference
+/// `filter` ignores.
+pub fn collect_markdown_asset_references_with(
+  markdown: &str,
+  filter: &AssetReferenceFilter,
+) -> BTreeSet<String> {
   let mut options = Options::empty();
   options.insert(Options::ENABLE_TABLES);
   options.insert(Options::ENABLE_FOOTNOTES);
@@ -40,18 +312,15 @@ pub fn collect_markdown_asset_references(markdown: &str) -> BTreeSet<String> {
 
   for event in parser {
     match event {
-      Event::Start(Tag::Image { .. }) | Event::End(TagEnd::Image) => {}
-      Event::Start(Tag::Link { dest_url, .. }) => {
-        add_reference(&mut references, &dest_url);
+      Event::Start(Tag::Image { dest_url, .. }) | Event::Start(Tag::Link { dest_url, .. }) => {
+        add_reference(&mut references, filter, &dest_url);
       }
-      Event::End(TagEnd::Link) => {}
+      Event::End(TagEnd::Image) | Event::End(TagEnd::Link) => {}
       Event::Html(html) | Event::InlineHtml(html) => {
-        extract_inline_asset_values(&html, &mut references);
+        extract_inline_asset_values(&html, filter, &mut references);
       }
-      Event::Text(text) => {
-        if text.starts_with("![") || text.contains("](") {
-          extract_inline_asset_values(&text, &mut references);
-        }
+      Event::Text(text) if text.starts_with("![") || text.contains("](") => {
+        extract_inline_asset_values(&text, filter, &mut references);
       }
       _ => {}
     }
@@ -73,7 +342,14 @@ pub fn resolve_markdown_assets(
   let mut unresolved = Vec::new();
 
   for reference in references {
-    let candidates = generate_asset_candidates(layout, entry_id, asset_slug, reference);
+    // References written with the literal prefix (`/content/programs/P001/assets/x.png`) are
+    // scoped to the collection before candidate generation.
+    let prefixed = strip_collection_literal_prefix(layout, reference);
+    let scoped = prefixed
+      .and_then(|path| path.strip_prefix(collection_id))
+      .and_then(|path| path.strip_prefix('/'))
+      .unwrap_or(reference);
+    let candidates = generate_asset_candidates(layout, entry_id, asset_slug, scoped);
     let mut found = false;
 
     for candidate in candidates {
@@ -81,13 +357,35 @@ pub fn resolve_markdown_assets(
         resolved.insert(make_offline_asset_path(
           layout,
           &entry.collection_id,
-          &entry.relative_path,
+          entry.output_relative_path(),
         ));
         found = true;
         break;
       }
     }
 
+    // Prefixed references and references leaving the collection, e.g.
+    // `../../P000-common/assets/logo.png`, are resolved against the collections root and may
+    // point into another collection.
+    let root_path = match prefixed {
+      Some(path) => Some(path.to_string()),
+      None if is_parent_relative(reference) => resolve_relative_path(
+        &format!("{collection_id}/{entry_id}"),
+        reference.trim_matches('/'),
+      ),
+      None => None,
+    };
+    if !found
+      && let Some(entry) = root_path.and_then(|path| find_asset_by_root_path(asset_map, &path))
+    {
+      resolved.insert(make_offline_asset_path(
+        layout,
+        &entry.collection_id,
+        entry.output_relative_path(),
+      ));
+      found = true;
+    }
+
     if !found {
       unresolved.push(reference.clone());
     }
@@ -96,6 +394,16 @@ pub fn resolve_markdown_assets(
   (resolved.into_iter().collect(), unresolved)
 }
 
+/// Asset whose `<collection_id>/<relative_path>` equals `path`, trying every collection prefix.
+fn find_asset_by_root_path<'a>(
+  asset_map: &'a BTreeMap<(String, String), AssetEntry>,
+  path: &str,
+) -> Option<&'a AssetEntry> {
+  path.match_indices('/').find_map(|(index, _)| {
+    asset_map.get(&(path[..index].to_string(), path[index + 1..].to_string()))
+  })
+}
+
 /// Parse an entry markdown file, extracting frontmatter metadata and the content body.
 pub fn parse_entry_markdown(
   entry_markdown_path: &Path,
@@ -148,17 +456,21 @@ pub(super) fn extract_first_heading(body: &str) -> Option<String> {
   None
 }
 
-fn add_reference(references: &mut BTreeSet<String>, value: &str) {
-  if should_ignore_asset_reference(value) {
+fn add_reference(references: &mut BTreeSet<String>, filter: &AssetReferenceFilter, value: &str) {
+  if filter.is_ignored(value) {
     return;
   }
   references.insert(value.to_string());
 }
 
-fn extract_inline_asset_values(fragment: &str, references: &mut BTreeSet<String>) {
-  extract_attribute_values(fragment, "src", references);
-  extract_attribute_values(fragment, "href", references);
-  extract_attribute_values(fragment, "poster", references);
+fn extract_inline_asset_values(
+  fragment: &str,
+  filter: &AssetReferenceFilter,
+  references: &mut BTreeSet<String>,
+) {
+  extract_attribute_values(fragment, "src", filter, references);
+  extract_attribute_values(fragment, "href", filter, references);
+  extract_attribute_values(fragment, "poster", filter, references);
 
   let mut chars = fragment.chars().peekable();
   while let Some(ch) = chars.next() {
@@ -173,7 +485,7 @@ fn extract_inline_asset_values(fragment: &str, references: &mut BTreeSet<String>
             }
             path.push(ch);
           }
-          add_reference(references, path.trim());
+          add_reference(references, filter, path.trim());
           break;
         }
       }
@@ -181,7 +493,12 @@ fn extract_inline_asset_values(fragment: &str, references: &mut BTreeSet<String>
   }
 }
 
-fn extract_attribute_values(fragment: &str, attribute: &str, references: &mut BTreeSet<String>) {
+fn extract_attribute_values(
+  fragment: &str,
+  attribute: &str,
+  filter: &AssetReferenceFilter,
+  references: &mut BTreeSet<String>,
+) {
   let pattern = format!("{}=\"", attribute);
   let mut start = 0;
 
@@ -189,7 +506,7 @@ fn extract_attribute_values(fragment: &str, attribute: &str, references: &mut BT
     let attr_start = start + pos + pattern.len();
     if let Some(end) = fragment[attr_start..].find('"') {
       let value = &fragment[attr_start..attr_start + end];
-      add_reference(references, value);
+      add_reference(references, filter, value);
       start = attr_start + end + 1;
     } else {
       break;
@@ -202,7 +519,7 @@ fn extract_attribute_values(fragment: &str, attribute: &str, references: &mut BT
     let attr_start = start + pos + pattern_single.len();
     if let Some(end) = fragment[attr_start..].find('\'') {
       let value = &fragment[attr_start..attr_start + end];
-      add_reference(references, value);
+      add_reference(references, filter, value);
       start = attr_start + end + 1;
     } else {
       break;
@@ -226,9 +543,13 @@ mod tests {
       offline_site_root: "site".into(),
       collections_dir_name: "programs".into(),
       offline_bundle_root: "target/offline-html".into(),
+      output_root: None,
       index_html_file: "index.html".into(),
       target_dir: "target".into(),
       offline_manifest_json: "offline_manifest.json".into(),
+      scan_max_depth: 32,
+      asset_extensions: Vec::new(),
+      excluded_extensions: Vec::new(),
     }
   }
 
@@ -238,6 +559,65 @@ mod tests {
     assert_eq!(parse_order_from_id("intro"), None);
   }
 
+  #[test]
+  fn counts_words_without_markup() {
+    let markdown = "# Safety first\n\nCheck the [life jackets](assets/jackets.png) and run `drill --all`.\n\n<div class=\"note\"></div>\n";
+
+    assert_eq!(count_words(markdown), 10);
+    assert_eq!(reading_minutes(0), 0);
+    assert_eq!(reading_minutes(10), 1);
+    assert_eq!(reading_minutes(WORDS_PER_MINUTE * 5 + 1), 6);
+  }
+
+  #[test]
+  fn extracts_plain_text_excerpt_from_first_paragraph() {
+    let markdown = "# Title\n\n![Hero](assets/hero.png)\n\nKeep a **sharp** lookout, and [report](x.md)\nany `hazards`.\n\nSecond paragraph.\n";
+
+    assert_eq!(
+      extract_excerpt(markdown, 200).as_deref(),
+      Some("Keep a sharp lookout, and report any hazards.")
+    );
+    assert_eq!(
+      extract_excerpt(markdown, 24).as_deref(),
+      Some("Keep a sharp lookout…")
+    );
+    assert_eq!(extract_excerpt("# Only a heading\n", 200), None);
+  }
+
+  #[test]
+  fn maps_heading_anchors_to_text() {
+    let markdown = "# Getting Started (v2)\n\n## Setup\n\n## Setup\n\n## Custom `id` {#custom}\n";
+
+    assert_eq!(
+      collect_heading_anchors(markdown),
+      BTreeMap::from([
+        ("custom".to_string(), "Custom id".to_string()),
+        (
+          "getting-started-v2".to_string(),
+          "Getting Started (v2)".to_string()
+        ),
+        ("setup".to_string(), "Setup".to_string()),
+        ("setup-1".to_string(), "Setup".to_string()),
+      ])
+    );
+  }
+
+  #[test]
+  fn records_image_alt_text() {
+    let markdown = "![Rescue boat](assets/boat.png)\n\n![](assets/knot.png)\n\n<img src=\"assets/map.png\" alt=\"\">\n";
+
+    let images = collect_image_alt_texts(markdown);
+    let summary: Vec<(&str, bool)> = images
+      .iter()
+      .map(|image| (image.src.as_str(), image.has_alt()))
+      .collect();
+    assert_eq!(summary, [
+      ("assets/boat.png", true),
+      ("assets/knot.png", false),
+      ("assets/map.png", false),
+    ]);
+  }
+
   #[test]
   fn collects_asset_references_from_markdown() {
     let markdown = "![Alt](image.png) <img src=\"video.mp4\">";
@@ -260,6 +640,10 @@ mod tests {
         literal_path: "".into(),
         collection_id: "collection".into(),
         relative_path: "entry/assets/image.png".into(),
+        output_path: None,
+        source_file: None,
+        size: None,
+        modified: None,
       },
     );
 
@@ -277,4 +661,51 @@ mod tests {
     assert_eq!(resolved.len(), 1);
     assert_eq!(resolved[0], "programs/collection/entry/assets/image.png");
   }
+
+  #[test]
+  fn resolves_parent_relative_and_prefixed_references() {
+    let layout = layout();
+    let asset = |collection_id: &str, relative_path: &str| {
+      (
+        (collection_id.to_string(), relative_path.to_string()),
+        AssetEntry {
+          const_name: "CONST".into(),
+          literal_path: "".into(),
+          collection_id: collection_id.into(),
+          relative_path: relative_path.into(),
+          output_path: None,
+          source_file: None,
+          size: None,
+          modified: None,
+        },
+      )
+    };
+    let asset_map = BTreeMap::from([
+      asset("P001", "shared/figure.png"),
+      asset("P000-common", "assets/logo.png"),
+    ]);
+
+    let references = BTreeSet::from([
+      "../shared/figure.png".to_string(),
+      "../../P000-common/assets/logo.png".to_string(),
+      "../../../outside.png".to_string(),
+    ]);
+    let (resolved, unresolved) =
+      resolve_markdown_assets(&layout, &references, &asset_map, "P001", "intro", None);
+
+    assert_eq!(resolved, [
+      "programs/P000-common/assets/logo.png",
+      "programs/P001/shared/figure.png",
+    ]);
+    assert_eq!(unresolved, ["../../../outside.png"]);
+
+    let references = BTreeSet::from([
+      "/content/programs/P001/shared/figure.png".to_string(),
+      "/content/programs/P000-common/assets/logo.png".to_string(),
+    ]);
+    let (resolved, unresolved) =
+      resolve_markdown_assets(&layout, &references, &asset_map, "P001", "intro", None);
+    assert_eq!(resolved.len(), 2);
+    assert!(unresolved.is_empty());
+  }
 }
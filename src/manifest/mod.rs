@@ -1,14 +1,34 @@
 //! Offline manifest generation broken into focused submodules for easier testing.
 
+pub mod attribution;
+mod bundleignore;
+pub mod captions;
+pub mod catalog_module;
+pub mod embeds;
 mod generation;
+pub mod inline_images;
+pub mod inventory;
 mod markdown;
+pub mod posters;
+pub mod remote;
 mod scanning;
+mod source;
+pub mod vendor;
 
-pub use generation::generate_offline_manifest;
+pub use attribution::{AssetCredit, collect_asset_credits, render_credits_page};
+pub use bundleignore::{BUNDLE_IGNORE_FILE, BundleIgnore};
+pub use catalog_module::{CATALOG_TYPES, write_catalog_module};
+pub use generation::{
+  COLLECTION_INTRO_FILES, discover_collections, generate_offline_manifest,
+  generate_offline_manifest_with_filter,
+};
+pub use inventory::{AssetInventory, build_asset_inventory};
 #[allow(unused_imports)]
 pub use markdown::{
-  collect_markdown_asset_references, parse_entry_markdown, parse_order_from_id,
-  resolve_markdown_assets,
+  DEFAULT_EXCERPT_LENGTH, WORDS_PER_MINUTE, collect_heading_anchors, collect_image_alt_texts,
+  collect_markdown_asset_references, collect_markdown_asset_references_with, count_words,
+  extract_excerpt, heading_slug, lead_section, parse_entry_markdown, parse_order_from_id,
+  reading_minutes, resolve_markdown_assets,
 };
 #[allow(unused_imports)]
 pub use scanning::{collect_assets_recursively, sanitize_const_name};
@@ -0,0 +1,412 @@
+//! Generate poster frames for bundled videos that have none.
+//!
+//! A `<video>` without a `poster` renders as a black rectangle until playback starts, which
+//! looks broken in the offline UI. [`generate_video_posters`] runs an external command, `ffmpeg`
+//! by default, to extract a frame from every such video into a cache keyed by the video's
+//! content, registers the frame as a derived collection asset next to the video and adds the
+//! `poster` attribute to the entry body.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use regex::{Captures, Regex};
+use sha2::{Digest, Sha256};
+
+use crate::asset_paths::{
+  AssetReferenceFilter, make_offline_asset_path, should_ignore_asset_reference,
+};
+use crate::builder::BuildResult;
+use crate::manifest::inventory::file_digest;
+use crate::manifest::markdown::resolve_markdown_assets;
+use crate::manifest::scanning::sanitize_const_name;
+use crate::models::{AssetEntry, ManifestGenerationResult};
+use crate::project::OfflineProjectLayout;
+
+/// Arguments passed to [`PosterOptions::program`] by default; `{input}` and `{output}` are
+/// replaced with the video and the poster destination.
+pub const DEFAULT_POSTER_ARGS: &[&str] = &[
+  "-loglevel",
+  "error",
+  "-y",
+  "-ss",
+  "1",
+  "-i",
+  "{input}",
+  "-frames:v",
+  "1",
+  "{output}",
+];
+
+/// How poster frames are extracted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PosterOptions {
+  /// Program extracting the frame, `ffmpeg` by default.
+  pub program: String,
+  /// Arguments passed to `program`, see [`DEFAULT_POSTER_ARGS`].
+  pub args: Vec<String>,
+  /// Extension of the generated frames, which also selects `ffmpeg`'s output format.
+  pub extension: String,
+  /// Frame cache. `None` uses `<target_dir>/offline-posters` below the manifest directory.
+  pub cache_dir: Option<PathBuf>,
+}
+
+impl Default for PosterOptions {
+  fn default() -> Self {
+    Self {
+      program: "ffmpeg".into(),
+      args: DEFAULT_POSTER_ARGS
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect(),
+      extension: "jpg".into(),
+      cache_dir: None,
+    }
+  }
+}
+
+/// Poster frame registered for a video.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeneratedPoster {
+  /// Collection the video belongs to.
+  pub collection_id: String,
+  /// Relative path of the video within the collection.
+  pub video: String,
+  /// Relative path of the poster within the collection.
+  pub poster: String,
+  /// Whether the frame was extracted by this run rather than taken from the cache.
+  pub generated: bool,
+}
+
+/// Path of the poster for `video`: the same directory and stem with `.poster.<extension>`.
+pub fn poster_path(video: &str, extension: &str) -> String {
+  let (parent, name) = match video.rsplit_once('/') {
+    Some((parent, name)) => (Some(parent), name),
+    None => (None, video),
+  };
+  let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+  match parent {
+    Some(parent) => format!("{parent}/{stem}.poster.{extension}"),
+    None => format!("{stem}.poster.{extension}"),
+  }
+}
+
+/// Give every `<video>` in the entry bodies that lacks a `poster` and plays a bundled video a
+/// generated poster frame.
+///
+/// An asset already at the poster path, e.g. an authored `clip.poster.jpg`, is used as is.
+///
+/// Frames are cached by the video's SHA-256 digest and the command line, so only new or changed
+/// videos run the command. The poster is added to the entry's asset paths and recorded in
+/// `video_posters`; a failed extraction aborts with an error naming the video.
+pub fn generate_video_posters(
+  layout: &OfflineProjectLayout,
+  collections_dir: &Path,
+  manifest: &mut ManifestGenerationResult,
+  filter: &AssetReferenceFilter,
+  options: &PosterOptions,
+  cache_dir: &Path,
+) -> BuildResult<Vec<GeneratedPoster>> {
+  let video_pattern =
+    Regex::new(r"(?is)<video\b([^>]*)>(.*?)</video>").expect("invalid video regex");
+  let source_pattern = Regex::new(r"(?is)<source\b([^>]*)>").expect("invalid source regex");
+  let asset_slugs: BTreeMap<String, Option<String>> = manifest
+    .collection_catalog
+    .iter()
+    .map(|record| (record.id.clone(), record.meta.asset_slug.clone()))
+    .collect();
+  let keys_by_path: BTreeMap<String, (String, String)> = manifest
+    .asset_map
+    .iter()
+    .map(|(key, entry)| {
+      let path =
+        make_offline_asset_path(layout, &entry.collection_id, entry.output_relative_path());
+      (path, key.clone())
+    })
+    .collect();
+  let mut used_names = manifest
+    .asset_map
+    .values()
+    .map(|entry| entry.const_name.clone())
+    .collect();
+  let mut posters = Vec::new();
+
+  for entry in &mut manifest.offline_entries {
+    let asset_slug = asset_slugs
+      .get(&entry.collection_id)
+      .and_then(|slug| slug.as_deref());
+    // Poster reference per video reference, relative the same way as the video.
+    let mut poster_references = BTreeMap::new();
+    for caps in video_pattern.captures_iter(&entry.body) {
+      if attribute(&caps[1], "poster").is_some() {
+        continue;
+      }
+      let Some(reference) = attribute(&caps[1], "src").or_else(|| {
+        source_pattern
+          .captures_iter(&caps[2])
+          .find_map(|source| attribute(&source[1], "src"))
+      }) else {
+        continue;
+      };
+      if poster_references.contains_key(&reference)
+        || should_ignore_asset_reference(&reference)
+        || filter.is_ignored(&reference)
+      {
+        continue;
+      }
+      let (resolved, _) = resolve_markdown_assets(
+        layout,
+        &BTreeSet::from([reference.clone()]),
+        &manifest.asset_map,
+        &entry.collection_id,
+        &entry.entry_id,
+        asset_slug,
+      );
+      let Some((video_path, video_key)) = resolved
+        .first()
+        .and_then(|path| Some((path, keys_by_path.get(path)?)))
+      else {
+        continue;
+      };
+
+      let (collection_id, video) = video_key;
+      let poster_relative = poster_path(video, &options.extension);
+      let poster_key = (collection_id.clone(), poster_relative.clone());
+      if !manifest.asset_map.contains_key(&poster_key) {
+        let source = manifest.asset_map[video_key].source_path(collections_dir);
+        if !source.is_file() {
+          return Err(format!("video {video_path} not found at {}", source.display()).into());
+        }
+        let (cached, generated) = extract_frame(options, &source, cache_dir)?;
+        let const_name = sanitize_const_name(collection_id, &poster_relative, &used_names);
+        used_names.insert(const_name.clone());
+        let mut asset = AssetEntry {
+          const_name,
+          literal_path: format!(
+            "{}/{}/{}",
+            layout.collection_asset_literal_prefix, collection_id, poster_relative
+          ),
+          collection_id: collection_id.clone(),
+          relative_path: poster_relative.clone(),
+          output_path: None,
+          source_file: Some(cached.clone()),
+          size: None,
+          modified: None,
+        };
+        if let Ok(metadata) = fs::metadata(&cached) {
+          asset.record_metadata(&metadata);
+        }
+        manifest.asset_map.insert(poster_key.clone(), asset);
+        posters.push(GeneratedPoster {
+          collection_id: collection_id.clone(),
+          video: video.clone(),
+          poster: poster_relative.clone(),
+          generated,
+        });
+      }
+
+      let poster_bundle_path = make_offline_asset_path(layout, &poster_key.0, &poster_key.1);
+      entry
+        .video_posters
+        .insert(video_path.clone(), poster_bundle_path.clone());
+      if !entry.asset_paths.contains(&poster_bundle_path) {
+        entry.asset_paths.push(poster_bundle_path);
+        entry.asset_paths.sort();
+      }
+      let poster_reference = poster_path(&reference, &options.extension);
+      poster_references.insert(reference, poster_reference);
+    }
+
+    if poster_references.is_empty() {
+      continue;
+    }
+    entry.body = video_pattern
+      .replace_all(&entry.body, |caps: &Captures| {
+        let reference = attribute(&caps[1], "src").or_else(|| {
+          source_pattern
+            .captures_iter(&caps[2])
+            .find_map(|source| attribute(&source[1], "src"))
+        });
+        match reference.and_then(|reference| poster_references.get(&reference)) {
+          Some(poster) if attribute(&caps[1], "poster").is_none() => {
+            format!(
+              "<video poster=\"{poster}\"{}>{}</video>",
+              &caps[1], &caps[2]
+            )
+          }
+          _ => caps[0].to_string(),
+        }
+      })
+      .into_owned();
+  }
+  Ok(posters)
+}
+
+/// Value of the `name` attribute in an HTML tag's attribute list.
+pub(crate) fn attribute(attributes: &str, name: &str) -> Option<String> {
+  let pattern = Regex::new(&format!(
+    r#"(?i)(?:^|\s){name}\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s>]+))"#
+  ))
+  .expect("invalid attribute regex");
+  let caps = pattern.captures(attributes)?;
+  caps
+    .get(1)
+    .or_else(|| caps.get(2))
+    .or_else(|| caps.get(3))
+    .map(|value| value.as_str().trim().to_string())
+}
+
+/// Cached frame of `video`, extracting it first when missing. Returns whether it was extracted.
+fn extract_frame(
+  options: &PosterOptions,
+  video: &Path,
+  cache_dir: &Path,
+) -> BuildResult<(PathBuf, bool)> {
+  let (_, video_digest) = file_digest(video)?;
+  let mut hasher = Sha256::new();
+  hasher.update(video_digest.as_bytes());
+  for part in std::iter::once(&options.program).chain(&options.args) {
+    hasher.update([0]);
+    hasher.update(part.as_bytes());
+  }
+  let digest = format!("{:x}", hasher.finalize());
+  let cached = cache_dir.join(format!("{}.{}", &digest[..16], options.extension));
+  if cached.is_file() {
+    return Ok((cached, false));
+  }
+
+  fs::create_dir_all(cache_dir)?;
+  // Extract next to the cache entry and rename afterwards so an interrupted run never leaves a
+  // truncated frame that later runs would treat as cached. The extension stays last because
+  // `ffmpeg` picks the output format from it.
+  let partial = cache_dir.join(format!("{}.partial.{}", &digest[..16], options.extension));
+  let args = options.args.iter().map(|arg| {
+    arg
+      .replace("{input}", &video.to_string_lossy())
+      .replace("{output}", &partial.to_string_lossy())
+  });
+  let status = Command::new(&options.program)
+    .args(args)
+    .status()
+    .map_err(|err| {
+      format!(
+        "failed to run `{}` to extract a poster from {}: {err}",
+        options.program,
+        video.display()
+      )
+    })?;
+  if !status.success() || !partial.is_file() {
+    let _ = fs::remove_file(&partial);
+    return Err(
+      format!(
+        "failed to extract a poster from {} ({status})",
+        video.display()
+      )
+      .into(),
+    );
+  }
+  fs::rename(&partial, &cached)?;
+  Ok((cached, true))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+  use super::*;
+  use crate::models::OfflineEntryRecord;
+  use tempfile::tempdir;
+
+  #[test]
+  fn registers_extracted_posters_for_videos_without_one() -> BuildResult<()> {
+    let dir = tempdir()?;
+    let collections = dir.path().join("collections");
+    let cache = dir.path().join("cache");
+    fs::create_dir_all(collections.join("alpha/intro/assets"))?;
+    fs::write(collections.join("alpha/intro/assets/clip.mp4"), "video")?;
+    fs::write(collections.join("alpha/intro/assets/tour.webm"), "tour")?;
+    let layout = OfflineProjectLayout::default();
+    let video_asset = |relative_path: &str| AssetEntry {
+      const_name: relative_path.to_uppercase().replace(['/', '.'], "_"),
+      literal_path: format!("/content/programs/alpha/{relative_path}"),
+      collection_id: "alpha".into(),
+      relative_path: relative_path.into(),
+      output_path: None,
+      source_file: None,
+      size: None,
+      modified: None,
+    };
+    let body = concat!(
+      "<video controls src=\"assets/clip.mp4\"></video>\n\n",
+      "<video poster=\"assets/tour.png\" src=\"assets/tour.webm\"></video>\n",
+    );
+    let mut manifest = ManifestGenerationResult {
+      collection_catalog: Vec::new(),
+      offline_entries: vec![OfflineEntryRecord {
+        collection_id: "alpha".into(),
+        entry_id: "intro".into(),
+        body: body.into(),
+        asset_paths: Vec::new(),
+        word_count: 0,
+        reading_minutes: 0,
+        headings: Default::default(),
+        images: Vec::new(),
+        documents: Vec::new(),
+        priority_assets: Vec::new(),
+        video_posters: BTreeMap::new(),
+      }],
+      asset_map: ["intro/assets/clip.mp4", "intro/assets/tour.webm"]
+        .map(|path| (("alpha".to_string(), path.to_string()), video_asset(path)))
+        .into_iter()
+        .collect(),
+      hero_asset_paths: BTreeSet::new(),
+      hero_match_arms: Vec::new(),
+      excluded_collections: Vec::new(),
+    };
+    let options = PosterOptions {
+      program: "sh".into(),
+      args: vec![
+        "-c".into(),
+        "cp \"$0\" \"$1\"".into(),
+        "{input}".into(),
+        "{output}".into(),
+      ],
+      ..PosterOptions::default()
+    };
+    let filter = AssetReferenceFilter::default();
+    let generate = |manifest: &mut ManifestGenerationResult| {
+      generate_video_posters(&layout, &collections, manifest, &filter, &options, &cache)
+    };
+
+    let posters = generate(&mut manifest.clone())?;
+    assert_eq!(posters, [GeneratedPoster {
+      collection_id: "alpha".into(),
+      video: "intro/assets/clip.mp4".into(),
+      poster: "intro/assets/clip.poster.jpg".into(),
+      generated: true,
+    }]);
+
+    let posters = generate(&mut manifest)?;
+    assert!(!posters[0].generated);
+
+    let entry = &manifest.offline_entries[0];
+    assert!(entry.body.starts_with(
+      "<video poster=\"assets/clip.poster.jpg\" controls src=\"assets/clip.mp4\"></video>"
+    ));
+    assert!(entry.body.contains("<video poster=\"assets/tour.png\" src"));
+    let poster_path = make_offline_asset_path(&layout, "alpha", "intro/assets/clip.poster.jpg");
+    assert_eq!(entry.asset_paths, [poster_path.as_str()]);
+    assert_eq!(
+      entry.video_posters,
+      BTreeMap::from([(
+        make_offline_asset_path(&layout, "alpha", "intro/assets/clip.mp4"),
+        poster_path
+      )])
+    );
+    let poster = &manifest.asset_map[&("alpha".to_string(), "intro/assets/clip.poster.jpg".into())];
+    assert_eq!(
+      fs::read_to_string(poster.source_path(&collections))?,
+      "video"
+    );
+    Ok(())
+  }
+}
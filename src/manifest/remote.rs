@@ -0,0 +1,432 @@
+//! Sync collections from a git repository or an S3 prefix before they are scanned.
+//!
+//! Content often lives in a separate repository or bucket that build machines would otherwise
+//! have to check out by hand. [`sync_remote_content`] brings a cache directory up to date with a
+//! [`RemoteSource`] using the `git` or `aws` command line tools, optionally pinned to a git
+//! revision or to the combined ETag of an S3 prefix, and skips the transfer when the cache
+//! already holds the pinned or current content.
+//!
+//! The cache lives below `<target_dir>/offline-remote`, see [`RemoteSource::cache_dir`], and is
+//! the directory the build context scans. Syncs force checkouts and delete files that are gone
+//! from the bucket, so they never run in an authored collections directory.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::builder::BuildResult;
+use crate::project::OfflineProjectLayout;
+
+/// File in a synced S3 cache recording the combined ETag of its content.
+pub const REMOTE_STATE_FILE: &str = ".offline-remote";
+/// Directory below the target directory holding one cache per remote source.
+pub const REMOTE_CACHE_DIR: &str = "offline-remote";
+
+/// Where collections are synced from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RemoteSource {
+  /// A git repository, fetched shallowly and checked out detached.
+  Git {
+    /// Repository URL or path accepted by `git fetch`.
+    url: String,
+    /// Branch, tag or commit to check out; `None` follows the remote `HEAD`. A full commit
+    /// hash that is already checked out is not fetched again.
+    revision: Option<String>,
+    /// Program run for git commands, `git` by default.
+    program: String,
+  },
+  /// Objects below an S3 prefix, synced with `aws s3 sync --delete`.
+  S3 {
+    /// Prefix as `s3://bucket/path`.
+    uri: String,
+    /// Expected combined ETag, see [`SyncedContent::revision`]; a mismatch fails the sync.
+    etag: Option<String>,
+    /// Program run for AWS commands, `aws` by default.
+    program: String,
+  },
+}
+
+impl RemoteSource {
+  /// Follow the remote `HEAD` of the repository at `url`.
+  pub fn git(url: impl Into<String>) -> Self {
+    Self::Git {
+      url: url.into(),
+      revision: None,
+      program: "git".into(),
+    }
+  }
+
+  /// Mirror the objects below `uri`, e.g. `s3://content/programs`.
+  pub fn s3(uri: impl Into<String>) -> Self {
+    Self::S3 {
+      uri: uri.into(),
+      etag: None,
+      program: "aws".into(),
+    }
+  }
+
+  /// Pin the source to a git revision or an S3 combined ETag.
+  pub fn pinned(mut self, pin: impl Into<String>) -> Self {
+    match &mut self {
+      Self::Git { revision, .. } => *revision = Some(pin.into()),
+      Self::S3 { etag, .. } => *etag = Some(pin.into()),
+    }
+    self
+  }
+
+  /// Repository URL or S3 prefix.
+  pub fn location(&self) -> &str {
+    match self {
+      Self::Git { url, .. } => url,
+      Self::S3 { uri, .. } => uri,
+    }
+  }
+
+  /// Cache directory this source is synced into, created when missing.
+  ///
+  /// It is `<target_dir>/offline-remote/<digest>`, keyed by [`RemoteSource::location`] so
+  /// switching sources never mixes their files. Build the context with it as the collections
+  /// directory; [`RemoteSourceStage`](crate::pipeline::RemoteSourceStage) syncs nowhere else.
+  pub fn cache_dir(
+    &self,
+    manifest_dir: &Path,
+    layout: &OfflineProjectLayout,
+  ) -> io::Result<PathBuf> {
+    let digest = format!("{:x}", Sha256::digest(self.location().as_bytes()));
+    let dir = manifest_dir
+      .join(&layout.target_dir)
+      .join(REMOTE_CACHE_DIR)
+      .join(&digest[..16]);
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+  }
+}
+
+/// Outcome of [`sync_remote_content`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncedContent {
+  /// Repository URL or S3 prefix.
+  pub source: String,
+  /// Checked out commit, or the SHA-256 over the keys and ETags of every object below the S3
+  /// prefix.
+  pub revision: String,
+  /// Whether the destination changed.
+  pub updated: bool,
+}
+
+/// Bring `destination` up to date with `source`.
+///
+/// `destination` should be a dedicated cache such as [`RemoteSource::cache_dir`]: local changes
+/// in it are overwritten.
+///
+/// S3 syncs keep [`REMOTE_STATE_FILE`] and `*.local.json` files such as the local collection
+/// selection.
+pub fn sync_remote_content(
+  source: &RemoteSource,
+  destination: &Path,
+) -> BuildResult<SyncedContent> {
+  fs::create_dir_all(destination)?;
+  let destination = destination
+    .to_str()
+    .ok_or_else(|| format!("{} is not valid UTF-8", destination.display()))?;
+  match source {
+    RemoteSource::Git {
+      url,
+      revision,
+      program,
+    } => sync_git(program, url, revision.as_deref(), destination),
+    RemoteSource::S3 { uri, etag, program } => sync_s3(program, uri, etag.as_deref(), destination),
+  }
+}
+
+fn sync_git(
+  program: &str,
+  url: &str,
+  revision: Option<&str>,
+  destination: &str,
+) -> BuildResult<SyncedContent> {
+  for value in [Some(url), revision].into_iter().flatten() {
+    if value.starts_with('-') {
+      return Err(format!("git source `{value}` must not start with `-`").into());
+    }
+  }
+  let git = |args: &[&str]| {
+    let mut all = vec!["-C", destination];
+    all.extend_from_slice(args);
+    run(program, &all)
+  };
+  if !Path::new(destination).join(".git").exists() {
+    git(&["init", "--quiet"])?;
+  }
+  let previous = git(&["rev-parse", "--verify", "--quiet", "HEAD"]).ok();
+  let pinned_commit = revision
+    .filter(|revision| revision.len() == 40 && revision.chars().all(|c| c.is_ascii_hexdigit()));
+  if let (Some(previous), Some(pinned)) = (&previous, pinned_commit)
+    && previous.eq_ignore_ascii_case(pinned)
+  {
+    return Ok(SyncedContent {
+      source: url.to_string(),
+      revision: previous.clone(),
+      updated: false,
+    });
+  }
+
+  git(&[
+    "fetch",
+    "--quiet",
+    "--depth",
+    "1",
+    "--",
+    url,
+    revision.unwrap_or("HEAD"),
+  ])?;
+  git(&["checkout", "--quiet", "--force", "--detach", "FETCH_HEAD"])?;
+  let commit = git(&["rev-parse", "HEAD"])?;
+  Ok(SyncedContent {
+    source: url.to_string(),
+    updated: previous.as_deref() != Some(commit.as_str()),
+    revision: commit,
+  })
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ObjectListing {
+  #[serde(default)]
+  contents: Vec<ListedObject>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListedObject {
+  key: String,
+  #[serde(rename = "ETag")]
+  etag: String,
+}
+
+fn sync_s3(
+  program: &str,
+  uri: &str,
+  pinned: Option<&str>,
+  destination: &str,
+) -> BuildResult<SyncedContent> {
+  let (bucket, prefix) = uri
+    .strip_prefix("s3://")
+    .map(|rest| rest.split_once('/').unwrap_or((rest, "")))
+    .filter(|(bucket, _)| !bucket.is_empty())
+    .ok_or_else(|| format!("expected an s3://bucket/prefix URI, found `{uri}`"))?;
+  let listing = run(program, &[
+    "s3api",
+    "list-objects-v2",
+    "--bucket",
+    bucket,
+    "--prefix",
+    prefix,
+    "--output",
+    "json",
+  ])?;
+  let mut objects = if listing.is_empty() {
+    Vec::new()
+  } else {
+    serde_json::from_str::<ObjectListing>(&listing)
+      .map_err(|err| format!("unexpected object listing for {uri}: {err}"))?
+      .contents
+  };
+  if objects.is_empty() {
+    return Err(format!("no objects found below {uri}").into());
+  }
+  objects.sort_by(|a, b| a.key.cmp(&b.key));
+  let mut hasher = Sha256::new();
+  for object in &objects {
+    hasher.update(object.key.as_bytes());
+    hasher.update([0]);
+    hasher.update(object.etag.trim_matches('"').as_bytes());
+    hasher.update(b"\n");
+  }
+  let revision = format!("{:x}", hasher.finalize());
+  if let Some(pinned) = pinned
+    && !pinned.eq_ignore_ascii_case(&revision)
+  {
+    return Err(format!("content below {uri} has ETag {revision}, pinned to {pinned}").into());
+  }
+
+  let state_path = Path::new(destination).join(REMOTE_STATE_FILE);
+  if fs::read_to_string(&state_path).is_ok_and(|state| state.trim() == revision) {
+    return Ok(SyncedContent {
+      source: uri.to_string(),
+      revision,
+      updated: false,
+    });
+  }
+  run(program, &[
+    "s3",
+    "sync",
+    "--only-show-errors",
+    "--delete",
+    "--exclude",
+    REMOTE_STATE_FILE,
+    "--exclude",
+    "*.local.json",
+    uri,
+    destination,
+  ])?;
+  fs::write(&state_path, format!("{revision}\n"))?;
+  Ok(SyncedContent {
+    source: uri.to_string(),
+    revision,
+    updated: true,
+  })
+}
+
+/// Run `program` and return its trimmed standard output, failing with its standard error.
+fn run(program: &str, args: &[&str]) -> BuildResult<String> {
+  let output = Command::new(program)
+    .args(args)
+    .output()
+    .map_err(|err| format!("failed to run `{program}`: {err}"))?;
+  if !output.status.success() {
+    return Err(
+      format!(
+        "`{program} {}` failed ({}): {}",
+        args.join(" "),
+        output.status,
+        String::from_utf8_lossy(&output.stderr).trim()
+      )
+      .into(),
+    );
+  }
+  Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+  use super::*;
+  use crate::builder::OfflineBuilder;
+  use crate::pipeline::{FETCH, Pipeline, RemoteSourceStage, SCAN};
+  use crate::project::OfflineBuildContext;
+  use std::os::unix::fs::PermissionsExt;
+  use tempfile::tempdir;
+
+  fn commit(repo: &Path, files: &[(&str, &str)]) -> BuildResult<String> {
+    for (path, content) in files {
+      let path = repo.join(path);
+      fs::create_dir_all(path.parent().unwrap())?;
+      fs::write(path, content)?;
+    }
+    let repo = repo.to_str().unwrap();
+    run("git", &["-C", repo, "add", "--all"])?;
+    run("git", &[
+      "-C",
+      repo,
+      "-c",
+      "user.name=Content",
+      "-c",
+      "user.email=content@example.com",
+      "commit",
+      "--quiet",
+      "--message",
+      "content",
+    ])?;
+    run("git", &["-C", repo, "rev-parse", "HEAD"])
+  }
+
+  #[test]
+  fn syncs_git_repositories_into_the_cache() -> BuildResult<()> {
+    let dir = tempdir()?;
+    let repo = dir.path().join("content");
+    fs::create_dir_all(&repo)?;
+    run("git", &["init", "--quiet", repo.to_str().unwrap()])?;
+    let first = commit(&repo, &[
+      ("P001/collection.json", r#"{"title":"Program"}"#),
+      ("P001/intro/index.md", "# Intro\n"),
+    ])?;
+    let second = commit(&repo, &[("P001/outro/index.md", "# Outro\n")])?;
+
+    let manifest_dir = dir.path().join("app");
+    let authored = manifest_dir.join("programs");
+    fs::create_dir_all(&authored)?;
+    let source = RemoteSource::git(format!("file://{}", repo.display()));
+    let layout = OfflineProjectLayout::default();
+    let cache = source.cache_dir(&manifest_dir, &layout)?;
+    assert!(cache.starts_with(manifest_dir.join("target").join(REMOTE_CACHE_DIR)));
+
+    let synced = sync_remote_content(&source.clone().pinned(&first), &cache)?;
+    assert_eq!(synced.revision, first);
+    assert!(synced.updated);
+    assert!(!cache.join("P001/outro/index.md").exists());
+    let again = sync_remote_content(&source.clone().pinned(&first), &cache)?;
+    assert!(!again.updated);
+
+    let mut pipeline = Pipeline::standard();
+    pipeline.insert_before(SCAN, FETCH, RemoteSourceStage {
+      source: source.clone(),
+    })?;
+    let context = OfflineBuildContext::builder()
+      .manifest_dir(&manifest_dir)
+      .collections_dir(&cache)
+      .build()?;
+    let state = pipeline.run(&OfflineBuilder::new(context), &crate::IncludeAll)?;
+    assert_eq!(state.synced_content.unwrap().revision, second);
+    assert_eq!(state.manifest.unwrap().offline_entries.len(), 2);
+
+    let context = OfflineBuildContext::builder()
+      .manifest_dir(&manifest_dir)
+      .collections_dir(&authored)
+      .build()?;
+    let error = pipeline
+      .run(&OfflineBuilder::new(context), &crate::IncludeAll)
+      .err()
+      .unwrap();
+    assert!(error.to_string().contains("RemoteSource::cache_dir"));
+    assert_eq!(fs::read_dir(&authored)?.count(), 0);
+
+    let error =
+      sync_remote_content(&RemoteSource::git("--upload-pack=touch pwned"), &cache).unwrap_err();
+    assert!(error.to_string().contains("must not start with `-`"));
+    Ok(())
+  }
+
+  #[test]
+  fn syncs_s3_prefixes_once_per_etag() -> BuildResult<()> {
+    let dir = tempdir()?;
+    let remote = dir.path().join("bucket");
+    fs::create_dir_all(remote.join("P001"))?;
+    fs::write(remote.join("P001/collection.json"), "{}")?;
+    let runs = dir.path().join("runs");
+    // Stand-in for the AWS CLI listing one object and copying the fake bucket on `s3 sync`.
+    let aws = dir.path().join("aws");
+    fs::write(
+      &aws,
+      format!(
+        "#!/bin/sh\nif [ \"$1\" = s3api ]; then\n  \
+         echo '{{\"Contents\":[{{\"Key\":\"programs/P001/collection.json\",\"ETag\":\"\\\"abc\\\"\"}}]}}'\n\
+         else\n  echo sync >> {runs}\n  cp -R {remote}/. \"${{10}}\"\nfi\n",
+        runs = runs.display(),
+        remote = remote.display(),
+      ),
+    )?;
+    fs::set_permissions(&aws, fs::Permissions::from_mode(0o755))?;
+    let source = RemoteSource::S3 {
+      uri: "s3://content/programs".into(),
+      etag: None,
+      program: aws.to_string_lossy().into_owned(),
+    };
+    let cache = dir.path().join("cache");
+
+    let first = sync_remote_content(&source, &cache)?;
+    assert!(first.updated);
+    assert!(cache.join("P001/collection.json").is_file());
+    let second = sync_remote_content(&source.clone().pinned(&first.revision), &cache)?;
+    assert_eq!(second.revision, first.revision);
+    assert!(!second.updated);
+    assert_eq!(fs::read_to_string(&runs)?.lines().count(), 1);
+
+    let error = sync_remote_content(&source.pinned("0123"), &cache).unwrap_err();
+    assert!(error.to_string().contains("pinned to 0123"));
+    Ok(())
+  }
+}
@@ -4,9 +4,20 @@ use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 
+use same_file::Handle;
+
+use crate::manifest::attribution::ATTRIBUTION_FILE;
+use crate::manifest::bundleignore::{BUNDLE_IGNORE_FILE, BundleIgnore};
+use crate::manifest::source::SourceTree;
 use crate::models::{AssetEntry, AssetScanningConfig};
 
 /// Walk the collection directory collecting asset entries and generated constant names.
+///
+/// Files and directories matched by a
+/// [`BUNDLE_IGNORE_FILE`](crate::manifest::BUNDLE_IGNORE_FILE) in `dir` or any directory below it
+/// are skipped. Symlinked directories are followed, but directories deeper than
+/// `config.max_depth` or already being scanned further up (symlink loops, bind mounts) are
+/// skipped with a cargo warning.
 pub fn collect_assets_recursively(
   collection_id: &str,
   dir: &Path,
@@ -16,70 +27,201 @@ pub fn collect_assets_recursively(
   used_names: &mut BTreeSet<String>,
   config: &AssetScanningConfig,
 ) {
-  if let Ok(entries) = fs::read_dir(dir) {
-    for entry in entries.flatten() {
-      let file_name = entry.file_name();
-      let name_str = file_name.to_string_lossy();
-      if name_str.starts_with('.') {
+  let scope = ScanScope {
+    relative_root: relative_root.to_path_buf(),
+    in_assets_tree,
+    depth: 0,
+    ignore: BundleIgnore::default(),
+  };
+  let mut state = ScanState {
+    ancestors: Vec::new(),
+    sources: &SourceTree::default(),
+  };
+  scan_directory(
+    collection_id,
+    dir,
+    scope,
+    asset_map,
+    used_names,
+    config,
+    &mut state,
+  );
+}
+
+/// Collect the assets of the collection rooted at `dir` like [`collect_assets_recursively`],
+/// reusing the directory listings of `sources`.
+pub(crate) fn collect_collection_assets(
+  collection_id: &str,
+  dir: &Path,
+  asset_map: &mut BTreeMap<(String, String), AssetEntry>,
+  used_names: &mut BTreeSet<String>,
+  config: &AssetScanningConfig,
+  sources: &SourceTree,
+) {
+  let scope = ScanScope {
+    relative_root: PathBuf::new(),
+    in_assets_tree: false,
+    depth: 0,
+    ignore: BundleIgnore::default(),
+  };
+  let mut state = ScanState {
+    ancestors: Vec::new(),
+    sources,
+  };
+  scan_directory(
+    collection_id,
+    dir,
+    scope,
+    asset_map,
+    used_names,
+    config,
+    &mut state,
+  );
+}
+
+/// Position of a directory within the collection walk.
+struct ScanScope {
+  relative_root: PathBuf,
+  in_assets_tree: bool,
+  depth: usize,
+  ignore: BundleIgnore,
+}
+
+/// State shared by the whole walk: the directories currently being scanned and the listings.
+struct ScanState<'a> {
+  ancestors: Vec<Handle>,
+  sources: &'a SourceTree,
+}
+
+fn scan_directory(
+  collection_id: &str,
+  dir: &Path,
+  mut scope: ScanScope,
+  asset_map: &mut BTreeMap<(String, String), AssetEntry>,
+  used_names: &mut BTreeSet<String>,
+  config: &AssetScanningConfig,
+  state: &mut ScanState,
+) {
+  // Compare device and inode (or file index) against the directories currently being walked,
+  // which catches symlink loops regardless of the path they are reached through.
+  let Ok(handle) = Handle::from_path(dir) else {
+    return;
+  };
+  if state.ancestors.contains(&handle) {
+    println!(
+      "cargo:warning=Skipping directory cycle at {} in collection '{}'",
+      dir.display(),
+      collection_id
+    );
+    return;
+  }
+  state.ancestors.push(handle);
+
+  let relative_root = scope.relative_root.as_path();
+  let in_assets_tree = scope.in_assets_tree;
+  if state.sources.has_file(dir, BUNDLE_IGNORE_FILE) {
+    scope
+      .ignore
+      .load(dir, &relative_root.to_string_lossy().replace('\\', "/"));
+  }
+
+  let entries = state.sources.list(dir);
+  for entry in entries.iter() {
+    let file_name = &entry.name;
+    let name_str = file_name.as_str();
+    if name_str.starts_with('.') || name_str == ATTRIBUTION_FILE {
+      continue;
+    }
+
+    let path = &entry.path;
+    let (is_dir, is_file) = (entry.is_dir, entry.is_file);
+
+    let mut next_relative = PathBuf::from(relative_root);
+    if !relative_root.as_os_str().is_empty() {
+      next_relative.push(file_name);
+    } else {
+      next_relative = PathBuf::from(file_name);
+    }
+    let rel_path_str = next_relative.to_string_lossy().replace('\\', "/");
+    if scope.ignore.is_ignored(&rel_path_str, is_dir) {
+      continue;
+    }
+
+    if is_dir {
+      if in_assets_tree && name_str == config.excluded_dir_name {
+        continue;
+      }
+      if scope.depth >= config.max_depth {
+        println!(
+          "cargo:warning=Skipping {}/{}: deeper than the scan depth limit of {}",
+          collection_id, rel_path_str, config.max_depth
+        );
         continue;
       }
+      let next_scope = ScanScope {
+        relative_root: next_relative,
+        in_assets_tree: in_assets_tree || name_str == config.entry_assets_dir,
+        depth: scope.depth + 1,
+        ignore: scope.ignore.clone(),
+      };
+      scan_directory(
+        collection_id,
+        path,
+        next_scope,
+        asset_map,
+        used_names,
+        config,
+        state,
+      );
+    } else if is_file
+      && (in_assets_tree
+        || name_str == config.entry_markdown_file
+        || name_str == config.collection_metadata_file)
+    {
+      if rel_path_str.contains(config.excluded_path_fragment) {
+        continue;
+      }
+      if name_str != config.entry_markdown_file
+        && name_str != config.collection_metadata_file
+        && let Some(reason) = config.extension_rejection(name_str)
+      {
+        println!(
+          "cargo:warning=Skipping {}/{}: {}",
+          collection_id, rel_path_str, reason
+        );
+        continue;
+      }
+
+      let key = (collection_id.to_string(), rel_path_str.clone());
+      if asset_map.contains_key(&key) {
+        continue;
+      }
+
+      let const_name = sanitize_const_name(collection_id, &rel_path_str, used_names);
+      used_names.insert(const_name.clone());
+      let literal_path = format!(
+        "{}/{}/{}",
+        config.collection_asset_literal_prefix, collection_id, rel_path_str
+      );
 
-      let path = entry.path();
-      if let Ok(file_type) = entry.file_type() {
-        let mut next_relative = PathBuf::from(relative_root);
-        if !relative_root.as_os_str().is_empty() {
-          next_relative.push(&file_name);
-        } else {
-          next_relative = PathBuf::from(&file_name);
-        }
-
-        if file_type.is_dir() {
-          if in_assets_tree && name_str == config.excluded_dir_name {
-            continue;
-          }
-          let next_in_assets = in_assets_tree || name_str == config.entry_assets_dir;
-          collect_assets_recursively(
-            collection_id,
-            &path,
-            &next_relative,
-            next_in_assets,
-            asset_map,
-            used_names,
-            config,
-          );
-        } else if file_type.is_file()
-          && (in_assets_tree
-            || name_str == config.entry_markdown_file
-            || name_str == config.collection_metadata_file)
-        {
-          let rel_path_str = next_relative.to_string_lossy().replace('\\', "/");
-
-          if rel_path_str.contains(config.excluded_path_fragment) {
-            continue;
-          }
-
-          let key = (collection_id.to_string(), rel_path_str.clone());
-          if asset_map.contains_key(&key) {
-            continue;
-          }
-
-          let const_name = sanitize_const_name(collection_id, &rel_path_str, used_names);
-          used_names.insert(const_name.clone());
-          let literal_path = format!(
-            "{}/{}/{}",
-            config.collection_asset_literal_prefix, collection_id, rel_path_str
-          );
-
-          asset_map.insert(key, AssetEntry {
-            const_name,
-            literal_path,
-            collection_id: collection_id.to_string(),
-            relative_path: rel_path_str,
-          });
-        }
+      let mut asset = AssetEntry {
+        const_name,
+        literal_path,
+        collection_id: collection_id.to_string(),
+        relative_path: rel_path_str,
+        output_path: None,
+        source_file: None,
+        size: None,
+        modified: None,
+      };
+      if let Ok(metadata) = fs::metadata(path) {
+        asset.record_metadata(&metadata);
       }
+      asset_map.insert(key, asset);
     }
   }
+
+  state.ancestors.pop();
 }
 
 /// Generate a valid Rust identifier for a collection asset, deduplicating collisions.
@@ -152,6 +294,9 @@ mod tests {
       excluded_path_fragment: "/prod/",
       collection_asset_literal_prefix: "/content/programs",
       collection_metadata_file: "collection.json",
+      max_depth: 32,
+      asset_extensions: &[],
+      excluded_extensions: &[],
     };
 
     collect_assets_recursively(
@@ -166,9 +311,148 @@ mod tests {
 
     assert!(asset_map.contains_key(&("collection".into(), "collection.json".into())));
     assert!(asset_map.contains_key(&("collection".into(), "entries/entry-one/index.md".into())));
-    assert!(asset_map.contains_key(&(
-      "collection".into(),
-      "entries/entry-one/assets/image.png".into()
-    )));
+    let image = &asset_map[&(
+      "collection".to_string(),
+      "entries/entry-one/assets/image.png".to_string(),
+    )];
+    assert_eq!(image.size, Some(6));
+    assert!(image.modified.is_some());
+  }
+
+  #[test]
+  fn skips_files_matched_by_bundleignore() {
+    let dir = tempdir().unwrap();
+    let collection_dir = dir.path().join("collection");
+    let assets = collection_dir.join("intro/assets");
+    fs::create_dir_all(assets.join("raw")).unwrap();
+    fs::write(collection_dir.join(".bundleignore"), "*.psd\n").unwrap();
+    fs::write(assets.join(".bundleignore"), "raw/\n!logo.psd\n").unwrap();
+    for file in ["cover.png", "cover.psd", "logo.psd", "raw/take-1.mov"] {
+      fs::write(assets.join(file), "data").unwrap();
+    }
+
+    let mut asset_map = BTreeMap::new();
+    let config = AssetScanningConfig {
+      excluded_dir_name: "prod",
+      entry_assets_dir: "assets",
+      entry_markdown_file: "index.md",
+      excluded_path_fragment: "/prod/",
+      collection_asset_literal_prefix: "/content/programs",
+      collection_metadata_file: "collection.json",
+      max_depth: 32,
+      asset_extensions: &[],
+      excluded_extensions: &[],
+    };
+    collect_assets_recursively(
+      "collection",
+      &collection_dir,
+      Path::new(""),
+      false,
+      &mut asset_map,
+      &mut BTreeSet::new(),
+      &config,
+    );
+
+    let paths: Vec<&str> = asset_map.keys().map(|(_, path)| path.as_str()).collect();
+    assert_eq!(paths, ["intro/assets/cover.png", "intro/assets/logo.psd"]);
+  }
+
+  #[test]
+  fn applies_extension_allow_and_deny_lists() {
+    let dir = tempdir().unwrap();
+    let collection_dir = dir.path().join("collection");
+    let assets = collection_dir.join("intro/assets");
+    fs::create_dir_all(&assets).unwrap();
+    fs::write(collection_dir.join("intro/index.md"), "content").unwrap();
+    for file in [
+      "cover.PNG",
+      "clip.mp4",
+      "master.mov",
+      "sources.zip",
+      "README",
+    ] {
+      fs::write(assets.join(file), "data").unwrap();
+    }
+
+    let allowed = ["png".to_string(), "mp4".to_string(), "mov".to_string()];
+    let excluded = ["mov".to_string()];
+    let mut asset_map = BTreeMap::new();
+    let config = AssetScanningConfig {
+      excluded_dir_name: "prod",
+      entry_assets_dir: "assets",
+      entry_markdown_file: "index.md",
+      excluded_path_fragment: "/prod/",
+      collection_asset_literal_prefix: "/content/programs",
+      collection_metadata_file: "collection.json",
+      max_depth: 32,
+      asset_extensions: &allowed,
+      excluded_extensions: &excluded,
+    };
+    collect_assets_recursively(
+      "collection",
+      &collection_dir,
+      Path::new(""),
+      false,
+      &mut asset_map,
+      &mut BTreeSet::new(),
+      &config,
+    );
+
+    let paths: Vec<&str> = asset_map.keys().map(|(_, path)| path.as_str()).collect();
+    assert_eq!(paths, [
+      "intro/assets/clip.mp4",
+      "intro/assets/cover.PNG",
+      "intro/index.md"
+    ]);
+    assert_eq!(
+      config.extension_rejection("sources.zip").as_deref(),
+      Some("`.zip` is not in the allowed asset extensions")
+    );
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn stops_at_symlink_loops_and_depth_limit() {
+    let dir = tempdir().unwrap();
+    let collection_dir = dir.path().join("collection");
+    let assets = collection_dir.join("intro/assets");
+    fs::create_dir_all(assets.join("a/b")).unwrap();
+    fs::write(assets.join("top.png"), "data").unwrap();
+    fs::write(assets.join("a/b/deep.png"), "data").unwrap();
+    std::os::unix::fs::symlink(&collection_dir, assets.join("loop")).unwrap();
+
+    let scan = |max_depth| {
+      let mut asset_map = BTreeMap::new();
+      let config = AssetScanningConfig {
+        excluded_dir_name: "prod",
+        entry_assets_dir: "assets",
+        entry_markdown_file: "index.md",
+        excluded_path_fragment: "/prod/",
+        collection_asset_literal_prefix: "/content/programs",
+        collection_metadata_file: "collection.json",
+        max_depth,
+        asset_extensions: &[],
+        excluded_extensions: &[],
+      };
+      collect_assets_recursively(
+        "collection",
+        &collection_dir,
+        Path::new(""),
+        false,
+        &mut asset_map,
+        &mut BTreeSet::new(),
+        &config,
+      );
+      asset_map
+        .into_keys()
+        .map(|(_, path)| path)
+        .collect::<Vec<_>>()
+    };
+
+    assert_eq!(scan(32), [
+      "intro/assets/a/b/deep.png",
+      "intro/assets/top.png"
+    ]);
+    assert_eq!(scan(2), ["intro/assets/top.png"]);
   }
 }
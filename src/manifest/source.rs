@@ -0,0 +1,126 @@
+//! Memoised view of the authored content tree shared by one manifest generation.
+//!
+//! Collection walking, entry discovery and asset scanning visit the same directories. Rather
+//! than probing paths with `exists`/`is_dir` and re-reading metadata documents at every step,
+//! [`SourceTree`] lists each directory once, answers existence checks from those listings and
+//! reads each metadata document at most once, which keeps round trips down on network file
+//! systems.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use serde_json::Value;
+
+use crate::config::{CollectionConfigOverrides, load_document};
+use crate::project::LEGACY_COLLECTION_METADATA_FILE;
+
+/// Directory entry with symlinks resolved to what they point at.
+#[derive(Debug, Clone)]
+pub(crate) struct SourceEntry {
+  pub name: String,
+  pub path: PathBuf,
+  pub is_dir: bool,
+  pub is_file: bool,
+}
+
+/// Directory listings and parsed metadata documents, each read from disk once.
+#[derive(Debug, Default)]
+pub(crate) struct SourceTree {
+  listings: RefCell<BTreeMap<PathBuf, Rc<[SourceEntry]>>>,
+  documents: RefCell<BTreeMap<PathBuf, Option<(Value, CollectionConfigOverrides)>>>,
+}
+
+impl SourceTree {
+  /// Entries of `dir` sorted by name; empty when it cannot be read.
+  pub fn list(&self, dir: &Path) -> Rc<[SourceEntry]> {
+    if let Some(listing) = self.listings.borrow().get(dir) {
+      return Rc::clone(listing);
+    }
+    let mut entries: Vec<SourceEntry> = fs::read_dir(dir)
+      .into_iter()
+      .flatten()
+      .flatten()
+      .filter_map(|entry| {
+        let path = entry.path();
+        let file_type = entry.file_type().ok()?;
+        let (is_dir, is_file) = if file_type.is_symlink() {
+          let metadata = fs::metadata(&path).ok()?;
+          (metadata.is_dir(), metadata.is_file())
+        } else {
+          (file_type.is_dir(), file_type.is_file())
+        };
+        Some(SourceEntry {
+          name: entry.file_name().to_string_lossy().to_string(),
+          path,
+          is_dir,
+          is_file,
+        })
+      })
+      .collect();
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    let listing: Rc<[SourceEntry]> = entries.into();
+    self
+      .listings
+      .borrow_mut()
+      .insert(dir.to_path_buf(), Rc::clone(&listing));
+    listing
+  }
+
+  /// Whether `dir` contains a regular file (or a link to one) called `name`.
+  pub fn has_file(&self, dir: &Path, name: &str) -> bool {
+    self
+      .list(dir)
+      .iter()
+      .any(|entry| entry.is_file && entry.name == name)
+  }
+
+  /// Metadata document of the collection in `dir`, with the same legacy fallback as
+  /// [`collection_metadata_path`](crate::project::collection_metadata_path), or `None` when
+  /// `dir` holds neither file.
+  pub fn metadata_path(&self, dir: &Path, file_name: &str) -> Option<PathBuf> {
+    [file_name, LEGACY_COLLECTION_METADATA_FILE]
+      .into_iter()
+      .find(|name| self.has_file(dir, name))
+      .map(|name| dir.join(name))
+  }
+
+  /// Payload and configuration overrides of the document at `path`, see [`load_document`].
+  pub fn document(&self, path: &Path) -> Option<(Value, CollectionConfigOverrides)> {
+    self
+      .documents
+      .borrow_mut()
+      .entry(path.to_path_buf())
+      .or_insert_with(|| load_document(path))
+      .clone()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::tempdir;
+
+  #[test]
+  fn answers_from_cached_listings() {
+    let dir = tempdir().unwrap();
+    fs::write(dir.path().join("program.json"), r#"{"title": "Legacy"}"#).unwrap();
+    fs::create_dir(dir.path().join("entry")).unwrap();
+    let sources = SourceTree::default();
+
+    assert_eq!(
+      sources.metadata_path(dir.path(), "collection.json"),
+      Some(dir.path().join("program.json"))
+    );
+    assert!(!sources.has_file(dir.path(), "entry"));
+
+    // Files created after the first listing are not seen again.
+    fs::write(dir.path().join("collection.json"), "{}").unwrap();
+    assert!(!sources.has_file(dir.path(), "collection.json"));
+    let listing = sources.list(dir.path());
+    let names: Vec<&str> = listing.iter().map(|entry| entry.name.as_str()).collect();
+    assert_eq!(names, ["entry", "program.json"]);
+  }
+}
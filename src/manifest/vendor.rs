@@ -0,0 +1,310 @@
+//! Download allowlisted remote assets referenced by entry bodies so they ship with the bundle.
+//!
+//! Authors often link images straight from a CDN. [`vendor_remote_assets`] fetches every image
+//! or `src` URL whose host is allowlisted into a cache keyed by the URL's hash, registers the
+//! cached file as a collection asset below `<entry_assets_dir>/vendor/` and points the entry
+//! body at it, so the mirror, inventory and codegen treat it like any authored asset.
+
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use pulldown_cmark::{Event, Options, Parser, Tag};
+use regex::Regex;
+use sha2::{Digest, Sha256};
+
+use crate::asset_paths::make_offline_asset_path;
+use crate::builder::BuildResult;
+use crate::bundle::site::{external_host, host_matches};
+use crate::manifest::scanning::sanitize_const_name;
+use crate::models::{AssetEntry, ManifestGenerationResult};
+use crate::project::OfflineProjectLayout;
+
+/// Directory below a collection's assets directory that receives vendored files.
+pub const VENDOR_DIR_NAME: &str = "vendor";
+
+/// Arguments passed to [`VendorOptions::program`] by default; `{url}` and `{output}` are
+/// replaced with the remote URL and the download destination.
+pub const DEFAULT_FETCH_ARGS: &[&str] = &[
+  "--fail",
+  "--silent",
+  "--show-error",
+  "--location",
+  "--output",
+  "{output}",
+  "{url}",
+];
+
+/// Which remote references to vendor and how to download them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VendorOptions {
+  /// Hosts whose references are downloaded. Subdomains match, `*` matches every host.
+  pub allowed_hosts: Vec<String>,
+  /// Download cache. `None` uses `<target_dir>/offline-vendor` below the manifest directory.
+  pub cache_dir: Option<PathBuf>,
+  /// Program used for downloads, `curl` by default.
+  pub program: String,
+  /// Arguments passed to `program`, see [`DEFAULT_FETCH_ARGS`].
+  pub args: Vec<String>,
+}
+
+impl VendorOptions {
+  /// Vendor references to `allowed_hosts` using `curl` and the default cache.
+  pub fn new<I, S>(allowed_hosts: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+  {
+    Self {
+      allowed_hosts: allowed_hosts.into_iter().map(Into::into).collect(),
+      cache_dir: None,
+      program: "curl".into(),
+      args: DEFAULT_FETCH_ARGS
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect(),
+    }
+  }
+}
+
+/// Remote reference replaced by a bundled copy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VendoredAsset {
+  /// URL as written in the entry body.
+  pub url: String,
+  /// Collection the asset was registered in.
+  pub collection_id: String,
+  /// Relative path of the asset within the collection.
+  pub relative_path: String,
+  /// Whether the file was downloaded by this run rather than taken from the cache.
+  pub downloaded: bool,
+}
+
+/// Remote image and `src` URLs of a markdown body whose host is in `allowed_hosts`.
+pub fn collect_vendor_urls(markdown: &str, allowed_hosts: &[String]) -> BTreeSet<String> {
+  let src_pattern = Regex::new(r#"(?i)\bsrc\s*=\s*["']([^"']+)["']"#).expect("invalid src regex");
+  let mut urls = BTreeSet::new();
+  let mut add = |url: &str| {
+    if external_host(url).is_some_and(|host| host_matches(&host, allowed_hosts)) {
+      urls.insert(url.trim().to_string());
+    }
+  };
+  for event in Parser::new_ext(markdown, Options::all()) {
+    match event {
+      Event::Start(Tag::Image { dest_url, .. }) => add(&dest_url),
+      Event::Html(html) | Event::InlineHtml(html) => {
+        for caps in src_pattern.captures_iter(&html) {
+          add(&caps[1]);
+        }
+      }
+      _ => {}
+    }
+  }
+  urls
+}
+
+/// Cache file name for `url`: a prefix of its SHA-256 digest plus the URL's file extension.
+pub fn vendor_file_name(url: &str) -> String {
+  let digest = format!("{:x}", Sha256::digest(url.as_bytes()));
+  let path = url.split(['?', '#']).next().unwrap_or_default();
+  let extension = path
+    .rsplit('/')
+    .next()
+    .and_then(|name| name.rsplit_once('.'))
+    .map(|(_, extension)| extension.to_ascii_lowercase())
+    .filter(|extension| {
+      !extension.is_empty()
+        && extension.len() <= 5
+        && extension.chars().all(|c| c.is_ascii_alphanumeric())
+    })
+    .unwrap_or_else(|| "bin".to_string());
+  format!("{}.{extension}", &digest[..16])
+}
+
+/// Download the allowlisted remote references of every entry into `cache_dir` and register them
+/// as collection assets.
+///
+/// Cached files are reused, so only new URLs hit the network. Entry bodies, image records and
+/// asset paths are updated to the vendored copies; a failed download aborts with an error
+/// naming the URL.
+pub fn vendor_remote_assets(
+  layout: &OfflineProjectLayout,
+  manifest: &mut ManifestGenerationResult,
+  options: &VendorOptions,
+  cache_dir: &Path,
+) -> BuildResult<Vec<VendoredAsset>> {
+  let mut used_names: BTreeSet<String> = manifest
+    .asset_map
+    .values()
+    .map(|entry| entry.const_name.clone())
+    .collect();
+  let mut fetched: BTreeMap<String, (PathBuf, bool)> = BTreeMap::new();
+  let mut vendored = Vec::new();
+
+  for entry in &mut manifest.offline_entries {
+    for url in collect_vendor_urls(&entry.body, &options.allowed_hosts) {
+      let file_name = vendor_file_name(&url);
+      if !fetched.contains_key(&url) {
+        let cached = cache_dir.join(&file_name);
+        let downloaded = !cached.is_file();
+        if downloaded {
+          download(options, &url, &cached)?;
+        }
+        fetched.insert(url.clone(), (cached, downloaded));
+      }
+      let (cached, downloaded) = &fetched[&url];
+
+      let relative_path = format!("{}/{VENDOR_DIR_NAME}/{file_name}", layout.entry_assets_dir);
+      let key = (entry.collection_id.clone(), relative_path.clone());
+      if let Entry::Vacant(slot) = manifest.asset_map.entry(key) {
+        let const_name = sanitize_const_name(&entry.collection_id, &relative_path, &used_names);
+        used_names.insert(const_name.clone());
+        let asset = slot.insert(AssetEntry {
+          const_name,
+          literal_path: format!(
+            "{}/{}/{}",
+            layout.collection_asset_literal_prefix, entry.collection_id, relative_path
+          ),
+          collection_id: entry.collection_id.clone(),
+          relative_path: relative_path.clone(),
+          output_path: None,
+          source_file: Some(cached.clone()),
+          size: None,
+          modified: None,
+        });
+        if let Ok(metadata) = fs::metadata(cached) {
+          asset.record_metadata(&metadata);
+        }
+        vendored.push(VendoredAsset {
+          url: url.clone(),
+          collection_id: entry.collection_id.clone(),
+          relative_path: relative_path.clone(),
+          downloaded: *downloaded,
+        });
+      }
+
+      entry.body = entry.body.replace(&url, &relative_path);
+      for image in entry.images.iter_mut().filter(|image| image.src == url) {
+        image.src = relative_path.clone();
+      }
+      let bundle_path = make_offline_asset_path(layout, &entry.collection_id, &relative_path);
+      if !entry.asset_paths.contains(&bundle_path) {
+        entry.asset_paths.push(bundle_path);
+        entry.asset_paths.sort();
+      }
+    }
+  }
+  Ok(vendored)
+}
+
+fn download(options: &VendorOptions, url: &str, destination: &Path) -> BuildResult<()> {
+  if let Some(parent) = destination.parent() {
+    fs::create_dir_all(parent)?;
+  }
+  // Download next to the cache entry and rename afterwards so an interrupted fetch never
+  // leaves a truncated file that later runs would treat as cached.
+  let partial = destination.with_extension("part");
+  let args = options.args.iter().map(|arg| {
+    arg
+      .replace("{url}", url)
+      .replace("{output}", &partial.to_string_lossy())
+  });
+  let status = Command::new(&options.program)
+    .args(args)
+    .status()
+    .map_err(|err| format!("failed to run `{}` to fetch {url}: {err}", options.program))?;
+  if !status.success() || !partial.is_file() {
+    let _ = fs::remove_file(&partial);
+    return Err(format!("failed to fetch {url} ({status})").into());
+  }
+  fs::rename(&partial, destination)?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::models::{ImageAltRecord, OfflineEntryRecord};
+  use tempfile::tempdir;
+
+  const BODY: &str = concat!(
+    "![Cover](https://cdn.example.com/img/cover.PNG?v=2)\n\n",
+    "<img src=\"https://static.cdn.example.com/diagram.svg\" alt=\"Diagram\">\n\n",
+    "![Other](https://elsewhere.org/photo.jpg)\n",
+  );
+
+  #[test]
+  fn collects_allowlisted_urls_only() {
+    let urls = collect_vendor_urls(BODY, &["cdn.example.com".to_string()]);
+
+    assert_eq!(urls.into_iter().collect::<Vec<_>>(), [
+      "https://cdn.example.com/img/cover.PNG?v=2",
+      "https://static.cdn.example.com/diagram.svg",
+    ]);
+    assert!(vendor_file_name("https://cdn.example.com/img/cover.PNG?v=2").ends_with(".png"));
+    assert!(vendor_file_name("https://cdn.example.com/render").ends_with(".bin"));
+  }
+
+  #[test]
+  fn registers_cached_downloads_as_collection_assets() -> BuildResult<()> {
+    let dir = tempdir()?;
+    let layout = OfflineProjectLayout::default();
+    let url = "https://cdn.example.com/img/cover.PNG?v=2";
+    fs::write(dir.path().join(vendor_file_name(url)), "png")?;
+
+    let mut manifest = ManifestGenerationResult {
+      collection_catalog: Vec::new(),
+      offline_entries: vec![OfflineEntryRecord {
+        collection_id: "alpha".into(),
+        entry_id: "intro".into(),
+        body: format!("![Cover]({url})"),
+        asset_paths: Vec::new(),
+        word_count: 0,
+        reading_minutes: 0,
+        headings: Default::default(),
+        images: vec![ImageAltRecord {
+          src: url.into(),
+          alt: "Cover".into(),
+        }],
+        documents: Vec::new(),
+        priority_assets: Vec::new(),
+        video_posters: BTreeMap::new(),
+      }],
+      asset_map: BTreeMap::new(),
+      hero_asset_paths: BTreeSet::new(),
+      hero_match_arms: Vec::new(),
+      excluded_collections: Vec::new(),
+    };
+    // `false` fails if it is ever run, proving the cached copy is reused.
+    let options = VendorOptions {
+      program: "false".into(),
+      ..VendorOptions::new(["cdn.example.com"])
+    };
+
+    let vendored = vendor_remote_assets(&layout, &mut manifest, &options, dir.path())?;
+
+    let relative_path = format!("assets/vendor/{}", vendor_file_name(url));
+    assert_eq!(vendored.len(), 1);
+    assert!(!vendored[0].downloaded);
+    let entry = &manifest.offline_entries[0];
+    assert_eq!(entry.body, format!("![Cover]({relative_path})"));
+    assert_eq!(entry.images[0].src, relative_path);
+    assert_eq!(entry.asset_paths, [make_offline_asset_path(
+      &layout,
+      "alpha",
+      &relative_path
+    )]);
+    let asset = &manifest.asset_map[&("alpha".to_string(), relative_path)];
+    assert_eq!(
+      fs::read_to_string(asset.source_path(Path::new("unused")))?,
+      "png"
+    );
+
+    manifest.offline_entries[0].body = "![New](https://cdn.example.com/new.png)".into();
+    let err = vendor_remote_assets(&layout, &mut manifest, &options, dir.path()).unwrap_err();
+    assert!(err.to_string().contains("https://cdn.example.com/new.png"));
+    Ok(())
+  }
+}
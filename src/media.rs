@@ -0,0 +1,297 @@
+//! Transcoding of bundled video and audio while the asset mirror is populated.
+//!
+//! Authors tend to drop 1080p60 masters into their assets, which quickly pushes a bundle past
+//! distribution limits. [`MediaTranscoder`] is an [`AssetProcessor`] that runs every video and
+//! audio asset through an external command, `ffmpeg` by default, using the codec and bitrate of
+//! its [`TranscodeProfile`]. Results are cached by the source's SHA-256 digest and the command
+//! line, so unchanged masters are only encoded once.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use sha2::{Digest, Sha256};
+
+use crate::builder::BuildResult;
+use crate::manifest::inventory::file_digest;
+use crate::models::AssetEntry;
+use crate::processor::{AssetOutcome, AssetProcessor};
+
+/// Extensions routed through [`MediaTranscoder::video`].
+pub use crate::manifest::captions::VIDEO_EXTENSIONS;
+/// Extensions routed through [`MediaTranscoder::audio`].
+pub const AUDIO_EXTENSIONS: [&str; 7] = ["mp3", "wav", "flac", "aac", "m4a", "ogg", "opus"];
+
+/// Arguments of [`TranscodeProfile::video`]: at most 720p at 30 frames per second with AAC audio.
+pub const DEFAULT_VIDEO_ARGS: &[&str] = &[
+  "-loglevel",
+  "error",
+  "-y",
+  "-i",
+  "{input}",
+  "-c:v",
+  "{codec}",
+  "-b:v",
+  "{bitrate}",
+  "-vf",
+  "scale=-2:'min(720,ih)'",
+  "-r",
+  "30",
+  "-c:a",
+  "aac",
+  "-b:a",
+  "128k",
+  "-movflags",
+  "+faststart",
+  "{output}",
+];
+
+/// Arguments of [`TranscodeProfile::audio`].
+pub const DEFAULT_AUDIO_ARGS: &[&str] = &[
+  "-loglevel",
+  "error",
+  "-y",
+  "-i",
+  "{input}",
+  "-vn",
+  "-c:a",
+  "{codec}",
+  "-b:a",
+  "{bitrate}",
+  "{output}",
+];
+
+/// Target encoding of one kind of media.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranscodeProfile {
+  /// Codec substituted for `{codec}`, e.g. `libx264` or `aac`.
+  pub codec: String,
+  /// Bitrate substituted for `{bitrate}`, e.g. `2M` or `96k`.
+  pub bitrate: String,
+  /// Extension of the transcoded file, which also selects `ffmpeg`'s container.
+  pub extension: String,
+  /// Arguments passed to the program; `{input}`, `{output}`, `{codec}` and `{bitrate}` are
+  /// replaced.
+  pub args: Vec<String>,
+}
+
+impl TranscodeProfile {
+  /// H.264 MP4 video at `bitrate`, see [`DEFAULT_VIDEO_ARGS`].
+  pub fn video(bitrate: impl Into<String>) -> Self {
+    Self {
+      codec: "libx264".into(),
+      bitrate: bitrate.into(),
+      extension: "mp4".into(),
+      args: DEFAULT_VIDEO_ARGS
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect(),
+    }
+  }
+
+  /// AAC audio in an M4A container at `bitrate`, see [`DEFAULT_AUDIO_ARGS`].
+  pub fn audio(bitrate: impl Into<String>) -> Self {
+    Self {
+      codec: "aac".into(),
+      bitrate: bitrate.into(),
+      extension: "m4a".into(),
+      args: DEFAULT_AUDIO_ARGS
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect(),
+    }
+  }
+
+  fn resolved_args(&self, input: &Path, output: &Path) -> Vec<String> {
+    self
+      .args
+      .iter()
+      .map(|arg| {
+        arg
+          .replace("{codec}", &self.codec)
+          .replace("{bitrate}", &self.bitrate)
+          .replace("{input}", &input.to_string_lossy())
+          .replace("{output}", &output.to_string_lossy())
+      })
+      .collect()
+  }
+}
+
+/// [`AssetProcessor`] transcoding video and audio assets into the mirror.
+///
+/// Register it with [`OfflineBuilder::with_asset_processor`](crate::OfflineBuilder::with_asset_processor);
+/// builders for different bundles, e.g. a full and a low-bandwidth one, each get their own
+/// profiles. Files whose extension changes are renamed in the manifest and generated code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaTranscoder {
+  /// Program run for every file, `ffmpeg` by default.
+  pub program: String,
+  /// Profile for [`VIDEO_EXTENSIONS`]; `None` mirrors videos unchanged.
+  pub video: Option<TranscodeProfile>,
+  /// Profile for [`AUDIO_EXTENSIONS`]; `None` mirrors audio unchanged.
+  pub audio: Option<TranscodeProfile>,
+  /// Directory holding transcoded files between builds.
+  pub cache_dir: PathBuf,
+}
+
+impl MediaTranscoder {
+  /// `ffmpeg` with 2 Mbit/s video and 96 kbit/s audio, caching results in `cache_dir`.
+  pub fn new(cache_dir: impl Into<PathBuf>) -> Self {
+    Self {
+      program: "ffmpeg".into(),
+      video: Some(TranscodeProfile::video("2M")),
+      audio: Some(TranscodeProfile::audio("96k")),
+      cache_dir: cache_dir.into(),
+    }
+  }
+
+  /// Profile applying to `relative_path`, by extension.
+  pub fn profile_for(&self, relative_path: &str) -> Option<&TranscodeProfile> {
+    let extension = relative_path.rsplit_once('.')?.1.to_ascii_lowercase();
+    if VIDEO_EXTENSIONS.contains(&extension.as_str()) {
+      self.video.as_ref()
+    } else if AUDIO_EXTENSIONS.contains(&extension.as_str()) {
+      self.audio.as_ref()
+    } else {
+      None
+    }
+  }
+
+  /// Transcoded copy of `source` in the cache, encoding it first when missing.
+  fn transcode(&self, source: &Path, profile: &TranscodeProfile) -> BuildResult<PathBuf> {
+    let (_, source_digest) = file_digest(source)?;
+    let mut hasher = Sha256::new();
+    hasher.update(source_digest.as_bytes());
+    let placeholders = profile.resolved_args(Path::new("{input}"), Path::new("{output}"));
+    for part in std::iter::once(&self.program).chain(&placeholders) {
+      hasher.update([0]);
+      hasher.update(part.as_bytes());
+    }
+    let digest = format!("{:x}", hasher.finalize());
+    let cached = self
+      .cache_dir
+      .join(format!("{}.{}", &digest[..16], profile.extension));
+    if cached.is_file() {
+      return Ok(cached);
+    }
+
+    fs::create_dir_all(&self.cache_dir)?;
+    // Encode next to the cache entry and rename afterwards so an interrupted run never leaves
+    // a truncated file that later builds would treat as cached.
+    let partial = self
+      .cache_dir
+      .join(format!("{}.partial.{}", &digest[..16], profile.extension));
+    let status = Command::new(&self.program)
+      .args(profile.resolved_args(source, &partial))
+      .status()
+      .map_err(|err| {
+        format!(
+          "failed to run `{}` to transcode {}: {err}",
+          self.program,
+          source.display()
+        )
+      })?;
+    if !status.success() || !partial.is_file() {
+      let _ = fs::remove_file(&partial);
+      return Err(format!("failed to transcode {} ({status})", source.display()).into());
+    }
+    fs::rename(&partial, &cached)?;
+    Ok(cached)
+  }
+}
+
+impl AssetProcessor for MediaTranscoder {
+  fn process(
+    &self,
+    source: &Path,
+    destination: &Path,
+    entry: &AssetEntry,
+  ) -> BuildResult<AssetOutcome> {
+    let Some(profile) = self.profile_for(&entry.relative_path) else {
+      return Ok(AssetOutcome::Unchanged);
+    };
+    let cached = self.transcode(source, profile)?;
+
+    let file_name = destination
+      .file_name()
+      .map(|name| name.to_string_lossy().into_owned())
+      .unwrap_or_default();
+    let stem = file_name
+      .rsplit_once('.')
+      .map_or(file_name.as_str(), |(stem, _)| stem);
+    let output_name = format!("{stem}.{}", profile.extension);
+    let output = destination.with_file_name(&output_name);
+    if output.exists() {
+      fs::remove_file(&output)?;
+    }
+    if fs::hard_link(&cached, &output).is_err() {
+      fs::copy(&cached, &output)?;
+    }
+
+    Ok(if output_name == file_name {
+      AssetOutcome::Written
+    } else {
+      AssetOutcome::Renamed(output_name)
+    })
+  }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+  use super::*;
+  use tempfile::tempdir;
+
+  #[test]
+  fn transcodes_media_once_and_renames_changed_extensions() -> BuildResult<()> {
+    let dir = tempdir()?;
+    let source = dir.path().join("master.mov");
+    fs::write(&source, "master")?;
+    let runs = dir.path().join("runs");
+    let mirror = dir.path().join("mirror");
+    fs::create_dir_all(&mirror)?;
+    let profile = TranscodeProfile {
+      args: vec![
+        "-c".into(),
+        "printf '%s@%s' \"$(cat \"$0\")\" \"$1\" > \"$2\"; echo run >> \"$3\"".into(),
+        "{input}".into(),
+        "{bitrate}".into(),
+        "{output}".into(),
+        runs.to_string_lossy().into_owned(),
+      ],
+      ..TranscodeProfile::video("1M")
+    };
+    let transcoder = MediaTranscoder {
+      program: "sh".into(),
+      video: Some(profile),
+      audio: None,
+      cache_dir: dir.path().join("cache"),
+    };
+    let entry = |relative_path: &str| AssetEntry {
+      const_name: "ASSET".into(),
+      literal_path: String::new(),
+      collection_id: "alpha".into(),
+      relative_path: relative_path.into(),
+      output_path: None,
+      source_file: None,
+      size: None,
+      modified: None,
+    };
+
+    for _ in 0..2 {
+      let outcome = transcoder.process(
+        &source,
+        &mirror.join("master.mov"),
+        &entry("intro/master.mov"),
+      )?;
+      assert_eq!(outcome, AssetOutcome::Renamed("master.mp4".into()));
+    }
+    assert_eq!(fs::read_to_string(mirror.join("master.mp4"))?, "master@1M");
+    assert_eq!(fs::read_to_string(&runs)?.lines().count(), 1);
+
+    assert_eq!(
+      transcoder.process(&source, &mirror.join("talk.mp3"), &entry("intro/talk.mp3"))?,
+      AssetOutcome::Unchanged
+    );
+    Ok(())
+  }
+}
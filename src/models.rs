@@ -1,28 +1,117 @@
 //! Data structures produced while preparing an offline bundle.
 
 use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs::Metadata;
 use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
 
 use serde::{Deserialize, Serialize};
 
+use crate::asset_paths::AssetReferenceFilter;
+
 /// Metadata describing an authored collection parsed from the metadata file.
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CollectionMetaRecord {
   /// Collection title taken from the frontmatter metadata file.
-  pub title: String,
+  pub title: LocalizedText,
   /// Optional collection description rendered alongside the title.
-  pub description: Option<String>,
+  pub description: Option<LocalizedText>,
   /// Optional semantic version string attached to the collection.
   pub version: Option<String>,
   /// Optional asset slug used to construct asset paths.
   pub asset_slug: Option<String>,
   /// Optional hero asset path to display in listings.
   pub hero_image: Option<String>,
+  /// Optional position of the collection in the catalog, ahead of directory-name ordering.
+  #[serde(default)]
+  pub order: Option<usize>,
+}
+
+impl CollectionMetaRecord {
+  /// Resolve the title and description to `language`, see [`LocalizedText::localize`].
+  pub fn localize(&mut self, language: &str) {
+    self.title.localize(language);
+    if let Some(description) = &mut self.description {
+      description.localize(language);
+    }
+  }
+}
+
+/// Metadata text given either as one string or per language, e.g.
+/// `{"en": "Safety", "fr": "Sécurité"}`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum LocalizedText {
+  /// The same text for every language.
+  Plain(String),
+  /// Text keyed by language tag.
+  Localized(BTreeMap<String, String>),
+}
+
+impl LocalizedText {
+  /// Plain text, or the translation of the alphabetically first language.
+  pub fn as_str(&self) -> &str {
+    match self {
+      Self::Plain(text) => text,
+      Self::Localized(texts) => texts.values().next().map_or("", String::as_str),
+    }
+  }
+
+  /// Text for `language`, falling back to its primary subtag (`fr` for `fr-CA`) and then to
+  /// [`as_str`](Self::as_str).
+  pub fn get(&self, language: &str) -> &str {
+    let Self::Localized(texts) = self else {
+      return self.as_str();
+    };
+    let primary = language.split(['-', '_']).next().unwrap_or(language);
+    texts
+      .get(language)
+      .or_else(|| texts.get(primary))
+      .map_or_else(|| self.as_str(), String::as_str)
+  }
+
+  /// Replace the translations with the [`Plain`](Self::Plain) text for `language`.
+  pub fn localize(&mut self, language: &str) {
+    if matches!(self, Self::Localized(_)) {
+      *self = Self::Plain(self.get(language).to_string());
+    }
+  }
+}
+
+impl Default for LocalizedText {
+  fn default() -> Self {
+    Self::Plain(String::new())
+  }
+}
+
+impl From<&str> for LocalizedText {
+  fn from(text: &str) -> Self {
+    Self::Plain(text.to_string())
+  }
+}
+
+impl From<String> for LocalizedText {
+  fn from(text: String) -> Self {
+    Self::Plain(text)
+  }
+}
+
+impl PartialEq<&str> for LocalizedText {
+  fn eq(&self, other: &&str) -> bool {
+    matches!(self, Self::Plain(text) if text == other)
+  }
+}
+
+impl fmt::Display for LocalizedText {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
 }
 
 /// Optional frontmatter fields attached to entry markdown files.
-#[derive(Debug, Default, Clone, Deserialize)]
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
 pub struct EntryFrontmatterRecord {
   /// Entry title rendered in the offline experience.
   pub title: Option<String>,
@@ -30,10 +119,13 @@ pub struct EntryFrontmatterRecord {
   pub section: Option<String>,
   /// Explicit ordering override supplied in authored content.
   pub order: Option<usize>,
+  /// Asset references loaded first in addition to those in the entry's lead.
+  #[serde(default)]
+  pub priority_assets: Vec<String>,
 }
 
 /// Structured representation of a collection and its discovered entries.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct CollectionCatalogRecord {
   /// Stable identifier for the collection.
   pub id: String,
@@ -41,10 +133,65 @@ pub struct CollectionCatalogRecord {
   pub meta: CollectionMetaRecord,
   /// Entries discovered for the collection.
   pub entries: Vec<EntryRecord>,
+  /// Bundle path of the hero image in `make_offline_asset_path` form.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub hero_asset: Option<String>,
+  /// Bundle paths of hero variants next to the hero image, keyed by the suffix after its file
+  /// stem, e.g. `thumb` for `cover-thumb.webp` or `2x` for `cover@2x.png`.
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub hero_variants: BTreeMap<String, String>,
+  /// Landing content from a `README.md` or `intro.md` in the collection's root directory.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub intro: Option<CollectionIntroRecord>,
+}
+
+/// Collection listed by [`discover_collections`](crate::manifest::discover_collections).
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CollectionSummaryRecord {
+  /// Stable identifier for the collection.
+  pub id: String,
+  /// Metadata describing the collection.
+  pub meta: CollectionMetaRecord,
+  /// Number of entry directories in the collection, including grouped entries.
+  pub entry_count: usize,
+}
+
+/// Collection landing content written outside of any entry.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CollectionIntroRecord {
+  /// Path to the markdown source, relative to the collections directory.
+  pub source: String,
+  /// Title from the front matter or the first heading.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub title: Option<String>,
+  /// Markdown body without front matter.
+  pub body: String,
+  /// Bundle paths of the assets referenced by the body.
+  #[serde(default)]
+  pub asset_paths: Vec<String>,
+}
+
+/// Version of the `collection_catalog_json` format written as [`CollectionCatalog`].
+///
+/// Version 1 was a bare array of [`CollectionCatalogRecord`] values without hero bundle paths;
+/// version 2 had no [`LocalizedText::Localized`] titles and descriptions.
+pub const COLLECTION_CATALOG_SCHEMA_VERSION: u32 = 3;
+
+/// Versioned collection catalog document, see `runtime::parse_collection_catalog`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CollectionCatalog {
+  /// Format version, see [`COLLECTION_CATALOG_SCHEMA_VERSION`].
+  pub schema_version: u32,
+  /// Language the titles and descriptions were resolved to, if any; otherwise localized text
+  /// keeps every translation.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub language: Option<String>,
+  /// Collections in catalog order.
+  pub collections: Vec<CollectionCatalogRecord>,
 }
 
 /// Rendered entry metadata for catalog presentation.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct EntryRecord {
   /// Stable identifier for the entry.
   pub id: String,
@@ -56,10 +203,19 @@ pub struct EntryRecord {
   pub sequence: usize,
   /// Path to the markdown source file that produced the entry body.
   pub source: String,
+  /// Number of words in the entry's text, excluding markup.
+  #[serde(default)]
+  pub word_count: usize,
+  /// Estimated reading time in whole minutes, see `manifest::reading_minutes`.
+  #[serde(default)]
+  pub reading_minutes: usize,
+  /// Plain-text summary of the first paragraph, see `manifest::extract_excerpt`.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub excerpt: Option<String>,
 }
 
 /// Representation of a collection asset required by the offline bundle.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[allow(dead_code)]
 pub struct AssetEntry {
   /// Constant name generated for the asset entry.
@@ -70,24 +226,56 @@ pub struct AssetEntry {
   pub collection_id: String,
   /// Relative path of the asset within the collection directory.
   pub relative_path: String,
+  /// Relative path of the mirrored output when an asset processor renamed the asset.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub output_path: Option<String>,
+  /// Source file outside the collections directory, e.g. a download cached by the vendor stage.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub source_file: Option<PathBuf>,
+  /// Byte size of the source file when it was collected.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub size: Option<u64>,
+  /// Modification time of the source file when it was collected, in seconds since the Unix
+  /// epoch.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub modified: Option<u64>,
 }
 
 impl AssetEntry {
+  /// Relative path of the bundled asset within its collection, after any renaming.
+  pub fn output_relative_path(&self) -> &str {
+    self.output_path.as_deref().unwrap_or(&self.relative_path)
+  }
+
   /// Relative path within the asset mirror for this entry.
   pub fn mirror_relative_path(&self) -> PathBuf {
-    PathBuf::from(&self.collection_id).join(&self.relative_path)
+    PathBuf::from(&self.collection_id).join(self.output_relative_path())
+  }
+
+  /// Record the size and modification time of the source file from `metadata`.
+  pub fn record_metadata(&mut self, metadata: &Metadata) {
+    self.size = Some(metadata.len());
+    self.modified = metadata
+      .modified()
+      .ok()
+      .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+      .map(|elapsed| elapsed.as_secs());
   }
 
-  /// Source path of the asset relative to the authored collections directory.
+  /// Source path of the asset, relative to the authored collections directory unless
+  /// [`source_file`](Self::source_file) is set.
   pub fn source_path(&self, collections_dir: &Path) -> PathBuf {
-    collections_dir
-      .join(&self.collection_id)
-      .join(&self.relative_path)
+    match &self.source_file {
+      Some(source_file) => source_file.clone(),
+      None => collections_dir
+        .join(&self.collection_id)
+        .join(&self.relative_path),
+    }
   }
 }
 
 /// Fully rendered offline entry representation.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct OfflineEntryRecord {
   /// Collection identifier the entry belongs to.
   pub collection_id: String,
@@ -97,6 +285,118 @@ pub struct OfflineEntryRecord {
   pub body: String,
   /// Relative asset paths referenced by the entry.
   pub asset_paths: Vec<String>,
+  /// Number of words in the entry's text, excluding markup.
+  #[serde(default)]
+  pub word_count: usize,
+  /// Estimated reading time in whole minutes.
+  #[serde(default)]
+  pub reading_minutes: usize,
+  /// Heading anchors mapped to the heading text, see `manifest::collect_heading_anchors`.
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub headings: BTreeMap<String, String>,
+  /// Images in the entry body with their alternative text, in document order.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub images: Vec<ImageAltRecord>,
+  /// Supplementary markdown files next to the entry markdown, sorted by name.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub documents: Vec<EntryDocumentRecord>,
+  /// Bundle paths of the assets to load first, see [`OfflineEntrySummary::priority_assets`].
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub priority_assets: Vec<String>,
+  /// Generated poster frames, see [`OfflineEntrySummary::video_posters`].
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub video_posters: BTreeMap<String, String>,
+}
+
+/// Additional markdown document of an entry, such as `notes.md` or `quiz.md`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EntryDocumentRecord {
+  /// File stem of the document, e.g. `notes`.
+  pub name: String,
+  /// Title from the front matter or the first heading.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub title: Option<String>,
+  /// Markdown body without front matter.
+  pub body: String,
+  /// Bundle paths of the assets referenced by the body.
+  #[serde(default)]
+  pub asset_paths: Vec<String>,
+}
+
+/// Image referenced by an entry body, recorded for the accessibility report.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ImageAltRecord {
+  /// Image source as written in the markdown or `<img>` tag.
+  pub src: String,
+  /// Alternative text; empty when the author provided none.
+  pub alt: String,
+}
+
+impl ImageAltRecord {
+  /// Whether the image has non-blank alternative text.
+  pub fn has_alt(&self) -> bool {
+    !self.alt.trim().is_empty()
+  }
+}
+
+/// Version of the `offline_manifest.json` format written by [`OfflineManifestSummary`].
+///
+/// Version 2 added entry titles and sections and `asset_sizes`, version 3 entry `headings`,
+/// version 4 `provenance`, version 5 entry `priority_assets` and version 6 entry
+/// `video_posters`; manifests without a `schema_version` field are version 1.
+pub const OFFLINE_MANIFEST_SCHEMA_VERSION: u32 = 6;
+
+/// File written to the bundle root with the [`BuildProvenance`] of the bundle.
+pub const VERSION_FILE: &str = "version.json";
+
+/// Where a bundle came from, recorded in the offline manifest and in [`VERSION_FILE`].
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct BuildProvenance {
+  /// Version of offline_dx_bundler that produced the bundle.
+  pub tool_version: String,
+  /// `HEAD` commit of the repository holding the project, when it is a git checkout.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub git_commit: Option<String>,
+  /// SHA-256 over the bundled entry bodies and asset digests; equal trees hash equally.
+  pub content_hash: String,
+  /// Build time in seconds since the Unix epoch, taken from `SOURCE_DATE_EPOCH` when set.
+  pub built_at: u64,
+}
+
+impl BuildProvenance {
+  /// Parse the contents of a [`VERSION_FILE`], e.g. one fetched by the running app.
+  pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+    serde_json::from_str(json)
+  }
+}
+
+/// File written to the bundle root with the [`UpdateMetadata`] of the bundle.
+pub const UPDATE_FILE: &str = "update.json";
+
+/// What a launcher needs to decide whether a bundle updates the installed one, stored in
+/// [`UPDATE_FILE`].
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct UpdateMetadata {
+  /// Identifier shared by every version of the bundle.
+  pub bundle_id: String,
+  /// Version of the bundled content, compared leniently as semver.
+  pub version: String,
+  /// [`BuildProvenance::content_hash`] of the bundle.
+  pub content_hash: String,
+  /// Oldest app version able to open the bundle.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub min_app_version: Option<String>,
+}
+
+impl UpdateMetadata {
+  /// Parse the contents of an [`UPDATE_FILE`].
+  pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+    serde_json::from_str(json)
+  }
+}
+
+pub(crate) fn legacy_manifest_schema_version() -> u32 {
+  1
 }
 
 /// Serializable summary of an offline entry.
@@ -106,19 +406,55 @@ pub struct OfflineEntrySummary {
   pub collection_id: String,
   /// Entry identifier.
   pub entry_id: String,
+  /// Entry title from the catalog.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub title: Option<String>,
+  /// Section the entry is grouped under in the catalog.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub section: Option<String>,
   /// Relative asset paths referenced by the entry.
   pub asset_paths: Vec<String>,
+  /// Heading anchors (`#fragment` without the `#`) mapped to the heading text.
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub headings: BTreeMap<String, String>,
+  /// Bundle paths of the assets to load first: those referenced before the entry's first
+  /// heading and those listed in the `priority_assets` front matter.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub priority_assets: Vec<String>,
+  /// Bundle paths of videos without an authored `poster` mapped to the bundle path of the frame
+  /// generated as their poster.
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub video_posters: BTreeMap<String, String>,
 }
 
 /// Serializable summary of the offline manifest written to disk.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct OfflineManifestSummary {
+  /// Format version, see [`OFFLINE_MANIFEST_SCHEMA_VERSION`].
+  #[serde(default = "legacy_manifest_schema_version")]
+  pub schema_version: u32,
   /// Relative path to the offline site root inside the bundle output.
   pub site_root: String,
   /// Summary of entries included in the manifest.
   pub entries: Vec<OfflineEntrySummary>,
   /// Collected hero asset paths required by the offline experience.
   pub hero_assets: Vec<String>,
+  /// SHA-256 digest of every bundled collection asset keyed by its bundle path.
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub asset_hashes: BTreeMap<String, String>,
+  /// Bundle paths of byte-identical duplicates mapped to their canonical bundle path.
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub asset_aliases: BTreeMap<String, String>,
+  /// Byte size of every bundled collection asset keyed by its bundle path.
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub asset_sizes: BTreeMap<String, u64>,
+  /// Source modification time of bundled collection assets in seconds since the Unix epoch,
+  /// keyed by bundle path.
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub asset_modified: BTreeMap<String, u64>,
+  /// Tool version, commit, content hash and time of the build.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub provenance: Option<BuildProvenance>,
 }
 
 /// Context for asset collection operations.
@@ -134,6 +470,15 @@ pub struct AssetCollectionContext<'a> {
   pub hero_match_arms: &'a mut Vec<String>,
 }
 
+/// Collection that matched the selection but was excluded while generating the manifest.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ExcludedCollectionRecord {
+  /// Identifier of the excluded collection.
+  pub collection_id: String,
+  /// Human readable reason for the exclusion.
+  pub reason: String,
+}
+
 /// Context for manifest generation operations.
 #[derive(Debug)]
 pub struct ManifestGenerationContext<'a> {
@@ -143,6 +488,10 @@ pub struct ManifestGenerationContext<'a> {
   pub collection_catalog: &'a mut Vec<CollectionCatalogRecord>,
   /// Complete representation of entries required for the offline bundle.
   pub offline_entries: &'a mut Vec<OfflineEntryRecord>,
+  /// Collections excluded because they failed selection constraints such as versions.
+  pub excluded_collections: &'a mut Vec<ExcludedCollectionRecord>,
+  /// Rules for references in entry bodies that are not bundled assets.
+  pub reference_filter: &'a AssetReferenceFilter,
 }
 
 /// Configuration for asset scanning operations.
@@ -160,19 +509,86 @@ pub struct AssetScanningConfig<'a> {
   pub collection_asset_literal_prefix: &'a str,
   /// Name of collection metadata file.
   pub collection_metadata_file: &'a str,
+  /// Maximum directory depth below the scan root.
+  pub max_depth: usize,
+  /// Extensions allowed in assets trees; empty allows every extension.
+  pub asset_extensions: &'a [String],
+  /// Extensions never collected from assets trees.
+  pub excluded_extensions: &'a [String],
+}
+
+impl AssetScanningConfig<'_> {
+  /// Reason an asset file named `file_name` is kept out of the bundle by the extension lists,
+  /// or `None` when it may be bundled.
+  pub fn extension_rejection(&self, file_name: &str) -> Option<String> {
+    let extension = Path::new(file_name)
+      .extension()
+      .map(|extension| extension.to_string_lossy().to_ascii_lowercase())
+      .unwrap_or_default();
+    if self.excluded_extensions.contains(&extension) {
+      Some(format!("`.{extension}` files are excluded"))
+    } else if !self.asset_extensions.is_empty() && !self.asset_extensions.contains(&extension) {
+      Some(if extension.is_empty() {
+        "files without an extension are not in the allowed asset extensions".to_string()
+      } else {
+        format!("`.{extension}` is not in the allowed asset extensions")
+      })
+    } else {
+      None
+    }
+  }
 }
 
 /// Complete manifest generation output returned by [`crate::OfflineBuilder`].
-#[derive(Debug)]
+///
+/// Serialises to the intermediate JSON written by `OfflineBuilder::export_intermediate_json`;
+/// `asset_map` is stored as a list of [`AssetEntry`] values since JSON has no tuple keys.
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ManifestGenerationResult {
   /// Records describing the discovered collections and entries.
   pub collection_catalog: Vec<CollectionCatalogRecord>,
   /// Complete representation of entries required for the offline bundle.
   pub offline_entries: Vec<OfflineEntryRecord>,
   /// Mapping of collection and relative path to offline asset entries.
+  #[serde(with = "asset_list")]
   pub asset_map: BTreeMap<(String, String), AssetEntry>,
   /// Hero assets collected while scanning collection metadata.
   pub hero_asset_paths: BTreeSet<String>,
   /// Match arms used to generate hero asset lookup code.
   pub hero_match_arms: Vec<String>,
+  /// Collections excluded because they failed selection constraints such as versions.
+  pub excluded_collections: Vec<ExcludedCollectionRecord>,
+}
+
+/// (De)serialise an asset map keyed by `(collection_id, relative_path)` as a list of entries.
+mod asset_list {
+  use std::collections::BTreeMap;
+
+  use serde::{Deserialize, Deserializer, Serializer};
+
+  use super::AssetEntry;
+
+  pub(super) fn serialize<S: Serializer>(
+    map: &BTreeMap<(String, String), AssetEntry>,
+    serializer: S,
+  ) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(map.values())
+  }
+
+  pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+  ) -> Result<BTreeMap<(String, String), AssetEntry>, D::Error> {
+    let entries = Vec::<AssetEntry>::deserialize(deserializer)?;
+    Ok(
+      entries
+        .into_iter()
+        .map(|entry| {
+          (
+            (entry.collection_id.clone(), entry.relative_path.clone()),
+            entry,
+          )
+        })
+        .collect(),
+    )
+  }
 }
@@ -0,0 +1,870 @@
+//! Explicit build pipeline made of named stages sharing one [`BuildState`].
+//!
+//! [`OfflineBuilder::build`] runs [`Pipeline::standard`], i.e. the [`PRE_BUILD`] hooks and the
+//! [`SCAN`], [`MIRROR`] and [`CODEGEN`] stages. Pipelines can be reordered, stages skipped or replaced, and custom
+//! stages inserted anywhere, including the optional [`PatchSiteStage`] and [`LauncherStage`]
+//! that prepare the `dx build` output, the [`AccessibilityAuditStage`] checking the generated
+//! pages, the opt-in [`VendorStage`] that bundles allowlisted remote assets, the
+//! [`InlineImagesStage`] that turns small images into `data:` URIs, the [`PosterStage`] that
+//! generates missing video posters, or the [`RemoteSourceStage`] that syncs collections from a
+//! git repository or S3 before scanning.
+
+use std::fs;
+use std::path::PathBuf;
+
+use crate::builder::{BuildResult, OfflineArtifacts, OfflineBuilder, ProfileArtifacts};
+use crate::bundle::accessibility::audit_html_accessibility;
+use crate::bundle::entry_points::write_entry_points;
+use crate::bundle::history_fallback::write_history_fallback;
+use crate::bundle::js_patch::{find_binary_name, patch_site_module};
+use crate::bundle::launcher::{write_root_launcher, write_themed_launcher_scripts};
+use crate::bundle::site::{PatchedSiteIndex, SitePatchOptions, patch_site_index_with};
+use crate::bundle::stable_names::stabilize_asset_names;
+use crate::hooks::run_hook_commands;
+pub use crate::hooks::{POST_BUNDLE, PRE_BUILD};
+use crate::manifest::inline_images::{InlinedImage, inline_small_images};
+use crate::manifest::inventory::AssetInventory;
+use crate::manifest::posters::{GeneratedPoster, PosterOptions, generate_video_posters};
+use crate::manifest::remote::{RemoteSource, SyncedContent, sync_remote_content};
+use crate::manifest::vendor::{VendorOptions, VendoredAsset, vendor_remote_assets};
+use crate::models::{ExcludedCollectionRecord, ManifestGenerationResult, VERSION_FILE};
+use crate::project::OfflineProjectLayout;
+use crate::report::{BuildStats, collect_stats, record_wasm_embedding, timed};
+use crate::selection::CollectionInclusion;
+use crate::theme::ThemePalette;
+
+/// Name of the stage generating the manifest, see [`ScanStage`].
+pub const SCAN: &str = "scan";
+/// Name of the stage mirroring collection assets, see [`MirrorStage`].
+pub const MIRROR: &str = "mirror";
+/// Name of the stage rendering the generated artifacts, see [`CodegenStage`].
+pub const CODEGEN: &str = "codegen";
+/// Conventional name for [`PatchSiteStage`].
+pub const PATCH: &str = "patch";
+/// Conventional name for [`LauncherStage`].
+pub const LAUNCHER: &str = "launcher";
+/// Conventional name for [`VendorStage`].
+pub const VENDOR: &str = "vendor";
+/// Conventional name for [`InlineImagesStage`].
+pub const INLINE_IMAGES: &str = "inline-images";
+/// Conventional name for [`PosterStage`].
+pub const POSTERS: &str = "posters";
+/// Conventional name for [`RemoteSourceStage`].
+pub const FETCH: &str = "fetch";
+/// Conventional name for [`AccessibilityAuditStage`].
+pub const ACCESSIBILITY: &str = "accessibility";
+
+/// Duplicate asset keys mapped to the key of their canonical, byte-identical copy.
+pub type DuplicateAssets = std::collections::BTreeMap<(String, String), (String, String)>;
+
+/// State shared by every stage of a [`Pipeline`] run.
+pub struct BuildState<'s> {
+  /// Collections to include in the build.
+  pub selection: &'s dyn CollectionInclusion,
+  /// Manifest produced by the [`SCAN`] stage.
+  pub manifest: Option<ManifestGenerationResult>,
+  /// Byte-identical assets found by the [`MIRROR`] stage when
+  /// [`OfflineBuilder::with_asset_deduplication`] is enabled.
+  pub duplicates: DuplicateAssets,
+  /// Digests of the assets mirrored by the [`MIRROR`] stage, reused by the [`CODEGEN`] stage.
+  pub inventory: Option<AssetInventory>,
+  /// Artifacts rendered by the [`CODEGEN`] stage.
+  pub artifacts: Option<OfflineArtifacts>,
+  /// Site index patched by a [`PatchSiteStage`].
+  pub patched_site: Option<PatchedSiteIndex>,
+  /// Files written by stages such as [`LauncherStage`].
+  pub written_files: Vec<PathBuf>,
+  /// Remote assets bundled by a [`VendorStage`].
+  pub vendored_assets: Vec<VendoredAsset>,
+  /// Images inlined into entry bodies by an [`InlineImagesStage`].
+  pub inlined_images: Vec<InlinedImage>,
+  /// Poster frames registered by a [`PosterStage`].
+  pub generated_posters: Vec<GeneratedPoster>,
+  /// Remote content synced by a [`RemoteSourceStage`].
+  pub synced_content: Option<SyncedContent>,
+  /// Profile built by a [`Pipeline::run_profiles`] run, `None` for a plain [`Pipeline::run`].
+  pub profile: Option<BuildProfile>,
+  /// Time spent in each stage, filled in once [`Pipeline::run`] finishes.
+  pub stats: BuildStats,
+}
+
+impl<'s> BuildState<'s> {
+  /// Empty state for a run over `selection`.
+  pub fn new(selection: &'s dyn CollectionInclusion) -> Self {
+    Self {
+      selection,
+      manifest: None,
+      duplicates: DuplicateAssets::new(),
+      inventory: None,
+      artifacts: None,
+      patched_site: None,
+      written_files: Vec::new(),
+      vendored_assets: Vec::new(),
+      inlined_images: Vec::new(),
+      generated_posters: Vec::new(),
+      synced_content: None,
+      profile: None,
+      stats: BuildStats::default(),
+    }
+  }
+
+  /// State for `profile`, starting from this shared state with the manifest narrowed to
+  /// `selection`.
+  fn for_profile<'p>(
+    &self,
+    layout: &OfflineProjectLayout,
+    selection: &'p dyn CollectionInclusion,
+    profile: BuildProfile,
+  ) -> BuildState<'p> {
+    let manifest = self.manifest.clone().map(|mut manifest| {
+      let mut excluded = Vec::new();
+      manifest.retain_collections(layout, |record| {
+        if !selection.is_included(&record.id) {
+          return false;
+        }
+        match selection.version_rejection(&record.id, record.meta.version.as_deref()) {
+          Some(reason) => {
+            excluded.push(ExcludedCollectionRecord {
+              collection_id: record.id.clone(),
+              reason,
+            });
+            false
+          }
+          None => true,
+        }
+      });
+      manifest.excluded_collections.extend(excluded);
+      manifest
+    });
+    BuildState {
+      selection,
+      manifest,
+      duplicates: self.duplicates.clone(),
+      inventory: self.inventory.clone(),
+      artifacts: None,
+      patched_site: self.patched_site.clone(),
+      written_files: self.written_files.clone(),
+      vendored_assets: self.vendored_assets.clone(),
+      inlined_images: self.inlined_images.clone(),
+      generated_posters: self.generated_posters.clone(),
+      synced_content: self.synced_content.clone(),
+      profile: Some(profile),
+      stats: self.stats.clone(),
+    }
+  }
+
+  /// Manifest produced by an earlier stage, or an error naming the stage that needs it.
+  pub fn require_manifest(&mut self, stage: &str) -> BuildResult<&mut ManifestGenerationResult> {
+    self
+      .manifest
+      .as_mut()
+      .ok_or_else(|| format!("pipeline stage `{stage}` requires the `{SCAN}` stage").into())
+  }
+}
+
+/// Named profile built by [`Pipeline::run_profiles`].
+#[derive(Debug, Clone)]
+pub struct BuildProfile {
+  /// Profile name, also used as the bundle subdirectory.
+  pub name: String,
+  /// Layout with `offline_bundle_root` (and `output_root`, if set) pointing at the profile's
+  /// bundle directory; the [`CODEGEN`] stage renders with it.
+  pub layout: OfflineProjectLayout,
+  /// Subdirectory of [`OfflineBuilder::artifact_dir`] receiving the profile's artifacts.
+  pub artifact_dir: Option<PathBuf>,
+}
+
+impl BuildProfile {
+  fn new(builder: &OfflineBuilder<'_>, name: &str) -> Self {
+    let mut layout = builder.context().layout.clone();
+    layout.offline_bundle_root = format!(
+      "{}/{}",
+      layout.offline_bundle_root.trim_end_matches('/'),
+      name
+    );
+    layout.output_root = layout.output_root.map(|root| root.join(name));
+    Self {
+      name: name.to_string(),
+      layout,
+      artifact_dir: builder.artifact_dir().map(|dir| dir.join(name)),
+    }
+  }
+}
+
+/// Selection including every collection that at least one profile includes.
+struct ProfileUnion<'p, 's, S> {
+  profiles: &'p [(&'s str, S)],
+}
+
+impl<S: CollectionInclusion> CollectionInclusion for ProfileUnion<'_, '_, S> {
+  fn is_included(&self, collection_id: &str) -> bool {
+    self
+      .profiles
+      .iter()
+      .any(|(_, selection)| selection.is_included(collection_id))
+  }
+
+  fn version_rejection(&self, collection_id: &str, version: Option<&str>) -> Option<String> {
+    let mut reasons = Vec::new();
+    for (_, selection) in self.profiles {
+      if !selection.is_included(collection_id) {
+        continue;
+      }
+      reasons.push(selection.version_rejection(collection_id, version)?);
+    }
+    reasons.into_iter().next()
+  }
+}
+
+/// One step of a [`Pipeline`].
+///
+/// Closures taking the builder and the state implement the trait.
+pub trait PipelineStage {
+  /// Run the stage, reading and updating the shared state.
+  fn run(&self, builder: &OfflineBuilder<'_>, state: &mut BuildState<'_>) -> BuildResult<()>;
+}
+
+impl<F> PipelineStage for F
+where
+  F: Fn(&OfflineBuilder<'_>, &mut BuildState<'_>) -> BuildResult<()>,
+{
+  fn run(&self, builder: &OfflineBuilder<'_>, state: &mut BuildState<'_>) -> BuildResult<()> {
+    self(builder, state)
+  }
+}
+
+/// Ordered list of named stages.
+#[derive(Default)]
+pub struct Pipeline {
+  stages: Vec<(String, Box<dyn PipelineStage>)>,
+}
+
+impl Pipeline {
+  /// Pipeline without any stages.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// The stages run by [`OfflineBuilder::build`]: [`PRE_BUILD`], [`SCAN`], [`MIRROR`] and
+  /// [`CODEGEN`].
+  pub fn standard() -> Self {
+    Self::new()
+      .stage(PRE_BUILD, HookStage::PreBuild)
+      .stage(SCAN, ScanStage)
+      .stage(MIRROR, MirrorStage)
+      .stage(CODEGEN, CodegenStage)
+  }
+
+  /// Append a stage.
+  pub fn stage(mut self, name: impl Into<String>, stage: impl PipelineStage + 'static) -> Self {
+    self.stages.push((name.into(), Box::new(stage)));
+    self
+  }
+
+  /// Names of the stages in execution order.
+  pub fn names(&self) -> Vec<&str> {
+    self.stages.iter().map(|(name, _)| name.as_str()).collect()
+  }
+
+  /// Insert a stage directly before the stage named `anchor`.
+  pub fn insert_before(
+    &mut self,
+    anchor: &str,
+    name: impl Into<String>,
+    stage: impl PipelineStage + 'static,
+  ) -> BuildResult<()> {
+    let index = self.position(anchor)?;
+    self.stages.insert(index, (name.into(), Box::new(stage)));
+    Ok(())
+  }
+
+  /// Insert a stage directly after the stage named `anchor`.
+  pub fn insert_after(
+    &mut self,
+    anchor: &str,
+    name: impl Into<String>,
+    stage: impl PipelineStage + 'static,
+  ) -> BuildResult<()> {
+    let index = self.position(anchor)?;
+    self
+      .stages
+      .insert(index + 1, (name.into(), Box::new(stage)));
+    Ok(())
+  }
+
+  /// Replace the implementation of the stage named `name`, keeping its position.
+  pub fn replace(&mut self, name: &str, stage: impl PipelineStage + 'static) -> BuildResult<()> {
+    let index = self.position(name)?;
+    self.stages[index].1 = Box::new(stage);
+    Ok(())
+  }
+
+  /// Remove the stage named `name`. Returns false when no such stage exists.
+  pub fn skip(&mut self, name: &str) -> bool {
+    let before = self.stages.len();
+    self.stages.retain(|(stage, _)| stage != name);
+    self.stages.len() != before
+  }
+
+  /// Move the stage named `name` directly after the stage named `anchor`.
+  pub fn move_after(&mut self, name: &str, anchor: &str) -> BuildResult<()> {
+    let index = self.position(name)?;
+    let stage = self.stages.remove(index);
+    match self.position(anchor) {
+      Ok(anchor) => {
+        self.stages.insert(anchor + 1, stage);
+        Ok(())
+      }
+      Err(err) => {
+        self.stages.insert(index, stage);
+        Err(err)
+      }
+    }
+  }
+
+  /// Run every stage in order and return the final state.
+  ///
+  /// The first failing stage aborts the run; its error is returned unchanged so typed errors
+  /// such as [`MirrorConsistencyError`](crate::MirrorConsistencyError) can be downcast.
+  pub fn run<'s>(
+    &self,
+    builder: &OfflineBuilder<'_>,
+    selection: &'s dyn CollectionInclusion,
+  ) -> BuildResult<BuildState<'s>> {
+    let mut state = BuildState::new(selection);
+    run_stages(&self.stages, builder, &mut state)?;
+    Ok(state)
+  }
+
+  /// Run the pipeline once per named profile, scanning and mirroring collections only once.
+  ///
+  /// The stages up to and including [`MIRROR`] (or [`SCAN`] when the mirror stage was skipped)
+  /// run once over the union of all profile selections, so the pre-build hooks run once and
+  /// every asset is mirrored once. Each profile then runs the remaining stages on its own
+  /// [`BuildState`], starting from the shared state with the manifest narrowed to the profile's
+  /// selection and [`BuildState::profile`] set. Every profile gets a layout whose
+  /// `offline_bundle_root` is `<offline_bundle_root>/<profile>` (and `output_root`, if set,
+  /// `<output_root>/<profile>`), and its offline manifest JSON and `version.json` are written
+  /// there so the bundle steps can run per profile.
+  pub fn run_profiles<S: CollectionInclusion>(
+    &self,
+    builder: &OfflineBuilder<'_>,
+    profiles: &[(&str, S)],
+  ) -> BuildResult<Vec<ProfileArtifacts>> {
+    for (index, (name, _)) in profiles.iter().enumerate() {
+      if name.is_empty() || name.contains(['/', '\\']) || *name == "." || *name == ".." {
+        return Err(format!("invalid profile name `{name}`").into());
+      }
+      if profiles[..index].iter().any(|(other, _)| other == name) {
+        return Err(format!("duplicate profile name `{name}`").into());
+      }
+    }
+
+    let split = self.position(MIRROR).or_else(|_| self.position(SCAN))? + 1;
+    let (shared_stages, profile_stages) = self.stages.split_at(split);
+    let union = ProfileUnion { profiles };
+    let mut shared = BuildState::new(&union);
+    run_stages(shared_stages, builder, &mut shared)?;
+
+    let context = builder.context();
+    let mut outputs = Vec::with_capacity(profiles.len());
+    for (name, selection) in profiles {
+      let profile = BuildProfile::new(builder, name);
+      let mut state = shared.for_profile(&context.layout, selection, profile);
+      run_stages(profile_stages, builder, &mut state)?;
+      let (Some(profile), Some(artifacts)) = (state.profile, state.artifacts) else {
+        return Err(
+          format!("build pipeline did not produce artifacts for profile `{name}`").into(),
+        );
+      };
+
+      let bundle_root = profile.layout.bundle_root(context.manifest_dir);
+      fs::create_dir_all(&bundle_root)?;
+      artifacts
+        .offline_manifest_json
+        .save(&bundle_root.join(&profile.layout.offline_manifest_json))?;
+      artifacts
+        .version_json
+        .save(&bundle_root.join(VERSION_FILE))?;
+
+      outputs.push(ProfileArtifacts {
+        name: profile.name,
+        layout: profile.layout,
+        artifacts,
+      });
+    }
+
+    Ok(outputs)
+  }
+
+  fn position(&self, name: &str) -> BuildResult<usize> {
+    self
+      .stages
+      .iter()
+      .position(|(stage, _)| stage == name)
+      .ok_or_else(|| format!("unknown pipeline stage `{name}`").into())
+  }
+}
+
+/// Run `stages` in order on `state`, adding their timings to [`BuildState::stats`].
+fn run_stages(
+  stages: &[(String, Box<dyn PipelineStage>)],
+  builder: &OfflineBuilder<'_>,
+  state: &mut BuildState<'_>,
+) -> BuildResult<()> {
+  let (result, stats) = collect_stats(|| {
+    stages
+      .iter()
+      .try_for_each(|(name, stage)| timed(name, || stage.run(builder, state)))
+  });
+  result?;
+  state.stats.extend(stats);
+  if let Some(artifacts) = &mut state.artifacts {
+    artifacts.report.stats = state.stats.clone();
+  }
+  Ok(())
+}
+
+/// Run the builder's hook commands, see [`OfflineBuilder::with_hooks`].
+///
+/// [`Pipeline::standard`] starts with the pre-build hooks; append [`HookStage::PostBundle`]
+/// after the stages that write the bundle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookStage {
+  /// Run [`BuildHooks::pre_build`](crate::config::BuildHooks::pre_build).
+  PreBuild,
+  /// Run [`BuildHooks::post_bundle`](crate::config::BuildHooks::post_bundle).
+  PostBundle,
+}
+
+impl PipelineStage for HookStage {
+  fn run(&self, builder: &OfflineBuilder<'_>, _state: &mut BuildState<'_>) -> BuildResult<()> {
+    let hooks = builder.hooks();
+    let (name, commands) = match self {
+      Self::PreBuild => (PRE_BUILD, &hooks.pre_build),
+      Self::PostBundle => (POST_BUNDLE, &hooks.post_bundle),
+    };
+    let context = builder.context();
+    run_hook_commands(
+      name,
+      commands,
+      context.manifest_dir,
+      &context.layout.bundle_root(context.manifest_dir),
+    )
+  }
+}
+
+/// Generate the offline manifest, see [`OfflineBuilder::scan`].
+pub struct ScanStage;
+
+impl PipelineStage for ScanStage {
+  fn run(&self, builder: &OfflineBuilder<'_>, state: &mut BuildState<'_>) -> BuildResult<()> {
+    state.manifest = Some(builder.scan(&state.selection)?);
+    Ok(())
+  }
+}
+
+/// Check the target file system, hash the manifest's assets and mirror them, running asset
+/// processors.
+pub struct MirrorStage;
+
+impl PipelineStage for MirrorStage {
+  fn run(&self, builder: &OfflineBuilder<'_>, state: &mut BuildState<'_>) -> BuildResult<()> {
+    let manifest = state.require_manifest(MIRROR)?;
+    let (duplicates, inventory) = builder.mirror_assets(manifest)?;
+    state.duplicates = duplicates;
+    state.inventory = Some(inventory);
+    Ok(())
+  }
+}
+
+/// Verify the mirror and render [`OfflineArtifacts`] from the manifest, with the profile's layout
+/// during [`Pipeline::run_profiles`].
+pub struct CodegenStage;
+
+impl PipelineStage for CodegenStage {
+  fn run(&self, builder: &OfflineBuilder<'_>, state: &mut BuildState<'_>) -> BuildResult<()> {
+    let manifest = state.require_manifest(CODEGEN)?.clone();
+    let (layout, artifact_dir) = match &state.profile {
+      Some(profile) => (&profile.layout, profile.artifact_dir.as_deref()),
+      None => (&builder.context().layout, builder.artifact_dir()),
+    };
+    state.artifacts = Some(builder.render_artifacts(
+      layout,
+      manifest,
+      &state.duplicates,
+      state.inventory.as_ref(),
+      artifact_dir,
+    )?);
+    Ok(())
+  }
+}
+
+/// Download allowlisted remote references into the manifest as collection assets.
+///
+/// Not part of [`Pipeline::standard`]; insert it between [`SCAN`] and [`MIRROR`]:
+/// `pipeline.insert_after(SCAN, VENDOR, VendorStage { options })`.
+pub struct VendorStage {
+  /// Allowlist, cache and download command, see [`VendorOptions`].
+  pub options: VendorOptions,
+}
+
+impl PipelineStage for VendorStage {
+  fn run(&self, builder: &OfflineBuilder<'_>, state: &mut BuildState<'_>) -> BuildResult<()> {
+    let context = builder.context();
+    let cache_dir = match &self.options.cache_dir {
+      Some(dir) => context.manifest_dir.join(dir),
+      None => context
+        .manifest_dir
+        .join(&context.layout.target_dir)
+        .join("offline-vendor"),
+    };
+    let manifest = state.require_manifest(VENDOR)?;
+    let vendored = vendor_remote_assets(&context.layout, manifest, &self.options, &cache_dir)?;
+    state.vendored_assets.extend(vendored);
+    Ok(())
+  }
+}
+
+/// Extract poster frames for videos in entry bodies that have no `poster`.
+///
+/// Not part of [`Pipeline::standard`]; insert it between [`SCAN`] and [`MIRROR`]:
+/// `pipeline.insert_after(SCAN, POSTERS, PosterStage { options: PosterOptions::default() })`.
+pub struct PosterStage {
+  /// Extraction command and cache, see [`PosterOptions`].
+  pub options: PosterOptions,
+}
+
+impl PipelineStage for PosterStage {
+  fn run(&self, builder: &OfflineBuilder<'_>, state: &mut BuildState<'_>) -> BuildResult<()> {
+    let context = builder.context();
+    let cache_dir = match &self.options.cache_dir {
+      Some(dir) => context.manifest_dir.join(dir),
+      None => context
+        .manifest_dir
+        .join(&context.layout.target_dir)
+        .join("offline-posters"),
+    };
+    let manifest = state.require_manifest(POSTERS)?;
+    let posters = generate_video_posters(
+      &context.layout,
+      &context.collections_dir,
+      manifest,
+      &context.asset_reference_filter,
+      &self.options,
+      &cache_dir,
+    )?;
+    state.generated_posters.extend(posters);
+    Ok(())
+  }
+}
+
+/// Sync collections from a git repository or an S3 prefix into the directory the build scans.
+///
+/// Not part of [`Pipeline::standard`]; insert it before [`SCAN`]:
+/// `pipeline.insert_before(SCAN, FETCH, RemoteSourceStage { source })`. The context's
+/// collections directory must be [`RemoteSource::cache_dir`]; any other directory is rejected so
+/// authored content is never overwritten.
+pub struct RemoteSourceStage {
+  /// Repository or prefix to sync, see [`RemoteSource`].
+  pub source: RemoteSource,
+}
+
+impl PipelineStage for RemoteSourceStage {
+  fn run(&self, builder: &OfflineBuilder<'_>, state: &mut BuildState<'_>) -> BuildResult<()> {
+    let context = builder.context();
+    let cache_dir = self
+      .source
+      .cache_dir(context.manifest_dir, &context.layout)?;
+    if fs::canonicalize(&context.collections_dir)? != fs::canonicalize(&cache_dir)? {
+      return Err(
+        format!(
+          "pipeline stage `{FETCH}` syncs into {}, but the build context scans {}; build the \
+           context with `RemoteSource::cache_dir` as its collections directory",
+          cache_dir.display(),
+          context.collections_dir.display()
+        )
+        .into(),
+      );
+    }
+    state.synced_content = Some(sync_remote_content(&self.source, &cache_dir)?);
+    Ok(())
+  }
+}
+
+/// Inline images of at most `max_bytes` referenced from entry bodies as `data:` URIs.
+///
+/// Not part of [`Pipeline::standard`]; insert it before [`MIRROR`] so dropped images are never
+/// copied, and after a [`VendorStage`] to also inline small vendored images:
+/// `pipeline.insert_before(MIRROR, INLINE_IMAGES, InlineImagesStage { max_bytes: 4096 })`.
+pub struct InlineImagesStage {
+  /// Largest image inlined, in bytes; see [`ProjectConfig::inline_image_limit`](crate::config::ProjectConfig::inline_image_limit).
+  pub max_bytes: u64,
+}
+
+impl PipelineStage for InlineImagesStage {
+  fn run(&self, builder: &OfflineBuilder<'_>, state: &mut BuildState<'_>) -> BuildResult<()> {
+    let context = builder.context();
+    let manifest = state.require_manifest(INLINE_IMAGES)?;
+    let inlined = inline_small_images(
+      &context.layout,
+      &context.collections_dir,
+      manifest,
+      &context.asset_reference_filter,
+      self.max_bytes,
+    )?;
+    state.inlined_images.extend(inlined);
+    Ok(())
+  }
+}
+
+/// Patch the site index and JavaScript bootstrap in `site_root` for offline use and write the
+/// configured entry-point and history fallback pages.
+pub struct PatchSiteStage {
+  /// Site produced by `dx build`.
+  pub site_root: PathBuf,
+  /// Options passed to [`patch_site_index_with`].
+  pub options: SitePatchOptions,
+}
+
+impl PipelineStage for PatchSiteStage {
+  fn run(&self, builder: &OfflineBuilder<'_>, state: &mut BuildState<'_>) -> BuildResult<()> {
+    let layout = &builder.context().layout;
+    let mut patched = timed("index", || {
+      patch_site_index_with(layout, &self.site_root, &self.options)
+    })?;
+    let wasm = timed("js", || {
+      patch_site_module(
+        layout,
+        &self.site_root,
+        &patched,
+        &self.options,
+        find_binary_name,
+      )
+    })?;
+    if let Some(wasm) = wasm {
+      record_wasm_embedding(wasm);
+    }
+    if self.options.stable_asset_names {
+      let renamed = timed("names", || {
+        stabilize_asset_names(layout, &self.site_root, self.options.stable_name_hashes)
+      })?;
+      for name in [&mut patched.js_name, &mut patched.wasm_name] {
+        if let Some(stable) = renamed.get(name.as_str()) {
+          name.clone_from(stable);
+        }
+      }
+    }
+    state.written_files.extend(timed("pages", || {
+      let mut pages = write_entry_points(layout, &self.site_root, &self.options.entry_points)?;
+      if let Some(fallback) = &self.options.history_fallback {
+        pages.extend(write_history_fallback(layout, &self.site_root, fallback)?);
+      }
+      anyhow::Ok(pages)
+    })?);
+    state.patched_site = Some(patched);
+    Ok(())
+  }
+}
+
+/// Write the root redirect, double-clickable launchers and, after codegen, `version.json` into
+/// the bundle root.
+pub struct LauncherStage {
+  /// Bundle root receiving the launcher files.
+  pub root_dir: PathBuf,
+  /// Site directory relative to `root_dir`.
+  pub site_prefix: String,
+  /// Title shown by the launchers.
+  pub title: String,
+  /// Optional branding for the instructions page.
+  pub theme: Option<ThemePalette>,
+}
+
+impl PipelineStage for LauncherStage {
+  fn run(&self, builder: &OfflineBuilder<'_>, state: &mut BuildState<'_>) -> BuildResult<()> {
+    let layout = &builder.context().layout;
+    write_root_launcher(layout, &self.root_dir, &self.site_prefix)?;
+    if let Some(artifacts) = &state.artifacts {
+      let version_file = self.root_dir.join(VERSION_FILE);
+      artifacts.version_json.save(&version_file)?;
+      state.written_files.push(version_file);
+    }
+    state.written_files.extend(write_themed_launcher_scripts(
+      layout,
+      &self.root_dir,
+      &self.site_prefix,
+      &self.title,
+      self.theme.as_ref(),
+    )?);
+    Ok(())
+  }
+}
+
+/// Audit the HTML pages below `root_dir` and add the findings to
+/// [`AccessibilityReport::pages`](crate::report::AccessibilityReport::pages).
+///
+/// Insert it after the stages writing pages, e.g. after [`PATCH`] and [`LAUNCHER`]; it needs the
+/// report rendered by the [`CODEGEN`] stage.
+pub struct AccessibilityAuditStage {
+  /// Bundle or site root whose pages are audited.
+  pub root_dir: PathBuf,
+}
+
+impl PipelineStage for AccessibilityAuditStage {
+  fn run(&self, _builder: &OfflineBuilder<'_>, state: &mut BuildState<'_>) -> BuildResult<()> {
+    let findings = audit_html_accessibility(&self.root_dir)?;
+    let artifacts = state
+      .artifacts
+      .as_mut()
+      .ok_or_else(|| format!("pipeline stage `{ACCESSIBILITY}` requires the `{CODEGEN}` stage"))?;
+    artifacts.report.accessibility.pages.extend(findings);
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::OfflineBuildContext;
+  use std::cell::RefCell;
+  use std::collections::BTreeSet;
+  use std::rc::Rc;
+  use tempfile::tempdir;
+
+  #[test]
+  fn stages_can_be_inserted_skipped_and_reordered() -> BuildResult<()> {
+    let temp = tempdir()?;
+    let manifest_dir = temp.path();
+    let collections_dir = manifest_dir.join("programs");
+    fs::create_dir_all(collections_dir.join("P001"))?;
+    fs::write(
+      collections_dir.join("P001/collection.json"),
+      r#"{"title":"Program"}"#,
+    )?;
+    fs::create_dir_all(collections_dir.join("P001/001-intro"))?;
+    fs::write(collections_dir.join("P001/001-intro/index.md"), "# Intro\n")?;
+    let context = OfflineBuildContext::builder()
+      .manifest_dir(manifest_dir)
+      .collections_dir(&collections_dir)
+      .build()?;
+    let builder = OfflineBuilder::new(context);
+
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    let recorder = Rc::clone(&seen);
+    let mut pipeline = Pipeline::standard();
+    pipeline.insert_after(
+      SCAN,
+      "lint",
+      move |_: &OfflineBuilder<'_>, state: &mut BuildState<'_>| -> BuildResult<()> {
+        let manifest = state.require_manifest("lint")?;
+        recorder.borrow_mut().extend(
+          manifest
+            .collection_catalog
+            .iter()
+            .map(|record| record.id.clone()),
+        );
+        Ok(())
+      },
+    )?;
+    assert!(pipeline.skip(MIRROR));
+    assert!(!pipeline.skip(MIRROR));
+    assert_eq!(pipeline.names(), [PRE_BUILD, SCAN, "lint", CODEGEN]);
+
+    let state = pipeline.run(&builder, &crate::IncludeAll)?;
+    assert_eq!(*seen.borrow(), ["P001"]);
+    assert!(state.artifacts.is_some());
+    assert_eq!(
+      state.stats.get("scan/markdown").map(|timing| timing.calls),
+      Some(1)
+    );
+    assert!(state.stats.get("lint").is_some());
+    assert!(state.stats.get("mirror").is_none());
+    assert_eq!(
+      state.stats.get("codegen/json").map(|timing| timing.calls),
+      Some(5)
+    );
+    assert_eq!(state.artifacts.unwrap().report.stats, state.stats);
+
+    pipeline.move_after(SCAN, CODEGEN)?;
+    let err = pipeline.run(&builder, &crate::IncludeAll).err().unwrap();
+    assert_eq!(
+      err.to_string(),
+      "pipeline stage `lint` requires the `scan` stage"
+    );
+    assert!(pipeline.insert_before("missing", "x", ScanStage).is_err());
+
+    Ok(())
+  }
+
+  #[test]
+  fn profiles_share_the_scan_and_run_later_stages_each() -> BuildResult<()> {
+    let temp = tempdir()?;
+    let manifest_dir = temp.path();
+    let collections_dir = manifest_dir.join("programs");
+    for id in ["P001", "P002"] {
+      fs::create_dir_all(collections_dir.join(id).join("001-intro"))?;
+      fs::write(
+        collections_dir.join(id).join("collection.json"),
+        r#"{"title":"Program"}"#,
+      )?;
+      fs::write(
+        collections_dir.join(id).join("001-intro/index.md"),
+        "# Intro\n",
+      )?;
+    }
+    let context = OfflineBuildContext::builder()
+      .manifest_dir(manifest_dir)
+      .collections_dir(&collections_dir)
+      .build()?;
+    let builder = OfflineBuilder::new(context);
+
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    let mut pipeline = Pipeline::standard();
+    let recorder = Rc::clone(&seen);
+    pipeline.insert_before(
+      SCAN,
+      "before",
+      move |_: &OfflineBuilder<'_>, _: &mut BuildState<'_>| -> BuildResult<()> {
+        recorder.borrow_mut().push("before".to_string());
+        Ok(())
+      },
+    )?;
+    let recorder = Rc::clone(&seen);
+    pipeline.insert_after(
+      MIRROR,
+      "after",
+      move |_: &OfflineBuilder<'_>, state: &mut BuildState<'_>| -> BuildResult<()> {
+        let profile = state.profile.as_ref().map(|profile| profile.name.clone());
+        let manifest = state.require_manifest("after")?;
+        recorder.borrow_mut().push(format!(
+          "{}:{}",
+          profile.unwrap_or_default(),
+          manifest.collection_catalog.len()
+        ));
+        Ok(())
+      },
+    )?;
+
+    let profiles = [
+      ("demo", BTreeSet::from(["P001".to_string()])),
+      (
+        "full",
+        BTreeSet::from(["P001".to_string(), "P002".to_string()]),
+      ),
+    ];
+    let outputs = pipeline.run_profiles(&builder, &profiles)?;
+    assert_eq!(*seen.borrow(), ["before", "demo:1", "full:2"]);
+    assert_eq!(outputs[0].name, "demo");
+    assert!(outputs[0].layout.offline_bundle_root.ends_with("/demo"));
+    let stats = &outputs[1].artifacts.report.stats;
+    assert_eq!(stats.get(SCAN).map(|timing| timing.calls), Some(1));
+    assert!(stats.get("after").is_some());
+
+    let err = pipeline
+      .run_profiles(&builder, &[("a/b", BTreeSet::new())])
+      .err()
+      .unwrap();
+    assert_eq!(err.to_string(), "invalid profile name `a/b`");
+    Ok(())
+  }
+}
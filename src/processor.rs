@@ -0,0 +1,48 @@
+//! Hooks for transforming collection assets while they are mirrored.
+
+use std::path::Path;
+
+use crate::builder::BuildResult;
+use crate::models::AssetEntry;
+
+/// Result of running an [`AssetProcessor`] on one asset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AssetOutcome {
+  /// The asset was not handled; the next processor or the default hard link/copy installs it.
+  Unchanged,
+  /// The processor wrote the asset to the destination path itself.
+  Written,
+  /// The processor wrote the asset next to the destination under a new file name, e.g.
+  /// `photo.webp` for `photo.png`. The new name is recorded in the manifest and generated code.
+  Renamed(String),
+}
+
+/// Transformation applied to collection assets as they are copied into the asset mirror.
+///
+/// Processors run in registration order for every asset that is not a byte-identical
+/// duplicate; the first one that does not return [`AssetOutcome::Unchanged`] wins. Duplicates
+/// reuse the outcome of their canonical copy. Any file previously at `destination` has already
+/// been removed, so processors never write through a hard link into the authored source.
+pub trait AssetProcessor {
+  /// Process `source` into `destination` for the given asset.
+  fn process(
+    &self,
+    source: &Path,
+    destination: &Path,
+    entry: &AssetEntry,
+  ) -> BuildResult<AssetOutcome>;
+}
+
+impl<F> AssetProcessor for F
+where
+  F: Fn(&Path, &Path, &AssetEntry) -> BuildResult<AssetOutcome>,
+{
+  fn process(
+    &self,
+    source: &Path,
+    destination: &Path,
+    entry: &AssetEntry,
+  ) -> BuildResult<AssetOutcome> {
+    self(source, destination, entry)
+  }
+}
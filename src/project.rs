@@ -1,8 +1,15 @@
 //! Configuration describing the authored content layout and offline build context.
 
+use std::borrow::Cow;
 use std::path::{Path, PathBuf};
 
-use crate::config::CollectionConfigOverrides;
+#[cfg(all(feature = "archive", not(target_arch = "wasm32")))]
+use crate::archive::{
+  ARCHIVE_CACHE_DIR, ArchiveChecksum, extract_collections, is_collections_archive,
+};
+use crate::asset_paths::AssetReferenceFilter;
+use crate::config::{CollectionConfigOverrides, ConfigValidationError, ProjectConfig};
+use crate::filesystem::FilesystemProfile;
 
 /// Static layout describing how authored content and offline bundles are organised.
 #[derive(Clone, Debug)]
@@ -25,12 +32,48 @@ pub struct OfflineProjectLayout {
   pub collections_dir_name: String,
   /// Output directory for the offline HTML bundle.
   pub offline_bundle_root: String,
+  /// Absolute directory used for the bundle instead of `offline_bundle_root`, e.g. one outside
+  /// `target/` that survives `cargo clean`.
+  pub output_root: Option<PathBuf>,
   /// Index HTML file generated by `dx` for the application shell.
   pub index_html_file: String,
   /// Cargo target directory used for build artifacts.
   pub target_dir: String,
   /// Manifest JSON file produced by the build script.
   pub offline_manifest_json: String,
+  /// Maximum directory depth below a collection that asset scanning descends into.
+  pub scan_max_depth: usize,
+  /// Lowercase file extensions bundled from assets trees; empty allows every extension.
+  pub asset_extensions: Vec<String>,
+  /// Lowercase file extensions never bundled from assets trees.
+  pub excluded_extensions: Vec<String>,
+}
+
+/// Default for [`OfflineProjectLayout::scan_max_depth`].
+pub const DEFAULT_SCAN_MAX_DEPTH: usize = 32;
+
+/// Collection metadata file name used by older content trees.
+pub const LEGACY_COLLECTION_METADATA_FILE: &str = "program.json";
+
+/// Metadata document of the collection in `dir`.
+///
+/// Falls back to [`LEGACY_COLLECTION_METADATA_FILE`] when `file_name` is missing but the legacy
+/// file exists, so older trees keep building until they are migrated.
+pub fn collection_metadata_path(dir: &Path, file_name: &str) -> PathBuf {
+  let path = dir.join(file_name);
+  if !path.exists() {
+    let legacy = dir.join(LEGACY_COLLECTION_METADATA_FILE);
+    if legacy.exists() {
+      return legacy;
+    }
+  }
+  path
+}
+
+/// Builder for [`OfflineProjectLayout`] where only deviations from the defaults are specified.
+#[derive(Clone, Debug, Default)]
+pub struct OfflineProjectLayoutBuilder {
+  layout: OfflineProjectLayout,
 }
 
 /// Runtime parameters required to build offline artifacts for a project.
@@ -39,15 +82,43 @@ pub struct OfflineBuildContext<'a> {
   pub layout: OfflineProjectLayout,
   /// Directory containing the Cargo manifest.
   pub manifest_dir: &'a Path,
-  /// Absolute path to the authored collections directory.
-  pub collections_dir: &'a Path,
+  /// Absolute path to the authored collections directory, or to the extracted copy of a
+  /// collections archive.
+  pub collections_dir: Cow<'a, Path>,
   /// Local path to the collections directory used for rerun hints.
   pub collections_local_path: &'a Path,
   /// Directory where assets referenced by markdown will be mirrored.
   pub asset_mirror_dir: PathBuf,
+  /// Removable media file system that bundled assets are checked against, if any.
+  pub target_filesystem: Option<FilesystemProfile>,
+  /// Rules for references in entry bodies that are never treated as bundled assets.
+  pub asset_reference_filter: AssetReferenceFilter,
+}
+
+/// Builder for [`OfflineBuildContext`] validating paths when [`build`](Self::build) is called.
+///
+/// Only the manifest and collections directories are required. The layout defaults to
+/// [`ProjectConfig::default`], the selection path to the collections directory and the asset
+/// mirror to `<manifest_dir>/<target_dir>/offline-assets`.
+#[derive(Default)]
+pub struct OfflineBuildContextBuilder<'a> {
+  layout: Option<OfflineProjectLayout>,
+  manifest_dir: Option<&'a Path>,
+  collections_dir: Option<&'a Path>,
+  collections_local_path: Option<&'a Path>,
+  asset_mirror_dir: Option<PathBuf>,
+  target_filesystem: Option<FilesystemProfile>,
+  asset_reference_filter: Option<AssetReferenceFilter>,
+  #[cfg(all(feature = "archive", not(target_arch = "wasm32")))]
+  archive_checksum: ArchiveChecksum,
 }
 
 impl<'a> OfflineBuildContext<'a> {
+  /// Start building a context; see [`OfflineBuildContextBuilder`] for defaults.
+  pub fn builder() -> OfflineBuildContextBuilder<'a> {
+    OfflineBuildContextBuilder::default()
+  }
+
   /// Construct a new build context with project-specific paths.
   pub fn new(
     layout: OfflineProjectLayout,
@@ -59,14 +130,354 @@ impl<'a> OfflineBuildContext<'a> {
     Self {
       layout,
       manifest_dir,
-      collections_dir,
+      collections_dir: Cow::Borrowed(collections_dir),
       collections_local_path,
       asset_mirror_dir,
+      target_filesystem: None,
+      asset_reference_filter: AssetReferenceFilter::default(),
+    }
+  }
+}
+
+impl<'a> OfflineBuildContextBuilder<'a> {
+  /// Use the provided layout instead of the default one.
+  pub fn layout(mut self, layout: OfflineProjectLayout) -> Self {
+    self.layout = Some(layout);
+    self
+  }
+
+  /// Directory containing the Cargo manifest.
+  pub fn manifest_dir(mut self, path: &'a Path) -> Self {
+    self.manifest_dir = Some(path);
+    self
+  }
+
+  /// Absolute path to the authored collections directory.
+  ///
+  /// With the `archive` feature this may also be a `.zip`, `.tar` or `.tar.zst` archive, which
+  /// [`build`](Self::build) verifies and extracts into `<target_dir>/offline-collections`; see
+  /// [`crate::archive`].
+  pub fn collections_dir(mut self, path: &'a Path) -> Self {
+    self.collections_dir = Some(path);
+    self
+  }
+
+  /// Local path to the collections directory used for rerun hints.
+  pub fn collections_local_path(mut self, path: &'a Path) -> Self {
+    self.collections_local_path = Some(path);
+    self
+  }
+
+  /// Directory where assets referenced by markdown will be mirrored.
+  pub fn asset_mirror_dir(mut self, path: impl Into<PathBuf>) -> Self {
+    self.asset_mirror_dir = Some(path.into());
+    self
+  }
+
+  /// Check bundled assets against a removable media file system, see [`FilesystemProfile`].
+  pub fn target_filesystem(mut self, profile: FilesystemProfile) -> Self {
+    self.target_filesystem = Some(profile);
+    self
+  }
+
+  /// Skip asset references matching `filter`, e.g. from [`ProjectConfig::asset_reference_filter`].
+  pub fn asset_reference_filter(mut self, filter: AssetReferenceFilter) -> Self {
+    self.asset_reference_filter = Some(filter);
+    self
+  }
+
+  /// Verify a collections archive as `checksum` says instead of against its `.sha256` file.
+  #[cfg(all(feature = "archive", not(target_arch = "wasm32")))]
+  pub fn archive_checksum(mut self, checksum: ArchiveChecksum) -> Self {
+    self.archive_checksum = checksum;
+    self
+  }
+
+  /// Validate the provided values and construct the build context.
+  pub fn build(self) -> Result<OfflineBuildContext<'a>, ConfigValidationError> {
+    let manifest_dir = self.manifest_dir.ok_or(ConfigValidationError::Missing {
+      field: "manifest_dir",
+    })?;
+    let collections_dir = self.collections_dir.ok_or(ConfigValidationError::Missing {
+      field: "collections_dir",
+    })?;
+
+    let layout = self.layout.unwrap_or_default();
+    #[cfg(all(feature = "archive", not(target_arch = "wasm32")))]
+    let extracted = if is_collections_archive(collections_dir) && manifest_dir.is_dir() {
+      let cache_dir = manifest_dir
+        .join(&layout.target_dir)
+        .join(ARCHIVE_CACHE_DIR);
+      let root = extract_collections(collections_dir, &cache_dir, &self.archive_checksum).map_err(
+        |err| ConfigValidationError::Archive {
+          path: collections_dir.to_path_buf(),
+          reason: err.to_string(),
+        },
+      )?;
+      Some(root)
+    } else {
+      None
+    };
+    #[cfg(not(all(feature = "archive", not(target_arch = "wasm32"))))]
+    let extracted: Option<PathBuf> = None;
+
+    for (field, path) in [
+      ("manifest_dir", manifest_dir),
+      (
+        "collections_dir",
+        extracted.as_deref().unwrap_or(collections_dir),
+      ),
+    ] {
+      if !path.is_dir() {
+        return Err(ConfigValidationError::PathNotFound {
+          field,
+          path: path.to_path_buf(),
+        });
+      }
+    }
+
+    let asset_mirror_dir = self
+      .asset_mirror_dir
+      .unwrap_or_else(|| manifest_dir.join(&layout.target_dir).join("offline-assets"));
+
+    let mut context = OfflineBuildContext::new(
+      layout,
+      manifest_dir,
+      collections_dir,
+      self.collections_local_path.unwrap_or(collections_dir),
+      asset_mirror_dir,
+    );
+    if let Some(extracted) = extracted {
+      context.collections_dir = Cow::Owned(extracted);
     }
+    context.target_filesystem = self.target_filesystem;
+    context.asset_reference_filter = self.asset_reference_filter.unwrap_or_default();
+    Ok(context)
+  }
+}
+
+impl Default for OfflineProjectLayout {
+  fn default() -> Self {
+    ProjectConfig::default().into_layout()
+  }
+}
+
+impl OfflineProjectLayoutBuilder {
+  /// Set the directory containing static assets for each entry relative to the collection root.
+  pub fn entry_assets_dir(mut self, value: impl Into<String>) -> Self {
+    self.layout.entry_assets_dir = value.into();
+    self
+  }
+
+  /// Set the markdown file name used for authored entries.
+  pub fn entry_markdown_file(mut self, value: impl Into<String>) -> Self {
+    self.layout.entry_markdown_file = value.into();
+    self
+  }
+
+  /// Set the metadata file describing the collection (title, description, etc.).
+  pub fn collection_metadata_file(mut self, value: impl Into<String>) -> Self {
+    self.layout.collection_metadata_file = value.into();
+    self
+  }
+
+  /// Set the directory containing production-only assets that should be ignored.
+  pub fn excluded_dir_name(mut self, value: impl Into<String>) -> Self {
+    self.layout.excluded_dir_name = value.into();
+    self
+  }
+
+  /// Set the path fragment signalling that a file should be excluded from the offline bundle.
+  pub fn excluded_path_fragment(mut self, value: impl Into<String>) -> Self {
+    self.layout.excluded_path_fragment = value.into();
+    self
+  }
+
+  /// Set the literal prefix applied when embedding collection assets in generated Rust code.
+  pub fn collection_asset_literal_prefix(mut self, value: impl Into<String>) -> Self {
+    self.layout.collection_asset_literal_prefix = value.into();
+    self
+  }
+
+  /// Set the relative path to the offline site root inside the bundle output.
+  pub fn offline_site_root(mut self, value: impl Into<String>) -> Self {
+    self.layout.offline_site_root = value.into();
+    self
+  }
+
+  /// Set the directory containing all collections relative to the manifest root.
+  pub fn collections_dir_name(mut self, value: impl Into<String>) -> Self {
+    self.layout.collections_dir_name = value.into();
+    self
+  }
+
+  /// Set the output directory for the offline HTML bundle.
+  pub fn offline_bundle_root(mut self, value: impl Into<String>) -> Self {
+    self.layout.offline_bundle_root = value.into();
+    self
+  }
+
+  /// Write the bundle to the absolute directory `value` instead of `offline_bundle_root`.
+  pub fn output_root(mut self, value: impl Into<PathBuf>) -> Self {
+    self.layout.output_root = Some(value.into());
+    self
+  }
+
+  /// Set the index HTML file generated by `dx` for the application shell.
+  pub fn index_html_file(mut self, value: impl Into<String>) -> Self {
+    self.layout.index_html_file = value.into();
+    self
+  }
+
+  /// Set the cargo target directory used for build artifacts.
+  pub fn target_dir(mut self, value: impl Into<String>) -> Self {
+    self.layout.target_dir = value.into();
+    self
+  }
+
+  /// Set the manifest JSON file produced by the build script.
+  pub fn offline_manifest_json(mut self, value: impl Into<String>) -> Self {
+    self.layout.offline_manifest_json = value.into();
+    self
+  }
+
+  /// Set the maximum directory depth below a collection that asset scanning descends into.
+  pub fn scan_max_depth(mut self, value: usize) -> Self {
+    self.layout.scan_max_depth = value;
+    self
+  }
+
+  /// Only bundle asset files with one of these extensions (case-insensitive, leading `.` optional).
+  pub fn asset_extensions<I, S>(mut self, extensions: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+  {
+    self.layout.asset_extensions = normalize_extensions(extensions);
+    self
+  }
+
+  /// Never bundle asset files with one of these extensions (case-insensitive, leading `.`
+  /// optional).
+  pub fn excluded_extensions<I, S>(mut self, extensions: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+  {
+    self.layout.excluded_extensions = normalize_extensions(extensions);
+    self
+  }
+
+  /// Validate the layout and return it.
+  pub fn build(self) -> Result<OfflineProjectLayout, ConfigValidationError> {
+    self.layout.validate()?;
+    Ok(self.layout)
   }
 }
 
 impl OfflineProjectLayout {
+  /// Start building a layout from the defaults used by [`ProjectConfig::default`].
+  pub fn builder() -> OfflineProjectLayoutBuilder {
+    OfflineProjectLayoutBuilder::default()
+  }
+
+  /// Directory the bundle is written to: `output_root` when set, otherwise
+  /// `offline_bundle_root` resolved against `base`, usually the manifest directory.
+  pub fn bundle_root(&self, base: &Path) -> PathBuf {
+    match &self.output_root {
+      Some(root) => root.clone(),
+      None => base.join(&self.offline_bundle_root),
+    }
+  }
+
+  /// Metadata document of the collection in `dir`, see [`collection_metadata_path`].
+  pub fn collection_metadata_path(&self, dir: &Path) -> PathBuf {
+    collection_metadata_path(dir, &self.collection_metadata_file)
+  }
+
+  /// Check the layout for values that would silently break scanning or bundling.
+  ///
+  /// File and directory names must be non-empty single path segments, the excluded path
+  /// fragment must be wrapped in slashes (`/dev/`), the literal prefix must start with `/`, the
+  /// output root must be absolute and the offline site root must be relative.
+  pub fn validate(&self) -> Result<(), ConfigValidationError> {
+    let segments = [
+      ("entry_assets_dir", &self.entry_assets_dir),
+      ("entry_markdown_file", &self.entry_markdown_file),
+      ("collection_metadata_file", &self.collection_metadata_file),
+      ("excluded_dir_name", &self.excluded_dir_name),
+      ("collections_dir_name", &self.collections_dir_name),
+      ("index_html_file", &self.index_html_file),
+      ("offline_manifest_json", &self.offline_manifest_json),
+    ];
+    for (field, value) in segments {
+      if value.trim().is_empty() {
+        return Err(ConfigValidationError::Missing { field });
+      }
+      if value.contains(['/', '\\']) {
+        return Err(ConfigValidationError::Invalid {
+          field,
+          value: value.clone(),
+          reason: "must be a single path segment",
+        });
+      }
+    }
+
+    for (field, value) in [
+      ("offline_bundle_root", &self.offline_bundle_root),
+      ("target_dir", &self.target_dir),
+    ] {
+      if value.trim().is_empty() {
+        return Err(ConfigValidationError::Missing { field });
+      }
+    }
+
+    if let Some(root) = self.output_root.as_ref().filter(|root| !root.is_absolute()) {
+      return Err(ConfigValidationError::Invalid {
+        field: "output_root",
+        value: root.display().to_string(),
+        reason: "must be an absolute path",
+      });
+    }
+
+    if self.scan_max_depth == 0 {
+      return Err(ConfigValidationError::Invalid {
+        field: "scan_max_depth",
+        value: self.scan_max_depth.to_string(),
+        reason: "must be at least 1",
+      });
+    }
+
+    let fragment = &self.excluded_path_fragment;
+    if fragment.len() < 3 || !fragment.starts_with('/') || !fragment.ends_with('/') {
+      return Err(ConfigValidationError::Invalid {
+        field: "excluded_path_fragment",
+        value: fragment.clone(),
+        reason: "must be wrapped in slashes, e.g. `/dev/`",
+      });
+    }
+
+    if !self.collection_asset_literal_prefix.starts_with('/') {
+      return Err(ConfigValidationError::Invalid {
+        field: "collection_asset_literal_prefix",
+        value: self.collection_asset_literal_prefix.clone(),
+        reason: "must start with `/`",
+      });
+    }
+
+    if self.offline_site_root.starts_with(['/', '\\'])
+      || Path::new(&self.offline_site_root).is_absolute()
+    {
+      return Err(ConfigValidationError::Invalid {
+        field: "offline_site_root",
+        value: self.offline_site_root.clone(),
+        reason: "must be relative to the offline bundle root",
+      });
+    }
+
+    Ok(())
+  }
+
   /// Directory relative to each collection containing authored assets.
   pub fn entry_assets_dir(&self) -> &str {
     self.entry_assets_dir.as_str()
@@ -79,3 +490,158 @@ impl OfflineProjectLayout {
     layout
   }
 }
+
+/// Lowercase `extensions` without leading dots, dropping blanks.
+pub(crate) fn normalize_extensions<I, S>(extensions: I) -> Vec<String>
+where
+  I: IntoIterator<Item = S>,
+  S: AsRef<str>,
+{
+  extensions
+    .into_iter()
+    .map(|extension| {
+      extension
+        .as_ref()
+        .trim()
+        .trim_start_matches('.')
+        .to_ascii_lowercase()
+    })
+    .filter(|extension| !extension.is_empty())
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::tempdir;
+
+  #[test]
+  fn layout_builder_overrides_defaults() {
+    let layout = OfflineProjectLayout::builder()
+      .entry_assets_dir("media")
+      .offline_site_root("site/deep")
+      .build()
+      .unwrap();
+
+    assert_eq!(layout.entry_assets_dir, "media");
+    assert_eq!(layout.offline_site_root, "site/deep");
+    assert_eq!(layout.index_html_file, "index.html");
+  }
+
+  #[test]
+  fn layout_validation_rejects_malformed_values() {
+    let error = OfflineProjectLayout::builder()
+      .excluded_path_fragment("dev")
+      .build()
+      .unwrap_err();
+    assert!(matches!(error, ConfigValidationError::Invalid {
+      field: "excluded_path_fragment",
+      ..
+    }));
+
+    let error = OfflineProjectLayout::builder()
+      .offline_site_root("/var/www")
+      .build()
+      .unwrap_err();
+    assert!(matches!(error, ConfigValidationError::Invalid {
+      field: "offline_site_root",
+      ..
+    }));
+
+    let error = OfflineProjectLayout::builder()
+      .output_root("offline-html")
+      .build()
+      .unwrap_err();
+    assert!(matches!(error, ConfigValidationError::Invalid {
+      field: "output_root",
+      ..
+    }));
+
+    assert!(OfflineProjectLayout::default().validate().is_ok());
+  }
+
+  #[test]
+  fn context_builder_applies_defaults() {
+    let dir = tempdir().unwrap();
+    let context = OfflineBuildContext::builder()
+      .manifest_dir(dir.path())
+      .collections_dir(dir.path())
+      .build()
+      .unwrap();
+
+    assert_eq!(context.collections_local_path, dir.path());
+    assert_eq!(
+      context.asset_mirror_dir,
+      dir.path().join("target").join("offline-assets")
+    );
+    assert_eq!(context.layout.entry_markdown_file, "index.md");
+  }
+
+  #[test]
+  fn context_builder_rejects_missing_paths() {
+    let dir = tempdir().unwrap();
+    let missing = dir.path().join("missing");
+
+    let error = OfflineBuildContext::builder()
+      .manifest_dir(dir.path())
+      .collections_dir(&missing)
+      .build()
+      .err()
+      .unwrap();
+    assert_eq!(error, ConfigValidationError::PathNotFound {
+      field: "collections_dir",
+      path: missing,
+    });
+
+    let error = OfflineBuildContext::builder()
+      .collections_dir(dir.path())
+      .build()
+      .err()
+      .unwrap();
+    assert_eq!(error, ConfigValidationError::Missing {
+      field: "manifest_dir"
+    });
+  }
+
+  #[cfg(all(feature = "archive", not(target_arch = "wasm32")))]
+  #[test]
+  fn context_builder_extracts_collections_archives() {
+    let dir = tempdir().unwrap();
+    let mut builder = tar::Builder::new(Vec::new());
+    let mut header = tar::Header::new_gnu();
+    header.set_size(8);
+    header.set_mode(0o644);
+    builder
+      .append_data(&mut header, "P001/intro/index.md", &b"# Intro\n"[..])
+      .unwrap();
+    let archive = dir.path().join("programs.tar");
+    std::fs::write(&archive, builder.into_inner().unwrap()).unwrap();
+
+    let error = OfflineBuildContext::builder()
+      .manifest_dir(dir.path())
+      .collections_dir(&archive)
+      .build()
+      .err()
+      .unwrap();
+    assert!(matches!(error, ConfigValidationError::Archive { .. }));
+
+    let context = OfflineBuildContext::builder()
+      .manifest_dir(dir.path())
+      .collections_dir(&archive)
+      .archive_checksum(ArchiveChecksum::Skip)
+      .build()
+      .unwrap();
+    assert!(
+      context
+        .collections_dir
+        .starts_with(dir.path().join("target").join(ARCHIVE_CACHE_DIR))
+    );
+    assert!(
+      context
+        .collections_dir
+        .join("P001/intro/index.md")
+        .is_file()
+    );
+    assert_eq!(context.collections_local_path, archive);
+  }
+}
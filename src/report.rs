@@ -0,0 +1,444 @@
+//! Findings collected during a build and returned with the generated artifacts.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::bundle::js_patch::WasmEmbedding;
+use crate::manifest::embeds::{ExternalEmbed, detect_external_embeds};
+use crate::models::{AssetEntry, OfflineEntryRecord};
+
+/// Build findings that do not stop the build unless a strict mode asks for it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct BuildReport {
+  /// Image alternative text coverage per collection and entry, and generated page findings.
+  pub accessibility: AccessibilityReport,
+  /// Remote iframes and video links that will not work offline.
+  pub embeds: Vec<EmbedFinding>,
+  /// Count, size and age of the collected collection assets.
+  pub assets: AssetStats,
+  /// Time spent in each pipeline stage and instrumented step.
+  pub stats: BuildStats,
+}
+
+/// Wall-clock time spent in the stages of a [`Pipeline`](crate::Pipeline) run.
+///
+/// Every stage is timed under its name; steps inside it are recorded as `stage/step`, e.g.
+/// `scan/markdown`, `scan/assets`, `mirror/copy`, `codegen/code`, `codegen/json` and
+/// `patch/index`. With the `tracing` feature each step also opens a `tracing` span.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct BuildStats {
+  /// Timings in the order their step first ran.
+  pub timings: Vec<StepTiming>,
+  /// Encoding and size of the WebAssembly module embedded by the `patch` stage, if it ran.
+  pub wasm: Option<WasmEmbedding>,
+}
+
+/// Accumulated time of one stage or step.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StepTiming {
+  /// Stage name, or `stage/step` for steps inside a stage.
+  pub name: String,
+  /// Number of times the step ran, e.g. once per parsed markdown file.
+  pub calls: usize,
+  /// Total time across all calls.
+  pub duration: Duration,
+}
+
+impl BuildStats {
+  /// Timing recorded under `name`.
+  pub fn get(&self, name: &str) -> Option<&StepTiming> {
+    self.timings.iter().find(|timing| timing.name == name)
+  }
+
+  /// Add the timings of `other`, e.g. of a later [`Pipeline`](crate::pipeline::Pipeline) run.
+  pub(crate) fn extend(&mut self, other: BuildStats) {
+    for timing in other.timings {
+      match self.timings.iter_mut().find(|own| own.name == timing.name) {
+        Some(own) => {
+          own.calls += timing.calls;
+          own.duration += timing.duration;
+        }
+        None => self.timings.push(timing),
+      }
+    }
+    if other.wasm.is_some() {
+      self.wasm = other.wasm;
+    }
+  }
+
+  fn record(&mut self, name: String, duration: Duration) {
+    match self.timings.iter_mut().find(|timing| timing.name == name) {
+      Some(timing) => {
+        timing.calls += 1;
+        timing.duration += duration;
+      }
+      None => self.timings.push(StepTiming {
+        name,
+        calls: 1,
+        duration,
+      }),
+    }
+  }
+}
+
+impl fmt::Display for BuildStats {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let width = self
+      .timings
+      .iter()
+      .map(|timing| timing.name.len())
+      .max()
+      .unwrap_or(0);
+    for timing in &self.timings {
+      writeln!(
+        f,
+        "{:width$}  {:>10.3?}  {}x",
+        timing.name, timing.duration, timing.calls
+      )?;
+    }
+    if let Some(wasm) = &self.wasm {
+      writeln!(
+        f,
+        "wasm {}: {} bytes embedded as {} bytes",
+        wasm.encoding.as_str(),
+        wasm.wasm_bytes,
+        wasm.embedded_bytes
+      )?;
+    }
+    Ok(())
+  }
+}
+
+/// Steps being timed on this thread and the stats they are recorded into.
+#[derive(Default)]
+struct StatsCollector {
+  stack: Vec<String>,
+  stats: BuildStats,
+}
+
+thread_local! {
+  static COLLECTOR: RefCell<Option<StatsCollector>> = const { RefCell::new(None) };
+}
+
+/// Run `f` while recording the [`timed`] steps it performs.
+pub(crate) fn collect_stats<T>(f: impl FnOnce() -> T) -> (T, BuildStats) {
+  let outer = COLLECTOR.with(|collector| collector.replace(Some(StatsCollector::default())));
+  let value = f();
+  let collected = COLLECTOR.with(|collector| collector.replace(outer));
+  (
+    value,
+    collected
+      .map(|collector| collector.stats)
+      .unwrap_or_default(),
+  )
+}
+
+/// Run `f` as the build step `name`, nested in the step currently running.
+///
+/// Outside [`collect_stats`] only the `tracing` span, if enabled, is emitted.
+pub(crate) fn timed<T>(name: &str, f: impl FnOnce() -> T) -> T {
+  #[cfg(feature = "tracing")]
+  let _span = tracing::info_span!("offline_build", step = name).entered();
+  let active = COLLECTOR.with(|collector| {
+    collector.borrow_mut().as_mut().map(|collector| {
+      collector.stack.push(name.to_string());
+      collector.stack.join("/")
+    })
+  });
+  let start = Instant::now();
+  let value = f();
+  if let Some(path) = active {
+    let elapsed = start.elapsed();
+    COLLECTOR.with(|collector| {
+      if let Some(collector) = collector.borrow_mut().as_mut() {
+        collector.stack.pop();
+        collector.stats.record(path, elapsed);
+      }
+    });
+  }
+  value
+}
+
+/// Record the WebAssembly embedding of the current [`collect_stats`] run.
+pub(crate) fn record_wasm_embedding(wasm: WasmEmbedding) {
+  COLLECTOR.with(|collector| {
+    if let Some(collector) = collector.borrow_mut().as_mut() {
+      collector.stats.wasm = Some(wasm);
+    }
+  });
+}
+
+/// Totals over the collected collection assets, from the metadata recorded while scanning.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct AssetStats {
+  /// Number of collected assets.
+  pub files: usize,
+  /// Combined byte size of the source files.
+  pub bytes: u64,
+  /// Most recent source modification time in seconds since the Unix epoch.
+  pub newest_modified: Option<u64>,
+}
+
+impl AssetStats {
+  /// Sum the recorded [`AssetEntry::size`] and [`AssetEntry::modified`] of `assets`.
+  pub fn from_assets<'a>(assets: impl IntoIterator<Item = &'a AssetEntry>) -> Self {
+    assets
+      .into_iter()
+      .fold(Self::default(), |stats, asset| Self {
+        files: stats.files + 1,
+        bytes: stats.bytes + asset.size.unwrap_or(0),
+        newest_modified: stats.newest_modified.max(asset.modified),
+      })
+  }
+}
+
+/// Remote embed found in an entry body.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EmbedFinding {
+  /// Collection the entry belongs to.
+  pub collection_id: String,
+  /// Entry containing the embed.
+  pub entry_id: String,
+  /// The detected embed.
+  pub embed: ExternalEmbed,
+  /// Whether the embed was replaced with an offline placeholder.
+  pub replaced: bool,
+}
+
+impl EmbedFinding {
+  /// Detect the remote embeds of every entry, see [`detect_external_embeds`].
+  pub fn from_entries(entries: &[OfflineEntryRecord], replaced: bool) -> Vec<Self> {
+    entries
+      .iter()
+      .flat_map(|entry| {
+        detect_external_embeds(&entry.body)
+          .into_iter()
+          .map(move |embed| EmbedFinding {
+            collection_id: entry.collection_id.clone(),
+            entry_id: entry.entry_id.clone(),
+            replaced: replaced && embed.iframe,
+            embed,
+          })
+      })
+      .collect()
+  }
+}
+
+/// Image alt-text coverage of the bundled entries.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct AccessibilityReport {
+  /// Collections with at least one image, sorted by identifier.
+  pub collections: Vec<CollectionAccessibility>,
+  /// Problems in the generated HTML pages, filled in by an
+  /// [`AccessibilityAuditStage`](crate::pipeline::AccessibilityAuditStage).
+  pub pages: Vec<PageFinding>,
+}
+
+/// Accessibility problem found in a generated HTML page.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PageFinding {
+  /// Page relative to the audited directory.
+  pub file: PathBuf,
+  /// Failed check.
+  pub issue: PageIssue,
+  /// What was found, e.g. the image source or the failing color pair.
+  pub detail: String,
+}
+
+/// WCAG 2.1 check failed by a [`PageFinding`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PageIssue {
+  /// `<html>` has no `lang` attribute (3.1.1).
+  MissingLang,
+  /// The page has no non-empty `<title>` (2.4.2).
+  MissingTitle,
+  /// An `<img>` has no `alt` attribute (1.1.1); `alt=""` marks decorative images.
+  MissingAlt,
+  /// An injected theme color is below the contrast minimum against the page background
+  /// (1.4.3, 1.4.11).
+  LowContrast,
+}
+
+impl PageIssue {
+  /// Kebab-case name, e.g. `missing-lang`.
+  pub fn as_str(self) -> &'static str {
+    match self {
+      Self::MissingLang => "missing-lang",
+      Self::MissingTitle => "missing-title",
+      Self::MissingAlt => "missing-alt",
+      Self::LowContrast => "low-contrast",
+    }
+  }
+}
+
+impl fmt::Display for PageFinding {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "{}: {}: {}",
+      self.file.display(),
+      self.issue.as_str(),
+      self.detail
+    )
+  }
+}
+
+/// Alt-text coverage of one collection.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CollectionAccessibility {
+  /// Collection identifier.
+  pub collection_id: String,
+  /// Number of images across the collection's entries.
+  pub images: usize,
+  /// Number of those images without alternative text.
+  pub missing_alt: usize,
+  /// Entries with at least one image, in manifest order.
+  pub entries: Vec<EntryAccessibility>,
+}
+
+/// Alt-text coverage of one entry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EntryAccessibility {
+  /// Entry identifier.
+  pub entry_id: String,
+  /// Number of images in the entry body.
+  pub images: usize,
+  /// Sources of the images without alternative text.
+  pub missing_alt: Vec<String>,
+}
+
+impl AccessibilityReport {
+  /// Summarise the images recorded on `entries`.
+  pub fn from_entries(entries: &[OfflineEntryRecord]) -> Self {
+    let mut collections: Vec<CollectionAccessibility> = Vec::new();
+    for entry in entries.iter().filter(|entry| !entry.images.is_empty()) {
+      let missing_alt: Vec<String> = entry
+        .images
+        .iter()
+        .filter(|image| !image.has_alt())
+        .map(|image| image.src.clone())
+        .collect();
+      let index = match collections
+        .iter()
+        .position(|collection| collection.collection_id == entry.collection_id)
+      {
+        Some(index) => index,
+        None => {
+          collections.push(CollectionAccessibility {
+            collection_id: entry.collection_id.clone(),
+            images: 0,
+            missing_alt: 0,
+            entries: Vec::new(),
+          });
+          collections.len() - 1
+        }
+      };
+      let collection = &mut collections[index];
+      collection.images += entry.images.len();
+      collection.missing_alt += missing_alt.len();
+      collection.entries.push(EntryAccessibility {
+        entry_id: entry.entry_id.clone(),
+        images: entry.images.len(),
+        missing_alt,
+      });
+    }
+    collections.sort_by(|a, b| a.collection_id.cmp(&b.collection_id));
+    Self {
+      collections,
+      pages: Vec::new(),
+    }
+  }
+
+  /// Total number of images without alternative text.
+  pub fn missing_alt_count(&self) -> usize {
+    self
+      .collections
+      .iter()
+      .map(|collection| collection.missing_alt)
+      .sum()
+  }
+}
+
+/// Error returned by strict alt-text builds when images lack alternative text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingAltTextError {
+  /// Report listing the offending images.
+  pub report: AccessibilityReport,
+}
+
+impl fmt::Display for MissingAltTextError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "{} image(s) have no alt text:",
+      self.report.missing_alt_count()
+    )?;
+    for collection in &self.report.collections {
+      for entry in &collection.entries {
+        for src in &entry.missing_alt {
+          write!(
+            f,
+            "\n  {src} (entry {}/{})",
+            collection.collection_id, entry.entry_id
+          )?;
+        }
+      }
+    }
+    Ok(())
+  }
+}
+
+impl std::error::Error for MissingAltTextError {}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::models::ImageAltRecord;
+
+  fn entry(collection_id: &str, entry_id: &str, images: &[(&str, &str)]) -> OfflineEntryRecord {
+    OfflineEntryRecord {
+      collection_id: collection_id.into(),
+      entry_id: entry_id.into(),
+      body: String::new(),
+      asset_paths: Vec::new(),
+      word_count: 0,
+      reading_minutes: 0,
+      headings: Default::default(),
+      images: images
+        .iter()
+        .map(|(src, alt)| ImageAltRecord {
+          src: src.to_string(),
+          alt: alt.to_string(),
+        })
+        .collect(),
+      documents: Vec::new(),
+      priority_assets: Vec::new(),
+      video_posters: Default::default(),
+    }
+  }
+
+  #[test]
+  fn groups_missing_alt_text_by_collection_and_entry() {
+    let report = AccessibilityReport::from_entries(&[
+      entry("beta", "intro", &[("a.png", "Diagram"), ("b.png", " ")]),
+      entry("alpha", "setup", &[("c.png", "")]),
+      entry("alpha", "text-only", &[]),
+    ]);
+
+    assert_eq!(report.missing_alt_count(), 2);
+    assert_eq!(report.collections[0].collection_id, "alpha");
+    assert_eq!(report.collections[0].entries.len(), 1);
+    assert_eq!(report.collections[1].images, 2);
+    assert_eq!(report.collections[1].entries[0].missing_alt, ["b.png"]);
+    assert!(
+      MissingAltTextError { report }
+        .to_string()
+        .contains("c.png (entry alpha/setup)")
+    );
+  }
+}
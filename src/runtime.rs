@@ -0,0 +1,178 @@
+//! Lookups over the offline manifest for use inside the running app, including on wasm32.
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+use crate::models::{
+  BuildProvenance, COLLECTION_CATALOG_SCHEMA_VERSION, CollectionCatalog, CollectionCatalogRecord,
+  OfflineManifestSummary,
+};
+
+/// Catalog layouts accepted by [`parse_collection_catalog`].
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CatalogFormat {
+  Versioned(CollectionCatalog),
+  Legacy(Vec<CollectionCatalogRecord>),
+}
+
+/// Parse `collection_catalog_json` written by this or an earlier version of the crate.
+///
+/// Bare record arrays from before versioning are returned as schema version 1; catalogs newer
+/// than [`COLLECTION_CATALOG_SCHEMA_VERSION`] are rejected instead of being misread.
+pub fn parse_collection_catalog(json: &str) -> Result<CollectionCatalog, serde_json::Error> {
+  let catalog = match serde_json::from_str(json)? {
+    CatalogFormat::Versioned(catalog) => catalog,
+    CatalogFormat::Legacy(collections) => CollectionCatalog {
+      schema_version: 1,
+      language: None,
+      collections,
+    },
+  };
+  if catalog.schema_version > COLLECTION_CATALOG_SCHEMA_VERSION {
+    return Err(serde::de::Error::custom(format!(
+      "unsupported collection catalog schema version {} (newest supported is {})",
+      catalog.schema_version, COLLECTION_CATALOG_SCHEMA_VERSION
+    )));
+  }
+  Ok(catalog)
+}
+
+/// Indexed view of an [`OfflineManifestSummary`] for runtime queries.
+///
+/// Parse it from an embedded (`include_str!`) or fetched `offline_manifest.json` when the
+/// generated match functions are not enough, e.g. to list the entries of a collection.
+#[derive(Clone, Debug)]
+pub struct OfflineManifestIndex {
+  summary: OfflineManifestSummary,
+  entries: BTreeMap<(String, String), usize>,
+}
+
+impl OfflineManifestIndex {
+  /// Parse the JSON written to `offline_manifest.json`.
+  pub fn parse(json: &str) -> Result<Self, serde_json::Error> {
+    serde_json::from_str(json).map(Self::new)
+  }
+
+  /// Index an already deserialised manifest.
+  pub fn new(summary: OfflineManifestSummary) -> Self {
+    let entries = summary
+      .entries
+      .iter()
+      .enumerate()
+      .map(|(index, entry)| ((entry.collection_id.clone(), entry.entry_id.clone()), index))
+      .collect();
+    Self { summary, entries }
+  }
+
+  /// Underlying manifest summary.
+  pub fn summary(&self) -> &OfflineManifestSummary {
+    &self.summary
+  }
+
+  /// Provenance of the build that wrote the manifest; `None` before schema version 4.
+  pub fn provenance(&self) -> Option<&BuildProvenance> {
+    self.summary.provenance.as_ref()
+  }
+
+  /// Bundle paths of the assets referenced by an entry, or `None` for unknown entries.
+  pub fn entry_assets(&self, collection_id: &str, entry_id: &str) -> Option<&[String]> {
+    self
+      .entries
+      .get(&(collection_id.to_string(), entry_id.to_string()))
+      .map(|&index| self.summary.entries[index].asset_paths.as_slice())
+  }
+
+  /// Text of the heading an entry's `#anchor` points at, or `None` for broken fragments.
+  pub fn heading(&self, collection_id: &str, entry_id: &str, anchor: &str) -> Option<&str> {
+    self
+      .entries
+      .get(&(collection_id.to_string(), entry_id.to_string()))
+      .and_then(|&index| self.summary.entries[index].headings.get(anchor))
+      .map(String::as_str)
+  }
+
+  /// Bundle path of a collection's hero asset.
+  ///
+  /// Hero paths have the `<collections_dir_name>/<collection>/<path>` form, so the collection is
+  /// matched against the second path segment.
+  pub fn hero_asset(&self, collection_id: &str) -> Option<&str> {
+    self
+      .summary
+      .hero_assets
+      .iter()
+      .find(|path| path.split('/').nth(1) == Some(collection_id))
+      .map(String::as_str)
+  }
+
+  /// Entry identifiers of a collection in manifest order.
+  pub fn entry_ids_for_collection<'a>(
+    &'a self,
+    collection_id: &'a str,
+  ) -> impl Iterator<Item = &'a str> + 'a {
+    self
+      .summary
+      .entries
+      .iter()
+      .filter(move |entry| entry.collection_id == collection_id)
+      .map(|entry| entry.entry_id.as_str())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn answers_lookups_from_manifest_json() {
+    let index = OfflineManifestIndex::parse(
+      r#"{
+        "site_root": "site",
+        "entries": [
+          {"collection_id": "alpha", "entry_id": "intro", "asset_paths": ["programs/alpha/assets/a.png"], "headings": {"safety": "Safety"}},
+          {"collection_id": "beta", "entry_id": "setup", "asset_paths": []},
+          {"collection_id": "alpha", "entry_id": "advanced", "asset_paths": []}
+        ],
+        "hero_assets": ["programs/alpha/assets/cover.png", "programs/beta/assets/hero.jpg"]
+      }"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+      index.entry_assets("alpha", "intro"),
+      Some(&["programs/alpha/assets/a.png".to_string()][..])
+    );
+    assert_eq!(index.entry_assets("alpha", "missing"), None);
+    assert_eq!(index.heading("alpha", "intro", "safety"), Some("Safety"));
+    assert_eq!(index.heading("alpha", "intro", "setup"), None);
+    assert_eq!(
+      index.hero_asset("beta"),
+      Some("programs/beta/assets/hero.jpg")
+    );
+    assert_eq!(index.hero_asset("gamma"), None);
+    assert_eq!(
+      index.entry_ids_for_collection("alpha").collect::<Vec<_>>(),
+      ["intro", "advanced"]
+    );
+  }
+
+  #[test]
+  fn loads_current_and_legacy_catalogs() {
+    let record = r#"{"id":"alpha","meta":{"title":"Alpha","description":null,"version":null,"assetSlug":null,"heroImage":null},"entries":[]}"#;
+
+    let legacy = parse_collection_catalog(&format!("[{record}]")).unwrap();
+    assert_eq!(legacy.schema_version, 1);
+    assert_eq!(legacy.collections[0].id, "alpha");
+
+    let current = parse_collection_catalog(&format!(
+      r#"{{"schema_version":{COLLECTION_CATALOG_SCHEMA_VERSION},"collections":[{record}]}}"#
+    ))
+    .unwrap();
+    assert_eq!(current.schema_version, COLLECTION_CATALOG_SCHEMA_VERSION);
+    assert_eq!(current.collections[0].meta.title, "Alpha");
+
+    let newer = parse_collection_catalog(r#"{"schema_version":99,"collections":[]}"#);
+    assert!(newer.unwrap_err().to_string().contains("99"));
+  }
+}
@@ -1,27 +1,106 @@
 //! Helpers used to filter which collections are included in the offline bundle.
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 /// Trait describing selection filters for offline build content.
+///
+/// Besides [`CollectionSelection`], the trait is implemented for closures
+/// (`|id: &str| id.starts_with("P0")`), `BTreeSet<String>` include lists, trait objects behind
+/// `&dyn` and `Box<dyn>`, and the [`IncludeAll`]/[`IncludeNone`] markers.
+///
+/// There is no blanket implementation for references (`&S`): it would overlap the closure
+/// implementation, since `&F` is itself a closure. The builder and manifest functions take
+/// `&S` with `S: ?Sized` instead, so they accept a `&dyn CollectionInclusion` directly; only
+/// profile lists, which store selections by value, need `&dyn` or `Box<dyn>` elements.
 pub trait CollectionInclusion {
   /// Returns `true` when the collection should be included in the offline bundle.
   fn is_included(&self, collection_id: &str) -> bool;
+
+  /// Explain why a collection must be excluded because of its metadata `version`.
+  ///
+  /// Called for collections that passed [`is_included`](Self::is_included). The default
+  /// implementation accepts every version.
+  fn version_rejection(&self, _collection_id: &str, _version: Option<&str>) -> Option<String> {
+    None
+  }
+}
+
+/// Selection that includes every collection.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IncludeAll;
+
+/// Selection that excludes every collection.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IncludeNone;
+
+impl CollectionInclusion for IncludeAll {
+  fn is_included(&self, _collection_id: &str) -> bool {
+    true
+  }
+}
+
+impl CollectionInclusion for IncludeNone {
+  fn is_included(&self, _collection_id: &str) -> bool {
+    false
+  }
+}
+
+impl<F> CollectionInclusion for F
+where
+  F: Fn(&str) -> bool,
+{
+  fn is_included(&self, collection_id: &str) -> bool {
+    self(collection_id)
+  }
+}
+
+impl CollectionInclusion for &dyn CollectionInclusion {
+  fn is_included(&self, collection_id: &str) -> bool {
+    (**self).is_included(collection_id)
+  }
+
+  fn version_rejection(&self, collection_id: &str, version: Option<&str>) -> Option<String> {
+    (**self).version_rejection(collection_id, version)
+  }
+}
+
+impl CollectionInclusion for Box<dyn CollectionInclusion + '_> {
+  fn is_included(&self, collection_id: &str) -> bool {
+    (**self).is_included(collection_id)
+  }
+
+  fn version_rejection(&self, collection_id: &str, version: Option<&str>) -> Option<String> {
+    (**self).version_rejection(collection_id, version)
+  }
+}
+
+/// Treats the set as an include list with the same hierarchical scoping as selection files.
+impl CollectionInclusion for BTreeSet<String> {
+  fn is_included(&self, collection_id: &str) -> bool {
+    self.iter().any(|value| scope_matches(value, collection_id))
+  }
 }
 
 /// Default selection file name searched for in collection directories.
 pub const DEFAULT_SELECTION_FILE: &str = "collections.local.json";
 
 /// Configuration file layout for selecting which collections to compile.
-#[derive(Debug, Default, Deserialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
 struct CollectionSelectionFile {
-  #[serde(default)]
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
   include: Vec<String>,
-  #[serde(default)]
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
   exclude: Vec<String>,
+  #[serde(
+    default,
+    rename = "minVersion",
+    skip_serializing_if = "BTreeMap::is_empty"
+  )]
+  min_version: BTreeMap<String, String>,
 }
 
 /// Selection helper allowing build-time filtering of authored collections.
@@ -29,6 +108,7 @@ struct CollectionSelectionFile {
 pub struct CollectionSelection {
   include: Option<BTreeSet<String>>,
   exclude: BTreeSet<String>,
+  min_version: BTreeMap<String, String>,
 }
 
 /// Errors that can occur while loading the selection configuration.
@@ -48,10 +128,36 @@ pub enum CollectionSelectionError {
     /// Source parse error.
     source: serde_json::Error,
   },
+  /// Failed to parse a TOML selection file.
+  ParseToml {
+    /// Path that caused the error.
+    path: PathBuf,
+    /// Source parse error.
+    source: toml::de::Error,
+  },
+  /// Failed to parse a YAML selection file.
+  ParseYaml {
+    /// Path that caused the error.
+    path: PathBuf,
+    /// Source parse error.
+    source: serde_yaml::Error,
+  },
+  /// A `minVersion` entry is not a valid semver requirement.
+  VersionRequirement {
+    /// Path that caused the error.
+    path: PathBuf,
+    /// Collection the requirement applies to.
+    collection_id: String,
+    /// Source parse error.
+    source: semver::Error,
+  },
 }
 
 impl CollectionSelection {
   /// Load configuration from the selection file if present.
+  ///
+  /// The format is detected from the extension: `.toml`, `.yaml`/`.yml`, and JSON for anything
+  /// else. TOML and YAML files may carry comments explaining why collections are excluded.
   pub fn load_from_path(path: impl AsRef<Path>) -> Result<Self, CollectionSelectionError> {
     let path = path.as_ref();
     let contents = match fs::read_to_string(path) {
@@ -67,14 +173,92 @@ impl CollectionSelection {
       }
     };
 
-    let file: CollectionSelectionFile =
-      serde_json::from_str(&contents).map_err(|err| CollectionSelectionError::Parse {
+    let extension = path
+      .extension()
+      .map(|value| value.to_string_lossy().to_ascii_lowercase());
+    let file: CollectionSelectionFile = match extension.as_deref() {
+      Some("toml") => {
+        toml::from_str(&contents).map_err(|err| CollectionSelectionError::ParseToml {
+          path: path.to_path_buf(),
+          source: err,
+        })?
+      }
+      Some("yaml" | "yml") => {
+        serde_yaml::from_str(&contents).map_err(|err| CollectionSelectionError::ParseYaml {
+          path: path.to_path_buf(),
+          source: err,
+        })?
+      }
+      _ => serde_json::from_str(&contents).map_err(|err| CollectionSelectionError::Parse {
         path: path.to_path_buf(),
         source: err,
+      })?,
+    };
+    for (collection_id, requirement) in &file.min_version {
+      semver::VersionReq::parse(requirement).map_err(|err| {
+        CollectionSelectionError::VersionRequirement {
+          path: path.to_path_buf(),
+          collection_id: collection_id.clone(),
+          source: err,
+        }
       })?;
+    }
     Ok(Self::from(file))
   }
 
+  /// Load and merge several selection files in order, see [`CollectionSelection::merge`].
+  ///
+  /// Missing files are skipped so optional developer overlays can be listed unconditionally.
+  pub fn load_all<I, P>(paths: I) -> Result<Self, CollectionSelectionError>
+  where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+  {
+    paths
+      .into_iter()
+      .try_fold(Self::default(), |selection, path| {
+        Ok(selection.merge(Self::load_from_path(path)?))
+      })
+  }
+
+  /// Layer `other` on top of this selection.
+  ///
+  /// Later layers take precedence: a non-empty `include` list in `other` replaces the current
+  /// include list, exclusions accumulate across layers, and an identifier explicitly included
+  /// by `other` lifts an identical exclusion inherited from earlier layers. Exclusions in
+  /// `other` therefore always win over anything included before.
+  pub fn merge(mut self, other: CollectionSelection) -> Self {
+    if let Some(include) = other.include {
+      self.exclude.retain(|value| !include.contains(value));
+      self.include = Some(include);
+    }
+    self.exclude.extend(other.exclude);
+    self.min_version.extend(other.min_version);
+    self
+  }
+
+  /// Check a collection version against the `minVersion` requirement for its identifier.
+  ///
+  /// Versions are parsed leniently: a leading `v` is ignored and missing minor/patch
+  /// components default to zero, so `2.1` is treated as `2.1.0`. Collections without a
+  /// parseable version never satisfy a requirement.
+  pub fn version_rejection(&self, collection_id: &str, version: Option<&str>) -> Option<String> {
+    let requirement = self.min_version.get(collection_id)?;
+    let Ok(parsed_requirement) = semver::VersionReq::parse(requirement) else {
+      return Some(format!("invalid version requirement `{requirement}`"));
+    };
+    let Some(version) = version else {
+      return Some(format!("requires version {requirement} but declares none"));
+    };
+    match parse_lenient_version(version) {
+      Some(parsed) if parsed_requirement.matches(&parsed) => None,
+      Some(_) => Some(format!("version {version} does not satisfy {requirement}")),
+      None => Some(format!(
+        "version `{version}` is not valid semver (requires {requirement})"
+      )),
+    }
+  }
+
   /// Determine whether a collection should be compiled into the bundle.
   pub fn is_included(&self, collection_id: &str) -> bool {
     if self
@@ -93,6 +277,41 @@ impl CollectionSelection {
     }
   }
 
+  /// Selection that includes exactly the `selected` identifiers out of `all`, keeping the
+  /// `minVersion` requirements of this selection.
+  ///
+  /// Unselected collections nested below a selected one, or every unselected collection when
+  /// nothing is selected, are written as exclusions since an include list cannot express them.
+  pub fn with_selected<'a>(
+    &self,
+    all: impl IntoIterator<Item = &'a str>,
+    selected: &BTreeSet<String>,
+  ) -> Self {
+    let exclude = all
+      .into_iter()
+      .filter(|id| !selected.contains(*id))
+      .filter(|id| selected.is_empty() || selected.iter().any(|rule| scope_matches(rule, id)))
+      .map(str::to_string)
+      .collect();
+    Self {
+      include: (!selected.is_empty()).then(|| selected.clone()),
+      exclude,
+      min_version: self.min_version.clone(),
+    }
+  }
+
+  /// Render the selection as JSON in the format read by [`CollectionSelection::load_from_path`].
+  pub fn to_json(&self) -> String {
+    let file = CollectionSelectionFile {
+      include: self.include.iter().flatten().cloned().collect(),
+      exclude: self.exclude.iter().cloned().collect(),
+      min_version: self.min_version.clone(),
+    };
+    let mut json = serde_json::to_string_pretty(&file).expect("selection serialises to JSON");
+    json.push('\n');
+    json
+  }
+
   /// Returns true when no filtering rules are active.
   #[cfg(test)]
   fn is_unfiltered(&self) -> bool {
@@ -104,6 +323,10 @@ impl CollectionInclusion for CollectionSelection {
   fn is_included(&self, collection_id: &str) -> bool {
     CollectionSelection::is_included(self, collection_id)
   }
+
+  fn version_rejection(&self, collection_id: &str, version: Option<&str>) -> Option<String> {
+    CollectionSelection::version_rejection(self, collection_id, version)
+  }
 }
 
 impl From<CollectionSelectionFile> for CollectionSelection {
@@ -111,9 +334,17 @@ impl From<CollectionSelectionFile> for CollectionSelection {
     let include = normalise_list(file.include);
     let exclude = normalise_list(file.exclude);
 
+    let min_version = file
+      .min_version
+      .into_iter()
+      .map(|(id, requirement)| (id.trim().trim_matches('/').to_string(), requirement))
+      .filter(|(id, _)| !id.is_empty())
+      .collect();
+
     Self {
       include: (!include.is_empty()).then_some(include),
       exclude,
+      min_version,
     }
   }
 }
@@ -127,6 +358,23 @@ impl std::fmt::Display for CollectionSelectionError {
       Self::Parse { path, source } => {
         write!(f, "failed to parse {}: {}", path.display(), source)
       }
+      Self::ParseToml { path, source } => {
+        write!(f, "failed to parse {}: {}", path.display(), source)
+      }
+      Self::ParseYaml { path, source } => {
+        write!(f, "failed to parse {}: {}", path.display(), source)
+      }
+      Self::VersionRequirement {
+        path,
+        collection_id,
+        source,
+      } => write!(
+        f,
+        "invalid minVersion for {} in {}: {}",
+        collection_id,
+        path.display(),
+        source
+      ),
     }
   }
 }
@@ -136,6 +384,9 @@ impl std::error::Error for CollectionSelectionError {
     match self {
       Self::Io { source, .. } => Some(source),
       Self::Parse { source, .. } => Some(source),
+      Self::ParseToml { source, .. } => Some(source),
+      Self::ParseYaml { source, .. } => Some(source),
+      Self::VersionRequirement { source, .. } => Some(source),
     }
   }
 }
@@ -151,6 +402,28 @@ fn normalise_list(values: impl IntoIterator<Item = String>) -> BTreeSet<String>
     .collect()
 }
 
+/// Parse `value` as semver, ignoring a leading `v` and defaulting missing minor/patch
+/// components to zero.
+pub(crate) fn parse_lenient_version(value: &str) -> Option<semver::Version> {
+  let trimmed = value.trim().trim_start_matches(['v', 'V']);
+  if let Ok(version) = semver::Version::parse(trimmed) {
+    return Some(version);
+  }
+
+  let (core, suffix) = match trimmed.find(['-', '+']) {
+    Some(index) => trimmed.split_at(index),
+    None => (trimmed, ""),
+  };
+  let mut parts: Vec<&str> = core.split('.').collect();
+  if parts.len() > 3 {
+    return None;
+  }
+  while parts.len() < 3 {
+    parts.push("0");
+  }
+  semver::Version::parse(&format!("{}{}", parts.join("."), suffix)).ok()
+}
+
 fn scope_matches(rule: &str, candidate: &str) -> bool {
   if candidate == rule {
     return true;
@@ -178,6 +451,7 @@ mod tests {
     let selection = CollectionSelection::from(CollectionSelectionFile {
       include: Vec::new(),
       exclude: vec!["P001".into(), String::new(), " P002 ".into()],
+      ..Default::default()
     });
 
     assert!(!selection.is_included("P001"));
@@ -190,6 +464,7 @@ mod tests {
     let selection = CollectionSelection::from(CollectionSelectionFile {
       include: Vec::new(),
       exclude: vec!["P001".into()],
+      ..Default::default()
     });
 
     assert!(!selection.is_included("P001"));
@@ -201,6 +476,7 @@ mod tests {
     let selection = CollectionSelection::from(CollectionSelectionFile {
       include: vec!["P001".into()],
       exclude: Vec::new(),
+      ..Default::default()
     });
 
     assert!(selection.is_included("P001"));
@@ -213,6 +489,7 @@ mod tests {
     let selection = CollectionSelection::from(CollectionSelectionFile {
       include: vec!["P001/module-a".into()],
       exclude: vec!["P001/module-a/draft".into()],
+      ..Default::default()
     });
 
     assert!(!selection.is_included("P001"));
@@ -225,6 +502,7 @@ mod tests {
     let selection = CollectionSelection::from(CollectionSelectionFile {
       include: vec!["A".into(), "B".into()],
       exclude: vec!["B".into(), "C".into()],
+      ..Default::default()
     });
 
     assert!(selection.is_included("A"));
@@ -252,6 +530,79 @@ mod tests {
     ]);
   }
 
+  fn includes<S: CollectionInclusion + ?Sized>(selection: &S, collection_id: &str) -> bool {
+    selection.is_included(collection_id)
+  }
+
+  #[test]
+  fn utility_inclusions_filter_collections() {
+    assert!(includes(&IncludeAll, "P001"));
+    assert!(!includes(&IncludeNone, "P001"));
+    assert!(includes(&|id: &str| id.starts_with("P0"), "P001"));
+    assert!(!includes(&|id: &str| id.starts_with("P0"), "X001"));
+
+    let set = BTreeSet::from(["P001".to_string()]);
+    assert!(includes(&set, "P001/module-a"));
+    assert!(!includes(&set, "P002"));
+
+    let boxed: Box<dyn CollectionInclusion> = Box::new(set);
+    assert!(includes(&boxed, "P001"));
+    let borrowed: &dyn CollectionInclusion = &CollectionSelection::default();
+    assert!(includes(&borrowed, "anything"));
+
+    let prefix = String::from("P0");
+    let scoped: Box<dyn CollectionInclusion + '_> = Box::new(|id: &str| id.starts_with(&*prefix));
+    assert!(includes(&scoped, "P001"));
+    assert!(includes(borrowed, "anything"));
+  }
+
+  #[test]
+  fn merge_applies_later_layers_on_top() {
+    let base = CollectionSelection::from(CollectionSelectionFile {
+      include: vec!["A".into(), "B".into()],
+      exclude: vec!["C".into(), "D".into()],
+      ..Default::default()
+    });
+    let overlay = CollectionSelection::from(CollectionSelectionFile {
+      include: vec!["A".into(), "D".into()],
+      exclude: vec!["A/drafts".into()],
+      ..Default::default()
+    });
+
+    let merged = base.clone().merge(overlay);
+    assert!(merged.is_included("A"));
+    assert!(!merged.is_included("A/drafts"));
+    assert!(!merged.is_included("B"));
+    assert!(!merged.is_included("C"));
+    assert!(merged.is_included("D"));
+
+    let excludes_only = CollectionSelection::from(CollectionSelectionFile {
+      include: Vec::new(),
+      exclude: vec!["A".into()],
+      ..Default::default()
+    });
+    let merged = base.merge(excludes_only);
+    assert!(!merged.is_included("A"));
+    assert!(merged.is_included("B"));
+  }
+
+  #[test]
+  fn load_all_merges_files_in_order() {
+    let temp = tempdir().expect("failed to create temp dir");
+    let team = temp.path().join("collections.team.json");
+    let local = temp.path().join("collections.local.yaml");
+    std::fs::write(&team, r#"{"include": ["A", "B"]}"#).expect("failed to write team file");
+    std::fs::write(&local, "exclude: [B]\n").expect("failed to write local file");
+
+    let selection =
+      CollectionSelection::load_all([&team, &local, &temp.path().join("missing.json")])
+        .expect("selections should load");
+
+    assert!(selection.is_included("A"));
+    assert!(!selection.is_included("B"));
+    assert!(!selection.is_included("C"));
+  }
+
   #[test]
   fn load_from_path_returns_default_for_missing_file() {
     let temp = tempdir().expect("failed to create temp dir");
@@ -282,4 +633,63 @@ mod tests {
     assert!(!selection.is_included("C"));
     assert!(!selection.is_included("missing"));
   }
+
+  #[test]
+  fn load_from_path_detects_toml_and_yaml() {
+    let temp = tempdir().expect("failed to create temp dir");
+
+    let toml_path = temp.path().join("collections.local.toml");
+    std::fs::write(
+      &toml_path,
+      "# demo content is not approved yet\ninclude = [\"A\", \"B\"]\nexclude = [\"B\"]\n",
+    )
+    .expect("failed to write selection file");
+    let selection = CollectionSelection::load_from_path(&toml_path).expect("toml should load");
+    assert!(selection.is_included("A"));
+    assert!(!selection.is_included("B"));
+
+    let yaml_path = temp.path().join("collections.local.yml");
+    std::fs::write(&yaml_path, "# pilot build\nexclude:\n  - C\n")
+      .expect("failed to write selection file");
+    let selection = CollectionSelection::load_from_path(&yaml_path).expect("yaml should load");
+    assert!(selection.is_included("A"));
+    assert!(!selection.is_included("C"));
+
+    let broken_path = temp.path().join("broken.toml");
+    std::fs::write(&broken_path, "include = [").expect("failed to write selection file");
+    assert!(matches!(
+      CollectionSelection::load_from_path(&broken_path),
+      Err(CollectionSelectionError::ParseToml { .. })
+    ));
+  }
+
+  #[test]
+  fn rejects_collections_failing_version_requirements() {
+    let selection = CollectionSelection::from(CollectionSelectionFile {
+      min_version: BTreeMap::from([("P001".into(), ">=2.0".into())]),
+      ..Default::default()
+    });
+
+    assert_eq!(selection.version_rejection("P001", Some("2.1")), None);
+    assert_eq!(selection.version_rejection("P001", Some("v2.0.0")), None);
+    assert_eq!(
+      selection.version_rejection("P001", Some("1.9")),
+      Some("version 1.9 does not satisfy >=2.0".into())
+    );
+    assert!(selection.version_rejection("P001", None).is_some());
+    assert_eq!(selection.version_rejection("P002", None), None);
+  }
+
+  #[test]
+  fn load_from_path_rejects_invalid_version_requirements() {
+    let temp = tempdir().expect("failed to create temp dir");
+    let path = temp.path().join("collections.local.json");
+    std::fs::write(&path, r#"{"minVersion": {"P001": "at least two"}}"#)
+      .expect("failed to write selection file");
+
+    assert!(matches!(
+      CollectionSelection::load_from_path(&path),
+      Err(CollectionSelectionError::VersionRequirement { .. })
+    ));
+  }
 }
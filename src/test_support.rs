@@ -0,0 +1,291 @@
+//! Synthetic content trees for tests, enabled with the `test-fixtures` feature.
+//!
+//! [`ProjectFixture`] describes collections, entries, assets and metadata and writes them into
+//! a temporary directory laid out according to an [`OfflineProjectLayout`]. The returned
+//! [`FixtureProject`] keeps the directory alive and hands out the matching layout and build
+//! context:
+//!
+//! ```ignore
+//! let project = ProjectFixture::new()
+//!   .collection(
+//!     CollectionFixture::new("P001")
+//!       .title("Basics")
+//!       .asset("cover.png", "png")
+//!       .entry(EntryFixture::new("001-intro").title("Intro").body("![Cover](cover.png)")),
+//!   )
+//!   .write()?;
+//! let builder = OfflineBuilder::new(project.context()?);
+//! ```
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde_json::{Map, Value};
+use tempfile::TempDir;
+
+use crate::config::ConfigValidationError;
+use crate::project::{OfflineBuildContext, OfflineProjectLayout};
+
+/// Entry directory with its markdown file, front matter and assets.
+#[derive(Debug, Clone)]
+pub struct EntryFixture {
+  id: String,
+  frontmatter: Map<String, Value>,
+  body: String,
+  assets: Vec<(String, Vec<u8>)>,
+}
+
+impl EntryFixture {
+  /// Entry in the directory `id`, e.g. `001-intro`, with an empty body.
+  pub fn new(id: impl Into<String>) -> Self {
+    Self {
+      id: id.into(),
+      frontmatter: Map::new(),
+      body: String::new(),
+      assets: Vec::new(),
+    }
+  }
+
+  /// Set the `title` front matter key.
+  pub fn title(self, title: impl Into<String>) -> Self {
+    self.frontmatter("title", title.into())
+  }
+
+  /// Set a front matter key.
+  pub fn frontmatter(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+    self.frontmatter.insert(key.into(), value.into());
+    self
+  }
+
+  /// Set the markdown body written after the front matter.
+  pub fn body(mut self, body: impl Into<String>) -> Self {
+    self.body = body.into();
+    self
+  }
+
+  /// Add a file below the entry's assets directory; `path` is relative to it.
+  pub fn asset(mut self, path: impl Into<String>, contents: impl Into<Vec<u8>>) -> Self {
+    self.assets.push((path.into(), contents.into()));
+    self
+  }
+
+  fn markdown(&self) -> io::Result<String> {
+    if self.frontmatter.is_empty() {
+      return Ok(self.body.clone());
+    }
+    let frontmatter =
+      serde_yaml::to_string(&self.frontmatter).map_err(|err| io::Error::other(err.to_string()))?;
+    Ok(format!("---\n{frontmatter}---\n{}", self.body))
+  }
+}
+
+/// Collection directory with its metadata document, entries and shared assets.
+#[derive(Debug, Clone)]
+pub struct CollectionFixture {
+  id: String,
+  metadata: Map<String, Value>,
+  entries: Vec<EntryFixture>,
+  assets: Vec<(String, Vec<u8>)>,
+}
+
+impl CollectionFixture {
+  /// Collection in the directory `id` titled after it.
+  pub fn new(id: impl Into<String>) -> Self {
+    let id = id.into();
+    let mut metadata = Map::new();
+    metadata.insert("title".into(), Value::String(id.clone()));
+    Self {
+      id,
+      metadata,
+      entries: Vec::new(),
+      assets: Vec::new(),
+    }
+  }
+
+  /// Set the `title` metadata key.
+  pub fn title(self, title: impl Into<String>) -> Self {
+    self.metadata("title", title.into())
+  }
+
+  /// Set a key of the collection metadata document, e.g. `assetSlug` or `heroImage`.
+  pub fn metadata(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+    self.metadata.insert(key.into(), value.into());
+    self
+  }
+
+  /// Add an entry.
+  pub fn entry(mut self, entry: EntryFixture) -> Self {
+    self.entries.push(entry);
+    self
+  }
+
+  /// Add a file below the collection's assets directory; `path` is relative to it.
+  pub fn asset(mut self, path: impl Into<String>, contents: impl Into<Vec<u8>>) -> Self {
+    self.assets.push((path.into(), contents.into()));
+    self
+  }
+
+  fn write(&self, layout: &OfflineProjectLayout, collections_dir: &Path) -> io::Result<()> {
+    let dir = collections_dir.join(&self.id);
+    write_file(
+      &dir.join(&layout.collection_metadata_file),
+      serde_json::to_string_pretty(&self.metadata)?,
+    )?;
+    for (path, contents) in &self.assets {
+      write_file(&dir.join(&layout.entry_assets_dir).join(path), contents)?;
+    }
+    for entry in &self.entries {
+      let entry_dir = dir.join(&entry.id);
+      write_file(
+        &entry_dir.join(&layout.entry_markdown_file),
+        entry.markdown()?,
+      )?;
+      for (path, contents) in &entry.assets {
+        write_file(
+          &entry_dir.join(&layout.entry_assets_dir).join(path),
+          contents,
+        )?;
+      }
+    }
+    Ok(())
+  }
+}
+
+/// Description of a project tree written by [`write`](Self::write).
+#[derive(Debug, Clone, Default)]
+pub struct ProjectFixture {
+  layout: OfflineProjectLayout,
+  collections: Vec<CollectionFixture>,
+  files: Vec<(String, Vec<u8>)>,
+}
+
+impl ProjectFixture {
+  /// Empty project using the default layout.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Lay the tree out according to `layout` instead of the default.
+  pub fn layout(mut self, layout: OfflineProjectLayout) -> Self {
+    self.layout = layout;
+    self
+  }
+
+  /// Add a collection below the collections directory.
+  pub fn collection(mut self, collection: CollectionFixture) -> Self {
+    self.collections.push(collection);
+    self
+  }
+
+  /// Add an arbitrary file, e.g. `offline.toml` or a selection file; `path` is relative to the
+  /// project root.
+  pub fn file(mut self, path: impl Into<String>, contents: impl Into<Vec<u8>>) -> Self {
+    self.files.push((path.into(), contents.into()));
+    self
+  }
+
+  /// Write the tree into a new temporary directory.
+  pub fn write(self) -> io::Result<FixtureProject> {
+    let dir = tempfile::tempdir()?;
+    let collections_dir = dir.path().join(&self.layout.collections_dir_name);
+    fs::create_dir_all(&collections_dir)?;
+    for collection in &self.collections {
+      collection.write(&self.layout, &collections_dir)?;
+    }
+    for (path, contents) in &self.files {
+      write_file(&dir.path().join(path), contents)?;
+    }
+    Ok(FixtureProject {
+      dir,
+      layout: self.layout,
+      collections_dir,
+    })
+  }
+}
+
+/// Project tree on disk, removed when dropped.
+#[derive(Debug)]
+pub struct FixtureProject {
+  dir: TempDir,
+  layout: OfflineProjectLayout,
+  collections_dir: PathBuf,
+}
+
+impl FixtureProject {
+  /// Project root, used as the manifest directory.
+  pub fn root(&self) -> &Path {
+    self.dir.path()
+  }
+
+  /// Directory holding the collections.
+  pub fn collections_dir(&self) -> &Path {
+    &self.collections_dir
+  }
+
+  /// Layout the tree was written with.
+  pub fn layout(&self) -> &OfflineProjectLayout {
+    &self.layout
+  }
+
+  /// Build context for the project with the default asset mirror and selection path.
+  pub fn context(&self) -> Result<OfflineBuildContext<'_>, ConfigValidationError> {
+    OfflineBuildContext::builder()
+      .layout(self.layout.clone())
+      .manifest_dir(self.root())
+      .collections_dir(&self.collections_dir)
+      .build()
+  }
+
+  /// Write or overwrite a file relative to the project root after the tree was created.
+  pub fn write_file(&self, path: &str, contents: impl AsRef<[u8]>) -> io::Result<PathBuf> {
+    let path = self.root().join(path);
+    write_file(&path, contents)?;
+    Ok(path)
+  }
+}
+
+fn write_file(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+  if let Some(parent) = path.parent() {
+    fs::create_dir_all(parent)?;
+  }
+  fs::write(path, contents)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::manifest::generate_offline_manifest;
+  use crate::{IncludeAll, OfflineBuilder};
+
+  #[test]
+  fn written_tree_builds_with_the_matching_layout() -> crate::BuildResult<()> {
+    let project = ProjectFixture::new()
+      .collection(
+        CollectionFixture::new("P001")
+          .title("Basics")
+          .metadata("heroImage", "/assets/cover.png")
+          .asset("cover.png", "png")
+          .entry(
+            EntryFixture::new("001-intro")
+              .title("Intro")
+              .body("![Diagram](diagram.svg)\n")
+              .asset("diagram.svg", "<svg/>"),
+          ),
+      )
+      .write()?;
+
+    let manifest =
+      generate_offline_manifest(project.layout(), project.collections_dir(), &IncludeAll)?;
+    assert_eq!(manifest.collection_catalog[0].meta.title, "Basics");
+    assert_eq!(manifest.collection_catalog[0].entries[0].title, "Intro");
+    for path in ["assets/cover.png", "001-intro/assets/diagram.svg"] {
+      let key = ("P001".to_string(), path.to_string());
+      assert!(manifest.asset_map.contains_key(&key), "missing {path}");
+    }
+
+    let artifacts = OfflineBuilder::new(project.context()?).build(&IncludeAll)?;
+    assert_eq!(artifacts.report.assets.files, manifest.asset_map.len());
+    Ok(())
+  }
+}
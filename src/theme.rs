@@ -0,0 +1,118 @@
+//! Brand palette injected into the offline site and launcher pages as CSS custom properties.
+//!
+//! Deployments rebrand the bundle by defining colors in the project configuration instead of
+//! forking the site stylesheet: every color becomes a `--<name>` variable on `:root`, with an
+//! optional override block for `prefers-color-scheme: dark`.
+
+/// Colors and logo emitted as CSS custom properties.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ThemePalette {
+  /// `(name, value)` pairs emitted as `--<name>: <value>` on `:root`.
+  pub colors: Vec<(String, String)>,
+  /// Overrides applied when the user prefers a dark color scheme.
+  pub dark_colors: Vec<(String, String)>,
+  /// Logo path relative to the site root, exposed as `--logo: url(...)`.
+  pub logo: Option<String>,
+}
+
+impl ThemePalette {
+  /// Returns true when the palette defines nothing.
+  pub fn is_empty(&self) -> bool {
+    self.colors.is_empty() && self.dark_colors.is_empty() && self.logo.is_none()
+  }
+
+  /// Render the palette as a stylesheet.
+  ///
+  /// `asset_prefix` is prepended to the logo path, e.g. `site/` for pages in the bundle root.
+  pub fn stylesheet(&self, asset_prefix: &str) -> String {
+    let mut declarations: Vec<String> = self
+      .colors
+      .iter()
+      .map(|(name, value)| format!("--{name}:{value};"))
+      .collect();
+    if let Some(logo) = &self.logo {
+      declarations.push(format!(
+        "--logo:url(\"{}{}\");",
+        asset_prefix,
+        logo.trim_start_matches('/').replace('"', "%22")
+      ));
+    }
+
+    let mut css = String::new();
+    if !declarations.is_empty() {
+      css.push_str(&format!(":root{{{}}}", declarations.concat()));
+    }
+    if !self.dark_colors.is_empty() {
+      let dark: String = self
+        .dark_colors
+        .iter()
+        .map(|(name, value)| format!("--{name}:{value};"))
+        .collect();
+      css.push_str(&format!(
+        "@media (prefers-color-scheme: dark){{:root{{{dark}}}}}"
+      ));
+    }
+    css
+  }
+}
+
+/// Parse comma-separated `name=value` pairs, ignoring commas inside parentheses so values such
+/// as `rgb(10, 20, 30)` stay intact. Returns `None` for malformed input.
+pub(crate) fn parse_colors(value: &str) -> Option<Vec<(String, String)>> {
+  let mut pairs = Vec::new();
+  let mut depth = 0usize;
+  let mut start = 0;
+  let mut segments = Vec::new();
+  for (index, c) in value.char_indices() {
+    match c {
+      '(' => depth += 1,
+      ')' => depth = depth.saturating_sub(1),
+      ',' if depth == 0 => {
+        segments.push(&value[start..index]);
+        start = index + 1;
+      }
+      _ => {}
+    }
+  }
+  segments.push(&value[start..]);
+
+  for segment in segments
+    .into_iter()
+    .map(str::trim)
+    .filter(|s| !s.is_empty())
+  {
+    let (name, color) = segment.split_once('=')?;
+    let name = name.trim().trim_start_matches("--");
+    let color = color.trim();
+    let valid_name = !name.is_empty()
+      && name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid_name || color.is_empty() || color.contains([';', '{', '}', '<', '>']) {
+      return None;
+    }
+    pairs.push((name.to_string(), color.to_string()));
+  }
+  Some(pairs)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn renders_light_and_dark_variables() {
+    let palette = ThemePalette {
+      colors: parse_colors("brand=#0a84ff, surface=rgb(250, 250, 250)").unwrap(),
+      dark_colors: parse_colors("surface=#111").unwrap(),
+      logo: Some("branding/logo.svg".into()),
+    };
+    assert_eq!(
+      palette.stylesheet("site/"),
+      ":root{--brand:#0a84ff;--surface:rgb(250, 250, 250);--logo:url(\"site/branding/logo.svg\");}\
+@media (prefers-color-scheme: dark){:root{--surface:#111;}}"
+    );
+    assert!(parse_colors("brand=red;}body{").is_none());
+    assert!(parse_colors("brand").is_none());
+  }
+}
@@ -0,0 +1,260 @@
+//! Terminal editor for the collection selection file, enabled with the `tui` feature.
+//!
+//! [`edit_selection_file`] lists the discovered collections with their titles, lets the user
+//! toggle them and writes a `collections.local.json` that
+//! [`CollectionSelection::load_from_path`] reads back.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{self, ClearType};
+use crossterm::{cursor, execute, queue};
+
+use crate::manifest::discover_collections;
+use crate::models::CollectionSummaryRecord;
+use crate::project::OfflineProjectLayout;
+use crate::selection::{CollectionSelection, CollectionSelectionError};
+
+/// Key bindings shown below the collection list.
+const HELP: &str = "↑/↓ move  space toggle  a all  n none  enter save  q cancel";
+
+/// State of the selection editor, independent of the terminal.
+#[derive(Debug, Clone)]
+pub struct SelectionEditor {
+  collections: Vec<CollectionSummaryRecord>,
+  included: Vec<bool>,
+  cursor: usize,
+}
+
+impl SelectionEditor {
+  /// Start editing with the collections `selection` currently includes ticked.
+  pub fn new(collections: Vec<CollectionSummaryRecord>, selection: &CollectionSelection) -> Self {
+    let included = collections
+      .iter()
+      .map(|record| selection.is_included(&record.id))
+      .collect();
+    Self {
+      collections,
+      included,
+      cursor: 0,
+    }
+  }
+
+  /// Move the cursor by `delta` rows, stopping at the first and last collection.
+  pub fn move_cursor(&mut self, delta: isize) {
+    let last = self.collections.len().saturating_sub(1);
+    self.cursor = self.cursor.saturating_add_signed(delta).min(last);
+  }
+
+  /// Toggle the collection under the cursor.
+  pub fn toggle(&mut self) {
+    if let Some(included) = self.included.get_mut(self.cursor) {
+      *included = !*included;
+    }
+  }
+
+  /// Tick or untick every collection.
+  pub fn set_all(&mut self, included: bool) {
+    self.included.fill(included);
+  }
+
+  /// One display row per collection: cursor marker, checkbox, id, title and entry count.
+  pub fn lines(&self) -> Vec<String> {
+    let id_width = self
+      .collections
+      .iter()
+      .map(|record| record.id.chars().count())
+      .max()
+      .unwrap_or(0);
+    self
+      .collections
+      .iter()
+      .zip(&self.included)
+      .enumerate()
+      .map(|(index, (record, included))| {
+        format!(
+          "{} [{}] {:id_width$}  {} ({} entries)",
+          if index == self.cursor { '>' } else { ' ' },
+          if *included { 'x' } else { ' ' },
+          record.id,
+          record.meta.title,
+          record.entry_count,
+        )
+      })
+      .collect()
+  }
+
+  /// Selection with the ticked collections, keeping the `minVersion` requirements of `base`.
+  pub fn selection(&self, base: &CollectionSelection) -> CollectionSelection {
+    let selected: BTreeSet<String> = self
+      .collections
+      .iter()
+      .zip(&self.included)
+      .filter(|(_, included)| **included)
+      .map(|(record, _)| record.id.clone())
+      .collect();
+    base.with_selected(
+      self.collections.iter().map(|record| record.id.as_str()),
+      &selected,
+    )
+  }
+
+  /// Run the editor on the terminal until the user saves (`Some`) or cancels (`None`).
+  pub fn run(mut self, base: &CollectionSelection) -> io::Result<Option<CollectionSelection>> {
+    let mut stdout = io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
+    let result = self.event_loop(&mut stdout, base);
+    execute!(stdout, cursor::Show, terminal::LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+    result
+  }
+
+  fn event_loop(
+    &mut self,
+    stdout: &mut io::Stdout,
+    base: &CollectionSelection,
+  ) -> io::Result<Option<CollectionSelection>> {
+    loop {
+      self.draw(stdout)?;
+      let Event::Key(key) = event::read()? else {
+        continue;
+      };
+      if key.kind != KeyEventKind::Press {
+        continue;
+      }
+      match key.code {
+        KeyCode::Up | KeyCode::Char('k') => self.move_cursor(-1),
+        KeyCode::Down | KeyCode::Char('j') => self.move_cursor(1),
+        KeyCode::Char(' ') => self.toggle(),
+        KeyCode::Char('a') => self.set_all(true),
+        KeyCode::Char('n') => self.set_all(false),
+        KeyCode::Enter | KeyCode::Char('s') => return Ok(Some(self.selection(base))),
+        KeyCode::Esc | KeyCode::Char('q') => return Ok(None),
+        _ => {}
+      }
+    }
+  }
+
+  fn draw(&self, stdout: &mut io::Stdout) -> io::Result<()> {
+    let (_, rows) = terminal::size()?;
+    // Keep the cursor visible by scrolling the list when it is taller than the terminal.
+    let visible = usize::from(rows).saturating_sub(3).max(1);
+    let first = self.cursor.saturating_sub(visible - 1);
+    queue!(
+      stdout,
+      terminal::Clear(ClearType::All),
+      cursor::MoveTo(0, 0),
+      SetAttribute(Attribute::Bold),
+      Print("Select collections to bundle"),
+      SetAttribute(Attribute::Reset),
+    )?;
+    for (row, line) in self.lines().iter().skip(first).take(visible).enumerate() {
+      queue!(stdout, cursor::MoveTo(0, row as u16 + 1), Print(line))?;
+    }
+    queue!(
+      stdout,
+      cursor::MoveTo(0, visible as u16 + 2),
+      SetAttribute(Attribute::Dim),
+      Print(HELP),
+      SetAttribute(Attribute::Reset),
+    )?;
+    stdout.flush()
+  }
+}
+
+/// Interactively edit the selection file at `selection_path` for the collections below
+/// `collections_dir`.
+///
+/// The current file, if any, decides which collections start ticked and its `minVersion`
+/// requirements are kept. Returns `false` when the user cancelled and nothing was written.
+pub fn edit_selection_file(
+  layout: &OfflineProjectLayout,
+  collections_dir: &Path,
+  selection_path: &Path,
+) -> Result<bool, Box<dyn std::error::Error>> {
+  let collections = discover_collections(layout, collections_dir);
+  if collections.is_empty() {
+    return Err(format!("no collections found in {}", collections_dir.display()).into());
+  }
+  let base = CollectionSelection::load_from_path(selection_path)?;
+  let Some(selection) = SelectionEditor::new(collections, &base).run(&base)? else {
+    return Ok(false);
+  };
+  fs::write(selection_path, selection.to_json()).map_err(|source| {
+    CollectionSelectionError::Io {
+      path: selection_path.to_path_buf(),
+      source,
+    }
+  })?;
+  Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::models::CollectionMetaRecord;
+  use tempfile::tempdir;
+
+  fn record(id: &str, title: &str) -> CollectionSummaryRecord {
+    CollectionSummaryRecord {
+      id: id.into(),
+      meta: CollectionMetaRecord {
+        title: title.into(),
+        description: None,
+        version: None,
+        asset_slug: None,
+        hero_image: None,
+        order: None,
+      },
+      entry_count: 2,
+    }
+  }
+
+  #[test]
+  fn toggled_collections_round_trip_through_the_selection_file() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("collections.local.json");
+    fs::write(
+      &path,
+      r#"{"include": ["P001"], "minVersion": {"P001": ">=1.0"}}"#,
+    )
+    .unwrap();
+    let base = CollectionSelection::load_from_path(&path).unwrap();
+
+    let mut editor = SelectionEditor::new(
+      vec![
+        record("P001", "Basics"),
+        record("P001/extras", "Extras"),
+        record("P002", "Advanced"),
+      ],
+      &base,
+    );
+    assert!(editor.lines()[0].starts_with("> [x] P001         Basics (2 entries)"));
+    assert!(editor.lines()[1].starts_with("  [x] P001/extras"));
+
+    editor.move_cursor(1);
+    editor.toggle();
+    editor.move_cursor(5);
+    editor.toggle();
+    fs::write(&path, editor.selection(&base).to_json()).unwrap();
+
+    let saved = CollectionSelection::load_from_path(&path).unwrap();
+    assert!(saved.is_included("P001"));
+    assert!(!saved.is_included("P001/extras"));
+    assert!(saved.is_included("P002"));
+    assert!(saved.version_rejection("P001", Some("0.9")).is_some());
+
+    editor.set_all(false);
+    let none = editor.selection(&base);
+    assert!(
+      !["P001", "P001/extras", "P002"]
+        .iter()
+        .any(|id| none.is_included(id))
+    );
+  }
+}